@@ -0,0 +1,51 @@
+//! Screen reader support. egui builds the AccessKit tree from the widgets on its
+//! own, this fills in what it can't know, like what an icon button does.
+
+use eframe::egui::accesskit::{Live, Role};
+use eframe::egui::Response;
+
+pub trait AccessibleExt {
+    /// Name read out instead of the visible text, for buttons showing an icon.
+    fn accessible_name(self, name: &str) -> Self;
+
+    /// Value read out for a slider, e.g. "1:23 of 4:56" instead of a timestamp.
+    fn accessible_value(self, value: &str) -> Self;
+
+    /// Reads out `text` whenever it changes, without the user moving to the widget.
+    fn announce(self, text: &str) -> Self;
+
+    /// Marks the widget as a list entry, read out as `name`.
+    fn list_item(self, name: &str, selected: bool) -> Self;
+}
+
+impl AccessibleExt for Response {
+    fn accessible_name(self, name: &str) -> Self {
+        // None while no screen reader is connected
+        self.ctx
+            .accesskit_node_builder(self.id, |node| node.set_label(name));
+        self
+    }
+
+    fn accessible_value(self, value: &str) -> Self {
+        self.ctx
+            .accesskit_node_builder(self.id, |node| node.set_value(value));
+        self
+    }
+
+    fn announce(self, text: &str) -> Self {
+        self.ctx.accesskit_node_builder(self.id, |node| {
+            node.set_value(text);
+            node.set_live(Live::Polite);
+        });
+        self
+    }
+
+    fn list_item(self, name: &str, selected: bool) -> Self {
+        self.ctx.accesskit_node_builder(self.id, |node| {
+            node.set_role(Role::ListItem);
+            node.set_label(name);
+            node.set_selected(selected);
+        });
+        self
+    }
+}
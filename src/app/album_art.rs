@@ -0,0 +1,240 @@
+//! Embedded pictures extracted to the album_art folder. Files are named after their
+//! content, so the cover shared by every track of an album is stored once.
+
+use super::library::LibraryPathStatus;
+use super::{t, tf, tp, App, LibraryCommand};
+use rusqlite::{Connection, Result as SqlResult};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Album art nothing refers to is looked for once a month
+const CLEANUP_INTERVAL_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// Writes `data` to `dir` unless the same picture is already there, returning its path.
+pub fn store_picture(dir: &Path, data: &[u8], mime_type: &str) -> io::Result<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    let extension = match mime_type {
+        "image/png" => "png",
+        _ => "jpg",
+    };
+    let path = dir.join(format!(
+        "{:016x}_{:x}.{}",
+        hasher.finish(),
+        data.len(),
+        extension
+    ));
+    if path.exists() {
+        return Ok(path);
+    }
+
+    // Tracks of one album are imported in parallel, write aside and move into place so
+    // no one sees a half written file
+    let partial = dir.join(format!(
+        "{}.{:?}.part",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        std::thread::current().id()
+    ));
+    fs::write(&partial, data)?;
+    fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// Files in `dir` that none of `referenced` point to.
+pub fn orphaned_files(dir: &Path, referenced: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && !referenced.contains(path))
+        .collect()
+}
+
+/// Deletes `files`, returning how many went and the bytes they took.
+pub fn delete_files(files: &[PathBuf]) -> (usize, u64) {
+    let mut removed = 0;
+    let mut bytes = 0;
+    for file in files {
+        let size = fs::metadata(file).map_or(0, |metadata| metadata.len());
+        match fs::remove_file(file) {
+            Ok(()) => {
+                removed += 1;
+                bytes += size;
+            }
+            Err(e) => tracing::warn!("Failed to remove album art {:?}: {}", file, e),
+        }
+    }
+    (removed, bytes)
+}
+
+/// Every file a picture row points to.
+pub fn referenced_in_db(conn: &Arc<Mutex<Connection>>) -> SqlResult<HashSet<PathBuf>> {
+    let conn = conn.lock().unwrap();
+    let mut stmt = conn.prepare("SELECT DISTINCT file_path FROM pictures")?;
+    let paths = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    paths.map(|path| path.map(PathBuf::from)).collect()
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// Sizes like 3.2 MB, for reporting reclaimed space.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+impl App {
+    /// Deletes album art no track refers to anymore, e.g. the copies a re-import
+    /// replaced. Skipped while a folder is importing, its pictures aren't listed yet.
+    pub fn remove_orphaned_album_art(&self) {
+        if self.is_importing() {
+            return;
+        }
+
+        let referenced = self.pictures_in_use();
+        std::thread::spawn(move || {
+            let orphans = orphaned_files(&App::get_album_art_dir(), &referenced);
+            let (removed, _) = delete_files(&orphans);
+            if removed > 0 {
+                tracing::info!("Removed {} unused album art files", removed);
+            }
+        });
+    }
+
+    /// Deletes the album art neither a picture row nor a track refers to, reporting
+    /// the reclaimed space once done.
+    pub fn clean_album_art(&mut self) {
+        let (Some(db), Some(lib_cmd_tx)) = (&self.database, &self.library_cmd_tx) else {
+            return;
+        };
+        if self.is_importing() {
+            self.album_art_status = Some(t("album_art_clean_importing"));
+            return;
+        }
+
+        let in_use = self.pictures_in_use();
+        let lib_cmd_tx = lib_cmd_tx.clone();
+        // Queued after the saves, so the rows of new pictures are written by then
+        db.write_in_background(move |conn| {
+            let result = referenced_in_db(conn)
+                .map(|referenced| {
+                    let referenced = referenced.into_iter().chain(in_use).collect();
+                    delete_files(&orphaned_files(&App::get_album_art_dir(), &referenced))
+                })
+                .map_err(|e| e.to_string());
+            let _ = lib_cmd_tx.send(LibraryCommand::AlbumArtCleaned(result));
+        });
+        self.album_art_cleaned_at = Some(unix_now());
+        self.album_art_status = Some(t("album_art_cleaning"));
+    }
+
+    /// Runs the cleanup when the last one was a month ago or longer.
+    pub fn clean_album_art_if_due(&mut self) {
+        let due = self
+            .album_art_cleaned_at
+            .is_none_or(|cleaned_at| unix_now() - cleaned_at >= CLEANUP_INTERVAL_SECS);
+        if due {
+            self.clean_album_art();
+        }
+    }
+
+    pub fn album_art_cleaned(&mut self, result: Result<(usize, u64), String>) {
+        self.album_art_status = Some(match result {
+            Ok((files, bytes)) => {
+                tracing::info!("Album art cleanup removed {} files, {} bytes", files, bytes);
+                tp(
+                    "album_art_cleaned",
+                    files as u64,
+                    &[&files.to_string(), &format_size(bytes)],
+                )
+            }
+            Err(e) => {
+                tracing::error!("Album art cleanup failed: {}", e);
+                tf("album_art_clean_failed", &[&e])
+            }
+        });
+    }
+
+    // Pictures of tracks in memory, some may not be saved yet. Removed folders can
+    // still be brought back.
+    fn pictures_in_use(&self) -> HashSet<PathBuf> {
+        self.library
+            .items()
+            .iter()
+            .chain(self.playlists.iter().flat_map(|playlist| &playlist.tracks))
+            .chain(
+                self.removed_folders
+                    .iter()
+                    .flat_map(|removed| removed.items()),
+            )
+            .flat_map(|item| item.pictures())
+            .map(|picture| picture.file_path.clone())
+            .collect()
+    }
+
+    // Pictures of a folder being imported aren't listed yet, nor those of a library
+    // still loading
+    fn is_importing(&self) -> bool {
+        self.library_loading
+            || self
+                .library
+                .paths()
+                .iter()
+                .any(|path| path.status() == LibraryPathStatus::NotImported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_picture_is_stored_once_and_unused_files_are_found() {
+        let dir = std::env::temp_dir().join(format!("bird-player-art-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let cover = store_picture(&dir, b"cover", "image/jpeg").unwrap();
+        assert_eq!(store_picture(&dir, b"cover", "image/jpeg").unwrap(), cover);
+        let back = store_picture(&dir, b"back", "image/png").unwrap();
+        assert_ne!(back, cover);
+        assert_eq!(back.extension().unwrap(), "png");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        let referenced = HashSet::from([cover]);
+        let orphans = orphaned_files(&dir, &referenced);
+        assert_eq!(orphans, vec![back]);
+        assert_eq!(delete_files(&orphans), (1, 4));
+        assert!(orphaned_files(&dir, &referenced).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sizes_are_shown_in_the_largest_fitting_unit() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024 + 200 * 1024), "3.2 MB");
+    }
+}
@@ -0,0 +1,209 @@
+//! Listening statistics of the albums, worked out from the play history: an album is
+//! complete once each of its tracks was played, and the albums played least are worth
+//! another listen.
+
+use super::library::{LibraryItem, TrackKey};
+use super::App;
+
+use rusqlite::{Connection, Result as SqlResult};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// How often a track shows up in the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plays {
+    pub count: u32,
+    pub last_played: i64,
+}
+
+/// The history summed up per track. Tracks are found by key or, once they were imported
+/// again, by path, like when playing from the history.
+#[derive(Debug, Default)]
+pub struct HistoryPlays {
+    by_key: HashMap<TrackKey, Plays>,
+    by_path: HashMap<PathBuf, Plays>,
+}
+
+impl HistoryPlays {
+    fn of(&self, item: &LibraryItem) -> Option<Plays> {
+        self.by_key
+            .get(&item.key())
+            .or_else(|| self.by_path.get(&item.path()))
+            .copied()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AlbumStats {
+    pub tracks: usize,
+    // Tracks played at least once
+    pub played_tracks: usize,
+    pub plays: u32,
+    pub last_played: Option<i64>,
+}
+
+impl AlbumStats {
+    pub fn is_complete(&self) -> bool {
+        self.tracks > 0 && self.played_tracks == self.tracks
+    }
+}
+
+/// An album the way the library groups tracks, see `LibraryItem::album_key`: its artist
+/// and its title.
+pub type AlbumKey = (String, String);
+
+/// The statistics of every album, tracks without an album tag aren't in any.
+pub fn album_stats(items: &[LibraryItem], plays: &HistoryPlays) -> HashMap<AlbumKey, AlbumStats> {
+    let mut albums: HashMap<AlbumKey, AlbumStats> = HashMap::new();
+    for item in items {
+        let Some(key) = item.album_key() else {
+            continue;
+        };
+        let album = albums.entry(key).or_default();
+        album.tracks += 1;
+        if let Some(track) = plays.of(item) {
+            album.played_tracks += 1;
+            album.plays += track.count;
+            album.last_played = album.last_played.max(Some(track.last_played));
+        }
+    }
+    albums
+}
+
+/// Albums with the fewest plays first, of those the ones not heard for the longest.
+pub fn least_listened(albums: &HashMap<AlbumKey, AlbumStats>) -> Vec<(&AlbumKey, AlbumStats)> {
+    let mut albums: Vec<_> = albums.iter().map(|(key, stats)| (key, *stats)).collect();
+    albums.sort_by_key(|(key, stats)| (stats.plays, stats.last_played, *key));
+    albums
+}
+
+/// How many of the albums with tracks among `items` are complete, and of how many.
+pub fn completed_albums<'a>(
+    items: impl IntoIterator<Item = &'a LibraryItem>,
+    albums: &HashMap<AlbumKey, AlbumStats>,
+) -> (usize, usize) {
+    let keys: HashSet<AlbumKey> = items
+        .into_iter()
+        .filter_map(LibraryItem::album_key)
+        .collect();
+    let complete = keys
+        .iter()
+        .filter(|key| albums.get(*key).is_some_and(AlbumStats::is_complete))
+        .count();
+    (complete, keys.len())
+}
+
+fn load_plays(conn: &Arc<Mutex<Connection>>) -> SqlResult<HistoryPlays> {
+    let conn_guard = conn.lock().unwrap();
+    let grouped = |column: &str| -> SqlResult<Vec<(String, Plays)>> {
+        let mut stmt = conn_guard.prepare(&format!(
+            "SELECT {0}, COUNT(*), MAX(played_at) FROM play_history GROUP BY {0}",
+            column
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                Plays {
+                    count: row.get(1)?,
+                    last_played: row.get(2)?,
+                },
+            ))
+        })?;
+        rows.collect()
+    };
+
+    Ok(HistoryPlays {
+        by_key: grouped("library_item_id")?
+            .into_iter()
+            .filter_map(|(key, plays)| Some((key.parse::<TrackKey>().ok()?, plays)))
+            .collect(),
+        by_path: grouped("path")?
+            .into_iter()
+            .map(|(path, plays)| (PathBuf::from(path), plays))
+            .collect(),
+    })
+}
+
+impl App {
+    /// Works the album statistics out again, after plays were logged or the library
+    /// changed.
+    pub fn reload_album_stats(&mut self) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match load_plays(&db.connection()) {
+            Ok(plays) => self.album_stats = album_stats(self.library.items(), &plays),
+            Err(e) => tracing::error!("Failed to load album statistics: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+
+    fn item(path: &str, album: &str) -> LibraryItem {
+        let mut item = LibraryItem::new(PathBuf::from(path), LibraryPathId::new(0));
+        item.set_album(Some(album)).set_artist(Some("Artist"))
+    }
+
+    fn key(album: &str) -> AlbumKey {
+        ("Artist".to_string(), album.to_string())
+    }
+
+    #[test]
+    fn albums_are_complete_once_each_track_was_played() {
+        let items = [
+            item("/a/1.mp3", "A"),
+            item("/a/2.mp3", "A"),
+            item("/b/1.mp3", "B"),
+            item("/c/1.mp3", "C"),
+        ];
+        let mut plays = HistoryPlays::default();
+        let played = |count, last_played| Plays { count, last_played };
+        plays.by_key.insert(items[0].key(), played(3, 100));
+        // Imported again since, found by its path
+        plays
+            .by_path
+            .insert(PathBuf::from("/a/2.mp3"), played(1, 300));
+        plays.by_key.insert(items[3].key(), played(1, 200));
+
+        let albums = album_stats(&items, &plays);
+        let a = albums[&key("A")];
+        assert!(a.is_complete());
+        assert_eq!((a.plays, a.last_played), (4, Some(300)));
+        assert!(!albums[&key("B")].is_complete());
+
+        // Never played first, then the fewest plays
+        let order: Vec<&str> = least_listened(&albums)
+            .iter()
+            .map(|(key, _)| key.1.as_str())
+            .collect();
+        assert_eq!(order, ["B", "C", "A"]);
+    }
+
+    #[test]
+    fn albums_in_one_library_folder_are_counted_apart() {
+        let items = [
+            item("/music/a/1.mp3", "A"),
+            item("/music/a/2.mp3", "A"),
+            item("/music/b/1.mp3", "B"),
+            LibraryItem::new(PathBuf::from("/music/loose.mp3"), LibraryPathId::new(0)),
+        ];
+        let mut plays = HistoryPlays::default();
+        let played = Plays {
+            count: 1,
+            last_played: 100,
+        };
+        plays.by_key.insert(items[2].key(), played);
+
+        let albums = album_stats(&items, &plays);
+        assert_eq!(albums.len(), 2);
+        assert_eq!(albums[&key("A")].tracks, 2);
+        assert_eq!(albums[&key("A")].played_tracks, 0);
+        assert!(albums[&key("B")].is_complete());
+        assert_eq!(completed_albums(&items, &albums), (1, 2));
+    }
+}
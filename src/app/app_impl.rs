@@ -1,8 +1,9 @@
 use eframe::egui;
 
-use super::{App, LibraryCommand};
+use super::{App, AudioCommand, LibraryCommand, ReplayGainMode};
 use crate::app::components::{
-    footer::Footer, library_component::LibraryComponent, player_component::PlayerComponent,
+    chapter_panel::ChapterPanel, footer::Footer, library_component::LibraryComponent,
+    mini_player::MiniPlayer, now_playing_view::NowPlayingView, player_component::PlayerComponent,
     playlist_table::PlaylistTable, playlist_tabs::PlaylistTabs, window_chrome::WindowChrome,
     AppComponent,
 };
@@ -10,27 +11,89 @@ use crate::app::components::{
 impl eframe::App for App {
     fn on_exit(&mut self, _ctx: Option<&eframe::glow::Context>) {
         tracing::info!("exiting and saving");
+        self.save_listened_time();
         self.update_player_persistence();
         self.save_state();
+        if let Some(db) = &self.database {
+            db.flush();
+        }
+        self.delete_art_of_removed_folders();
+        if let Some(player) = &self.player {
+            let _ = player.audio_tx.send(AudioCommand::Shutdown);
+        }
     }
 
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         if self.quit {
             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
         }
 
+        // The volume was changed in the system mixer
+        if let Some(player) = &mut self.player {
+            player.follow_system_volume();
+        }
+
         if let Some(lib_cmd_rx) = &self.library_cmd_rx {
             if let Ok(lib_cmd) = lib_cmd_rx.try_recv() {
                 match lib_cmd {
-                    LibraryCommand::AddItem(lib_item) => self.library.add_item(lib_item),
+                    LibraryCommand::AddItem(lib_item) => {
+                        self.library.add_item(*lib_item, self.read_only_library)
+                    }
                     LibraryCommand::AddView(lib_view) => self.library.add_view(lib_view),
                     LibraryCommand::AddPathId(path_id) => {
-                        self.library.set_path_to_imported(path_id)
+                        self.library.set_path_to_imported(path_id);
+                        self.refresh_recently_added();
+                        self.refresh_label_playlists();
+                        self.refresh_favorites();
+                        self.reload_album_stats();
+                        self.remove_orphaned_album_art();
+
+                        // Measure tracks without ReplayGain tags so normalization covers them too
+                        if self.replay_gain_mode != ReplayGainMode::Off {
+                            let items = self
+                                .library
+                                .items()
+                                .iter()
+                                .filter(|item| item.library_id() == path_id)
+                                .cloned()
+                                .collect();
+                            self.scan_replay_gain(items);
+                        }
+                    }
+                    LibraryCommand::UpdateReplayGain(key, replay_gain) => {
+                        self.apply_replay_gain(key, replay_gain)
+                    }
+                    LibraryCommand::MetadataWritten(edits) => self.apply_metadata_edits(edits),
+                    LibraryCommand::LibraryLoaded(library) => self.library_loaded(*library),
+                    LibraryCommand::PlaylistLoaded(playlist) => self.playlist_loaded(*playlist),
+                    LibraryCommand::AlbumArtCleaned(result) => self.album_art_cleaned(result),
+                    LibraryCommand::PathAvailability(availability) => {
+                        for (path_id, online) in availability {
+                            self.set_path_availability(path_id, online);
+                        }
                     }
                 }
             }
         }
 
+        self.process_ui_commands();
+        self.handle_seek_keys(ctx);
+        self.handle_jump_key(ctx);
+        self.handle_perf_overlay(ctx, frame);
+
+        // Keep edits safe from a crash, the repaint makes sure the check runs while idle
+        self.autosave();
+        ctx.request_repaint_after(super::autosave::AUTOSAVE_INTERVAL);
+
+        // Notice shares and drives coming and going even while idle
+        self.check_library_volumes();
+        ctx.request_repaint_after(super::volumes::VOLUME_CHECK_INTERVAL);
+
+        self.request_playback_repaint(ctx);
+        if self.is_loading() {
+            ctx.request_repaint();
+        }
+
         if let Some(selected_track) = &self.player.as_mut().unwrap().selected_track {
             let display = format!(
                 "{} - {} [ Music Player ]",
@@ -45,6 +108,25 @@ impl eframe::App for App {
             ctx.send_viewport_cmd(egui::ViewportCommand::Title(display));
         }
 
+        // Keep where the window of the current mode was for the next start
+        if ctx.input(|input| input.viewport().close_requested()) {
+            self.remember_window_geometry(ctx);
+        }
+
+        if self.now_playing_view {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                NowPlayingView::add(self, ui);
+            });
+            return;
+        }
+
+        if self.mini_player {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                MiniPlayer::add(self, ui);
+            });
+            return;
+        }
+
         // Add window chrome at the top
         egui::TopBottomPanel::top("Window Chrome")
             .show_separator_line(true)
@@ -62,30 +144,56 @@ impl eframe::App for App {
         });
 
         egui::CentralPanel::default().show(ctx, |_ui| {
-            egui::SidePanel::left("Library Window")
-                .default_width(200.0)
+            let library_panel = egui::SidePanel::left("Library Window")
+                .default_width(self.library_panel_width.unwrap_or(200.0))
                 .show(ctx, |ui| {
                     LibraryComponent::add(self, ui);
                 });
+            self.library_panel_width = Some(library_panel.response.rect.width());
         });
 
         egui::CentralPanel::default().show(ctx, |_ui| {
+            // Chapters of the playing track, e.g. an audiobook, next to the playlist
+            if self.show_chapter_panel && !self.track_markers.chapters.is_empty() {
+                egui::SidePanel::right("Chapter Panel")
+                    .default_width(220.0)
+                    .show(ctx, |ui| {
+                        ChapterPanel::add(self, ui);
+                    });
+            }
+
             egui::TopBottomPanel::top("Playlist Tabs").show(ctx, |ui| {
                 PlaylistTabs::add(self, ui);
             });
 
             egui::CentralPanel::default().show(ctx, |ui| {
-                if let Some(_current_playlist_idx) = &mut self.current_playlist_idx {
+                if let Some(current_playlist_idx) = self.current_playlist_idx {
                     // Create a scroll area with a unique ID for tracking scroll position
-                    let playlist_id =
-                        format!("playlist_{}", self.current_playlist_idx.unwrap_or(0));
+                    let playlist_id = format!("playlist_{}", current_playlist_idx);
                     let scroll_area_id = ui.id().with(playlist_id).with("scroll_area");
 
-                    egui::ScrollArea::both().show(ui, |ui| {
+                    // Coming back to a tab scrolls to where it was left
+                    let mut scroll_area = egui::ScrollArea::both();
+                    if self.shown_playlist_idx != Some(current_playlist_idx) {
+                        if let Some(playlist) = self.playlists.get(current_playlist_idx) {
+                            let [x, y] = playlist.view.scroll_offset;
+                            scroll_area = scroll_area.scroll_offset(egui::vec2(x, y));
+                        }
+                    }
+
+                    let output = scroll_area.show(ui, |ui| {
                         ui.push_id(scroll_area_id, |ui| {
                             PlaylistTable::add(self, ui);
                         });
                     });
+
+                    // The table may have switched tabs, e.g. to locate the playing track
+                    if self.current_playlist_idx == Some(current_playlist_idx) {
+                        if let Some(playlist) = self.playlists.get_mut(current_playlist_idx) {
+                            playlist.view.scroll_offset = output.state.offset.into();
+                        }
+                    }
+                    self.shown_playlist_idx = self.current_playlist_idx;
                 }
             });
         });
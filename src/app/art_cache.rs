@@ -0,0 +1,142 @@
+//! Bounded cache for decoded album art. Entries are dropped least recently used first
+//! once the budget is exceeded, and after they haven't been shown for a while.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Roughly 16 covers at 512x512 RGBA
+pub const MAX_ART_CACHE_BYTES: usize = 16 * 1024 * 1024;
+
+// Seconds a cover can stay off screen before its texture is released
+pub const ART_STALE_AFTER: f64 = 120.0;
+
+struct Entry<V> {
+    value: V,
+    bytes: usize,
+    last_used: f64,
+}
+
+pub struct ArtCache<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    max_bytes: usize,
+    used_bytes: usize,
+}
+
+impl<K: Eq + Hash + Clone, V> ArtCache<K, V> {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            max_bytes,
+            used_bytes: 0,
+        }
+    }
+
+    /// Looks up an entry and marks it used at `now`.
+    pub fn get(&mut self, key: &K, now: f64) -> Option<&V> {
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = now;
+        Some(&entry.value)
+    }
+
+    /// Adds or replaces an entry, evicting the least recently used ones to make room.
+    /// An entry bigger than the whole budget is still kept, alone.
+    pub fn insert(&mut self, key: K, value: V, bytes: usize, now: f64) {
+        self.remove(&key);
+        while self.used_bytes + bytes > self.max_bytes && self.evict_oldest() {}
+
+        self.used_bytes += bytes;
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                bytes,
+                last_used: now,
+            },
+        );
+    }
+
+    /// Drops entries that weren't used since `now - max_age`.
+    pub fn evict_stale(&mut self, now: f64, max_age: f64) {
+        let used_bytes = &mut self.used_bytes;
+        self.entries.retain(|_, entry| {
+            let keep = now - entry.last_used <= max_age;
+            if !keep {
+                *used_bytes -= entry.bytes;
+            }
+            keep
+        });
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.used_bytes -= entry.bytes;
+        }
+    }
+
+    fn evict_oldest(&mut self) -> bool {
+        let Some(oldest) = self
+            .entries
+            .iter()
+            .min_by(|a, b| a.1.last_used.total_cmp(&b.1.last_used))
+            .map(|(key, _)| key.clone())
+        else {
+            return false;
+        };
+        self.remove(&oldest);
+        true
+    }
+}
+
+/// Texture side in pixels to decode art at for a rect of `points` on a screen with
+/// `pixels_per_point`. Rounded up to steps of 64 so resizing doesn't reload every frame.
+pub fn display_resolution(points: f32, pixels_per_point: f32) -> u32 {
+    let pixels = (points * pixels_per_point).ceil().max(1.0) as u32;
+    pixels.div_ceil(64) * 64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_goes_first_when_over_budget() {
+        let mut cache = ArtCache::new(300);
+        cache.insert("a", 1, 100, 0.0);
+        cache.insert("b", 2, 100, 1.0);
+        cache.insert("c", 3, 100, 2.0);
+
+        // Touching "a" makes "b" the oldest
+        assert_eq!(cache.get(&"a", 3.0), Some(&1));
+        cache.insert("d", 4, 100, 4.0);
+
+        assert_eq!(cache.get(&"b", 5.0), None);
+        assert_eq!(cache.entries.len(), 3);
+        assert_eq!(cache.used_bytes, 300);
+
+        // Replacing an entry doesn't count it twice
+        cache.insert("d", 5, 150, 6.0);
+        assert_eq!(cache.used_bytes, 250);
+        assert_eq!(cache.get(&"d", 7.0), Some(&5));
+    }
+
+    #[test]
+    fn stale_entries_are_dropped_and_oversized_ones_kept_alone() {
+        let mut cache = ArtCache::new(100);
+        cache.insert("old", 1, 40, 0.0);
+        cache.insert("new", 2, 40, 50.0);
+        cache.evict_stale(100.0, 60.0);
+        assert_eq!(cache.get(&"old", 100.0), None);
+        assert_eq!(cache.used_bytes, 40);
+
+        cache.insert("huge", 3, 500, 101.0);
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.get(&"huge", 102.0), Some(&3));
+    }
+
+    #[test]
+    fn resolution_follows_the_screen_in_steps() {
+        assert_eq!(display_resolution(120.0, 1.0), 128);
+        assert_eq!(display_resolution(120.0, 2.0), 256);
+        assert_eq!(display_resolution(0.0, 1.0), 64);
+    }
+}
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use rusqlite::Result as SqlResult;
+use symphonia::core::codecs::{CodecParameters, CODEC_TYPE_NULL};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+// Codecs whose decoded output is bit-identical to the source
+const LOSSLESS_CODECS: [&str; 4] = ["flac", "alac", "wavpack", "pcm"];
+
+/// Technical properties of a track's audio stream.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioFormat {
+    /// Short codec name as used by symphonia, e.g. "mp3", "flac" or "pcm_s16le".
+    pub codec: Option<String>,
+    /// Average bitrate in kbit/s.
+    pub bitrate: Option<u32>,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    pub channels: Option<u32>,
+}
+
+// Length of the stream, known when the container stores the number of frames
+fn stream_seconds(params: &CodecParameters) -> Option<f64> {
+    let time = params.time_base?.calc_time(params.n_frames?);
+    Some(time.seconds as f64 + time.frac)
+}
+
+impl AudioFormat {
+    /// Describes a stream from its codec parameters, the bitrate is averaged over
+    /// the file size when the duration is known.
+    pub fn from_codec_params(params: &CodecParameters, file_size: Option<u64>) -> Self {
+        let codec = symphonia::default::get_codecs()
+            .get_codec(params.codec)
+            .map(|descriptor| descriptor.short_name.to_string());
+
+        let bitrate = match (file_size, stream_seconds(params)) {
+            (Some(size), Some(seconds)) if seconds > 0.0 => {
+                Some((size as f64 * 8.0 / seconds / 1000.0).round() as u32)
+            }
+            _ => None,
+        };
+
+        Self {
+            codec,
+            bitrate,
+            sample_rate: params.sample_rate,
+            bit_depth: params.bits_per_sample,
+            channels: params.channels.map(|channels| channels.count() as u32),
+        }
+    }
+
+    /// Reads the stream properties from the file headers without decoding.
+    pub fn probe(path: &Path) -> Option<Self> {
+        Self::probe_with_duration(path).map(|(format, _)| format)
+    }
+
+    /// Like `probe`, along with the length in seconds when the headers tell it.
+    pub fn probe_with_duration(path: &Path) -> Option<(Self, Option<f64>)> {
+        let file = std::fs::File::open(path).ok()?;
+        let file_size = file.metadata().ok().map(|metadata| metadata.len());
+        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                mss,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .ok()?;
+
+        let track = probed
+            .format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)?;
+
+        Some((
+            Self::from_codec_params(&track.codec_params, file_size),
+            stream_seconds(&track.codec_params),
+        ))
+    }
+
+    /// Reads the five format columns starting at `first_column`.
+    pub fn from_row(row: &rusqlite::Row, first_column: usize) -> SqlResult<Self> {
+        Ok(Self {
+            codec: row.get(first_column)?,
+            bitrate: row.get(first_column + 1)?,
+            sample_rate: row.get(first_column + 2)?,
+            bit_depth: row.get(first_column + 3)?,
+            channels: row.get(first_column + 4)?,
+        })
+    }
+
+    pub fn is_lossless(&self) -> bool {
+        self.codec.as_deref().is_some_and(|codec| {
+            LOSSLESS_CODECS
+                .iter()
+                .any(|lossless| codec.starts_with(lossless))
+        })
+    }
+
+    /// Codec name for display, e.g. "FLAC" or "PCM".
+    pub fn codec_name(&self) -> Option<String> {
+        let codec = self.codec.as_deref()?;
+        let name = if codec.starts_with("pcm") {
+            "pcm"
+        } else {
+            codec
+        };
+        Some(name.to_uppercase())
+    }
+
+    /// Codec with bit depth and sample rate, e.g. "FLAC 24/96" or "MP3 44.1".
+    pub fn short_summary(&self) -> String {
+        let mut summary = self.codec_name().unwrap_or_default();
+
+        if let Some(sample_rate) = self.sample_rate {
+            let khz = format_khz(sample_rate);
+            let detail = match self.bit_depth {
+                Some(bit_depth) => format!("{}/{}", bit_depth, khz),
+                None => khz,
+            };
+            if !summary.is_empty() {
+                summary.push(' ');
+            }
+            summary.push_str(&detail);
+        }
+
+        summary
+    }
+
+    /// Everything known about the stream, e.g. "FLAC · 16-bit · 44.1 kHz · 2 ch · 912 kbps".
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(codec) = self.codec_name() {
+            parts.push(codec);
+        }
+        if let Some(bit_depth) = self.bit_depth {
+            parts.push(format!("{}-bit", bit_depth));
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            parts.push(format!("{} kHz", format_khz(sample_rate)));
+        }
+        if let Some(channels) = self.channels {
+            parts.push(format!("{} ch", channels));
+        }
+        if let Some(bitrate) = self.bitrate {
+            parts.push(format!("{} kbps", bitrate));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// Sample rate in kHz for display, 44100 -> "44.1", 48000 -> "48".
+pub fn format_khz(sample_rate: u32) -> String {
+    let khz = format!("{:.1}", sample_rate as f64 / 1000.0);
+    khz.trim_end_matches(".0").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summaries_describe_lossy_and_lossless_streams() {
+        let flac = AudioFormat {
+            codec: Some("flac".to_string()),
+            bitrate: Some(912),
+            sample_rate: Some(44100),
+            bit_depth: Some(16),
+            channels: Some(2),
+        };
+        assert!(flac.is_lossless());
+        assert_eq!(flac.short_summary(), "FLAC 16/44.1");
+        assert_eq!(flac.summary(), "FLAC · 16-bit · 44.1 kHz · 2 ch · 912 kbps");
+
+        let mp3 = AudioFormat {
+            codec: Some("mp3".to_string()),
+            bitrate: Some(320),
+            sample_rate: Some(48000),
+            bit_depth: None,
+            channels: Some(2),
+        };
+        assert!(!mp3.is_lossless());
+        assert_eq!(mp3.short_summary(), "MP3 48");
+
+        let wav = AudioFormat {
+            codec: Some("pcm_s24le".to_string()),
+            ..Default::default()
+        };
+        assert!(wav.is_lossless());
+        assert_eq!(wav.codec_name().as_deref(), Some("PCM"));
+        assert_eq!(AudioFormat::default().summary(), "");
+    }
+}
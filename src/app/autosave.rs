@@ -0,0 +1,86 @@
+use super::library::LibraryItem;
+use super::App;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+// How often to look for unsaved edits, a crash loses at most this much
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(15);
+
+impl App {
+    /// Saves playlists, the library and the player state when they changed since the
+    /// last save, so edits survive a crash instead of waiting for a clean quit.
+    pub fn autosave(&mut self) {
+        if self.last_autosave.elapsed() < AUTOSAVE_INTERVAL {
+            return;
+        }
+        self.last_autosave = Instant::now();
+
+        let fingerprint = self.state_fingerprint();
+        if self.saved_fingerprint == Some(fingerprint) {
+            return;
+        }
+
+        // New playlists need their database id, or every save would insert them again
+        for idx in 0..self.playlists.len() {
+            if self.playlists[idx].id.is_none() {
+                self.persist_playlist(idx);
+            }
+        }
+
+        tracing::info!("Autosaving changed state");
+        self.update_player_persistence();
+        self.save_state();
+        self.saved_fingerprint = Some(fingerprint);
+    }
+
+    // Summarizes everything the autosave writes. The playback position is left out,
+    // it's saved on its own schedule during playback.
+    pub fn state_fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        for playlist in &self.playlists {
+            playlist.id.hash(&mut hasher);
+            playlist.get_name().hash(&mut hasher);
+            playlist.tracks.len().hash(&mut hasher);
+            for track in &playlist.tracks {
+                track.key().hash(&mut hasher);
+            }
+        }
+        self.playing_playlist_idx.hash(&mut hasher);
+
+        for path in self.library.paths() {
+            path.hash(&mut hasher);
+        }
+        self.library.items().len().hash(&mut hasher);
+        for item in self.library.items() {
+            hash_item(item, &mut hasher);
+        }
+
+        if let Some(player) = &self.player {
+            player
+                .selected_track
+                .as_ref()
+                .map(|track| track.key())
+                .hash(&mut hasher);
+            player.playback_mode.hash(&mut hasher);
+            player.volume.to_bits().hash(&mut hasher);
+            player.muted.hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+}
+
+// The fields a tag edit can change
+fn hash_item(item: &LibraryItem, hasher: &mut DefaultHasher) {
+    item.key().hash(hasher);
+    item.title().hash(hasher);
+    item.artist().hash(hasher);
+    item.album_artist().hash(hasher);
+    item.album().hash(hasher);
+    item.year().hash(hasher);
+    item.genre().hash(hasher);
+    item.track_number().hash(hasher);
+}
@@ -0,0 +1,266 @@
+//! Backups of the database and the album art in a single file, for moving to
+//! another machine.
+//!
+//! The archive starts with a magic line, followed by one entry per file: the length
+//! and UTF-8 path, the length and content and a checksum of both. An entry count
+//! closes the archive, so truncated files are caught as well as damaged ones.
+
+use super::App;
+use crate::db::Database;
+
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"BIRDBAK1";
+const DATABASE_ENTRY: &str = "bird-player.db";
+const ALBUM_ART_PREFIX: &str = "album_art/";
+
+// FNV-1a, enough to notice damaged files
+fn checksum(path: &str, data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in path.as_bytes().iter().chain(data) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+pub struct ArchiveWriter<W: Write> {
+    out: W,
+    entries: u64,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        Ok(Self { out, entries: 0 })
+    }
+
+    pub fn add(&mut self, path: &str, data: &[u8]) -> io::Result<()> {
+        self.out.write_all(&(path.len() as u32).to_le_bytes())?;
+        self.out.write_all(path.as_bytes())?;
+        self.out.write_all(&(data.len() as u64).to_le_bytes())?;
+        self.out.write_all(data)?;
+        self.out.write_all(&checksum(path, data).to_le_bytes())?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        // A zero length path can't be an entry and marks the end
+        self.out.write_all(&0u32.to_le_bytes())?;
+        self.out.write_all(&self.entries.to_le_bytes())?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Reads every entry of an archive, failing if any of them is damaged.
+pub fn read_archive(mut input: impl Read) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a Bird Player backup"));
+    }
+
+    let mut entries = Vec::new();
+    loop {
+        let path_len = read_u32(&mut input)? as usize;
+        if path_len == 0 {
+            break;
+        }
+
+        let mut path = vec![0; path_len];
+        input.read_exact(&mut path)?;
+        let path = String::from_utf8(path).map_err(|_| invalid("damaged entry name"))?;
+
+        let data_len = read_u64(&mut input)?;
+        let mut data = Vec::new();
+        input.by_ref().take(data_len).read_to_end(&mut data)?;
+        if data.len() as u64 != data_len {
+            return Err(invalid("backup is truncated"));
+        }
+
+        if read_u64(&mut input)? != checksum(&path, &data) {
+            return Err(invalid(&format!("{} is damaged", path)));
+        }
+        entries.push((path, data));
+    }
+
+    if read_u64(&mut input)? != entries.len() as u64 {
+        return Err(invalid("backup is incomplete"));
+    }
+    Ok(entries)
+}
+
+// Relative path of an album art entry, refusing anything that would leave the folder
+fn album_art_path(entry: &str) -> Option<PathBuf> {
+    let relative = Path::new(entry.strip_prefix(ALBUM_ART_PREFIX)?);
+    let safe = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    (safe && !relative.as_os_str().is_empty()).then(|| relative.to_path_buf())
+}
+
+impl App {
+    /// Writes the database and the album art to `target`. Returns the number of files.
+    pub fn create_backup(&self, target: &Path) -> Result<usize, String> {
+        let database = self.database.as_ref().ok_or("no database")?;
+
+        // Store what's in memory first, the backup should match what the user sees
+        self.save_state();
+
+        let snapshot =
+            std::env::temp_dir().join(format!("bird-player-backup-{}.db", std::process::id()));
+        let _ = fs::remove_file(&snapshot);
+        database
+            .backup_to(&snapshot)
+            .map_err(|e| format!("couldn't copy the database: {}", e))?;
+        let database_bytes = fs::read(&snapshot);
+        let _ = fs::remove_file(&snapshot);
+        let database_bytes =
+            database_bytes.map_err(|e| format!("couldn't read the database copy: {}", e))?;
+
+        let write = || -> io::Result<usize> {
+            let file = fs::File::create(target)?;
+            let mut archive = ArchiveWriter::new(BufWriter::new(file))?;
+            archive.add(DATABASE_ENTRY, &database_bytes)?;
+            let mut files = 1;
+
+            let art_dir = App::get_album_art_dir();
+            for entry in walkdir::WalkDir::new(&art_dir)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+            {
+                let Ok(relative) = entry.path().strip_prefix(&art_dir) else {
+                    continue;
+                };
+                let name = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                archive.add(
+                    &format!("{}{}", ALBUM_ART_PREFIX, name),
+                    &fs::read(entry.path())?,
+                )?;
+                files += 1;
+            }
+
+            archive.finish()?;
+            Ok(files)
+        };
+
+        write().map_err(|e| {
+            let _ = fs::remove_file(target);
+            format!("couldn't write the backup: {}", e)
+        })
+    }
+
+    /// Verifies the backup at `source` and stages it. The database is swapped in on
+    /// the next start, the album art is restored right away.
+    pub fn restore_backup(&self, source: &Path) -> Result<usize, String> {
+        let file = fs::File::open(source).map_err(|e| format!("couldn't open: {}", e))?;
+        let entries = read_archive(BufReader::new(file)).map_err(|e| e.to_string())?;
+
+        let (_, database_bytes) = entries
+            .iter()
+            .find(|(path, _)| path == DATABASE_ENTRY)
+            .ok_or("the backup has no database")?;
+
+        let restore_path =
+            Database::pending_restore_path().map_err(|e| format!("no database folder: {}", e))?;
+        let staged = restore_path.with_extension("restore.tmp");
+        fs::write(&staged, database_bytes)
+            .map_err(|e| format!("couldn't write the database: {}", e))?;
+        if let Err(e) = Database::verify_file(&staged) {
+            let _ = fs::remove_file(&staged);
+            return Err(format!("the database in the backup is damaged: {}", e));
+        }
+        fs::rename(&staged, &restore_path)
+            .map_err(|e| format!("couldn't stage the database: {}", e))?;
+
+        let art_dir = App::get_album_art_dir();
+        let mut files = 1;
+        for (path, data) in &entries {
+            let Some(relative) = album_art_path(path) else {
+                continue;
+            };
+            let target = art_dir.join(relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("couldn't restore album art: {}", e))?;
+            }
+            fs::write(&target, data).map_err(|e| format!("couldn't restore album art: {}", e))?;
+            files += 1;
+        }
+
+        tracing::info!("Backup from {} staged for the next start", source.display());
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ArchiveWriter::new(Vec::new()).unwrap();
+        for (path, data) in entries {
+            writer.add(path, data).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn archives_round_trip_and_reject_damage() {
+        let bytes = archive(&[("bird-player.db", b"sqlite"), ("album_art/a.jpg", b"")]);
+        let entries = read_archive(&bytes[..]).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ("bird-player.db".to_string(), b"sqlite".to_vec()),
+                ("album_art/a.jpg".to_string(), Vec::new()),
+            ]
+        );
+
+        // A flipped byte in the content
+        let mut damaged = bytes.clone();
+        damaged[8 + 4 + 14 + 8] ^= 1;
+        assert!(read_archive(&damaged[..]).is_err());
+
+        // Cut off before the end marker
+        assert!(read_archive(&bytes[..bytes.len() - 12]).is_err());
+        assert!(read_archive(&b"NOTABACKUP"[..]).is_err());
+    }
+
+    #[test]
+    fn album_art_entries_stay_inside_the_folder() {
+        assert_eq!(
+            album_art_path("album_art/ab/cover.jpg"),
+            Some(PathBuf::from("ab/cover.jpg"))
+        );
+        assert_eq!(album_art_path("album_art/../config.yml"), None);
+        assert_eq!(album_art_path("album_art//etc/passwd"), None);
+        assert_eq!(album_art_path("album_art/"), None);
+        assert_eq!(album_art_path("bird-player.db"), None);
+    }
+}
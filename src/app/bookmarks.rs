@@ -0,0 +1,185 @@
+use super::library::{LibraryItem, TrackKey};
+use super::App;
+
+use id3::Tag;
+use rusqlite::{Connection, Result as SqlResult};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A user defined, named position inside a track.
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub id: i64,
+    pub name: String,
+    pub position_ms: u64,
+}
+
+/// A chapter read from the file's ID3 CHAP frames or MP4 chapter list.
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub title: String,
+    pub start_ms: u64,
+}
+
+/// Bookmarks and chapters of the selected track.
+#[derive(Debug, Clone, Default)]
+pub struct TrackMarkers {
+    pub track_key: Option<TrackKey>,
+    pub bookmarks: Vec<Bookmark>,
+    pub chapters: Vec<Chapter>,
+}
+
+impl TrackMarkers {
+    pub fn load(track: &LibraryItem, conn: Option<&Arc<Mutex<Connection>>>) -> Self {
+        let bookmarks = match conn.map(|conn| load_bookmarks(conn, track.key())) {
+            Some(Ok(bookmarks)) => bookmarks,
+            Some(Err(e)) => {
+                tracing::error!("Failed to load bookmarks: {}", e);
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        Self {
+            track_key: Some(track.key()),
+            bookmarks,
+            chapters: read_chapters(&track.path()),
+        }
+    }
+
+    /// The chapter playing at `position_ms`, by its index.
+    pub fn current_chapter(&self, position_ms: u64) -> Option<usize> {
+        self.chapters
+            .iter()
+            .rposition(|chapter| chapter.start_ms <= position_ms)
+    }
+
+    /// Start positions of all markers in milliseconds.
+    pub fn positions_ms(&self) -> impl Iterator<Item = u64> + '_ {
+        self.chapters
+            .iter()
+            .map(|c| c.start_ms)
+            .chain(self.bookmarks.iter().map(|b| b.position_ms))
+    }
+}
+
+fn read_chapters(path: &Path) -> Vec<Chapter> {
+    let is_mp4 = path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+        ["m4b", "m4a", "mp4"]
+            .iter()
+            .any(|mp4| e.eq_ignore_ascii_case(mp4))
+    });
+    if is_mp4 {
+        return super::mp4_chapters::read(path).unwrap_or_else(|e| {
+            tracing::warn!("Failed to read chapters of {}: {}", path.display(), e);
+            Vec::new()
+        });
+    }
+
+    let Ok(tag) = Tag::read_from_path(path) else {
+        return Vec::new();
+    };
+
+    let mut chapters: Vec<Chapter> = tag
+        .chapters()
+        .map(|chapter| {
+            let title = chapter
+                .frames
+                .iter()
+                .find(|frame| frame.id() == "TIT2")
+                .and_then(|frame| frame.content().text())
+                .unwrap_or(&chapter.element_id)
+                .to_string();
+
+            Chapter {
+                title,
+                start_ms: chapter.start_time as u64,
+            }
+        })
+        .collect();
+
+    chapters.sort_by_key(|c| c.start_ms);
+    chapters
+}
+
+// Database methods
+
+fn load_bookmarks(conn: &Arc<Mutex<Connection>>, track_key: TrackKey) -> SqlResult<Vec<Bookmark>> {
+    let conn_guard = conn.lock().unwrap();
+    let mut stmt = conn_guard.prepare(
+        "SELECT id, name, position_ms FROM bookmarks
+         WHERE library_item_id = ?1 ORDER BY position_ms",
+    )?;
+
+    let rows = stmt.query_map(rusqlite::params![track_key.to_string()], |row| {
+        Ok(Bookmark {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            position_ms: row.get::<_, i64>(2)? as u64,
+        })
+    })?;
+
+    rows.collect()
+}
+
+impl App {
+    /// Reloads the markers when the selected track changed.
+    pub fn refresh_track_markers(&mut self) {
+        let selected = self
+            .player
+            .as_ref()
+            .and_then(|player| player.selected_track.as_ref());
+
+        match selected {
+            Some(track) if self.track_markers.track_key != Some(track.key()) => {
+                let conn = self.database.as_ref().map(|db| db.connection());
+                self.track_markers = TrackMarkers::load(track, conn.as_ref());
+            }
+            None if self.track_markers.track_key.is_some() => {
+                self.track_markers = TrackMarkers::default();
+            }
+            _ => (),
+        }
+    }
+
+    pub fn add_bookmark(&mut self, name: String, position_ms: u64) {
+        let (Some(track_key), Some(db)) = (self.track_markers.track_key, &self.database) else {
+            return;
+        };
+
+        let conn = db.connection();
+        let conn_guard = conn.lock().unwrap();
+        let result = conn_guard.execute(
+            "INSERT INTO bookmarks (library_item_id, name, position_ms) VALUES (?1, ?2, ?3)",
+            rusqlite::params![track_key.to_string(), name, position_ms as i64],
+        );
+
+        match result {
+            Ok(_) => {
+                let bookmarks = &mut self.track_markers.bookmarks;
+                bookmarks.push(Bookmark {
+                    id: conn_guard.last_insert_rowid(),
+                    name,
+                    position_ms,
+                });
+                bookmarks.sort_by_key(|b| b.position_ms);
+            }
+            Err(e) => tracing::error!("Failed to add bookmark: {}", e),
+        }
+    }
+
+    pub fn remove_bookmark(&mut self, id: i64) {
+        if let Some(db) = &self.database {
+            let conn = db.connection();
+            let conn_guard = conn.lock().unwrap();
+            if let Err(e) =
+                conn_guard.execute("DELETE FROM bookmarks WHERE id = ?1", rusqlite::params![id])
+            {
+                tracing::error!("Failed to remove bookmark: {}", e);
+                return;
+            }
+        }
+
+        self.track_markers.bookmarks.retain(|b| b.id != id);
+    }
+}
@@ -0,0 +1,111 @@
+use super::AppComponent;
+use crate::app::player::TrackState;
+use crate::app::seek::format_time;
+use crate::app::t;
+use crate::app::App;
+use eframe::egui;
+
+pub struct BookmarksMenu;
+
+impl AppComponent for BookmarksMenu {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        let has_selected_track = ctx
+            .player
+            .as_ref()
+            .is_some_and(|player| player.selected_track.is_some());
+
+        // Position to jump to, in milliseconds
+        let mut jump_to: Option<u64> = None;
+        let mut bookmark_to_remove: Option<i64> = None;
+
+        ui.add_enabled_ui(has_selected_track, |ui| {
+            ui.menu_button("🔖", |ui| {
+                let name_id = ui.id().with("new_bookmark_name");
+                let mut name = ui
+                    .memory_mut(|mem| mem.data.get_temp::<String>(name_id))
+                    .unwrap_or_default();
+
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut name)
+                            .desired_width(120.0)
+                            .hint_text(t("bookmark_name")),
+                    );
+
+                    if ui.button(t("add_bookmark")).clicked() {
+                        let position_ms = ctx
+                            .player
+                            .as_ref()
+                            .and_then(|player| player.ts_to_ms(player.seek_to_timestamp));
+
+                        if let Some(position_ms) = position_ms {
+                            let bookmark_name = if name.trim().is_empty() {
+                                crate::app::tf("bookmark_at", &[&format_ms(position_ms)])
+                            } else {
+                                name.trim().to_string()
+                            };
+                            ctx.add_bookmark(bookmark_name, position_ms);
+                            name.clear();
+                        }
+                    }
+                });
+
+                ui.memory_mut(|mem| mem.data.insert_temp(name_id, name));
+
+                if !ctx.track_markers.chapters.is_empty() {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.strong(t("chapters"));
+                        ui.checkbox(&mut ctx.show_chapter_panel, t("show_chapter_panel"));
+                    });
+                    for chapter in &ctx.track_markers.chapters {
+                        let label = format!("{}  {}", format_ms(chapter.start_ms), chapter.title);
+                        if ui.button(label).clicked() {
+                            jump_to = Some(chapter.start_ms);
+                            ui.close_menu();
+                        }
+                    }
+                }
+
+                if !ctx.track_markers.bookmarks.is_empty() {
+                    ui.separator();
+                    ui.strong(t("bookmarks"));
+                    for bookmark in &ctx.track_markers.bookmarks {
+                        ui.horizontal(|ui| {
+                            let label =
+                                format!("{}  {}", format_ms(bookmark.position_ms), bookmark.name);
+                            if ui.button(label).clicked() {
+                                jump_to = Some(bookmark.position_ms);
+                                ui.close_menu();
+                            }
+                            if ui.small_button("🗑").clicked() {
+                                bookmark_to_remove = Some(bookmark.id);
+                            }
+                        });
+                    }
+                }
+            })
+            .response
+            .on_hover_text(t("bookmarks"));
+        });
+
+        if let Some(id) = bookmark_to_remove {
+            ctx.remove_bookmark(id);
+        }
+
+        if let Some(position_ms) = jump_to {
+            if let Some(player) = &mut ctx.player {
+                if let Some(ts) = player.ms_to_ts(position_ms) {
+                    player.seek_to(ts);
+                    player.track_state = TrackState::Playing;
+                }
+            }
+        }
+    }
+}
+
+fn format_ms(ms: u64) -> String {
+    format_time(ms / 1000)
+}
@@ -1,13 +1,14 @@
 use super::AppComponent;
-use crate::app::App;
+use crate::app::art_cache::{display_resolution, ArtCache, ART_STALE_AFTER, MAX_ART_CACHE_BYTES};
+use crate::app::skin::{Skin, SkinStyle};
+use crate::app::{t, App};
 use crate::egui::epaint::*;
 use crate::egui::{vec2, ColorImage, Shape, TextureHandle};
 use ::image::io::Reader as ImageReader;
 use eframe::egui::{Rect, Sense};
 use log::{error, info, warn};
-use std::collections::HashMap;
 use std::io::Cursor;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 struct CassetteColors {
@@ -19,8 +20,8 @@ struct CassetteColors {
 }
 
 impl CassetteColors {
-    fn from_theme(ui: &eframe::egui::Ui) -> Self {
-        if ui.visuals().dark_mode {
+    fn from_theme(ui: &eframe::egui::Ui, skin: &Skin) -> Self {
+        let theme = if ui.visuals().dark_mode {
             Self {
                 stroke: Color32::from_rgb(60, 60, 65),
                 tape: Color32::from_rgb(0, 0, 0),
@@ -36,6 +37,18 @@ impl CassetteColors {
                 reel_spokes: Color32::from_rgb(180, 180, 185),
                 default_album_art: Color32::from_rgb(255, 255, 255),
             }
+        };
+
+        // The skin's own colors win over the theme's
+        let rgb = |color: Option<[u8; 3]>, fallback: Color32| {
+            color.map_or(fallback, |[r, g, b]| Color32::from_rgb(r, g, b))
+        };
+        Self {
+            stroke: rgb(skin.frame_color, theme.stroke),
+            tape: rgb(skin.tape_color, theme.tape),
+            reel_stroke: rgb(skin.frame_color, theme.reel_stroke),
+            reel_spokes: theme.reel_spokes,
+            default_album_art: rgb(skin.label_color, theme.default_album_art),
         }
     }
 }
@@ -48,7 +61,7 @@ const REEL_RADIUS: f32 = 40.0;
 
 thread_local! {
     static LAST_UPDATE: std::cell::RefCell<Instant> = std::cell::RefCell::new(Instant::now());
-    static IMAGE_CACHE: std::cell::RefCell<HashMap<PathBuf, TextureHandle>> = std::cell::RefCell::new(HashMap::new());
+    static IMAGE_CACHE: std::cell::RefCell<ArtCache<PathBuf, CachedArt>> = std::cell::RefCell::new(ArtCache::new(MAX_ART_CACHE_BYTES));
     static ROTATION_ANGLE: std::cell::RefCell<f32> = const {std::cell::RefCell::new(0.0)};
 }
 
@@ -57,146 +70,30 @@ impl AppComponent for CassetteComponent {
 
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
         ui.horizontal(|ui| {
-            let colors = CassetteColors::from_theme(ui);
+            let skin = ctx.skin.clone();
+            let colors = CassetteColors::from_theme(ui, &skin);
             let rect = ui.available_rect_before_wrap().shrink(10.0);
             let rect = Rect::from_min_size(rect.min, vec2(CASSETTE_WIDTH, CASSETTE_HEIGHT));
 
-            ui.allocate_rect(rect, Sense::hover());
-
-            let left_reel_center = rect.left_center() + vec2(REEL_RADIUS + 20.0, 0.0);
-            let right_reel_center = rect.right_center() - vec2(REEL_RADIUS + 20.0, 0.0);
-            let center_rect = eframe::egui::Rect::from_center_size(
-                rect.center(),
-                vec2(ALBUM_ART_SIZE, ALBUM_ART_SIZE),
-            );
-
-            // Draw main cassette frame with rounded corners
-            let corner_radius = 8.0;
-            ui.painter().add(Shape::Rect(RectShape {
-                rect,
-                corner_radius: corner_radius.into(),
-                fill: Color32::TRANSPARENT,
-                stroke: Stroke::new(1.0, colors.stroke),
-                stroke_kind: StrokeKind::Middle,
-                round_to_pixels: None,
-                blur_width: 0.0,
-                brush: None,
-            }));
-
-            // Draw bottom detail area
-            let detail_height = 20.0;
-            let detail_rect = Rect::from_min_max(
-                rect.left_bottom() - vec2(0.0, detail_height),
-                rect.right_bottom(),
-            );
-
-            // Draw horizontal lines for detail area
-            ui.painter().line_segment(
-                [
-                    detail_rect.left_top(),
-                    eframe::egui::pos2(detail_rect.right(), detail_rect.top()),
-                ],
-                Stroke::new(1.0, colors.stroke),
-            );
-
-            // Draw round holes on the sides
-            let button_radius = 8.0;
-            let button_margin = 20.0;
-
-            // Left hole
-            ui.painter().circle_stroke(
-                detail_rect.left_center() + vec2(button_margin, 0.0),
-                button_radius,
-                Stroke::new(1.0, colors.stroke),
-            );
-
-            // Right hole
-            ui.painter().circle_stroke(
-                detail_rect.right_center() - vec2(button_margin, 0.0),
-                button_radius,
-                Stroke::new(1.0, colors.stroke),
-            );
-
-            // Draw trapezoid frame in the center
-            let trapezoid_width = 120.0;
-            let trapezoid_inset = 10.0;
-            let center_x = detail_rect.center().x;
-
-            let trapezoid_points = vec![
-                eframe::egui::pos2(
-                    center_x - (trapezoid_width - trapezoid_inset) / 2.0,
-                    detail_rect.top() + 4.0,
-                ),
-                eframe::egui::pos2(
-                    center_x + (trapezoid_width - trapezoid_inset) / 2.0,
-                    detail_rect.top() + 4.0,
-                ),
-                eframe::egui::pos2(center_x + trapezoid_width / 2.0, detail_rect.bottom() - 2.0),
-                eframe::egui::pos2(center_x - trapezoid_width / 2.0, detail_rect.bottom() - 2.0),
-            ];
-
-            ui.painter().add(Shape::convex_polygon(
-                trapezoid_points.clone(),
-                Color32::TRANSPARENT,
-                Stroke::new(1.0, colors.stroke),
-            ));
-
-            // Draw holes in the trapezoid frame with varying sizes as rounded rectangles
-            let hole_sizes = [2.0, 3.0, 4.0, 4.0, 3.0, 2.0]; // Height of the holes
-            let hole_width = 3.0; // Fixed width for all holes
-            let num_holes = hole_sizes.len();
-            let hole_spacing = (trapezoid_width - trapezoid_inset / 2.0) / (num_holes as f32 + 1.0);
-            let hole_y = detail_rect.bottom() - 8.0;
-
-            for i in 1..=num_holes {
-                let hole_x = center_x - trapezoid_width / 2.0 + (i as f32 * hole_spacing);
-                let hole_height = hole_sizes[i - 1];
-
-                let hole_rect = Rect::from_center_size(
-                    eframe::egui::pos2(hole_x, hole_y),
-                    vec2(hole_width, hole_height),
-                );
-
-                ui.painter().add(Shape::Rect(RectShape {
-                    rect: hole_rect,
-                    corner_radius: 1.0.into(),
-                    fill: ui.visuals().window_fill(), // Use window background color for transparent/white fill
-                    stroke: Stroke::new(1.0, colors.stroke),
-                    stroke_kind: StrokeKind::Middle,
-                    round_to_pixels: None,
-                    blur_width: 0.0,
-                    brush: None,
-                }));
+            // Double-click the tape for the full screen Now Playing view
+            if ui
+                .allocate_rect(rect, Sense::click())
+                .on_hover_text(t("now_playing_open_hint"))
+                .double_clicked()
+            {
+                ctx.enter_now_playing(ui.ctx());
             }
 
-            let (current_angle, tape_progress) = update_animation(ctx);
+            let (current_angle, progress) = update_animation(ctx, skin.spin_speed);
 
-            draw_tape(
-                ui,
-                left_reel_center,
-                right_reel_center,
-                center_rect,
-                tape_progress,
-                &colors,
-            );
-
-            draw_reel(
-                ui,
-                left_reel_center,
-                current_angle,
-                colors.reel_stroke,
-                1.0 - tape_progress,
-                &colors,
-            );
-
-            draw_reel(
-                ui,
-                right_reel_center,
-                -current_angle,
-                colors.reel_stroke,
-                tape_progress,
-                &colors,
-            );
+            // Each skin draws its body and says where the artwork goes
+            let label_rect = match skin.style {
+                SkinStyle::Cassette => draw_cassette(ui, rect, current_angle, progress, &colors),
+                SkinStyle::Vinyl => draw_vinyl(ui, rect, current_angle, progress, &colors),
+                SkinStyle::Artwork => {
+                    Rect::from_center_size(rect.center(), vec2(CASSETTE_HEIGHT, CASSETTE_HEIGHT))
+                }
+            };
 
             let mut show_wave_canvas = true;
 
@@ -204,85 +101,338 @@ impl AppComponent for CassetteComponent {
                 if let Some(picture) = selected_track.pictures().first() {
                     let path = picture.file_path.clone();
 
-                    show_wave_canvas = !IMAGE_CACHE.with(|cache| {
-                        if !cache.borrow().contains_key(&path) {
-                            if let Ok(image_bytes) = std::fs::read(&path) {
-                                let reader = ImageReader::new(Cursor::new(image_bytes))
-                                    .with_guessed_format();
-
-                                if let Ok(reader) = reader {
-                                    if let Ok(img) = reader.decode() {
-                                        let rgba_img = img.into_rgba8();
-                                        let size = [rgba_img.width() as _, rgba_img.height() as _];
-                                        let pixels = rgba_img.into_raw();
-                                        let color_image =
-                                            ColorImage::from_rgba_unmultiplied(size, &pixels);
-                                        let texture = ui.ctx().load_texture(
-                                            path.to_str().unwrap_or_default(),
-                                            color_image,
-                                            Default::default(),
-                                        );
-                                        info!("Successfully loaded image from: {:?}", path);
-                                        cache.borrow_mut().insert(path.clone(), texture);
-                                    } else {
-                                        error!("Failed to decode image for path: {:?}", path);
-                                    }
-                                } else {
-                                    error!("Failed to guess image format for path: {:?}", path);
-                                }
-                            } else {
-                                error!("Failed to read image file at path: {:?}", path);
-                            }
-                        }
-
-                        if let Some(texture) = cache.borrow().get(&path) {
-                            let image_rect = center_rect;
-
-                            // Calculate UV coordinates for center-cropped fit
-                            let image_aspect = texture.size_vec2()[0] / texture.size_vec2()[1];
-                            let rect_aspect = image_rect.width() / image_rect.height();
-
-                            let (uv_min, uv_max) = if image_aspect > rect_aspect {
-                                // Image is wider than display area - crop sides
-                                let crop_width = rect_aspect / image_aspect;
-                                let offset = (1.0 - crop_width) / 2.0;
-                                (
-                                    eframe::egui::pos2(offset, 0.0),
-                                    eframe::egui::pos2(1.0 - offset, 1.0),
-                                )
-                            } else {
-                                // Image is taller than display area - crop top/bottom
-                                let crop_height = image_aspect / rect_aspect;
-                                let offset = (1.0 - crop_height) / 2.0;
-                                (
-                                    eframe::egui::pos2(0.0, offset),
-                                    eframe::egui::pos2(1.0, 1.0 - offset),
-                                )
-                            };
-
-                            ui.painter().image(
-                                texture.id(),
-                                image_rect,
-                                eframe::egui::Rect::from_min_max(uv_min, uv_max),
-                                Color32::WHITE,
-                            );
-                            true
-                        } else {
-                            warn!("Image not found in cache for path: {:?}", path);
-                            false
-                        }
-                    });
+                    show_wave_canvas = !paint_album_art(ui, &path, label_rect);
                 }
             }
 
             if show_wave_canvas {
-                show_default_album_art(ctx, ui, center_rect);
+                show_default_album_art(ctx, ui, label_rect, &colors, skin.show_label);
             }
         });
     }
 }
 
-fn update_animation(ctx: &mut App) -> (f32, f32) {
+// The classic tape: frame, reels winding from one side to the other and a window for the art
+fn draw_cassette(
+    ui: &mut eframe::egui::Ui,
+    rect: Rect,
+    current_angle: f32,
+    tape_progress: f32,
+    colors: &CassetteColors,
+) -> Rect {
+    let left_reel_center = rect.left_center() + vec2(REEL_RADIUS + 20.0, 0.0);
+    let right_reel_center = rect.right_center() - vec2(REEL_RADIUS + 20.0, 0.0);
+    let center_rect =
+        eframe::egui::Rect::from_center_size(rect.center(), vec2(ALBUM_ART_SIZE, ALBUM_ART_SIZE));
+
+    // Draw main cassette frame with rounded corners
+    let corner_radius = 8.0;
+    ui.painter().add(Shape::Rect(RectShape {
+        rect,
+        corner_radius: corner_radius.into(),
+        fill: Color32::TRANSPARENT,
+        stroke: Stroke::new(1.0, colors.stroke),
+        stroke_kind: StrokeKind::Middle,
+        round_to_pixels: None,
+        blur_width: 0.0,
+        brush: None,
+    }));
+
+    // Draw bottom detail area
+    let detail_height = 20.0;
+    let detail_rect = Rect::from_min_max(
+        rect.left_bottom() - vec2(0.0, detail_height),
+        rect.right_bottom(),
+    );
+
+    // Draw horizontal lines for detail area
+    ui.painter().line_segment(
+        [
+            detail_rect.left_top(),
+            eframe::egui::pos2(detail_rect.right(), detail_rect.top()),
+        ],
+        Stroke::new(1.0, colors.stroke),
+    );
+
+    // Draw round holes on the sides
+    let button_radius = 8.0;
+    let button_margin = 20.0;
+
+    // Left hole
+    ui.painter().circle_stroke(
+        detail_rect.left_center() + vec2(button_margin, 0.0),
+        button_radius,
+        Stroke::new(1.0, colors.stroke),
+    );
+
+    // Right hole
+    ui.painter().circle_stroke(
+        detail_rect.right_center() - vec2(button_margin, 0.0),
+        button_radius,
+        Stroke::new(1.0, colors.stroke),
+    );
+
+    // Draw trapezoid frame in the center
+    let trapezoid_width = 120.0;
+    let trapezoid_inset = 10.0;
+    let center_x = detail_rect.center().x;
+
+    let trapezoid_points = vec![
+        eframe::egui::pos2(
+            center_x - (trapezoid_width - trapezoid_inset) / 2.0,
+            detail_rect.top() + 4.0,
+        ),
+        eframe::egui::pos2(
+            center_x + (trapezoid_width - trapezoid_inset) / 2.0,
+            detail_rect.top() + 4.0,
+        ),
+        eframe::egui::pos2(center_x + trapezoid_width / 2.0, detail_rect.bottom() - 2.0),
+        eframe::egui::pos2(center_x - trapezoid_width / 2.0, detail_rect.bottom() - 2.0),
+    ];
+
+    ui.painter().add(Shape::convex_polygon(
+        trapezoid_points.clone(),
+        Color32::TRANSPARENT,
+        Stroke::new(1.0, colors.stroke),
+    ));
+
+    // Draw holes in the trapezoid frame with varying sizes as rounded rectangles
+    let hole_sizes = [2.0, 3.0, 4.0, 4.0, 3.0, 2.0]; // Height of the holes
+    let hole_width = 3.0; // Fixed width for all holes
+    let num_holes = hole_sizes.len();
+    let hole_spacing = (trapezoid_width - trapezoid_inset / 2.0) / (num_holes as f32 + 1.0);
+    let hole_y = detail_rect.bottom() - 8.0;
+
+    for i in 1..=num_holes {
+        let hole_x = center_x - trapezoid_width / 2.0 + (i as f32 * hole_spacing);
+        let hole_height = hole_sizes[i - 1];
+
+        let hole_rect = Rect::from_center_size(
+            eframe::egui::pos2(hole_x, hole_y),
+            vec2(hole_width, hole_height),
+        );
+
+        ui.painter().add(Shape::Rect(RectShape {
+            rect: hole_rect,
+            corner_radius: 1.0.into(),
+            fill: ui.visuals().window_fill(), // Use window background color for transparent/white fill
+            stroke: Stroke::new(1.0, colors.stroke),
+            stroke_kind: StrokeKind::Middle,
+            round_to_pixels: None,
+            blur_width: 0.0,
+            brush: None,
+        }));
+    }
+
+    draw_tape(
+        ui,
+        left_reel_center,
+        right_reel_center,
+        center_rect,
+        tape_progress,
+        colors,
+    );
+
+    draw_reel(
+        ui,
+        left_reel_center,
+        current_angle,
+        colors.reel_stroke,
+        1.0 - tape_progress,
+        colors,
+    );
+
+    draw_reel(
+        ui,
+        right_reel_center,
+        -current_angle,
+        colors.reel_stroke,
+        tape_progress,
+        colors,
+    );
+
+    center_rect
+}
+
+// A record turning under a tonearm that moves in as the track plays. The art sits on
+// the center label.
+fn draw_vinyl(
+    ui: &mut eframe::egui::Ui,
+    rect: Rect,
+    current_angle: f32,
+    progress: f32,
+    colors: &CassetteColors,
+) -> Rect {
+    let radius = rect.height() / 2.0 - 4.0;
+    let center = rect.center();
+    let label_radius = radius * 0.45;
+
+    ui.painter()
+        .circle(center, radius, colors.tape, Stroke::new(1.0, colors.stroke));
+
+    // Grooves
+    let groove_color = colors.reel_spokes.gamma_multiply(0.35);
+    let mut groove = label_radius + 6.0;
+    while groove < radius - 3.0 {
+        ui.painter()
+            .circle_stroke(center, groove, Stroke::new(0.5, groove_color));
+        groove += 5.0;
+    }
+
+    // Light catching the grooves, it's what shows the record turning
+    for offset in [0.0, std::f32::consts::PI] {
+        let angle = current_angle + offset;
+        let direction = vec2(angle.cos(), angle.sin());
+        ui.painter().line_segment(
+            [
+                center + direction * (label_radius + 4.0),
+                center + direction * (radius - 4.0),
+            ],
+            Stroke::new(2.0, colors.reel_spokes.gamma_multiply(0.6)),
+        );
+    }
+
+    ui.painter()
+        .circle_filled(center, label_radius, colors.default_album_art);
+
+    // Tonearm from the top right, swinging from the edge towards the label
+    let pivot = eframe::egui::pos2(rect.right() - 12.0, rect.top() + 14.0);
+    let edge = center + vec2(radius * 0.65, -radius * 0.6);
+    let inner = center + vec2(label_radius * 0.9, -label_radius * 1.1);
+    let needle = edge + (inner - edge) * progress.clamp(0.0, 1.0);
+    ui.painter()
+        .line_segment([pivot, needle], Stroke::new(3.0, colors.reel_stroke));
+    ui.painter().circle_filled(pivot, 6.0, colors.reel_stroke);
+    ui.painter().circle_filled(needle, 3.0, colors.reel_spokes);
+
+    // The largest square that fits on the label
+    let side = label_radius * std::f32::consts::SQRT_2;
+    Rect::from_center_size(center, vec2(side, side))
+}
+
+struct CachedArt {
+    texture: TextureHandle,
+    // Longest side uploaded, and whether that's the whole picture rather than a downscale
+    resolution: u32,
+    complete: bool,
+}
+
+/// Paints the picture at `path` center-cropped into `rect`. Returns false when it
+/// can't be loaded, so the caller can draw a placeholder instead.
+pub fn paint_album_art(ui: &eframe::egui::Ui, path: &Path, rect: Rect) -> bool {
+    let now = ui.input(|input| input.time);
+    let wanted = display_resolution(rect.width().max(rect.height()), ui.ctx().pixels_per_point());
+
+    IMAGE_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache.evict_stale(now, ART_STALE_AFTER);
+
+        // A bigger view than the art was loaded for, e.g. the full screen one
+        let sharp_enough = cache
+            .get(&path.to_path_buf(), now)
+            .is_some_and(|art| art.complete || art.resolution >= wanted);
+        if !sharp_enough {
+            if let Some((art, bytes)) = load_album_art(ui.ctx(), path, wanted) {
+                info!("Successfully loaded image from: {:?}", path);
+                cache.insert(path.to_path_buf(), art, bytes, now);
+            }
+        }
+
+        if let Some(art) = cache.get(&path.to_path_buf(), now) {
+            let texture = &art.texture;
+            let image_rect = rect;
+
+            // Calculate UV coordinates for center-cropped fit
+            let image_aspect = texture.size_vec2()[0] / texture.size_vec2()[1];
+            let rect_aspect = image_rect.width() / image_rect.height();
+
+            let (uv_min, uv_max) = if image_aspect > rect_aspect {
+                // Image is wider than display area - crop sides
+                let crop_width = rect_aspect / image_aspect;
+                let offset = (1.0 - crop_width) / 2.0;
+                (
+                    eframe::egui::pos2(offset, 0.0),
+                    eframe::egui::pos2(1.0 - offset, 1.0),
+                )
+            } else {
+                // Image is taller than display area - crop top/bottom
+                let crop_height = image_aspect / rect_aspect;
+                let offset = (1.0 - crop_height) / 2.0;
+                (
+                    eframe::egui::pos2(0.0, offset),
+                    eframe::egui::pos2(1.0, 1.0 - offset),
+                )
+            };
+
+            ui.painter().image(
+                texture.id(),
+                image_rect,
+                eframe::egui::Rect::from_min_max(uv_min, uv_max),
+                Color32::WHITE,
+            );
+            true
+        } else {
+            warn!("Image not found in cache for path: {:?}", path);
+            false
+        }
+    })
+}
+
+// Decodes the picture and uploads it no larger than `resolution` on its shortest side,
+// which is the one that fills the rect after cropping. Returns the art and its size in bytes.
+fn load_album_art(
+    ctx: &eframe::egui::Context,
+    path: &Path,
+    resolution: u32,
+) -> Option<(CachedArt, usize)> {
+    let Ok(image_bytes) = std::fs::read(path) else {
+        error!("Failed to read image file at path: {:?}", path);
+        return None;
+    };
+    let Ok(reader) = ImageReader::new(Cursor::new(image_bytes)).with_guessed_format() else {
+        error!("Failed to guess image format for path: {:?}", path);
+        return None;
+    };
+    let Ok(img) = reader.decode() else {
+        error!("Failed to decode image for path: {:?}", path);
+        return None;
+    };
+
+    let shortest = img.width().min(img.height());
+    let complete = shortest <= resolution;
+    let img = if complete {
+        img
+    } else {
+        let scale = resolution as f32 / shortest as f32;
+        img.resize(
+            (img.width() as f32 * scale).round() as u32,
+            (img.height() as f32 * scale).round() as u32,
+            ::image::imageops::FilterType::Triangle,
+        )
+    };
+
+    let rgba_img = img.into_rgba8();
+    let size = [rgba_img.width() as _, rgba_img.height() as _];
+    let pixels = rgba_img.into_raw();
+    let bytes = pixels.len();
+    let color_image = ColorImage::from_rgba_unmultiplied(size, &pixels);
+    let texture = ctx.load_texture(
+        path.to_str().unwrap_or_default(),
+        color_image,
+        Default::default(),
+    );
+
+    Some((
+        CachedArt {
+            texture,
+            resolution,
+            complete,
+        },
+        bytes,
+    ))
+}
+
+fn update_animation(ctx: &mut App, spin_speed: f32) -> (f32, f32) {
     let current_angle = ROTATION_ANGLE.with(|angle| {
         let now = Instant::now();
         let elapsed = LAST_UPDATE.with(|last| {
@@ -291,10 +441,15 @@ fn update_animation(ctx: &mut App) -> (f32, f32) {
             elapsed
         });
 
-        let is_playing = ctx.player.as_ref().unwrap().track_state.to_string() == "Playing";
-        let rotation_speed = if is_playing { 2.0 } else { 0.0 };
+        let rotation_speed = if ctx.animations_running() {
+            spin_speed
+        } else {
+            0.0
+        };
 
-        *angle.borrow_mut() += rotation_speed * elapsed.as_secs_f32();
+        // The first frame after being idle shouldn't jump by the whole pause
+        let elapsed = elapsed.as_secs_f32().min(0.1);
+        *angle.borrow_mut() += rotation_speed * elapsed;
         *angle.borrow()
     });
 
@@ -417,8 +572,13 @@ fn draw_reel(
     );
 }
 
-fn show_default_album_art(ctx: &App, ui: &mut eframe::egui::Ui, rect: eframe::egui::Rect) {
-    let colors = CassetteColors::from_theme(ui);
+fn show_default_album_art(
+    ctx: &App,
+    ui: &mut eframe::egui::Ui,
+    rect: eframe::egui::Rect,
+    colors: &CassetteColors,
+    show_label: bool,
+) {
     let corner_radius = 0.0;
     ui.painter().add(Shape::Rect(RectShape {
         rect,
@@ -431,6 +591,10 @@ fn show_default_album_art(ctx: &App, ui: &mut eframe::egui::Ui, rect: eframe::eg
         brush: None,
     }));
 
+    if !show_label {
+        return;
+    }
+
     // Create a vertical layout for the text
     let text_spacing = 24.0;
     let title_pos = rect.center();
@@ -440,81 +604,55 @@ fn show_default_album_art(ctx: &App, ui: &mut eframe::egui::Ui, rect: eframe::eg
     if let Some(selected_track) = &ctx.player.as_ref().unwrap().selected_track {
         // Calculate maximum text width (80% of rect width to leave some margin)
         let max_width = rect.width() * 0.8;
-        let title_font = eframe::egui::FontId::proportional(12.0);
-        let artist_font = eframe::egui::FontId::proportional(12.0);
-
-        // Draw title with truncation
-        let title = selected_track
-            .title()
-            .unwrap_or("Unknown Title".to_string());
-        let title_galley =
-            ui.painter()
-                .layout_no_wrap(title.clone(), title_font.clone(), Color32::DARK_GRAY);
-
-        let truncated_title = if title_galley.rect.width() > max_width {
-            // Find appropriate truncation point
-            let mut truncated = title.clone();
-            while truncated.len() > 3 {
-                // Keep at least 3 chars
-                truncated.pop();
-                let test_galley = ui.painter().layout_no_wrap(
-                    format!("{}...", truncated),
-                    title_font.clone(),
-                    Color32::DARK_GRAY,
-                );
-                if test_galley.rect.width() <= max_width {
-                    truncated.push_str("...");
-                    break;
-                }
-            }
-            truncated
-        } else {
-            title
-        };
+        let font = eframe::egui::FontId::proportional(12.0);
 
+        let title = selected_track.title().unwrap_or_else(|| t("unknown_title"));
         ui.painter().text(
             title_pos,
             eframe::egui::Align2::CENTER_CENTER,
-            truncated_title,
-            title_font,
+            truncate_to_width(ui, title, &font, max_width),
+            font.clone(),
             Color32::DARK_GRAY,
         );
 
-        // Draw artist with truncation
         let artist = selected_track
             .artist()
-            .unwrap_or("Unknown Artist".to_string());
-        let artist_galley =
-            ui.painter()
-                .layout_no_wrap(artist.clone(), artist_font.clone(), Color32::DARK_GRAY);
-
-        let truncated_artist = if artist_galley.rect.width() > max_width {
-            // Find appropriate truncation point
-            let mut truncated = artist.clone();
-            while truncated.len() > 3 {
-                // Keep at least 3 chars
-                truncated.pop();
-                let test_galley = ui.painter().layout_no_wrap(
-                    format!("{}...", truncated),
-                    artist_font.clone(),
-                    Color32::DARK_GRAY,
-                );
-                if test_galley.rect.width() <= max_width {
-                    truncated.push_str("...");
-                    break;
-                }
-            }
-            truncated
-        } else {
-            artist
-        };
-
+            .unwrap_or_else(|| t("unknown_artist"));
         ui.painter().text(
             artist_pos,
             eframe::egui::Align2::CENTER_CENTER,
-            truncated_artist,
-            artist_font,
+            truncate_to_width(ui, artist, &font, max_width),
+            font,
             Color32::DARK_GRAY,
         );
     }
 }
+
+// Cuts the text short with "..." so it fits in `max_width`
+fn truncate_to_width(
+    ui: &eframe::egui::Ui,
+    text: String,
+    font: &eframe::egui::FontId,
+    max_width: f32,
+) -> String {
+    let width = |text: String| {
+        ui.painter()
+            .layout_no_wrap(text, font.clone(), Color32::DARK_GRAY)
+            .rect
+            .width()
+    };
+    if width(text.clone()) <= max_width {
+        return text;
+    }
+
+    let mut truncated = text;
+    // Keep at least 3 chars
+    while truncated.chars().count() > 3 {
+        truncated.pop();
+        if width(format!("{}...", truncated)) <= max_width {
+            break;
+        }
+    }
+    truncated.push_str("...");
+    truncated
+}
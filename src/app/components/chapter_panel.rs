@@ -0,0 +1,63 @@
+use super::AppComponent;
+use crate::app::seek::format_time;
+use crate::app::{t, App};
+use eframe::egui;
+
+pub struct ChapterPanel;
+
+impl AppComponent for ChapterPanel {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        let position_ms = ctx
+            .player
+            .as_ref()
+            .and_then(|player| player.ts_to_ms(player.seek_to_timestamp))
+            .unwrap_or(0);
+        let current = ctx.track_markers.current_chapter(position_ms);
+
+        // Scroll to the playing chapter when the book moves on to another one
+        let shown_id = ui.id().with("shown_chapter");
+        let moved_on = ui.memory_mut(|mem| {
+            let shown = mem.data.get_temp::<Option<usize>>(shown_id);
+            mem.data.insert_temp(shown_id, current);
+            shown != Some(current)
+        });
+
+        ui.horizontal(|ui| {
+            ui.strong(t("chapters"));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .small_button("✕")
+                    .on_hover_text(t("hide_chapter_panel"))
+                    .clicked()
+                {
+                    ctx.show_chapter_panel = false;
+                }
+            });
+        });
+        ui.separator();
+
+        let mut jump_to: Option<u64> = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (idx, chapter) in ctx.track_markers.chapters.iter().enumerate() {
+                let label = format!(
+                    "{}  {}",
+                    format_time(chapter.start_ms / 1000),
+                    chapter.title
+                );
+                let response = ui.selectable_label(current == Some(idx), label);
+                if response.clicked() {
+                    jump_to = Some(chapter.start_ms);
+                }
+                if moved_on && current == Some(idx) {
+                    response.scroll_to_me(Some(egui::Align::Center));
+                }
+            }
+        });
+
+        if let Some(start_ms) = jump_to {
+            ctx.seek_to_seconds(start_ms as f64 / 1000.0);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use super::AppComponent;
+use crate::app::{t, tf, App};
+use eframe::egui::{self, Window};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+pub struct ExportWindow;
+
+impl AppComponent for ExportWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if ctx.export_playlist_idx.is_none() && ctx.playlist_export.is_none() {
+            return;
+        }
+
+        let mut open = true;
+        let mut close = false;
+        Window::new(t("export_to_folder"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                if let Some(export) = &ctx.playlist_export {
+                    ui.label(tf(
+                        "export_copying",
+                        &[&export.playlist_name, &export.target.display().to_string()],
+                    ));
+                    ui.add_space(5.0);
+
+                    let copied = export.copied.load(Ordering::Relaxed);
+                    let failed = export.failed.load(Ordering::Relaxed);
+                    ui.add(egui::ProgressBar::new(export.progress()).text(format!(
+                        "{} / {}",
+                        copied + failed,
+                        export.total
+                    )));
+                    ui.add_space(5.0);
+
+                    if export.is_running() {
+                        if ui.button(t("cancel")).clicked() {
+                            export.cancel.store(true, Ordering::Relaxed);
+                        }
+                        ui.ctx().request_repaint_after(Duration::from_millis(100));
+                    } else {
+                        ui.label(tf(
+                            "export_finished",
+                            &[&copied.to_string(), &failed.to_string()],
+                        ));
+                        if ui.button(t("close")).clicked() {
+                            close = true;
+                        }
+                    }
+                } else if let Some(idx) = ctx.export_playlist_idx {
+                    ui.horizontal(|ui| {
+                        ui.label(t("export_pattern"));
+                        if ui
+                            .text_edit_singleline(&mut ctx.export_pattern)
+                            .on_hover_text(t("export_pattern_hint"))
+                            .lost_focus()
+                        {
+                            ctx.save_state();
+                        }
+                    });
+                    ui.add_space(5.0);
+
+                    if ui.button(t("export_choose_folder")).clicked() {
+                        if let Some(target) = rfd::FileDialog::new().pick_folder() {
+                            ctx.save_state();
+                            ctx.export_playlist(idx, target);
+                        }
+                    }
+                }
+            });
+
+        let running = ctx
+            .playlist_export
+            .as_ref()
+            .is_some_and(|export| export.is_running());
+
+        // Closing the window while copying cancels the export
+        if !open && running {
+            if let Some(export) = &ctx.playlist_export {
+                export.cancel.store(true, Ordering::Relaxed);
+            }
+        }
+
+        if close || (!open && !running) {
+            ctx.export_playlist_idx = None;
+            ctx.playlist_export = None;
+        }
+    }
+}
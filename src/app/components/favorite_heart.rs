@@ -0,0 +1,21 @@
+use crate::app::t;
+use eframe::egui::{self, RichText};
+
+// Red of a favorite's heart
+const HEART_COLOR: egui::Color32 = egui::Color32::from_rgb(0xe0, 0x4f, 0x4f);
+
+/// A heart that's red on favorites and faint on other tracks, clicked to toggle.
+pub fn heart(ui: &mut egui::Ui, favorite: bool) -> egui::Response {
+    let (color, hover) = if favorite {
+        (HEART_COLOR, "remove_favorite")
+    } else {
+        (ui.visuals().weak_text_color(), "add_favorite")
+    };
+    let response = ui
+        .add(egui::Label::new(RichText::new("♥").color(color)).sense(egui::Sense::click()))
+        .on_hover_text(t(hover));
+    if response.hovered() {
+        ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::PointingHand);
+    }
+    response
+}
@@ -1,3 +1,4 @@
+use super::level_meter::LevelMeter;
 use super::AppComponent;
 use crate::app::App;
 
@@ -96,6 +97,8 @@ impl AppComponent for Footer {
                                             || artist_lower.contains(&search_lower)
                                             || album_lower.contains(&search_lower)
                                             || genre_lower.contains(&search_lower)
+                                            || ctx.labels.track_matches(track.key(), &search_lower)
+                                            || ctx.track_notes.matches(track.key(), &search_lower)
                                         {
                                             playlist.selected_indices.insert(idx);
                                             match_count += 1;
@@ -267,6 +270,14 @@ impl AppComponent for Footer {
                     }
                 }
             }
+
+            // Output levels at the far right
+            ui.with_layout(
+                eframe::egui::Layout::right_to_left(eframe::egui::Align::Center),
+                |ui| {
+                    LevelMeter::add(ctx, ui);
+                },
+            );
         });
     }
 }
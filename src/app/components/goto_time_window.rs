@@ -0,0 +1,62 @@
+use super::AppComponent;
+use crate::app::seek::parse_time;
+use crate::app::t;
+use crate::app::App;
+use eframe::egui::{self, Window};
+
+pub struct GotoTimeWindow;
+
+impl AppComponent for GotoTimeWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.show_goto_time_dialog {
+            return;
+        }
+
+        let mut open = true;
+        let mut target: Option<f64> = None;
+        Window::new(t("goto_time"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                let parsed = parse_time(&ctx.goto_time_input);
+
+                ui.horizontal(|ui| {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut ctx.goto_time_input)
+                            .hint_text("mm:ss")
+                            .desired_width(100.0),
+                    );
+                    if ui.memory(|mem| mem.focused().is_none()) {
+                        response.request_focus();
+                    }
+                    let submitted =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                    if ui
+                        .add_enabled(parsed.is_some(), egui::Button::new(t("goto")))
+                        .clicked()
+                        || (submitted && parsed.is_some())
+                    {
+                        target = parsed;
+                    }
+                });
+
+                if parsed.is_none() && !ctx.goto_time_input.trim().is_empty() {
+                    ui.colored_label(ui.visuals().error_fg_color, t("goto_time_invalid"));
+                }
+            });
+
+        if let Some(seconds) = target {
+            ctx.seek_to_seconds(seconds);
+            ctx.goto_time_input.clear();
+            open = false;
+        }
+
+        if !open {
+            ctx.show_goto_time_dialog = false;
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use super::AppComponent;
+use crate::app::history::{self, HistoryEntry, HistoryRange};
+use crate::app::App;
+use crate::app::{t, tf, tp};
+use eframe::egui::{self, Window};
+
+pub struct HistoryWindow;
+
+impl AppComponent for HistoryWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.show_history_dialog {
+            return;
+        }
+
+        let mut open = true;
+        let mut entry_to_play: Option<HistoryEntry> = None;
+        let mut session_to_add: Option<Vec<HistoryEntry>> = None;
+        let mut clear = false;
+        let mut export = false;
+
+        Window::new(t("history"))
+            .collapsible(false)
+            .default_size([420.0, 360.0])
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    let previous_range = ctx.history_range;
+                    egui::ComboBox::from_id_salt("history_range")
+                        .selected_text(range_label(ctx.history_range))
+                        .show_ui(ui, |ui| {
+                            for range in [
+                                HistoryRange::Today,
+                                HistoryRange::Week,
+                                HistoryRange::Month,
+                                HistoryRange::All,
+                            ] {
+                                ui.selectable_value(
+                                    &mut ctx.history_range,
+                                    range,
+                                    range_label(range),
+                                );
+                            }
+                        });
+
+                    if ctx.history_range != previous_range {
+                        ctx.reload_history();
+                    }
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button(t("clear_history")).clicked() {
+                            clear = true;
+                        }
+                    });
+                });
+
+                ui.collapsing(t("export_history"), |ui| {
+                    ui.horizontal(|ui| {
+                        for (label, date) in [
+                            ("history_export_from", &mut ctx.history_export_from),
+                            ("history_export_to", &mut ctx.history_export_to),
+                        ] {
+                            ui.label(t(label));
+                            ui.add(
+                                egui::TextEdit::singleline(date)
+                                    .hint_text("YYYY-MM-DD")
+                                    .desired_width(80.0),
+                            )
+                            .on_hover_text(t("history_export_hint"));
+                        }
+                        if ui.button(t("export_history_button")).clicked() {
+                            export = true;
+                        }
+                    });
+                    if let Some(status) = &ctx.history_export_status {
+                        ui.label(egui::RichText::new(status).small());
+                    }
+                });
+
+                ui.separator();
+
+                if ctx.history_entries.is_empty() {
+                    ui.label(t("history_empty"));
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (idx, session) in history::sessions(&ctx.history_entries)
+                        .into_iter()
+                        .enumerate()
+                    {
+                        ui.push_id(idx, |ui| {
+                            ui.horizontal(|ui| {
+                                let started = session
+                                    .last()
+                                    .map(|entry| entry.played_at_local.as_str())
+                                    .unwrap_or_default();
+                                ui.strong(started);
+                                if ui.small_button(t("add_session_to_playlist")).clicked() {
+                                    session_to_add = Some(session.to_vec());
+                                }
+                            });
+
+                            for entry in session {
+                                ui.horizontal(|ui| {
+                                    if ui
+                                        .small_button("▶")
+                                        .on_hover_text(t("play_again"))
+                                        .clicked()
+                                    {
+                                        entry_to_play = Some(entry.clone());
+                                    }
+
+                                    let time =
+                                        entry.played_at_local.split(' ').nth(1).unwrap_or_default();
+                                    ui.label(time);
+                                    ui.label(format!(
+                                        "{} - {}",
+                                        entry.artist.clone().unwrap_or_else(|| t("unknown_artist")),
+                                        entry.title.clone().unwrap_or_else(|| t("unknown_title"))
+                                    ));
+                                });
+                            }
+                            ui.add_space(5.0);
+                        });
+                    }
+                });
+            });
+
+        if let Some(entry) = entry_to_play {
+            ctx.play_history_entry(&entry);
+        }
+
+        if let Some(session) = session_to_add {
+            ctx.add_history_session_to_playlist(&session);
+        }
+
+        if clear {
+            ctx.clear_history();
+        }
+
+        if export {
+            if let Some(target) = rfd::FileDialog::new()
+                .add_filter("CSV", &["csv"])
+                .add_filter("JSON", &["json"])
+                .set_file_name("listening-history.csv")
+                .save_file()
+            {
+                let exported =
+                    ctx.export_history(&target, &ctx.history_export_from, &ctx.history_export_to);
+                ctx.history_export_status = Some(match exported {
+                    Ok(plays) => tp("history_exported", plays as u64, &[&plays.to_string()]),
+                    Err(e) => tf("history_export_failed", &[&e]),
+                });
+            }
+        }
+
+        if !open {
+            ctx.show_history_dialog = false;
+        }
+    }
+}
+
+fn range_label(range: HistoryRange) -> String {
+    match range {
+        HistoryRange::Today => t("history_today"),
+        HistoryRange::Week => t("history_week"),
+        HistoryRange::Month => t("history_month"),
+        HistoryRange::All => t("history_all"),
+    }
+}
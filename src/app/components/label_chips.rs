@@ -0,0 +1,104 @@
+use crate::app::labels::{Label, LabelId, Labels};
+use crate::app::library::TrackKey;
+use crate::app::{t, App};
+use eframe::egui::{self, RichText};
+
+/// What was picked in a labels menu.
+pub enum LabelAction {
+    Toggle(LabelId),
+    // A new label to put on right away
+    Create(String),
+}
+
+impl LabelAction {
+    /// Applies the pick to tracks. A new label is put on them all.
+    pub fn apply_to_tracks(self, ctx: &mut App, keys: &[TrackKey]) {
+        let id = match self {
+            LabelAction::Toggle(id) => id,
+            LabelAction::Create(name) => match ctx.create_label(&name) {
+                Some(id) if !keys.iter().all(|key| ctx.labels.track_has(*key, id)) => id,
+                _ => return,
+            },
+        };
+        ctx.toggle_track_label(keys, id);
+    }
+
+    /// Applies the pick to the playlist at `idx`. A new label is put on it.
+    pub fn apply_to_playlist(self, ctx: &mut App, idx: usize) {
+        let playlist_id = ctx.playlists.get(idx).and_then(|p| p.id);
+        let id = match self {
+            LabelAction::Toggle(id) => id,
+            LabelAction::Create(name) => match ctx.create_label(&name) {
+                Some(id) if !ctx.labels.playlist_has(playlist_id, id) => id,
+                _ => return,
+            },
+        };
+        ctx.toggle_playlist_label(idx, id);
+    }
+}
+
+/// A label as a small colored chip.
+pub fn chip(ui: &mut egui::Ui, label: &Label) -> egui::Response {
+    let fill = label.color32();
+    // Dark text on light colors, light text on dark ones
+    let [r, g, b] = label.color;
+    let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    let text = if luma > 150.0 {
+        egui::Color32::BLACK
+    } else {
+        egui::Color32::WHITE
+    };
+
+    egui::Frame::new()
+        .fill(fill)
+        .corner_radius(6.0)
+        .inner_margin(egui::Margin::symmetric(4, 0))
+        .show(ui, |ui| {
+            ui.label(RichText::new(&label.name).small().color(text));
+        })
+        .response
+}
+
+/// A submenu with a checkbox per label and a field for a new one. `has` tells which
+/// labels are on what the menu is for.
+pub fn labels_menu(
+    ui: &mut egui::Ui,
+    labels: &Labels,
+    has: impl Fn(LabelId) -> bool,
+) -> Option<LabelAction> {
+    let mut action = None;
+    ui.menu_button(t("labels"), |ui| {
+        for label in labels.all() {
+            let mut checked = has(label.id);
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut checked, "").clicked() {
+                    action = Some(LabelAction::Toggle(label.id));
+                    ui.close_menu();
+                }
+                chip(ui, label);
+            });
+        }
+        if !labels.all().is_empty() {
+            ui.separator();
+        }
+
+        let name_id = ui.id().with("new_label");
+        let mut name = ui
+            .memory_mut(|mem| mem.data.get_temp::<String>(name_id))
+            .unwrap_or_default();
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut name)
+                .desired_width(120.0)
+                .hint_text(t("new_label")),
+        );
+        if response.lost_focus()
+            && ui.input(|i| i.key_pressed(egui::Key::Enter))
+            && !name.trim().is_empty()
+        {
+            action = Some(LabelAction::Create(std::mem::take(&mut name)));
+            ui.close_menu();
+        }
+        ui.memory_mut(|mem| mem.data.insert_temp(name_id, name));
+    });
+    action
+}
@@ -0,0 +1,83 @@
+use super::AppComponent;
+use crate::app::album_stats;
+use crate::app::library::format_date;
+use crate::app::App;
+use crate::app::{t, tf, tp};
+use eframe::egui::{self, Window};
+
+pub struct LeastListenedWindow;
+
+impl AppComponent for LeastListenedWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.show_least_listened {
+            return;
+        }
+
+        let mut open = true;
+        let mut album_to_play = None;
+
+        Window::new(t("least_listened_albums"))
+            .collapsible(false)
+            .default_size([380.0, 320.0])
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                let albums = album_stats::least_listened(&ctx.album_stats);
+                if albums.is_empty() {
+                    ui.label(t("least_listened_empty"));
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("least_listened_grid")
+                        .num_columns(5)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for ((artist, album), stats) in albums {
+                                if ui
+                                    .small_button("▶")
+                                    .on_hover_text(t("play_album"))
+                                    .clicked()
+                                {
+                                    album_to_play = Some((artist.clone(), album.clone()));
+                                }
+                                let name = if artist.is_empty() {
+                                    album.clone()
+                                } else {
+                                    format!("{} - {}", album, artist)
+                                };
+                                if stats.is_complete() {
+                                    ui.label(format!("{} ✔", name))
+                                        .on_hover_text(t("album_complete"));
+                                } else {
+                                    ui.label(name);
+                                }
+                                ui.label(tf(
+                                    "album_tracks_played",
+                                    &[&stats.played_tracks.to_string(), &stats.tracks.to_string()],
+                                ));
+                                ui.label(tp(
+                                    "album_plays",
+                                    stats.plays as u64,
+                                    &[&stats.plays.to_string()],
+                                ));
+                                ui.weak(stats.last_played.map_or_else(
+                                    || t("never_played"),
+                                    |played_at| tf("album_last_played", &[&format_date(played_at)]),
+                                ));
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+
+        if let Some(album) = album_to_play {
+            ctx.play_album(&album);
+        }
+
+        if !open {
+            ctx.show_least_listened = false;
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use eframe::egui::{self, vec2, Color32, Rect, Sense};
+
+use super::AppComponent;
+use crate::app::accessibility::AccessibleExt;
+use crate::app::{t, App};
+use crate::meter::OUTPUT_LEVELS;
+
+pub struct LevelMeter;
+
+// The meters cover the range from here up to full scale
+const FLOOR_DB: f32 = -60.0;
+
+// How fast the bars fall back after a loud passage
+const FALL_DB_PER_SECOND: f32 = 30.0;
+
+const BAR_SIZE: egui::Vec2 = vec2(100.0, 4.0);
+const BAR_GAP: f32 = 2.0;
+
+// What the meters showed last frame, as positions between 0 and 1
+#[derive(Clone, Copy, Default)]
+struct Shown {
+    rms: [f32; 2],
+    peak: [f32; 2],
+    time: f64,
+}
+
+impl AppComponent for LevelMeter {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        let levels = OUTPUT_LEVELS.take();
+        let playing = ctx.is_playing();
+
+        let shown_id = ui.id().with("level_meter");
+        let previous = ui
+            .memory_mut(|mem| mem.data.get_temp::<Shown>(shown_id))
+            .unwrap_or_default();
+        let time = ui.input(|input| input.time);
+        let fallen = FALL_DB_PER_SECOND * (time - previous.time).max(0.0) as f32 / -FLOOR_DB;
+
+        let mut shown = Shown {
+            time,
+            ..Default::default()
+        };
+        // Paused there's nothing left to fall, the window isn't redrawn anyway
+        if playing {
+            for side in 0..2 {
+                shown.rms[side] = position(levels.rms[side]).max(previous.rms[side] - fallen);
+                shown.peak[side] = position(levels.peak[side]).max(previous.peak[side] - fallen);
+            }
+        }
+        ui.memory_mut(|mem| mem.data.insert_temp(shown_id, shown));
+
+        let clip_size = BAR_SIZE.y;
+        let (rect, response) = ui.allocate_exact_size(
+            vec2(BAR_SIZE.x + BAR_GAP + clip_size, BAR_SIZE.y * 2.0 + BAR_GAP),
+            Sense::click(),
+        );
+
+        let visuals = ui.visuals();
+        let painter = ui.painter();
+        for side in 0..2 {
+            let top = rect.top() + side as f32 * (BAR_SIZE.y + BAR_GAP);
+            let bar = Rect::from_min_size(egui::pos2(rect.left(), top), BAR_SIZE);
+            painter.rect_filled(bar, 1.0, visuals.extreme_bg_color);
+
+            let mut filled = bar;
+            filled.set_width(bar.width() * shown.rms[side]);
+            painter.rect_filled(filled, 1.0, visuals.selection.bg_fill);
+
+            if shown.peak[side] > 0.0 {
+                let x = bar.left() + bar.width() * shown.peak[side];
+                painter.line_segment(
+                    [egui::pos2(x, bar.top()), egui::pos2(x, bar.bottom())],
+                    (1.0, visuals.strong_text_color()),
+                );
+            }
+
+            let clip = Rect::from_min_size(
+                egui::pos2(bar.right() + BAR_GAP, top),
+                vec2(clip_size, clip_size),
+            );
+            let clip_color = if levels.clipped[side] {
+                Color32::RED
+            } else {
+                visuals.extreme_bg_color
+            };
+            painter.rect_filled(clip, 1.0, clip_color);
+        }
+
+        let response = response
+            .on_hover_text(t("level_meter_hint"))
+            .accessible_name(&t("output_level"));
+        if response.clicked() {
+            OUTPUT_LEVELS.reset_clipped();
+        }
+    }
+}
+
+// Where a linear level sits on the meter
+fn position(level: f32) -> f32 {
+    if level <= 0.0 {
+        return 0.0;
+    }
+    ((20.0 * level.log10() - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
@@ -1,7 +1,10 @@
+use super::playlist_table::radio_menu;
 use super::AppComponent;
-use crate::app::t;
-use crate::app::{App, LibraryItem, LibraryPathId};
-use eframe::egui::{CollapsingHeader, Label, RichText, Sense, TextWrapMode};
+use crate::app::album_stats;
+use crate::app::radio::RadioKind;
+use crate::app::{t, tf, tp};
+use crate::app::{App, LibraryItem, LibraryPathId, LibrarySort};
+use eframe::egui::{self, CollapsingHeader, Label, RichText, Sense, TextWrapMode, Window};
 use std::collections::HashMap;
 
 pub struct LibraryComponent;
@@ -12,6 +15,8 @@ impl AppComponent for LibraryComponent {
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
         // Keep track of paths to remove (if any)
         let mut path_to_remove: Option<LibraryPathId> = None;
+        let mut folder_to_play: Option<LibraryPathId> = None;
+        let mut radio_to_play: Option<(LibraryItem, RadioKind)> = None;
 
         eframe::egui::ScrollArea::both().show(ui, |ui| {
             ui.horizontal(|ui| {
@@ -46,6 +51,19 @@ impl AppComponent for LibraryComponent {
                         ui.close_menu();
                     }
 
+                    ui.menu_button(t("sort_by"), |ui| {
+                        for (sort, label) in [
+                            (LibrarySort::Title, t("column_title")),
+                            (LibrarySort::Added, t("column_added")),
+                            (LibrarySort::Modified, t("column_modified")),
+                        ] {
+                            if ui.radio_value(&mut ctx.library_sort, sort, label).clicked() {
+                                ctx.save_state();
+                                ui.close_menu();
+                            }
+                        }
+                    });
+
                     if ui.button(t("resync_all")).clicked() {
                         // Get all paths that need to be reimported
                         let paths_to_resync: Vec<_> = ctx
@@ -104,11 +122,31 @@ impl AppComponent for LibraryComponent {
             // Add some vertical spacing
             ui.add_space(5.0);
 
+            if ctx.library_loading {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.weak(t("loading_library"));
+                });
+            }
+
+            if let Some(removed) = ctx.removed_folders.last() {
+                let name = removed.name().to_string();
+                ui.horizontal(|ui| {
+                    ui.weak(tf("library_folder_removed", &[&name]));
+                    if ui.small_button(t("undo")).clicked() {
+                        ctx.undo_remove_library_path();
+                    }
+                });
+            }
+
             // Group library items by their library_id (which corresponds to folder paths)
             let mut folder_items: HashMap<LibraryPathId, Vec<&LibraryItem>> = HashMap::new();
 
             // Collect all library items and group them by path id
             for item in ctx.library.items() {
+                if ctx.clean_filter && item.explicit() {
+                    continue;
+                }
                 folder_items
                     .entry(item.library_id())
                     .or_default()
@@ -119,11 +157,23 @@ impl AppComponent for LibraryComponent {
             for lib_path in ctx.library.paths() {
                 if lib_path.status() == crate::app::library::LibraryPathStatus::Imported {
                     let path_id = lib_path.id();
-                    let folder_name = lib_path.display_name();
+                    let (complete, albums) = folder_items.get(&path_id).map_or((0, 0), |items| {
+                        album_stats::completed_albums(items.iter().copied(), &ctx.album_stats)
+                    });
+                    let folder_name = if ctx.library.is_path_online(path_id) {
+                        if albums > 0 && complete == albums {
+                            RichText::new(format!("{} ✔", lib_path.display_name())).strong()
+                        } else {
+                            RichText::new(lib_path.display_name()).strong()
+                        }
+                    } else {
+                        RichText::new(format!("{} ({})", lib_path.display_name(), t("offline")))
+                            .weak()
+                    };
 
                     // Create a header with default behavior that allows individual control
                     // but is also affected by the global expand/collapse actions
-                    let header = CollapsingHeader::new(RichText::new(folder_name).strong())
+                    let header = CollapsingHeader::new(folder_name)
                         .default_open(ctx.library_folders_expanded); // Use the global setting after memory clear
 
                     // Show the header and get its response
@@ -132,11 +182,7 @@ impl AppComponent for LibraryComponent {
                         if let Some(items) = folder_items.get(&path_id) {
                             // Create a sorted copy for display
                             let mut sorted_items = items.clone();
-                            sorted_items.sort_by(|a, b| {
-                                a.title()
-                                    .unwrap_or_default()
-                                    .cmp(&b.title().unwrap_or_default())
-                            });
+                            ctx.library_sort.sort(&mut sorted_items);
 
                             for item in sorted_items {
                                 // Format display with title and artist if available
@@ -151,12 +197,21 @@ impl AppComponent for LibraryComponent {
                                     (None, None) => t("unknown_track"),
                                 };
 
-                                // Create a clickable label for each track
-                                let item_label = ui.add(
-                                    Label::new(RichText::new(display_text))
-                                        .sense(Sense::click())
-                                        .wrap_mode(TextWrapMode::Truncate),
-                                );
+                                // Create a clickable label for each track, behind its cover
+                                let item_label = ui
+                                    .horizontal(|ui| {
+                                        let cover = item
+                                            .pictures()
+                                            .first()
+                                            .map(|picture| picture.file_path.as_path());
+                                        ctx.thumbnails.show(ui, cover);
+                                        ui.add(
+                                            Label::new(RichText::new(display_text))
+                                                .sense(Sense::click())
+                                                .wrap_mode(TextWrapMode::Truncate),
+                                        )
+                                    })
+                                    .inner;
                                 if item_label.hovered() {
                                     ui.ctx()
                                         .set_cursor_icon(eframe::egui::CursorIcon::PointingHand);
@@ -187,14 +242,28 @@ impl AppComponent for LibraryComponent {
                                             ui.close_menu();
                                         }
                                     }
+
+                                    if let Some(kind) = radio_menu(ui, item) {
+                                        radio_to_play = Some(((*item).clone(), kind));
+                                    }
                                 });
                             }
                         }
                     });
 
+                    let header_response = section.header_response.on_hover_text(tf(
+                        "folder_albums_hint",
+                        &[&complete.to_string(), &albums.to_string()],
+                    ));
+
                     // Add context menu to the header response
-                    section.header_response.context_menu(|ui| {
+                    header_response.context_menu(|ui| {
                         // Add context menu for the folder header
+                        if ui.button(t("play_folder_as_album")).clicked() {
+                            folder_to_play = Some(path_id);
+                            ui.close_menu();
+                        }
+
                         if ui.button(t("add_all_to_playlist")).clicked() {
                             if let Some(current_playlist_idx) = &ctx.current_playlist_idx {
                                 let current_playlist = &mut ctx.playlists[*current_playlist_idx];
@@ -221,9 +290,72 @@ impl AppComponent for LibraryComponent {
             }
         });
 
-        // Process any path removal after rendering the UI
+        if let Some(path_id) = folder_to_play {
+            ctx.play_folder_as_album(path_id);
+        }
+
+        if let Some((seed, kind)) = radio_to_play {
+            ctx.play_radio(&seed, kind);
+        }
+
+        // Removing a folder is confirmed first
         if let Some(path_id) = path_to_remove {
-            ctx.library.remove_path(path_id);
+            ctx.confirm_remove_path = Some(path_id);
         }
+        confirm_removal(ctx, ui);
+    }
+}
+
+fn confirm_removal(ctx: &mut App, ui: &mut egui::Ui) {
+    let Some(path_id) = ctx.confirm_remove_path else {
+        return;
+    };
+    let Some(name) = ctx
+        .library
+        .paths()
+        .iter()
+        .find(|p| p.id() == path_id)
+        .map(|p| p.display_name().to_string())
+    else {
+        ctx.confirm_remove_path = None;
+        return;
+    };
+    let tracks = ctx
+        .library
+        .items()
+        .iter()
+        .filter(|item| item.library_id() == path_id)
+        .count();
+
+    let mut open = true;
+    let mut confirmed = false;
+    Window::new(t("remove_from_library"))
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ui.ctx(), |ui| {
+            ui.label(tp(
+                "remove_folder_confirm",
+                tracks as u64,
+                &[&name, &tracks.to_string()],
+            ));
+            ui.weak(t("remove_folder_undo_hint"));
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                if ui.button(t("remove")).clicked() {
+                    confirmed = true;
+                }
+                if ui.button(t("cancel")).clicked() {
+                    ctx.confirm_remove_path = None;
+                }
+            });
+        });
+
+    if confirmed {
+        ctx.remove_library_path(path_id);
+        open = false;
+    }
+    if !open {
+        ctx.confirm_remove_path = None;
     }
 }
@@ -0,0 +1,279 @@
+use super::cassette_component::paint_album_art;
+use super::favorite_heart;
+use super::AppComponent;
+use crate::app::accessibility::AccessibleExt;
+use crate::app::history::HistoryEntry;
+use crate::app::library::LibraryItem;
+use crate::app::player::TrackState;
+use crate::app::App;
+use crate::app::{t, tf};
+use eframe::egui::{self, vec2, Align2, FontId, Sense, WidgetInfo, WidgetType};
+
+pub struct MiniPlayer;
+
+const ART_SIZE: f32 = 80.0;
+
+// Points per second a title that doesn't fit scrolls by, and the gap between repeats
+const MARQUEE_SPEED: f32 = 30.0;
+const MARQUEE_GAP: f32 = 40.0;
+
+impl AppComponent for MiniPlayer {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        let animate = ctx.animations_running();
+        let next_track = ctx
+            .queue
+            .as_ref()
+            .or_else(|| {
+                ctx.playing_playlist_idx
+                    .and_then(|idx| ctx.playlists.get(idx))
+            })
+            .zip(ctx.player.as_ref())
+            .and_then(|(source, player)| player.peek_next(source))
+            .cloned();
+        let Some(player) = &mut ctx.player else {
+            return;
+        };
+        let selected_track = player.selected_track.clone();
+        let is_playing = matches!(player.track_state, TrackState::Playing);
+        let has_selected_track = selected_track.is_some();
+
+        let mut skip_previous = false;
+        let mut skip_next = false;
+        let mut toggle_on_top = false;
+        let mut toggle_favorite = false;
+        let mut toggle_recents = false;
+        let mut play_recent = None;
+        let mut leave = false;
+
+        ui.horizontal(|ui| {
+            // The artwork doubles as the handle to move the borderless window
+            let (art_rect, art_response) =
+                ui.allocate_exact_size(vec2(ART_SIZE, ART_SIZE), Sense::click_and_drag());
+            let has_art = selected_track
+                .as_ref()
+                .and_then(|track| track.pictures().first())
+                .is_some_and(|picture| paint_album_art(ui, &picture.file_path, art_rect));
+            if !has_art {
+                ui.painter()
+                    .rect_filled(art_rect, 4.0, ui.visuals().extreme_bg_color);
+                ui.painter().text(
+                    art_rect.center(),
+                    Align2::CENTER_CENTER,
+                    "♪",
+                    FontId::proportional(32.0),
+                    ui.visuals().weak_text_color(),
+                );
+            }
+            if art_response.drag_started_by(egui::PointerButton::Primary) {
+                ui.ctx().send_viewport_cmd(egui::ViewportCommand::StartDrag);
+            }
+            if art_response.double_clicked() {
+                leave = true;
+            }
+
+            ui.vertical(|ui| {
+                match &selected_track {
+                    Some(track) => {
+                        let title = track.title().unwrap_or_else(|| t("unknown_title"));
+                        let artist = track.artist().unwrap_or_else(|| t("unknown_artist"));
+                        marquee(ui, &format!("{} - {}", artist, title), animate)
+                            .announce(&tf("now_playing", &[&title, &artist]));
+                    }
+                    None => {
+                        marquee(ui, &t("no_track"), animate);
+                    }
+                }
+
+                // Seek strip
+                let duration = player.duration;
+                let mut current_seek = player.seek_to_timestamp;
+                ui.style_mut().spacing.slider_width = ui.available_width();
+                ui.style_mut().visuals.slider_trailing_fill = true;
+                let seek_strip = ui
+                    .add_enabled(
+                        has_selected_track,
+                        egui::Slider::new(&mut current_seek, 0..=duration)
+                            .show_value(false)
+                            .clamping(egui::SliderClamping::Always)
+                            .handle_shape(egui::style::HandleShape::Rect { aspect_ratio: 0.3 }),
+                    )
+                    .accessible_name(&t("position"));
+                if seek_strip.dragged() {
+                    player.set_seek_to_timestamp(current_seek);
+                }
+                if seek_strip.drag_stopped() {
+                    player.seek_to(current_seek);
+                    player.track_state = TrackState::Playing;
+                }
+
+                ui.horizontal(|ui| {
+                    let button_size = vec2(28.0, 22.0);
+                    skip_previous = ui
+                        .add_enabled(
+                            has_selected_track,
+                            egui::Button::new("|◀").min_size(button_size),
+                        )
+                        .accessible_name(&t("previous"))
+                        .clicked();
+
+                    let play_pause = ui
+                        .add_enabled(
+                            has_selected_track,
+                            egui::Button::new(if is_playing { "⏸" } else { "▶" })
+                                .min_size(button_size),
+                        )
+                        .accessible_name(&t(if is_playing { "pause" } else { "play" }));
+                    if play_pause.clicked() {
+                        if is_playing {
+                            player.pause();
+                        } else {
+                            player.play();
+                        }
+                    }
+
+                    skip_next = ui
+                        .add_enabled(
+                            has_selected_track,
+                            egui::Button::new("▶|").min_size(button_size),
+                        )
+                        .accessible_name(&t("next"))
+                        .clicked();
+
+                    if let Some(track) = &selected_track {
+                        let favorite = ctx.favorites.contains(track.key());
+                        toggle_favorite = favorite_heart::heart(ui, favorite).clicked();
+                    }
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        leave |= ui
+                            .add(egui::Button::new("⛶").min_size(button_size))
+                            .on_hover_text(t("full_player"))
+                            .accessible_name(&t("full_player"))
+                            .clicked();
+
+                        toggle_on_top = ui
+                            .add(
+                                egui::Button::new("📌")
+                                    .selected(ctx.mini_always_on_top)
+                                    .min_size(button_size),
+                            )
+                            .on_hover_text(t("always_on_top"))
+                            .accessible_name(&t("always_on_top"))
+                            .clicked();
+
+                        toggle_recents = ui
+                            .add(
+                                egui::Button::new("🕘")
+                                    .selected(ctx.mini_recents_open)
+                                    .min_size(button_size),
+                            )
+                            .on_hover_text(t("recently_played"))
+                            .accessible_name(&t("recently_played"))
+                            .clicked();
+                    });
+                });
+            });
+        });
+
+        if ctx.mini_recents_open {
+            ui.separator();
+            play_recent = recents(ui, ctx, next_track.as_ref(), &mut skip_next);
+        }
+
+        if skip_previous {
+            ctx.previous_track();
+        }
+        if skip_next {
+            ctx.skip_track();
+        }
+        if toggle_on_top {
+            ctx.toggle_always_on_top(ui.ctx());
+        }
+        if let (true, Some(track)) = (toggle_favorite, &selected_track) {
+            ctx.toggle_favorite(track.key());
+        }
+        if toggle_recents {
+            ctx.toggle_mini_recents(ui.ctx());
+        }
+        if let Some(entry) = play_recent {
+            ctx.play_history_entry(&entry);
+        }
+        if leave {
+            ctx.leave_mini_player(ui.ctx());
+        }
+    }
+}
+
+// The last plays and the track up next, one click to play them. Returns the play
+// clicked, the next track is skipped to, which its play icon and hover text tell.
+fn recents(
+    ui: &mut egui::Ui,
+    ctx: &App,
+    next_track: Option<&LibraryItem>,
+    skip_next: &mut bool,
+) -> Option<HistoryEntry> {
+    let describe = |title: Option<String>, artist: Option<String>| {
+        format!(
+            "{} - {}",
+            artist.unwrap_or_else(|| t("unknown_artist")),
+            title.unwrap_or_else(|| t("unknown_title"))
+        )
+    };
+    let mut clicked = None;
+
+    ui.strong(t("recently_played"));
+    if ctx.recent_plays.is_empty() {
+        ui.weak(t("history_empty"));
+    }
+    for entry in &ctx.recent_plays {
+        let text = describe(entry.title.clone(), entry.artist.clone());
+        if ui
+            .add(egui::Button::new(text).frame(false).truncate())
+            .on_hover_text(&entry.played_at_local)
+            .clicked()
+        {
+            clicked = Some(entry.clone());
+        }
+    }
+
+    ui.add_space(4.0);
+    ui.strong(t("status_next"));
+    match next_track {
+        Some(track) => {
+            let text = format!("▶ {}", describe(track.title(), track.artist()));
+            *skip_next |= ui
+                .add(egui::Button::new(text).frame(false).truncate())
+                .on_hover_text(t("skip_to_next"))
+                .clicked();
+        }
+        None => {
+            ui.weak("–");
+        }
+    }
+    clicked
+}
+
+// Shows the text on one line, scrolling it like a car radio display when it's too long.
+// Stands still while paused or with animations off.
+fn marquee(ui: &mut egui::Ui, text: &str, animate: bool) -> egui::Response {
+    let font = egui::TextStyle::Body.resolve(ui.style());
+    let color = ui.visuals().strong_text_color();
+    let galley = ui.painter().layout_no_wrap(text.to_string(), font, color);
+    let (rect, response) =
+        ui.allocate_exact_size(vec2(ui.available_width(), galley.size().y), Sense::hover());
+    response.widget_info(|| WidgetInfo::labeled(WidgetType::Label, true, text));
+
+    let painter = ui.painter_at(rect);
+    if galley.size().x <= rect.width() || !animate {
+        painter.galley(rect.left_top(), galley, color);
+        return response;
+    }
+
+    let cycle = galley.size().x + MARQUEE_GAP;
+    let offset = (ui.input(|input| input.time) as f32 * MARQUEE_SPEED) % cycle;
+    painter.galley(rect.left_top() - vec2(offset, 0.0), galley.clone(), color);
+    painter.galley(rect.left_top() + vec2(cycle - offset, 0.0), galley, color);
+    response
+}
@@ -1,10 +1,27 @@
+pub mod bookmarks_menu;
 pub mod cassette_component;
+pub mod chapter_panel;
+pub mod export_window;
+pub mod favorite_heart;
 pub mod footer;
+pub mod goto_time_window;
+pub mod history_window;
+pub mod label_chips;
 pub mod language_selector;
+pub mod least_listened_window;
+pub mod level_meter;
 pub mod library_component;
+pub mod mini_player;
+pub mod now_playing_view;
+pub mod onboarding_window;
 pub mod player_component;
 pub mod playlist_table;
 pub mod playlist_tabs;
+pub mod properties_window;
+pub mod report_window;
+pub mod settings_window;
+pub mod tag_cleanup_window;
+pub mod tag_replace_window;
 pub mod window_chrome;
 
 pub trait AppComponent {
@@ -0,0 +1,155 @@
+use super::cassette_component::paint_album_art;
+use super::AppComponent;
+use crate::app::seek::format_time;
+use crate::app::t;
+use crate::app::App;
+use eframe::egui::{self, vec2, Align, Align2, FontId, Layout, RichText, Sense};
+
+pub struct NowPlayingView;
+
+// Lines of lyrics shown around the current one
+const LYRICS_CONTEXT: usize = 4;
+
+impl AppComponent for NowPlayingView {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if ui.input(|input| input.key_pressed(egui::Key::Escape)) {
+            ctx.leave_now_playing(ui.ctx());
+            return;
+        }
+
+        let Some(player) = &ctx.player else {
+            return;
+        };
+        let track = player.selected_track.clone();
+        let position = player
+            .ts_to_seconds(player.seek_to_timestamp)
+            .unwrap_or(0.0);
+        let duration = player.ts_to_seconds(player.duration).unwrap_or(0.0);
+
+        let full = ui.available_rect_before_wrap();
+        let art_size = (full.height() * 0.7).min(full.width() * 0.45).max(64.0);
+
+        // Double-clicking anywhere goes back, like Esc
+        let background = ui.interact(full, ui.id().with("now_playing"), Sense::click());
+        if background.double_clicked() {
+            ctx.leave_now_playing(ui.ctx());
+        }
+
+        ui.add_space((full.height() - art_size) / 3.0);
+        ui.horizontal(|ui| {
+            ui.add_space(full.width() * 0.05);
+
+            let (art_rect, _) = ui.allocate_exact_size(vec2(art_size, art_size), Sense::hover());
+            let has_art = track
+                .as_ref()
+                .and_then(|track| track.pictures().first())
+                .is_some_and(|picture| paint_album_art(ui, &picture.file_path, art_rect));
+            if !has_art {
+                ui.painter()
+                    .rect_filled(art_rect, 8.0, ui.visuals().extreme_bg_color);
+                ui.painter().text(
+                    art_rect.center(),
+                    Align2::CENTER_CENTER,
+                    "♪",
+                    FontId::proportional(art_size / 3.0),
+                    ui.visuals().weak_text_color(),
+                );
+            }
+
+            ui.add_space(full.width() * 0.04);
+
+            ui.allocate_ui_with_layout(
+                vec2(ui.available_width() - full.width() * 0.05, art_size),
+                Layout::top_down(Align::LEFT),
+                |ui| {
+                    let Some(track) = &track else {
+                        ui.label(RichText::new(t("no_track")).size(32.0).strong());
+                        return;
+                    };
+
+                    ui.label(
+                        RichText::new(track.title().unwrap_or_else(|| t("unknown_title")))
+                            .size(36.0)
+                            .strong(),
+                    );
+                    ui.label(
+                        RichText::new(track.artist().unwrap_or_else(|| t("unknown_artist")))
+                            .size(24.0),
+                    );
+                    if let Some(album) = track.album() {
+                        ui.label(RichText::new(album).size(18.0).weak());
+                    }
+
+                    ui.add_space(24.0);
+                    show_lyrics(ctx, ui, position);
+                },
+            );
+        });
+
+        // Progress along the bottom
+        ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
+            ui.add_space(12.0);
+            ui.label(RichText::new(t("now_playing_exit_hint")).small().weak());
+            ui.horizontal(|ui| {
+                ui.add_space(full.width() * 0.05);
+                ui.label(format_time(position as u64));
+                let progress = if duration > 0.0 {
+                    (position / duration) as f32
+                } else {
+                    0.0
+                };
+                ui.add(
+                    egui::ProgressBar::new(progress)
+                        .desired_width(full.width() * 0.9 - 120.0)
+                        .desired_height(6.0),
+                );
+                ui.label(format_time(duration as u64));
+            });
+        });
+    }
+}
+
+fn show_lyrics(ctx: &mut App, ui: &mut egui::Ui, position: f64) {
+    let Some(lyrics) = ctx.current_lyrics() else {
+        ui.label(RichText::new(t("no_lyrics")).weak());
+        return;
+    };
+
+    let lines = lyrics.lines();
+    let Some(current) = lyrics.current_line(position) else {
+        if lyrics.is_synced() {
+            // Before the first line, show what's coming
+            for line in lines.iter().take(LYRICS_CONTEXT) {
+                ui.label(RichText::new(&line.text).size(18.0).weak());
+            }
+        } else {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for line in lines {
+                    ui.label(RichText::new(&line.text).size(18.0));
+                }
+            });
+        }
+        return;
+    };
+
+    let first = current.saturating_sub(LYRICS_CONTEXT / 2);
+    for (idx, line) in lines
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(LYRICS_CONTEXT + 1)
+    {
+        let text = RichText::new(&line.text);
+        if idx == current {
+            ui.label(
+                text.size(24.0)
+                    .strong()
+                    .color(ui.visuals().selection.bg_fill),
+            );
+        } else {
+            ui.label(text.size(18.0).weak());
+        }
+    }
+}
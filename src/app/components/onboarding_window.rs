@@ -0,0 +1,122 @@
+use super::AppComponent;
+use crate::app::onboarding::OnboardingStep;
+use crate::app::{t, tf, tp, App, Language};
+use eframe::egui::{self, Window};
+
+pub struct OnboardingWindow;
+
+impl AppComponent for OnboardingWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        let Some(step) = ctx.onboarding.as_ref().map(|onboarding| onboarding.step) else {
+            return;
+        };
+
+        let mut open = true;
+        let mut finish = false;
+        Window::new(t("onboarding_title"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ui.set_min_width(360.0);
+                match step {
+                    OnboardingStep::Language => language_step(ctx, ui),
+                    OnboardingStep::Folders => folders_step(ctx, ui),
+                    OnboardingStep::Import => import_step(ctx, ui),
+                }
+
+                ui.add_space(10.0);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if step != OnboardingStep::Language && ui.button(t("onboarding_back")).clicked()
+                    {
+                        if let Some(onboarding) = &mut ctx.onboarding {
+                            onboarding.step = step.previous();
+                        }
+                    }
+                    if step == OnboardingStep::Import {
+                        if ui.button(t("onboarding_finish")).clicked() {
+                            finish = true;
+                        }
+                    } else if ui.button(t("onboarding_next")).clicked() {
+                        if let Some(onboarding) = &mut ctx.onboarding {
+                            onboarding.step = step.next();
+                        }
+                    }
+                });
+            });
+
+        if finish {
+            ctx.finish_onboarding();
+        } else if !open {
+            ctx.skip_onboarding();
+        }
+    }
+}
+
+fn language_step(ctx: &mut App, ui: &mut egui::Ui) {
+    ui.heading(t("onboarding_welcome"));
+    ui.label(t("onboarding_language"));
+    ui.add_space(5.0);
+    for lang in Language::all() {
+        if ui.radio(ctx.get_language() == lang, lang.name()).clicked() {
+            ctx.set_language(lang);
+        }
+    }
+}
+
+fn folders_step(ctx: &mut App, ui: &mut egui::Ui) {
+    let Some(onboarding) = &mut ctx.onboarding else {
+        return;
+    };
+
+    ui.label(t("onboarding_folders"));
+    ui.add_space(5.0);
+    let mut removed = None;
+    for (idx, folder) in onboarding.folders.iter().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.small_button("✖").on_hover_text(t("remove")).clicked() {
+                removed = Some(idx);
+            }
+            ui.label(folder.display().to_string());
+        });
+    }
+    if let Some(idx) = removed {
+        onboarding.folders.remove(idx);
+    }
+
+    if ui.button(t("add_music_folder")).clicked() {
+        if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+            onboarding.add_folder(folder);
+        }
+    }
+    if onboarding.folders.is_empty() {
+        ui.weak(tf("onboarding_folders_later", &[&t("music_files")]));
+    }
+}
+
+fn import_step(ctx: &mut App, ui: &mut egui::Ui) {
+    let Some(onboarding) = &mut ctx.onboarding else {
+        return;
+    };
+
+    if onboarding.folders.is_empty() {
+        ui.label(tf("onboarding_no_folders", &[&t("music_files")]));
+        return;
+    }
+
+    let count = onboarding.folders.len();
+    ui.label(tp(
+        "onboarding_import_summary",
+        count as u64,
+        &[&count.to_string()],
+    ));
+    ui.add_space(5.0);
+    ui.checkbox(&mut onboarding.import_now, t("onboarding_import_now"));
+    if !onboarding.import_now {
+        ui.weak(tf("onboarding_import_later", &[&t("settings")]));
+    }
+}
@@ -1,21 +1,29 @@
 use eframe::egui::{self, vec2};
-use std::time::Instant;
 
+use super::bookmarks_menu::BookmarksMenu;
 use super::cassette_component::CassetteComponent;
+use super::favorite_heart;
 use super::AppComponent;
+use crate::app::accessibility::AccessibleExt;
+use crate::app::audio_format::format_khz;
+use crate::app::player::ts_to_seconds;
+use crate::app::seek::format_time;
 use crate::app::style::{ButtonExt, SliderExt};
-use crate::app::t;
+use crate::app::volume_scale;
+use crate::app::{t, tf};
+use crate::app::{App, DEFAULT_WINDOW_HEIGHT};
+use crate::buffer_stats::OUTPUT_BUFFER;
 use crate::egui::style::HandleShape;
-use crate::{app::App, UiCommand};
 
 pub struct PlayerComponent;
 
 const CASSETTE_WIDTH: f32 = 280.0;
 
-// For periodic state saving
-thread_local! {
-    static LAST_SAVE: std::cell::RefCell<Instant> = std::cell::RefCell::new(Instant::now());
-}
+// Seconds between the seeks issued while scrubbing the time slider
+const SCRUB_INTERVAL: f64 = 0.15;
+
+// Room for the volume percentage next to its slider
+const VOLUME_LABEL_WIDTH: f32 = 36.0;
 
 impl AppComponent for PlayerComponent {
     type Context = App;
@@ -39,60 +47,14 @@ impl AppComponent for PlayerComponent {
             let duration = player.duration;
             let volume = player.volume;
 
-            // Process UI commands
-            if let Ok(new_seek_cmd) = player.ui_rx.try_recv() {
-                match new_seek_cmd {
-                    UiCommand::CurrentTimestamp(seek_timestamp) => {
-                        // Save player state every 30 seconds during playback
-                        LAST_SAVE.with(|last_save| {
-                            let elapsed = last_save.borrow().elapsed().as_secs();
-                            if elapsed > 30 {
-                                // Update persistence state
-                                ctx.update_player_persistence();
-                                ctx.save_state();
-                                // Reset timer
-                                *last_save.borrow_mut() = Instant::now();
-                            }
-                        });
-
-                        if let Some(player) = &mut ctx.player {
-                            player.set_seek_to_timestamp(seek_timestamp);
-                        }
-                    }
-                    UiCommand::TotalTrackDuration(dur) => {
-                        tracing::info!("Received Duration: {}", dur);
-                        if let Some(player) = &mut ctx.player {
-                            player.set_duration(dur);
-                        }
-                    }
-                    UiCommand::AudioFinished => {
-                        tracing::info!("Track finished, getting next...");
-                        if let Some(current_playlist_idx) = ctx.current_playlist_idx {
-                            if let Some(player) = &mut ctx.player {
-                                player.next(&ctx.playlists[current_playlist_idx]);
-                            }
-                        }
-                    }
-                    UiCommand::PlaybackStateChanged(is_playing) => {
-                        tracing::info!(
-                            "Playback state changed to: {}",
-                            if is_playing { "Playing" } else { "Paused" }
-                        );
-                        if let Some(player) = &mut ctx.player {
-                            if is_playing {
-                                player.track_state = crate::app::player::TrackState::Playing;
-                            } else {
-                                player.track_state = crate::app::player::TrackState::Paused;
-                            }
-                        }
-                    }
-                }
-            }
-
             // Get current playlist name using map_or for cleaner code
             let current_playlist_name = ctx
-                .playing_playlist_idx
-                .and_then(|idx| ctx.playlists.get(idx))
+                .queue
+                .as_ref()
+                .or_else(|| {
+                    ctx.playing_playlist_idx
+                        .and_then(|idx| ctx.playlists.get(idx))
+                })
                 .and_then(|playlist| playlist.get_name())
                 .unwrap_or_default();
 
@@ -128,6 +90,7 @@ impl AppComponent for PlayerComponent {
         }
 
         let has_selected_track = selected_track.is_some();
+        let muted = ctx.player.as_ref().is_some_and(|player| player.muted);
 
         // Get playlist tracks info for the current playlist
         let current_playlist_idx = ctx.current_playlist_idx;
@@ -157,23 +120,86 @@ impl AppComponent for PlayerComponent {
 
                     // Show track info if selected, otherwise show default message
                     if let Some(track) = &selected_track {
-                        ui.add(
-                            eframe::egui::Label::new(format!(
-                                "{}{}",
-                                t("song"),
-                                track.title().unwrap_or("unknown title".to_string())
-                            ))
-                            .wrap_mode(eframe::egui::TextWrapMode::Truncate),
-                        )
-                        .highlight();
+                        let title = track.title().unwrap_or("unknown title".to_string());
+                        let artist = track.artist().unwrap_or("unknown artist".to_string());
 
-                        ui.label(format!(
-                            "{}{}",
-                            t("artist"),
-                            track.artist().unwrap_or("unknown artist".to_string())
-                        ));
+                        ui.horizontal(|ui| {
+                            let favorite = ctx.favorites.contains(track.key());
+                            if favorite_heart::heart(ui, favorite).clicked() {
+                                ctx.toggle_favorite(track.key());
+                            }
+
+                            // Screen readers read out the new track when it changes
+                            ui.add(
+                                eframe::egui::Label::new(format!("{}{}", t("song"), title))
+                                    .wrap_mode(eframe::egui::TextWrapMode::Truncate),
+                            )
+                            .highlight()
+                            .announce(&tf("now_playing", &[&title, &artist]));
+                        });
+
+                        ui.label(format!("{}{}", t("artist"), artist));
 
                         ui.label(format!("{}{}", t("playlist"), current_playlist_name));
+
+                        // Prefer what the audio thread probed, tracks imported before
+                        // formats were recorded have none stored
+                        let format = ctx
+                            .player
+                            .as_ref()
+                            .and_then(|player| player.track_format.clone())
+                            .unwrap_or_else(|| track.format().clone());
+                        let summary = format.summary();
+                        if !summary.is_empty() {
+                            let kind = if format.is_lossless() {
+                                t("lossless")
+                            } else {
+                                t("lossy")
+                            };
+                            ui.label(
+                                egui::RichText::new(format!("{} · {}", summary, kind))
+                                    .small()
+                                    .weak(),
+                            );
+                        }
+
+                        // Whether the stream reaches the device at its own sample rate
+                        if let Some(info) = ctx
+                            .player
+                            .as_ref()
+                            .and_then(|player| player.output_info.as_ref())
+                        {
+                            let source_rate = format_khz(info.source_rate);
+                            let device_rate = format_khz(info.device_rate);
+                            let (status, color) = if info.is_resampled() {
+                                (
+                                    tf("output_resampled", &[&source_rate, &device_rate]),
+                                    ui.visuals().warn_fg_color,
+                                )
+                            } else {
+                                (
+                                    tf("output_native_rate", &[&device_rate]),
+                                    ui.visuals().weak_text_color(),
+                                )
+                            };
+                            let mut details =
+                                tf("output_device", &[&info.device_name, &info.sample_format]);
+                            let buffer = OUTPUT_BUFFER.report();
+                            if buffer.len > 0 {
+                                details.push('\n');
+                                details.push_str(&tf(
+                                    "output_buffer",
+                                    &[
+                                        &buffer.fill_percent().to_string(),
+                                        &buffer.len.to_string(),
+                                        &buffer.underruns.to_string(),
+                                        &buffer.overflows.to_string(),
+                                    ],
+                                ));
+                            }
+                            ui.label(egui::RichText::new(status).small().color(color))
+                                .on_hover_text(details);
+                        }
                     } else {
                         // Default display when no track is selected
                         ui.add(
@@ -190,43 +216,144 @@ impl AppComponent for PlayerComponent {
                         }
                     }
 
+                    // Opening can take a moment on a network share
+                    if ctx.player.as_ref().is_some_and(|player| player.loading) {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(egui::RichText::new(t("loading_track")).small().weak())
+                                .announce(&t("loading_track"));
+                        });
+                    }
+
+                    // The last track that couldn't be played, kept until dismissed
+                    if let Some(message) = ctx
+                        .player
+                        .as_ref()
+                        .and_then(|player| player.playback_error.clone())
+                    {
+                        let label = egui::Label::new(
+                            egui::RichText::new(tf("playback_error", &[&message]))
+                                .small()
+                                .color(ui.visuals().error_fg_color),
+                        )
+                        .wrap_mode(egui::TextWrapMode::Truncate)
+                        .sense(egui::Sense::click());
+                        if ui
+                            .add(label)
+                            .announce(&tf("playback_error", &[&message]))
+                            .on_hover_text(format!("{}\n{}", message, t("dismiss_hint")))
+                            .clicked()
+                        {
+                            if let Some(player) = &mut ctx.player {
+                                player.playback_error = None;
+                            }
+                        }
+                    }
+
                     // Add space to push controls to bottom
                     ui.add_space(ui.available_height() - 70.0);
 
                     // Time Slider
                     ui.horizontal(|ui| {
-                        let format_time = |timestamp: u64| -> String {
-                            let seconds = timestamp / 1000;
-                            let minutes = seconds / 60;
-                            let hours = minutes / 60;
-                            let _seconds_remainder = seconds % 60;
-                            let minutes_remainder = minutes % 60;
-
-                            format!("{:02}:{:02}", hours, minutes_remainder)
+                        // Timestamps are in the time base of the track
+                        let time_base = ctx.player.as_ref().and_then(|player| player.time_base);
+                        let format_ts = |timestamp: u64| -> String {
+                            let seconds = ts_to_seconds(time_base, timestamp).unwrap_or(0.0);
+                            format_time(seconds as u64)
                         };
 
                         let mut current_seek = seek_to_timestamp;
 
-                        ui.style_mut().spacing.slider_width = ui.available_width() - 100.0;
+                        ctx.refresh_track_markers();
+
+                        ui.style_mut().spacing.slider_width = ui.available_width() - 130.0;
                         ui.style_mut().visuals.slider_trailing_fill = true;
-                        let time_slider = ui.add(
-                            eframe::egui::Slider::new(&mut current_seek, 0..=duration)
-                                .logarithmic(false)
-                                .show_value(false)
-                                .clamping(eframe::egui::SliderClamping::Always)
-                                .trailing_fill(true)
-                                .handle_shape(HandleShape::Rect { aspect_ratio: 0.5 }),
-                        );
+                        let time_slider = ui
+                            .add(
+                                eframe::egui::Slider::new(&mut current_seek, 0..=duration)
+                                    .logarithmic(false)
+                                    .show_value(false)
+                                    .clamping(eframe::egui::SliderClamping::Always)
+                                    .trailing_fill(true)
+                                    .handle_shape(HandleShape::Rect { aspect_ratio: 0.5 }),
+                            )
+                            .accessible_name(&t("position"))
+                            .accessible_value(&tf(
+                                "position_of",
+                                &[&format_ts(current_seek), &format_ts(duration)],
+                            ));
+
+                        // Draw bookmark and chapter markers above the slider
+                        if let (Some(player), true) = (&ctx.player, duration > 0) {
+                            let rect = time_slider.rect;
+                            let color = ui.style().visuals.selection.bg_fill;
+                            for ms in ctx.track_markers.positions_ms() {
+                                if let Some(ts) = player.ms_to_ts(ms) {
+                                    let fraction = (ts as f32 / duration as f32).min(1.0);
+                                    let x = rect.left() + rect.width() * fraction;
+                                    ui.painter().line_segment(
+                                        [
+                                            egui::pos2(x, rect.top()),
+                                            egui::pos2(x, rect.top() + 4.0),
+                                        ],
+                                        egui::Stroke::new(2.0, color),
+                                    );
+                                }
+                            }
+                        }
+
+                        // Preview the time being dragged to, or the time under the pointer
+                        if has_selected_track && duration > 0 {
+                            let preview = if time_slider.dragged() {
+                                Some(current_seek)
+                            } else {
+                                time_slider.hover_pos().map(|pos| {
+                                    let rect = time_slider.rect;
+                                    let fraction =
+                                        ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+                                    (duration as f64 * fraction as f64) as u64
+                                })
+                            };
+                            if let Some(preview) = preview {
+                                egui::show_tooltip_at_pointer(
+                                    ui.ctx(),
+                                    ui.layer_id(),
+                                    time_slider.id.with("seek_preview"),
+                                    |ui| ui.label(format_ts(preview)),
+                                );
+                            }
+                        }
 
                         // Update in real-time while dragging (just the timestamp, not seeking the audio)
                         if time_slider.dragged() && has_selected_track {
+                            let scrub_id = time_slider.id.with("last_scrub");
+                            let now = ui.input(|i| i.time);
+                            let last_scrub =
+                                ui.memory_mut(|mem| mem.data.get_temp::<(f64, u64)>(scrub_id));
+
                             if let Some(player) = &mut ctx.player {
                                 player.set_seek_to_timestamp(current_seek);
+
+                                // Scrubbing seeks the audio too, throttled as every seek reopens the file
+                                let due = last_scrub.is_none_or(|(time, ts)| {
+                                    now - time >= SCRUB_INTERVAL && ts != current_seek
+                                });
+                                if ctx.scrub_while_dragging && due {
+                                    player.seek_to(current_seek);
+                                    player.track_state = crate::app::player::TrackState::Playing;
+                                    ui.memory_mut(|mem| {
+                                        mem.data.insert_temp(scrub_id, (now, current_seek))
+                                    });
+                                }
                             }
                         }
 
                         // Only perform the actual seek when drag is stopped
                         if time_slider.drag_stopped() && has_selected_track {
+                            ui.memory_mut(|mem| {
+                                mem.data
+                                    .remove::<(f64, u64)>(time_slider.id.with("last_scrub"))
+                            });
                             if let Some(player) = &mut ctx.player {
                                 // We already updated seek_to_timestamp during dragging,
                                 // now actually seek the audio playback
@@ -238,9 +365,24 @@ impl AppComponent for PlayerComponent {
                             }
                         }
 
-                        ui.label(format_time(current_seek));
+                        // Click the position to switch between elapsed and remaining time
+                        let position = if ctx.show_remaining_time {
+                            format!("-{}", format_ts(duration.saturating_sub(current_seek)))
+                        } else {
+                            format_ts(current_seek)
+                        };
+                        if ui
+                            .add(egui::Label::new(position).sense(egui::Sense::click()))
+                            .on_hover_text(t("toggle_remaining_time"))
+                            .clicked()
+                        {
+                            ctx.show_remaining_time = !ctx.show_remaining_time;
+                            ctx.save_state();
+                        }
                         ui.label("/");
-                        ui.label(format_time(duration));
+                        ui.label(format_ts(duration));
+
+                        BookmarksMenu::add(ctx, ui);
                     });
 
                     ui.add_space(10.0); // Add margin at the bottom
@@ -248,21 +390,22 @@ impl AppComponent for PlayerComponent {
                     // Play/Pause, Previous, Next, Mode buttons
                     ui.horizontal(|ui| {
                         // Create buttons but disable them if no track is selected
-                        let prev_btn = ui.add_enabled(
-                            has_selected_track,
-                            egui::Button::new("|◀").player_style(),
-                        );
+                        let prev_btn = ui
+                            .add_enabled(has_selected_track, egui::Button::new("|◀").player_style())
+                            .accessible_name(&t("previous"));
 
                         // Merge play/pause into a single button
-                        let play_pause_btn = ui.add_enabled(
-                            has_selected_track,
-                            egui::Button::new(if is_playing { "⏸" } else { "▶" }).player_style(),
-                        );
-
-                        let next_btn = ui.add_enabled(
-                            has_selected_track,
-                            egui::Button::new("▶|").player_style(),
-                        );
+                        let play_pause_btn = ui
+                            .add_enabled(
+                                has_selected_track,
+                                egui::Button::new(if is_playing { "⏸" } else { "▶" })
+                                    .player_style(),
+                            )
+                            .accessible_name(&t(if is_playing { "pause" } else { "play" }));
+
+                        let next_btn = ui
+                            .add_enabled(has_selected_track, egui::Button::new("▶|").player_style())
+                            .accessible_name(&t("next"));
 
                         let mode_icon = match playback_mode {
                             crate::app::player::PlaybackMode::Normal => "➡",
@@ -271,10 +414,19 @@ impl AppComponent for PlayerComponent {
                             crate::app::player::PlaybackMode::Shuffle => "🔀",
                         };
 
-                        let mode_btn = ui.add_enabled(
-                            has_selected_track,
-                            egui::Button::new(mode_icon).player_style(),
-                        );
+                        let mode_name = t(match playback_mode {
+                            crate::app::player::PlaybackMode::Normal => "mode_normal",
+                            crate::app::player::PlaybackMode::Repeat => "mode_repeat",
+                            crate::app::player::PlaybackMode::RepeatOne => "mode_repeat_one",
+                            crate::app::player::PlaybackMode::Shuffle => "mode_shuffle",
+                        });
+
+                        let mode_btn = ui
+                            .add_enabled(
+                                has_selected_track,
+                                egui::Button::new(mode_icon).player_style(),
+                            )
+                            .accessible_name(&tf("play_mode", &[&mode_name]));
 
                         ui.vertical(|ui| {
                             // small buttons
@@ -283,10 +435,13 @@ impl AppComponent for PlayerComponent {
                                 ui.add_enabled_ui(false, |ui| ui.button("1.0x"));
 
                                 if ui.button(t("playlist_btn")).clicked() {
+                                    // Keep the size to come back to when showing them again
+                                    ctx.remember_window_geometry(ui.ctx());
                                     ctx.show_library_and_playlist = !ctx.show_library_and_playlist;
                                     // Adjust window height based on visibility
                                     let new_height = if ctx.show_library_and_playlist {
-                                        ctx.default_window_height as f32
+                                        ctx.full_geometry
+                                            .map_or(DEFAULT_WINDOW_HEIGHT, |full| full.size[1])
                                     } else {
                                         200.0 // Compact height when library and playlist are hidden
                                     };
@@ -295,19 +450,12 @@ impl AppComponent for PlayerComponent {
                                     ));
                                 };
 
-                                ui.add_enabled_ui(false, |ui| ui.button(t("lyrics")));
+                                if ui.button(t("lyrics")).clicked() {
+                                    ctx.enter_now_playing(ui.ctx());
+                                }
 
                                 if ui.button(t("mini")).clicked() {
-                                    // Hide library and playlist
-                                    ctx.show_library_and_playlist = false;
-
-                                    // Set minimal window size
-                                    ui.ctx().send_viewport_cmd(egui::ViewportCommand::InnerSize(
-                                        vec2(
-                                            300.0, // Minimal width
-                                            200.0, // Same compact height as 列表 button
-                                        ),
-                                    ));
+                                    ctx.enter_mini_player(ui.ctx());
                                 };
 
                                 // Only enable the remove button if there's a selected track
@@ -376,31 +524,61 @@ impl AppComponent for PlayerComponent {
 
                             // volume slider
                             ui.horizontal(|ui| {
-                                let mut current_volume = volume;
-                                let previous_vol = current_volume;
-                                ui.label("📢");
-                                ui.style_mut().spacing.slider_width = ui.available_width();
-                                let volume_slider = ui.add(
-                                    eframe::egui::Slider::new(
-                                        &mut current_volume,
-                                        0.0_f32..=1.0_f32,
+                                // The slider moves in decibels, the player keeps the gain
+                                let mut position = volume_scale::gain_to_slider(volume);
+                                let previous_position = position;
+
+                                let (icon, mute_hint) = if muted || volume <= 0.0 {
+                                    ("🔇", t("unmute"))
+                                } else {
+                                    ("📢", t("mute"))
+                                };
+                                let mute_btn = ui
+                                    .add(egui::Button::new(icon).frame(false))
+                                    .on_hover_text(&mute_hint)
+                                    .accessible_name(&mute_hint);
+                                if mute_btn.clicked() {
+                                    if let Some(player) = &mut ctx.player {
+                                        player.set_muted(!muted);
+                                    }
+                                }
+
+                                let percent = if muted {
+                                    t("muted")
+                                } else {
+                                    format!("{}%", (position * 100.0).round())
+                                };
+                                ui.style_mut().spacing.slider_width =
+                                    (ui.available_width() - VOLUME_LABEL_WIDTH).max(0.0);
+                                let volume_slider = ui
+                                    .add(
+                                        eframe::egui::Slider::new(&mut position, 0.0_f32..=1.0_f32)
+                                            .volume_style(),
                                     )
-                                    .volume_style(),
+                                    .accessible_name(&t("volume"))
+                                    .accessible_value(&percent);
+                                ui.add_sized(
+                                    [VOLUME_LABEL_WIDTH, ui.available_height()],
+                                    egui::Label::new(egui::RichText::new(&percent).small()),
                                 );
 
-                                if volume_slider.dragged() {
-                                    if let Some(is_processing_ui_change) =
-                                        &ctx.is_processing_ui_change
+                                // Nudge the volume with the mouse wheel while over the slider
+                                if volume_slider.hovered() {
+                                    let scroll = ui.input(|input| input.raw_scroll_delta.y);
+                                    if scroll != 0.0 {
+                                        position = volume_scale::scrolled(position, scroll);
+                                    }
+                                }
+
+                                // Only send if the volume is actually changing
+                                if position != previous_position {
+                                    if let (Some(is_processing_ui_change), Some(player)) =
+                                        (&ctx.is_processing_ui_change, &mut ctx.player)
                                     {
-                                        // Only send if the volume is actually changing
-                                        if current_volume != previous_vol {
-                                            if let Some(player) = &mut ctx.player {
-                                                player.set_volume(
-                                                    current_volume,
-                                                    is_processing_ui_change,
-                                                );
-                                            }
-                                        }
+                                        player.set_volume(
+                                            volume_scale::slider_to_gain(position),
+                                            is_processing_ui_change,
+                                        );
                                     }
                                 }
 
@@ -418,20 +596,14 @@ impl AppComponent for PlayerComponent {
                                                 player.play();
                                             }
                                         }
+                                    }
 
-                                        if prev_btn.clicked() && ctx.playing_playlist_idx.is_some()
-                                        {
-                                            player.previous(
-                                                &ctx.playlists[ctx.playing_playlist_idx.unwrap()],
-                                            );
-                                        }
+                                    if prev_btn.clicked() {
+                                        ctx.previous_track();
+                                    }
 
-                                        if next_btn.clicked() && ctx.playing_playlist_idx.is_some()
-                                        {
-                                            player.next(
-                                                &ctx.playlists[ctx.playing_playlist_idx.unwrap()],
-                                            );
-                                        }
+                                    if next_btn.clicked() {
+                                        ctx.skip_track();
                                     }
                                 }
                             });
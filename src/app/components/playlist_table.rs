@@ -1,11 +1,26 @@
+use super::favorite_heart;
+use super::label_chips::{self, LabelAction};
 use super::AppComponent;
-use crate::app::t;
+use crate::app::accessibility::AccessibleExt;
+use crate::app::library::{LibraryItem, TrackKey};
+use crate::app::player::TrackState;
+use crate::app::playlist::{matches_filters, PlaylistColumn};
+use crate::app::preamp::{MAX_PREAMP_DB, MIN_PREAMP_DB};
+use crate::app::radio::RadioKind;
+use crate::app::tag_replace::ReplaceScope;
 use crate::app::App;
+use crate::app::{t, tf};
 use eframe::egui;
-use std::sync::atomic::{AtomicUsize, Ordering};
-
-// Static variable to track the last played track
-static LAST_PLAYED_TRACK: AtomicUsize = AtomicUsize::new(0);
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+// Seconds after the last keystroke at which the type-ahead prefix starts over
+const TYPE_AHEAD_TIMEOUT: f64 = 1.0;
+// Seconds between checks for files of the playlist that went missing
+const MISSING_CHECK_INTERVAL: f64 = 5.0;
+// Frames of the now playing icon, one shown after the other while playing
+const PLAYING_FRAMES: [&str; 3] = ["🔈", "🔉", "🔊"];
+const PLAYING_FRAME_TIME: Duration = Duration::from_millis(400);
 
 pub struct PlaylistTable;
 
@@ -14,6 +29,14 @@ impl AppComponent for PlaylistTable {
 
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
         if let Some(current_playlist_idx) = ctx.current_playlist_idx {
+            if ctx.playlists[current_playlist_idx].loading {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.weak(t("loading_playlist"));
+                });
+                return;
+            }
+
             // Generate a base ID for the current playlist
             let base_id = ui.id().with(format!("playlist_{}", current_playlist_idx));
 
@@ -21,15 +44,36 @@ impl AppComponent for PlaylistTable {
             let drag_id = base_id.with("drag_source");
             let drop_id = base_id.with("drop_target");
             let is_dragging_id = base_id.with("is_dragging");
+            let filters_id = base_id.with("column_filters");
 
             // Track which item to remove (if any)
             let mut track_to_remove: Option<usize> = None;
 
+            // Track which item playback should stop after (if any)
+            let mut stop_after_idx: Option<usize> = None;
+
+            // Track whose pre-amp changed, with the new value and whether it's final
+            let mut preamp_change: Option<(usize, f32, bool)> = None;
+
+            // Track a radio was asked for from, and which radio
+            let mut radio_request: Option<(usize, RadioKind)> = None;
+            let mut properties_request: Option<usize> = None;
+            let mut replace_request: Option<usize> = None;
+            let mut cleanup_request: Option<usize> = None;
+            let mut label_request: Option<(usize, LabelAction)> = None;
+            let mut favorite_request: Option<usize> = None;
+
             // Track which field is being edited with unique IDs
             let edit_field_id = base_id.with("edit_field_id");
             let edit_track_idx_id = base_id.with("edit_track_idx_id");
             let edit_value_id = base_id.with("edit_value_id");
 
+            // In read-only mode edits only reach the database
+            let read_only_library = ctx.read_only_library;
+
+            // Virtual playlists follow the library, their tracks can't be removed or moved
+            let is_virtual_playlist = ctx.playlists[current_playlist_idx].is_virtual();
+
             // Get editing state from memory
             let editing_field = ui
                 .memory_mut(|mem| mem.data.get_temp::<Option<String>>(edit_field_id))
@@ -59,9 +103,14 @@ impl AppComponent for PlaylistTable {
             } else {
                 None
             };
+            let current_track_key =
+                current_track_idx.map(|idx| ctx.playlists[current_playlist_idx].tracks[idx].key());
 
-            // Get the last played track index
-            let last_played_track = LAST_PLAYED_TRACK.load(Ordering::Relaxed);
+            // Key of the playing track this playlist last scrolled to
+            let followed_id = base_id.with("followed_track");
+            let followed_track = ui
+                .memory_mut(|mem| mem.data.get_temp::<Option<TrackKey>>(followed_id))
+                .flatten();
 
             // Get the scroll area ID for tracking scroll position
             let _scroll_area_id = base_id.with("scroll_area");
@@ -102,6 +151,59 @@ impl AppComponent for PlaylistTable {
             // Track indices to toggle selection
             let mut toggle_selection: Option<usize> = None;
 
+            // Column the playlist was last sorted by and whether ascending
+            let sorted_by = ctx.playlists[current_playlist_idx].view.sort;
+            let mut sort_request: Option<(PlaylistColumn, bool)> = None;
+
+            // Per-column filters, in the order of `PlaylistColumn::ALL`
+            let mut filters = ui
+                .memory_mut(|mem| mem.data.get_temp::<Vec<String>>(filters_id))
+                .unwrap_or_default();
+            filters.resize(PlaylistColumn::ALL.len(), String::new());
+
+            // Columns shown in the table, the format columns are optional
+            let columns: Vec<PlaylistColumn> = PlaylistColumn::ALL
+                .into_iter()
+                .filter(|column| ctx.show_format_columns || !column.is_format())
+                .collect();
+
+            // A hidden column must not keep filtering rows
+            for column in PlaylistColumn::ALL {
+                if !columns.contains(&column) {
+                    filters[column.index()].clear();
+                }
+            }
+
+            // What the status column shows, worked out once for all rows
+            let playing_key = ctx
+                .player
+                .as_ref()
+                .and_then(|player| player.selected_track.as_ref())
+                .map(|track| track.key());
+            let paused = ctx
+                .player
+                .as_ref()
+                .is_some_and(|player| !matches!(player.track_state, TrackState::Playing));
+            let next_key = ctx
+                .queue
+                .as_ref()
+                .or_else(|| {
+                    ctx.playing_playlist_idx
+                        .and_then(|idx| ctx.playlists.get(idx))
+                })
+                .zip(ctx.player.as_ref())
+                .and_then(|(source, player)| player.peek_next(source))
+                .map(|track| track.key());
+            let playing_frame = if ctx.battery_saver || paused || playing_key.is_none() {
+                0
+            } else {
+                ui.ctx().request_repaint_after(PLAYING_FRAME_TIME);
+                let frame = ui.input(|i| i.time) / PLAYING_FRAME_TIME.as_secs_f64();
+                frame as usize % PLAYING_FRAMES.len()
+            };
+            let missing =
+                missing_files(ctx, ui, base_id.with("missing_files"), current_playlist_idx);
+
             // Get available width for the table
             let available_width = ui.available_width();
 
@@ -112,9 +214,18 @@ impl AppComponent for PlaylistTable {
                     // Set the width to use all available space
                     ui.set_min_width(available_width);
 
-                    // Define column proportions (sum should be 1.0)
-                    let column_proportions = [0.05, 0.35, 0.20, 0.25, 0.15];
-                    let num_columns = 5; // Changed from 6 to 5 (we don't need empty columns)
+                    // Define column proportions (sum should be 1.0), the number, status
+                    // and favorite columns first and the rest scaled to fit
+                    let total_width: f32 = columns.iter().map(|column| column.width()).sum();
+                    let column_proportions: Vec<f32> = [0.05, 0.05, 0.03]
+                        .into_iter()
+                        .chain(
+                            columns
+                                .iter()
+                                .map(|column| column.width() / total_width * 0.87),
+                        )
+                        .collect();
+                    let num_columns = column_proportions.len();
 
                     // Use a single Grid for all rows (including header) to ensure alignment
                     egui::Grid::new("playlist_full")
@@ -130,38 +241,76 @@ impl AppComponent for PlaylistTable {
                                 ui.strong(t("column_number"));
                             });
 
-                            // Title column
+                            // Status column
                             ui.scope(|ui| {
                                 let col_width = available_width * column_proportions[1];
                                 ui.set_min_width(col_width);
-                                ui.strong(t("column_title"));
+                                ui.strong(t("column_status"));
                             });
 
-                            // Artist column
+                            // Favorite column
                             ui.scope(|ui| {
                                 let col_width = available_width * column_proportions[2];
                                 ui.set_min_width(col_width);
-                                ui.strong(t("column_artist"));
+                                ui.strong("♥").on_hover_text(t("favorites"));
                             });
 
-                            // Album column
-                            ui.scope(|ui| {
-                                let col_width = available_width * column_proportions[3];
-                                ui.set_min_width(col_width);
-                                ui.strong(t("column_album"));
-                            });
+                            for (position, column) in columns.iter().enumerate() {
+                                ui.scope(|ui| {
+                                    let col_width =
+                                        available_width * column_proportions[position + 3];
+                                    ui.set_min_width(col_width);
+                                    sort_request = sort_request.or(sort_header(
+                                        ui,
+                                        t(column.label_key()),
+                                        *column,
+                                        sorted_by,
+                                    ));
+                                });
+                            }
+
+                            ui.end_row();
 
-                            // Genre column
+                            // Filter row, narrows the visible rows without reordering the playlist
                             ui.scope(|ui| {
-                                let col_width = available_width * column_proportions[4];
+                                let col_width = available_width * column_proportions[0];
                                 ui.set_min_width(col_width);
-                                ui.strong(t("column_genre"));
+                                let is_filtered = filters.iter().any(|filter| !filter.is_empty());
+                                if ui
+                                    .add_enabled(is_filtered, egui::Button::new("✖").small())
+                                    .on_hover_text(t("clear_filters"))
+                                    .clicked()
+                                {
+                                    filters.iter_mut().for_each(String::clear);
+                                }
                             });
+                            ui.label("");
+                            ui.label("");
+
+                            for (position, column) in columns.iter().enumerate() {
+                                ui.scope(|ui| {
+                                    let col_width =
+                                        available_width * column_proportions[position + 3];
+                                    ui.set_min_width(col_width);
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut filters[column.index()])
+                                            .desired_width(col_width)
+                                            .hint_text(t("filter")),
+                                    );
+                                });
+                            }
 
                             ui.end_row();
 
                             // Playlist items
                             for idx in 0..playlist_len {
+                                let track = &ctx.playlists[current_playlist_idx].tracks[idx];
+                                if !matches_filters(track, &filters)
+                                    || (ctx.clean_filter && track.explicit())
+                                {
+                                    continue;
+                                }
+
                                 // Generate a unique ID for this row
                                 let row_id = base_id.with(format!("row_{}", idx));
 
@@ -210,28 +359,88 @@ impl AppComponent for PlaylistTable {
                                 let track_genre =
                                     track.genre().unwrap_or_else(|| t("unknown_genre"));
 
+                                // What screen readers read for the row
+                                let is_current =
+                                    ctx.player.as_ref().and_then(|p| p.selected_track.as_ref())
+                                        == Some(track);
+                                let row_name = tf(
+                                    if is_current {
+                                        "track_row_playing"
+                                    } else {
+                                        "track_row"
+                                    },
+                                    &[&(idx + 1).to_string(), &track_title, &track_artist],
+                                );
+
                                 // First column - Drag handle + playing indicator
-                                let drag_handle_text = (idx + 1).to_string();
-                                let mut drag_handle_text =
+                                let stops_after = ctx
+                                    .player
+                                    .as_ref()
+                                    .is_some_and(|p| p.stop_after_track == Some(track.key()));
+                                let drag_handle_text = if stops_after {
+                                    format!("{} ⏹", idx + 1)
+                                } else {
+                                    (idx + 1).to_string()
+                                };
+                                let drag_handle_text =
                                     egui::RichText::new(drag_handle_text).strong();
-                                let mut title_text = egui::RichText::new(track_title.clone());
-                                let mut artist_text = egui::RichText::new(track_artist.clone());
-                                let mut album_text = egui::RichText::new(track_album.clone());
-                                let mut genre_text = egui::RichText::new(track_genre.clone());
-
-                                if let Some(selected_track) =
-                                    &ctx.player.as_ref().unwrap().selected_track
-                                {
-                                    if selected_track == track {
-                                        let highlight_color = ui.style().visuals.selection.bg_fill;
-
-                                        // Highlight the row in blue when it's the currently playing track
-                                        drag_handle_text = drag_handle_text.color(highlight_color);
-                                        title_text = title_text.color(highlight_color);
-                                        artist_text = artist_text.color(highlight_color);
-                                        album_text = album_text.color(highlight_color);
-                                        genre_text = genre_text.color(highlight_color);
-                                    }
+                                let title_text = egui::RichText::new(track_title.clone());
+                                let artist_text = egui::RichText::new(track_artist.clone());
+                                let album_text = egui::RichText::new(track_album.clone());
+                                let genre_text = egui::RichText::new(track_genre.clone());
+
+                                // Dates and format details are shown as plain text
+                                let detail_texts: Vec<egui::RichText> = columns[4..]
+                                    .iter()
+                                    .map(|column| {
+                                        egui::RichText::new(column.value(track).unwrap_or_default())
+                                            .weak()
+                                    })
+                                    .collect();
+
+                                // Icons of the status column with what they mean on hover
+                                let mut status: Vec<(egui::RichText, &str)> = Vec::new();
+                                if playing_key == Some(track.key()) {
+                                    let (icon, hover) = if paused {
+                                        ("⏸", "status_paused")
+                                    } else {
+                                        (PLAYING_FRAMES[playing_frame], "status_playing")
+                                    };
+                                    let color = ui.visuals().selection.bg_fill;
+                                    status.push((egui::RichText::new(icon).color(color), hover));
+                                }
+                                if !ctx.library_loading && !ctx.library.contains(track) {
+                                    let color = ui.visuals().error_fg_color;
+                                    status.push((
+                                        egui::RichText::new("✖").color(color),
+                                        "status_removed",
+                                    ));
+                                } else if !ctx.library.is_path_online(track.library_id()) {
+                                    let color = ui.visuals().weak_text_color();
+                                    status.push((
+                                        egui::RichText::new("⚠").color(color),
+                                        "status_offline",
+                                    ));
+                                } else if missing.contains(&track.key()) {
+                                    let color = ui.visuals().warn_fg_color;
+                                    status.push((
+                                        egui::RichText::new("⚠").color(color),
+                                        "status_missing",
+                                    ));
+                                }
+                                if ctx.pending_edits.contains(&track.key()) {
+                                    status.push((egui::RichText::new("✏"), "status_unsaved"));
+                                }
+                                if next_key == Some(track.key()) {
+                                    status.push((egui::RichText::new("⏭"), "status_next"));
+                                }
+                                if track.format().codec.is_some() {
+                                    let (icon, hover) = if track.format().is_lossless() {
+                                        ("◆", "status_lossless")
+                                    } else {
+                                        ("◇", "status_lossy")
+                                    };
+                                    status.push((egui::RichText::new(icon).weak(), hover));
                                 }
 
                                 // Disable text selection on drag handle
@@ -276,11 +485,37 @@ impl AppComponent for PlaylistTable {
                                     });
                                 });
 
+                                // Status column
+                                ui.scope(|ui| {
+                                    ui.push_id(row_id.with("status_col"), |ui| {
+                                        let col_width = available_width * column_proportions[1];
+                                        ui.set_min_width(col_width);
+                                        ui.horizontal(|ui| {
+                                            ui.spacing_mut().item_spacing.x = 2.0;
+                                            for (icon, hover) in status {
+                                                ui.label(icon).on_hover_text(t(hover));
+                                            }
+                                        });
+                                    });
+                                });
+
+                                // Favorite column
+                                ui.scope(|ui| {
+                                    ui.push_id(row_id.with("favorite_col"), |ui| {
+                                        let col_width = available_width * column_proportions[2];
+                                        ui.set_min_width(col_width);
+                                        let favorite = ctx.favorites.contains(track.key());
+                                        if favorite_heart::heart(ui, favorite).clicked() {
+                                            favorite_request = Some(idx);
+                                        }
+                                    });
+                                });
+
                                 // Title column
                                 ui.scope(|ui| {
                                     // Use the row_id to create a unique widget ID for this column
                                     ui.push_id(row_id.with("title_col"), |ui| {
-                                        let col_width = available_width * column_proportions[1];
+                                        let col_width = available_width * column_proportions[3];
                                         ui.set_min_width(col_width);
 
                                         // First handle the title column - make it editable via right-click menu
@@ -294,8 +529,10 @@ impl AppComponent for PlaylistTable {
                                                     .unwrap_or_else(|| track_title.clone())
                                             });
 
-                                            let response =
-                                                ui.text_edit_singleline(&mut current_value);
+                                            let response = read_only_hint(
+                                                ui.text_edit_singleline(&mut current_value),
+                                                read_only_library,
+                                            );
 
                                             // Update the value in memory
                                             ui.memory_mut(|mem| {
@@ -331,10 +568,24 @@ impl AppComponent for PlaylistTable {
                                             }
                                         } else {
                                             // Regular title display with click-to-play functionality
-                                            let title_response = ui.add(
-                                                egui::Label::new(title_text)
-                                                    .sense(egui::Sense::click()),
-                                            );
+                                            let title_response = ui
+                                                .horizontal(|ui| {
+                                                    let cover = track
+                                                        .pictures()
+                                                        .first()
+                                                        .map(|picture| picture.file_path.as_path());
+                                                    ctx.thumbnails.show(ui, cover);
+                                                    let response = ui.add(
+                                                        egui::Label::new(title_text)
+                                                            .sense(egui::Sense::click()),
+                                                    );
+                                                    for label in ctx.labels.of_track(track.key()) {
+                                                        label_chips::chip(ui, label);
+                                                    }
+                                                    response
+                                                })
+                                                .inner
+                                                .list_item(&row_name, is_selected);
 
                                             // Show pointing hand cursor when hovering over the title (only when not dragging)
                                             if title_response.hovered() && !is_dragging {
@@ -364,10 +615,56 @@ impl AppComponent for PlaylistTable {
                                                     ui.close_menu();
                                                 }
 
-                                                if ui.button(t("remove_from_playlist")).clicked() {
+                                                if ui
+                                                    .add_enabled(
+                                                        !is_virtual_playlist,
+                                                        egui::Button::new(t(
+                                                            "remove_from_playlist",
+                                                        )),
+                                                    )
+                                                    .clicked()
+                                                {
                                                     track_to_remove = Some(idx);
                                                     ui.close_menu();
                                                 }
+
+                                                if ui.button(t("stop_after_this_track")).clicked() {
+                                                    stop_after_idx = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if let Some((preamp_db, persist)) =
+                                                    preamp_menu_item(ui, track.preamp_db())
+                                                {
+                                                    preamp_change = Some((idx, preamp_db, persist));
+                                                }
+
+                                                if let Some(kind) = radio_menu(ui, track) {
+                                                    radio_request = Some((idx, kind));
+                                                }
+
+                                                if ui.button(t("properties")).clicked() {
+                                                    properties_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if ui.button(t("tag_replace_menu")).clicked() {
+                                                    replace_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if ui.button(t("tag_cleanup_menu")).clicked() {
+                                                    cleanup_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if let Some(action) = label_chips::labels_menu(
+                                                    ui,
+                                                    &ctx.labels,
+                                                    |id| ctx.labels.track_has(track.key(), id),
+                                                ) {
+                                                    label_request = Some((idx, action));
+                                                }
                                             });
 
                                             // Check for double-click to start editing
@@ -417,7 +714,7 @@ impl AppComponent for PlaylistTable {
                                 ui.scope(|ui| {
                                     // Use the row_id to create a unique widget ID for this column
                                     ui.push_id(row_id.with("artist_col"), |ui| {
-                                        let col_width = available_width * column_proportions[2];
+                                        let col_width = available_width * column_proportions[4];
                                         ui.set_min_width(col_width);
 
                                         // Artist - make editable
@@ -431,8 +728,10 @@ impl AppComponent for PlaylistTable {
                                                     .unwrap_or_else(|| track_artist.clone())
                                             });
 
-                                            let response =
-                                                ui.text_edit_singleline(&mut current_value);
+                                            let response = read_only_hint(
+                                                ui.text_edit_singleline(&mut current_value),
+                                                read_only_library,
+                                            );
 
                                             // Update the value in memory
                                             ui.memory_mut(|mem| {
@@ -494,10 +793,56 @@ impl AppComponent for PlaylistTable {
                                                     ui.close_menu();
                                                 }
 
-                                                if ui.button(t("remove_from_playlist")).clicked() {
+                                                if ui
+                                                    .add_enabled(
+                                                        !is_virtual_playlist,
+                                                        egui::Button::new(t(
+                                                            "remove_from_playlist",
+                                                        )),
+                                                    )
+                                                    .clicked()
+                                                {
                                                     track_to_remove = Some(idx);
                                                     ui.close_menu();
                                                 }
+
+                                                if ui.button(t("stop_after_this_track")).clicked() {
+                                                    stop_after_idx = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if let Some((preamp_db, persist)) =
+                                                    preamp_menu_item(ui, track.preamp_db())
+                                                {
+                                                    preamp_change = Some((idx, preamp_db, persist));
+                                                }
+
+                                                if let Some(kind) = radio_menu(ui, track) {
+                                                    radio_request = Some((idx, kind));
+                                                }
+
+                                                if ui.button(t("properties")).clicked() {
+                                                    properties_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if ui.button(t("tag_replace_menu")).clicked() {
+                                                    replace_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if ui.button(t("tag_cleanup_menu")).clicked() {
+                                                    cleanup_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if let Some(action) = label_chips::labels_menu(
+                                                    ui,
+                                                    &ctx.labels,
+                                                    |id| ctx.labels.track_has(track.key(), id),
+                                                ) {
+                                                    label_request = Some((idx, action));
+                                                }
                                             });
 
                                             // Check for double-click to start editing
@@ -533,7 +878,7 @@ impl AppComponent for PlaylistTable {
                                 ui.scope(|ui| {
                                     // Use the row_id to create a unique widget ID for this column
                                     ui.push_id(row_id.with("album_col"), |ui| {
-                                        let col_width = available_width * column_proportions[3];
+                                        let col_width = available_width * column_proportions[5];
                                         ui.set_min_width(col_width);
 
                                         // Album - make editable
@@ -547,8 +892,10 @@ impl AppComponent for PlaylistTable {
                                                     .unwrap_or_else(|| track_album.clone())
                                             });
 
-                                            let response =
-                                                ui.text_edit_singleline(&mut current_value);
+                                            let response = read_only_hint(
+                                                ui.text_edit_singleline(&mut current_value),
+                                                read_only_library,
+                                            );
 
                                             // Update the value in memory
                                             ui.memory_mut(|mem| {
@@ -610,10 +957,56 @@ impl AppComponent for PlaylistTable {
                                                     ui.close_menu();
                                                 }
 
-                                                if ui.button(t("remove_from_playlist")).clicked() {
+                                                if ui
+                                                    .add_enabled(
+                                                        !is_virtual_playlist,
+                                                        egui::Button::new(t(
+                                                            "remove_from_playlist",
+                                                        )),
+                                                    )
+                                                    .clicked()
+                                                {
                                                     track_to_remove = Some(idx);
                                                     ui.close_menu();
                                                 }
+
+                                                if ui.button(t("stop_after_this_track")).clicked() {
+                                                    stop_after_idx = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if let Some((preamp_db, persist)) =
+                                                    preamp_menu_item(ui, track.preamp_db())
+                                                {
+                                                    preamp_change = Some((idx, preamp_db, persist));
+                                                }
+
+                                                if let Some(kind) = radio_menu(ui, track) {
+                                                    radio_request = Some((idx, kind));
+                                                }
+
+                                                if ui.button(t("properties")).clicked() {
+                                                    properties_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if ui.button(t("tag_replace_menu")).clicked() {
+                                                    replace_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if ui.button(t("tag_cleanup_menu")).clicked() {
+                                                    cleanup_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if let Some(action) = label_chips::labels_menu(
+                                                    ui,
+                                                    &ctx.labels,
+                                                    |id| ctx.labels.track_has(track.key(), id),
+                                                ) {
+                                                    label_request = Some((idx, action));
+                                                }
                                             });
 
                                             // Check for double-click to start editing
@@ -649,7 +1042,7 @@ impl AppComponent for PlaylistTable {
                                 ui.scope(|ui| {
                                     // Use the row_id to create a unique widget ID for this column
                                     ui.push_id(row_id.with("genre_col"), |ui| {
-                                        let col_width = available_width * column_proportions[4];
+                                        let col_width = available_width * column_proportions[6];
                                         ui.set_min_width(col_width);
 
                                         // Genre - make editable
@@ -663,8 +1056,10 @@ impl AppComponent for PlaylistTable {
                                                     .unwrap_or_else(|| track_genre.clone())
                                             });
 
-                                            let response =
-                                                ui.text_edit_singleline(&mut current_value);
+                                            let response = read_only_hint(
+                                                ui.text_edit_singleline(&mut current_value),
+                                                read_only_library,
+                                            );
 
                                             // Update the value in memory
                                             ui.memory_mut(|mem| {
@@ -726,10 +1121,56 @@ impl AppComponent for PlaylistTable {
                                                     ui.close_menu();
                                                 }
 
-                                                if ui.button(t("remove_from_playlist")).clicked() {
+                                                if ui
+                                                    .add_enabled(
+                                                        !is_virtual_playlist,
+                                                        egui::Button::new(t(
+                                                            "remove_from_playlist",
+                                                        )),
+                                                    )
+                                                    .clicked()
+                                                {
                                                     track_to_remove = Some(idx);
                                                     ui.close_menu();
                                                 }
+
+                                                if ui.button(t("stop_after_this_track")).clicked() {
+                                                    stop_after_idx = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if let Some((preamp_db, persist)) =
+                                                    preamp_menu_item(ui, track.preamp_db())
+                                                {
+                                                    preamp_change = Some((idx, preamp_db, persist));
+                                                }
+
+                                                if let Some(kind) = radio_menu(ui, track) {
+                                                    radio_request = Some((idx, kind));
+                                                }
+
+                                                if ui.button(t("properties")).clicked() {
+                                                    properties_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if ui.button(t("tag_replace_menu")).clicked() {
+                                                    replace_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if ui.button(t("tag_cleanup_menu")).clicked() {
+                                                    cleanup_request = Some(idx);
+                                                    ui.close_menu();
+                                                }
+
+                                                if let Some(action) = label_chips::labels_menu(
+                                                    ui,
+                                                    &ctx.labels,
+                                                    |id| ctx.labels.track_has(track.key(), id),
+                                                ) {
+                                                    label_request = Some((idx, action));
+                                                }
                                             });
 
                                             // Check for double-click to start editing
@@ -761,11 +1202,206 @@ impl AppComponent for PlaylistTable {
                                     });
                                 });
 
+                                // Number, date and format columns, the track number and
+                                // year can be edited
+                                for (position, (column, text)) in
+                                    columns[4..].iter().zip(detail_texts).enumerate()
+                                {
+                                    ui.scope(|ui| {
+                                        ui.push_id(row_id.with(column.label_key()), |ui| {
+                                            let col_width =
+                                                available_width * column_proportions[position + 7];
+                                            ui.set_min_width(col_width);
+
+                                            let Some(field) = column.tag_field() else {
+                                                ui.label(text);
+                                                return;
+                                            };
+                                            let value = column.value(track).unwrap_or_default();
+
+                                            if editing_field.as_deref() == Some(field.name())
+                                                && editing_track_idx == Some(idx)
+                                            {
+                                                let mut current_value = ui.memory_mut(|mem| {
+                                                    mem.data
+                                                        .get_temp::<String>(edit_value_id)
+                                                        .unwrap_or_else(|| value.clone())
+                                                });
+
+                                                // Show a value that can't be written in red
+                                                let valid = field.is_valid(&current_value);
+                                                let mut text_edit =
+                                                    egui::TextEdit::singleline(&mut current_value)
+                                                        .desired_width(col_width);
+                                                if !valid {
+                                                    text_edit = text_edit
+                                                        .text_color(ui.visuals().error_fg_color);
+                                                }
+                                                let mut response = read_only_hint(
+                                                    ui.add(text_edit),
+                                                    read_only_library,
+                                                );
+                                                if !valid {
+                                                    response = response.on_hover_text(t(
+                                                        if *column == PlaylistColumn::Track {
+                                                            "invalid_track_number"
+                                                        } else {
+                                                            "invalid_year"
+                                                        },
+                                                    ));
+                                                }
+
+                                                ui.memory_mut(|mem| {
+                                                    mem.data.insert_temp(
+                                                        edit_value_id,
+                                                        current_value.clone(),
+                                                    );
+                                                });
+
+                                                let enter_pressed =
+                                                    ui.input(|i| i.key_pressed(egui::Key::Enter));
+                                                if response.lost_focus() || enter_pressed {
+                                                    // An invalid value is dropped, not written
+                                                    let new_value = current_value.trim();
+                                                    if valid && new_value != value {
+                                                        tracks_to_update.push((
+                                                            idx,
+                                                            field.name().to_string(),
+                                                            new_value.to_string(),
+                                                        ));
+                                                    }
+
+                                                    ui.memory_mut(|mem| {
+                                                        mem.data.insert_temp(
+                                                            edit_field_id,
+                                                            None::<String>,
+                                                        );
+                                                        mem.data.insert_temp(
+                                                            edit_track_idx_id,
+                                                            None::<usize>,
+                                                        );
+                                                    });
+                                                }
+                                            } else {
+                                                // Empty cells get a dash to double-click on
+                                                let text = if value.is_empty() {
+                                                    egui::RichText::new("–").weak()
+                                                } else {
+                                                    text
+                                                };
+                                                let response = ui.add(
+                                                    egui::Label::new(text)
+                                                        .sense(egui::Sense::click()),
+                                                );
+
+                                                if response.double_clicked() && !is_dragging {
+                                                    ui.memory_mut(|mem| {
+                                                        mem.data.insert_temp(
+                                                            edit_field_id,
+                                                            Some(field.name().to_string()),
+                                                        );
+                                                        mem.data.insert_temp(
+                                                            edit_track_idx_id,
+                                                            Some(idx),
+                                                        );
+                                                        mem.data.insert_temp(edit_value_id, value);
+                                                    });
+                                                }
+
+                                                if response.clicked()
+                                                    && !response.double_clicked()
+                                                    && !is_dragging
+                                                    && ctrl_pressed
+                                                {
+                                                    toggle_selection = Some(idx);
+                                                }
+                                            }
+                                        });
+                                    });
+                                }
+
                                 ui.end_row();
                             }
                         });
                 });
 
+            // Type-ahead: typing over the table jumps to the next track with a matching title
+            let type_ahead_id = base_id.with("type_ahead");
+            let (mut type_ahead, last_typed) = ui
+                .memory_mut(|mem| mem.data.get_temp::<(String, f64)>(type_ahead_id))
+                .unwrap_or_default();
+            let now = ui.input(|i| i.time);
+            if now - last_typed > TYPE_AHEAD_TIMEOUT {
+                type_ahead.clear();
+            }
+
+            if ui.ui_contains_pointer()
+                && !ui.ctx().wants_keyboard_input()
+                && editing_field.is_none()
+            {
+                let typed: String = ui.input(|i| {
+                    i.events
+                        .iter()
+                        .filter_map(|event| match event {
+                            egui::Event::Text(text) => Some(text.as_str()),
+                            _ => None,
+                        })
+                        .collect()
+                });
+
+                if !typed.is_empty() {
+                    let clean_filter = ctx.clean_filter;
+                    let playlist = &mut ctx.playlists[current_playlist_idx];
+
+                    // A new prefix moves past the selected track, a longer one may stay on it
+                    let start = match playlist.selected_indices.iter().min() {
+                        Some(&idx) if !type_ahead.is_empty() => idx,
+                        Some(&idx) => idx + 1,
+                        None => 0,
+                    };
+                    type_ahead.push_str(&typed);
+
+                    if let Some(idx) = playlist.find_by_prefix(&type_ahead, start, |track| {
+                        matches_filters(track, &filters) && !(clean_filter && track.explicit())
+                    }) {
+                        playlist.clear_selection();
+                        playlist.toggle_selection(idx);
+
+                        if let Some((_, row_rect)) = row_rects.iter().find(|(i, _)| *i == idx) {
+                            ui.scroll_to_rect(*row_rect, Some(egui::Align::Center));
+                        }
+                    }
+
+                    ui.memory_mut(|mem| {
+                        mem.data
+                            .insert_temp(type_ahead_id, (type_ahead.clone(), now))
+                    });
+                }
+            }
+
+            // Show the typed prefix until it times out
+            if !type_ahead.is_empty() {
+                egui::Area::new(type_ahead_id.with("overlay"))
+                    .order(egui::Order::Foreground)
+                    .fixed_pos(ui.clip_rect().right_top() + egui::vec2(-160.0, 8.0))
+                    .show(ui.ctx(), |ui| {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            ui.label(format!("🔍 {}", type_ahead));
+                        });
+                    });
+                ui.ctx()
+                    .request_repaint_after(std::time::Duration::from_secs_f64(TYPE_AHEAD_TIMEOUT));
+            }
+
+            ui.memory_mut(|mem| mem.data.insert_temp(filters_id, filters));
+
+            // Sort the playlist when a column header was clicked
+            if let Some((column, ascending)) = sort_request {
+                let playlist = &mut ctx.playlists[current_playlist_idx];
+                playlist.sort_by_column(column, ascending);
+                playlist.view.sort = Some((column, ascending));
+            }
+
             // Toggle selection for track if needed
             if let Some(idx) = toggle_selection {
                 ctx.playlists[current_playlist_idx].toggle_selection(idx);
@@ -773,26 +1409,90 @@ impl AppComponent for PlaylistTable {
 
             // Process track updates after the grid rendering
             for (idx, field, value) in tracks_to_update {
-                if idx < ctx.playlists[current_playlist_idx].tracks.len() {
-                    let mut track = ctx.playlists[current_playlist_idx].tracks[idx].clone();
-                    if ctx.update_track_metadata(&mut track, &field, &value) {
-                        ctx.playlists[current_playlist_idx].tracks[idx] = track;
-                    }
+                if let Some(track) = ctx.playlists[current_playlist_idx].tracks.get(idx).cloned() {
+                    ctx.queue_metadata_edit(ui.ctx(), &track, &field, &value);
                 }
             }
 
             // Handle track play/stop after the grid rendering
             if let Some(idx) = track_to_play {
-                if idx < ctx.playlists[current_playlist_idx].tracks.len() {
+                let is_offline = ctx.playlists[current_playlist_idx]
+                    .tracks
+                    .get(idx)
+                    .is_some_and(|track| !ctx.library.is_path_online(track.library_id()));
+
+                if is_offline {
+                    tracing::warn!("Track is on an offline volume, not playing it");
+                } else if idx < ctx.playlists[current_playlist_idx].tracks.len() {
                     let track_clone = ctx.playlists[current_playlist_idx].tracks[idx].clone();
                     ctx.player.as_mut().unwrap().selected_track = Some(track_clone.clone());
                     ctx.player.as_mut().unwrap().select_track(Some(track_clone));
                     ctx.player.as_mut().unwrap().play();
                     // Set the current playlist as the playing playlist
                     ctx.playing_playlist_idx = Some(current_playlist_idx);
+                    ctx.queue = None;
+                }
+            }
+
+            // Toggle stopping after the chosen track
+            if let Some(idx) = stop_after_idx {
+                if let Some(track) = ctx.playlists[current_playlist_idx].tracks.get(idx) {
+                    let key = track.key();
+                    ctx.player.as_mut().unwrap().toggle_stop_after_track(key);
                 }
             }
 
+            if let Some((idx, preamp_db, persist)) = preamp_change {
+                if let Some(track) = ctx.playlists[current_playlist_idx].tracks.get(idx) {
+                    let key = track.key();
+                    ctx.set_track_preamp(key, preamp_db, persist);
+                }
+            }
+
+            if let Some((idx, kind)) = radio_request {
+                if let Some(track) = ctx.playlists[current_playlist_idx].tracks.get(idx).cloned() {
+                    ctx.play_radio(&track, kind);
+                }
+            }
+
+            // Step through the selection when the track is part of it, else the playlist
+            if let Some(idx) = properties_request {
+                let playlist = &ctx.playlists[current_playlist_idx];
+                let indices: Vec<usize> = if playlist.is_selected(idx) {
+                    let mut selected: Vec<usize> =
+                        playlist.selected_indices.iter().copied().collect();
+                    selected.sort_unstable();
+                    selected
+                } else {
+                    (0..playlist.tracks.len()).collect()
+                };
+                let keys = indices
+                    .iter()
+                    .filter_map(|idx| playlist.tracks.get(*idx))
+                    .map(|track| track.key())
+                    .collect();
+                let position = indices.iter().position(|i| *i == idx).unwrap_or(0);
+                ctx.open_properties(keys, position);
+            }
+
+            // Tag tools work on the selection when the track is part of it
+            if let Some(idx) = replace_request {
+                let keys = ctx.playlists[current_playlist_idx].keys_for_action(idx);
+                ctx.open_tag_replace(ReplaceScope::Tracks(keys));
+            }
+            if let Some(idx) = cleanup_request {
+                let keys = ctx.playlists[current_playlist_idx].keys_for_action(idx);
+                ctx.open_tag_cleanup(ReplaceScope::Tracks(keys));
+            }
+            if let Some(idx) = favorite_request {
+                let key = ctx.playlists[current_playlist_idx].tracks[idx].key();
+                ctx.toggle_favorite(key);
+            }
+            if let Some((idx, action)) = label_request {
+                let keys = ctx.playlists[current_playlist_idx].keys_for_action(idx);
+                action.apply_to_tracks(ctx, &keys);
+            }
+
             // Handle track removal after the iteration is complete
             if let Some(idx) = track_to_remove {
                 if idx < ctx.playlists[current_playlist_idx].tracks.len() {
@@ -800,24 +1500,20 @@ impl AppComponent for PlaylistTable {
                 }
             }
 
-            // Auto-scroll to current track if it exists and has changed
-            if let Some(current_idx) = current_track_idx {
-                if last_played_track != current_idx {
-                    // Get the row rect for the current track
+            // Scroll to the playing track when asked to, or when playback moved on to
+            // another one while following it. Otherwise manual scrolling is left alone.
+            let moved_on = ctx.follow_playback && current_track_key != followed_track;
+            if moved_on || ctx.locate_requested {
+                if let Some(current_idx) = current_track_idx {
                     if let Some((_, row_rect)) =
                         row_rects.iter().find(|(idx, _)| *idx == current_idx)
                     {
-                        // Use egui's built-in scroll-to-rect functionality
                         ui.scroll_to_rect(*row_rect, Some(egui::Align::Center));
                     }
-
-                    // Update the last played track
-                    LAST_PLAYED_TRACK.store(current_idx, Ordering::Relaxed);
                 }
-            } else {
-                // Reset the last played track if no track is currently playing
-                LAST_PLAYED_TRACK.store(0, Ordering::Relaxed);
+                ctx.locate_requested = false;
             }
+            ui.memory_mut(|mem| mem.data.insert_temp(followed_id, current_track_key));
 
             // Check if we need to scroll to a specific track (from search results)
             let scroll_to_idx_id = ui.id().with("scroll_to_idx");
@@ -958,7 +1654,9 @@ impl AppComponent for PlaylistTable {
                         };
 
                         // Reorder the playlist
-                        ctx.playlists[current_playlist_idx].reorder(drag_idx, target_pos);
+                        if !is_virtual_playlist {
+                            ctx.playlists[current_playlist_idx].reorder(drag_idx, target_pos);
+                        }
                     }
                 }
 
@@ -972,3 +1670,113 @@ impl AppComponent for PlaylistTable {
         }
     }
 }
+
+// Column header that asks for sorting by its column when clicked,
+// a second click reverses the order
+fn sort_header(
+    ui: &mut egui::Ui,
+    label: String,
+    column: PlaylistColumn,
+    sorted_by: Option<(PlaylistColumn, bool)>,
+) -> Option<(PlaylistColumn, bool)> {
+    let label = match sorted_by {
+        Some((sorted, true)) if sorted == column => format!("{} ⏶", label),
+        Some((sorted, false)) if sorted == column => format!("{} ⏷", label),
+        _ => label,
+    };
+
+    let response =
+        ui.add(egui::Label::new(egui::RichText::new(label).strong()).sense(egui::Sense::click()));
+    response
+        .clicked()
+        .then(|| (column, sorted_by != Some((column, true))))
+}
+
+// Volume offset of the track, returns a changed value and whether it's final
+fn preamp_menu_item(ui: &mut egui::Ui, preamp_db: f32) -> Option<(f32, bool)> {
+    let mut value = preamp_db;
+    let (response, reset) = ui
+        .horizontal(|ui| {
+            ui.label(t("track_preamp"));
+            let response = ui
+                .add(
+                    egui::DragValue::new(&mut value)
+                        .range(MIN_PREAMP_DB..=MAX_PREAMP_DB)
+                        .speed(0.1)
+                        .fixed_decimals(1)
+                        .suffix(" dB"),
+                )
+                .accessible_name(&t("track_preamp"));
+            let reset = ui
+                .add_enabled(preamp_db != 0.0, egui::Button::new("↺"))
+                .on_hover_text(t("reset_preamp"))
+                .clicked();
+            (response, reset)
+        })
+        .inner;
+
+    if reset {
+        Some((0.0, true))
+    } else if response.changed() || response.drag_stopped() {
+        Some((value, !response.dragged()))
+    } else {
+        None
+    }
+}
+
+// Radios the track can seed, greyed out when it lacks the tag one is built around
+pub fn radio_menu(ui: &mut egui::Ui, track: &LibraryItem) -> Option<RadioKind> {
+    ui.menu_button(t("radio"), |ui| {
+        let mut chosen = None;
+        for kind in RadioKind::all() {
+            if ui
+                .add_enabled(kind.fits(track), egui::Button::new(t(kind.label_key())))
+                .clicked()
+            {
+                chosen = Some(kind);
+                ui.close_menu();
+            }
+        }
+        chosen
+    })
+    .inner
+    .flatten()
+}
+
+fn read_only_hint(response: egui::Response, read_only: bool) -> egui::Response {
+    if read_only {
+        response.on_hover_text(t("read_only_edit_hint"))
+    } else {
+        response
+    }
+}
+
+// Keys of the playlist's tracks whose file is gone, checked every few seconds. Tracks on
+// offline volumes aren't looked up, the status column shows those as offline anyway.
+fn missing_files(
+    ctx: &App,
+    ui: &egui::Ui,
+    id: egui::Id,
+    playlist_idx: usize,
+) -> Arc<HashSet<TrackKey>> {
+    let now = ui.input(|i| i.time);
+    let checked = ui.memory_mut(|mem| mem.data.get_temp::<(f64, Arc<HashSet<TrackKey>>)>(id));
+    if let Some((checked_at, missing)) = checked {
+        if now - checked_at < MISSING_CHECK_INTERVAL {
+            return missing;
+        }
+    }
+
+    let missing: Arc<HashSet<TrackKey>> = Arc::new(
+        ctx.playlists[playlist_idx]
+            .tracks
+            .iter()
+            .filter(|track| {
+                ctx.library.is_path_online(track.library_id()) && !track.path().exists()
+            })
+            .map(|track| track.key())
+            .collect(),
+    );
+    ui.memory_mut(|mem| mem.data.insert_temp(id, (now, missing.clone())));
+    missing
+}
@@ -1,15 +1,39 @@
+use super::label_chips::{self, LabelAction};
 use super::AppComponent;
+use crate::app::labels::LabelId;
+use crate::app::playlist::VirtualPlaylist;
+use crate::app::playlist_actions::JUMP_TO_PLAYING_SHORTCUT;
 use crate::app::t;
 use crate::app::{App, Playlist};
 use eframe::egui;
 
 pub struct PlaylistTabs;
 
+// Tab actions that change the list of playlists, applied after drawing the tabs
+enum TabAction {
+    Duplicate(usize),
+    Merge(usize, usize),
+    Append(usize, usize),
+    Label(usize, LabelAction),
+    CloseLabel(LabelId),
+}
+
 impl AppComponent for PlaylistTabs {
     type Context = App;
 
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
         ui.horizontal_wrapped(|ui| {
+            let mut tab_action = None;
+
+            // Playlists tracks can be merged or appended into
+            let targets: Vec<(usize, String)> = ctx
+                .playlists
+                .iter()
+                .enumerate()
+                .filter(|(_, playlist)| !playlist.is_virtual())
+                .map(|(idx, playlist)| (idx, playlist.get_name().unwrap_or_default()))
+                .collect();
+
             // Add playlist tabs
             for (idx, playlist) in ctx.playlists.iter_mut().enumerate() {
                 let is_selected = ctx.current_playlist_idx == Some(idx);
@@ -35,9 +59,14 @@ impl AppComponent for PlaylistTabs {
                         ctx.playlist_being_renamed = None;
                     }
                 } else {
-                    // Show normal tab button
-                    let mut tab_text =
-                        egui::RichText::new(playlist.get_name().unwrap_or_default()).size(12.0);
+                    // Show normal tab button, virtual playlists are pinned
+                    let name = playlist.get_name().unwrap_or_default();
+                    let name = if playlist.is_virtual() {
+                        format!("📌 {}", name)
+                    } else {
+                        name
+                    };
+                    let mut tab_text = egui::RichText::new(name).size(12.0);
                     if is_selected {
                         tab_text = tab_text.strong();
                     }
@@ -51,14 +80,69 @@ impl AppComponent for PlaylistTabs {
                     if tab_response.clicked() {
                         ctx.current_playlist_idx = Some(idx);
                     }
+                    for label in ctx.labels.of_playlist(playlist.id) {
+                        label_chips::chip(ui, label);
+                    }
 
-                    // Show context menu on right-click
+                    // Show context menu on right-click, virtual playlists can't be
+                    // renamed, deleted or merged away
+                    let is_virtual = playlist.is_virtual();
                     tab_response.context_menu(|ui| {
-                        if ui.button(t("rename")).clicked() {
+                        if ui
+                            .add_enabled(!is_virtual, egui::Button::new(t("rename")))
+                            .clicked()
+                        {
                             ctx.playlist_being_renamed = Some(idx);
                             ui.close_menu();
                         }
-                        if ui.button(t("delete")).clicked() {
+                        if ui.button(t("duplicate_playlist")).clicked() {
+                            tab_action = Some(TabAction::Duplicate(idx));
+                            ui.close_menu();
+                        }
+
+                        let other_targets = || targets.iter().filter(|(target, _)| *target != idx);
+
+                        ui.add_enabled_ui(!is_virtual, |ui| {
+                            ui.menu_button(t("merge_into"), |ui| {
+                                for (target, name) in other_targets() {
+                                    if ui.button(name).clicked() {
+                                        tab_action = Some(TabAction::Merge(idx, *target));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        });
+                        ui.menu_button(t("append_to"), |ui| {
+                            for (target, name) in other_targets() {
+                                if ui.button(name).clicked() {
+                                    tab_action = Some(TabAction::Append(idx, *target));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+
+                        if ui.button(t("export_to_folder")).clicked() {
+                            ctx.export_playlist_idx = Some(idx);
+                            ui.close_menu();
+                        }
+
+                        if let Some(VirtualPlaylist::Label(id)) = playlist.virtual_kind {
+                            if ui.button(t("close_label_playlist")).clicked() {
+                                tab_action = Some(TabAction::CloseLabel(id));
+                                ui.close_menu();
+                            }
+                        } else if let Some(action) =
+                            label_chips::labels_menu(ui, &ctx.labels, |id| {
+                                ctx.labels.playlist_has(playlist.id, id)
+                            })
+                        {
+                            tab_action = Some(TabAction::Label(idx, action));
+                        }
+
+                        if ui
+                            .add_enabled(!is_virtual, egui::Button::new(t("delete")))
+                            .clicked()
+                        {
                             ctx.playlist_idx_to_remove = Some(idx);
                             ui.close_menu();
                         }
@@ -78,20 +162,71 @@ impl AppComponent for PlaylistTabs {
                 ctx.playlist_being_renamed = Some(new_idx); // Start renaming the new playlist immediately
             }
 
-            // Handle playlist removal
-            if let Some(idx) = ctx.playlist_idx_to_remove {
-                ctx.playlist_idx_to_remove = None;
-
-                if let Some(mut current_playlist_idx) = ctx.current_playlist_idx {
-                    if current_playlist_idx == 0 && idx == 0 {
-                        ctx.current_playlist_idx = None;
-                    } else if current_playlist_idx >= idx {
-                        current_playlist_idx -= 1;
-                        ctx.current_playlist_idx = Some(current_playlist_idx);
+            // Every track with a label, as a playlist of its own
+            let mut open_label = None;
+            if !ctx.labels.all().is_empty() {
+                ui.menu_button("🏷", |ui| {
+                    for label in ctx.labels.all() {
+                        if label_chips::chip(ui, label)
+                            .interact(egui::Sense::click())
+                            .clicked()
+                        {
+                            open_label = Some(label.id);
+                            ui.close_menu();
+                        }
                     }
+                })
+                .response
+                .on_hover_text(t("open_label_playlist"));
+            }
+            if let Some(id) = open_label {
+                ctx.open_label_playlist(id);
+            }
+
+            ui.separator();
+            let has_track = ctx
+                .player
+                .as_ref()
+                .is_some_and(|player| player.selected_track.is_some());
+            if ui
+                .add_enabled(has_track, egui::Button::new("◎"))
+                .on_hover_text(t("locate_current_track"))
+                .clicked()
+            {
+                ctx.locate_current_track();
+            }
+            // Back to the playlist that's playing from another one
+            if let Some(idx) = ctx.playing_playlist_elsewhere() {
+                let name = ctx.playlists[idx].get_name().unwrap_or_default();
+                let shortcut = ui.ctx().format_shortcut(&JUMP_TO_PLAYING_SHORTCUT);
+                if ui
+                    .button(format!("▶ {}", name))
+                    .on_hover_text(format!("{} ({})", t("jump_to_playing"), shortcut))
+                    .clicked()
+                {
+                    ctx.jump_to_playing_playlist();
                 }
+            }
+            if ui
+                .toggle_value(&mut ctx.follow_playback, "⇣")
+                .on_hover_text(t("follow_playback"))
+                .changed()
+            {
+                ctx.save_state();
+            }
+
+            // Handle playlist removal
+            if let Some(idx) = ctx.playlist_idx_to_remove.take() {
+                ctx.remove_playlist(idx);
+            }
 
-                ctx.playlists.remove(idx);
+            match tab_action {
+                Some(TabAction::Duplicate(idx)) => ctx.duplicate_playlist(idx),
+                Some(TabAction::Merge(source, target)) => ctx.merge_playlist(source, target),
+                Some(TabAction::Append(source, target)) => ctx.append_playlist(source, target),
+                Some(TabAction::Label(idx, action)) => action.apply_to_playlist(ctx, idx),
+                Some(TabAction::CloseLabel(id)) => ctx.close_label_playlist(id),
+                None => (),
             }
         });
     }
@@ -0,0 +1,252 @@
+use super::cassette_component::paint_album_art;
+use super::AppComponent;
+use crate::app::library::{format_date, LibraryItem};
+use crate::app::properties::EDITABLE_FIELDS;
+use crate::app::tag_writer::TagField;
+use crate::app::{t, tf, App};
+use eframe::egui::{self, vec2, Sense, Window};
+
+// Side of an embedded picture in the dialog, in points
+const PICTURE_POINTS: f32 = 96.0;
+
+pub struct PropertiesWindow;
+
+impl AppComponent for PropertiesWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        let Some(key) = ctx
+            .properties
+            .as_ref()
+            .and_then(|properties| properties.key())
+        else {
+            return;
+        };
+        let Some(track) = ctx.find_track(key).cloned() else {
+            ctx.properties = None;
+            return;
+        };
+        let read_only = ctx.read_only_library;
+
+        let mut open = true;
+        let mut step: Option<isize> = None;
+        let mut save = false;
+        let mut close = false;
+        let mut audio_track: Option<u32> = None;
+        Window::new(t("properties"))
+            .collapsible(false)
+            .default_width(420.0)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                let Some(properties) = &mut ctx.properties else {
+                    return;
+                };
+
+                // Stepping through the selection
+                if properties.keys.len() > 1 {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(properties.position > 0, egui::Button::new("◀"))
+                            .on_hover_text(t("previous"))
+                            .clicked()
+                        {
+                            step = Some(-1);
+                        }
+                        ui.label(tf(
+                            "properties_position",
+                            &[
+                                &(properties.position + 1).to_string(),
+                                &properties.keys.len().to_string(),
+                            ],
+                        ));
+                        if ui
+                            .add_enabled(
+                                properties.position + 1 < properties.keys.len(),
+                                egui::Button::new("▶"),
+                            )
+                            .on_hover_text(t("next"))
+                            .clicked()
+                        {
+                            step = Some(1);
+                        }
+                    });
+                    ui.separator();
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(ui.ctx().screen_rect().height() * 0.6)
+                    .show(ui, |ui| {
+                        egui::Grid::new("properties_tags")
+                            .num_columns(2)
+                            .spacing([10.0, 4.0])
+                            .show(ui, |ui| {
+                                for (field, value) in
+                                    EDITABLE_FIELDS.iter().zip(properties.values.iter_mut())
+                                {
+                                    ui.label(t(field.label_key()));
+                                    let valid = field.is_valid(value);
+                                    let mut edit = if *field == TagField::Lyrics {
+                                        egui::TextEdit::multiline(value).desired_rows(4)
+                                    } else {
+                                        egui::TextEdit::singleline(value)
+                                    }
+                                    .desired_width(f32::INFINITY);
+                                    if !valid {
+                                        edit = edit.text_color(ui.visuals().error_fg_color);
+                                    }
+                                    let response = ui.add(edit);
+                                    if read_only {
+                                        response.on_hover_text(t("read_only_edit_hint"));
+                                    }
+                                    ui.end_row();
+                                }
+
+                                // Kept in the database only, never written to the file
+                                ui.label(t("notes"));
+                                ui.add(
+                                    egui::TextEdit::multiline(&mut properties.note)
+                                        .desired_rows(3)
+                                        .desired_width(f32::INFINITY),
+                                )
+                                .on_hover_text(t("notes_hint"));
+                                ui.end_row();
+
+                                // Files with several audio tracks, e.g. languages in Matroska
+                                if properties.audio_tracks.len() > 1 {
+                                    ui.label(t("audio_track"));
+                                    let current = (track.audio_track().unwrap_or(0) as usize)
+                                        .min(properties.audio_tracks.len() - 1);
+                                    egui::ComboBox::from_id_salt("properties_audio_track")
+                                        .selected_text(&properties.audio_tracks[current])
+                                        .show_ui(ui, |ui| {
+                                            for (idx, name) in
+                                                properties.audio_tracks.iter().enumerate()
+                                            {
+                                                if ui
+                                                    .selectable_label(idx == current, name)
+                                                    .clicked()
+                                                    && idx != current
+                                                {
+                                                    audio_track = Some(idx as u32);
+                                                }
+                                            }
+                                        })
+                                        .response
+                                        .on_hover_text(t("audio_track_hint"));
+                                    ui.end_row();
+                                }
+
+                                read_only_rows(ui, &track);
+                            });
+
+                        pictures(ui, &track);
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let can_save = properties.is_changed() && properties.is_valid();
+                    if ui
+                        .add_enabled(can_save, egui::Button::new(t("apply")))
+                        .clicked()
+                    {
+                        save = true;
+                    }
+                    if ui.button(t("close")).clicked() {
+                        close = true;
+                    }
+                    if !properties.is_valid() {
+                        ui.colored_label(ui.visuals().error_fg_color, t("properties_invalid"));
+                    }
+                });
+            });
+
+        if let Some(audio_track) = audio_track {
+            ctx.set_audio_track(key, Some(audio_track));
+        }
+        if let Some(step) = step {
+            ctx.step_properties(ui.ctx(), step);
+        }
+        if save {
+            ctx.save_properties(ui.ctx());
+        }
+        if close || !open {
+            ctx.properties = None;
+        }
+    }
+}
+
+// What the library knows of the track that can't be edited here
+fn read_only_rows(ui: &mut egui::Ui, track: &LibraryItem) {
+    let mut row = |label: String, value: String| {
+        if value.is_empty() {
+            return;
+        }
+        ui.label(label);
+        ui.add(egui::Label::new(egui::RichText::new(value).weak()).selectable(true));
+        ui.end_row();
+    };
+
+    row(t("album_artist"), track.album_artist().unwrap_or_default());
+    if track.artists().len() > 1 {
+        row(t("properties_artists"), track.artists().join("; "));
+    }
+    row(t("properties_path"), track.path().display().to_string());
+    row(t("column_format"), track.format().summary());
+
+    let replay_gain = track.replay_gain();
+    let gain = |gain: Option<f32>, peak: Option<f32>| match (gain, peak) {
+        (Some(gain), Some(peak)) => format!("{:+.2} dB, {} {:.6}", gain, t("peak"), peak),
+        (Some(gain), None) => format!("{:+.2} dB", gain),
+        _ => String::new(),
+    };
+    row(
+        format!("{} ({})", t("replay_gain"), t("replay_gain_track")),
+        gain(replay_gain.track_gain, replay_gain.track_peak),
+    );
+    row(
+        format!("{} ({})", t("replay_gain"), t("replay_gain_album")),
+        gain(replay_gain.album_gain, replay_gain.album_peak),
+    );
+    if track.preamp_db() != 0.0 {
+        row(t("track_preamp"), format!("{:+.1} dB", track.preamp_db()));
+    }
+
+    row(
+        t("column_added"),
+        track.added_at().map(format_date).unwrap_or_default(),
+    );
+    row(
+        t("column_modified"),
+        track.modified_at().map(format_date).unwrap_or_default(),
+    );
+    row(t("column_plays"), track.play_count().to_string());
+    row(t("column_skips"), track.skip_count().to_string());
+    row(t("column_rating"), "★".repeat(track.rating() as usize));
+    if track.explicit() {
+        row(t("properties_explicit"), t("yes"));
+    }
+}
+
+// The pictures embedded in the track, with their description on hover
+fn pictures(ui: &mut egui::Ui, track: &LibraryItem) {
+    if track.pictures().is_empty() {
+        return;
+    }
+    ui.add_space(8.0);
+    ui.strong(t("properties_pictures"));
+    ui.horizontal_wrapped(|ui| {
+        for picture in track.pictures() {
+            let (rect, response) =
+                ui.allocate_exact_size(vec2(PICTURE_POINTS, PICTURE_POINTS), Sense::hover());
+            if !paint_album_art(ui, &picture.file_path, rect) {
+                ui.painter()
+                    .rect_filled(rect, 4.0, ui.visuals().extreme_bg_color);
+            }
+            let mut hover = picture.mime_type.clone();
+            if !picture.description.is_empty() {
+                hover = format!("{}\n{}", picture.description, hover);
+            }
+            response.on_hover_text(hover);
+        }
+    });
+}
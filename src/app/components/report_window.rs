@@ -0,0 +1,151 @@
+use super::AppComponent;
+use crate::app::report::{self, ListeningReport, ReportPeriod};
+use crate::app::App;
+use crate::app::{t, tf, tp};
+use eframe::egui::{self, Window};
+
+pub struct ReportWindow;
+
+impl AppComponent for ReportWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.show_report {
+            return;
+        }
+
+        let mut open = true;
+        let mut copy = false;
+        let mut save_image = false;
+
+        let window = Window::new(t("listening_report"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    let previous_period = ctx.report_period;
+                    egui::ComboBox::from_id_salt("report_period")
+                        .selected_text(ctx.report_period.label())
+                        .show_ui(ui, |ui| {
+                            for period in [ReportPeriod::Week, ReportPeriod::Month] {
+                                ui.selectable_value(&mut ctx.report_period, period, period.label());
+                            }
+                        });
+                    if ctx.report_period != previous_period {
+                        ctx.reload_report();
+                    }
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let has_report = ctx.report.is_some();
+                        if ui
+                            .add_enabled(has_report, egui::Button::new(t("report_save_image")))
+                            .clicked()
+                        {
+                            save_image = true;
+                        }
+                        if ui
+                            .add_enabled(has_report, egui::Button::new(t("report_copy")))
+                            .clicked()
+                        {
+                            copy = true;
+                        }
+                    });
+                });
+                ui.separator();
+
+                match &ctx.report {
+                    Some(report) if report.plays > 0 => show_report(ui, report),
+                    _ => {
+                        ui.label(t("report_empty"));
+                    }
+                }
+
+                if let Some(status) = &ctx.report_status {
+                    ui.separator();
+                    ui.label(egui::RichText::new(status).small());
+                }
+            });
+
+        if copy {
+            if let Some(report) = &ctx.report {
+                ui.ctx()
+                    .copy_text(report::to_text(report, ctx.report_period));
+                ctx.report_status = Some(t("report_copied"));
+            }
+        }
+
+        if save_image {
+            if let Some(target) = rfd::FileDialog::new()
+                .add_filter("PNG", &["png"])
+                .set_file_name("listening-report.png")
+                .save_file()
+            {
+                ctx.report_image_target = Some(target);
+                ui.ctx()
+                    .send_viewport_cmd(egui::ViewportCommand::Screenshot(Default::default()));
+            }
+        }
+
+        // The screenshot comes in a frame or two later, the window is cut out of it
+        if let (Some(window), Some(target)) = (&window, &ctx.report_image_target) {
+            let screenshot = ui.ctx().input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    egui::Event::Screenshot { image, .. } => Some(image.clone()),
+                    _ => None,
+                })
+            });
+            if let Some(screenshot) = screenshot {
+                let pixels_per_point = ui.ctx().pixels_per_point();
+                let image = screenshot.region(&window.response.rect, Some(pixels_per_point));
+                ctx.report_status = Some(match report::save_image(&image, target) {
+                    Ok(()) => tf("report_saved", &[&target.display().to_string()]),
+                    Err(e) => tf("report_failed", &[&e]),
+                });
+                ctx.report_image_target = None;
+            } else {
+                ui.ctx().request_repaint();
+            }
+        }
+
+        if !open {
+            ctx.show_report = false;
+            ctx.report_image_target = None;
+        }
+    }
+}
+
+fn show_report(ui: &mut egui::Ui, report: &ListeningReport) {
+    ui.heading(tp(
+        "report_listened",
+        report.plays as u64,
+        &[&format!("{:.1}", report.hours()), &report.plays.to_string()],
+    ));
+    ui.label(tp(
+        "report_streak",
+        report.longest_streak as u64,
+        &[&report.longest_streak.to_string()],
+    ));
+
+    ui.add_space(5.0);
+    ui.horizontal_top(|ui| {
+        for (label, list) in [
+            ("report_top_artists", &report.top_artists),
+            ("report_top_tracks", &report.top_tracks),
+            ("report_top_genres", &report.top_genres),
+        ] {
+            ui.vertical(|ui| {
+                ui.set_max_width(180.0);
+                ui.strong(t(label));
+                if list.is_empty() {
+                    ui.weak("–");
+                }
+                for (idx, (name, plays)) in list.iter().enumerate() {
+                    ui.add(
+                        egui::Label::new(format!("{}. {} ({})", idx + 1, name, plays)).truncate(),
+                    );
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,984 @@
+use super::AppComponent;
+use crate::app::library::LibraryPathStatus;
+use crate::app::player::ReplayGainMode;
+use crate::app::skin::SkinStyle;
+use crate::app::App;
+use crate::app::{t, tf, tp};
+use crate::channel_mix::ChannelMix;
+use crate::crossfeed::CrossfeedLevel;
+use crate::limiter::MIN_CEILING_DB;
+use crate::transition::{Transition, MAX_TRANSITION_SECONDS};
+use eframe::egui::{self, Window};
+use std::sync::atomic::Ordering;
+
+pub struct SettingsWindow;
+
+impl AppComponent for SettingsWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.show_settings_dialog {
+            return;
+        }
+
+        let mut open = true;
+        Window::new(t("settings"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ui.heading(t("replay_gain"));
+                ui.add_space(5.0);
+
+                let previous_mode = ctx.replay_gain_mode;
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut ctx.replay_gain_mode, ReplayGainMode::Off, t("off"));
+                    ui.radio_value(
+                        &mut ctx.replay_gain_mode,
+                        ReplayGainMode::Track,
+                        t("replay_gain_track"),
+                    );
+                    ui.radio_value(
+                        &mut ctx.replay_gain_mode,
+                        ReplayGainMode::Album,
+                        t("replay_gain_album"),
+                    );
+                });
+
+                if ctx.replay_gain_mode != previous_mode {
+                    if let Some(player) = &mut ctx.player {
+                        player.set_replay_gain_mode(ctx.replay_gain_mode);
+                    }
+                    ctx.save_state();
+                }
+
+                ui.add_enabled_ui(!ctx.read_only_library, |ui| {
+                    if ui
+                        .checkbox(&mut ctx.replay_gain_write_tags, t("replay_gain_write_tags"))
+                        .changed()
+                    {
+                        ctx.save_state();
+                    }
+                });
+
+                if ui
+                    .checkbox(&mut ctx.volume_leveling, t("volume_leveling"))
+                    .on_hover_text(t("volume_leveling_hint"))
+                    .changed()
+                {
+                    if let Some(player) = &mut ctx.player {
+                        player.set_volume_leveling(ctx.volume_leveling);
+                    }
+                    ctx.save_state();
+                }
+
+                ui.add_space(5.0);
+                if ctx.replay_gain_scanning.load(Ordering::Relaxed) {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(t("replay_gain_scanning"));
+                    });
+                } else if ui.button(t("replay_gain_scan_library")).clicked() {
+                    ctx.scan_replay_gain(ctx.library.items().clone());
+                }
+
+                ui.add_space(10.0);
+                ui.heading(t("playback"));
+                ui.add_space(5.0);
+
+                let previous_mix = ctx.channel_mix;
+                ui.horizontal(|ui| {
+                    ui.label(t("channel_mix"));
+                    ui.radio_value(
+                        &mut ctx.channel_mix,
+                        ChannelMix::Source,
+                        t("channel_mix_source"),
+                    );
+                    ui.radio_value(
+                        &mut ctx.channel_mix,
+                        ChannelMix::Stereo,
+                        t("channel_mix_stereo"),
+                    )
+                    .on_hover_text(t("channel_mix_stereo_hint"));
+                    ui.radio_value(
+                        &mut ctx.channel_mix,
+                        ChannelMix::Mono,
+                        t("channel_mix_mono"),
+                    )
+                    .on_hover_text(t("channel_mix_mono_hint"));
+                });
+
+                if ctx.channel_mix != previous_mix {
+                    if let Some(player) = &ctx.player {
+                        player.set_channel_mix(ctx.channel_mix);
+                    }
+                    ctx.save_state();
+                }
+
+                let mut balance_changed = false;
+                let mut balance_done = false;
+                ui.horizontal(|ui| {
+                    ui.label(t("balance"));
+                    let response = ui.add(
+                        egui::Slider::new(&mut ctx.balance, -1.0..=1.0).custom_formatter(
+                            |value, _| match value {
+                                v if v < -0.005 => format!("L {:.0}%", -v * 100.0),
+                                v if v > 0.005 => format!("R {:.0}%", v * 100.0),
+                                _ => t("balance_center"),
+                            },
+                        ),
+                    );
+                    if response.double_clicked() {
+                        ctx.balance = 0.0;
+                    }
+                    balance_changed |= response.changed() || response.double_clicked();
+                    balance_done |= response.drag_stopped() || response.lost_focus();
+                })
+                .response
+                .on_hover_text(t("balance_hint"));
+
+                ui.horizontal(|ui| {
+                    ui.label(t("channel_trim"));
+                    for (label, trim) in
+                        [("L", &mut ctx.left_trim_db), ("R", &mut ctx.right_trim_db)]
+                    {
+                        let response = ui.add(
+                            egui::DragValue::new(trim)
+                                .range(-12.0..=6.0)
+                                .speed(0.1)
+                                .fixed_decimals(1)
+                                .prefix(format!("{} ", label))
+                                .suffix(" dB"),
+                        );
+                        balance_changed |= response.changed();
+                        balance_done |= response.drag_stopped() || response.lost_focus();
+                    }
+                })
+                .response
+                .on_hover_text(t("channel_trim_hint"));
+
+                if balance_changed {
+                    if let Some(player) = &ctx.player {
+                        player.set_dsp_chain(ctx.dsp_chain());
+                    }
+                }
+                if balance_done {
+                    ctx.save_state();
+                }
+
+                if ui
+                    .checkbox(&mut ctx.soft_fades, t("soft_fades"))
+                    .on_hover_text(t("soft_fades_hint"))
+                    .changed()
+                {
+                    if let Some(player) = &ctx.player {
+                        player.set_soft_fades(ctx.soft_fades);
+                    }
+                    ctx.save_state();
+                }
+
+                ui.label(t("transitions"))
+                    .on_hover_text(t("transitions_hint"));
+                let mut transitions = ctx.transitions;
+                let mut transitions_done = false;
+                for (label, transition) in [
+                    ("transition_same_album", &mut transitions.same_album),
+                    ("transition_shuffle", &mut transitions.shuffle),
+                    ("transition_other", &mut transitions.other),
+                ] {
+                    transitions_done |= transition_row(ui, label, transition);
+                }
+                if transitions != ctx.transitions {
+                    ctx.transitions = transitions;
+                    if let Some(player) = &mut ctx.player {
+                        player.transitions = transitions;
+                    }
+                }
+                if transitions_done {
+                    ctx.save_state();
+                }
+
+                ui.horizontal(|ui| {
+                    let previous_level = ctx.crossfeed_level;
+                    let toggled = ui
+                        .checkbox(&mut ctx.crossfeed, t("crossfeed"))
+                        .on_hover_text(t("crossfeed_hint"))
+                        .changed();
+                    ui.add_enabled_ui(ctx.crossfeed, |ui| {
+                        for level in CrossfeedLevel::all() {
+                            ui.radio_value(&mut ctx.crossfeed_level, level, t(level.label_key()));
+                        }
+                    });
+                    if toggled || ctx.crossfeed_level != previous_level {
+                        if let Some(player) = &ctx.player {
+                            player.set_dsp_chain(ctx.dsp_chain());
+                        }
+                        ctx.save_state();
+                    }
+                });
+
+                #[cfg(feature = "ladspa")]
+                plugin_settings(ctx, ui);
+
+                ui.horizontal(|ui| {
+                    let toggled = ui
+                        .checkbox(&mut ctx.limiter, t("limiter"))
+                        .on_hover_text(t("limiter_hint"))
+                        .changed();
+                    let response = ui.add_enabled(
+                        ctx.limiter,
+                        egui::DragValue::new(&mut ctx.limiter_ceiling_db)
+                            .range(MIN_CEILING_DB..=0.0)
+                            .speed(0.1)
+                            .fixed_decimals(1)
+                            .suffix(" dBFS"),
+                    );
+                    if toggled || response.changed() {
+                        if let Some(player) = &ctx.player {
+                            player.set_dsp_chain(ctx.dsp_chain());
+                        }
+                    }
+                    if toggled || response.drag_stopped() || response.lost_focus() {
+                        ctx.save_state();
+                    }
+                });
+
+                // Order of the effect chain, the top one runs first
+                ui.label(t("effect_order"))
+                    .on_hover_text(t("effect_order_hint"));
+                let mut moved = None;
+                let count = ctx.effect_order.len();
+                for (idx, kind) in ctx.effect_order.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}. {}", idx + 1, t(kind.label_key())));
+                        if ui
+                            .add_enabled(idx > 0, egui::Button::new("⏶"))
+                            .on_hover_text(t("move_up"))
+                            .clicked()
+                        {
+                            moved = Some((idx, idx - 1));
+                        }
+                        if ui
+                            .add_enabled(idx + 1 < count, egui::Button::new("⏷"))
+                            .on_hover_text(t("move_down"))
+                            .clicked()
+                        {
+                            moved = Some((idx, idx + 1));
+                        }
+                    });
+                }
+                if let Some((from, to)) = moved {
+                    ctx.effect_order.swap(from, to);
+                    if let Some(player) = &ctx.player {
+                        player.set_dsp_chain(ctx.dsp_chain());
+                    }
+                    ctx.save_state();
+                }
+
+                if ui
+                    .checkbox(&mut ctx.downrank_skipped, t("downrank_skipped"))
+                    .on_hover_text(t("downrank_skipped_hint"))
+                    .changed()
+                {
+                    if let Some(player) = &mut ctx.player {
+                        player.downrank_skipped = ctx.downrank_skipped;
+                    }
+                    ctx.save_state();
+                }
+
+                if ui
+                    .checkbox(&mut ctx.scrub_while_dragging, t("scrub_while_dragging"))
+                    .on_hover_text(t("scrub_while_dragging_hint"))
+                    .changed()
+                {
+                    ctx.save_state();
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label(t("resume_min_minutes"));
+                    let response = ui.add(
+                        egui::DragValue::new(&mut ctx.resume_min_minutes)
+                            .range(0..=600)
+                            .suffix(" min"),
+                    );
+                    if response.changed() {
+                        if let Some(player) = &mut ctx.player {
+                            player.resume_min_minutes = ctx.resume_min_minutes;
+                        }
+                    }
+                    if response.drag_stopped() || response.lost_focus() {
+                        ctx.save_state();
+                    }
+                })
+                .response
+                .on_hover_text(t("resume_min_minutes_hint"));
+
+                ui.horizontal(|ui| {
+                    ui.label(t("history_retention_days"));
+                    let response = ui.add(
+                        egui::DragValue::new(&mut ctx.history_retention_days)
+                            .range(0..=3650)
+                            .suffix(" d"),
+                    );
+                    if response.drag_stopped() || response.lost_focus() {
+                        ctx.prune_history();
+                        ctx.save_state();
+                    }
+                })
+                .response
+                .on_hover_text(t("history_retention_days_hint"));
+
+                ui.add_space(10.0);
+                ui.heading(t("skin"));
+                ui.add_space(5.0);
+
+                let previous_skin = ctx.skin.clone();
+                ui.horizontal(|ui| {
+                    ui.label(t("skin_style"));
+                    for style in SkinStyle::all() {
+                        ui.radio_value(&mut ctx.skin.style, style, t(style.label_key()));
+                    }
+                });
+
+                let speed = ui
+                    .horizontal(|ui| {
+                        ui.label(t("skin_spin_speed"));
+                        ui.add(egui::Slider::new(&mut ctx.skin.spin_speed, 0.0..=10.0))
+                    })
+                    .inner;
+
+                ui.checkbox(&mut ctx.skin.show_label, t("skin_show_label"));
+
+                if ui
+                    .checkbox(&mut ctx.battery_saver, t("battery_saver"))
+                    .on_hover_text(t("battery_saver_hint"))
+                    .changed()
+                {
+                    ctx.save_state();
+                }
+
+                ui.horizontal(|ui| {
+                    let visuals = ui.visuals().clone();
+                    skin_color(
+                        ui,
+                        &mut ctx.skin.frame_color,
+                        visuals.widgets.noninteractive.bg_stroke.color,
+                        "skin_frame_color",
+                    );
+                    skin_color(
+                        ui,
+                        &mut ctx.skin.tape_color,
+                        egui::Color32::BLACK,
+                        "skin_tape_color",
+                    );
+                    skin_color(
+                        ui,
+                        &mut ctx.skin.label_color,
+                        visuals.extreme_bg_color,
+                        "skin_label_color",
+                    );
+                    if ui.button(t("skin_reset_colors")).clicked() {
+                        ctx.skin.frame_color = None;
+                        ctx.skin.tape_color = None;
+                        ctx.skin.label_color = None;
+                    }
+                });
+
+                // The slider changes every frame while dragged, save once it's let go
+                if (ctx.skin != previous_skin && !speed.dragged()) || speed.drag_stopped() {
+                    ctx.save_state();
+                }
+
+                ui.add_space(10.0);
+                font_settings(ctx, ui);
+
+                ui.add_space(10.0);
+                ui.heading(t("library"));
+                ui.add_space(5.0);
+
+                if ui
+                    .checkbox(&mut ctx.read_only_library, t("read_only_library"))
+                    .on_hover_text(t("read_only_library_hint"))
+                    .changed()
+                {
+                    ctx.save_state();
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label(t("recently_added_days"));
+                    let response = ui.add(
+                        egui::DragValue::new(&mut ctx.recently_added_days)
+                            .range(0..=3650)
+                            .suffix(" d"),
+                    );
+                    if response.drag_stopped() || response.lost_focus() {
+                        ctx.refresh_recently_added();
+                        ctx.save_state();
+                    }
+                })
+                .response
+                .on_hover_text(t("recently_added_days_hint"));
+
+                if ui
+                    .checkbox(&mut ctx.show_format_columns, t("show_format_columns"))
+                    .changed()
+                {
+                    ctx.save_state();
+                }
+
+                if ui
+                    .checkbox(&mut ctx.clean_filter, t("clean_filter"))
+                    .on_hover_text(t("clean_filter_hint"))
+                    .changed()
+                {
+                    if let Some(player) = &mut ctx.player {
+                        player.clean_filter = ctx.clean_filter;
+                        player.shuffle.reset();
+                    }
+                    ctx.save_state();
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label(t("min_track_seconds"));
+                    let response = ui.add(
+                        egui::DragValue::new(&mut ctx.min_track_seconds)
+                            .range(0..=600)
+                            .suffix(" s"),
+                    );
+                    if response.drag_stopped() || response.lost_focus() {
+                        ctx.save_state();
+                    }
+                })
+                .response
+                .on_hover_text(t("min_track_seconds_hint"));
+
+                if ui
+                    .checkbox(&mut ctx.follow_symlinks, t("follow_symlinks"))
+                    .on_hover_text(t("follow_symlinks_hint"))
+                    .changed()
+                {
+                    ctx.save_state();
+                }
+
+                if ui
+                    .checkbox(&mut ctx.scan_video_files, t("scan_video_files"))
+                    .on_hover_text(t("scan_video_files_hint"))
+                    .changed()
+                {
+                    ctx.save_state();
+                }
+
+                library_folders(ctx, ui);
+
+                ui.add_space(5.0);
+                if ui
+                    .button(t("album_art_clean"))
+                    .on_hover_text(t("album_art_clean_hint"))
+                    .clicked()
+                {
+                    ctx.clean_album_art();
+                }
+                if let Some(status) = &ctx.album_art_status {
+                    ui.label(egui::RichText::new(status).small());
+                }
+
+                ui.add_space(10.0);
+                label_settings(ctx, ui);
+
+                ui.add_space(10.0);
+                ui.heading(t("backup"));
+                ui.add_space(5.0);
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(t("backup_create"))
+                        .on_hover_text(t("backup_hint"))
+                        .clicked()
+                    {
+                        if let Some(target) = rfd::FileDialog::new()
+                            .add_filter("Bird Player backup", &["birdbak"])
+                            .set_file_name("bird-player.birdbak")
+                            .save_file()
+                        {
+                            ctx.backup_status = Some(match ctx.create_backup(&target) {
+                                Ok(files) => {
+                                    tp("backup_created", files as u64, &[&files.to_string()])
+                                }
+                                Err(e) => tf("backup_failed", &[&e]),
+                            });
+                        }
+                    }
+
+                    if ui.button(t("backup_restore")).clicked() {
+                        if let Some(source) = rfd::FileDialog::new()
+                            .add_filter("Bird Player backup", &["birdbak"])
+                            .pick_file()
+                        {
+                            ctx.backup_status = Some(match ctx.restore_backup(&source) {
+                                Ok(files) => {
+                                    tp("backup_restored", files as u64, &[&files.to_string()])
+                                }
+                                Err(e) => tf("backup_failed", &[&e]),
+                            });
+                        }
+                    }
+                });
+
+                if let Some(status) = &ctx.backup_status {
+                    ui.label(egui::RichText::new(status).small());
+                }
+
+                if ui
+                    .button(t("import_from_player"))
+                    .on_hover_text(t("import_from_player_hint"))
+                    .clicked()
+                {
+                    if let Some(source) = rfd::FileDialog::new()
+                        .add_filter(&t("player_library_files"), &["xml", "db"])
+                        .pick_file()
+                    {
+                        ctx.player_import_status = Some(match ctx.import_from_player(&source) {
+                            Ok(summary) => tf(
+                                "import_from_player_done",
+                                &[
+                                    &summary.matched.to_string(),
+                                    &summary.tracks.to_string(),
+                                    &summary.playlists.to_string(),
+                                ],
+                            ),
+                            Err(e) => tf("import_from_player_failed", &[&e]),
+                        });
+                    }
+                }
+                if let Some(status) = &ctx.player_import_status {
+                    ui.label(egui::RichText::new(status).small());
+                }
+            });
+
+        if !open {
+            ctx.show_settings_dialog = false;
+        }
+    }
+}
+
+// A color picker that starts from the theme's color until one is picked
+// Names shown in the sidebar and which folder wins when both hold a copy of a file
+fn library_folders(ctx: &mut App, ui: &mut egui::Ui) {
+    if ctx.library.paths().is_empty() {
+        return;
+    }
+
+    ui.add_space(5.0);
+    ui.label(t("library_folders"))
+        .on_hover_text(t("library_folders_hint"));
+    let mut renamed = None;
+    let mut reprioritized = None;
+    let mut excludes_changed = None;
+    let mut to_import = None;
+    egui::Grid::new("library_folders")
+        .num_columns(4)
+        .spacing([10.0, 4.0])
+        .show(ui, |ui| {
+            for lib_path in ctx.library.paths() {
+                let name_id = ui.id().with(("library_folder_name", lib_path.id()));
+                let mut name = ui
+                    .memory_mut(|mem| mem.data.get_temp::<String>(name_id))
+                    .unwrap_or_else(|| lib_path.display_name().to_string());
+                let response = ui
+                    .add(
+                        egui::TextEdit::singleline(&mut name)
+                            .desired_width(160.0)
+                            .hint_text(t("library_folder_name")),
+                    )
+                    .on_hover_text(lib_path.path().display().to_string());
+                if response.lost_focus() {
+                    renamed = Some((lib_path.id(), name));
+                    ui.memory_mut(|mem| mem.data.remove::<String>(name_id));
+                } else if response.has_focus() {
+                    ui.memory_mut(|mem| mem.data.insert_temp(name_id, name));
+                }
+
+                let mut priority = lib_path.priority();
+                let response = ui
+                    .add(
+                        egui::DragValue::new(&mut priority)
+                            .range(-99..=99)
+                            .prefix(format!("{} ", t("library_folder_priority"))),
+                    )
+                    .on_hover_text(t("library_folder_priority_hint"));
+                if response.changed() {
+                    reprioritized = Some((lib_path.id(), priority));
+                }
+
+                let excludes_id = ui.id().with(("library_folder_excludes", lib_path.id()));
+                let mut excludes = ui
+                    .memory_mut(|mem| mem.data.get_temp::<String>(excludes_id))
+                    .unwrap_or_else(|| lib_path.exclude_patterns().join(", "));
+                let response = ui
+                    .add(
+                        egui::TextEdit::singleline(&mut excludes)
+                            .desired_width(200.0)
+                            .hint_text(t("library_folder_excludes")),
+                    )
+                    .on_hover_text(t("library_folder_excludes_hint"));
+                if response.lost_focus() {
+                    excludes_changed = Some((lib_path.id(), excludes));
+                    ui.memory_mut(|mem| mem.data.remove::<String>(excludes_id));
+                } else if response.has_focus() {
+                    ui.memory_mut(|mem| mem.data.insert_temp(excludes_id, excludes));
+                }
+
+                // Added without importing, e.g. to set the excludes first
+                if lib_path.status() == LibraryPathStatus::NotImported {
+                    if ui.button(t("library_folder_import")).clicked() {
+                        to_import = Some(lib_path.id());
+                    }
+                } else {
+                    ui.label("");
+                }
+                ui.end_row();
+            }
+        });
+
+    if let Some((id, name)) = renamed {
+        ctx.library.set_path_display_name(id, &name);
+        ctx.save_state();
+    }
+    if let Some((id, priority)) = reprioritized {
+        ctx.library.set_path_priority(id, priority);
+        ctx.save_state();
+    }
+    if let Some((id, excludes)) = excludes_changed {
+        let patterns = excludes.split(',').map(str::to_string).collect();
+        ctx.library.set_path_exclude_patterns(id, patterns);
+        ctx.save_state();
+    }
+    if let Some(lib_path) =
+        to_import.and_then(|id| ctx.library.paths().iter().find(|p| p.id() == id))
+    {
+        ctx.import_library_paths(lib_path);
+    }
+}
+
+// Labels are put on in the playlist menus, here they're renamed, recolored and deleted
+fn label_settings(ctx: &mut App, ui: &mut egui::Ui) {
+    ui.heading(t("labels"));
+    ui.add_space(5.0);
+
+    let mut updated = None;
+    let mut deleted = None;
+    let mut opened = None;
+    if ctx.labels.all().is_empty() {
+        ui.label(t("labels_hint"));
+    }
+    egui::Grid::new("labels")
+        .num_columns(4)
+        .spacing([10.0, 4.0])
+        .show(ui, |ui| {
+            for label in ctx.labels.all() {
+                let mut color = label.color;
+                if ui.color_edit_button_srgb(&mut color).changed() {
+                    updated = Some((label.id, label.name.clone(), color));
+                }
+
+                let name_id = ui.id().with(("label_name", label.id));
+                let mut name = ui
+                    .memory_mut(|mem| mem.data.get_temp::<String>(name_id))
+                    .unwrap_or_else(|| label.name.clone());
+                let response = ui.add(egui::TextEdit::singleline(&mut name).desired_width(160.0));
+                if response.lost_focus() {
+                    updated = Some((label.id, name, label.color));
+                    ui.memory_mut(|mem| mem.data.remove::<String>(name_id));
+                } else if response.has_focus() {
+                    ui.memory_mut(|mem| mem.data.insert_temp(name_id, name));
+                }
+
+                if ui.button(t("open_label_playlist")).clicked() {
+                    opened = Some(label.id);
+                }
+                if ui.button("✖").on_hover_text(t("delete_label")).clicked() {
+                    deleted = Some(label.id);
+                }
+                ui.end_row();
+            }
+        });
+
+    let name_id = ui.id().with("new_label");
+    let mut name = ui
+        .memory_mut(|mem| mem.data.get_temp::<String>(name_id))
+        .unwrap_or_default();
+    let response = ui.add(
+        egui::TextEdit::singleline(&mut name)
+            .desired_width(160.0)
+            .hint_text(t("new_label")),
+    );
+    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+        ctx.create_label(&name);
+        name.clear();
+    }
+    ui.memory_mut(|mem| mem.data.insert_temp(name_id, name));
+
+    if let Some((id, name, color)) = updated {
+        ctx.update_label(id, &name, color);
+    }
+    if let Some(id) = deleted {
+        ctx.delete_label(id);
+    }
+    if let Some(id) = opened {
+        ctx.open_label_playlist(id);
+    }
+}
+
+fn skin_color(ui: &mut egui::Ui, color: &mut Option<[u8; 3]>, theme: egui::Color32, key: &str) {
+    ui.label(t(key));
+    let mut rgb = color.unwrap_or([theme.r(), theme.g(), theme.b()]);
+    if ui.color_edit_button_srgb(&mut rgb).changed() {
+        *color = Some(rgb);
+    }
+}
+
+// Fonts for the interface in fallback order. They apply right away, the window itself
+// is the preview.
+fn font_settings(ctx: &mut App, ui: &mut egui::Ui) {
+    ui.heading(t("fonts"));
+    ui.add_space(5.0);
+
+    let mut changed = false;
+    let mut moved = None;
+    let mut removed = None;
+    let count = ctx.ui_fonts.len();
+    if count == 0 {
+        ui.label(t("fonts_automatic"))
+            .on_hover_text(t("fonts_automatic_hint"));
+    }
+    for (idx, family) in ctx.ui_fonts.iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!("{}. {}", idx + 1, family));
+            if ui
+                .add_enabled(idx > 0, egui::Button::new("⏶"))
+                .on_hover_text(t("move_up"))
+                .clicked()
+            {
+                moved = Some((idx, idx - 1));
+            }
+            if ui
+                .add_enabled(idx + 1 < count, egui::Button::new("⏷"))
+                .on_hover_text(t("move_down"))
+                .clicked()
+            {
+                moved = Some((idx, idx + 1));
+            }
+            if ui.button("✖").on_hover_text(t("remove")).clicked() {
+                removed = Some(idx);
+            }
+        });
+    }
+    if let Some((from, to)) = moved {
+        ctx.ui_fonts.swap(from, to);
+        changed = true;
+    }
+    if let Some(idx) = removed {
+        ctx.ui_fonts.remove(idx);
+        changed = true;
+    }
+
+    ui.horizontal(|ui| {
+        let mut added = None;
+        egui::ComboBox::from_id_salt("add_font")
+            .selected_text(t("fonts_add"))
+            .height(300.0)
+            .show_ui(ui, |ui| {
+                // Listing them takes a moment, only done once the list is opened
+                let families = ctx
+                    .system_fonts
+                    .get_or_insert_with(crate::fonts::system_families);
+                for family in families.iter().filter(|f| !ctx.ui_fonts.contains(f)) {
+                    if ui.selectable_label(false, family).clicked() {
+                        added = Some(family.clone());
+                    }
+                }
+            });
+        if let Some(family) = added {
+            ctx.ui_fonts.push(family);
+            changed = true;
+        }
+        if ui
+            .add_enabled(count > 0, egui::Button::new(t("fonts_reset")))
+            .clicked()
+        {
+            ctx.ui_fonts.clear();
+            changed = true;
+        }
+    })
+    .response
+    .on_hover_text(t("fonts_hint"));
+
+    // Characters of each language the app is translated to, to see all are covered
+    ui.label(
+        egui::RichText::new("Bird Player · 鸟播放器 · バードプレーヤー · Äöü · Éèç · Ñ").size(16.0),
+    );
+
+    if changed {
+        crate::fonts::install(ui.ctx(), &ctx.ui_fonts);
+        ctx.save_state();
+    }
+}
+
+// The external plugin: its library, which plugin of it and the values of the controls
+// A kind of transition and its length, returns whether a change is final
+fn transition_row(ui: &mut egui::Ui, label: &str, transition: &mut Transition) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(t(label));
+        let mut done = false;
+        egui::ComboBox::from_id_salt(label)
+            .selected_text(t(transition.label_key()))
+            .show_ui(ui, |ui| {
+                for kind in Transition::all() {
+                    let selected = kind.label_key() == transition.label_key();
+                    if ui.selectable_label(selected, t(kind.label_key())).clicked() && !selected {
+                        *transition = kind;
+                        done = true;
+                    }
+                }
+            });
+        if let Some(seconds) = transition.seconds_mut() {
+            let response = ui.add(
+                egui::DragValue::new(seconds)
+                    .range(0.1..=MAX_TRANSITION_SECONDS)
+                    .speed(0.1)
+                    .fixed_decimals(1)
+                    .suffix(" s"),
+            );
+            done |= response.drag_stopped() || response.lost_focus();
+        }
+        done
+    })
+    .inner
+}
+
+#[cfg(feature = "ladspa")]
+fn plugin_settings(ctx: &mut App, ui: &mut egui::Ui) {
+    use crate::plugin::{self, PluginConfig, PluginInfo};
+
+    let mut changed = false;
+    let mut done = false;
+    ui.horizontal(|ui| {
+        if ui
+            .checkbox(&mut ctx.plugin, t("plugin"))
+            .on_hover_text(t("plugin_hint"))
+            .changed()
+        {
+            changed = true;
+            done = true;
+        }
+        if ui.button(t("plugin_choose")).clicked() {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("LADSPA", &[std::env::consts::DLL_EXTENSION])
+                .pick_file()
+            {
+                ctx.plugin_config = PluginConfig {
+                    path,
+                    ..Default::default()
+                };
+                changed = true;
+                done = true;
+            }
+        }
+        if let Some(name) = ctx.plugin_config.path.file_name() {
+            ui.label(name.to_string_lossy());
+        }
+    });
+
+    if !ctx.plugin_config.path.as_os_str().is_empty() {
+        // Looked up once per library, opening it every frame would be slow
+        let found_id = ui.id().with(("plugins_found", &ctx.plugin_config.path));
+        let found = ui
+            .memory_mut(|mem| {
+                mem.data
+                    .get_temp::<Result<Vec<PluginInfo>, String>>(found_id)
+            })
+            .unwrap_or_else(|| {
+                let found = plugin::describe(&ctx.plugin_config.path);
+                ui.memory_mut(|mem| mem.data.insert_temp(found_id, found.clone()));
+                found
+            });
+
+        match found {
+            Err(e) => {
+                ui.label(tf("plugin_failed", &[&e]));
+            }
+            Ok(plugins) => {
+                let selected = plugins
+                    .iter()
+                    .find(|info| info.label == ctx.plugin_config.label);
+                egui::ComboBox::from_id_salt("plugin_label")
+                    .selected_text(
+                        selected.map_or_else(|| t("plugin_none"), |info| info.name.clone()),
+                    )
+                    .show_ui(ui, |ui| {
+                        for info in &plugins {
+                            let text =
+                                format!("{} ({} → {})", info.name, info.inputs, info.outputs);
+                            if ui
+                                .selectable_label(info.label == ctx.plugin_config.label, text)
+                                .clicked()
+                                && info.label != ctx.plugin_config.label
+                            {
+                                ctx.plugin_config.label = info.label.clone();
+                                ctx.plugin_config.controls.clear();
+                                changed = true;
+                                done = true;
+                            }
+                        }
+                    });
+
+                if let Some(info) = selected {
+                    for control in &info.controls {
+                        let value = ctx
+                            .plugin_config
+                            .controls
+                            .entry(control.name.clone())
+                            .or_insert(control.default);
+                        if control.toggled {
+                            let mut on = *value > 0.0;
+                            if ui.checkbox(&mut on, &control.name).changed() {
+                                *value = if on { 1.0 } else { 0.0 };
+                                changed = true;
+                                done = true;
+                            }
+                            continue;
+                        }
+                        ui.horizontal(|ui| {
+                            let min = control.min.unwrap_or(f32::NEG_INFINITY);
+                            let max = control.max.unwrap_or(f32::INFINITY);
+                            let speed = if min.is_finite() && max.is_finite() {
+                                (max - min) / 200.0
+                            } else {
+                                0.1
+                            };
+                            let decimals = if control.integer { 0 } else { 2 };
+                            let response = ui.add(
+                                egui::DragValue::new(value)
+                                    .range(min..=max)
+                                    .speed(speed)
+                                    .fixed_decimals(decimals),
+                            );
+                            ui.label(&control.name);
+                            changed |= response.changed();
+                            done |= response.drag_stopped() || response.lost_focus();
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    if changed {
+        if let Some(player) = &ctx.player {
+            player.set_dsp_chain(ctx.dsp_chain());
+        }
+    }
+    if done {
+        ctx.save_state();
+    }
+}
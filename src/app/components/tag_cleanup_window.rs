@@ -0,0 +1,102 @@
+use super::AppComponent;
+use crate::app::tag_cleanup::CleanupOp;
+use crate::app::tag_replace::{ReplaceScope, MAX_PREVIEW};
+use crate::app::{t, tf, App};
+use eframe::egui::{self, Window};
+
+pub struct TagCleanupWindow;
+
+impl AppComponent for TagCleanupWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if ctx.tag_cleanup.is_none() {
+            return;
+        }
+
+        let mut open = true;
+        let mut changed = false;
+        let mut apply = false;
+        let read_only = ctx.read_only_library;
+        Window::new(t("tag_cleanup"))
+            .collapsible(false)
+            .default_width(520.0)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                let Some(cleanup) = &mut ctx.tag_cleanup else {
+                    return;
+                };
+
+                for op in CleanupOp::all() {
+                    let mut enabled = cleanup.ops.contains(&op);
+                    if ui.checkbox(&mut enabled, t(op.label_key())).changed() {
+                        if enabled {
+                            cleanup.ops.push(op);
+                        } else {
+                            cleanup.ops.retain(|other| *other != op);
+                        }
+                        changed = true;
+                    }
+                }
+                if let ReplaceScope::Tracks(keys) = &cleanup.scope {
+                    ui.weak(tf("tag_replace_tracks", &[&keys.len().to_string()]));
+                } else {
+                    ui.weak(t("tag_replace_library"));
+                }
+
+                ui.separator();
+                if cleanup.preview.is_empty() {
+                    ui.weak(t("tag_replace_nothing"));
+                } else {
+                    let count = cleanup.preview.len();
+                    ui.label(tf("tag_cleanup_preview", &[&count.to_string()]));
+                    egui::ScrollArea::vertical()
+                        .max_height(240.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("tag_cleanup_preview")
+                                .num_columns(4)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for change in cleanup.preview.iter().take(MAX_PREVIEW) {
+                                        ui.weak(t(change.field.label_key()));
+                                        ui.label(
+                                            egui::RichText::new(&change.before).strikethrough(),
+                                        );
+                                        ui.label("→");
+                                        ui.label(&change.after);
+                                        ui.end_row();
+                                    }
+                                });
+                            if count > MAX_PREVIEW {
+                                ui.weak(tf(
+                                    "tag_replace_more",
+                                    &[&(count - MAX_PREVIEW).to_string()],
+                                ));
+                            }
+                        });
+                }
+
+                ui.separator();
+                let response = ui.add_enabled(
+                    !cleanup.preview.is_empty(),
+                    egui::Button::new(t("tag_cleanup_apply")),
+                );
+                let response = if read_only {
+                    response.on_hover_text(t("read_only_edit_hint"))
+                } else {
+                    response
+                };
+                apply = response.clicked();
+            });
+
+        if changed {
+            ctx.update_tag_cleanup_preview();
+        }
+        if apply {
+            ctx.apply_tag_cleanup(ui.ctx());
+        }
+        if !open {
+            ctx.tag_cleanup = None;
+        }
+    }
+}
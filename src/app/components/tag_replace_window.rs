@@ -0,0 +1,139 @@
+use super::AppComponent;
+use crate::app::tag_replace::{ReplaceScope, MAX_PREVIEW, REPLACE_FIELDS};
+use crate::app::{t, tf, App};
+use eframe::egui::{self, Window};
+
+pub struct TagReplaceWindow;
+
+impl AppComponent for TagReplaceWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if ctx.tag_replace.is_none() {
+            return;
+        }
+
+        let mut open = true;
+        let mut changed = false;
+        let mut apply = false;
+        let read_only = ctx.read_only_library;
+        Window::new(t("tag_replace"))
+            .collapsible(false)
+            .default_width(460.0)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                let Some(replace) = &mut ctx.tag_replace else {
+                    return;
+                };
+
+                egui::Grid::new("tag_replace_options")
+                    .num_columns(2)
+                    .spacing([10.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label(t("tag_replace_field"));
+                        egui::ComboBox::from_id_salt("tag_replace_field")
+                            .selected_text(t(replace.field.label_key()))
+                            .show_ui(ui, |ui| {
+                                for field in REPLACE_FIELDS {
+                                    changed |= ui
+                                        .selectable_value(
+                                            &mut replace.field,
+                                            field,
+                                            t(field.label_key()),
+                                        )
+                                        .changed();
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label(t("tag_replace_find"));
+                        changed |= ui.text_edit_singleline(&mut replace.find).changed();
+                        ui.end_row();
+
+                        ui.label(t("tag_replace_with"));
+                        changed |= ui.text_edit_singleline(&mut replace.replace).changed();
+                        ui.end_row();
+
+                        ui.label("");
+                        ui.horizontal(|ui| {
+                            changed |= ui
+                                .checkbox(&mut replace.use_regex, t("tag_replace_regex"))
+                                .on_hover_text(t("tag_replace_regex_hint"))
+                                .changed();
+                            changed |= ui
+                                .checkbox(&mut replace.case_sensitive, t("tag_replace_case"))
+                                .changed();
+                        });
+                        ui.end_row();
+
+                        ui.label(t("tag_replace_scope"));
+                        if let ReplaceScope::Tracks(keys) = &replace.scope {
+                            ui.label(tf("tag_replace_tracks", &[&keys.len().to_string()]));
+                        } else {
+                            ui.label(t("tag_replace_library"));
+                        }
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                match &replace.preview {
+                    Err(error) => {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                    Ok(changes) if changes.is_empty() => {
+                        ui.weak(t("tag_replace_nothing"));
+                    }
+                    Ok(changes) => {
+                        ui.label(tf("tag_replace_preview", &[&changes.len().to_string()]));
+                        egui::ScrollArea::vertical()
+                            .max_height(240.0)
+                            .show(ui, |ui| {
+                                egui::Grid::new("tag_replace_preview")
+                                    .num_columns(3)
+                                    .striped(true)
+                                    .show(ui, |ui| {
+                                        for change in changes.iter().take(MAX_PREVIEW) {
+                                            ui.label(
+                                                egui::RichText::new(&change.before).strikethrough(),
+                                            );
+                                            ui.label("→");
+                                            ui.label(&change.after);
+                                            ui.end_row();
+                                        }
+                                    });
+                                if changes.len() > MAX_PREVIEW {
+                                    ui.weak(tf(
+                                        "tag_replace_more",
+                                        &[&(changes.len() - MAX_PREVIEW).to_string()],
+                                    ));
+                                }
+                            });
+                    }
+                }
+
+                ui.separator();
+                let has_changes = replace
+                    .preview
+                    .as_ref()
+                    .is_ok_and(|changes| !changes.is_empty());
+                let response =
+                    ui.add_enabled(has_changes, egui::Button::new(t("tag_replace_apply")));
+                let response = if read_only {
+                    response.on_hover_text(t("read_only_edit_hint"))
+                } else {
+                    response
+                };
+                apply = response.clicked();
+            });
+
+        if changed {
+            ctx.update_tag_replace_preview();
+        }
+        if apply {
+            ctx.apply_tag_replace(ui.ctx());
+        }
+        if !open {
+            ctx.tag_replace = None;
+        }
+    }
+}
@@ -1,6 +1,18 @@
+use super::export_window::ExportWindow;
+use super::goto_time_window::GotoTimeWindow;
+use super::history_window::HistoryWindow;
 use super::language_selector::LanguageSelector;
+use super::least_listened_window::LeastListenedWindow;
+use super::onboarding_window::OnboardingWindow;
+use super::properties_window::PropertiesWindow;
+use super::report_window::ReportWindow;
+use super::settings_window::SettingsWindow;
+use super::tag_cleanup_window::TagCleanupWindow;
+use super::tag_replace_window::TagReplaceWindow;
 use super::AppComponent;
+use crate::app::playlist_actions::JUMP_TO_PLAYING_SHORTCUT;
 use crate::app::t;
+use crate::app::tag_replace::ReplaceScope;
 use crate::app::version_info;
 use crate::app::App;
 use crate::app::{DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
@@ -32,11 +44,18 @@ impl AppComponent for WindowChrome {
                     }
                     ui.close_menu();
                 }
-                let settings_label =
-                    egui::RichText::new(t("settings")).text_style(egui::TextStyle::Button);
-                ui.add_enabled_ui(false, |ui| ui.button(settings_label))
-                    .response
-                    .on_hover_text("Not implemented yet");
+                if ui.button(t("settings")).clicked() {
+                    ctx.show_settings_dialog = true;
+                    ui.close_menu();
+                }
+                if ui.button(t("tag_replace_menu")).clicked() {
+                    ctx.open_tag_replace(ReplaceScope::Library);
+                    ui.close_menu();
+                }
+                if ui.button(t("tag_cleanup_menu")).clicked() {
+                    ctx.open_tag_cleanup(ReplaceScope::Library);
+                    ui.close_menu();
+                }
                 ui.separator();
                 if ui.button(t("exit")).clicked() {
                     ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
@@ -46,6 +65,8 @@ impl AppComponent for WindowChrome {
 
             // Add Playback menu
             ui.menu_button(t("playback"), |ui| {
+                let mut skip_previous = false;
+                let mut skip_next = false;
                 if let Some(player) = &mut ctx.player {
                     if let Some(_selected_track) = &player.selected_track {
                         if ui.button(t("play_pause")).clicked() {
@@ -60,15 +81,11 @@ impl AppComponent for WindowChrome {
                             ui.close_menu();
                         }
                         if ui.button(t("previous")).clicked() {
-                            if let Some(playing_playlist_idx) = ctx.playing_playlist_idx {
-                                player.previous(&ctx.playlists[playing_playlist_idx]);
-                            }
+                            skip_previous = true;
                             ui.close_menu();
                         }
                         if ui.button(t("next")).clicked() {
-                            if let Some(playing_playlist_idx) = ctx.playing_playlist_idx {
-                                player.next(&ctx.playlists[playing_playlist_idx]);
-                            }
+                            skip_next = true;
                             ui.close_menu();
                         }
                         ui.separator();
@@ -86,6 +103,11 @@ impl AppComponent for WindowChrome {
                             player.toggle_playback_mode();
                             ui.close_menu();
                         }
+                        ui.checkbox(&mut player.stop_after_current, t("stop_after_current"));
+                        if ui.button(t("goto_time")).clicked() {
+                            ctx.show_goto_time_dialog = true;
+                            ui.close_menu();
+                        }
                         ui.separator();
                         if ui.button(t("restore_window")).clicked() {
                             ui.ctx().send_viewport_cmd(egui::ViewportCommand::InnerSize(
@@ -103,9 +125,66 @@ impl AppComponent for WindowChrome {
                         });
                     }
                 }
+                if skip_previous {
+                    ctx.previous_track();
+                }
+                if skip_next {
+                    ctx.skip_track();
+                }
+                let shortcut = ui.ctx().format_shortcut(&JUMP_TO_PLAYING_SHORTCUT);
+                if ui
+                    .add_enabled(
+                        ctx.playing_playlist_elsewhere().is_some(),
+                        egui::Button::new(t("jump_to_playing")).shortcut_text(shortcut),
+                    )
+                    .clicked()
+                {
+                    ctx.jump_to_playing_playlist();
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button(t("history")).clicked() {
+                    ctx.show_history_dialog = true;
+                    ctx.reload_history();
+                    ui.close_menu();
+                }
+                if ui
+                    .button(t("least_listened_albums"))
+                    .on_hover_text(t("least_listened_hint"))
+                    .clicked()
+                {
+                    ctx.show_least_listened = true;
+                    ctx.reload_album_stats();
+                    ui.close_menu();
+                }
+                if ui.button(t("listening_report")).clicked() {
+                    ctx.show_report = true;
+                    ctx.reload_report();
+                    ui.close_menu();
+                }
             });
 
             ui.menu_button(t("help"), |ui| {
+                if ui.button(t("open_logs")).clicked() {
+                    ctx.open_log_dir();
+                    ui.close_menu();
+                }
+                if ui
+                    .button(t("copy_diagnostics"))
+                    .on_hover_text(t("copy_diagnostics_hint"))
+                    .clicked()
+                {
+                    ui.ctx().copy_text(ctx.diagnostics());
+                    ui.close_menu();
+                }
+                if ui
+                    .checkbox(&mut ctx.show_perf_overlay, t("perf_overlay"))
+                    .on_hover_text(t("perf_overlay_hint"))
+                    .clicked()
+                {
+                    ui.close_menu();
+                }
+                ui.separator();
                 if ui.button(t("about")).clicked() {
                     ctx.show_about_dialog = true;
                     ui.close_menu();
@@ -206,5 +285,16 @@ impl AppComponent for WindowChrome {
                     });
                 });
         }
+
+        SettingsWindow::add(ctx, ui);
+        HistoryWindow::add(ctx, ui);
+        LeastListenedWindow::add(ctx, ui);
+        ReportWindow::add(ctx, ui);
+        ExportWindow::add(ctx, ui);
+        GotoTimeWindow::add(ctx, ui);
+        PropertiesWindow::add(ctx, ui);
+        TagReplaceWindow::add(ctx, ui);
+        TagCleanupWindow::add(ctx, ui);
+        OnboardingWindow::add(ctx, ui);
     }
 }
@@ -0,0 +1,87 @@
+//! What a bug report needs: the version, the system, the audio device and the end of
+//! the log, copied in one go from the Help menu.
+
+use super::{version_info, App};
+use std::fmt::Write;
+use std::path::Path;
+
+// Enough log for the last few minutes of a session
+const RECENT_LOG_BYTES: u64 = 64 * 1024;
+
+impl App {
+    pub fn diagnostics(&self) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "Bird Player {}", version_info::formatted_version());
+        let _ = writeln!(
+            report,
+            "OS: {} ({}, {})",
+            std::env::consts::OS,
+            std::env::consts::FAMILY,
+            std::env::consts::ARCH
+        );
+
+        match self
+            .player
+            .as_ref()
+            .and_then(|player| player.output_info.as_ref())
+        {
+            Some(info) => {
+                let _ = writeln!(
+                    report,
+                    "Audio device: {} ({}, {} Hz, source {} Hz)",
+                    info.device_name, info.sample_format, info.device_rate, info.source_rate
+                );
+            }
+            None => {
+                let _ = writeln!(report, "Audio device: not opened yet");
+            }
+        }
+        let buffer = crate::buffer_stats::OUTPUT_BUFFER.report();
+        if buffer.len > 0 {
+            let _ = writeln!(
+                report,
+                "Output buffer: {} samples, {}% full, {} underruns, {} overflows",
+                buffer.len,
+                buffer.fill_percent(),
+                buffer.underruns,
+                buffer.overflows
+            );
+        }
+        let _ = writeln!(
+            report,
+            "Library: {} folders, {} tracks, {} playlists",
+            self.library.paths().len(),
+            self.library.items().len(),
+            self.playlists.len()
+        );
+        let _ = writeln!(report, "Database: {}", self.database.is_some());
+
+        let _ = writeln!(report, "\nRecent log:");
+        match crate::logging::recent_log(&App::get_log_dir(), RECENT_LOG_BYTES) {
+            Ok(log) => report.push_str(&log),
+            Err(e) => {
+                let _ = writeln!(report, "(unavailable: {})", e);
+            }
+        }
+        report
+    }
+
+    /// Shows the log folder in the system's file manager.
+    pub fn open_log_dir(&self) {
+        if let Err(e) = open_in_file_manager(&App::get_log_dir()) {
+            tracing::error!("Failed to open the log folder: {}", e);
+        }
+    }
+}
+
+fn open_in_file_manager(dir: &Path) -> std::io::Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(program).arg(dir).spawn()?;
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use super::library::LibraryItem;
+use super::player::{PlaybackMode, TrackState};
+use super::{App, UiCommand};
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+// Save player state this often during playback
+const PERSIST_INTERVAL: Duration = Duration::from_secs(30);
+// Stop skipping when this many tracks in a row couldn't be played
+const MAX_FAILED_IN_A_ROW: usize = 10;
+
+/// Something that happened in playback, delivered to every subscriber.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerEvent {
+    /// The audio thread loaded a track.
+    TrackChanged(Box<LibraryItem>),
+    /// Length of the loaded track, in its time base.
+    Duration(u64),
+    /// Current position, in the time base of the track.
+    Position(u64),
+    PlaybackStateChanged(bool), // true = playing, false = paused
+    /// The track played to its end.
+    Finished,
+    /// The track couldn't be played, with the reason.
+    Failed(String),
+}
+
+/// Hands playback events to any number of subscribers, each with its own receiver,
+/// so subsystems don't compete for the single channel from the audio thread.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Vec<Sender<PlayerEvent>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self) -> Receiver<PlayerEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Sends an event to all subscribers, dropping those whose receiver is gone.
+    pub fn publish(&mut self, event: PlayerEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+impl App {
+    /// Drains the messages of the audio thread, updates the player and publishes
+    /// the matching events. Runs once per frame before any component.
+    pub fn process_ui_commands(&mut self) {
+        if self.history_events.is_none() {
+            self.history_events = Some(self.events.subscribe());
+        }
+
+        while let Some(command) = self
+            .player
+            .as_ref()
+            .and_then(|player| player.ui_rx.try_recv().ok())
+        {
+            self.handle_ui_command(command);
+            // Before the next command can select another track
+            self.follow_history();
+        }
+    }
+
+    fn handle_ui_command(&mut self, command: UiCommand) {
+        match command {
+            UiCommand::CurrentTimestamp(seek_timestamp) => {
+                if self.last_persisted.elapsed() > PERSIST_INTERVAL {
+                    self.save_listened_time();
+                    self.update_player_persistence();
+                    self.save_state();
+                    self.last_persisted = Instant::now();
+                }
+
+                if let Some(player) = &mut self.player {
+                    player.set_seek_to_timestamp(seek_timestamp);
+                    player.remember_position();
+                    player.failed_in_a_row = 0;
+                }
+                self.prefetch_next_track();
+
+                self.events.publish(PlayerEvent::Position(seek_timestamp));
+            }
+            UiCommand::TrackTimeBase(numer, denom) => {
+                if let Some(player) = &mut self.player {
+                    player.time_base = Some((numer, denom));
+                }
+            }
+            UiCommand::TrackFormat(format) => {
+                if let Some(player) = &mut self.player {
+                    player.track_format = Some(format);
+                }
+            }
+            UiCommand::OutputInfo(info) => {
+                tracing::info!("Audio output: {:?}", info);
+                if let Some(player) = &mut self.player {
+                    player.output_info = Some(info);
+                }
+            }
+            UiCommand::TotalTrackDuration(dur) => {
+                tracing::info!("Received Duration: {}", dur);
+                if let Some(player) = &mut self.player {
+                    player.set_duration(dur);
+                    player.loading = false;
+                }
+
+                // The duration arrives once the audio thread loaded the track
+                if let Some(track) = self
+                    .player
+                    .as_ref()
+                    .and_then(|player| player.selected_track.clone())
+                {
+                    self.events
+                        .publish(PlayerEvent::TrackChanged(Box::new(track)));
+                }
+                self.events.publish(PlayerEvent::Duration(dur));
+            }
+            UiCommand::AudioFinished if self.playback_lost_volume() => {
+                // Resumed once the volume is back
+                tracing::warn!("Track became unreachable, waiting for its volume");
+            }
+            UiCommand::AudioFinished => {
+                self.events.publish(PlayerEvent::Finished);
+
+                // A finished track starts from the beginning next time
+                if let Some(player) = &mut self.player {
+                    player.forget_position();
+                }
+
+                let stop_requested = self
+                    .player
+                    .as_mut()
+                    .is_some_and(|player| player.take_stop_after());
+
+                if stop_requested {
+                    // The audio thread is already stopped at the end of the track
+                    tracing::info!("Track finished, stopping as requested");
+                    if let Some(player) = &mut self.player {
+                        player.track_state = TrackState::Stopped;
+                        player.seek_to_timestamp = 0;
+                    }
+                } else {
+                    tracing::info!("Track finished, getting next...");
+                    self.next_track();
+                }
+            }
+            UiCommand::PlaybackStateChanged(is_playing) => {
+                tracing::info!(
+                    "Playback state changed to: {}",
+                    if is_playing { "Playing" } else { "Paused" }
+                );
+                if let Some(player) = &mut self.player {
+                    player.track_state = if is_playing {
+                        TrackState::Playing
+                    } else {
+                        TrackState::Paused
+                    };
+                }
+                self.events
+                    .publish(PlayerEvent::PlaybackStateChanged(is_playing));
+            }
+            UiCommand::PlaybackError(message) => {
+                tracing::error!("Couldn't play track: {}", message);
+                self.events.publish(PlayerEvent::Failed(message.clone()));
+
+                // The audio thread dropped the track, nothing is playing
+                let Some(player) = &mut self.player else {
+                    return;
+                };
+                player.track_state = TrackState::Stopped;
+                player.seek_to_timestamp = 0;
+                player.loading = false;
+                player.playback_error = Some(message);
+                player.failed_in_a_row += 1;
+
+                // Repeating the same track or a playlist of broken files would never end
+                let give_up = player.playback_mode == PlaybackMode::RepeatOne
+                    || player.failed_in_a_row >= MAX_FAILED_IN_A_ROW;
+                if give_up {
+                    tracing::warn!("Not skipping after {} failures", player.failed_in_a_row);
+                    player.failed_in_a_row = 0;
+                } else {
+                    self.next_track();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_subscriber_receives_events_until_it_goes_away() {
+        let mut bus = EventBus::default();
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+
+        bus.publish(PlayerEvent::Duration(10));
+        assert_eq!(first.try_recv(), Ok(PlayerEvent::Duration(10)));
+        assert_eq!(second.try_recv(), Ok(PlayerEvent::Duration(10)));
+
+        drop(first);
+        bus.publish(PlayerEvent::Finished);
+        assert_eq!(second.try_recv(), Ok(PlayerEvent::Finished));
+        assert_eq!(bus.subscribers.len(), 1);
+    }
+}
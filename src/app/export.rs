@@ -0,0 +1,196 @@
+use super::library::LibraryItem;
+use super::App;
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub const DEFAULT_EXPORT_PATTERN: &str = "{n} - {artist} - {title}";
+
+/// Progress of a playlist being copied to a folder, shared with the copying thread.
+#[derive(Debug, Clone)]
+pub struct PlaylistExport {
+    pub playlist_name: String,
+    pub target: PathBuf,
+    pub total: usize,
+    pub copied: Arc<AtomicUsize>,
+    pub failed: Arc<AtomicUsize>,
+    pub running: Arc<AtomicBool>,
+    pub cancel: Arc<AtomicBool>,
+}
+
+impl PlaylistExport {
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        let done = self.copied.load(Ordering::Relaxed) + self.failed.load(Ordering::Relaxed);
+        done as f32 / self.total as f32
+    }
+}
+
+// Characters that aren't allowed in file names on common file systems, FAT included
+fn sanitize(part: &str) -> String {
+    part.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// File name of an exported track. The pattern may use {n} (position in the
+/// playlist), {track}, {artist}, {album} and {title}; the extension is kept.
+/// An empty pattern keeps the original file name.
+pub fn export_file_name(pattern: &str, track: &LibraryItem, position: usize) -> String {
+    let path = track.path();
+    let original_stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let stem = if pattern.trim().is_empty() {
+        original_stem.clone()
+    } else {
+        pattern
+            .replace("{n}", &format!("{:02}", position + 1))
+            .replace(
+                "{track}",
+                &track
+                    .track_number()
+                    .map(|n| format!("{:02}", n))
+                    .unwrap_or_default(),
+            )
+            .replace("{artist}", &track.artist().unwrap_or_default())
+            .replace("{album}", &track.album().unwrap_or_default())
+            .replace("{title}", &track.title().unwrap_or(original_stem.clone()))
+    };
+
+    let stem = match sanitize(&stem) {
+        stem if stem.is_empty() => sanitize(&original_stem),
+        stem => stem,
+    };
+
+    match path.extension() {
+        Some(extension) => format!("{}.{}", stem, extension.to_string_lossy()),
+        None => stem,
+    }
+}
+
+// Never overwrite: "name.mp3" becomes "name (2).mp3" if taken
+fn unique_path(dir: &Path, file_name: &str) -> PathBuf {
+    let candidate = dir.join(file_name);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let file_name = Path::new(file_name);
+    let stem = file_name
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = file_name
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    (2..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+impl App {
+    /// Copies the audio files of a playlist into `target` on a background thread,
+    /// renaming them by `export_pattern`. Files keep their format.
+    pub fn export_playlist(&mut self, idx: usize, target: PathBuf) {
+        if self
+            .playlist_export
+            .as_ref()
+            .is_some_and(|e| e.is_running())
+        {
+            tracing::info!("A playlist export is already running");
+            return;
+        }
+
+        let Some(playlist) = self.playlists.get(idx) else {
+            return;
+        };
+
+        let tracks = playlist.tracks.clone();
+        let pattern = self.export_pattern.clone();
+        let export = PlaylistExport {
+            playlist_name: playlist.get_name().unwrap_or_default(),
+            target: target.clone(),
+            total: tracks.len(),
+            copied: Arc::new(AtomicUsize::new(0)),
+            failed: Arc::new(AtomicUsize::new(0)),
+            running: Arc::new(AtomicBool::new(true)),
+            cancel: Arc::new(AtomicBool::new(false)),
+        };
+        self.playlist_export = Some(export.clone());
+
+        std::thread::spawn(move || {
+            tracing::info!("Exporting {} tracks to {:?}", tracks.len(), target);
+
+            for (position, track) in tracks.iter().enumerate() {
+                if export.cancel.load(Ordering::Relaxed) {
+                    tracing::info!("Playlist export cancelled");
+                    break;
+                }
+
+                let file_name = export_file_name(&pattern, track, position);
+                let destination = unique_path(&target, &file_name);
+
+                match std::fs::copy(track.path(), &destination) {
+                    Ok(_) => {
+                        export.copied.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            "Failed to copy {:?} to {:?}: {}",
+                            track.path(),
+                            destination,
+                            e
+                        );
+                        export.failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+
+            export.running.store(false, Ordering::Relaxed);
+            tracing::info!("Playlist export finished");
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+
+    #[test]
+    fn export_file_name_fills_pattern_and_keeps_extension() {
+        let mut track =
+            LibraryItem::new(PathBuf::from("/music/01 track.flac"), LibraryPathId::new(0));
+        track.set_title(Some("What? / Why"));
+        track.set_artist(Some("AC/DC"));
+        track.set_track_number(Some(3));
+
+        assert_eq!(
+            export_file_name("{n} - {artist} - {title}", &track, 0),
+            "01 - AC_DC - What_ _ Why.flac"
+        );
+        assert_eq!(export_file_name("{track} {album}", &track, 0), "03.flac");
+        assert_eq!(export_file_name("", &track, 0), "01 track.flac");
+        assert_eq!(export_file_name("{album}", &track, 0), "01 track.flac");
+    }
+}
@@ -0,0 +1,183 @@
+//! Tracks marked with a heart. They're kept in the database and gathered in a Favorites
+//! playlist that's pinned after Recently Added while there are any.
+
+use super::library::{LibraryItem, TrackKey};
+use super::playlist::{Playlist, VirtualPlaylist};
+use super::{t, App};
+
+use rusqlite::{Connection, Result as SqlResult};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The favorite tracks with when they were marked.
+#[derive(Debug, Clone, Default)]
+pub struct Favorites(HashMap<TrackKey, i64>);
+
+impl Favorites {
+    pub fn contains(&self, key: TrackKey) -> bool {
+        self.0.contains_key(&key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The library's favorite tracks, the ones marked last first.
+pub fn favorite_tracks(items: &[LibraryItem], favorites: &Favorites) -> Vec<LibraryItem> {
+    let mut tracks: Vec<(i64, LibraryItem)> = items
+        .iter()
+        .filter_map(|item| Some((*favorites.0.get(&item.key())?, item.clone())))
+        .collect();
+    tracks.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.path().cmp(&b.1.path())));
+    tracks.into_iter().map(|(_, track)| track).collect()
+}
+
+fn load(conn: &Arc<Mutex<Connection>>) -> SqlResult<Favorites> {
+    let conn_guard = conn.lock().unwrap();
+    let mut stmt = conn_guard.prepare("SELECT library_item_id, added_at FROM favorites")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    })?;
+
+    let mut favorites = HashMap::new();
+    for row in rows {
+        let (key, added_at) = row?;
+        if let Ok(key) = key.parse::<TrackKey>() {
+            favorites.insert(key, added_at);
+        }
+    }
+    Ok(Favorites(favorites))
+}
+
+impl App {
+    pub fn load_favorites(&mut self) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match load(&db.connection()) {
+            Ok(favorites) => self.favorites = favorites,
+            Err(e) => tracing::error!("Failed to load favorites: {}", e),
+        }
+    }
+
+    /// Hearts the track, or takes the heart away again.
+    pub fn toggle_favorite(&mut self, key: TrackKey) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        let favorite = !self.favorites.contains(key);
+
+        // The track must be saved in the library before a favorite can refer to it
+        db.wait_for_writes();
+        let conn = db.connection();
+        let conn_guard = conn.lock().unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        let result = if favorite {
+            conn_guard.execute(
+                "INSERT OR IGNORE INTO favorites (library_item_id, added_at)
+                 SELECT ?1, ?2 WHERE EXISTS (SELECT 1 FROM library_items WHERE key = ?1)",
+                rusqlite::params![key.to_string(), now],
+            )
+        } else {
+            conn_guard.execute(
+                "DELETE FROM favorites WHERE library_item_id = ?1",
+                rusqlite::params![key.to_string()],
+            )
+        };
+        drop(conn_guard);
+
+        match result {
+            // Tracks outside the library can't be kept
+            Ok(0) if favorite => {
+                tracing::warn!("{} isn't in the library, not a favorite", key);
+                return;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                tracing::error!("Failed to change the favorites: {}", e);
+                return;
+            }
+        }
+
+        if favorite {
+            self.favorites.0.insert(key, now);
+        } else {
+            self.favorites.0.remove(&key);
+        }
+        self.refresh_favorites();
+    }
+
+    fn favorites_idx(&self) -> Option<usize> {
+        self.playlists
+            .iter()
+            .position(|p| p.virtual_kind == Some(VirtualPlaylist::Favorites))
+    }
+
+    /// Rebuilds the Favorites playlist from the library, it goes away with the last
+    /// favorite.
+    pub fn refresh_favorites(&mut self) {
+        let tracks = favorite_tracks(self.library.items(), &self.favorites);
+        let existing = self.favorites_idx();
+
+        if tracks.is_empty() {
+            if let Some(idx) = existing {
+                self.playlists.remove(idx);
+                self.shift_playlist_indices(idx, false);
+            }
+            return;
+        }
+
+        let idx = match existing {
+            Some(idx) => idx,
+            None => {
+                // Right after Recently Added, which is the first tab
+                let idx = self
+                    .playlists
+                    .iter()
+                    .position(|p| p.virtual_kind == Some(VirtualPlaylist::RecentlyAdded))
+                    .map_or(0, |idx| idx + 1);
+                self.playlists
+                    .insert(idx, Playlist::new_virtual(VirtualPlaylist::Favorites));
+                self.shift_playlist_indices(idx, true);
+                idx
+            }
+        };
+
+        let playlist = &mut self.playlists[idx];
+        playlist.set_name(t("favorites"));
+        if playlist.tracks != tracks {
+            playlist.tracks = tracks;
+            playlist.selected_indices.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+    use std::path::PathBuf;
+
+    #[test]
+    fn favorites_list_the_ones_marked_last_first() {
+        let items: Vec<LibraryItem> = ["/a.mp3", "/b.mp3", "/c.mp3"]
+            .iter()
+            .map(|path| LibraryItem::new(PathBuf::from(path), LibraryPathId::new(0)))
+            .collect();
+        let mut favorites = Favorites::default();
+        favorites.0.insert(items[0].key(), 100);
+        favorites.0.insert(items[2].key(), 200);
+        // Removed from the library since
+        favorites.0.insert(TrackKey::nil(), 300);
+
+        let paths: Vec<PathBuf> = favorite_tracks(&items, &favorites)
+            .iter()
+            .map(LibraryItem::path)
+            .collect();
+        assert_eq!(paths, [PathBuf::from("/c.mp3"), PathBuf::from("/a.mp3")]);
+    }
+}
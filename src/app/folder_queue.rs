@@ -0,0 +1,110 @@
+use super::album_stats::AlbumKey;
+use super::library::{LibraryItem, LibraryPathId};
+use super::playlist::Playlist;
+use super::App;
+
+/// Orders a folder's tracks the way an album plays: by track number, then by
+/// file name. Tracks without a number go after the numbered ones.
+pub fn album_order(tracks: &mut [LibraryItem]) {
+    tracks.sort_by(|a, b| {
+        let by_number = match (a.track_number(), b.track_number()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        };
+
+        by_number.then_with(|| a.path().file_name().cmp(&b.path().file_name()))
+    });
+}
+
+impl App {
+    /// Plays all tracks of a library folder as a temporary album. The tracks go into
+    /// a transient queue instead of a saved playlist.
+    pub fn play_folder_as_album(&mut self, path_id: LibraryPathId) {
+        let tracks: Vec<LibraryItem> = self
+            .library
+            .items()
+            .iter()
+            .filter(|item| item.library_id() == path_id)
+            .cloned()
+            .collect();
+        let name = self
+            .library
+            .paths()
+            .iter()
+            .find(|p| p.id() == path_id)
+            .map(|lib_path| lib_path.display_name().to_string());
+        self.play_in_queue(tracks, name);
+    }
+
+    /// Plays an album's tracks, wherever they are in the library, like a folder.
+    pub fn play_album(&mut self, album: &AlbumKey) {
+        let tracks: Vec<LibraryItem> = self
+            .library
+            .items()
+            .iter()
+            .filter(|item| item.album_key().as_ref() == Some(album))
+            .cloned()
+            .collect();
+        self.play_in_queue(tracks, Some(album.1.clone()));
+    }
+
+    fn play_in_queue(&mut self, mut tracks: Vec<LibraryItem>, name: Option<String>) {
+        if tracks.is_empty() {
+            return;
+        }
+
+        album_order(&mut tracks);
+
+        let mut queue = Playlist::new();
+        if let Some(name) = name {
+            queue.set_name(name);
+        }
+        for track in tracks {
+            queue.add(track);
+        }
+
+        if let Some(player) = &mut self.player {
+            player.select_track(queue.tracks.first().cloned());
+            player.play();
+        }
+
+        self.queue = Some(queue);
+    }
+
+    pub fn next_track(&mut self) {
+        // The transient queue takes over from the playing playlist while it's active
+        let source = self.queue.as_ref().or_else(|| {
+            self.playing_playlist_idx
+                .and_then(|idx| self.playlists.get(idx))
+        });
+
+        if let (Some(player), Some(source)) = (&mut self.player, source) {
+            player.next(source);
+        }
+    }
+
+    /// Reads ahead whatever `next_track` will play, see `Player::prefetch_next`.
+    pub fn prefetch_next_track(&mut self) {
+        let source = self.queue.as_ref().or_else(|| {
+            self.playing_playlist_idx
+                .and_then(|idx| self.playlists.get(idx))
+        });
+
+        if let (Some(player), Some(source)) = (&mut self.player, source) {
+            player.prefetch_next(source);
+        }
+    }
+
+    pub fn previous_track(&mut self) {
+        let source = self.queue.as_ref().or_else(|| {
+            self.playing_playlist_idx
+                .and_then(|idx| self.playlists.get(idx))
+        });
+
+        if let (Some(player), Some(source)) = (&mut self.player, source) {
+            player.previous(source);
+        }
+    }
+}
@@ -0,0 +1,610 @@
+use super::events::PlayerEvent;
+use super::library::{LibraryItem, TrackKey};
+use super::playlist::Playlist;
+use super::{tf, App};
+
+use rusqlite::{Connection, Result as SqlResult, ToSql};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Plays further apart than this start a new listening session
+const SESSION_GAP_SECS: i64 = 30 * 60;
+
+// Longer gaps between position updates are pauses, not listening
+const MAX_TICK_GAP: Duration = Duration::from_secs(2);
+
+// Plays the mini-player lists
+const RECENT_PLAYS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub track_key: TrackKey,
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub played_at: i64,          // Unix timestamp
+    pub played_at_local: String, // Formatted by SQLite in local time
+    pub listened_ms: i64,
+}
+
+/// A logged play that's still running, adding up how long audio actually played.
+#[derive(Debug)]
+pub struct Listening {
+    id: i64,
+    listened: Duration,
+    last_tick: Instant,
+}
+
+impl Listening {
+    fn new(id: i64) -> Self {
+        Self {
+            id,
+            listened: Duration::ZERO,
+            last_tick: Instant::now(),
+        }
+    }
+
+    fn tick(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_tick);
+        if elapsed <= MAX_TICK_GAP {
+            self.listened += elapsed;
+        }
+        self.last_tick = now;
+    }
+}
+
+/// File format of a history export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// The format going by the file extension, CSV unless it's `.json`.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ExportFormat::Json,
+            _ => ExportFormat::Csv,
+        }
+    }
+}
+
+// One play as written to an export
+#[derive(Serialize)]
+struct ExportedPlay<'a> {
+    played_at: i64,
+    played_at_local: &'a str,
+    artist: Option<&'a str>,
+    title: Option<&'a str>,
+    path: String,
+    listened_secs: f64,
+}
+
+impl<'a> From<&'a HistoryEntry> for ExportedPlay<'a> {
+    fn from(entry: &'a HistoryEntry) -> Self {
+        Self {
+            played_at: entry.played_at,
+            played_at_local: &entry.played_at_local,
+            artist: entry.artist.as_deref(),
+            title: entry.title.as_deref(),
+            path: entry.path.to_string_lossy().to_string(),
+            listened_secs: entry.listened_ms as f64 / 1000.0,
+        }
+    }
+}
+
+/// Checks a date typed as YYYY-MM-DD. Empty means no limit and gives `Ok(None)`.
+pub fn parse_date(text: &str) -> Result<Option<String>, ()> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let parts: Vec<&str> = text.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(());
+    };
+    let valid = |part: &str, len: usize, max: u32| {
+        part.len() == len
+            && part
+                .parse::<u32>()
+                .is_ok_and(|value| (1..=max).contains(&value))
+    };
+    if valid(year, 4, 9999) && valid(month, 2, 12) && valid(day, 2, 31) {
+        Ok(Some(text.to_string()))
+    } else {
+        Err(())
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Plays as CSV, oldest first, with a header row.
+pub fn to_csv(entries: &[HistoryEntry]) -> String {
+    let mut csv = String::from("played_at,played_at_local,artist,title,path,listened_secs\n");
+    for entry in entries.iter().rev() {
+        let play = ExportedPlay::from(entry);
+        csv.push_str(&format!(
+            "{},{},{},{},{},{:.1}\n",
+            play.played_at,
+            csv_field(play.played_at_local),
+            csv_field(play.artist.unwrap_or_default()),
+            csv_field(play.title.unwrap_or_default()),
+            csv_field(&play.path),
+            play.listened_secs
+        ));
+    }
+    csv
+}
+
+/// Plays as a JSON array, oldest first.
+pub fn to_json(entries: &[HistoryEntry]) -> Result<String, String> {
+    let plays: Vec<ExportedPlay> = entries.iter().rev().map(ExportedPlay::from).collect();
+    serde_json::to_string_pretty(&plays).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryRange {
+    Today,
+    Week,
+    Month,
+    #[default]
+    All,
+}
+
+impl HistoryRange {
+    fn sql_filter(&self) -> &'static str {
+        match self {
+            HistoryRange::Today => {
+                "played_at >= CAST(strftime('%s', 'now', 'localtime', 'start of day', 'utc') AS INTEGER)"
+            }
+            HistoryRange::Week => "played_at >= CAST(strftime('%s', 'now') AS INTEGER) - 7 * 86400",
+            HistoryRange::Month => {
+                "played_at >= CAST(strftime('%s', 'now') AS INTEGER) - 30 * 86400"
+            }
+            HistoryRange::All => "1 = 1",
+        }
+    }
+}
+
+/// Splits entries (newest first) into listening sessions.
+pub fn sessions(entries: &[HistoryEntry]) -> Vec<&[HistoryEntry]> {
+    let mut sessions = Vec::new();
+    let mut start = 0;
+
+    for idx in 1..entries.len() {
+        if entries[idx - 1].played_at - entries[idx].played_at > SESSION_GAP_SECS {
+            sessions.push(&entries[start..idx]);
+            start = idx;
+        }
+    }
+
+    if start < entries.len() {
+        sessions.push(&entries[start..]);
+    }
+
+    sessions
+}
+
+// Database methods
+
+fn record(conn: &Arc<Mutex<Connection>>, track: &LibraryItem) -> SqlResult<i64> {
+    let conn_guard = conn.lock().unwrap();
+    conn_guard.execute(
+        "INSERT INTO play_history (library_item_id, path, title, artist, played_at)
+         VALUES (?1, ?2, ?3, ?4, CAST(strftime('%s', 'now') AS INTEGER))",
+        rusqlite::params![
+            track.key().to_string(),
+            track.path().to_string_lossy().to_string(),
+            track.title(),
+            track.artist(),
+        ],
+    )?;
+
+    Ok(conn_guard.last_insert_rowid())
+}
+
+fn save_listened(conn: &Arc<Mutex<Connection>>, listening: &Listening) -> SqlResult<()> {
+    let conn_guard = conn.lock().unwrap();
+    conn_guard.execute(
+        "UPDATE play_history SET listened_ms = ?1 WHERE id = ?2",
+        rusqlite::params![listening.listened.as_millis() as i64, listening.id],
+    )?;
+
+    Ok(())
+}
+
+pub fn load(conn: &Arc<Mutex<Connection>>, range: HistoryRange) -> SqlResult<Vec<HistoryEntry>> {
+    query(conn, range.sql_filter(), &[])
+}
+
+/// The last `count` plays, newest first.
+fn load_recent(conn: &Arc<Mutex<Connection>>, count: usize) -> SqlResult<Vec<HistoryEntry>> {
+    query(
+        conn,
+        "id IN (SELECT id FROM play_history ORDER BY played_at DESC, id DESC LIMIT ?1)",
+        &[&(count as i64)],
+    )
+}
+
+// Plays between two local dates, both included
+fn load_between(
+    conn: &Arc<Mutex<Connection>>,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> SqlResult<Vec<HistoryEntry>> {
+    query(
+        conn,
+        "(?1 IS NULL OR played_at >= CAST(strftime('%s', ?1, 'utc') AS INTEGER))
+         AND (?2 IS NULL OR played_at < CAST(strftime('%s', ?2, '+1 day', 'utc') AS INTEGER))",
+        &[&from, &to],
+    )
+}
+
+fn query(
+    conn: &Arc<Mutex<Connection>>,
+    filter: &str,
+    params: &[&dyn ToSql],
+) -> SqlResult<Vec<HistoryEntry>> {
+    let conn_guard = conn.lock().unwrap();
+    let mut stmt = conn_guard.prepare(&format!(
+        "SELECT id, library_item_id, path, title, artist, played_at,
+                datetime(played_at, 'unixepoch', 'localtime'), listened_ms
+         FROM play_history WHERE {} ORDER BY played_at DESC, id DESC",
+        filter
+    ))?;
+
+    let rows = stmt.query_map(params, |row| {
+        let key_str: String = row.get(1)?;
+        let path: String = row.get(2)?;
+
+        Ok(HistoryEntry {
+            id: row.get(0)?,
+            track_key: key_str.parse::<TrackKey>().unwrap_or_default(),
+            path: PathBuf::from(path),
+            title: row.get(3)?,
+            artist: row.get(4)?,
+            played_at: row.get(5)?,
+            played_at_local: row.get(6)?,
+            listened_ms: row.get(7)?,
+        })
+    })?;
+
+    rows.collect()
+}
+
+fn prune(conn: &Arc<Mutex<Connection>>, retention_days: u32) -> SqlResult<()> {
+    let conn_guard = conn.lock().unwrap();
+    if retention_days == 0 {
+        conn_guard.execute("DELETE FROM play_history", [])?;
+    } else {
+        conn_guard.execute(
+            "DELETE FROM play_history
+             WHERE played_at < CAST(strftime('%s', 'now') AS INTEGER) - ?1 * 86400",
+            rusqlite::params![retention_days],
+        )?;
+    }
+
+    Ok(())
+}
+
+impl App {
+    /// Follows the playback events: a loaded track is logged as played once its audio
+    /// runs, and the play ends with the track.
+    pub fn follow_history(&mut self) {
+        let Some(events) = &self.history_events else {
+            return;
+        };
+        let events: Vec<PlayerEvent> = events.try_iter().collect();
+
+        for event in events {
+            match event {
+                PlayerEvent::Duration(_) => self.history_pending = true,
+                PlayerEvent::Position(_) => {
+                    self.tick_history();
+                    // Audio is actually playing, log the play once
+                    if self.history_pending {
+                        self.history_pending = false;
+                        self.record_history();
+                    }
+                }
+                PlayerEvent::Finished => {
+                    // Whatever plays next, including a repeat, is a new history entry
+                    self.history_pending = true;
+                    self.end_listening();
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Logs the selected track as played.
+    pub fn record_history(&mut self) {
+        let Some(track) = self
+            .player
+            .as_ref()
+            .and_then(|player| player.selected_track.clone())
+        else {
+            return;
+        };
+
+        // The running play ends here, the reload below covers both
+        self.save_listened_time();
+        self.history_listening = None;
+        if let Some(db) = &self.database {
+            match record(&db.connection(), &track) {
+                Ok(id) => self.history_listening = Some(Listening::new(id)),
+                Err(e) => tracing::error!("Failed to record play history: {}", e),
+            }
+        }
+        self.count_play(track.key());
+        self.reload_album_stats();
+
+        if self.show_history_dialog {
+            self.reload_history();
+        }
+        if self.mini_recents_open {
+            self.reload_recent_plays();
+        }
+    }
+
+    /// Ends the running play, keeping how long it was listened to. The mini-player's
+    /// recent plays only change here and when a play starts, not while one runs.
+    pub fn end_listening(&mut self) {
+        if self.history_listening.is_none() {
+            return;
+        }
+        self.save_listened_time();
+        self.history_listening = None;
+        if self.mini_recents_open {
+            self.reload_recent_plays();
+        }
+    }
+
+    /// Counts the time since the last position update as listened, audio is playing.
+    pub fn tick_history(&mut self) {
+        if let Some(listening) = &mut self.history_listening {
+            listening.tick();
+        }
+    }
+
+    /// Writes how long the running play has been listened to so far.
+    pub fn save_listened_time(&self) {
+        let (Some(listening), Some(db)) = (&self.history_listening, &self.database) else {
+            return;
+        };
+        if let Err(e) = save_listened(&db.connection(), listening) {
+            tracing::error!("Failed to save listened time: {}", e);
+        }
+    }
+
+    /// Writes the plays between two local dates (YYYY-MM-DD, empty for no limit) to
+    /// `target`, in the format its extension asks for. Returns the number of plays.
+    pub fn export_history(&self, target: &Path, from: &str, to: &str) -> Result<usize, String> {
+        let from = parse_date(from).map_err(|_| tf("invalid_date", &[from]))?;
+        let to = parse_date(to).map_err(|_| tf("invalid_date", &[to]))?;
+        let Some(db) = &self.database else {
+            return Err("no database".to_string());
+        };
+
+        self.save_listened_time();
+        let entries = load_between(&db.connection(), from.as_deref(), to.as_deref())
+            .map_err(|e| e.to_string())?;
+        let contents = match ExportFormat::from_path(target) {
+            ExportFormat::Csv => to_csv(&entries),
+            ExportFormat::Json => to_json(&entries)?,
+        };
+        std::fs::write(target, contents).map_err(|e| e.to_string())?;
+
+        tracing::info!("Exported {} plays to {:?}", entries.len(), target);
+        Ok(entries.len())
+    }
+
+    pub fn reload_history(&mut self) {
+        if let Some(db) = &self.database {
+            match load(&db.connection(), self.history_range) {
+                Ok(entries) => self.history_entries = entries,
+                Err(e) => tracing::error!("Failed to load play history: {}", e),
+            }
+        }
+    }
+
+    /// Loads the plays the mini-player lists, leaving out the one still playing.
+    pub fn reload_recent_plays(&mut self) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match load_recent(&db.connection(), RECENT_PLAYS + 1) {
+            Ok(mut entries) => {
+                let playing = self
+                    .history_listening
+                    .as_ref()
+                    .map(|listening| listening.id);
+                entries.retain(|entry| Some(entry.id) != playing);
+                entries.truncate(RECENT_PLAYS);
+                self.recent_plays = entries;
+            }
+            Err(e) => tracing::error!("Failed to load recent plays: {}", e),
+        }
+    }
+
+    /// Drops history older than the retention setting (0 keeps everything).
+    pub fn prune_history(&mut self) {
+        if self.history_retention_days == 0 {
+            return;
+        }
+
+        if let Some(db) = &self.database {
+            if let Err(e) = prune(&db.connection(), self.history_retention_days) {
+                tracing::error!("Failed to prune play history: {}", e);
+            }
+        }
+    }
+
+    pub fn clear_history(&mut self) {
+        if let Some(db) = &self.database {
+            if let Err(e) = prune(&db.connection(), 0) {
+                tracing::error!("Failed to clear play history: {}", e);
+            }
+        }
+        self.history_entries.clear();
+        self.recent_plays.clear();
+        self.history_listening = None;
+        self.reload_album_stats();
+    }
+
+    fn history_track(&self, entry: &HistoryEntry) -> Option<LibraryItem> {
+        self.library
+            .items()
+            .iter()
+            .find(|item| item.key() == entry.track_key || item.path() == entry.path)
+            .cloned()
+    }
+
+    pub fn play_history_entry(&mut self, entry: &HistoryEntry) {
+        let Some(track) = self.history_track(entry) else {
+            tracing::warn!(
+                "Track from history is no longer in the library: {:?}",
+                entry.path
+            );
+            return;
+        };
+
+        if let Some(player) = &mut self.player {
+            player.select_track(Some(track));
+            player.play();
+        }
+    }
+
+    /// Creates a new playlist from a listening session, in the order it was played.
+    pub fn add_history_session_to_playlist(&mut self, session: &[HistoryEntry]) {
+        let mut playlist = Playlist::new();
+        let started = session
+            .last()
+            .map(|entry| entry.played_at_local.clone())
+            .unwrap_or_default();
+        playlist.set_name(tf("history_session_playlist", &[&started]));
+
+        for entry in session.iter().rev() {
+            if let Some(track) = self.history_track(entry) {
+                playlist.add(track);
+            }
+        }
+
+        self.playlists.push(playlist);
+        self.current_playlist_idx = Some(self.playlists.len() - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(played_at: i64, title: &str, listened_ms: i64) -> HistoryEntry {
+        HistoryEntry {
+            id: played_at,
+            track_key: TrackKey::nil(),
+            path: PathBuf::from("/music/track.flac"),
+            title: Some(title.to_string()),
+            artist: None,
+            played_at,
+            played_at_local: "2024-05-01 10:00:00".to_string(),
+            listened_ms,
+        }
+    }
+
+    #[test]
+    fn dates_must_be_complete() {
+        assert_eq!(parse_date(" "), Ok(None));
+        assert_eq!(parse_date("2024-05-01"), Ok(Some("2024-05-01".to_string())));
+        assert!(parse_date("2024-5-1").is_err());
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+
+    #[test]
+    fn csv_lists_plays_oldest_first_and_quotes_fields() {
+        let entries = [entry(200, "Second", 1500), entry(100, "One, \"two\"", 0)];
+        let csv = to_csv(&entries);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("100,"));
+        assert!(lines[1].contains(",\"One, \"\"two\"\"\","));
+        assert!(lines[2].ends_with(",Second,/music/track.flac,1.5"));
+    }
+
+    #[test]
+    fn recent_plays_are_the_newest_ones() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE play_history (
+                id INTEGER PRIMARY KEY, library_item_id TEXT NOT NULL, path TEXT NOT NULL,
+                title TEXT, artist TEXT, played_at INTEGER NOT NULL,
+                listened_ms INTEGER NOT NULL DEFAULT 0
+             );
+             INSERT INTO play_history (id, library_item_id, path, title, played_at) VALUES
+                 (1, '', '/a.mp3', 'A', 100), (2, '', '/b.mp3', 'B', 300),
+                 (3, '', '/c.mp3', 'C', 200), (4, '', '/d.mp3', 'D', 300);",
+        )
+        .unwrap();
+
+        let entries = load_recent(&Arc::new(Mutex::new(conn)), 3).unwrap();
+        let ids: Vec<i64> = entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, [4, 2, 3]);
+    }
+
+    #[test]
+    fn plays_are_logged_from_playback_events() {
+        use crate::app::player::Player;
+        use crate::app::UiCommand;
+
+        let (audio_tx, _audio_rx) = std::sync::mpsc::channel();
+        let (ui_tx, ui_rx) = std::sync::mpsc::channel();
+        let mut player = Player::new(audio_tx, ui_rx, Arc::new(Default::default()));
+        let track = LibraryItem::new(
+            PathBuf::from("/a.mp3"),
+            crate::app::library::LibraryPathId::new(0),
+        );
+        player.select_track(Some(track));
+
+        let mut app = App {
+            player: Some(player),
+            database: Some(Arc::new(crate::db::Database::open_in_memory().unwrap())),
+            ..Default::default()
+        };
+        let plays = |app: &App| -> i64 {
+            let conn = app.database.as_ref().unwrap().connection();
+            let conn = conn.lock().unwrap();
+            conn.query_row("SELECT COUNT(*) FROM play_history", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        // Loaded, the play counts once audio runs
+        ui_tx.send(UiCommand::TotalTrackDuration(1000)).unwrap();
+        app.process_ui_commands();
+        assert_eq!(plays(&app), 0);
+        ui_tx.send(UiCommand::CurrentTimestamp(10)).unwrap();
+        ui_tx.send(UiCommand::CurrentTimestamp(20)).unwrap();
+        app.process_ui_commands();
+        assert_eq!(plays(&app), 1);
+        assert!(app.history_listening.is_some());
+
+        // Played again after its end
+        app.player.as_mut().unwrap().playback_mode = crate::app::player::PlaybackMode::RepeatOne;
+        ui_tx.send(UiCommand::AudioFinished).unwrap();
+        ui_tx.send(UiCommand::CurrentTimestamp(0)).unwrap();
+        app.process_ui_commands();
+        assert_eq!(plays(&app), 2);
+    }
+}
@@ -1,6 +1,7 @@
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::RwLock;
 
 // Supported languages
@@ -9,6 +10,10 @@ pub enum Language {
     #[default]
     English,
     Chinese,
+    Japanese,
+    German,
+    French,
+    Spanish,
 }
 
 impl Language {
@@ -16,6 +21,10 @@ impl Language {
         match self {
             Language::English => "en",
             Language::Chinese => "zh",
+            Language::Japanese => "ja",
+            Language::German => "de",
+            Language::French => "fr",
+            Language::Spanish => "es",
         }
     }
 
@@ -23,11 +32,53 @@ impl Language {
         match self {
             Language::English => "En",
             Language::Chinese => "中文",
+            Language::Japanese => "日本語",
+            Language::German => "Deutsch",
+            Language::French => "Français",
+            Language::Spanish => "Español",
         }
     }
 
     pub fn all() -> Vec<Language> {
-        vec![Language::English, Language::Chinese]
+        vec![
+            Language::English,
+            Language::Chinese,
+            Language::Japanese,
+            Language::German,
+            Language::French,
+            Language::Spanish,
+        ]
+    }
+
+    /// The language of a locale like `de_DE.UTF-8`, `zh-Hans-CN` or `ja`, if it's
+    /// one we have.
+    pub fn from_locale(locale: &str) -> Option<Language> {
+        let code = locale
+            .split(['_', '-', '.', '@'])
+            .next()?
+            .to_ascii_lowercase();
+        Language::all().into_iter().find(|lang| lang.code() == code)
+    }
+
+    // The translations shipped with the binary
+    fn embedded(&self) -> &'static str {
+        match self {
+            Language::English => include_str!("../../locales/en.toml"),
+            Language::Chinese => include_str!("../../locales/zh.toml"),
+            Language::Japanese => include_str!("../../locales/ja.toml"),
+            Language::German => include_str!("../../locales/de.toml"),
+            Language::French => include_str!("../../locales/fr.toml"),
+            Language::Spanish => include_str!("../../locales/es.toml"),
+        }
+    }
+
+    // CLDR plural category of `count`, limited to the forms these languages use
+    fn plural_category(&self, count: u64) -> &'static str {
+        match self {
+            Language::English | Language::German | Language::Spanish if count == 1 => "one",
+            Language::French if count <= 1 => "one",
+            _ => "other",
+        }
     }
 }
 
@@ -40,245 +91,217 @@ lazy_static! {
     static ref TRANSLATIONS: RwLock<HashMap<Language, Translations>> = RwLock::new(HashMap::new());
 }
 
+/// Folder for user translations. A `<code>.toml` file in it overrides single keys of
+/// the shipped translation, so a translation can be fixed without a rebuild.
+pub fn user_locale_dir() -> Option<PathBuf> {
+    confy::get_configuration_file_path("bird-player", None)
+        .ok()?
+        .parent()
+        .map(|dir| dir.join("locales"))
+}
+
+/// The first supported language among the user's preferred ones, for a first start
+/// without a language picked in the selector.
+pub fn detect_system_language() -> Option<Language> {
+    let detected = system_locales()
+        .iter()
+        .find_map(|locale| Language::from_locale(locale));
+    tracing::info!("System language: {:?}", detected);
+    detected
+}
+
+// Preferred locales, most preferred first
+fn system_locales() -> Vec<String> {
+    let mut locales = Vec::new();
+
+    // LANGUAGE is a priority list for messages, the others name a single locale
+    for var in ["LANGUAGE", "LC_ALL", "LC_MESSAGES", "LANG"] {
+        if let Ok(value) = std::env::var(var) {
+            locales.extend(
+                value
+                    .split(':')
+                    .filter(|locale| !locale.is_empty())
+                    .map(str::to_string),
+            );
+        }
+    }
+
+    // Desktop apps on Windows and macOS usually don't get these variables
+    locales.extend(platform_locales());
+    locales
+}
+
+#[cfg(target_os = "windows")]
+fn platform_locales() -> Vec<String> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetUserDefaultLocaleName(name: *mut u16, len: i32) -> i32;
+    }
+
+    // LOCALE_NAME_MAX_LENGTH
+    let mut buffer = [0u16; 85];
+    // Safety: the buffer outlives the call and its length is passed along
+    let len = unsafe { GetUserDefaultLocaleName(buffer.as_mut_ptr(), buffer.len() as i32) };
+    if len <= 1 {
+        return Vec::new();
+    }
+    // The length includes the terminating null
+    vec![String::from_utf16_lossy(&buffer[..len as usize - 1])]
+}
+
+#[cfg(target_os = "macos")]
+fn platform_locales() -> Vec<String> {
+    // Prints a list like ("en-US", "zh-Hans-CN")
+    let Ok(output) = std::process::Command::new("defaults")
+        .args(["read", "-g", "AppleLanguages"])
+        .output()
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .split(|c: char| c == ',' || c == '\n')
+        .map(|locale| locale.trim_matches(|c: char| c.is_whitespace() || "()\"".contains(c)))
+        .filter(|locale| !locale.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn platform_locales() -> Vec<String> {
+    Vec::new()
+}
+
 // Initialize translations
 pub fn init() {
+    let user_dir = user_locale_dir();
     let mut translations = HashMap::new();
 
-    // English translations
-    let mut en = HashMap::new();
-    // File menu
-    en.insert("file".to_string(), "File".to_string());
-    en.insert("open".to_string(), "Open".to_string());
-    en.insert("settings".to_string(), "Settings".to_string());
-    en.insert("exit".to_string(), "Exit".to_string());
-
-    // Playback menu
-    en.insert("playback".to_string(), "Playback".to_string());
-    en.insert("play_pause".to_string(), "Play/Pause".to_string());
-    en.insert("previous".to_string(), "Previous".to_string());
-    en.insert("next".to_string(), "Next".to_string());
-    en.insert("play_mode".to_string(), "Play Mode: {}".to_string());
-    en.insert("restore_window".to_string(), "Restore Window".to_string());
-
-    // Help menu
-    en.insert("help".to_string(), "Help".to_string());
-    en.insert("about".to_string(), "About".to_string());
-
-    // Player component
-    en.insert("song".to_string(), "Song: ".to_string());
-    en.insert("artist".to_string(), "Artist: ".to_string());
-    en.insert("playlist".to_string(), "Playlist: ".to_string());
-    en.insert("no_track".to_string(), "No track selected".to_string());
-    en.insert(
-        "select_track".to_string(),
-        "Select a track from the playlist to play".to_string(),
-    );
-    en.insert(
-        "add_tracks".to_string(),
-        "Add tracks to your playlist to start playing".to_string(),
-    );
-    en.insert(
-        "create_playlist".to_string(),
-        "Create a playlist to start playing music".to_string(),
-    );
-    en.insert("remove_song".to_string(), "Remove Song".to_string());
-    en.insert("mini".to_string(), "Mini".to_string());
-    en.insert("playlist_btn".to_string(), "Playlist".to_string());
-    en.insert("lyrics".to_string(), "Lyrics".to_string());
-
-    // Library component
-    en.insert("music_files".to_string(), "Music Library".to_string());
-    en.insert("expand_all".to_string(), "Expand all folders".to_string());
-    en.insert(
-        "collapse_all".to_string(),
-        "Collapse all folders".to_string(),
-    );
-    en.insert("resync_all".to_string(), "Re-sync all folders".to_string());
-    en.insert(
-        "add_music_folder".to_string(),
-        "Add music folder".to_string(),
-    );
-    en.insert("unknown_title".to_string(), "Unknown Title".to_string());
-    en.insert("unknown_track".to_string(), "Unknown Track".to_string());
-    en.insert("add_to_playlist".to_string(), "Add to playlist".to_string());
-    en.insert(
-        "add_all_to_playlist".to_string(),
-        "Add all to playlist".to_string(),
-    );
-    en.insert(
-        "remove_from_library".to_string(),
-        "Remove from library".to_string(),
-    );
-
-    // Playlist tabs component
-    en.insert("rename".to_string(), "Rename".to_string());
-    en.insert("delete".to_string(), "Delete".to_string());
-    en.insert("new_playlist".to_string(), "New Playlist".to_string());
-    en.insert("enter_name".to_string(), "Enter name...".to_string());
-
-    // Playlist table component
-    en.insert("column_number".to_string(), "#".to_string());
-    en.insert("column_title".to_string(), "Title".to_string());
-    en.insert("column_artist".to_string(), "Artist".to_string());
-    en.insert("column_album".to_string(), "Album".to_string());
-    en.insert("column_genre".to_string(), "Genre".to_string());
-    en.insert("edit_title".to_string(), "Edit title".to_string());
-    en.insert("edit_artist".to_string(), "Edit artist".to_string());
-    en.insert("edit_album".to_string(), "Edit album".to_string());
-    en.insert("edit_genre".to_string(), "Edit genre".to_string());
-    en.insert(
-        "remove_from_playlist".to_string(),
-        "Remove from playlist".to_string(),
-    );
-    en.insert("unknown_title".to_string(), "unknown title".to_string());
-    en.insert("unknown_artist".to_string(), "unknown artist".to_string());
-    en.insert("unknown_album".to_string(), "unknown album".to_string());
-    en.insert("unknown_genre".to_string(), "unknown genre".to_string());
-
-    // Chinese translations
-    let mut zh = HashMap::new();
-    // File menu
-    zh.insert("file".to_string(), "文件".to_string());
-    zh.insert("open".to_string(), "打开".to_string());
-    zh.insert("settings".to_string(), "设置".to_string());
-    zh.insert("exit".to_string(), "退出".to_string());
-
-    // Playback menu
-    zh.insert("playback".to_string(), "播放".to_string());
-    zh.insert("play_pause".to_string(), "播放/暂停".to_string());
-    zh.insert("previous".to_string(), "上一首".to_string());
-    zh.insert("next".to_string(), "下一首".to_string());
-    zh.insert("play_mode".to_string(), "播放模式: {}".to_string());
-    zh.insert("restore_window".to_string(), "复原窗口".to_string());
-    // Help menu
-    zh.insert("help".to_string(), "帮助".to_string());
-    zh.insert("about".to_string(), "关于".to_string());
-
-    // Player component
-    zh.insert("song".to_string(), "歌曲：".to_string());
-    zh.insert("artist".to_string(), "艺术家：".to_string());
-    zh.insert("playlist".to_string(), "播放列表：".to_string());
-    zh.insert("no_track".to_string(), "未选择歌曲".to_string());
-    zh.insert(
-        "select_track".to_string(),
-        "从播放列表中选择一首歌曲播放".to_string(),
-    );
-    zh.insert(
-        "add_tracks".to_string(),
-        "添加歌曲到播放列表开始播放".to_string(),
-    );
-    zh.insert(
-        "create_playlist".to_string(),
-        "创建播放列表开始播放音乐".to_string(),
-    );
-    zh.insert("remove_song".to_string(), "移除歌曲".to_string());
-    zh.insert("mini".to_string(), "迷你".to_string());
-    zh.insert("playlist_btn".to_string(), "列表".to_string());
-    zh.insert("lyrics".to_string(), "歌词".to_string());
-
-    // Library component
-    zh.insert("music_files".to_string(), "音乐库".to_string());
-    zh.insert("expand_all".to_string(), "展开所有文件夹".to_string());
-    zh.insert("collapse_all".to_string(), "折叠所有文件夹".to_string());
-    zh.insert("resync_all".to_string(), "重新同步所有文件夹".to_string());
-    zh.insert("add_music_folder".to_string(), "添加音乐文件夹".to_string());
-    zh.insert("unknown_title".to_string(), "未知标题".to_string());
-    zh.insert("unknown_track".to_string(), "未知曲目".to_string());
-    zh.insert("add_to_playlist".to_string(), "添加到播放列表".to_string());
-    zh.insert(
-        "add_all_to_playlist".to_string(),
-        "全部添加到播放列表".to_string(),
-    );
-    zh.insert("remove_from_library".to_string(), "从库中移除".to_string());
-
-    // Playlist tabs component
-    zh.insert("rename".to_string(), "重命名".to_string());
-    zh.insert("delete".to_string(), "删除".to_string());
-    zh.insert("new_playlist".to_string(), "新播放列表".to_string());
-    zh.insert("enter_name".to_string(), "输入名称...".to_string());
-
-    // Playlist table component
-    zh.insert("column_number".to_string(), "#".to_string());
-    zh.insert("column_title".to_string(), "标题".to_string());
-    zh.insert("column_artist".to_string(), "艺术家".to_string());
-    zh.insert("column_album".to_string(), "专辑".to_string());
-    zh.insert("column_genre".to_string(), "类型".to_string());
-    zh.insert("edit_title".to_string(), "编辑标题".to_string());
-    zh.insert("edit_artist".to_string(), "编辑艺术家".to_string());
-    zh.insert("edit_album".to_string(), "编辑专辑".to_string());
-    zh.insert("edit_genre".to_string(), "编辑类型".to_string());
-    zh.insert(
-        "remove_from_playlist".to_string(),
-        "从播放列表中移除".to_string(),
-    );
-    zh.insert("unknown_title".to_string(), "未知标题".to_string());
-    zh.insert("unknown_artist".to_string(), "未知艺术家".to_string());
-    zh.insert("unknown_album".to_string(), "未知专辑".to_string());
-    zh.insert("unknown_genre".to_string(), "未知类型".to_string());
-
-    // Add about window translations
-    init_about_translations(&mut en, &mut zh);
-
-    // Add translations to the global map
-    translations.insert(Language::English, en);
-    translations.insert(Language::Chinese, zh);
-
-    // Store translations
-    let mut global_translations = TRANSLATIONS.write().unwrap();
-    *global_translations = translations;
+    for lang in Language::all() {
+        let mut strings = parse_locale(lang.embedded()).unwrap_or_else(|e| {
+            tracing::error!("Invalid {} translation: {}", lang.code(), e);
+            Translations::new()
+        });
+
+        if let Some(path) = user_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.toml", lang.code())))
+        {
+            if let Ok(source) = std::fs::read_to_string(&path) {
+                match parse_locale(&source) {
+                    Ok(overrides) => {
+                        tracing::info!("Loaded translations from {}", path.display());
+                        strings.extend(overrides);
+                    }
+                    Err(e) => tracing::error!("Ignoring {}: {}", path.display(), e),
+                }
+            }
+        }
+
+        translations.insert(lang, strings);
+    }
+
+    *TRANSLATIONS.write().unwrap() = translations;
 }
 
-// Add about window translations
-fn init_about_translations(en: &mut HashMap<String, String>, zh: &mut HashMap<String, String>) {
-    // About window - English
-    en.insert("app_name".to_string(), "Bird Player".to_string());
-    en.insert(
-        "app_description".to_string(),
-        "A music player dedicated for local MP3 files, inspired by the things from the amazing 2000s golden age.".to_string(),
-    );
-    en.insert("features".to_string(), "Features:".to_string());
-    en.insert(
-        "feature_1".to_string(),
-        "• Playing music, simple and straightforward, no streaming bullshit.".to_string(),
-    );
-    en.insert(
-        "feature_2".to_string(),
-        "• A cassette mimic, with a focus on simplicity and clean design.".to_string(),
-    );
-    en.insert(
-        "feature_3".to_string(),
-        "• Local Music library with ID3 editable tag support".to_string(),
-    );
-    en.insert("feature_4".to_string(), "• Playlist management".to_string());
-    en.insert(
-        "contact_email".to_string(),
-        "Contact: digimonkey@protonmail.com".to_string(),
-    );
-
-    // About window - Chinese
-    zh.insert("app_name".to_string(), "小鸟播放器".to_string());
-    zh.insert(
-        "app_description".to_string(),
-        "一款专为本地 MP3 文件设计的音乐播放器，灵感来源于2000年代的黄金时代。".to_string(),
-    );
-    zh.insert("features".to_string(), "特点：".to_string());
-    zh.insert(
-        "feature_1".to_string(),
-        "• 播放音乐，简单直接，拒绝狗屁流媒体。".to_string(),
-    );
-    zh.insert(
-        "feature_2".to_string(),
-        "• 模拟磁带播放器，注重简约和清晰的设计。".to_string(),
-    );
-    zh.insert(
-        "feature_3".to_string(),
-        "• 本地音乐库，支持ID3标签编辑".to_string(),
-    );
-    zh.insert("feature_4".to_string(), "• 播放列表管理".to_string());
-    zh.insert(
-        "contact_email".to_string(),
-        "联系邮箱: digimonkey@protonmail.com".to_string(),
-    );
+/// Parses a translation file. It is the part of TOML that translations need:
+/// `key = "value"` pairs with dotted keys, `[section]` headers that prefix the keys
+/// below them, comments and the usual string escapes.
+pub fn parse_locale(source: &str) -> Result<Translations, String> {
+    let mut translations = Translations::new();
+    let mut section = String::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+
+        if let Some(header) = line.strip_prefix('[') {
+            let (name, rest) = header.split_once(']').ok_or_else(|| error("unclosed ["))?;
+            if !is_comment_or_empty(rest) {
+                return Err(error("unexpected text after section"));
+            }
+            section = parse_key(name).ok_or_else(|| error("invalid section name"))?;
+            continue;
+        }
+
+        let (key, value) = line.split_once('=').ok_or_else(|| error("expected ="))?;
+        let key = parse_key(key).ok_or_else(|| error("invalid key"))?;
+        let (value, rest) = parse_string(value.trim_start()).map_err(|e| error(&e))?;
+        if !is_comment_or_empty(rest) {
+            return Err(error("unexpected text after value"));
+        }
+
+        let key = if section.is_empty() {
+            key
+        } else {
+            format!("{}.{}", section, key)
+        };
+        translations.insert(key, value);
+    }
+
+    Ok(translations)
+}
+
+fn is_comment_or_empty(rest: &str) -> bool {
+    let rest = rest.trim();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+// Bare keys, joined with dots
+fn parse_key(key: &str) -> Option<String> {
+    let parts = key
+        .split('.')
+        .map(str::trim)
+        .map(|part| {
+            let bare = !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            bare.then_some(part)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(parts.join("."))
+}
+
+// A basic "string" with escapes or a literal 'string', and what follows it
+fn parse_string(input: &str) -> Result<(String, &str), String> {
+    if let Some(literal) = input.strip_prefix('\'') {
+        let end = literal.find('\'').ok_or("unclosed string")?;
+        return Ok((literal[..end].to_string(), &literal[end + 1..]));
+    }
+
+    let basic = input.strip_prefix('"').ok_or("expected a quoted string")?;
+    let mut value = String::new();
+    let mut chars = basic.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &basic[idx + 1..])),
+            '\\' => {
+                let escaped = match chars.next().map(|(_, c)| c) {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some(marker @ ('u' | 'U')) => {
+                        let len = if marker == 'u' { 4 } else { 8 };
+                        let hex: String = chars.by_ref().take(len).map(|(_, c)| c).collect();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == len)
+                            .and_then(char::from_u32)
+                            .ok_or("invalid unicode escape")?
+                    }
+                    _ => return Err("invalid escape".to_string()),
+                };
+                value.push(escaped);
+            }
+            c => value.push(c),
+        }
+    }
+    Err("unclosed string".to_string())
 }
 
 // Set the current language
@@ -292,30 +315,167 @@ pub fn get_language() -> Language {
     *CURRENT_LANGUAGE.read().unwrap()
 }
 
-// Translate a key to the current language
-pub fn t(key: &str) -> String {
+// Looks a key up in the current language, then in English
+fn lookup(candidates: impl Fn(Language) -> Vec<String>) -> Option<String> {
     let lang = *CURRENT_LANGUAGE.read().unwrap();
     let translations = TRANSLATIONS.read().unwrap();
 
-    if let Some(lang_translations) = translations.get(&lang) {
-        if let Some(translation) = lang_translations.get(key) {
-            return translation.clone();
-        }
-    }
+    [lang, Language::English].into_iter().find_map(|lang| {
+        let strings = translations.get(&lang)?;
+        candidates(lang)
+            .iter()
+            .find_map(|key| strings.get(key).cloned())
+    })
+}
 
+// Translate a key to the current language
+pub fn t(key: &str) -> String {
     // Return the key if no translation is found
-    key.to_string()
+    lookup(|_| vec![key.to_string()]).unwrap_or_else(|| key.to_string())
 }
 
 // Translate a key with format arguments
 pub fn tf(key: &str, args: &[&str]) -> String {
-    let translated_format = t(key);
-    // Simple replacement of {} with arguments
-    let mut result = translated_format;
-    for arg in args {
-        if let Some(pos) = result.find("{}") {
-            result.replace_range(pos..pos + 2, arg);
+    format(&t(key), args)
+}
+
+/// Translates a key whose wording depends on `count`. The file holds one entry per
+/// plural form, like `key.one` and `key.other`.
+pub fn tp(key: &str, count: u64, args: &[&str]) -> String {
+    let template = lookup(|lang| {
+        vec![
+            format!("{}.{}", key, lang.plural_category(count)),
+            format!("{}.other", key),
+            key.to_string(),
+        ]
+    })
+    .unwrap_or_else(|| key.to_string());
+    format(&template, args)
+}
+
+// Fills `{}` with the arguments in order and `{0}`, `{1}`... by position. Any other
+// braces are kept, e.g. the export pattern hint.
+fn format(template: &str, args: &[&str]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut next_arg = 0;
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let placeholder = after
+            .find('}')
+            .map(|end| &after[..end])
+            .filter(|inner| inner.chars().all(|c| c.is_ascii_digit()));
+
+        let Some(inner) = placeholder else {
+            result.push('{');
+            rest = after;
+            continue;
+        };
+
+        let idx = if inner.is_empty() {
+            next_arg += 1;
+            next_arg - 1
+        } else {
+            inner.parse().unwrap_or(usize::MAX)
+        };
+        match args.get(idx) {
+            Some(arg) => result.push_str(arg),
+            // Leave placeholders without an argument as they are
+            None => {
+                result.push('{');
+                result.push_str(inner);
+                result.push('}');
+            }
         }
+        rest = &after[inner.len() + 1..];
     }
+
+    result.push_str(rest);
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_toml_subset() {
+        let source = "# comment\n\
+            title = \"Title\" # trailing\n\
+            quoted = \"Say \\\"hi\\\"\\n\\u00e9\"\n\
+            literal = 'C:\\music'\n\
+            count.one = \"{} file\"\n\
+            \n\
+            [count]\n\
+            other = \"{} files\"\n";
+        let strings = parse_locale(source).unwrap();
+
+        assert_eq!(strings["title"], "Title");
+        assert_eq!(strings["quoted"], "Say \"hi\"\né");
+        assert_eq!(strings["literal"], "C:\\music");
+        assert_eq!(strings["count.one"], "{} file");
+        assert_eq!(strings["count.other"], "{} files");
+
+        assert!(parse_locale("key = \"open").is_err());
+        assert!(parse_locale("key = unquoted").is_err());
+        assert!(parse_locale("bad key = \"x\"").is_err());
+        assert!(parse_locale("key = \"\\q\"").is_err());
+        assert_eq!(
+            parse_locale("a = \"x\"\nb \"y\"").unwrap_err(),
+            "line 2: expected ="
+        );
+    }
+
+    #[test]
+    fn every_locale_has_every_english_key() {
+        let english = parse_locale(Language::English.embedded()).unwrap();
+        for lang in Language::all() {
+            let strings = parse_locale(lang.embedded()).unwrap();
+            for key in english.keys() {
+                // Languages without a singular only need the `other` form
+                let base = key.strip_suffix(".one").unwrap_or(key);
+                let other = format!("{}.other", base.trim_end_matches(".other"));
+                assert!(
+                    strings.contains_key(key) || strings.contains_key(&other),
+                    "{} is missing {}",
+                    lang.code(),
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn formats_sequential_and_positional_arguments() {
+        assert_eq!(format("{} of {}", &["1", "2"]), "1 of 2");
+        assert_eq!(format("{1} before {0}", &["a", "b"]), "b before a");
+        assert_eq!(format("{} and {}", &["only"]), "only and {}");
+        assert_eq!(format("Use {n} and {title}", &["x"]), "Use {n} and {title}");
+        assert_eq!(format("{5}", &["x"]), "{5}");
+        assert_eq!(format("unclosed {", &["x"]), "unclosed {");
+    }
+
+    #[test]
+    fn picks_the_language_of_a_locale() {
+        assert_eq!(Language::from_locale("de_DE.UTF-8"), Some(Language::German));
+        assert_eq!(Language::from_locale("zh-Hans-CN"), Some(Language::Chinese));
+        assert_eq!(Language::from_locale("ja"), Some(Language::Japanese));
+        assert_eq!(Language::from_locale("fr_CA@euro"), Some(Language::French));
+        assert_eq!(Language::from_locale("ES-mx"), Some(Language::Spanish));
+        assert_eq!(Language::from_locale("pt_BR"), None);
+        assert_eq!(Language::from_locale("C"), None);
+        assert_eq!(Language::from_locale(""), None);
+    }
+
+    #[test]
+    fn plural_categories_follow_the_language() {
+        assert_eq!(Language::English.plural_category(1), "one");
+        assert_eq!(Language::English.plural_category(0), "other");
+        assert_eq!(Language::French.plural_category(0), "one");
+        assert_eq!(Language::French.plural_category(2), "other");
+        assert_eq!(Language::Japanese.plural_category(1), "other");
+        assert_eq!(Language::Chinese.plural_category(1), "other");
+    }
+}
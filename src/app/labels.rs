@@ -0,0 +1,461 @@
+//! Colored labels like "workout", "focus" or "chill" for tracks and playlists. They're
+//! kept in tables of their own, show as chips in the playlist table, are matched by the
+//! search and each can be opened as a playlist of every track that carries it.
+
+use super::library::TrackKey;
+use super::playlist::{Playlist, VirtualPlaylist};
+use super::App;
+
+use eframe::egui::Color32;
+use rusqlite::{Connection, Result as SqlResult};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+pub type LabelId = i64;
+
+// Colors new labels get in turn, changeable in the settings
+const PALETTE: [[u8; 3]; 8] = [
+    [0xe0, 0x4f, 0x4f],
+    [0xe8, 0x9a, 0x3c],
+    [0xd6, 0xc2, 0x3a],
+    [0x5c, 0xb8, 0x5c],
+    [0x3f, 0xa7, 0xb5],
+    [0x4f, 0x7f, 0xe0],
+    [0x9b, 0x6a, 0xd6],
+    [0xd6, 0x6a, 0xa8],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub id: LabelId,
+    pub name: String,
+    pub color: [u8; 3],
+}
+
+impl Label {
+    pub fn color32(&self) -> Color32 {
+        let [r, g, b] = self.color;
+        Color32::from_rgb(r, g, b)
+    }
+}
+
+fn color_to_sql(color: [u8; 3]) -> i64 {
+    let [r, g, b] = color;
+    (r as i64) << 16 | (g as i64) << 8 | b as i64
+}
+
+fn color_from_sql(value: i64) -> [u8; 3] {
+    [(value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+/// The labels and what carries them.
+#[derive(Debug, Clone, Default)]
+pub struct Labels {
+    // In name order
+    labels: Vec<Label>,
+    tracks: HashMap<TrackKey, BTreeSet<LabelId>>,
+    playlists: HashMap<i64, BTreeSet<LabelId>>,
+}
+
+impl Labels {
+    pub fn all(&self) -> &[Label] {
+        &self.labels
+    }
+
+    pub fn get(&self, id: LabelId) -> Option<&Label> {
+        self.labels.iter().find(|label| label.id == id)
+    }
+
+    fn find(&self, name: &str) -> Option<&Label> {
+        self.labels
+            .iter()
+            .find(|label| label.name.to_lowercase() == name.to_lowercase())
+    }
+
+    fn of<'a>(&'a self, ids: Option<&'a BTreeSet<LabelId>>) -> impl Iterator<Item = &'a Label> {
+        self.labels
+            .iter()
+            .filter(move |label| ids.is_some_and(|ids| ids.contains(&label.id)))
+    }
+
+    /// The labels of a track, in name order.
+    pub fn of_track(&self, key: TrackKey) -> impl Iterator<Item = &Label> {
+        self.of(self.tracks.get(&key))
+    }
+
+    /// The labels of a playlist, none before it was stored.
+    pub fn of_playlist(&self, playlist_id: Option<i64>) -> impl Iterator<Item = &Label> {
+        self.of(playlist_id.and_then(|id| self.playlists.get(&id)))
+    }
+
+    pub fn track_has(&self, key: TrackKey, id: LabelId) -> bool {
+        self.tracks.get(&key).is_some_and(|ids| ids.contains(&id))
+    }
+
+    pub fn playlist_has(&self, playlist_id: Option<i64>, id: LabelId) -> bool {
+        playlist_id
+            .and_then(|playlist_id| self.playlists.get(&playlist_id))
+            .is_some_and(|ids| ids.contains(&id))
+    }
+
+    /// Whether one of the track's label names contains `search`, which is lowercase.
+    pub fn track_matches(&self, key: TrackKey, search: &str) -> bool {
+        self.of_track(key)
+            .any(|label| label.name.to_lowercase().contains(search))
+    }
+
+    fn insert(&mut self, label: Label) {
+        self.labels.push(label);
+        self.labels
+            .sort_by_key(|label| (label.name.to_lowercase(), label.id));
+    }
+
+    fn update(&mut self, label: Label) {
+        self.labels.retain(|other| other.id != label.id);
+        self.insert(label);
+    }
+
+    fn next_color(&self) -> [u8; 3] {
+        PALETTE[self.labels.len() % PALETTE.len()]
+    }
+
+    // Puts the label on every track, or takes it off when they all have it already.
+    // Returns whether it was put on.
+    fn toggle_tracks(&mut self, keys: &[TrackKey], id: LabelId) -> bool {
+        let add = !keys.iter().all(|key| self.track_has(*key, id));
+        for key in keys {
+            let ids = self.tracks.entry(*key).or_default();
+            if add {
+                ids.insert(id);
+            } else {
+                ids.remove(&id);
+            }
+        }
+        self.tracks.retain(|_, ids| !ids.is_empty());
+        add
+    }
+
+    fn remove(&mut self, id: LabelId) {
+        self.labels.retain(|label| label.id != id);
+        for ids in self.tracks.values_mut().chain(self.playlists.values_mut()) {
+            ids.remove(&id);
+        }
+    }
+}
+
+// Database methods
+
+fn load(conn: &Arc<Mutex<Connection>>) -> SqlResult<Labels> {
+    let conn_guard = conn.lock().unwrap();
+    let mut labels = Labels::default();
+
+    let mut stmt = conn_guard.prepare("SELECT id, name, color FROM labels")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Label {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            color: color_from_sql(row.get(2)?),
+        })
+    })?;
+    for label in rows {
+        labels.insert(label?);
+    }
+
+    let mut stmt = conn_guard.prepare("SELECT library_item_id, label_id FROM track_labels")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, LabelId>(1)?))
+    })?;
+    for row in rows {
+        let (key, id) = row?;
+        if let Ok(key) = key.parse::<TrackKey>() {
+            labels.tracks.entry(key).or_default().insert(id);
+        }
+    }
+
+    let mut stmt = conn_guard.prepare("SELECT playlist_id, label_id FROM playlist_labels")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?;
+    for row in rows {
+        let (playlist_id, id) = row?;
+        labels.playlists.entry(playlist_id).or_default().insert(id);
+    }
+
+    Ok(labels)
+}
+
+impl App {
+    pub fn load_labels(&mut self) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match load(&db.connection()) {
+            Ok(labels) => self.labels = labels,
+            Err(e) => tracing::error!("Failed to load labels: {}", e),
+        }
+    }
+
+    /// Adds a label, or finds the one that has the name already.
+    pub fn create_label(&mut self, name: &str) -> Option<LabelId> {
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        if let Some(label) = self.labels.find(name) {
+            return Some(label.id);
+        }
+
+        let db = self.database.as_ref()?;
+        let color = self.labels.next_color();
+        let conn = db.connection();
+        let conn_guard = conn.lock().unwrap();
+        match conn_guard.execute(
+            "INSERT INTO labels (name, color) VALUES (?1, ?2)",
+            rusqlite::params![name, color_to_sql(color)],
+        ) {
+            Ok(_) => {
+                let id = conn_guard.last_insert_rowid();
+                self.labels.insert(Label {
+                    id,
+                    name: name.to_string(),
+                    color,
+                });
+                Some(id)
+            }
+            Err(e) => {
+                tracing::error!("Failed to add label {:?}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    /// Renames and recolors a label. An empty name or one another label has is ignored.
+    pub fn update_label(&mut self, id: LabelId, name: &str, color: [u8; 3]) {
+        let name = name.trim();
+        let Some(label) = self.labels.get(id).cloned() else {
+            return;
+        };
+        let name = match self.labels.find(name) {
+            Some(other) if other.id != id => label.name.clone(),
+            _ if name.is_empty() => label.name.clone(),
+            _ => name.to_string(),
+        };
+
+        if let Some(db) = &self.database {
+            let conn = db.connection();
+            let conn_guard = conn.lock().unwrap();
+            if let Err(e) = conn_guard.execute(
+                "UPDATE labels SET name = ?1, color = ?2 WHERE id = ?3",
+                rusqlite::params![name, color_to_sql(color), id],
+            ) {
+                tracing::error!("Failed to update label: {}", e);
+                return;
+            }
+        }
+
+        self.labels.update(Label { id, name, color });
+        self.refresh_label_playlists();
+    }
+
+    /// Deletes a label, taking it off every track and playlist.
+    pub fn delete_label(&mut self, id: LabelId) {
+        if let Some(db) = &self.database {
+            let conn = db.connection();
+            let conn_guard = conn.lock().unwrap();
+            if let Err(e) =
+                conn_guard.execute("DELETE FROM labels WHERE id = ?1", rusqlite::params![id])
+            {
+                tracing::error!("Failed to delete label: {}", e);
+                return;
+            }
+        }
+
+        self.labels.remove(id);
+        self.label_playlists.retain(|label_id| *label_id != id);
+        self.refresh_label_playlists();
+        self.save_state();
+    }
+
+    /// Puts a label on the tracks, or takes it off when they all carry it.
+    pub fn toggle_track_label(&mut self, keys: &[TrackKey], id: LabelId) {
+        let Some(db) = &self.database else {
+            return;
+        };
+
+        // The tracks must be saved in the library before labels can refer to them
+        db.wait_for_writes();
+        let add = self.labels.toggle_tracks(keys, id);
+        let conn = db.connection();
+        let conn_guard = conn.lock().unwrap();
+        for key in keys {
+            let result = if add {
+                conn_guard.execute(
+                    "INSERT OR IGNORE INTO track_labels (library_item_id, label_id)
+                     SELECT ?1, ?2 WHERE EXISTS (SELECT 1 FROM library_items WHERE key = ?1)",
+                    rusqlite::params![key.to_string(), id],
+                )
+            } else {
+                conn_guard.execute(
+                    "DELETE FROM track_labels WHERE library_item_id = ?1 AND label_id = ?2",
+                    rusqlite::params![key.to_string(), id],
+                )
+            };
+            if let Err(e) = result {
+                tracing::error!("Failed to change the labels of a track: {}", e);
+            }
+        }
+        drop(conn_guard);
+
+        self.refresh_label_playlists();
+    }
+
+    /// Puts a label on a playlist or takes it off.
+    pub fn toggle_playlist_label(&mut self, idx: usize, id: LabelId) {
+        if self.playlists.get(idx).is_none_or(|p| p.is_virtual()) {
+            return;
+        }
+        // A new playlist gets its database id first
+        if self.playlists[idx].id.is_none() {
+            self.persist_playlist(idx);
+        }
+        let (Some(playlist_id), Some(db)) = (self.playlists[idx].id, &self.database) else {
+            return;
+        };
+
+        let ids = self.labels.playlists.entry(playlist_id).or_default();
+        let add = ids.insert(id);
+        if !add {
+            ids.remove(&id);
+        }
+
+        let conn = db.connection();
+        let conn_guard = conn.lock().unwrap();
+        let result = if add {
+            conn_guard.execute(
+                "INSERT OR IGNORE INTO playlist_labels (playlist_id, label_id) VALUES (?1, ?2)",
+                rusqlite::params![playlist_id, id],
+            )
+        } else {
+            conn_guard.execute(
+                "DELETE FROM playlist_labels WHERE playlist_id = ?1 AND label_id = ?2",
+                rusqlite::params![playlist_id, id],
+            )
+        };
+        if let Err(e) = result {
+            tracing::error!("Failed to change the labels of a playlist: {}", e);
+        }
+    }
+
+    fn label_playlist_idx(&self, id: LabelId) -> Option<usize> {
+        self.playlists
+            .iter()
+            .position(|p| p.virtual_kind == Some(VirtualPlaylist::Label(id)))
+    }
+
+    /// Opens the playlist of every track with the label, kept up to date like Recently
+    /// Added.
+    pub fn open_label_playlist(&mut self, id: LabelId) {
+        if !self.label_playlists.contains(&id) {
+            self.label_playlists.push(id);
+            self.save_state();
+        }
+        self.refresh_label_playlists();
+        self.current_playlist_idx = self.label_playlist_idx(id).or(self.current_playlist_idx);
+    }
+
+    /// Closes a label's playlist, the label stays.
+    pub fn close_label_playlist(&mut self, id: LabelId) {
+        self.label_playlists.retain(|label_id| *label_id != id);
+        self.refresh_label_playlists();
+        self.save_state();
+    }
+
+    /// Rebuilds the playlists of the opened labels from the library.
+    pub fn refresh_label_playlists(&mut self) {
+        // Labels deleted since are forgotten once the labels are known
+        if self.database.is_some() {
+            let labels = &self.labels;
+            self.label_playlists.retain(|id| labels.get(*id).is_some());
+        }
+
+        // Tabs of labels no longer opened go away
+        while let Some(idx) = self.playlists.iter().position(|p| {
+            matches!(p.virtual_kind, Some(VirtualPlaylist::Label(id)) if !self.label_playlists.contains(&id))
+        }) {
+            self.playlists.remove(idx);
+            self.shift_playlist_indices(idx, false);
+        }
+
+        for id in self.label_playlists.clone() {
+            let Some(label) = self.labels.get(id) else {
+                continue;
+            };
+            let name = label.name.clone();
+            let tracks: Vec<_> = self
+                .library
+                .items()
+                .iter()
+                .filter(|item| self.labels.track_has(item.key(), id))
+                .cloned()
+                .collect();
+
+            let idx = match self.label_playlist_idx(id) {
+                Some(idx) => idx,
+                None => {
+                    self.playlists
+                        .push(Playlist::new_virtual(VirtualPlaylist::Label(id)));
+                    self.playlists.len() - 1
+                }
+            };
+            let playlist = &mut self.playlists[idx];
+            playlist.set_name(name);
+            if playlist.tracks != tracks {
+                playlist.tracks = tracks;
+                playlist.selected_indices.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(id: LabelId, name: &str) -> Label {
+        Label {
+            id,
+            name: name.to_string(),
+            color: PALETTE[0],
+        }
+    }
+
+    #[test]
+    fn labels_go_on_all_tracks_or_come_off_all() {
+        let mut labels = Labels::default();
+        labels.insert(label(1, "workout"));
+        labels.insert(label(2, "Chill"));
+        let (a, b) = (TrackKey::from_parts(1, 0), TrackKey::from_parts(2, 0));
+
+        assert!(labels.toggle_tracks(&[a], 1));
+        // One of them has it, so it goes on both
+        assert!(labels.toggle_tracks(&[a, b], 1));
+        assert!(labels.track_has(b, 1));
+        assert!(!labels.toggle_tracks(&[a, b], 1));
+        assert!(!labels.track_has(a, 1));
+
+        labels.toggle_tracks(&[a], 1);
+        labels.toggle_tracks(&[a], 2);
+        let names: Vec<&str> = labels.of_track(a).map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["Chill", "workout"]);
+        assert!(labels.track_matches(a, "chi"));
+        assert!(!labels.track_matches(b, "chi"));
+
+        labels.remove(2);
+        assert!(!labels.track_matches(a, "chi"));
+        assert_eq!(labels.find("WORKOUT").map(|l| l.id), Some(1));
+    }
+
+    #[test]
+    fn colors_are_stored_as_one_number() {
+        assert_eq!(color_to_sql([0x12, 0x34, 0x56]), 0x123456);
+        assert_eq!(color_from_sql(0x123456), [0x12, 0x34, 0x56]);
+    }
+}
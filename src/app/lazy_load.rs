@@ -0,0 +1,95 @@
+//! The library and the playlists not on screen are read from the database after the
+//! window is up, large collections would otherwise keep it from showing for seconds.
+
+use super::library::Library;
+use super::playlist::Playlist;
+use super::{App, LibraryCommand};
+
+impl App {
+    /// Reads what `App::load` left out on a background thread, the results come back
+    /// as library commands.
+    pub fn load_in_background(&self) {
+        let (Some(db), Some(lib_cmd_tx)) = (&self.database, &self.library_cmd_tx) else {
+            return;
+        };
+        if !self.library_loading {
+            return;
+        }
+
+        let conn = db.connection();
+        let lib_cmd_tx = lib_cmd_tx.clone();
+        let playlist_ids: Vec<i64> = self
+            .playlists
+            .iter()
+            .filter(|playlist| playlist.loading)
+            .filter_map(|playlist| playlist.id)
+            .collect();
+
+        std::thread::spawn(move || {
+            let library = Library::load_from_db(&conn).unwrap_or_else(|e| {
+                tracing::error!("Failed to load library from database: {}", e);
+                Library::new()
+            });
+            tracing::info!("Loaded {} library items", library.items().len());
+            if lib_cmd_tx
+                .send(LibraryCommand::LibraryLoaded(Box::new(library)))
+                .is_err()
+            {
+                return;
+            }
+
+            for id in playlist_ids {
+                match Playlist::load_from_db(&conn, id) {
+                    Ok(playlist) => {
+                        if lib_cmd_tx
+                            .send(LibraryCommand::PlaylistLoaded(Box::new(playlist)))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Err(e) => tracing::error!("Failed to load playlist {}: {}", id, e),
+                }
+            }
+        });
+    }
+
+    pub fn library_loaded(&mut self, library: Library) {
+        self.keeping_saved_state(|app| {
+            app.library.replace_loaded(library, app.read_only_library);
+            app.library_loading = false;
+            app.refresh_recently_added();
+            app.refresh_label_playlists();
+            app.refresh_favorites();
+            app.reload_album_stats();
+        });
+        self.clean_album_art_if_due();
+        self.start_onboarding_if_new();
+    }
+
+    pub fn playlist_loaded(&mut self, loaded: Playlist) {
+        self.keeping_saved_state(|app| {
+            if let Some(playlist) = app
+                .playlists
+                .iter_mut()
+                .find(|playlist| playlist.loading && playlist.id == loaded.id)
+            {
+                playlist.finish_loading(loaded);
+            }
+        });
+    }
+
+    /// Whether anything is still being read, the UI repaints until it's done.
+    pub fn is_loading(&self) -> bool {
+        self.library_loading || self.playlists.iter().any(|playlist| playlist.loading)
+    }
+
+    // What was read is already in the database, don't let the autosave write it back
+    fn keeping_saved_state(&mut self, load: impl FnOnce(&mut Self)) {
+        let unchanged = self.saved_fingerprint == Some(self.state_fingerprint());
+        load(self);
+        if unchanged {
+            self.saved_fingerprint = Some(self.state_fingerprint());
+        }
+    }
+}
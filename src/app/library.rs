@@ -1,13 +1,26 @@
+use super::audio_format::AudioFormat;
 use rusqlite::{Connection, Result as SqlResult};
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+/// Most stars a track can be rated.
+pub const MAX_RATING: u8 = 5;
+
+/// Identifies a track across the tables of the database and across rescans. ULIDs
+/// sort by the time they were made and don't collide like random numbers can.
+pub type TrackKey = ulid::Ulid;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Library {
     paths: Vec<LibraryPath>,
     items: Vec<LibraryItem>,
     library_view: LibraryView,
+    // Paths on unmounted shares or unplugged drives, checked at runtime
+    #[serde(skip)]
+    offline_paths: HashSet<LibraryPathId>,
 }
 
 impl Default for Library {
@@ -25,6 +38,7 @@ impl Library {
                 view_type: ViewType::Album,
                 containers: Vec::new(),
             },
+            offline_paths: HashSet::new(),
         }
     }
 
@@ -32,6 +46,21 @@ impl Library {
         &self.paths
     }
 
+    /// Takes the library read from the database, keeping the folders and tracks that
+    /// were added while it loaded.
+    pub fn replace_loaded(&mut self, loaded: Library, read_only: bool) {
+        let added = std::mem::replace(self, loaded);
+        for path in added.paths {
+            if !self.paths.iter().any(|p| p.path() == path.path()) {
+                self.paths.push(path);
+            }
+        }
+        for item in added.items {
+            self.add_item(item, read_only);
+        }
+        self.offline_paths.extend(added.offline_paths);
+    }
+
     pub fn add_path(&mut self, path: PathBuf) -> bool {
         if self.paths.iter().any(|p| *p.path() == path) {
             false
@@ -42,20 +71,18 @@ impl Library {
         }
     }
 
-    pub fn remove_path(&mut self, path_id: LibraryPathId) {
+    /// Removes the path and its items, returning them so they can be put back.
+    pub fn take_path(&mut self, path_id: LibraryPathId) -> Option<(LibraryPath, Vec<LibraryItem>)> {
         // Remove the path from the library path list
-        if let Some(idx) = self.paths.iter().position(|l| l.id() == path_id) {
-            self.paths.remove(idx);
-        }
+        let idx = self.paths.iter().position(|l| l.id() == path_id)?;
+        let path = self.paths.remove(idx);
+        self.offline_paths.remove(&path_id);
 
         // Remove the actual items.
-        while let Some(idx) = self
-            .items
-            .iter()
-            .position(|item| item.library_id() == path_id)
-        {
-            self.items.swap_remove(idx);
-        }
+        let (items, kept) = std::mem::take(&mut self.items)
+            .into_iter()
+            .partition(|item| item.library_id() == path_id);
+        self.items = kept;
 
         // Remove the view container items
         for container in &mut self.library_view.containers {
@@ -77,6 +104,19 @@ impl Library {
         {
             self.library_view.containers.swap_remove(idx);
         }
+
+        Some((path, items))
+    }
+
+    /// Puts back what `take_path` removed. The library view is rebuilt on the next import.
+    pub fn restore_path(&mut self, path: LibraryPath, items: Vec<LibraryItem>) {
+        if self.paths.iter().any(|p| p.path() == path.path()) {
+            return;
+        }
+        self.paths.push(path);
+        for item in items {
+            self.add_item(item, false);
+        }
     }
 
     pub fn set_path_to_imported(&mut self, id: LibraryPathId) {
@@ -95,25 +135,113 @@ impl Library {
         }
     }
 
+    pub fn is_path_online(&self, id: LibraryPathId) -> bool {
+        !self.offline_paths.contains(&id)
+    }
+
+    /// Returns true if the availability changed.
+    pub fn set_path_online(&mut self, id: LibraryPathId, online: bool) -> bool {
+        if online {
+            self.offline_paths.remove(&id)
+        } else {
+            self.offline_paths.insert(id)
+        }
+    }
+
     pub fn items(&self) -> &Vec<LibraryItem> {
         self.items.as_ref()
     }
 
+    /// Whether the track is still in the library, playlists keep tracks of removed folders.
+    pub fn contains(&self, track: &LibraryItem) -> bool {
+        self.paths.iter().any(|p| p.id() == track.library_id())
+    }
+
     pub fn view(&self) -> &LibraryView {
         &self.library_view
     }
 
-    pub fn add_item(&mut self, library_item: LibraryItem) {
-        // Check if an item with this path already exists
-        if let Some(idx) = self
-            .items
+    pub fn set_path_display_name(&mut self, id: LibraryPathId, name: &str) {
+        if let Some(path) = self.paths.iter_mut().find(|p| p.id() == id) {
+            path.set_display_name(name);
+        }
+    }
+
+    pub fn set_path_exclude_patterns(&mut self, id: LibraryPathId, patterns: Vec<String>) {
+        if let Some(path) = self.paths.iter_mut().find(|p| p.id() == id) {
+            path.set_exclude_patterns(patterns);
+        }
+    }
+
+    pub fn set_path_priority(&mut self, id: LibraryPathId, priority: i32) {
+        if let Some(path) = self.paths.iter_mut().find(|p| p.id() == id) {
+            path.set_priority(priority);
+        }
+    }
+
+    pub fn path_priority(&self, id: LibraryPathId) -> i32 {
+        self.paths
             .iter()
-            .position(|item| item.path() == library_item.path())
-        {
-            // Update the existing item but preserve its key
-            let existing_key = self.items[idx].key();
+            .find(|p| p.id() == id)
+            .map_or(0, |p| p.priority())
+    }
+
+    // Where the item lies inside its library path, the same for copies of a folder
+    // kept in two places, e.g. on the laptop and on the NAS
+    fn relative_path(&self, item: &LibraryItem) -> Option<PathBuf> {
+        let root = self.paths.iter().find(|p| p.id() == item.library_id())?;
+        item.path()
+            .strip_prefix(root.path())
+            .ok()
+            .map(PathBuf::from)
+    }
+
+    /// Adds a scanned track, or updates the track already imported from the same file.
+    /// Tag edits made in read-only mode live in the database only, so the tags of the
+    /// scan don't replace them then, nor when the file didn't change since.
+    pub fn add_item(&mut self, library_item: LibraryItem, read_only: bool) {
+        let relative_path = self.relative_path(&library_item);
+        // The same file, or a copy of it under another library path
+        if let Some(idx) = self.items.iter().position(|item| {
+            item.path() == library_item.path()
+                || (item.library_id() != library_item.library_id()
+                    && relative_path.is_some()
+                    && self.relative_path(item) == relative_path)
+        }) {
+            let existing_priority = self.path_priority(self.items[idx].library_id());
+            let new_priority = self.path_priority(library_item.library_id());
+            if self.items[idx].path() != library_item.path() && existing_priority >= new_priority {
+                // Keep the copy from the preferred source
+                return;
+            }
+
+            // Update the existing item but preserve its key and import time
+            let existing = &self.items[idx];
             let mut updated_item = library_item;
-            updated_item.set_key(existing_key);
+            updated_item.set_key(existing.key());
+            if existing_priority > new_priority {
+                // Folders inside each other, the file stays with the preferred one
+                updated_item.set_library_id(existing.library_id());
+            }
+            if let Some(added_at) = existing.added_at() {
+                updated_item.set_added_at(Some(added_at));
+            }
+            // Set in the app, the file doesn't know about them. The explicit flag
+            // can't be edited, it follows the file's advisory tag
+            updated_item.set_preamp_db(existing.preamp_db());
+            updated_item.set_play_count(existing.play_count());
+            updated_item.set_rating(existing.rating());
+            updated_item.set_skip_count(existing.skip_count());
+            updated_item.set_audio_track(existing.audio_track());
+            if updated_item.replay_gain().is_missing() {
+                // Measured by the scanner without writing tags
+                updated_item.set_replay_gain(existing.replay_gain());
+            }
+            let unchanged = existing.modified_at().is_some()
+                && existing.modified_at() == updated_item.modified_at();
+            if read_only || unchanged {
+                updated_item.keep_edits_of(existing);
+            }
             self.items[idx] = updated_item;
         } else {
             // Add as a new item
@@ -121,6 +249,30 @@ impl Library {
         }
     }
 
+    pub fn update_item(&mut self, key: TrackKey, mut update: impl FnMut(&mut LibraryItem)) {
+        for item in self.items.iter_mut().filter(|item| item.key() == key) {
+            update(item);
+        }
+
+        for container in &mut self.library_view.containers {
+            for item in container.items.iter_mut().filter(|item| item.key() == key) {
+                update(item);
+            }
+        }
+    }
+
+    pub fn set_replay_gain(&mut self, key: TrackKey, replay_gain: ReplayGain) {
+        for item in self.items.iter_mut().filter(|item| item.key() == key) {
+            item.set_replay_gain(replay_gain);
+        }
+
+        for container in &mut self.library_view.containers {
+            for item in container.items.iter_mut().filter(|item| item.key() == key) {
+                item.set_replay_gain(replay_gain);
+            }
+        }
+    }
+
     pub fn add_view(&mut self, library_view: LibraryView) {
         let mut new = library_view.containers.clone();
 
@@ -135,7 +287,7 @@ impl Library {
         // Start a transaction
         let tx = conn_guard.transaction()?;
 
-        // Save all library paths
+        // Rows are updated in place, replacing them would delete what refers to them
         for path in &self.paths {
             let status_value = match path.status() {
                 LibraryPathStatus::NotImported => 0,
@@ -143,13 +295,19 @@ impl Library {
             };
 
             tx.execute(
-                "INSERT OR REPLACE INTO library_paths (id, path, status, display_name) 
-                 VALUES (?1, ?2, ?3, ?4)",
+                "INSERT INTO library_paths
+                 (id, path, status, display_name, priority, exclude_patterns)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (id) DO UPDATE SET path = excluded.path, status = excluded.status,
+                  display_name = excluded.display_name, priority = excluded.priority,
+                  exclude_patterns = excluded.exclude_patterns",
                 rusqlite::params![
                     path.id().0 as i64,
                     path.path().to_string_lossy().to_string(),
                     status_value,
-                    path.display_name()
+                    path.display_name(),
+                    path.priority(),
+                    path.exclude_patterns().join("\n")
                 ],
             )?;
         }
@@ -157,9 +315,27 @@ impl Library {
         // Save all library items
         for item in &self.items {
             tx.execute(
-                "INSERT OR REPLACE INTO library_items 
-                 (key, library_path_id, path, title, artist, album, year, genre, track_number, lyrics) 
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                "INSERT INTO library_items
+                 (key, library_path_id, path, title, artist, album, year, genre, track_number, lyrics,
+                  track_gain, track_peak, album_gain, album_peak, album_artist, artists, added_at,
+                  modified_at, codec, bitrate, sample_rate, bit_depth, channels, preamp_db,
+                  play_count, rating, skip_count, explicit, audio_track) 
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+                  ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29)
+                 ON CONFLICT (key) DO UPDATE SET library_path_id = excluded.library_path_id,
+                  path = excluded.path, title = excluded.title, artist = excluded.artist,
+                  album = excluded.album, year = excluded.year, genre = excluded.genre,
+                  track_number = excluded.track_number, lyrics = excluded.lyrics,
+                  track_gain = excluded.track_gain, track_peak = excluded.track_peak,
+                  album_gain = excluded.album_gain, album_peak = excluded.album_peak,
+                  album_artist = excluded.album_artist, artists = excluded.artists,
+                  added_at = excluded.added_at, modified_at = excluded.modified_at,
+                  codec = excluded.codec, bitrate = excluded.bitrate,
+                  sample_rate = excluded.sample_rate, bit_depth = excluded.bit_depth,
+                  channels = excluded.channels, preamp_db = excluded.preamp_db,
+                  play_count = excluded.play_count, rating = excluded.rating,
+                  skip_count = excluded.skip_count, explicit = excluded.explicit,
+                  audio_track = excluded.audio_track",
                 rusqlite::params![
                     item.key().to_string(),
                     item.library_id().0 as i64,
@@ -171,13 +347,36 @@ impl Library {
                     item.genre(),
                     item.track_number(),
                     item.lyrics(),
+                    item.replay_gain().track_gain,
+                    item.replay_gain().track_peak,
+                    item.replay_gain().album_gain,
+                    item.replay_gain().album_peak,
+                    item.album_artist(),
+                    join_artists(item.artists()),
+                    item.added_at(),
+                    item.modified_at(),
+                    item.format().codec,
+                    item.format().bitrate,
+                    item.format().sample_rate,
+                    item.format().bit_depth,
+                    item.format().channels,
+                    item.preamp_db(),
+                    item.play_count(),
+                    item.rating(),
+                    item.skip_count(),
+                    item.explicit(),
+                    item.audio_track(),
                 ],
             )?;
 
-            // Save pictures for this item
+            // Save pictures for this item, in place of the ones saved before
+            tx.execute(
+                "DELETE FROM pictures WHERE library_item_id = ?1",
+                rusqlite::params![item.key().to_string()],
+            )?;
             for picture in item.pictures() {
                 tx.execute(
-                    "INSERT OR REPLACE INTO pictures 
+                    "INSERT INTO pictures
                      (library_item_id, mime_type, picture_type, description, file_path) 
                      VALUES (?1, ?2, ?3, ?4, ?5)",
                     rusqlite::params![
@@ -197,20 +396,35 @@ impl Library {
         Ok(())
     }
 
+    /// Deletes the path's rows, its tracks and what refers to them go along with it.
+    pub fn delete_path_from_db(
+        conn: &Arc<Mutex<Connection>>,
+        path_id: LibraryPathId,
+    ) -> SqlResult<()> {
+        conn.lock().unwrap().execute(
+            "DELETE FROM library_paths WHERE id = ?1",
+            rusqlite::params![path_id.0 as i64],
+        )?;
+        Ok(())
+    }
+
     pub fn load_from_db(conn: &Arc<Mutex<Connection>>) -> SqlResult<Self> {
         let conn_guard = conn.lock().unwrap();
 
         let mut library = Library::new();
 
         // Load library paths
-        let mut path_stmt =
-            conn_guard.prepare("SELECT id, path, status, display_name FROM library_paths")?;
+        let mut path_stmt = conn_guard.prepare(
+            "SELECT id, path, status, display_name, priority, exclude_patterns FROM library_paths",
+        )?;
 
         let path_rows = path_stmt.query_map([], |row| {
             let id: i64 = row.get(0)?;
             let path_str: String = row.get(1)?;
             let status_raw: u8 = row.get(2)?;
             let display_name: String = row.get(3)?;
+            let priority: i32 = row.get(4)?;
+            let exclude_patterns: String = row.get(5)?;
 
             let status = match status_raw {
                 0 => LibraryPathStatus::NotImported,
@@ -225,6 +439,8 @@ impl Library {
             lib_path.id = id;
             lib_path.status = status;
             lib_path.display_name = display_name;
+            lib_path.priority = priority;
+            lib_path.exclude_patterns = exclude_patterns.lines().map(str::to_string).collect();
 
             Ok(lib_path)
         })?;
@@ -235,7 +451,10 @@ impl Library {
 
         // Load library items
         let mut item_stmt = conn_guard.prepare(
-            "SELECT key, library_path_id, path, title, artist, album, year, genre, track_number, lyrics 
+            "SELECT key, library_path_id, path, title, artist, album, year, genre, track_number, lyrics,
+                    track_gain, track_peak, album_gain, album_peak, album_artist, artists, added_at,
+                    modified_at, codec, bitrate, sample_rate, bit_depth, channels, preamp_db,
+                    play_count, rating, skip_count, explicit, audio_track
              FROM library_items"
         )?;
 
@@ -258,9 +477,21 @@ impl Library {
             item.set_genre(row.get::<_, Option<String>>(7)?.as_deref());
             item.set_track_number(row.get::<_, Option<u32>>(8)?);
             item.set_lyrics(row.get::<_, Option<String>>(9)?.as_deref());
+            item.set_replay_gain(ReplayGain::from_row(row, 10)?);
+            item.set_album_artist(row.get::<_, Option<String>>(14)?.as_deref());
+            item.set_artists(split_joined_artists(row.get(15)?));
+            item.set_added_at(row.get(16)?);
+            item.set_modified_at(row.get(17)?);
+            item.set_format(AudioFormat::from_row(row, 18)?);
+            item.set_preamp_db(row.get::<_, Option<f32>>(23)?.unwrap_or(0.0));
+            item.set_play_count(row.get::<_, Option<u32>>(24)?.unwrap_or(0));
+            item.set_rating(row.get::<_, Option<u8>>(25)?.unwrap_or(0));
+            item.set_skip_count(row.get::<_, Option<u32>>(26)?.unwrap_or(0));
+            item.set_explicit(row.get::<_, Option<bool>>(27)?.unwrap_or(false));
+            item.set_audio_track(row.get(28)?);
 
             // Force the key to match the database
-            if let Ok(key_val) = key_str.parse::<usize>() {
+            if let Ok(key_val) = key_str.parse::<TrackKey>() {
                 item.set_key(key_val);
             }
 
@@ -272,31 +503,11 @@ impl Library {
             items.push(item_result?);
         }
 
-        // Load pictures for each item
+        // Load the pictures of all items at once
+        let mut pictures = Picture::load_all(&conn_guard, None)?;
         for item in &mut items {
-            let item_key = item.key() as i64;
-
-            let mut pic_stmt = conn_guard.prepare(
-                "SELECT mime_type, picture_type, description, file_path 
-                 FROM pictures WHERE library_item_id = ?",
-            )?;
-
-            let picture_rows = pic_stmt.query_map(rusqlite::params![item_key], |row| {
-                let mime_type: String = row.get(0)?;
-                let picture_type: u8 = row.get(1)?;
-                let description: String = row.get(2)?;
-                let file_path: String = row.get(3)?;
-
-                Ok(Picture::new(
-                    mime_type,
-                    picture_type,
-                    description,
-                    PathBuf::from(file_path),
-                ))
-            })?;
-
-            for picture_result in picture_rows {
-                item.add_picture(picture_result?);
+            for picture in pictures.remove(&item.key().to_string()).unwrap_or_default() {
+                item.add_picture(picture);
             }
         }
 
@@ -305,24 +516,22 @@ impl Library {
 
         // Build view containers from the items
         // This logic depends on how you want to organize your views
-        let mut album_containers: std::collections::HashMap<String, LibraryItemContainer> =
-            std::collections::HashMap::new();
+        let mut album_containers: std::collections::HashMap<
+            (String, String),
+            LibraryItemContainer,
+        > = std::collections::HashMap::new();
 
         for item in &library.items {
-            if let Some(album) = item.album() {
-                if !album_containers.contains_key(&album) {
-                    album_containers.insert(
-                        album.clone(),
-                        LibraryItemContainer {
-                            name: album.clone(),
-                            items: Vec::new(),
-                        },
-                    );
-                }
-
-                if let Some(container) = album_containers.get_mut(&album) {
-                    container.items.push(item.clone());
-                }
+            // Albums are told apart by album artist, so same-named albums don't merge
+            if let Some(album_key) = item.album_key() {
+                album_containers
+                    .entry(album_key.clone())
+                    .or_insert_with(|| LibraryItemContainer {
+                        name: album_key.1.clone(),
+                        items: Vec::new(),
+                    })
+                    .items
+                    .push(item.clone());
             }
         }
 
@@ -345,26 +554,37 @@ pub struct LibraryPath {
     path: PathBuf,
     status: LibraryPathStatus,
     display_name: String,
+    // Copies of a file under several paths come from the highest priority one
+    #[serde(default)]
+    priority: i32,
+    // Globs of files the importer skips, e.g. "**/ringtones/**" or "*.m4b"
+    #[serde(default)]
+    exclude_patterns: Vec<String>,
 }
 
 impl LibraryPath {
     pub fn new(path: PathBuf) -> Self {
         use rand::Rng; // TODO - use ULID?
-                       // Extract the folder name from the path for display
-        let display_name = path
-            .file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or("Unknown Folder")
-            .to_string();
+        let display_name = Self::folder_name(&path);
 
         Self {
             path,
             status: LibraryPathStatus::NotImported,
             id: LibraryPathId::new(rand::thread_rng().gen()),
             display_name,
+            priority: 0,
+            exclude_patterns: Vec::new(),
         }
     }
 
+    // Extract the folder name from the path for display
+    fn folder_name(path: &std::path::Path) -> String {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("Unknown Folder")
+            .to_string()
+    }
+
     pub fn id(&self) -> LibraryPathId {
         self.id
     }
@@ -384,6 +604,69 @@ impl LibraryPath {
     pub fn display_name(&self) -> &str {
         &self.display_name
     }
+
+    /// Renames the path in the sidebar, an empty name goes back to the folder name.
+    pub fn set_display_name(&mut self, name: &str) {
+        let name = name.trim();
+        self.display_name = if name.is_empty() {
+            Self::folder_name(&self.path)
+        } else {
+            name.to_string()
+        };
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+    }
+
+    pub fn exclude_patterns(&self) -> &[String] {
+        &self.exclude_patterns
+    }
+
+    pub fn set_exclude_patterns(&mut self, patterns: Vec<String>) {
+        self.exclude_patterns = patterns
+            .into_iter()
+            .map(|pattern| pattern.trim().to_string())
+            .filter(|pattern| !pattern.is_empty())
+            .collect();
+    }
+
+    /// The exclude patterns ready for matching, invalid ones are left out.
+    pub fn exclude_globs(&self) -> Vec<glob::Pattern> {
+        self.exclude_patterns
+            .iter()
+            .filter_map(|pattern| match glob::Pattern::new(pattern) {
+                Ok(glob) => Some(glob),
+                Err(e) => {
+                    tracing::warn!("Invalid exclude pattern {:?}: {}", pattern, e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Whether a file, given relative to its library path, matches one of the excludes.
+/// Patterns without a slash are matched against the file name alone, like `*.m4b`.
+pub fn is_excluded(excludes: &[glob::Pattern], relative_path: &std::path::Path) -> bool {
+    let options = glob::MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    excludes.iter().any(|glob| {
+        if glob.as_str().contains('/') {
+            glob.matches_path_with(relative_path, options)
+        } else {
+            relative_path
+                .file_name()
+                .is_some_and(|name| glob.matches_with(&name.to_string_lossy(), options))
+        }
+    })
 }
 
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Serialize, Deserialize)]
@@ -401,36 +684,68 @@ pub enum LibraryPathStatus {
     Imported,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LibraryItem {
     library_id: LibraryPathId,
     path: PathBuf,
     title: Option<String>,
     artist: Option<String>,
+    artists: Vec<String>,
+    album_artist: Option<String>,
     album: Option<String>,
     year: Option<i32>,
     genre: Option<String>,
     track_number: Option<u32>,
-    key: usize,
+    key: TrackKey,
     pictures: Vec<Picture>,
     lyrics: Option<String>,
+    replay_gain: ReplayGain,
+    #[serde(default)]
+    added_at: Option<i64>,
+    #[serde(default)]
+    modified_at: Option<i64>,
+    #[serde(default)]
+    format: AudioFormat,
+    #[serde(default)]
+    preamp_db: f32,
+    #[serde(default)]
+    play_count: u32,
+    #[serde(default)]
+    rating: u8,
+    #[serde(default)]
+    skip_count: u32,
+    #[serde(default)]
+    explicit: bool,
+    #[serde(default)]
+    audio_track: Option<u32>,
 }
 
 impl LibraryItem {
     pub fn new(path: PathBuf, library_id: LibraryPathId) -> Self {
-        use rand::Rng; // TODO - use ULID?
         Self {
             library_id,
             path,
             title: None,
             artist: None,
+            artists: Vec::new(),
+            album_artist: None,
             album: None,
             year: None,
             genre: None,
             track_number: None,
-            key: rand::thread_rng().gen(),
+            key: TrackKey::new(),
             pictures: Vec::new(),
             lyrics: None,
+            replay_gain: ReplayGain::default(),
+            added_at: None,
+            modified_at: None,
+            format: AudioFormat::default(),
+            preamp_db: 0.0,
+            play_count: 0,
+            rating: 0,
+            skip_count: 0,
+            explicit: false,
+            audio_track: None,
         }
     }
 
@@ -438,15 +753,38 @@ impl LibraryItem {
         self.library_id
     }
 
+    // The fields that can be edited in the app
+    fn keep_edits_of(&mut self, other: &LibraryItem) {
+        self.title = other.title.clone();
+        self.artist = other.artist.clone();
+        self.artists = other.artists.clone();
+        self.album = other.album.clone();
+        self.year = other.year;
+        self.genre = other.genre.clone();
+        self.track_number = other.track_number;
+        self.lyrics = other.lyrics.clone();
+    }
+
+    pub fn set_library_id(&mut self, library_id: LibraryPathId) {
+        self.library_id = library_id;
+    }
+
     pub fn path(&self) -> PathBuf {
         self.path.clone()
     }
 
-    pub fn key(&self) -> usize {
+    /// An MP4 audiobook, which plays in order and is always resumed where it was left.
+    pub fn is_audiobook(&self) -> bool {
+        self.path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("m4b"))
+    }
+
+    pub fn key(&self) -> TrackKey {
         self.key
     }
 
-    pub fn set_key(&mut self, key: usize) {
+    pub fn set_key(&mut self, key: TrackKey) {
         self.key = key;
     }
 
@@ -473,6 +811,40 @@ impl LibraryItem {
         self.artist.clone()
     }
 
+    /// All credited artists, `artist` holds them formatted for display.
+    pub fn set_artists(&mut self, artists: Vec<String>) -> Self {
+        self.artists = artists;
+        self.to_owned()
+    }
+
+    pub fn artists(&self) -> &Vec<String> {
+        &self.artists
+    }
+
+    pub fn set_album_artist(&mut self, album_artist: Option<&str>) -> Self {
+        if let Some(album_artist) = album_artist {
+            self.album_artist = Some(album_artist.to_string());
+        }
+        self.to_owned()
+    }
+
+    pub fn album_artist(&self) -> Option<String> {
+        self.album_artist.clone()
+    }
+
+    /// Groups tracks into albums: by album artist, falling back to the first artist
+    /// so compilations and guest appearances don't split an album.
+    pub fn album_key(&self) -> Option<(String, String)> {
+        let album = self.album.clone()?;
+        let artist = self
+            .album_artist
+            .clone()
+            .or_else(|| self.artists.first().cloned())
+            .or_else(|| self.artist.clone())
+            .unwrap_or_default();
+        Some((artist, album))
+    }
+
     pub fn set_album(&mut self, album: Option<&str>) -> Self {
         if let Some(album) = album {
             self.album = Some(album.to_string());
@@ -535,15 +907,197 @@ impl LibraryItem {
     pub fn lyrics(&self) -> Option<String> {
         self.lyrics.clone()
     }
+
+    pub fn set_replay_gain(&mut self, replay_gain: ReplayGain) -> Self {
+        self.replay_gain = replay_gain;
+        self.to_owned()
+    }
+
+    pub fn replay_gain(&self) -> ReplayGain {
+        self.replay_gain
+    }
+
+    /// When the track was imported into the library, in unix seconds.
+    pub fn set_added_at(&mut self, added_at: Option<i64>) -> Self {
+        self.added_at = added_at;
+        self.to_owned()
+    }
+
+    pub fn added_at(&self) -> Option<i64> {
+        self.added_at
+    }
+
+    /// Modification time of the file when it was last imported, in unix seconds.
+    pub fn set_modified_at(&mut self, modified_at: Option<i64>) -> Self {
+        self.modified_at = modified_at;
+        self.to_owned()
+    }
+
+    pub fn modified_at(&self) -> Option<i64> {
+        self.modified_at
+    }
+
+    pub fn set_format(&mut self, format: AudioFormat) -> Self {
+        self.format = format;
+        self.to_owned()
+    }
+
+    pub fn format(&self) -> &AudioFormat {
+        &self.format
+    }
+
+    /// Volume offset of this track in dB, on top of the master volume and ReplayGain.
+    pub fn set_preamp_db(&mut self, preamp_db: f32) -> Self {
+        self.preamp_db = preamp_db;
+        self.to_owned()
+    }
+
+    pub fn preamp_db(&self) -> f32 {
+        self.preamp_db
+    }
+
+    pub fn set_play_count(&mut self, play_count: u32) -> Self {
+        self.play_count = play_count;
+        self.to_owned()
+    }
+
+    pub fn play_count(&self) -> u32 {
+        self.play_count
+    }
+
+    /// Stars from 1 to 5, 0 when unrated.
+    pub fn set_rating(&mut self, rating: u8) -> Self {
+        self.rating = rating.min(MAX_RATING);
+        self.to_owned()
+    }
+
+    pub fn rating(&self) -> u8 {
+        self.rating
+    }
+
+    pub fn set_skip_count(&mut self, skip_count: u32) -> Self {
+        self.skip_count = skip_count;
+        self.to_owned()
+    }
+
+    pub fn skip_count(&self) -> u32 {
+        self.skip_count
+    }
+
+    /// Marked as explicit content, see `App::clean_filter`.
+    pub fn set_explicit(&mut self, explicit: bool) -> Self {
+        self.explicit = explicit;
+        self.to_owned()
+    }
+
+    pub fn explicit(&self) -> bool {
+        self.explicit
+    }
+
+    /// Which of the file's audio tracks plays, the first when unset. Counts the tracks
+    /// `media_source::audio_tracks` lists, e.g. the languages of a Matroska file.
+    pub fn set_audio_track(&mut self, audio_track: Option<u32>) -> Self {
+        self.audio_track = audio_track;
+        self.to_owned()
+    }
+
+    pub fn audio_track(&self) -> Option<u32> {
+        self.audio_track
+    }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+/// Formats unix seconds as a "YYYY-MM-DD" date in UTC.
+pub fn format_date(timestamp: i64) -> String {
+    // Civil-from-days conversion of the proleptic Gregorian calendar
+    let days = timestamp.div_euclid(86400);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Order of the tracks inside library folders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LibrarySort {
+    #[default]
+    Title,
+    // Newest first
+    Added,
+    Modified,
+}
+
+impl LibrarySort {
+    pub fn sort(&self, items: &mut [&LibraryItem]) {
+        match self {
+            LibrarySort::Title => items.sort_by(|a, b| {
+                a.title()
+                    .unwrap_or_default()
+                    .cmp(&b.title().unwrap_or_default())
+            }),
+            LibrarySort::Added => items.sort_by_key(|item| Reverse(item.added_at())),
+            LibrarySort::Modified => items.sort_by_key(|item| Reverse(item.modified_at())),
+        }
+    }
+}
+
+// Artists are stored in one column, separated like ID3v2.4 multi-value frames
+const ARTISTS_SEPARATOR: &str = "; ";
+
+pub fn join_artists(artists: &[String]) -> Option<String> {
+    (!artists.is_empty()).then(|| artists.join(ARTISTS_SEPARATOR))
+}
+
+pub fn split_joined_artists(artists: Option<String>) -> Vec<String> {
+    artists
+        .map(|artists| {
+            artists
+                .split(ARTISTS_SEPARATOR)
+                .map(|artist| artist.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// ReplayGain values for a track, gains in dB and peaks as linear amplitude.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Reads the four ReplayGain columns starting at `first_column`.
+    pub fn from_row(row: &rusqlite::Row, first_column: usize) -> SqlResult<Self> {
+        Ok(Self {
+            track_gain: row.get(first_column)?,
+            track_peak: row.get(first_column + 1)?,
+            album_gain: row.get(first_column + 2)?,
+            album_peak: row.get(first_column + 3)?,
+        })
+    }
+
+    pub fn is_missing(&self) -> bool {
+        self.track_gain.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LibraryView {
     pub view_type: ViewType,
     pub containers: Vec<LibraryItemContainer>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LibraryItemContainer {
     pub name: String,
     pub items: Vec<LibraryItem>,
@@ -578,4 +1132,276 @@ impl Picture {
             file_path,
         }
     }
+
+    /// Pictures by the key of their item, of all items or only those in a playlist.
+    /// One query instead of one per item, which took seconds on large playlists.
+    pub fn load_all(
+        conn: &Connection,
+        playlist_id: Option<i64>,
+    ) -> SqlResult<HashMap<String, Vec<Picture>>> {
+        let mut stmt = conn.prepare_cached(
+            "SELECT library_item_id, mime_type, picture_type, description, file_path
+             FROM pictures
+             WHERE ?1 IS NULL OR library_item_id IN
+                (SELECT library_item_id FROM playlist_items WHERE playlist_id = ?1)
+             ORDER BY id",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![playlist_id], |row| {
+            let key: String = row.get(0)?;
+            let file_path: String = row.get(4)?;
+            let picture = Picture::new(row.get(1)?, row.get(2)?, row.get(3)?, file_path.into());
+            Ok((key, picture))
+        })?;
+
+        let mut pictures: HashMap<String, Vec<Picture>> = HashMap::new();
+        for row in rows {
+            let (key, picture) = row?;
+            pictures.entry(key).or_default().push(picture);
+        }
+        Ok(pictures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_date_converts_unix_seconds() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_767_225_599), "2025-12-31");
+        assert_eq!(format_date(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn removed_paths_come_back_with_their_tracks() {
+        let mut library = Library::new();
+        library.add_path(PathBuf::from("/music"));
+        library.add_path(PathBuf::from("/podcasts"));
+        let music = library.paths()[0].id();
+        let podcasts = library.paths()[1].id();
+        library.add_item(
+            LibraryItem::new(PathBuf::from("/music/a.mp3"), music),
+            false,
+        );
+        library.add_item(
+            LibraryItem::new(PathBuf::from("/podcasts/b.mp3"), podcasts),
+            false,
+        );
+        let key = library.items()[1].key();
+
+        let (path, items) = library.take_path(podcasts).unwrap();
+        assert!(library.take_path(podcasts).is_none());
+        assert_eq!(library.items().len(), 1);
+        assert!(!library.contains(&items[0]));
+
+        library.restore_path(path, items);
+        assert_eq!(library.paths().len(), 2);
+        assert_eq!(library.items()[1].key(), key);
+        assert!(library.contains(&library.items()[1]));
+    }
+
+    #[test]
+    fn copies_come_from_the_preferred_path() {
+        let mut library = Library::new();
+        library.add_path(PathBuf::from("/laptop/music"));
+        library.add_path(PathBuf::from("/nas/music"));
+        let laptop = library.paths()[0].id();
+        let nas = library.paths()[1].id();
+        library.set_path_priority(laptop, 1);
+
+        let song = |root: &str, id| LibraryItem::new(PathBuf::from(root).join("a/song.mp3"), id);
+        library.add_item(song("/nas/music", nas), false);
+        library.add_item(song("/laptop/music", laptop), false);
+        library.add_item(song("/nas/music", nas), false);
+        assert_eq!(library.items().len(), 1);
+        assert_eq!(library.items()[0].library_id(), laptop);
+
+        // Other files of the lower priority path are still added
+        library.add_item(
+            LibraryItem::new(PathBuf::from("/nas/music/b.mp3"), nas),
+            false,
+        );
+        assert_eq!(library.items().len(), 2);
+
+        library.set_path_display_name(nas, "  NAS ");
+        assert_eq!(library.paths()[1].display_name(), "NAS");
+        library.set_path_display_name(nas, "");
+        assert_eq!(library.paths()[1].display_name(), "music");
+    }
+
+    // A track of /music/a.mp3 as the scanner reads it
+    fn scanned(library: &Library, title: &str, modified_at: i64) -> LibraryItem {
+        let mut item = LibraryItem::new(PathBuf::from("/music/a.mp3"), library.paths()[0].id());
+        item.set_title(Some(title));
+        item.set_modified_at(Some(modified_at));
+        item
+    }
+
+    // Saves the library and reads it back like the next start does
+    fn saved_and_loaded(library: &Library, db: &crate::db::Database) -> LibraryItem {
+        library.save_to_db(&db.connection()).unwrap();
+        Library::load_from_db(&db.connection()).unwrap().items()[0].clone()
+    }
+
+    #[test]
+    fn resync_keeps_edits_made_in_read_only_mode() {
+        let db = crate::db::Database::open_in_memory().unwrap();
+        let mut library = Library::new();
+        library.add_path(PathBuf::from("/music"));
+        library.add_item(scanned(&library, "Tilte", 1000), true);
+        let key = library.items()[0].key();
+        library.update_item(key, |item| {
+            item.set_title(Some("Title"));
+            item.set_lyrics(Some("la la"));
+        });
+        saved_and_loaded(&library, &db);
+
+        // Resync all, even with the file touched the edit stays
+        library.add_item(scanned(&library, "Tilte", 2000), true);
+        let item = saved_and_loaded(&library, &db);
+        assert_eq!(item.key(), key);
+        assert_eq!(item.title().as_deref(), Some("Title"));
+        assert_eq!(item.lyrics().as_deref(), Some("la la"));
+        assert_eq!(item.modified_at(), Some(2000));
+
+        // Out of read-only mode an unchanged file keeps it too
+        library.add_item(scanned(&library, "Tilte", 2000), false);
+        assert_eq!(
+            saved_and_loaded(&library, &db).title().as_deref(),
+            Some("Title")
+        );
+
+        // A file changed since brings its own tags
+        library.add_item(scanned(&library, "Other", 3000), false);
+        assert_eq!(
+            saved_and_loaded(&library, &db).title().as_deref(),
+            Some("Other")
+        );
+    }
+
+    // A track edited with `edit` after import, then rescanned with its file changed
+    fn rescanned(edit: impl FnMut(&mut LibraryItem)) -> LibraryItem {
+        let db = crate::db::Database::open_in_memory().unwrap();
+        let mut library = Library::new();
+        library.add_path(PathBuf::from("/music"));
+        library.add_item(scanned(&library, "Title", 1000), false);
+        library.update_item(library.items()[0].key(), edit);
+        saved_and_loaded(&library, &db);
+        library.add_item(scanned(&library, "Title", 2000), false);
+        saved_and_loaded(&library, &db)
+    }
+
+    #[test]
+    fn resync_keeps_the_preamp() {
+        let item = rescanned(|item| {
+            item.set_preamp_db(-4.5);
+        });
+        assert_eq!(item.preamp_db(), -4.5);
+    }
+
+    #[test]
+    fn resync_keeps_plays_and_rating() {
+        let item = rescanned(|item| {
+            item.set_play_count(12);
+            item.set_rating(4);
+        });
+        assert_eq!(item.play_count(), 12);
+        assert_eq!(item.rating(), 4);
+    }
+
+    #[test]
+    fn resync_keeps_skips() {
+        let item = rescanned(|item| {
+            item.set_skip_count(3);
+        });
+        assert_eq!(item.skip_count(), 3);
+    }
+
+    #[test]
+    fn resync_keeps_the_chosen_audio_track() {
+        let item = rescanned(|item| {
+            item.set_audio_track(Some(2));
+        });
+        assert_eq!(item.audio_track(), Some(2));
+    }
+
+    #[test]
+    fn resync_keeps_measured_replay_gain() {
+        let measured = ReplayGain {
+            track_gain: Some(-6.5),
+            track_peak: Some(0.9),
+            album_gain: None,
+            album_peak: None,
+        };
+        let item = rescanned(|item| {
+            item.set_replay_gain(measured);
+        });
+        assert_eq!(item.replay_gain(), measured);
+    }
+
+    #[test]
+    fn resync_reads_the_explicit_flag_from_the_file() {
+        let db = crate::db::Database::open_in_memory().unwrap();
+        let mut library = Library::new();
+        library.add_path(PathBuf::from("/music"));
+        let mut tagged = scanned(&library, "Title", 1000);
+        tagged.set_explicit(true);
+        library.add_item(tagged.clone(), false);
+        saved_and_loaded(&library, &db);
+
+        library.add_item(tagged, true);
+        assert!(saved_and_loaded(&library, &db).explicit());
+
+        // The advisory tag was removed from the file
+        library.add_item(scanned(&library, "Title", 2000), false);
+        assert!(!saved_and_loaded(&library, &db).explicit());
+    }
+
+    #[test]
+    fn pictures_load_by_item_and_playlist() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE pictures (id INTEGER PRIMARY KEY, library_item_id TEXT,
+                mime_type TEXT, picture_type INTEGER, description TEXT, file_path TEXT);
+             CREATE TABLE playlist_items (playlist_id INTEGER, library_item_id TEXT);
+             INSERT INTO pictures VALUES (1, '1', 'image/jpeg', 3, '', 'front.jpg');
+             INSERT INTO pictures VALUES (2, '1', 'image/png', 4, 'back', 'back.png');
+             INSERT INTO pictures VALUES (3, '2', 'image/jpeg', 3, '', 'other.jpg');
+             INSERT INTO playlist_items VALUES (7, '1');
+             INSERT INTO playlist_items VALUES (7, '1');",
+        )
+        .unwrap();
+
+        let all = Picture::load_all(&conn, None).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all["1"].len(), 2);
+        assert_eq!(all["1"][1].file_path, PathBuf::from("back.png"));
+
+        let in_playlist = Picture::load_all(&conn, Some(7)).unwrap();
+        assert_eq!(in_playlist.len(), 1);
+        assert_eq!(in_playlist["1"].len(), 2);
+    }
+
+    #[test]
+    fn excludes_match_folders_and_file_names() {
+        let mut lib_path = LibraryPath::new(PathBuf::from("/music"));
+        lib_path.set_exclude_patterns(vec![
+            "**/ringtones/**".to_string(),
+            " *.M4B ".to_string(),
+            String::new(),
+            "[invalid".to_string(),
+        ]);
+        assert_eq!(lib_path.exclude_patterns().len(), 3);
+        let excludes = lib_path.exclude_globs();
+        assert_eq!(excludes.len(), 2);
+
+        let excluded = |path: &str| is_excluded(&excludes, std::path::Path::new(path));
+        assert!(excluded("ringtones/beep.mp3"));
+        assert!(excluded("phone/ringtones/beep.mp3"));
+        assert!(excluded("books/novel.m4b"));
+        assert!(!excluded("albums/ringtones.mp3"));
+        assert!(!excluded("albums/song.mp3"));
+    }
 }
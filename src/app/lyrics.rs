@@ -0,0 +1,151 @@
+//! Lyrics from an `.lrc` file next to the track. Lines timed with `[mm:ss.xx]` tags
+//! follow playback, files without tags are shown as plain text.
+
+use super::seek::parse_time;
+use super::App;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+    /// Seconds into the track, None for untimed lyrics.
+    pub time: Option<f64>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Lyrics {
+    lines: Vec<LyricLine>,
+}
+
+impl Lyrics {
+    pub fn parse(source: &str) -> Self {
+        let mut timed = Vec::new();
+        let mut plain = Vec::new();
+        let mut offset = 0.0;
+
+        for line in source.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+
+            // A line can carry several time tags when it repeats, e.g. a chorus
+            while let Some(tag) = rest.strip_prefix('[') {
+                let Some((inside, after)) = tag.split_once(']') else {
+                    break;
+                };
+                if let Some(time) = parse_time(inside) {
+                    times.push(time);
+                } else if let Some(ms) = inside.strip_prefix("offset:") {
+                    // Positive offsets show the lyrics earlier
+                    offset = ms.trim().parse::<f64>().unwrap_or(0.0) / 1000.0;
+                } else if times.is_empty() {
+                    // Metadata like [ar:Artist], not part of the lyrics
+                    rest = "";
+                    break;
+                }
+                rest = after;
+            }
+
+            let text = rest.trim().to_string();
+            if times.is_empty() {
+                if !text.is_empty() {
+                    plain.push(LyricLine { time: None, text });
+                }
+            } else {
+                timed.extend(times.into_iter().map(|time| LyricLine {
+                    time: Some(time),
+                    text: text.clone(),
+                }));
+            }
+        }
+
+        if timed.is_empty() {
+            return Self { lines: plain };
+        }
+        for line in &mut timed {
+            line.time = line.time.map(|time| (time - offset).max(0.0));
+        }
+        timed.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        Self { lines: timed }
+    }
+
+    /// The `.lrc` file with the same name as the track, if there is one.
+    pub fn path_for(track: &Path) -> PathBuf {
+        track.with_extension("lrc")
+    }
+
+    pub fn load_for(track: &Path) -> Option<Self> {
+        let source = std::fs::read_to_string(Self::path_for(track)).ok()?;
+        let lyrics = Self::parse(&source);
+        (!lyrics.lines.is_empty()).then_some(lyrics)
+    }
+
+    pub fn lines(&self) -> &[LyricLine] {
+        &self.lines
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.lines.first().is_some_and(|line| line.time.is_some())
+    }
+
+    /// Index of the line being sung at `seconds`, None before the first one.
+    pub fn current_line(&self, seconds: f64) -> Option<usize> {
+        if !self.is_synced() {
+            return None;
+        }
+        self.lines
+            .partition_point(|line| line.time.unwrap_or(0.0) <= seconds)
+            .checked_sub(1)
+    }
+}
+
+impl App {
+    /// Lyrics of the selected track, read once per track.
+    pub fn current_lyrics(&mut self) -> Option<&Lyrics> {
+        let track = self.player.as_ref()?.selected_track.as_ref()?;
+        if self.lyrics.as_ref().map(|(key, _)| *key) != Some(track.key()) {
+            self.lyrics = Some((track.key(), Lyrics::load_for(&track.path())));
+        }
+        self.lyrics.as_ref()?.1.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timed_lines_are_sorted_and_repeated_tags_expanded() {
+        let lyrics = Lyrics::parse(
+            "[ar:Someone]\n\
+             [ti:Song]\n\
+             [00:12.50]First line\n\
+             [00:20.00][01:05.00]Chorus\n\
+             [00:30.00]\n\
+             [00:40.00]Second verse\n",
+        );
+
+        let times: Vec<_> = lyrics.lines().iter().map(|line| line.time).collect();
+        assert_eq!(
+            times,
+            vec![Some(12.5), Some(20.0), Some(30.0), Some(40.0), Some(65.0)]
+        );
+        assert_eq!(lyrics.lines()[4].text, "Chorus");
+        assert!(lyrics.is_synced());
+
+        assert_eq!(lyrics.current_line(5.0), None);
+        assert_eq!(lyrics.current_line(12.5), Some(0));
+        assert_eq!(lyrics.current_line(35.0), Some(2));
+        assert_eq!(lyrics.current_line(300.0), Some(4));
+    }
+
+    #[test]
+    fn offset_moves_lines_and_untagged_files_stay_plain() {
+        let lyrics = Lyrics::parse("[offset:500]\n[00:01.00]Early\n");
+        assert_eq!(lyrics.lines()[0].time, Some(0.5));
+
+        let plain = Lyrics::parse("Just words\n\nMore words\n");
+        assert!(!plain.is_synced());
+        assert_eq!(plain.lines().len(), 2);
+        assert_eq!(plain.current_line(10.0), None);
+    }
+}
@@ -0,0 +1,296 @@
+//! Tag edits from the playlist are written on a worker thread. Edits made in quick
+//! succession share one database transaction, and the library in memory is updated in
+//! place once they're stored.
+
+use super::library::{self, LibraryItem, TrackKey};
+use super::tag_writer::{self, TagField};
+use super::{tags, App, LibraryCommand};
+use eframe::egui;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// How long the worker waits for more edits before writing what it has
+const BATCH_WINDOW: Duration = Duration::from_millis(250);
+const MAX_BATCH: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataEdit {
+    pub key: TrackKey,
+    pub path: PathBuf,
+    pub field: TagField,
+    pub value: String,
+    // Read-only libraries keep the files untouched and only update the database
+    pub database_only: bool,
+}
+
+impl MetadataEdit {
+    pub fn apply_to(&self, item: &mut LibraryItem) {
+        let value = Some(self.value.as_str());
+        match self.field {
+            TagField::Title => {
+                item.set_title(value);
+            }
+            TagField::Artist => {
+                item.set_artist(value);
+                item.set_artists(tags::all_artists(&self.value));
+            }
+            TagField::Album => {
+                item.set_album(value);
+            }
+            TagField::Genre => {
+                item.set_genre(value);
+            }
+            TagField::Lyrics => {
+                item.set_lyrics(value);
+            }
+            TagField::TrackNumber => {
+                item.set_track_number(self.value.trim().parse().ok());
+            }
+            TagField::Year => {
+                item.set_year(self.value.trim().parse().ok());
+            }
+        }
+    }
+
+    fn write_tag(&self) -> bool {
+        if self.database_only {
+            tracing::info!(
+                "Read-only library, updating {} in the database only",
+                self.field.name()
+            );
+            return true;
+        }
+
+        let Some(writer) = tag_writer::writer_for(&self.path) else {
+            tracing::error!("No tag writer for file type: {:?}", self.path);
+            return false;
+        };
+        match writer.write(&self.path, self.field, &self.value) {
+            Ok(_) => {
+                tracing::info!(
+                    "Successfully updated {} to '{}' for file: {:?}",
+                    self.field.name(),
+                    self.value,
+                    self.path
+                );
+                true
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to write {} to {} tag for file {:?}: {}",
+                    self.field.name(),
+                    writer.format(),
+                    self.path,
+                    e
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Keeps the last edit of each field of each track, in the order they were made.
+pub fn coalesce(edits: Vec<MetadataEdit>) -> Vec<MetadataEdit> {
+    let mut last: HashMap<(TrackKey, TagField), usize> = HashMap::new();
+    for (idx, edit) in edits.iter().enumerate() {
+        last.insert((edit.key, edit.field), idx);
+    }
+    edits
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, edit)| last.get(&(edit.key, edit.field)) == Some(idx))
+        .map(|(_, edit)| edit)
+        .collect()
+}
+
+fn save_edits(conn: &Arc<Mutex<Connection>>, edits: &[MetadataEdit]) -> rusqlite::Result<()> {
+    let mut conn = conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    for edit in edits {
+        // Numbers are stored as such, an empty one clears the field
+        let value = if edit.field.is_numeric() {
+            edit.value
+                .trim()
+                .parse::<i64>()
+                .map_or(Value::Null, Value::Integer)
+        } else {
+            Value::Text(edit.value.clone())
+        };
+        tx.execute(
+            &format!(
+                "UPDATE library_items SET {} = ?1 WHERE key = ?2",
+                edit.field.name()
+            ),
+            rusqlite::params![value, edit.key.to_string()],
+        )?;
+        // Keep the separate artists in sync with the edited value
+        if edit.field == TagField::Artist {
+            tx.execute(
+                "UPDATE library_items SET artists = ?1 WHERE key = ?2",
+                rusqlite::params![
+                    library::join_artists(&tags::all_artists(&edit.value)),
+                    edit.key.to_string()
+                ],
+            )?;
+        }
+    }
+    tx.commit()
+}
+
+fn spawn_writer(
+    ctx: egui::Context,
+    conn: Option<Arc<Mutex<Connection>>>,
+    lib_cmd_tx: Sender<LibraryCommand>,
+) -> Sender<MetadataEdit> {
+    let (edit_tx, edit_rx): (Sender<MetadataEdit>, Receiver<MetadataEdit>) = channel();
+
+    std::thread::spawn(move || {
+        while let Ok(first) = edit_rx.recv() {
+            let mut batch = vec![first];
+            while batch.len() < MAX_BATCH {
+                match edit_rx.recv_timeout(BATCH_WINDOW) {
+                    Ok(edit) => batch.push(edit),
+                    Err(_) => break,
+                }
+            }
+
+            let written: Vec<MetadataEdit> = coalesce(batch)
+                .into_iter()
+                .filter(|edit| edit.write_tag())
+                .collect();
+            if written.is_empty() {
+                continue;
+            }
+
+            if let Some(conn) = &conn {
+                if let Err(e) = save_edits(conn, &written) {
+                    tracing::error!(
+                        "Failed to store {} metadata edits in database: {}",
+                        written.len(),
+                        e
+                    );
+                    continue;
+                }
+                tracing::info!("Stored {} metadata edits in database", written.len());
+            } else {
+                tracing::warn!("No database connection available for metadata update");
+            }
+
+            if lib_cmd_tx
+                .send(LibraryCommand::MetadataWritten(written))
+                .is_err()
+            {
+                break;
+            }
+            ctx.request_repaint();
+        }
+    });
+
+    edit_tx
+}
+
+impl App {
+    /// Hands an edit of `field` to the writer. The track changes everywhere once the
+    /// tag and the database are updated.
+    pub fn queue_metadata_edit(
+        &mut self,
+        ctx: &egui::Context,
+        track: &LibraryItem,
+        field: &str,
+        value: &str,
+    ) {
+        let Some(field) = TagField::from_name(field) else {
+            return; // Unsupported field
+        };
+        if !field.is_valid(value) {
+            tracing::warn!("Not a valid {}: {:?}", field.name(), value);
+            return;
+        }
+        let Some(lib_cmd_tx) = self.library_cmd_tx.clone() else {
+            return;
+        };
+
+        let edit = MetadataEdit {
+            key: track.key(),
+            path: track.path(),
+            field,
+            value: value.to_string(),
+            database_only: self.read_only_library,
+        };
+        let conn = self.database.as_ref().map(|db| db.connection());
+        let writer = self
+            .metadata_writer
+            .get_or_insert_with(|| spawn_writer(ctx.clone(), conn, lib_cmd_tx));
+        if writer.send(edit).is_err() {
+            tracing::error!("Metadata writer stopped, edit of {:?} lost", track.path());
+            self.metadata_writer = None;
+        } else {
+            self.pending_edits.insert(track.key());
+        }
+    }
+
+    /// Updates the library, the playlists and the playing track after edits were stored.
+    pub fn apply_metadata_edits(&mut self, edits: Vec<MetadataEdit>) {
+        for edit in &edits {
+            self.pending_edits.remove(&edit.key);
+            self.library
+                .update_item(edit.key, |item| edit.apply_to(item));
+
+            for playlist in &mut self.playlists {
+                for track in playlist.tracks.iter_mut() {
+                    if track.key() == edit.key {
+                        edit.apply_to(track);
+                    }
+                }
+            }
+
+            if let Some(track) = self
+                .player
+                .as_mut()
+                .and_then(|player| player.selected_track.as_mut())
+                .filter(|track| track.key() == edit.key)
+            {
+                edit.apply_to(track);
+            }
+        }
+        self.refresh_properties();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(key: u128, field: TagField, value: &str) -> MetadataEdit {
+        MetadataEdit {
+            key: TrackKey::from(key),
+            path: PathBuf::from(format!("{}.mp3", key)),
+            field,
+            value: value.to_string(),
+            database_only: false,
+        }
+    }
+
+    #[test]
+    fn only_the_last_edit_of_a_field_is_kept() {
+        let edits = coalesce(vec![
+            edit(1, TagField::Title, "Fir"),
+            edit(2, TagField::Title, "Other"),
+            edit(1, TagField::Artist, "Someone"),
+            edit(1, TagField::Title, "First"),
+        ]);
+        assert_eq!(
+            edits,
+            vec![
+                edit(2, TagField::Title, "Other"),
+                edit(1, TagField::Artist, "Someone"),
+                edit(1, TagField::Title, "First"),
+            ]
+        );
+    }
+}
@@ -1,45 +1,102 @@
+use crate::channel_mix::{Balance, ChannelMix};
+use crate::crossfeed::CrossfeedLevel;
+use crate::dsp::{self, EffectConfig, EffectKind};
+use crate::plugin::PluginConfig;
 use library::{
     Library, LibraryItem, LibraryItemContainer, LibraryPath, LibraryPathId, LibraryPathStatus,
-    LibraryView, Picture, ViewType,
+    LibrarySort, LibraryView, Picture, ReplayGain, TrackKey, ViewType,
 };
-use player::Player;
+use player::{Player, ReplayGainMode};
 use playlist::Playlist;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Arc;
 
-use id3::{Tag, TagLike};
 use rayon::prelude::*;
 
-use rand::Rng;
 use std::fs;
-use std::io::Write;
 use std::path::PathBuf;
 
 // Window size constants
 pub const DEFAULT_WINDOW_WIDTH: f32 = 750.0;
 pub const DEFAULT_WINDOW_HEIGHT: f32 = 468.0;
 
+pub const DEFAULT_RESUME_MIN_MINUTES: u32 = 20;
+
+mod accessibility;
+mod album_art;
+mod album_stats;
 mod app_impl;
+mod art_cache;
+pub mod audio_format;
+mod autosave;
+mod backup;
+mod bookmarks;
 mod components;
+mod diagnostics;
+pub mod events;
+mod export;
+mod favorites;
+mod folder_queue;
+mod history;
 pub mod i18n;
+mod labels;
+mod lazy_load;
 mod library;
+pub mod lyrics;
+mod metadata_writer;
+mod mp4_chapters;
+mod notes;
+mod onboarding;
+mod perf_overlay;
+mod play_stats;
 pub mod player;
+mod player_import;
 mod playlist;
+mod playlist_actions;
+mod preamp;
+mod properties;
+mod radio;
+mod recently_added;
+mod remove_folder;
+mod repaint;
+mod replaygain;
+mod report;
+pub mod resume;
+mod scan;
+pub mod seek;
+pub mod skin;
 mod style;
+mod tag_cleanup;
+mod tag_replace;
+mod tag_writer;
+mod tags;
+mod thumbnails;
+mod volume_scale;
+mod volumes;
+pub mod window_mode;
 
 // Re-export the i18n functions for convenience
-pub use i18n::{get_language, set_language, t, tf, Language};
+pub use i18n::{get_language, set_language, t, tf, tp, Language};
 
 pub enum AudioCommand {
     Stop,
     Play,
     Pause,
     Seek(u64),
-    LoadFile(std::path::PathBuf),
+    LoadFile(std::path::PathBuf, Option<u32>), // And which of the file's audio tracks to play
+    Prefetch(std::path::PathBuf),              // Read the likely next track ahead
     Select(usize),
     SetVolume(f32),
+    SetTrackGain(Option<f32>), // None = estimate loudness while playing
+    SetTrackPreamp(f32),       // Linear factor of the track's own offset
+    SetChannelMix(ChannelMix),
+    SetDspChain(Vec<EffectConfig>), // The effects to run, in order
+    SetSoftFades(bool),
+    SetTransition(Option<crate::transition::Transition>), // To the next track, None = stop
+    Shutdown, // Stop the stream and end the audio thread, the app is closing
 }
 
 pub enum UiCommand {
@@ -47,43 +104,199 @@ pub enum UiCommand {
     TotalTrackDuration(u64),
     CurrentTimestamp(u64),
     PlaybackStateChanged(bool), // true = playing, false = paused
+    TrackTimeBase(u32, u32),    // numerator, denominator
+    TrackFormat(audio_format::AudioFormat),
+    OutputInfo(crate::output::OutputInfo), // Reported whenever the output stream is (re)opened
+    PlaybackError(String), // The loaded track can't be played, the audio thread is idle
 }
 
 pub enum LibraryCommand {
     AddView(LibraryView),
-    AddItem(LibraryItem),
+    AddItem(Box<LibraryItem>),
     AddPathId(LibraryPathId),
+    UpdateReplayGain(TrackKey, ReplayGain),
+    PathAvailability(Vec<(LibraryPathId, bool)>),
+    MetadataWritten(Vec<metadata_writer::MetadataEdit>),
+    LibraryLoaded(Box<Library>),
+    PlaylistLoaded(Box<Playlist>),
+    AlbumArtCleaned(Result<(usize, u64), String>), // Files deleted and the bytes they took
 }
 
 // Struct for storing basic settings in confy
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
-    // Language setting
-    pub current_language: i18n::Language,
+    // Language picked in the selector, None follows the system language
+    pub current_language: Option<i18n::Language>,
 
     // Player state persistence
     pub last_track_path: Option<PathBuf>,
     pub last_position: Option<u64>,
     pub last_playback_mode: Option<player::PlaybackMode>,
     pub last_volume: Option<f32>,
+    pub muted: bool,
     pub was_playing: Option<bool>,
 
     // UI state
     pub library_folders_expanded: bool,
-    pub default_window_height: f64,
+    pub show_chapter_panel: bool,
+
+    // Where the full window and the mini-player were, and whether the latter floats
+    pub full_geometry: Option<window_mode::WindowGeometry>,
+    pub mini_geometry: Option<window_mode::WindowGeometry>,
+    pub mini_always_on_top: bool,
+    pub window_maximized: bool,
+    pub library_panel_width: Option<f32>,
+
+    // Look of the cassette area
+    pub skin: skin::Skin,
+    pub battery_saver: bool,
+
+    // Font families for the interface in fallback order, empty to pick automatically
+    pub ui_fonts: Vec<String>,
+
+    // When unused album art was last cleaned up, in seconds since the epoch
+    pub album_art_cleaned_at: Option<i64>,
+
+    // The first-run wizard was finished or skipped
+    pub onboarded: bool,
+
+    // Loudness normalization
+    pub replay_gain_mode: ReplayGainMode,
+    pub replay_gain_write_tags: bool,
+    pub volume_leveling: bool,
+
+    // Downmix, upmix or mono output
+    pub channel_mix: ChannelMix,
+
+    // Left/right balance from -1.0 to 1.0 and per-channel trims in dB
+    pub balance: f32,
+    pub left_trim_db: f32,
+    pub right_trim_db: f32,
+
+    // Short fades on pause, resume, stop and seek against clicks
+    pub soft_fades: bool,
+
+    // How tracks hand over to the next, by whether they share an album
+    pub transitions: crate::transition::TransitionPrefs,
+
+    // Shuffle and radios pick often skipped tracks less
+    pub downrank_skipped: bool,
+
+    // Blend of the channels for headphones
+    pub crossfeed: bool,
+    pub crossfeed_level: CrossfeedLevel,
+
+    // External LADSPA effect, only run with the ladspa feature
+    pub plugin: bool,
+    pub plugin_config: PluginConfig,
+
+    // Brick-wall limiter at the end of the chain and its ceiling in dBFS
+    pub limiter: bool,
+    pub limiter_ceiling_db: f32,
+
+    // Order the effects run in
+    pub effect_order: Vec<EffectKind>,
+
+    // Seek the audio while the time slider is dragged
+    pub scrub_while_dragging: bool,
+
+    // Count down the remaining time instead of the elapsed time
+    pub show_remaining_time: bool,
+
+    // Remember the position of tracks at least this long, 0 disables it
+    pub resume_min_minutes: u32,
+
+    // Days to keep play history, 0 keeps it forever
+    pub history_retention_days: u32,
+
+    // Never write tags or otherwise modify music files, edits only go to the database
+    pub read_only_library: bool,
+
+    // Days a track stays in the Recently Added playlist, 0 hides the playlist
+    pub recently_added_days: u32,
+
+    // Shorter files are skipped when importing, 0 imports everything
+    pub min_track_seconds: u32,
+
+    // Import files behind symbolic links found in library folders
+    pub follow_symlinks: bool,
+
+    // Import .mp4 and .mkv files too and play their audio
+    pub scan_video_files: bool,
+
+    pub library_sort: LibrarySort,
+
+    // File name pattern of tracks exported to a folder
+    pub export_pattern: String,
+
+    // Show the format and bitrate columns in the playlist table
+    pub show_format_columns: bool,
+
+    // Hide and skip tracks tagged as explicit, e.g. on a family computer
+    pub clean_filter: bool,
+
+    // Scroll the playlist table to each new track as playback moves on
+    pub follow_playback: bool,
+
+    // Labels whose playlists are open, in tab order
+    pub label_playlists: Vec<i64>,
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
-            current_language: i18n::Language::English,
+            current_language: None,
             last_track_path: None,
             last_position: None,
             last_playback_mode: None,
             last_volume: None,
+            muted: false,
             was_playing: None,
             library_folders_expanded: false,
-            default_window_height: DEFAULT_WINDOW_HEIGHT as f64,
+            show_chapter_panel: true,
+            replay_gain_mode: ReplayGainMode::Off,
+            replay_gain_write_tags: false,
+            volume_leveling: false,
+            channel_mix: ChannelMix::Source,
+            balance: 0.0,
+            left_trim_db: 0.0,
+            right_trim_db: 0.0,
+            soft_fades: true,
+            transitions: crate::transition::TransitionPrefs::default(),
+            downrank_skipped: false,
+            crossfeed: false,
+            crossfeed_level: CrossfeedLevel::default(),
+            plugin: false,
+            plugin_config: PluginConfig::default(),
+            limiter: false,
+            limiter_ceiling_db: crate::limiter::DEFAULT_CEILING_DB,
+            effect_order: dsp::default_order(),
+            scrub_while_dragging: false,
+            show_remaining_time: false,
+            resume_min_minutes: DEFAULT_RESUME_MIN_MINUTES,
+            history_retention_days: 0,
+            read_only_library: false,
+            recently_added_days: recently_added::DEFAULT_RECENTLY_ADDED_DAYS,
+            min_track_seconds: 0,
+            follow_symlinks: false,
+            scan_video_files: false,
+            library_sort: LibrarySort::Title,
+            export_pattern: export::DEFAULT_EXPORT_PATTERN.to_string(),
+            show_format_columns: false,
+            clean_filter: false,
+            follow_playback: true,
+            label_playlists: Vec::new(),
+            full_geometry: None,
+            mini_geometry: None,
+            mini_always_on_top: true,
+            window_maximized: false,
+            library_panel_width: None,
+            skin: skin::Skin::default(),
+            battery_saver: false,
+            ui_fonts: Vec::new(),
+            album_art_cleaned_at: None,
+            onboarded: false,
         }
     }
 }
@@ -112,12 +325,16 @@ pub struct App {
 
     // Language setting
     pub current_language: i18n::Language,
+    // Whether the language was picked in the selector rather than detected
+    #[serde(skip_serializing, skip_deserializing)]
+    pub language_chosen: bool,
 
     // New fields for player state persistence
     pub last_track_path: Option<PathBuf>,
     pub last_position: Option<u64>,
     pub last_playback_mode: Option<player::PlaybackMode>,
     pub last_volume: Option<f32>,
+    pub muted: bool,
     pub was_playing: Option<bool>,
 
     #[serde(skip_serializing, skip_deserializing)]
@@ -138,6 +355,14 @@ pub struct App {
     #[serde(skip_serializing, skip_deserializing)]
     pub database: Option<Arc<crate::db::Database>>,
 
+    // Why `database` couldn't be opened, until it's shown
+    #[serde(skip_serializing, skip_deserializing)]
+    pub database_error: Option<String>,
+
+    // The library is still read from the database, see `App::load_in_background`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub library_loading: bool,
+
     pub quit: bool,
 
     pub is_maximized: bool,
@@ -156,10 +381,295 @@ pub struct App {
 
     pub library_folders_expanded: bool,
 
+    // Next to the playlist while a track with chapters plays
+    pub show_chapter_panel: bool,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub show_about_dialog: bool,
 
-    pub default_window_height: f64,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub show_settings_dialog: bool,
+
+    pub replay_gain_mode: ReplayGainMode,
+
+    pub replay_gain_write_tags: bool,
+
+    pub volume_leveling: bool,
+
+    pub channel_mix: ChannelMix,
+
+    pub balance: f32,
+
+    pub left_trim_db: f32,
+
+    pub right_trim_db: f32,
+
+    pub soft_fades: bool,
+
+    pub transitions: crate::transition::TransitionPrefs,
+
+    pub downrank_skipped: bool,
+
+    pub crossfeed: bool,
+
+    pub crossfeed_level: CrossfeedLevel,
+
+    pub plugin: bool,
+
+    pub plugin_config: PluginConfig,
+
+    pub limiter: bool,
+
+    pub limiter_ceiling_db: f32,
+
+    pub effect_order: Vec<EffectKind>,
+
+    pub scrub_while_dragging: bool,
+
+    pub show_remaining_time: bool,
+
+    pub resume_min_minutes: u32,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_gain_scanning: Arc<AtomicBool>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub track_markers: bookmarks::TrackMarkers,
+
+    pub history_retention_days: u32,
+
+    pub read_only_library: bool,
+
+    pub recently_added_days: u32,
+
+    pub min_track_seconds: u32,
+
+    pub follow_symlinks: bool,
+
+    pub scan_video_files: bool,
+
+    pub library_sort: LibrarySort,
+
+    pub export_pattern: String,
+
+    pub show_format_columns: bool,
+
+    pub clean_filter: bool,
+
+    pub follow_playback: bool,
+
+    pub label_playlists: Vec<labels::LabelId>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub labels: labels::Labels,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub track_notes: notes::TrackNotes,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub favorites: favorites::Favorites,
+
+    // The playlist table scrolls to the playing track once
+    #[serde(skip_serializing, skip_deserializing)]
+    pub locate_requested: bool,
+
+    // Tab the playlist table showed last frame, a new one gets its scroll position back
+    #[serde(skip_serializing, skip_deserializing)]
+    pub shown_playlist_idx: Option<usize>,
+
+    // Showing the compact mini-player instead of the full window
+    #[serde(skip_serializing, skip_deserializing)]
+    pub mini_player: bool,
+
+    // The recent plays shown under the mini-player, the window grown to fit them
+    #[serde(skip_serializing, skip_deserializing)]
+    pub mini_recents_open: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub recent_plays: Vec<history::HistoryEntry>,
+
+    // Showing the full screen Now Playing view
+    #[serde(skip_serializing, skip_deserializing)]
+    pub now_playing_view: bool,
+
+    // Lyrics of the track with this key, None when it has no lyrics file
+    #[serde(skip_serializing, skip_deserializing)]
+    pub lyrics: Option<(TrackKey, Option<lyrics::Lyrics>)>,
+
+    pub full_geometry: Option<window_mode::WindowGeometry>,
+
+    pub mini_geometry: Option<window_mode::WindowGeometry>,
+
+    pub mini_always_on_top: bool,
+
+    // Width the library panel was dragged to
+    pub library_panel_width: Option<f32>,
+
+    pub skin: skin::Skin,
+
+    // Animations off, the window only redraws for the playback position
+    pub battery_saver: bool,
+
+    // System fonts to use first, the automatic choice stays behind them
+    pub ui_fonts: Vec<String>,
+
+    // Font families on the system, listed once the settings ask for them
+    #[serde(skip_serializing, skip_deserializing)]
+    pub system_fonts: Option<Vec<String>>,
+
+    pub album_art_cleaned_at: Option<i64>,
+
+    pub onboarded: bool,
+
+    // The first-run wizard, while it's open
+    #[serde(skip_serializing, skip_deserializing)]
+    pub onboarding: Option<onboarding::Onboarding>,
+
+    // Outcome of the last album art cleanup, shown in the settings
+    #[serde(skip_serializing, skip_deserializing)]
+    pub album_art_status: Option<String>,
+
+    // Worker writing tag edits, started with the first one
+    #[serde(skip_serializing, skip_deserializing)]
+    pub metadata_writer: Option<Sender<metadata_writer::MetadataEdit>>,
+
+    // Tracks with tag edits the writer hasn't stored yet
+    #[serde(skip_serializing, skip_deserializing)]
+    pub pending_edits: std::collections::HashSet<TrackKey>,
+
+    // Covers shown in the playlist and library rows
+    #[serde(skip_serializing, skip_deserializing)]
+    pub thumbnails: thumbnails::Thumbnails,
+
+    // Playlist the export window was opened for
+    #[serde(skip_serializing, skip_deserializing)]
+    pub export_playlist_idx: Option<usize>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub playlist_export: Option<export::PlaylistExport>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub show_history_dialog: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub show_goto_time_dialog: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub show_perf_overlay: bool,
+
+    // Tracks the properties dialog is open for
+    #[serde(skip_serializing, skip_deserializing)]
+    pub properties: Option<properties::TrackProperties>,
+
+    // Search and replace over tags, while its window is open
+    #[serde(skip_serializing, skip_deserializing)]
+    pub tag_replace: Option<tag_replace::TagReplace>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub tag_cleanup: Option<tag_cleanup::TagCleanup>,
+
+    // The folder waiting for the removal to be confirmed
+    #[serde(skip_serializing, skip_deserializing)]
+    pub confirm_remove_path: Option<LibraryPathId>,
+
+    // Folders removed this session, last one last, for undo
+    #[serde(skip_serializing, skip_deserializing)]
+    pub removed_folders: Vec<remove_folder::RemovedFolder>,
+
+    // Text typed into the go to time dialog
+    #[serde(skip_serializing, skip_deserializing)]
+    pub goto_time_input: String,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub history_range: history::HistoryRange,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub history_entries: Vec<history::HistoryEntry>,
+
+    // Listening statistics of each library folder, from the history
+    #[serde(skip_serializing, skip_deserializing)]
+    pub album_stats: HashMap<album_stats::AlbumKey, album_stats::AlbumStats>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub show_least_listened: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub show_report: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub report_period: report::ReportPeriod,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub report: Option<report::ListeningReport>,
+
+    // Result of the last copy or save of the report
+    #[serde(skip_serializing, skip_deserializing)]
+    pub report_status: Option<String>,
+
+    // Where to save the report once the screenshot of its window comes in
+    #[serde(skip_serializing, skip_deserializing)]
+    pub report_image_target: Option<PathBuf>,
+
+    // Playback events for any subsystem that wants to follow them
+    #[serde(skip_serializing, skip_deserializing)]
+    pub events: events::EventBus,
+
+    // When the player state was last saved during playback
+    #[serde(skip, default = "std::time::Instant::now")]
+    pub last_persisted: std::time::Instant,
+
+    // When the autosave last looked for changes
+    #[serde(skip, default = "std::time::Instant::now")]
+    pub last_autosave: std::time::Instant,
+
+    // Fingerprint of the state as of the last autosave
+    #[serde(skip_serializing, skip_deserializing)]
+    pub saved_fingerprint: Option<u64>,
+
+    // Outcome of the last backup or restore, shown in the settings
+    #[serde(skip_serializing, skip_deserializing)]
+    pub backup_status: Option<String>,
+
+    // Outcome of the last import from another player, shown in the settings
+    #[serde(skip_serializing, skip_deserializing)]
+    pub player_import_status: Option<String>,
+
+    // Set when a track was loaded, the play is logged once audio actually plays
+    #[serde(skip_serializing, skip_deserializing)]
+    pub history_pending: bool,
+
+    // The logged play audio is running for, to note how long it was listened to
+    #[serde(skip_serializing, skip_deserializing)]
+    pub history_listening: Option<history::Listening>,
+
+    // The history's subscription to the playback events
+    #[serde(skip_serializing, skip_deserializing)]
+    pub history_events: Option<Receiver<events::PlayerEvent>>,
+
+    // Dates the history export is limited to, as typed in the history window
+    #[serde(skip_serializing, skip_deserializing)]
+    pub history_export_from: String,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub history_export_to: String,
+
+    // Outcome of the last history export
+    #[serde(skip_serializing, skip_deserializing)]
+    pub history_export_status: Option<String>,
+
+    // Tracks of a folder played as an album, takes over from the playing playlist
+    #[serde(skip_serializing, skip_deserializing)]
+    pub queue: Option<Playlist>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub last_volume_check: Option<std::time::Instant>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub volume_check_running: Arc<AtomicBool>,
+
+    // Resumed once the volume of the track is reachable again
+    #[serde(skip_serializing, skip_deserializing)]
+    pub interrupted_playback: Option<volumes::InterruptedPlayback>,
 }
 
 impl Default for App {
@@ -170,18 +680,22 @@ impl Default for App {
             current_playlist_idx: None, // No playlist selected initially
             playing_playlist_idx: None,
             current_language: i18n::Language::English, // Default language
+            language_chosen: false,
             // Initialize the new fields
             last_track_path: None,
             last_position: None,
             last_playback_mode: None,
             last_volume: None,
+            muted: false,
             was_playing: None,
             player: None,
             playlist_idx_to_remove: None,
             playlist_being_renamed: None,
             library_cmd_tx: None,
             library_cmd_rx: None,
+            library_loading: false,
             database: None,
+            database_error: None,
             quit: false,
             is_maximized: false,
             lib_config_selections: Default::default(),
@@ -189,8 +703,104 @@ impl Default for App {
             is_processing_ui_change: None,
             show_library_and_playlist: true,
             library_folders_expanded: false,
+            show_chapter_panel: true,
             show_about_dialog: false,
-            default_window_height: DEFAULT_WINDOW_HEIGHT as f64,
+            show_settings_dialog: false,
+            replay_gain_mode: ReplayGainMode::Off,
+            replay_gain_write_tags: false,
+            volume_leveling: false,
+            channel_mix: ChannelMix::Source,
+            balance: 0.0,
+            left_trim_db: 0.0,
+            right_trim_db: 0.0,
+            soft_fades: true,
+            transitions: crate::transition::TransitionPrefs::default(),
+            downrank_skipped: false,
+            crossfeed: false,
+            crossfeed_level: CrossfeedLevel::default(),
+            plugin: false,
+            plugin_config: PluginConfig::default(),
+            limiter: false,
+            limiter_ceiling_db: crate::limiter::DEFAULT_CEILING_DB,
+            effect_order: dsp::default_order(),
+            scrub_while_dragging: false,
+            show_remaining_time: false,
+            resume_min_minutes: DEFAULT_RESUME_MIN_MINUTES,
+            replay_gain_scanning: Arc::new(AtomicBool::new(false)),
+            track_markers: Default::default(),
+            history_retention_days: 0,
+            read_only_library: false,
+            recently_added_days: recently_added::DEFAULT_RECENTLY_ADDED_DAYS,
+            min_track_seconds: 0,
+            follow_symlinks: false,
+            scan_video_files: false,
+            library_sort: LibrarySort::Title,
+            export_pattern: export::DEFAULT_EXPORT_PATTERN.to_string(),
+            show_format_columns: false,
+            clean_filter: false,
+            follow_playback: true,
+            label_playlists: Vec::new(),
+            labels: Default::default(),
+            track_notes: Default::default(),
+            favorites: Default::default(),
+            locate_requested: false,
+            shown_playlist_idx: None,
+            mini_player: false,
+            mini_recents_open: false,
+            recent_plays: Vec::new(),
+            now_playing_view: false,
+            lyrics: None,
+            full_geometry: None,
+            mini_geometry: None,
+            mini_always_on_top: true,
+            library_panel_width: None,
+            skin: skin::Skin::default(),
+            battery_saver: false,
+            ui_fonts: Vec::new(),
+            system_fonts: None,
+            album_art_cleaned_at: None,
+            onboarded: false,
+            onboarding: None,
+            album_art_status: None,
+            metadata_writer: None,
+            pending_edits: std::collections::HashSet::new(),
+            thumbnails: thumbnails::Thumbnails::default(),
+            export_playlist_idx: None,
+            playlist_export: None,
+            show_history_dialog: false,
+            show_goto_time_dialog: false,
+            show_perf_overlay: false,
+            properties: None,
+            tag_replace: None,
+            tag_cleanup: None,
+            confirm_remove_path: None,
+            removed_folders: Vec::new(),
+            goto_time_input: String::new(),
+            history_range: Default::default(),
+            history_entries: Vec::new(),
+            album_stats: HashMap::new(),
+            show_least_listened: false,
+            show_report: false,
+            report_period: Default::default(),
+            report: None,
+            report_status: None,
+            report_image_target: None,
+            events: Default::default(),
+            last_persisted: std::time::Instant::now(),
+            last_autosave: std::time::Instant::now(),
+            saved_fingerprint: None,
+            backup_status: None,
+            player_import_status: None,
+            history_pending: false,
+            history_listening: None,
+            history_events: None,
+            history_export_from: String::new(),
+            history_export_to: String::new(),
+            history_export_status: None,
+            queue: None,
+            last_volume_check: None,
+            volume_check_running: Arc::new(AtomicBool::new(false)),
+            interrupted_playback: None,
         }
     }
 }
@@ -208,14 +818,67 @@ impl App {
 
         if let Ok(settings) = config_result {
             // Apply settings from confy
-            app.current_language = settings.current_language;
+            if let Some(lang) = settings.current_language {
+                app.current_language = lang;
+                app.language_chosen = true;
+            }
             app.last_track_path = settings.last_track_path;
             app.last_position = settings.last_position;
             app.last_playback_mode = settings.last_playback_mode;
             app.last_volume = settings.last_volume;
+            app.muted = settings.muted;
             app.was_playing = settings.was_playing;
             app.library_folders_expanded = settings.library_folders_expanded;
-            app.default_window_height = settings.default_window_height;
+            app.show_chapter_panel = settings.show_chapter_panel;
+            app.replay_gain_mode = settings.replay_gain_mode;
+            app.replay_gain_write_tags = settings.replay_gain_write_tags;
+            app.volume_leveling = settings.volume_leveling;
+            app.channel_mix = settings.channel_mix;
+            app.balance = settings.balance;
+            app.left_trim_db = settings.left_trim_db;
+            app.right_trim_db = settings.right_trim_db;
+            app.soft_fades = settings.soft_fades;
+            app.transitions = settings.transitions;
+            app.downrank_skipped = settings.downrank_skipped;
+            app.crossfeed = settings.crossfeed;
+            app.crossfeed_level = settings.crossfeed_level;
+            app.plugin = settings.plugin;
+            app.plugin_config = settings.plugin_config;
+            app.limiter = settings.limiter;
+            app.limiter_ceiling_db = settings.limiter_ceiling_db;
+            app.effect_order = dsp::complete_order(&settings.effect_order);
+            app.scrub_while_dragging = settings.scrub_while_dragging;
+            app.show_remaining_time = settings.show_remaining_time;
+            app.resume_min_minutes = settings.resume_min_minutes;
+            app.history_retention_days = settings.history_retention_days;
+            app.read_only_library = settings.read_only_library;
+            app.recently_added_days = settings.recently_added_days;
+            app.min_track_seconds = settings.min_track_seconds;
+            app.follow_symlinks = settings.follow_symlinks;
+            app.scan_video_files = settings.scan_video_files;
+            app.library_sort = settings.library_sort;
+            app.export_pattern = settings.export_pattern;
+            app.show_format_columns = settings.show_format_columns;
+            app.clean_filter = settings.clean_filter;
+            app.follow_playback = settings.follow_playback;
+            app.label_playlists = settings.label_playlists;
+            app.full_geometry = settings.full_geometry;
+            app.mini_geometry = settings.mini_geometry;
+            app.mini_always_on_top = settings.mini_always_on_top;
+            app.is_maximized = settings.window_maximized;
+            app.library_panel_width = settings.library_panel_width;
+            app.skin = settings.skin;
+            app.battery_saver = settings.battery_saver;
+            app.ui_fonts = settings.ui_fonts;
+            app.album_art_cleaned_at = settings.album_art_cleaned_at;
+            app.onboarded = settings.onboarded;
+        }
+
+        // Until a language is picked, follow the system's
+        if !app.language_chosen {
+            if let Some(lang) = i18n::detect_system_language() {
+                app.current_language = lang;
+            }
         }
 
         // Set the language from the loaded config
@@ -230,47 +893,42 @@ impl App {
                 }
                 Err(e) => {
                     tracing::error!("Failed to create database during App::load(): {}", e);
+                    app.database_error = Some(e.to_string());
                 }
             }
         }
 
-        // Try to load library and playlists if we have a database
+        // Try to load playlists if we have a database. The library and the tracks of
+        // the playlists not on screen follow in `load_in_background`.
         if let Some(ref db) = app.database {
-            // Try to load library from database
-            match Library::load_from_db(&db.connection()) {
-                Ok(library) => {
-                    app.library = library;
-                    tracing::info!("Successfully loaded library from database");
-                }
-                Err(e) => {
-                    tracing::error!("Failed to load library from database: {}", e);
-                    // Keep the default empty library
-                }
-            }
+            app.library_loading = true;
 
             // Try to load playlists from database
-            match playlist::Playlist::load_all_from_db(&db.connection()) {
+            match playlist::Playlist::load_names_from_db(&db.connection()) {
                 Ok(playlists) => {
                     if !playlists.is_empty() {
                         app.playlists = playlists;
 
                         // If there was a last played track, try to find its playlist
-                        if let Some(last_track_path) = &app.last_track_path {
-                            for (idx, playlist) in app.playlists.iter().enumerate() {
-                                if playlist
-                                    .tracks
-                                    .iter()
-                                    .any(|track| track.path() == *last_track_path)
-                                {
-                                    app.current_playlist_idx = Some(idx);
-                                    app.playing_playlist_idx = Some(idx);
-                                    tracing::info!(
-                                        "Found last played track in playlist '{}', selecting it",
-                                        playlist.get_name().unwrap_or_default()
-                                    );
-                                    break;
-                                }
-                            }
+                        let last_playlist_id = app.last_track_path.as_ref().and_then(|path| {
+                            playlist::Playlist::find_in_db(&db.connection(), path).unwrap_or_else(
+                                |e| {
+                                    tracing::error!("Failed to look up the last track: {}", e);
+                                    None
+                                },
+                            )
+                        });
+                        if let Some(idx) = last_playlist_id.and_then(|id| {
+                            app.playlists
+                                .iter()
+                                .position(|playlist| playlist.id == Some(id))
+                        }) {
+                            app.current_playlist_idx = Some(idx);
+                            app.playing_playlist_idx = Some(idx);
+                            tracing::info!(
+                                "Found last played track in playlist '{}', selecting it",
+                                app.playlists[idx].get_name().unwrap_or_default()
+                            );
                         }
 
                         // If no playlist was selected (no last track or track not found), select first playlist
@@ -278,6 +936,15 @@ impl App {
                             app.current_playlist_idx = Some(0);
                             tracing::info!("No last played track found, selecting first playlist");
                         }
+
+                        // The playlist on screen is read right away
+                        let idx = app.current_playlist_idx.unwrap_or(0);
+                        if let Some(id) = app.playlists[idx].id {
+                            match playlist::Playlist::load_from_db(&db.connection(), id) {
+                                Ok(loaded) => app.playlists[idx].finish_loading(loaded),
+                                Err(e) => tracing::error!("Failed to load playlist {}: {}", id, e),
+                            }
+                        }
                     } else {
                         // Only create a default playlist if no playlists exist in the database
                         let mut default_playlist = playlist::Playlist::new();
@@ -292,13 +959,15 @@ impl App {
                     // Keep the default playlist
                 }
             }
+
+            app.prune_history();
         } else {
             tracing::warn!("No database connection available when loading app state");
         }
 
-        app.is_maximized = false;
         app.is_library_cfg_open = false;
         app.show_about_dialog = false;
+        app.show_settings_dialog = false;
         app.is_processing_ui_change = None;
         app.show_library_and_playlist = true;
 
@@ -314,17 +983,80 @@ impl App {
             .unwrap_or_else(|_| PathBuf::from("album_art"))
     }
 
+    pub fn get_log_dir() -> PathBuf {
+        confy::get_configuration_file_path("bird-player", None)
+            .map(|p| {
+                p.parent()
+                    .map_or_else(|| PathBuf::from("logs"), |path| path.join("logs"))
+            })
+            .unwrap_or_else(|_| PathBuf::from("logs"))
+    }
+
+    pub fn get_thumbnail_dir() -> PathBuf {
+        confy::get_configuration_file_path("bird-player", None)
+            .map(|p| {
+                p.parent().map_or_else(
+                    || PathBuf::from("thumbnails"),
+                    |path| path.join("thumbnails"),
+                )
+            })
+            .unwrap_or_else(|_| PathBuf::from("thumbnails"))
+    }
+
     pub fn save_state(&self) {
         // Split app state - settings go to confy, library and playlists go to SQLite
         let settings = AppSettings {
-            current_language: self.current_language,
+            current_language: self.language_chosen.then_some(self.current_language),
             last_track_path: self.last_track_path.clone(),
             last_position: self.last_position,
             last_playback_mode: self.last_playback_mode,
             last_volume: self.last_volume,
+            muted: self.muted,
             was_playing: self.was_playing,
             library_folders_expanded: self.library_folders_expanded,
-            default_window_height: self.default_window_height,
+            show_chapter_panel: self.show_chapter_panel,
+            replay_gain_mode: self.replay_gain_mode,
+            replay_gain_write_tags: self.replay_gain_write_tags,
+            volume_leveling: self.volume_leveling,
+            channel_mix: self.channel_mix,
+            balance: self.balance,
+            left_trim_db: self.left_trim_db,
+            right_trim_db: self.right_trim_db,
+            soft_fades: self.soft_fades,
+            transitions: self.transitions,
+            downrank_skipped: self.downrank_skipped,
+            crossfeed: self.crossfeed,
+            crossfeed_level: self.crossfeed_level,
+            plugin: self.plugin,
+            plugin_config: self.plugin_config.clone(),
+            limiter: self.limiter,
+            limiter_ceiling_db: self.limiter_ceiling_db,
+            effect_order: self.effect_order.clone(),
+            scrub_while_dragging: self.scrub_while_dragging,
+            show_remaining_time: self.show_remaining_time,
+            resume_min_minutes: self.resume_min_minutes,
+            history_retention_days: self.history_retention_days,
+            read_only_library: self.read_only_library,
+            recently_added_days: self.recently_added_days,
+            min_track_seconds: self.min_track_seconds,
+            follow_symlinks: self.follow_symlinks,
+            scan_video_files: self.scan_video_files,
+            library_sort: self.library_sort,
+            export_pattern: self.export_pattern.clone(),
+            show_format_columns: self.show_format_columns,
+            clean_filter: self.clean_filter,
+            follow_playback: self.follow_playback,
+            label_playlists: self.label_playlists.clone(),
+            full_geometry: self.full_geometry,
+            mini_geometry: self.mini_geometry,
+            mini_always_on_top: self.mini_always_on_top,
+            window_maximized: self.is_maximized,
+            library_panel_width: self.library_panel_width,
+            skin: self.skin.clone(),
+            battery_saver: self.battery_saver,
+            ui_fonts: self.ui_fonts.clone(),
+            album_art_cleaned_at: self.album_art_cleaned_at,
+            onboarded: self.onboarded,
         };
 
         // Save app settings to confy
@@ -336,17 +1068,38 @@ impl App {
 
         // Save library and playlists to SQLite if database is available
         if let Some(ref db) = &self.database {
-            // Save library
-            if let Err(e) = self.library.save_to_db(&db.connection()) {
-                tracing::error!("Failed to save library to database: {}", e);
-            }
+            // Writing a large library takes a while, it's done off the UI thread on a copy
+            let library = self.library.clone();
+            let playlists: Vec<Playlist> = self
+                .playlists
+                .iter()
+                .filter(|playlist| !playlist.is_virtual())
+                .cloned()
+                .collect();
+            let resume_positions = self
+                .player
+                .as_ref()
+                .map(|player| player.resume_positions.clone());
+            db.write_in_background(move |conn| {
+                // Save library
+                if let Err(e) = library.save_to_db(conn) {
+                    tracing::error!("Failed to save library to database: {}", e);
+                }
 
-            // Save playlists
-            for playlist in &self.playlists {
-                if let Err(e) = playlist.save_to_db(&db.connection()) {
-                    tracing::error!("Failed to save playlist to database: {}", e);
+                // Save playlists
+                for playlist in &playlists {
+                    if let Err(e) = playlist.save_to_db(conn) {
+                        tracing::error!("Failed to save playlist to database: {}", e);
+                    }
                 }
-            }
+
+                // Save resume positions of long tracks, they refer to the saved library
+                if let Some(resume_positions) = resume_positions {
+                    if let Err(e) = resume_positions.save_to_db(conn) {
+                        tracing::error!("Failed to save resume positions to database: {}", e);
+                    }
+                }
+            });
         }
     }
 
@@ -359,17 +1112,42 @@ impl App {
             // Save the current playing position
             self.last_position = Some(player.seek_to_timestamp);
 
-            // Save the current playback mode
-            self.last_playback_mode = Some(player.playback_mode);
+            // Save the current playback mode, the one for music while a book plays
+            self.last_playback_mode =
+                Some(player.mode_before_audiobook.unwrap_or(player.playback_mode));
 
             // Save the current volume
             self.last_volume = Some(player.volume);
+            self.muted = player.muted;
 
             // Save whether the player was playing or paused
             self.was_playing = Some(matches!(player.track_state, player::TrackState::Playing));
         }
     }
 
+    /// Per-channel gains of the balance and trim settings.
+    pub fn balance(&self) -> Balance {
+        Balance::new(self.balance, self.left_trim_db, self.right_trim_db)
+    }
+
+    /// The effects the settings switch on, in the chosen order.
+    pub fn dsp_chain(&self) -> Vec<EffectConfig> {
+        self.effect_order
+            .iter()
+            .filter_map(|kind| match kind {
+                EffectKind::Balance => Some(EffectConfig::Balance(self.balance())),
+                EffectKind::Crossfeed => self
+                    .crossfeed
+                    .then_some(EffectConfig::Crossfeed(self.crossfeed_level)),
+                EffectKind::Plugin => (self.plugin && !self.plugin_config.label.is_empty())
+                    .then(|| EffectConfig::Plugin(self.plugin_config.clone())),
+                EffectKind::Limiter => self.limiter.then_some(EffectConfig::Limiter {
+                    ceiling_db: self.limiter_ceiling_db,
+                }),
+            })
+            .collect()
+    }
+
     pub fn quit(&mut self) {
         self.quit = true;
     }
@@ -387,6 +1165,10 @@ impl App {
         let lib_cmd_tx = self.library_cmd_tx.as_ref().unwrap().clone();
         let path = lib_path.path().clone();
         let path_id = lib_path.id();
+        let excludes = lib_path.exclude_globs();
+        let min_seconds = self.min_track_seconds as f64;
+        let follow_symlinks = self.follow_symlinks;
+        let scan_video_files = self.scan_video_files;
         // Store path display string for later use
         let path_display = path.display().to_string();
 
@@ -399,23 +1181,47 @@ impl App {
         }
 
         std::thread::spawn(move || {
-            let files = walkdir::WalkDir::new(path)
+            let added_at = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs() as i64)
+                .ok();
+
+            let files = scan::files(&path, follow_symlinks)
                 .into_iter()
-                .filter_map(|e| e.ok())
-                .skip(1)
+                .filter(|entry| scan::is_importable(entry.path(), scan_video_files))
                 .filter(|entry| {
-                    entry.file_type().is_file()
-                        && entry.path().extension().unwrap_or(std::ffi::OsStr::new("")) == "mp3"
+                    let relative = entry.path().strip_prefix(&path).unwrap_or(entry.path());
+                    !library::is_excluded(&excludes, relative)
                 })
                 .collect::<Vec<_>>();
 
             let items = files
                 .par_iter()
-                .map(|entry| {
-                    let tag = Tag::read_from_path(entry.path());
+                .filter_map(|entry| {
+                    // A video without audio we can decode has nothing to play
+                    if scan::is_video(entry.path())
+                        && crate::media_source::audio_tracks(entry.path())
+                            .map_or(true, |tracks| tracks.is_empty())
+                    {
+                        tracing::debug!(
+                            "Skipping video without playable audio: {:?}",
+                            entry.path()
+                        );
+                        return None;
+                    }
+
+                    let probed = audio_format::AudioFormat::probe_with_duration(entry.path());
+                    if let Some((_, Some(seconds))) = &probed {
+                        if *seconds < min_seconds {
+                            tracing::debug!("Skipping short file: {:?}", entry.path());
+                            return None;
+                        }
+                    }
+
+                    let tags = tags::read_tags(entry.path());
 
-                    let library_item = match tag {
-                        Ok(tag) => {
+                    let mut library_item = match tags {
+                        Some(tags) => {
                             let mut item = LibraryItem::new(entry.path().to_path_buf(), path_id);
 
                             // Get filename without extension as fallback title
@@ -426,72 +1232,49 @@ impl App {
                                 .unwrap_or("Unknown Title")
                                 .to_string();
 
-                            // Use filename as title if ID3 tag is missing or contains invalid UTF-8
-                            let title = tag
-                                .title()
-                                .and_then(|t| {
-                                    if t.chars().any(|c| !c.is_ascii() && !c.is_alphabetic()) {
-                                        None
-                                    } else {
-                                        Some(t)
-                                    }
-                                })
-                                .unwrap_or(&filename_title);
+                            // Use filename as title if the tag has none
+                            let title = tags.title.as_deref().unwrap_or(&filename_title);
 
                             item = item
                                 .set_title(Some(title))
-                                .set_artist(tag.artist())
-                                .set_album(tag.album())
-                                .set_year(tag.year())
-                                .set_genre(tag.genre())
-                                .set_track_number(tag.get("TRCK").and_then(|frame| {
-                                    frame.content().text().map(|t| {
-                                        t.split('/')
-                                            .next()
-                                            .unwrap_or("0")
-                                            .parse::<u32>()
-                                            .unwrap_or(0)
-                                    })
-                                }))
-                                .set_lyrics(tag.lyrics().next().map(|l| l.text.as_str()));
-
-                            // Extract pictures from ID3 tag
-                            for pic in tag.pictures() {
-                                // Create a unique filename for the picture
-                                let file_name = album_art_dir.join(format!(
-                                    "{}_{}_{}.{}",
-                                    entry
-                                        .path()
-                                        .file_stem()
-                                        .unwrap_or_default()
-                                        .to_string_lossy(),
-                                    u8::from(pic.picture_type),
-                                    rand::thread_rng().gen::<u64>(), // Add random number to ensure uniqueness
-                                    match pic.mime_type.as_str() {
-                                        "image/jpeg" => "jpg",
-                                        "image/png" => "png",
-                                        _ => "jpg", // Default to jpg for unknown types
-                                    }
-                                ));
-
-                                // Save the picture data to a file
-                                if let Ok(mut file) = fs::File::create(&file_name) {
-                                    if file.write_all(&pic.data).is_ok() {
-                                        item.add_picture(Picture::new(
-                                            pic.mime_type.to_string(),
-                                            u8::from(pic.picture_type),
-                                            pic.description.to_string(),
-                                            file_name,
-                                        ));
-                                    }
+                                .set_artist(tags.artist.as_deref())
+                                .set_artists(tags.artists.clone())
+                                .set_album_artist(tags.album_artist.as_deref())
+                                .set_album(tags.album.as_deref())
+                                .set_year(tags.year)
+                                .set_genre(tags.genre.as_deref())
+                                .set_track_number(tags.track_number)
+                                .set_lyrics(tags.lyrics.as_deref())
+                                .set_replay_gain(tags.replay_gain)
+                                .set_explicit(tags.explicit);
+
+                            // Extract embedded pictures
+                            for pic in &tags.pictures {
+                                // Covers shared by an album's tracks are stored once
+                                match album_art::store_picture(
+                                    &album_art_dir,
+                                    &pic.data,
+                                    &pic.mime_type,
+                                ) {
+                                    Ok(file_name) => item.add_picture(Picture::new(
+                                        pic.mime_type.to_string(),
+                                        pic.picture_type,
+                                        pic.description.to_string(),
+                                        file_name,
+                                    )),
+                                    Err(e) => tracing::warn!(
+                                        "Failed to save picture of {:?}: {}",
+                                        entry.path(),
+                                        e
+                                    ),
                                 }
                             }
 
                             item
                         }
-                        Err(_err) => {
-                            tracing::warn!("Couldn't parse to id3: {:?}", &entry.path());
-                            // Get filename without extension as title for failed ID3 reads
+                        None => {
+                            tracing::warn!("No readable tags in: {:?}", &entry.path());
+                            // Get filename without extension as title for files without tags
                             let filename_title = entry
                                 .path()
                                 .file_stem()
@@ -504,7 +1287,19 @@ impl App {
                         }
                     };
 
-                    library_item
+                    let modified_at = entry
+                        .metadata()
+                        .ok()
+                        .and_then(|metadata| metadata.modified().ok())
+                        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                        .map(|elapsed| elapsed.as_secs() as i64);
+
+                    library_item.set_added_at(added_at);
+                    library_item.set_modified_at(modified_at);
+                    if let Some((format, _)) = probed {
+                        library_item.set_format(format);
+                    }
+                    Some(library_item)
                 })
                 .collect::<Vec<LibraryItem>>();
 
@@ -513,7 +1308,7 @@ impl App {
             // Populate the library with parsed items
             for item in &items {
                 lib_cmd_tx
-                    .send(LibraryCommand::AddItem((*item).clone()))
+                    .send(LibraryCommand::AddItem(Box::new(item.clone())))
                     .expect("failed to send library item")
             }
 
@@ -544,148 +1339,12 @@ impl App {
         });
     }
 
-    pub fn update_track_metadata(
-        &mut self,
-        track: &mut LibraryItem,
-        field: &str,
-        value: &str,
-    ) -> bool {
-        // Get the file path from the LibraryItem
-        let path = track.path();
-
-        // Try to read the existing tag
-        let mut tag = match id3::Tag::read_from_path(&path) {
-            Ok(tag) => tag,
-            Err(err) => {
-                // If there's no tag, create a new one
-                if let id3::ErrorKind::NoTag = err.kind {
-                    tracing::info!("Creating new ID3 tag for file: {:?}", path);
-                    id3::Tag::new()
-                } else {
-                    tracing::error!("Failed to read ID3 tag for file {:?}: {}", path, err);
-                    return false;
-                }
-            }
-        };
-
-        // Update the corresponding field in the tag and track
-        match field {
-            "title" => {
-                tag.set_title(value);
-                track.set_title(Some(value));
-            }
-            "artist" => {
-                tag.set_artist(value);
-                track.set_artist(Some(value));
-            }
-            "album" => {
-                tag.set_album(value);
-                track.set_album(Some(value));
-            }
-            "genre" => {
-                tag.set_genre(value);
-                track.set_genre(Some(value));
-            }
-            _ => return false, // Unsupported field
-        }
-
-        // Write the updated tag back to the file
-        let file_update_success = match tag.write_to_path(&path, id3::Version::Id3v24) {
-            Ok(_) => {
-                tracing::info!(
-                    "Successfully updated {} to '{}' for file: {:?}",
-                    field,
-                    value,
-                    path
-                );
-                true
-            }
-            Err(e) => {
-                tracing::error!("Failed to write {} tag for file {:?}: {}", field, path, e);
-                false
-            }
-        };
-
-        // Update the database if file update was successful
-        if file_update_success {
-            if let Some(ref db) = self.database {
-                let conn = db.connection();
-                let result = {
-                    let mut conn_guard = conn.lock().unwrap();
-                    let tx = conn_guard.transaction().ok();
-
-                    if let Some(tx) = tx {
-                        let update_result = tx.execute(
-                            &format!("UPDATE library_items SET {} = ?1 WHERE key = ?2", field),
-                            rusqlite::params![value, track.key().to_string()],
-                        );
-
-                        match update_result.and_then(|_| tx.commit()) {
-                            Ok(_) => {
-                                tracing::info!(
-                                    "Successfully updated {} in database for track {}",
-                                    field,
-                                    track.key()
-                                );
-                                true
-                            }
-                            Err(e) => {
-                                tracing::error!(
-                                    "Failed to update {} in database for track {}: {}",
-                                    field,
-                                    track.key(),
-                                    e
-                                );
-                                false
-                            }
-                        }
-                    } else {
-                        tracing::error!("Failed to start database transaction for metadata update");
-                        false
-                    }
-                };
-
-                // If database update was successful, update all instances of this track
-                if result {
-                    // Update all instances of this track in all playlists
-                    for playlist in &mut self.playlists {
-                        for playlist_track in playlist.tracks.iter_mut() {
-                            if playlist_track.key() == track.key() {
-                                let updated_track = match field {
-                                    "title" => playlist_track.set_title(Some(value)),
-                                    "artist" => playlist_track.set_artist(Some(value)),
-                                    "album" => playlist_track.set_album(Some(value)),
-                                    "genre" => playlist_track.set_genre(Some(value)),
-                                    _ => playlist_track.clone(),
-                                };
-                                *playlist_track = updated_track;
-                            }
-                        }
-                    }
-
-                    // Reload the library from the database to get updated metadata
-                    if let Ok(updated_library) = library::Library::load_from_db(&db.connection()) {
-                        self.library = updated_library;
-                    }
-
-                    // Save the updated state to ensure persistence
-                    self.save_state();
-                }
-
-                result
-            } else {
-                tracing::warn!("No database connection available for metadata update");
-                file_update_success
-            }
-        } else {
-            false
-        }
-    }
-
     // Add these new methods for language handling
     pub fn set_language(&mut self, lang: i18n::Language) {
         self.current_language = lang;
+        self.language_chosen = true;
         i18n::set_language(lang);
+        self.refresh_recently_added();
         // Save state to persist language preference
         self.save_state();
     }
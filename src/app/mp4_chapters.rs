@@ -0,0 +1,337 @@
+//! Chapters of MP4 audiobooks (.m4b): the Nero chapter list in the movie's user data, or
+//! else the text track the audio track names as its chapter track, as iTunes writes them.
+//! Only the movie header is read whole, the chapter titles are read one at a time.
+//! The box helpers are shared with the MP4 tag reader.
+
+use super::bookmarks::Chapter;
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+// A moov larger than this is no audiobook's, the sample tables of hours of AAC take a few MB
+const MAX_MOOV_BYTES: u64 = 64 * 1024 * 1024;
+// Beyond any book, protects against a corrupt table
+const MAX_CHAPTERS: usize = 10_000;
+
+pub fn read(path: &Path) -> io::Result<Vec<Chapter>> {
+    let mut file = File::open(path)?;
+    let Some(moov) = read_moov(&mut file)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut chapters = match nero_chapters(&moov) {
+        Some(chapters) if !chapters.is_empty() => chapters,
+        _ => track_chapters(&moov, &mut file)?,
+    };
+    chapters.sort_by_key(|chapter| chapter.start_ms);
+    Ok(chapters)
+}
+
+/// Walks the top level boxes up to the movie header and reads its payload.
+pub fn read_moov(file: &mut (impl Read + Seek)) -> io::Result<Option<Vec<u8>>> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    let mut pos = 0;
+    while pos + 8 <= file_len {
+        file.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8])?;
+        let size = u32::from_be_bytes(header[..4].try_into().unwrap()) as u64;
+        let (header_len, len) = match size {
+            0 => (8, file_len - pos),
+            1 => {
+                file.read_exact(&mut header[8..])?;
+                (16, u64::from_be_bytes(header[8..].try_into().unwrap()))
+            }
+            _ => (8, size),
+        };
+        if len < header_len {
+            return Ok(None);
+        }
+
+        if &header[4..8] == b"moov" {
+            if len - header_len > MAX_MOOV_BYTES {
+                return Ok(None);
+            }
+            let mut moov = vec![0; (len - header_len) as usize];
+            file.read_exact(&mut moov)?;
+            return Ok(Some(moov));
+        }
+        pos += len;
+    }
+    Ok(None)
+}
+
+/// The child boxes of a container's payload, with their payloads.
+pub fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let header = data.get(pos..pos + 8)?;
+        let size = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let (header_len, len) = match size {
+            0 => (8, data.len() - pos),
+            1 => (
+                16,
+                u64::from_be_bytes(data.get(pos + 8..pos + 16)?.try_into().unwrap()) as usize,
+            ),
+            _ => (8, size),
+        };
+        let payload = data.get(pos + header_len..pos.checked_add(len)?)?;
+        let kind = &header[4..8];
+        pos += len;
+        Some((kind, payload))
+    })
+}
+
+/// The payload of the box at `path` below a container's payload.
+pub fn child<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    path.iter().try_fold(data, |data, kind| {
+        boxes(data)
+            .find(|(found, _)| found == kind)
+            .map(|(_, payload)| payload)
+    })
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn u64_at(data: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(pos..pos + 8)?.try_into().ok()?))
+}
+
+// moov/udta/chpl: start times in 100 ns units, each with a title
+fn nero_chapters(moov: &[u8]) -> Option<Vec<Chapter>> {
+    let chpl = child(moov, &[b"udta", b"chpl"])?;
+    // Version 1 has four more bytes before the count
+    let mut pos = if *chpl.first()? == 1 { 8 } else { 4 };
+    let count = *chpl.get(pos)? as usize;
+    pos += 1;
+
+    let mut chapters = Vec::with_capacity(count);
+    for _ in 0..count {
+        let start = u64_at(chpl, pos)?;
+        let len = *chpl.get(pos + 8)? as usize;
+        let title = chpl.get(pos + 9..pos + 9 + len)?;
+        pos += 9 + len;
+        chapters.push(Chapter {
+            title: String::from_utf8_lossy(title).into_owned(),
+            start_ms: start / 10_000,
+        });
+    }
+    Some(chapters)
+}
+
+fn track_id(trak: &[u8]) -> Option<u32> {
+    let tkhd = child(trak, &[b"tkhd"])?;
+    let offset = if *tkhd.first()? == 1 { 20 } else { 12 };
+    u32_at(tkhd, offset)
+}
+
+// The text track a track refers to with tref/chap: each sample is a title, as long as
+// its duration
+fn track_chapters(moov: &[u8], file: &mut (impl Read + Seek)) -> io::Result<Vec<Chapter>> {
+    let traks: Vec<&[u8]> = boxes(moov)
+        .filter(|(kind, _)| kind == b"trak")
+        .map(|(_, payload)| payload)
+        .collect();
+    let chapter_id = traks
+        .iter()
+        .find_map(|trak| child(trak, &[b"tref", b"chap"]).and_then(|chap| u32_at(chap, 0)));
+    let Some(trak) = chapter_id.and_then(|id| traks.iter().find(|trak| track_id(trak) == Some(id)))
+    else {
+        return Ok(Vec::new());
+    };
+    let Some(samples) = sample_table(trak) else {
+        return Ok(Vec::new());
+    };
+
+    let mut chapters = Vec::with_capacity(samples.len());
+    for (start_ms, offset, size) in samples {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut sample = vec![0; size.min(64 * 1024) as usize];
+        file.read_exact(&mut sample)?;
+        chapters.push(Chapter {
+            title: sample_text(&sample),
+            start_ms,
+        });
+    }
+    Ok(chapters)
+}
+
+// Start in milliseconds, file offset and size of each sample of a track
+fn sample_table(trak: &[u8]) -> Option<Vec<(u64, u64, u32)>> {
+    let mdhd = child(trak, &[b"mdia", b"mdhd"])?;
+    let timescale = if *mdhd.first()? == 1 {
+        u32_at(mdhd, 20)?
+    } else {
+        u32_at(mdhd, 12)?
+    };
+    let stbl = child(trak, &[b"mdia", b"minf", b"stbl"])?;
+    if timescale == 0 {
+        return None;
+    }
+
+    let stsz = child(stbl, &[b"stsz"])?;
+    let fixed_size = u32_at(stsz, 4)?;
+    let count = (u32_at(stsz, 8)? as usize).min(MAX_CHAPTERS);
+    let sizes = (0..count)
+        .map(|idx| match fixed_size {
+            0 => u32_at(stsz, 12 + idx * 4),
+            size => Some(size),
+        })
+        .collect::<Option<Vec<u32>>>()?;
+
+    let offsets: Vec<u64> = match (child(stbl, &[b"stco"]), child(stbl, &[b"co64"])) {
+        (Some(stco), _) => (0..u32_at(stco, 4)? as usize)
+            .map(|idx| u32_at(stco, 8 + idx * 4).map(u64::from))
+            .collect::<Option<_>>()?,
+        (None, Some(co64)) => (0..u32_at(co64, 4)? as usize)
+            .map(|idx| u64_at(co64, 8 + idx * 8))
+            .collect::<Option<_>>()?,
+        (None, None) => return None,
+    };
+
+    // Runs of chunks with the same number of samples, by their first chunk
+    let stsc = child(stbl, &[b"stsc"])?;
+    let runs = (0..u32_at(stsc, 4)? as usize)
+        .map(|idx| Some((u32_at(stsc, 8 + idx * 12)?, u32_at(stsc, 12 + idx * 12)?)))
+        .collect::<Option<Vec<_>>>()?;
+    let mut positions = Vec::with_capacity(count);
+    for (chunk, offset) in offsets.iter().enumerate() {
+        let per_chunk = runs
+            .iter()
+            .take_while(|(first, _)| *first as usize <= chunk + 1)
+            .last()
+            .map_or(0, |(_, samples)| *samples);
+        let mut offset = *offset;
+        for _ in 0..per_chunk {
+            let Some(size) = sizes.get(positions.len()) else {
+                break;
+            };
+            positions.push((offset, *size));
+            offset += *size as u64;
+        }
+    }
+
+    // Durations, as runs of samples with the same one
+    let stts = child(stbl, &[b"stts"])?;
+    let mut starts = Vec::with_capacity(count);
+    let mut time = 0u64;
+    for idx in 0..u32_at(stts, 4)? as usize {
+        let run = u32_at(stts, 8 + idx * 8)?;
+        let delta = u32_at(stts, 12 + idx * 8)? as u64;
+        for _ in 0..run {
+            if starts.len() == count {
+                break;
+            }
+            starts.push(time * 1000 / timescale as u64);
+            time += delta;
+        }
+    }
+
+    Some(
+        starts
+            .into_iter()
+            .zip(positions)
+            .map(|(start, (offset, size))| (start, offset, size))
+            .collect(),
+    )
+}
+
+// A text sample: its length, then the text in UTF-8 or with a BOM in UTF-16
+fn sample_text(sample: &[u8]) -> String {
+    let len = match sample.get(..2) {
+        Some(len) => u16::from_be_bytes([len[0], len[1]]) as usize,
+        None => return String::new(),
+    };
+    let text = &sample[2..(2 + len).min(sample.len())];
+    match text {
+        [0xFE, 0xFF, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(text).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend(kind);
+        data.extend(payload);
+        data
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect()
+    }
+
+    fn titles(chapters: &[Chapter]) -> Vec<(&str, u64)> {
+        chapters
+            .iter()
+            .map(|chapter| (chapter.title.as_str(), chapter.start_ms))
+            .collect()
+    }
+
+    #[test]
+    fn reads_nero_chapter_lists() {
+        let mut chpl = vec![1, 0, 0, 0, 0, 0, 0, 0, 2];
+        for (start, title) in [(0u64, "Intro"), (600_000_000, "One")] {
+            chpl.extend(start.to_be_bytes());
+            chpl.push(title.len() as u8);
+            chpl.extend(title.as_bytes());
+        }
+        let moov = mp4_box(b"udta", &mp4_box(b"chpl", &chpl));
+
+        let chapters = nero_chapters(&moov).unwrap();
+        assert_eq!(titles(&chapters), vec![("Intro", 0), ("One", 60_000)]);
+    }
+
+    #[test]
+    fn reads_chapter_tracks() {
+        let mdat_payload: Vec<u8> = [&b"\x00\x05Intro"[..], &b"\x00\x07Chapter"[..]].concat();
+        let tkhd = |id| mp4_box(b"tkhd", &words(&[0, 0, 0, id]));
+
+        let audio = [tkhd(1), mp4_box(b"tref", &mp4_box(b"chap", &words(&[2])))].concat();
+        let ftyp = mp4_box(b"ftyp", b"M4B ");
+        // The samples follow ftyp and the mdat header
+        let sample_offset = ftyp.len() as u32 + 8;
+        let stbl = [
+            mp4_box(b"stts", &words(&[0, 1, 2, 90_000])),
+            mp4_box(b"stsc", &words(&[0, 1, 1, 2, 1])),
+            mp4_box(b"stsz", &words(&[0, 0, 2, 7, 9])),
+            mp4_box(b"stco", &words(&[0, 1, sample_offset])),
+        ]
+        .concat();
+        let mdia = [
+            mp4_box(b"mdhd", &words(&[0, 0, 0, 1000])),
+            mp4_box(b"minf", &mp4_box(b"stbl", &stbl)),
+        ]
+        .concat();
+        let text = [tkhd(2), mp4_box(b"mdia", &mdia)].concat();
+        let moov = [mp4_box(b"trak", &audio), mp4_box(b"trak", &text)].concat();
+
+        let file = [
+            ftyp,
+            mp4_box(b"mdat", &mdat_payload),
+            mp4_box(b"moov", &moov),
+        ]
+        .concat();
+        let mut file = Cursor::new(file);
+        let moov = read_moov(&mut file).unwrap().unwrap();
+        assert!(nero_chapters(&moov).is_none());
+        let chapters = track_chapters(&moov, &mut file).unwrap();
+        assert_eq!(titles(&chapters), vec![("Intro", 0), ("Chapter", 90_000)]);
+    }
+}
@@ -0,0 +1,92 @@
+//! Personal notes on tracks, like where a song was first heard. They're kept in the
+//! database only, never written to the files, and are matched by the search.
+
+use super::library::TrackKey;
+use super::App;
+
+use rusqlite::{Connection, Result as SqlResult};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The notes by track.
+#[derive(Debug, Clone, Default)]
+pub struct TrackNotes(HashMap<TrackKey, String>);
+
+impl TrackNotes {
+    /// The track's note, empty when it has none.
+    pub fn get(&self, key: TrackKey) -> &str {
+        self.0.get(&key).map_or("", String::as_str)
+    }
+
+    /// Whether the track's note contains `search`, which is lowercase.
+    pub fn matches(&self, key: TrackKey, search: &str) -> bool {
+        self.0
+            .get(&key)
+            .is_some_and(|note| note.to_lowercase().contains(search))
+    }
+}
+
+fn load(conn: &Arc<Mutex<Connection>>) -> SqlResult<TrackNotes> {
+    let conn_guard = conn.lock().unwrap();
+    let mut stmt = conn_guard.prepare("SELECT library_item_id, note FROM track_notes")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    let mut notes = HashMap::new();
+    for row in rows {
+        let (key, note) = row?;
+        if let Ok(key) = key.parse::<TrackKey>() {
+            notes.insert(key, note);
+        }
+    }
+    Ok(TrackNotes(notes))
+}
+
+impl App {
+    pub fn load_track_notes(&mut self) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match load(&db.connection()) {
+            Ok(notes) => self.track_notes = notes,
+            Err(e) => tracing::error!("Failed to load track notes: {}", e),
+        }
+    }
+
+    /// Keeps a note on the track, an empty one removes it.
+    pub fn set_track_note(&mut self, key: TrackKey, note: &str) {
+        let note = note.trim();
+        let Some(db) = &self.database else {
+            return;
+        };
+
+        // The track must be saved in the library before its note can refer to it
+        db.wait_for_writes();
+        let conn = db.connection();
+        let conn_guard = conn.lock().unwrap();
+        let result = if note.is_empty() {
+            conn_guard.execute(
+                "DELETE FROM track_notes WHERE library_item_id = ?1",
+                rusqlite::params![key.to_string()],
+            )
+        } else {
+            conn_guard.execute(
+                "INSERT INTO track_notes (library_item_id, note) VALUES (?1, ?2)
+                 ON CONFLICT(library_item_id) DO UPDATE SET note = excluded.note",
+                rusqlite::params![key.to_string(), note],
+            )
+        };
+        if let Err(e) = result {
+            tracing::error!("Failed to store the note of {}: {}", key, e);
+            return;
+        }
+        drop(conn_guard);
+
+        if note.is_empty() {
+            self.track_notes.0.remove(&key);
+        } else {
+            self.track_notes.0.insert(key, note.to_string());
+        }
+    }
+}
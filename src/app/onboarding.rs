@@ -0,0 +1,117 @@
+//! The wizard shown on first launch: the language, the music folders and whether to
+//! import them right away, instead of an empty window.
+
+use super::library::LibraryPathStatus;
+use super::App;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnboardingStep {
+    #[default]
+    Language,
+    Folders,
+    Import,
+}
+
+impl OnboardingStep {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Language => Self::Folders,
+            Self::Folders | Self::Import => Self::Import,
+        }
+    }
+
+    pub fn previous(self) -> Self {
+        match self {
+            Self::Language | Self::Folders => Self::Language,
+            Self::Import => Self::Folders,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Onboarding {
+    pub step: OnboardingStep,
+    pub folders: Vec<PathBuf>,
+    // Otherwise the folders wait in the settings, e.g. to set exclude patterns first
+    pub import_now: bool,
+}
+
+impl Default for Onboarding {
+    fn default() -> Self {
+        Self {
+            step: OnboardingStep::default(),
+            folders: Vec::new(),
+            import_now: true,
+        }
+    }
+}
+
+impl Onboarding {
+    pub fn add_folder(&mut self, folder: PathBuf) {
+        if !self.folders.contains(&folder) {
+            self.folders.push(folder);
+        }
+    }
+}
+
+impl App {
+    /// Opens the wizard once the library is known to be empty, unless it was seen before.
+    pub fn start_onboarding_if_new(&mut self) {
+        if self.onboarded {
+            return;
+        }
+        if self.library.paths().is_empty() {
+            self.onboarding = Some(Onboarding::default());
+        } else {
+            // Set up before there was a wizard
+            self.onboarded = true;
+        }
+    }
+
+    /// Adds the chosen folders and closes the wizard for good.
+    pub fn finish_onboarding(&mut self) {
+        let Some(onboarding) = self.onboarding.take() else {
+            return;
+        };
+
+        for folder in onboarding.folders {
+            if !self.library.add_path(folder) || !onboarding.import_now {
+                continue;
+            }
+            if let Some(lib_path) = self.library.paths().last() {
+                if lib_path.status() == LibraryPathStatus::NotImported {
+                    self.import_library_paths(lib_path);
+                }
+            }
+        }
+
+        self.onboarded = true;
+        self.save_state();
+    }
+
+    /// Closes the wizard without adding anything, it isn't shown again.
+    pub fn skip_onboarding(&mut self) {
+        self.onboarding = None;
+        self.onboarded = true;
+        self.save_state();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_stay_in_range_and_folders_are_added_once() {
+        let mut onboarding = Onboarding::default();
+        assert_eq!(onboarding.step.previous(), OnboardingStep::Language);
+        onboarding.step = onboarding.step.next().next().next();
+        assert_eq!(onboarding.step, OnboardingStep::Import);
+
+        onboarding.add_folder(PathBuf::from("/music"));
+        onboarding.add_folder(PathBuf::from("/music"));
+        assert_eq!(onboarding.folders.len(), 1);
+        assert!(onboarding.import_now);
+    }
+}
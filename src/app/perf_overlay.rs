@@ -0,0 +1,70 @@
+//! A corner overlay with what the UI and the audio thread cost, for stutter reports from
+//! slow machines. F12 or the Help menu shows and hides it.
+
+use super::{t, tf, App};
+use crate::buffer_stats::OUTPUT_BUFFER;
+use crate::perf_stats::{DECODE, RESAMPLER};
+use eframe::egui::{self, Key, Modifiers};
+use std::time::Duration;
+
+// The audio figures change once per second of audio, the frame time every frame
+const REFRESH: Duration = Duration::from_millis(250);
+
+impl App {
+    /// Toggles the overlay on F12 and shows it while it's on.
+    pub fn handle_perf_overlay(&mut self, ctx: &egui::Context, frame: &eframe::Frame) {
+        if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::F12)) {
+            self.show_perf_overlay = !self.show_perf_overlay;
+        }
+        if !self.show_perf_overlay {
+            return;
+        }
+
+        // Time spent building the last frame, not counting the painting
+        let frame_ms = frame.info().cpu_usage.map(|seconds| seconds * 1000.0);
+        let percent = |load: Option<f32>| match load {
+            Some(load) => format!("{:.1}%", load),
+            None => "–".to_string(),
+        };
+        let buffer = OUTPUT_BUFFER.report();
+
+        egui::Area::new(egui::Id::new("perf_overlay"))
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -32.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    egui::Grid::new("perf_overlay_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            let mut row = |label: String, value: String| {
+                                ui.label(label);
+                                ui.monospace(value);
+                                ui.end_row();
+                            };
+                            row(
+                                t("perf_frame"),
+                                frame_ms.map_or("–".to_string(), |ms| format!("{:.1} ms", ms)),
+                            );
+                            row(t("perf_decode"), percent(DECODE.percent()));
+                            row(t("perf_resampler"), percent(RESAMPLER.percent()));
+                            row(
+                                t("perf_buffer"),
+                                if buffer.len > 0 {
+                                    tf(
+                                        "perf_buffer_value",
+                                        &[
+                                            &buffer.fill_percent().to_string(),
+                                            &buffer.underruns.to_string(),
+                                        ],
+                                    )
+                                } else {
+                                    "–".to_string()
+                                },
+                            );
+                        });
+                });
+            });
+        ctx.request_repaint_after(REFRESH);
+    }
+}
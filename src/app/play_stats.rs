@@ -0,0 +1,102 @@
+//! Play counts, skip counts and star ratings of the tracks. Plays are counted as they're
+//! logged to the history and skips when a track is left early, counts and ratings can
+//! also come from another player's library.
+
+use super::library::{LibraryItem, TrackKey};
+use super::App;
+
+// Share of a track before which moving on to the next one counts as a skip
+const SKIP_BEFORE: f64 = 0.25;
+
+/// How likely shuffle and radios pick a track when skipped tracks are downranked.
+pub fn skip_weight(track: &LibraryItem) -> f32 {
+    1.0 / (1.0 + track.skip_count() as f32)
+}
+
+impl App {
+    // Applies `update` to every copy of the track
+    fn update_track(&mut self, key: TrackKey, update: impl Fn(&mut LibraryItem)) {
+        self.library.update_item(key, &update);
+        for playlist in &mut self.playlists {
+            for track in playlist.tracks.iter_mut() {
+                if track.key() == key {
+                    update(track);
+                }
+            }
+        }
+        if let Some(track) = self
+            .player
+            .as_mut()
+            .and_then(|player| player.selected_track.as_mut())
+            .filter(|track| track.key() == key)
+        {
+            update(track);
+        }
+    }
+
+    /// Sets the play count and rating of every copy of the track and stores them.
+    pub fn set_track_stats(&mut self, key: TrackKey, play_count: u32, rating: u8) {
+        self.update_track(key, |item| {
+            item.set_play_count(play_count);
+            item.set_rating(rating);
+        });
+
+        if let Some(db) = &self.database {
+            let conn = db.connection();
+            let conn_guard = conn.lock().unwrap();
+            if let Err(e) = conn_guard.execute(
+                "UPDATE library_items SET play_count = ?1, rating = ?2 WHERE key = ?3",
+                rusqlite::params![play_count, rating, key.to_string()],
+            ) {
+                tracing::error!("Failed to store play count and rating: {}", e);
+            }
+        }
+    }
+
+    /// Adds a play to the track's count.
+    pub fn count_play(&mut self, key: TrackKey) {
+        let Some(track) = self.library.items().iter().find(|item| item.key() == key) else {
+            return;
+        };
+        let (play_count, rating) = (track.play_count() + 1, track.rating());
+        self.set_track_stats(key, play_count, rating);
+    }
+
+    /// Adds a skip to the track's count and stores it.
+    pub fn count_skip(&mut self, key: TrackKey) {
+        let Some(track) = self.library.items().iter().find(|item| item.key() == key) else {
+            return;
+        };
+        let skip_count = track.skip_count() + 1;
+        self.update_track(key, |item| {
+            item.set_skip_count(skip_count);
+        });
+
+        if let Some(db) = &self.database {
+            let conn = db.connection();
+            let conn_guard = conn.lock().unwrap();
+            if let Err(e) = conn_guard.execute(
+                "UPDATE library_items SET skip_count = ?1 WHERE key = ?2",
+                rusqlite::params![skip_count, key.to_string()],
+            ) {
+                tracing::error!("Failed to store skip count: {}", e);
+            }
+        }
+    }
+
+    /// Moves on to the next track at the user's request, counting a skip when the
+    /// current one was left in its first quarter.
+    pub fn skip_track(&mut self) {
+        let skipped = self.player.as_ref().and_then(|player| {
+            let track = player.selected_track.as_ref()?;
+            let early = player.duration > 0
+                && (player.seek_to_timestamp as f64) < player.duration as f64 * SKIP_BEFORE;
+            early.then(|| track.key())
+        });
+        if let Some(key) = skipped {
+            tracing::info!("Counting a skip of track {}", key);
+            self.count_skip(key);
+        }
+        self.next_track();
+    }
+}
@@ -1,13 +1,25 @@
-use crate::app::library::LibraryItem;
+use crate::app::audio_format::AudioFormat;
+use crate::app::library::{LibraryItem, ReplayGain, TrackKey};
+use crate::app::play_stats::skip_weight;
 use crate::app::playlist::Playlist;
+use crate::app::preamp::preamp_factor;
+use crate::app::resume::ResumePositions;
+use crate::channel_mix::ChannelMix;
+use crate::dsp::EffectConfig;
+use crate::output::OutputInfo;
+use crate::transition::TransitionPrefs;
+use crate::volume_bridge::VolumeBridge;
 use crate::{AudioCommand, UiCommand};
-use rand::seq::SliceRandom;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Arc;
 
-#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+// Share of the current track after which the next one is read ahead
+const PREFETCH_AT: f64 = 0.8;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum PlaybackMode {
     Normal,
     Repeat,
@@ -15,16 +27,72 @@ pub enum PlaybackMode {
     Shuffle,
 }
 
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+impl ReplayGainMode {
+    /// Linear gain factor for a track, limited so the track peak doesn't clip.
+    /// Returns `None` when the track has no ReplayGain values for this mode.
+    pub fn gain_factor(&self, replay_gain: &ReplayGain) -> Option<f32> {
+        let (gain, peak) = match self {
+            ReplayGainMode::Off => return None,
+            ReplayGainMode::Track => (replay_gain.track_gain, replay_gain.track_peak),
+            ReplayGainMode::Album => (
+                replay_gain.album_gain.or(replay_gain.track_gain),
+                replay_gain.album_peak.or(replay_gain.track_peak),
+            ),
+        };
+
+        let factor = 10f32.powf(gain? / 20.0);
+        match peak {
+            Some(peak) if peak > 0.0 => Some(factor.min(1.0 / peak)),
+            _ => Some(factor),
+        }
+    }
+}
+
+/// Converts a timestamp to seconds given a time base as numerator / denominator.
+pub fn ts_to_seconds(time_base: Option<(u32, u32)>, ts: u64) -> Option<f64> {
+    time_base
+        .filter(|(_, denom)| *denom > 0)
+        .map(|(numer, denom)| ts as f64 * numer as f64 / denom as f64)
+}
+
 pub struct Player {
     pub track_state: TrackState,
     pub selected_track: Option<LibraryItem>,
     pub audio_tx: Sender<AudioCommand>,
     pub ui_rx: Receiver<UiCommand>,
     pub volume: f32,
+    pub muted: bool, // The output is silent, `volume` is kept for unmuting
     pub seek_to_timestamp: u64,
     pub duration: u64,
     pub cursor: Arc<AtomicU32>, // This can "overflow"
     pub playback_mode: PlaybackMode,
+    pub replay_gain_mode: ReplayGainMode,
+    pub volume_leveling: bool,
+    pub stop_after_current: bool,
+    pub stop_after_track: Option<TrackKey>, // Key of the track to stop after
+    pub time_base: Option<(u32, u32)>,      // Of the loaded track, numerator / denominator
+    pub resume_positions: ResumePositions,
+    pub resume_min_minutes: u32,
+    pub shuffle: ShuffleState,
+    pub track_format: Option<AudioFormat>, // Of the loaded track, as probed by the audio thread
+    pub output_info: Option<OutputInfo>,   // Of the open output stream
+    pub playback_error: Option<String>,    // Why the last track couldn't be played
+    pub failed_in_a_row: usize,            // Tracks skipped since something last played
+    pub loading: bool,                     // The audio thread is still opening the selected track
+    pub prefetched_for: Option<TrackKey>,  // Key of the track whose successor was read ahead
+    pub transitions: TransitionPrefs,
+    pub downrank_skipped: bool, // Shuffle leaves often skipped tracks for late in a round
+    pub clean_filter: bool,     // Explicit tracks are passed over
+    pub volume_bridge: Option<VolumeBridge>, // The system mixer holds the volume
+    pub mode_before_audiobook: Option<PlaybackMode>, // Comes back after the book
 }
 
 impl Player {
@@ -39,23 +107,203 @@ impl Player {
             audio_tx: audio_cmd_tx,
             ui_rx: ui_cmd_rx,
             volume: 1.0,
+            muted: false,
             seek_to_timestamp: 0, // TODO: This should have subsecond precision, but is okay for now.
             duration: 0,
             cursor,
             playback_mode: PlaybackMode::Normal,
+            replay_gain_mode: ReplayGainMode::Off,
+            volume_leveling: false,
+            stop_after_current: false,
+            stop_after_track: None,
+            time_base: None,
+            resume_positions: ResumePositions::default(),
+            resume_min_minutes: crate::app::DEFAULT_RESUME_MIN_MINUTES,
+            shuffle: ShuffleState::default(),
+            track_format: None,
+            output_info: None,
+            playback_error: None,
+            failed_in_a_row: 0,
+            loading: false,
+            prefetched_for: None,
+            transitions: TransitionPrefs::default(),
+            downrank_skipped: false,
+            clean_filter: false,
+            volume_bridge: None,
+            mode_before_audiobook: None,
         }
     }
 
     pub fn select_track(&mut self, track: Option<LibraryItem>) {
+        // Books play front to back, the mode picked for music comes back after them
+        if let Some(track) = &track {
+            if !track.is_audiobook() {
+                if let Some(mode) = self.mode_before_audiobook.take() {
+                    self.playback_mode = mode;
+                }
+            } else if self.playback_mode != PlaybackMode::Normal {
+                self.mode_before_audiobook.get_or_insert(self.playback_mode);
+                self.playback_mode = PlaybackMode::Normal;
+                self.shuffle.reset();
+            }
+        }
+
         self.selected_track = track;
+        self.track_format = None;
+        self.loading = self.selected_track.is_some();
 
         if let Some(track) = &self.selected_track {
+            self.send_track_gain(track);
             self.audio_tx
-                .send(AudioCommand::LoadFile(track.path()))
+                .send(AudioCommand::LoadFile(track.path(), track.audio_track()))
                 .expect("Failed to send select to audio thread");
+
+            // Continue long tracks where they were left off
+            if let Some(position) = self.resume_positions.get(track.key()) {
+                tracing::info!("Resuming track at {}", position);
+                self.seek_to(position);
+            }
+        }
+    }
+
+    /// Reopens the selected track with another of its audio tracks, at the same position
+    /// and paused if it was.
+    pub fn switch_audio_track(&mut self, audio_track: Option<u32>) {
+        let Some(track) = &mut self.selected_track else {
+            return;
+        };
+        track.set_audio_track(audio_track);
+        self.audio_tx
+            .send(AudioCommand::LoadFile(track.path(), audio_track))
+            .expect("Failed to send select to audio thread");
+        self.loading = true;
+
+        if self.seek_to_timestamp > 0 {
+            self.seek_to(self.seek_to_timestamp);
+        }
+        if !matches!(self.track_state, TrackState::Playing) {
+            self.audio_tx
+                .send(AudioCommand::Pause)
+                .expect("Failed to send pause to audio thread");
+        }
+    }
+
+    /// Converts a timestamp of the loaded track to seconds.
+    pub fn ts_to_seconds(&self, ts: u64) -> Option<f64> {
+        ts_to_seconds(self.time_base, ts)
+    }
+
+    pub fn ts_to_ms(&self, ts: u64) -> Option<u64> {
+        self.ts_to_seconds(ts).map(|secs| (secs * 1000.0) as u64)
+    }
+
+    pub fn ms_to_ts(&self, ms: u64) -> Option<u64> {
+        self.time_base
+            .filter(|(numer, _)| *numer > 0)
+            .map(|(numer, denom)| (ms as f64 / 1000.0 * denom as f64 / numer as f64) as u64)
+    }
+
+    /// Remembers the current position if the track is long enough to be resumed later.
+    /// Audiobooks are remembered whatever their length.
+    pub fn remember_position(&mut self) {
+        let is_long = self.resume_min_minutes > 0
+            && self
+                .ts_to_seconds(self.duration)
+                .is_some_and(|secs| secs >= self.resume_min_minutes as f64 * 60.0);
+
+        if let Some(track) = &self.selected_track {
+            if is_long || track.is_audiobook() {
+                self.resume_positions
+                    .set(track.key(), self.seek_to_timestamp);
+            }
         }
     }
 
+    pub fn forget_position(&mut self) {
+        if let Some(track) = &self.selected_track {
+            self.resume_positions.remove(track.key());
+        }
+    }
+
+    pub fn set_replay_gain_mode(&mut self, mode: ReplayGainMode) {
+        self.replay_gain_mode = mode;
+        self.refresh_track_gain();
+    }
+
+    pub fn set_volume_leveling(&mut self, enabled: bool) {
+        self.volume_leveling = enabled;
+        self.refresh_track_gain();
+    }
+
+    pub fn set_channel_mix(&self, mix: ChannelMix) {
+        self.audio_tx
+            .send(AudioCommand::SetChannelMix(mix))
+            .expect("Failed to send channel mix to audio thread");
+    }
+
+    /// Replaces the effects audio runs through, in the given order.
+    pub fn set_dsp_chain(&self, effects: Vec<EffectConfig>) {
+        self.audio_tx
+            .send(AudioCommand::SetDspChain(effects))
+            .expect("Failed to send effect chain to audio thread");
+    }
+
+    pub fn set_soft_fades(&self, enabled: bool) {
+        self.audio_tx
+            .send(AudioCommand::SetSoftFades(enabled))
+            .expect("Failed to send soft fades to audio thread");
+    }
+
+    /// Gain for a track; `None` asks the audio thread to estimate it while playing.
+    fn track_gain(&self, track: &LibraryItem) -> Option<f32> {
+        match self.replay_gain_mode.gain_factor(&track.replay_gain()) {
+            Some(gain) => Some(gain),
+            None if self.volume_leveling => None,
+            None => Some(1.0),
+        }
+    }
+
+    /// Re-sends the gain of the selected track, e.g. after its ReplayGain values changed.
+    pub fn refresh_track_gain(&mut self) {
+        if let Some(track) = &self.selected_track {
+            self.send_track_gain(track);
+        }
+    }
+
+    fn send_track_gain(&self, track: &LibraryItem) {
+        self.audio_tx
+            .send(AudioCommand::SetTrackGain(self.track_gain(track)))
+            .expect("Failed to send track gain to audio thread");
+        self.audio_tx
+            .send(AudioCommand::SetTrackPreamp(preamp_factor(
+                track.preamp_db(),
+            )))
+            .expect("Failed to send track pre-amp to audio thread");
+    }
+
+    pub fn toggle_stop_after_track(&mut self, key: TrackKey) {
+        if self.stop_after_track == Some(key) {
+            self.stop_after_track = None;
+        } else {
+            self.stop_after_track = Some(key);
+        }
+    }
+
+    /// Checks whether playback should stop now that the current track finished.
+    /// The request is one-shot and is cleared once it triggers.
+    pub fn take_stop_after(&mut self) -> bool {
+        let selected_key = self.selected_track.as_ref().map(|track| track.key());
+        let stop = self.stop_after_current
+            || (self.stop_after_track.is_some() && self.stop_after_track == selected_key);
+
+        if stop {
+            self.stop_after_current = false;
+            self.stop_after_track = None;
+        }
+
+        stop
+    }
+
     pub fn is_stopped(&self) -> bool {
         matches!(self.track_state, TrackState::Stopped)
     }
@@ -122,47 +370,139 @@ impl Player {
 
     pub fn previous(&mut self, playlist: &Playlist) {
         if let Some(selected_track) = &self.selected_track {
+            // In shuffle, walk back through the tracks actually played
+            if self.playback_mode == PlaybackMode::Shuffle {
+                let keys: Vec<TrackKey> = playlist.tracks.iter().map(|t| t.key()).collect();
+                if let Some(previous_key) = self.shuffle.previous(selected_track.key(), &keys) {
+                    if let Some(previous_track) =
+                        playlist.tracks.iter().find(|t| t.key() == previous_key)
+                    {
+                        self.select_track(Some(previous_track.clone()));
+                        self.play();
+                    }
+                }
+                return;
+            }
+
             if let Some(current_track_position) = playlist.get_pos(selected_track) {
-                if current_track_position > 0 {
-                    let previous_track = &playlist.tracks[current_track_position - 1];
-                    self.select_track(Some((*previous_track).clone()));
+                if let Some(previous_track) = playlist.tracks[..current_track_position]
+                    .iter()
+                    .rev()
+                    .find(|track| self.allowed(track))
+                {
+                    self.select_track(Some(previous_track.clone()));
                     self.play();
                 }
             }
         }
     }
 
+    // Whether the clean filter lets the track play
+    fn allowed(&self, track: &LibraryItem) -> bool {
+        !(self.clean_filter && track.explicit())
+    }
+
+    // The next track in playlist order the clean filter allows, wrapping around on repeat
+    fn following<'a>(&self, playlist: &'a Playlist, position: usize) -> Option<&'a LibraryItem> {
+        let wrapped = match self.playback_mode {
+            PlaybackMode::Repeat => position + 1,
+            _ => 0,
+        };
+        playlist
+            .tracks
+            .iter()
+            .skip(position + 1)
+            .chain(playlist.tracks.iter().take(wrapped))
+            .find(|track| self.allowed(track))
+    }
+
+    /// The track `next` would pick, if that's known before it's called.
+    pub fn peek_next<'a>(&self, playlist: &'a Playlist) -> Option<&'a LibraryItem> {
+        let selected_track = self.selected_track.as_ref()?;
+        let position = playlist.get_pos(selected_track)?;
+        match self.playback_mode {
+            PlaybackMode::Normal | PlaybackMode::Repeat => self.following(playlist, position),
+            // The current track is open already
+            PlaybackMode::RepeatOne => None,
+            PlaybackMode::Shuffle => {
+                let next_key = self.shuffle.peek()?;
+                playlist
+                    .tracks
+                    .iter()
+                    .find(|track| track.key() == next_key)
+                    .filter(|track| self.allowed(track))
+            }
+        }
+    }
+
+    /// Reads the next track ahead once the current one is far enough along, so a slow
+    /// share doesn't leave a gap between them, and tells the audio thread how to get there.
+    pub fn prefetch_next(&mut self, playlist: &Playlist) {
+        let Some(current_track) = self.selected_track.as_ref() else {
+            return;
+        };
+        let current = current_track.key();
+        let far_enough = self.duration > 0
+            && self.seek_to_timestamp as f64 >= self.duration as f64 * PREFETCH_AT;
+        if !far_enough || self.prefetched_for == Some(current) {
+            return;
+        }
+        self.prefetched_for = Some(current);
+
+        let stopping = self.stop_after_current || self.stop_after_track == Some(current);
+        let transition = self.peek_next(playlist).map(|next_track| {
+            let _ = self
+                .audio_tx
+                .send(AudioCommand::Prefetch(next_track.path()));
+            let same_album = current_track.album_key().is_some()
+                && current_track.album_key() == next_track.album_key();
+            self.transitions
+                .pick(same_album, self.playback_mode == PlaybackMode::Shuffle)
+        });
+        let _ = self.audio_tx.send(AudioCommand::SetTransition(
+            transition.filter(|_| !stopping),
+        ));
+    }
+
     pub fn next(&mut self, playlist: &Playlist) {
         if let Some(selected_track) = &self.selected_track {
             if let Some(current_track_position) = playlist.get_pos(selected_track) {
                 match self.playback_mode {
-                    PlaybackMode::Normal => {
-                        if current_track_position < playlist.tracks.len() - 1 {
-                            let next_track = &playlist.tracks[current_track_position + 1];
-                            self.select_track(Some((*next_track).clone()));
+                    PlaybackMode::Normal | PlaybackMode::Repeat => {
+                        if let Some(next_track) = self.following(playlist, current_track_position) {
+                            self.select_track(Some(next_track.clone()));
                             self.play();
                         }
                     }
-                    PlaybackMode::Repeat => {
-                        let next_position = (current_track_position + 1) % playlist.tracks.len();
-                        let next_track = &playlist.tracks[next_position];
-                        self.select_track(Some((*next_track).clone()));
-                        self.play();
-                    }
                     PlaybackMode::RepeatOne => {
                         // Just replay the current track
                         self.seek_to(0);
                         self.play();
                     }
                     PlaybackMode::Shuffle => {
-                        if playlist.tracks.len() > 1 {
-                            let mut rng = rand::thread_rng();
-                            let available_indices: Vec<usize> = (0..playlist.tracks.len())
-                                .filter(|&i| i != current_track_position)
-                                .collect();
-                            if let Some(&next_index) = available_indices.choose(&mut rng) {
-                                let next_track = &playlist.tracks[next_index];
-                                self.select_track(Some((*next_track).clone()));
+                        let tracks: Vec<&LibraryItem> = playlist
+                            .tracks
+                            .iter()
+                            .filter(|track| self.allowed(track))
+                            .collect();
+                        let keys: Vec<TrackKey> = tracks.iter().map(|t| t.key()).collect();
+                        let weights: Vec<f32> = tracks
+                            .iter()
+                            .map(|t| {
+                                if self.downrank_skipped {
+                                    skip_weight(t)
+                                } else {
+                                    1.0
+                                }
+                            })
+                            .collect();
+                        if let Some(next_key) =
+                            self.shuffle.next(selected_track.key(), &keys, &weights)
+                        {
+                            if let Some(next_track) =
+                                playlist.tracks.iter().find(|t| t.key() == next_key)
+                            {
+                                self.select_track(Some(next_track.clone()));
                                 self.play();
                             }
                         }
@@ -177,12 +517,63 @@ impl Player {
         if !is_processing_ui_change.load(Ordering::Acquire) {
             is_processing_ui_change.store(true, Ordering::Release);
             self.volume = volume;
+            self.muted = false;
+            if let Some(bridge) = &self.volume_bridge {
+                bridge.set(volume);
+            }
             self.audio_tx
-                .send(AudioCommand::SetVolume(volume))
+                .send(AudioCommand::SetVolume(self.output_volume()))
                 .expect("Failed to send play to audio thread");
         }
     }
 
+    /// Silences the output without losing the volume to return to. Unlike dragging the
+    /// slider this is a single click, so it's never dropped while a change is in flight.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        self.audio_tx
+            .send(AudioCommand::SetVolume(self.output_volume()))
+            .expect("Failed to send volume to audio thread");
+    }
+
+    // The gain the audio thread applies, none when the system mixer has the volume
+    fn output_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else if self
+            .volume_bridge
+            .as_ref()
+            .is_some_and(VolumeBridge::carries_volume)
+        {
+            1.0
+        } else {
+            self.volume
+        }
+    }
+
+    /// Hands the volume over to the system mixer, which gets the current one. The app
+    /// scales the samples until the mixer has the app's stream.
+    pub fn attach_volume_bridge(&mut self, bridge: VolumeBridge) {
+        bridge.set(self.volume);
+        self.volume_bridge = Some(bridge);
+    }
+
+    /// Takes over a volume set in the system mixer since the last call.
+    pub fn follow_system_volume(&mut self) {
+        let Some(bridge) = self.volume_bridge.as_mut() else {
+            return;
+        };
+        let was_carrying = bridge.carries_volume();
+        if let Some(volume) = bridge.changed() {
+            self.volume = volume.clamp(0.0, 1.0);
+        }
+        if bridge.carries_volume() != was_carrying {
+            self.audio_tx
+                .send(AudioCommand::SetVolume(self.output_volume()))
+                .expect("Failed to send volume to audio thread");
+        }
+    }
+
     pub fn set_seek_to_timestamp(&mut self, seek_to_timestamp: u64) {
         self.seek_to_timestamp = seek_to_timestamp;
     }
@@ -198,6 +589,72 @@ impl Player {
             PlaybackMode::RepeatOne => PlaybackMode::Shuffle,
             PlaybackMode::Shuffle => PlaybackMode::Normal,
         };
+        self.shuffle.reset();
+        // Picked for the book, it stays
+        self.mode_before_audiobook = None;
+    }
+}
+
+/// Shuffle order as a random permutation of the playlist, so no track repeats
+/// until every track has played, plus the history of played tracks for "previous".
+/// Tracks are identified by their library key so playlist edits don't break it.
+#[derive(Debug, Default)]
+pub struct ShuffleState<K = TrackKey> {
+    upcoming: Vec<K>, // Popped from the end
+    history: Vec<K>,
+}
+
+impl<K: Copy + PartialEq> ShuffleState<K> {
+    pub fn reset(&mut self) {
+        self.upcoming.clear();
+        self.history.clear();
+    }
+
+    /// The next track of the round, a new round puts tracks of higher weight earlier.
+    /// `weights` are in the order of `keys`.
+    pub fn next(&mut self, current: K, keys: &[K], weights: &[f32]) -> Option<K> {
+        if keys.len() < 2 {
+            return None;
+        }
+
+        // Forget tracks that were removed from the playlist
+        self.upcoming
+            .retain(|key| keys.contains(key) && *key != current);
+
+        if self.upcoming.is_empty() {
+            // Start a new round with everything but the current track, ordered by random
+            // keys scaled by weight so heavier tracks tend to come up first
+            let mut rng = rand::thread_rng();
+            let mut round: Vec<(f32, K)> = keys
+                .iter()
+                .zip(weights)
+                .filter(|(&key, _)| key != current)
+                .map(|(&key, &weight)| (rng.gen::<f32>().powf(1.0 / weight.max(f32::EPSILON)), key))
+                .collect();
+            round.sort_by(|a, b| a.0.total_cmp(&b.0));
+            self.upcoming = round.into_iter().map(|(_, key)| key).collect();
+        }
+
+        let next = self.upcoming.pop()?;
+        self.history.push(current);
+        Some(next)
+    }
+
+    /// The track `next` returns, unless a new round has to be shuffled first.
+    pub fn peek(&self) -> Option<K> {
+        self.upcoming.last().copied()
+    }
+
+    pub fn previous(&mut self, current: K, keys: &[K]) -> Option<K> {
+        while let Some(previous) = self.history.pop() {
+            if keys.contains(&previous) {
+                // Going forward again returns to the current track
+                self.upcoming.push(current);
+                return Some(previous);
+            }
+        }
+
+        None
     }
 }
 
@@ -218,3 +675,115 @@ impl std::fmt::Display for TrackState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shuffle_plays_every_track_once_per_round() {
+        let keys = vec![1, 2, 3, 4, 5];
+        let mut shuffle = ShuffleState::default();
+
+        let mut current = 1;
+        let mut played = vec![current];
+        for _ in 0..4 {
+            current = shuffle.next(current, &keys, &[1.0; 5]).unwrap();
+            played.push(current);
+        }
+
+        played.sort();
+        assert_eq!(played, keys);
+    }
+
+    #[test]
+    fn shuffle_leaves_light_tracks_for_late_in_the_round() {
+        let keys = vec![1, 2, 3];
+        let mut late = 0;
+        for _ in 0..200 {
+            let mut shuffle = ShuffleState::default();
+            if shuffle.next(1, &keys, &[1.0, 1.0, 0.01]) == Some(2) {
+                late += 1;
+            }
+        }
+        assert!(late > 180, "track 3 came first {} times", 200 - late);
+    }
+
+    #[test]
+    fn clean_filter_passes_over_explicit_tracks() {
+        let (audio_tx, _audio_rx) = std::sync::mpsc::channel();
+        let (_ui_tx, ui_rx) = std::sync::mpsc::channel();
+        let mut player = Player::new(audio_tx, ui_rx, Arc::new(AtomicU32::new(0)));
+        player.clean_filter = true;
+
+        let mut playlist = Playlist::new();
+        for (name, explicit) in [("a", false), ("b", true), ("c", false)] {
+            let path = std::path::PathBuf::from(name);
+            let mut track = LibraryItem::new(path, crate::app::library::LibraryPathId::new(0));
+            track.set_explicit(explicit);
+            playlist.add(track);
+        }
+
+        player.select_track(playlist.tracks.first().cloned());
+        assert_eq!(player.peek_next(&playlist), playlist.tracks.get(2));
+        player.next(&playlist);
+        assert_eq!(player.selected_track.as_ref(), playlist.tracks.get(2));
+        player.previous(&playlist);
+        assert_eq!(player.selected_track.as_ref(), playlist.tracks.first());
+    }
+
+    #[test]
+    fn audiobooks_play_in_order_until_music_plays_again() {
+        let (audio_tx, _audio_rx) = std::sync::mpsc::channel();
+        let (_ui_tx, ui_rx) = std::sync::mpsc::channel();
+        let mut player = Player::new(audio_tx, ui_rx, Arc::new(AtomicU32::new(0)));
+        player.playback_mode = PlaybackMode::Shuffle;
+        let track = |name: &str| {
+            LibraryItem::new(
+                std::path::PathBuf::from(name),
+                crate::app::library::LibraryPathId::new(0),
+            )
+        };
+
+        player.select_track(Some(track("book.M4B")));
+        assert_eq!(player.playback_mode, PlaybackMode::Normal);
+        player.select_track(Some(track("book 2.m4b")));
+        assert_eq!(player.playback_mode, PlaybackMode::Normal);
+        player.select_track(Some(track("song.mp3")));
+        assert_eq!(player.playback_mode, PlaybackMode::Shuffle);
+
+        // A mode picked while a book plays is kept
+        player.select_track(Some(track("book.m4b")));
+        player.toggle_playback_mode();
+        player.select_track(Some(track("song.mp3")));
+        assert_eq!(player.playback_mode, PlaybackMode::Repeat);
+    }
+
+    #[test]
+    fn shuffle_previous_walks_back_through_history() {
+        let keys = vec![1, 2, 3, 4];
+        let mut shuffle = ShuffleState::default();
+
+        let second = shuffle.next(1, &keys, &[1.0; 4]).unwrap();
+        let third = shuffle.next(second, &keys, &[1.0; 4]).unwrap();
+
+        assert_eq!(shuffle.previous(third, &keys), Some(second));
+        assert_eq!(shuffle.previous(second, &keys), Some(1));
+        assert_eq!(shuffle.previous(1, &keys), None);
+
+        // Going forward again replays the same order
+        assert_eq!(shuffle.next(1, &keys, &[1.0; 4]), Some(second));
+        assert_eq!(shuffle.next(second, &keys, &[1.0; 4]), Some(third));
+    }
+
+    #[test]
+    fn shuffle_peek_matches_the_next_pick() {
+        let keys = vec![1, 2, 3, 4];
+        let mut shuffle = ShuffleState::default();
+        assert_eq!(shuffle.peek(), None);
+
+        let second = shuffle.next(1, &keys, &[1.0; 4]).unwrap();
+        let upcoming = shuffle.peek();
+        assert_eq!(shuffle.next(second, &keys, &[1.0; 4]), upcoming);
+    }
+}
@@ -0,0 +1,557 @@
+//! Imports play counts, ratings and playlists from another player's library, so
+//! switching players doesn't start the listening statistics over. Understands the iTunes
+//! or Music library XML (which MusicBee can export too), Rhythmbox's rhythmdb.xml and
+//! playlists.xml, and the databases of Clementine and Strawberry. Tracks are matched to
+//! the library by path, or by artist, title and album when the files moved.
+
+use super::library::{LibraryItem, TrackKey, MAX_RATING};
+use super::playlist::Playlist;
+use super::App;
+
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A track as another player knows it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportedTrack {
+    pub path: Option<PathBuf>,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub play_count: u32,
+    pub rating: u8, // Stars, 0 when unrated
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportedPlaylist {
+    pub name: String,
+    pub tracks: Vec<usize>, // Indices into the imported tracks
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Imported {
+    pub tracks: Vec<ImportedTrack>,
+    pub playlists: Vec<ImportedPlaylist>,
+}
+
+/// What an import changed, for the status line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub tracks: usize,
+    pub matched: usize,
+    pub playlists: usize,
+}
+
+/// Reads the library file of another player, telling the format by its contents.
+pub fn read(path: &Path) -> Result<Imported, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    if bytes.starts_with(b"SQLite format 3\0") {
+        return read_clementine(path);
+    }
+
+    let text = String::from_utf8_lossy(&bytes);
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true, // The iTunes XML declares its plist DTD
+        ..Default::default()
+    };
+    let doc = roxmltree::Document::parse_with_options(&text, options).map_err(|e| e.to_string())?;
+    let root = doc.root_element();
+    match root.tag_name().name() {
+        "plist" => read_itunes(root),
+        "rhythmdb" => Ok(read_rhythmdb(root)),
+        "rhythmdb-playlists" => Ok(read_rhythmbox_playlists(root)),
+        other => Err(format!("unknown library format <{}>", other)),
+    }
+}
+
+/// The local path of a `file://` URL as players store them, percent-decoded.
+pub fn file_url_to_path(url: &str) -> Option<PathBuf> {
+    let rest = url.strip_prefix("file://")?;
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+
+    let bytes = rest.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escaped = (bytes[idx] == b'%')
+            .then(|| rest.get(idx + 1..idx + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            None => {
+                decoded.push(bytes[idx]);
+                idx += 1;
+            }
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+
+    // "/C:/Music/..." on Windows
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+// Key/value pairs of a plist <dict>
+fn plist_entries<'a, 'input>(
+    dict: roxmltree::Node<'a, 'input>,
+) -> Vec<(&'a str, roxmltree::Node<'a, 'input>)> {
+    let mut entries = Vec::new();
+    let mut children = dict.children().filter(|node| node.is_element());
+    while let Some(key) = children.next() {
+        let Some(value) = children.next() else {
+            break;
+        };
+        entries.push((key.text().unwrap_or_default(), value));
+    }
+    entries
+}
+
+fn plist_value<'a, 'input>(
+    entries: &[(&str, roxmltree::Node<'a, 'input>)],
+    key: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    entries
+        .iter()
+        .find(|(entry_key, _)| *entry_key == key)
+        .map(|(_, value)| *value)
+}
+
+fn plist_text(entries: &[(&str, roxmltree::Node)], key: &str) -> Option<String> {
+    plist_value(entries, key).and_then(|value| value.text().map(str::to_string))
+}
+
+fn plist_number(entries: &[(&str, roxmltree::Node)], key: &str) -> Option<i64> {
+    plist_value(entries, key).and_then(|value| value.text()?.trim().parse().ok())
+}
+
+fn plist_flag(entries: &[(&str, roxmltree::Node)], key: &str) -> bool {
+    plist_value(entries, key).is_some_and(|value| value.has_tag_name("true"))
+}
+
+fn read_itunes(root: roxmltree::Node) -> Result<Imported, String> {
+    let top = root
+        .children()
+        .find(|node| node.has_tag_name("dict"))
+        .ok_or("empty plist")?;
+    let top = plist_entries(top);
+
+    let mut imported = Imported::default();
+    let mut by_id = HashMap::new();
+    if let Some(tracks) = plist_value(&top, "Tracks") {
+        for (_, track) in plist_entries(tracks) {
+            let track = plist_entries(track);
+            // A rating iTunes derived from the album's isn't the user's own
+            let rating = if plist_flag(&track, "Rating Computed") {
+                0
+            } else {
+                plist_number(&track, "Rating").unwrap_or(0) / 20
+            };
+            if let Some(id) = plist_number(&track, "Track ID") {
+                by_id.insert(id, imported.tracks.len());
+            }
+            imported.tracks.push(ImportedTrack {
+                path: plist_text(&track, "Location").and_then(|url| file_url_to_path(&url)),
+                artist: plist_text(&track, "Artist"),
+                title: plist_text(&track, "Name"),
+                album: plist_text(&track, "Album"),
+                play_count: plist_number(&track, "Play Count").unwrap_or(0).max(0) as u32,
+                rating: rating.clamp(0, MAX_RATING as i64) as u8,
+            });
+        }
+    }
+
+    if let Some(playlists) = plist_value(&top, "Playlists") {
+        for playlist in playlists
+            .children()
+            .filter(|node| node.has_tag_name("dict"))
+        {
+            let playlist = plist_entries(playlist);
+            // The whole library, built-in lists like Music or Podcasts and folders
+            let built_in = plist_flag(&playlist, "Master")
+                || plist_value(&playlist, "Distinguished Kind").is_some()
+                || plist_flag(&playlist, "Folder");
+            if built_in {
+                continue;
+            }
+            let tracks = plist_value(&playlist, "Playlist Items")
+                .map(|items| {
+                    items
+                        .children()
+                        .filter(|node| node.has_tag_name("dict"))
+                        .filter_map(|item| plist_number(&plist_entries(item), "Track ID"))
+                        .filter_map(|id| by_id.get(&id).copied())
+                        .collect()
+                })
+                .unwrap_or_default();
+            imported.playlists.push(ImportedPlaylist {
+                name: plist_text(&playlist, "Name").unwrap_or_default(),
+                tracks,
+            });
+        }
+    }
+
+    Ok(imported)
+}
+
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(str::to_string)
+}
+
+fn read_rhythmdb(root: roxmltree::Node) -> Imported {
+    let tracks = root
+        .children()
+        .filter(|node| node.has_tag_name("entry") && node.attribute("type") == Some("song"))
+        .map(|entry| ImportedTrack {
+            path: child_text(entry, "location").and_then(|url| file_url_to_path(&url)),
+            artist: child_text(entry, "artist"),
+            title: child_text(entry, "title"),
+            album: child_text(entry, "album"),
+            play_count: child_text(entry, "play-count")
+                .and_then(|count| count.parse().ok())
+                .unwrap_or(0),
+            rating: child_text(entry, "rating")
+                .and_then(|rating| rating.parse::<f32>().ok())
+                .map_or(0, |rating| {
+                    rating.clamp(0.0, MAX_RATING as f32).round() as u8
+                }),
+        })
+        .collect();
+
+    Imported {
+        tracks,
+        playlists: Vec::new(),
+    }
+}
+
+// Rhythmbox keeps playlists apart from the tracks, they're matched by location only
+fn read_rhythmbox_playlists(root: roxmltree::Node) -> Imported {
+    let mut imported = Imported::default();
+    for playlist in root
+        .children()
+        .filter(|node| node.has_tag_name("playlist") && node.attribute("type") == Some("static"))
+    {
+        let mut tracks = Vec::new();
+        for location in playlist
+            .children()
+            .filter(|node| node.has_tag_name("location"))
+        {
+            tracks.push(imported.tracks.len());
+            imported.tracks.push(ImportedTrack {
+                path: location.text().and_then(file_url_to_path),
+                ..Default::default()
+            });
+        }
+        imported.playlists.push(ImportedPlaylist {
+            name: playlist.attribute("name").unwrap_or_default().to_string(),
+            tracks,
+        });
+    }
+    imported
+}
+
+fn read_clementine(path: &Path) -> Result<Imported, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| e.to_string())?;
+    let mut imported = Imported::default();
+    let mut by_row = HashMap::new();
+
+    {
+        // Ratings go from 0 to 1 with -1 for unrated
+        let mut stmt = conn
+            .prepare("SELECT ROWID, filename, artist, title, album, playcount, rating FROM songs")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                let rating: Option<f64> = row.get(6)?;
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    ImportedTrack {
+                        path: row
+                            .get::<_, Option<String>>(1)?
+                            .and_then(|url| file_url_to_path(&url)),
+                        artist: row.get(2)?,
+                        title: row.get(3)?,
+                        album: row.get(4)?,
+                        play_count: row.get::<_, Option<i64>>(5)?.unwrap_or(0).max(0) as u32,
+                        rating: rating.map_or(0, |rating| {
+                            (rating.clamp(0.0, 1.0) * MAX_RATING as f64).round() as u8
+                        }),
+                    },
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        for row in rows {
+            let (row_id, track) = row.map_err(|e| e.to_string())?;
+            by_row.insert(row_id, imported.tracks.len());
+            imported.tracks.push(track);
+        }
+    }
+
+    // Items from the library point at their song, others only have a URL
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.ROWID, p.name, i.type, i.library_id, i.url
+             FROM playlist_items i JOIN playlists p ON p.ROWID = i.playlist
+             ORDER BY p.ROWID, i.ROWID",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut current: Option<i64> = None;
+    for row in rows {
+        let (playlist_id, name, kind, library_id, url) = row.map_err(|e| e.to_string())?;
+        if current != Some(playlist_id) {
+            current = Some(playlist_id);
+            imported.playlists.push(ImportedPlaylist {
+                name,
+                tracks: Vec::new(),
+            });
+        }
+        let from_library = library_id
+            .filter(|_| kind.as_deref() == Some("Library"))
+            .and_then(|id| by_row.get(&id).copied());
+        let track = match from_library {
+            Some(track) => track,
+            None => {
+                imported.tracks.push(ImportedTrack {
+                    path: url.and_then(|url| file_url_to_path(&url)),
+                    ..Default::default()
+                });
+                imported.tracks.len() - 1
+            }
+        };
+        if let Some(playlist) = imported.playlists.last_mut() {
+            playlist.tracks.push(track);
+        }
+    }
+
+    Ok(imported)
+}
+
+// Artist, title and album as compared between players, ignoring case and punctuation
+fn fingerprint(artist: Option<&str>, title: Option<&str>, album: Option<&str>) -> Option<String> {
+    let normalize = |text: &str| -> String {
+        text.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let (artist, title) = (normalize(artist?), normalize(title?));
+    if artist.is_empty() || title.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "{}\u{1f}{}\u{1f}{}",
+        artist,
+        title,
+        album.map(normalize).unwrap_or_default()
+    ))
+}
+
+/// Finds library tracks for imported ones, by path first and by tags otherwise.
+struct Matcher {
+    by_path: HashMap<PathBuf, TrackKey>,
+    by_tags: HashMap<String, TrackKey>,
+}
+
+impl Matcher {
+    fn new(items: &[LibraryItem]) -> Self {
+        let mut matcher = Self {
+            by_path: HashMap::new(),
+            by_tags: HashMap::new(),
+        };
+        for item in items {
+            matcher.by_path.insert(item.path(), item.key());
+            let tags = fingerprint(
+                item.artist().as_deref(),
+                item.title().as_deref(),
+                item.album().as_deref(),
+            );
+            if let Some(tags) = tags {
+                matcher.by_tags.entry(tags).or_insert(item.key());
+            }
+        }
+        matcher
+    }
+
+    fn find(&self, track: &ImportedTrack) -> Option<TrackKey> {
+        track
+            .path
+            .as_ref()
+            .and_then(|path| self.by_path.get(path))
+            .or_else(|| {
+                let tags = fingerprint(
+                    track.artist.as_deref(),
+                    track.title.as_deref(),
+                    track.album.as_deref(),
+                )?;
+                self.by_tags.get(&tags)
+            })
+            .copied()
+    }
+}
+
+impl App {
+    /// Takes play counts, ratings and playlists from another player's library file.
+    /// Counts only ever go up and playlists are added under their name once, so
+    /// importing the same file again changes nothing.
+    pub fn import_from_player(&mut self, source: &Path) -> Result<ImportSummary, String> {
+        let imported = read(source)?;
+        let matcher = Matcher::new(self.library.items());
+        let keys: Vec<Option<TrackKey>> = imported
+            .tracks
+            .iter()
+            .map(|track| matcher.find(track))
+            .collect();
+
+        let mut summary = ImportSummary::default();
+        for (track, key) in imported.tracks.iter().zip(&keys) {
+            // Placeholders for playlist entries carry no statistics
+            if track.play_count == 0 && track.rating == 0 && track.title.is_none() {
+                continue;
+            }
+            summary.tracks += 1;
+            let Some(item) = key.and_then(|key| {
+                self.library
+                    .items()
+                    .iter()
+                    .find(|item| item.key() == key)
+                    .cloned()
+            }) else {
+                continue;
+            };
+            summary.matched += 1;
+
+            let play_count = item.play_count().max(track.play_count);
+            let rating = if track.rating > 0 {
+                track.rating
+            } else {
+                item.rating()
+            };
+            if play_count != item.play_count() || rating != item.rating() {
+                self.set_track_stats(item.key(), play_count, rating);
+            }
+        }
+
+        for imported_playlist in &imported.playlists {
+            let exists = self.playlists.iter().any(|playlist| {
+                playlist.get_name().as_deref() == Some(imported_playlist.name.as_str())
+            });
+            if imported_playlist.name.is_empty() || exists {
+                continue;
+            }
+
+            let mut playlist = Playlist::new();
+            playlist.set_name(imported_playlist.name.clone());
+            for key in imported_playlist.tracks.iter().filter_map(|idx| keys[*idx]) {
+                if let Some(item) = self.library.items().iter().find(|item| item.key() == key) {
+                    playlist.add(item.clone());
+                }
+            }
+            if !playlist.tracks.is_empty() {
+                self.playlists.push(playlist);
+                summary.playlists += 1;
+            }
+        }
+
+        tracing::info!("Imported from {}: {:?}", source.display(), summary);
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+
+    #[test]
+    fn file_urls_become_paths() {
+        assert_eq!(
+            file_url_to_path("file:///home/me/Music/A%20Song%C3%A9.mp3"),
+            Some(PathBuf::from("/home/me/Music/A Songé.mp3"))
+        );
+        assert_eq!(
+            file_url_to_path("file://localhost/C:/Music/x.m4a"),
+            Some(PathBuf::from("C:/Music/x.m4a"))
+        );
+        assert_eq!(file_url_to_path("http://example.com/x.mp3"), None);
+    }
+
+    #[test]
+    fn itunes_tracks_and_playlists_are_read() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0"><dict>
+  <key>Tracks</key><dict>
+    <key>7</key><dict>
+      <key>Track ID</key><integer>7</integer>
+      <key>Name</key><string>Song</string>
+      <key>Artist</key><string>Band</string>
+      <key>Play Count</key><integer>12</integer>
+      <key>Rating</key><integer>80</integer>
+      <key>Location</key><string>file:///music/song.mp3</string>
+    </dict>
+  </dict>
+  <key>Playlists</key><array>
+    <dict><key>Name</key><string>Library</string><key>Master</key><true/></dict>
+    <dict><key>Name</key><string>Road trip</string>
+      <key>Playlist Items</key><array><dict><key>Track ID</key><integer>7</integer></dict></array>
+    </dict>
+  </array>
+</dict></plist>"#;
+        let options = roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        };
+        let doc = roxmltree::Document::parse_with_options(xml, options).unwrap();
+        let imported = read_itunes(doc.root_element()).unwrap();
+
+        assert_eq!(imported.tracks.len(), 1);
+        let track = &imported.tracks[0];
+        assert_eq!(track.path, Some(PathBuf::from("/music/song.mp3")));
+        assert_eq!((track.play_count, track.rating), (12, 4));
+        assert_eq!(
+            imported.playlists,
+            vec![ImportedPlaylist {
+                name: "Road trip".to_string(),
+                tracks: vec![0],
+            }]
+        );
+    }
+
+    #[test]
+    fn moved_files_match_by_tags() {
+        let mut item = LibraryItem::new(PathBuf::from("/new/place.flac"), LibraryPathId::new(0));
+        item.set_artist(Some("The Band"));
+        item.set_title(Some("Song (Live)"));
+        let matcher = Matcher::new(std::slice::from_ref(&item));
+
+        let track = ImportedTrack {
+            path: Some(PathBuf::from("/old/place.mp3")),
+            artist: Some("the band".to_string()),
+            title: Some("Song Live".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(matcher.find(&track), Some(item.key()));
+    }
+}
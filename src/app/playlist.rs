@@ -1,11 +1,162 @@
+use crate::app::labels::LabelId;
+use crate::app::library::{format_date, Picture, TrackKey};
+use crate::app::tag_writer::TagField;
 use crate::app::LibraryItem;
 use crate::AudioCommand;
-use rusqlite::{Connection, Result as SqlResult};
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::path::Path;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 
+/// Playlists the app maintains itself instead of the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VirtualPlaylist {
+    RecentlyAdded,
+    Favorites,
+    // Every track with the label
+    Label(LabelId),
+}
+
+/// Columns of the playlist table the tracks can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistColumn {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Track,
+    Year,
+    Added,
+    Modified,
+    Plays,
+    Skips,
+    Rating,
+    Format,
+    Bitrate,
+}
+
+impl PlaylistColumn {
+    pub const ALL: [PlaylistColumn; 13] = [
+        PlaylistColumn::Title,
+        PlaylistColumn::Artist,
+        PlaylistColumn::Album,
+        PlaylistColumn::Genre,
+        PlaylistColumn::Track,
+        PlaylistColumn::Year,
+        PlaylistColumn::Added,
+        PlaylistColumn::Modified,
+        PlaylistColumn::Plays,
+        PlaylistColumn::Skips,
+        PlaylistColumn::Rating,
+        PlaylistColumn::Format,
+        PlaylistColumn::Bitrate,
+    ];
+
+    /// Position in `ALL`.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Translation key of the column header.
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            PlaylistColumn::Title => "column_title",
+            PlaylistColumn::Artist => "column_artist",
+            PlaylistColumn::Album => "column_album",
+            PlaylistColumn::Genre => "column_genre",
+            PlaylistColumn::Track => "column_track",
+            PlaylistColumn::Year => "column_year",
+            PlaylistColumn::Added => "column_added",
+            PlaylistColumn::Modified => "column_modified",
+            PlaylistColumn::Plays => "column_plays",
+            PlaylistColumn::Skips => "column_skips",
+            PlaylistColumn::Rating => "column_rating",
+            PlaylistColumn::Format => "column_format",
+            PlaylistColumn::Bitrate => "column_bitrate",
+        }
+    }
+
+    /// Relative width of the column in the table.
+    pub fn width(&self) -> f32 {
+        match self {
+            PlaylistColumn::Title => 0.27,
+            PlaylistColumn::Artist => 0.17,
+            PlaylistColumn::Album => 0.19,
+            PlaylistColumn::Genre => 0.10,
+            PlaylistColumn::Track | PlaylistColumn::Year => 0.05,
+            PlaylistColumn::Added | PlaylistColumn::Modified => 0.11,
+            PlaylistColumn::Plays | PlaylistColumn::Skips => 0.06,
+            PlaylistColumn::Rating => 0.08,
+            PlaylistColumn::Format => 0.10,
+            PlaylistColumn::Bitrate => 0.08,
+        }
+    }
+
+    /// Audio format columns are optional.
+    pub fn is_format(&self) -> bool {
+        matches!(self, PlaylistColumn::Format | PlaylistColumn::Bitrate)
+    }
+
+    /// The tag the column shows, for the columns that can be edited.
+    pub fn tag_field(&self) -> Option<TagField> {
+        match self {
+            PlaylistColumn::Title => Some(TagField::Title),
+            PlaylistColumn::Artist => Some(TagField::Artist),
+            PlaylistColumn::Album => Some(TagField::Album),
+            PlaylistColumn::Genre => Some(TagField::Genre),
+            PlaylistColumn::Track => Some(TagField::TrackNumber),
+            PlaylistColumn::Year => Some(TagField::Year),
+            _ => None,
+        }
+    }
+
+    /// The text of the column as shown in the table, dates as "YYYY-MM-DD".
+    pub fn value(&self, track: &LibraryItem) -> Option<String> {
+        match self {
+            PlaylistColumn::Title => track.title(),
+            PlaylistColumn::Artist => track.artist(),
+            PlaylistColumn::Album => track.album(),
+            PlaylistColumn::Genre => track.genre(),
+            PlaylistColumn::Track => track.track_number().map(|number| number.to_string()),
+            PlaylistColumn::Year => track.year().map(|year| year.to_string()),
+            PlaylistColumn::Added => track.added_at().map(format_date),
+            PlaylistColumn::Modified => track.modified_at().map(format_date),
+            PlaylistColumn::Plays => Some(track.play_count())
+                .filter(|plays| *plays > 0)
+                .map(|plays| plays.to_string()),
+            PlaylistColumn::Skips => Some(track.skip_count())
+                .filter(|skips| *skips > 0)
+                .map(|skips| skips.to_string()),
+            PlaylistColumn::Rating => Some(track.rating())
+                .filter(|rating| *rating > 0)
+                .map(|rating| "★".repeat(rating as usize)),
+            PlaylistColumn::Format => {
+                Some(track.format().short_summary()).filter(|summary| !summary.is_empty())
+            }
+            PlaylistColumn::Bitrate => track
+                .format()
+                .bitrate
+                .map(|bitrate| format!("{} kbps", bitrate)),
+        }
+    }
+}
+
+/// Returns true if the track contains every non-empty filter in its column,
+/// ignoring case. `filters` are in the order of `PlaylistColumn::ALL`.
+pub fn matches_filters(track: &LibraryItem, filters: &[String]) -> bool {
+    PlaylistColumn::ALL
+        .iter()
+        .zip(filters)
+        .filter(|(_, filter)| !filter.trim().is_empty())
+        .all(|(column, filter)| {
+            column
+                .value(track)
+                .is_some_and(|value| value.to_lowercase().contains(&filter.trim().to_lowercase()))
+        })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Playlist {
     pub id: Option<i64>,
@@ -14,6 +165,21 @@ pub struct Playlist {
     pub selected: Option<LibraryItem>,
     #[serde(skip_serializing, skip_deserializing)]
     pub selected_indices: HashSet<usize>,
+    // Virtual playlists are rebuilt from the library and never stored
+    #[serde(skip_serializing, skip_deserializing)]
+    pub virtual_kind: Option<VirtualPlaylist>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub view: PlaylistView,
+    // Only the name is known yet, the tracks are still read from the database
+    #[serde(skip_serializing, skip_deserializing)]
+    pub loading: bool,
+}
+
+/// Where the tab of a playlist was left, restored when switching back to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistView {
+    pub scroll_offset: [f32; 2],
+    pub sort: Option<(PlaylistColumn, bool)>, // Column and whether ascending
 }
 
 impl Default for Playlist {
@@ -30,9 +196,23 @@ impl Playlist {
             tracks: vec![],
             selected: None,
             selected_indices: HashSet::new(),
+            virtual_kind: None,
+            view: PlaylistView::default(),
+            loading: false,
+        }
+    }
+
+    pub fn new_virtual(kind: VirtualPlaylist) -> Self {
+        Self {
+            virtual_kind: Some(kind),
+            ..Self::new()
         }
     }
 
+    pub fn is_virtual(&self) -> bool {
+        self.virtual_kind.is_some()
+    }
+
     pub fn set_name(&mut self, name: String) {
         self.name = Some(name);
     }
@@ -42,9 +222,25 @@ impl Playlist {
     }
 
     pub fn add(&mut self, track: LibraryItem) {
+        // The contents of virtual playlists come from the library
+        if self.is_virtual() {
+            return;
+        }
         self.tracks.push(track);
     }
 
+    /// Adds the tracks not yet in the playlist, returning how many were added.
+    pub fn append_missing(&mut self, tracks: &[LibraryItem]) -> usize {
+        let mut added = 0;
+        for track in tracks {
+            if !self.tracks.iter().any(|t| t.key() == track.key()) {
+                self.add(track.clone());
+                added += 1;
+            }
+        }
+        added
+    }
+
     // TODO - should probably return a Result
     pub fn remove(&mut self, idx: usize) {
         self.tracks.remove(idx);
@@ -95,13 +291,63 @@ impl Playlist {
         self.selected_indices = new_selected;
     }
 
+    /// Sorts the tracks by a column, keeping the selection on the same tracks.
+    /// Tracks with equal values keep their relative order.
+    pub fn sort_by_column(&mut self, column: PlaylistColumn, ascending: bool) {
+        let text = |value: Option<String>| value.map(|value| value.to_lowercase());
+
+        let mut order: Vec<usize> = (0..self.tracks.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&self.tracks[a], &self.tracks[b]);
+            let ordering = match column {
+                PlaylistColumn::Title => text(a.title()).cmp(&text(b.title())),
+                PlaylistColumn::Artist => text(a.artist()).cmp(&text(b.artist())),
+                PlaylistColumn::Album => text(a.album()).cmp(&text(b.album())),
+                PlaylistColumn::Genre => text(a.genre()).cmp(&text(b.genre())),
+                PlaylistColumn::Track => a.track_number().cmp(&b.track_number()),
+                PlaylistColumn::Year => a.year().cmp(&b.year()),
+                PlaylistColumn::Added => a.added_at().cmp(&b.added_at()),
+                PlaylistColumn::Modified => a.modified_at().cmp(&b.modified_at()),
+                PlaylistColumn::Plays => a.play_count().cmp(&b.play_count()),
+                PlaylistColumn::Skips => a.skip_count().cmp(&b.skip_count()),
+                PlaylistColumn::Rating => a.rating().cmp(&b.rating()),
+                PlaylistColumn::Format => {
+                    let key = |t: &LibraryItem| {
+                        let format = t.format();
+                        (
+                            format.is_lossless(),
+                            format.codec_name(),
+                            format.bit_depth,
+                            format.sample_rate,
+                        )
+                    };
+                    key(a).cmp(&key(b))
+                }
+                PlaylistColumn::Bitrate => a.format().bitrate.cmp(&b.format().bitrate),
+            };
+            if ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+
+        self.selected_indices = order
+            .iter()
+            .enumerate()
+            .filter(|(_, old_idx)| self.selected_indices.contains(old_idx))
+            .map(|(new_idx, _)| new_idx)
+            .collect();
+        self.tracks = order.iter().map(|&idx| self.tracks[idx].clone()).collect();
+    }
+
     // TODO - should probably return a Result
     pub fn select(&mut self, idx: usize, audio_cmd_tx: &Sender<AudioCommand>) {
         tracing::info!("SELECTED");
         let track = self.tracks[idx].clone();
         let path = &track.path();
         audio_cmd_tx
-            .send(AudioCommand::LoadFile((*path).clone()))
+            .send(AudioCommand::LoadFile((*path).clone(), track.audio_track()))
             .expect("Failed to send to audio thread");
 
         self.selected = Some(track);
@@ -111,6 +357,26 @@ impl Playlist {
         self.tracks.iter().position(|t| t == track)
     }
 
+    /// Index of the first track from `start` on, wrapping around, whose title starts
+    /// with `prefix` ignoring case. Only tracks accepted by `visible` are considered.
+    pub fn find_by_prefix(
+        &self,
+        prefix: &str,
+        start: usize,
+        visible: impl Fn(&LibraryItem) -> bool,
+    ) -> Option<usize> {
+        let prefix = prefix.to_lowercase();
+        let len = self.tracks.len();
+
+        (0..len).map(|offset| (start + offset) % len).find(|&idx| {
+            let track = &self.tracks[idx];
+            visible(track)
+                && track
+                    .title()
+                    .is_some_and(|title| title.to_lowercase().starts_with(&prefix))
+        })
+    }
+
     pub fn select_all(&mut self) {
         self.selected_indices.clear();
         for i in 0..self.tracks.len() {
@@ -130,13 +396,38 @@ impl Playlist {
         }
     }
 
+    /// Keys of the selected tracks in playlist order when the track at `idx` is one of
+    /// them, else the key of that track alone. What a context menu action applies to.
+    pub fn keys_for_action(&self, idx: usize) -> Vec<TrackKey> {
+        if !self.is_selected(idx) {
+            return self
+                .tracks
+                .get(idx)
+                .map(|track| track.key())
+                .into_iter()
+                .collect();
+        }
+        let mut selected: Vec<usize> = self.selected_indices.iter().copied().collect();
+        selected.sort_unstable();
+        selected
+            .iter()
+            .filter_map(|idx| self.tracks.get(*idx))
+            .map(|track| track.key())
+            .collect()
+    }
+
     pub fn is_selected(&self, idx: usize) -> bool {
         self.selected_indices.contains(&idx)
     }
 
     // Database methods
 
-    pub fn save_to_db(&self, conn: &Arc<Mutex<Connection>>) -> SqlResult<()> {
+    /// Stores the playlist and returns its database id, virtual playlists aren't stored.
+    pub fn save_to_db(&self, conn: &Arc<Mutex<Connection>>) -> SqlResult<Option<i64>> {
+        if self.is_virtual() {
+            return Ok(None);
+        }
+
         let mut conn = conn.lock().unwrap();
 
         // Start a transaction
@@ -166,17 +457,23 @@ impl Playlist {
             None => tx.last_insert_rowid(),
         };
 
+        // The tracks in the database are all there is until they're loaded
+        if self.loading {
+            tx.commit()?;
+            return Ok(Some(playlist_id));
+        }
+
         // Clear existing playlist items
         tx.execute(
             "DELETE FROM playlist_items WHERE playlist_id = ?1",
             rusqlite::params![playlist_id],
         )?;
 
-        // Insert the tracks with their positions
+        // Insert the tracks with their positions, leaving out those removed from the library
         for (position, track) in self.tracks.iter().enumerate() {
             tx.execute(
-                "INSERT INTO playlist_items (playlist_id, library_item_id, position) 
-                 VALUES (?1, ?2, ?3)",
+                "INSERT INTO playlist_items (playlist_id, library_item_id, position)
+                 SELECT ?1, ?2, ?3 WHERE EXISTS (SELECT 1 FROM library_items WHERE key = ?2)",
                 rusqlite::params![playlist_id, track.key().to_string(), position as i32],
             )?;
         }
@@ -184,7 +481,7 @@ impl Playlist {
         // Commit the transaction
         tx.commit()?;
 
-        Ok(())
+        Ok(Some(playlist_id))
     }
 
     pub fn load_from_db(conn: &Arc<Mutex<Connection>>, playlist_id: i64) -> SqlResult<Self> {
@@ -206,10 +503,13 @@ impl Playlist {
                 tracks: vec![],
                 selected: None,
                 selected_indices: HashSet::new(),
+                virtual_kind: None,
+                view: PlaylistView::default(),
+                loading: false,
             };
 
             // Get the tracks
-            let mut items_stmt = conn_guard.prepare(
+            let mut items_stmt = conn_guard.prepare_cached(
                 "SELECT li.* FROM library_items li
                  JOIN playlist_items pi ON li.key = pi.library_item_id
                  WHERE pi.playlist_id = ?1
@@ -235,37 +535,33 @@ impl Playlist {
                 item.set_genre(row.get::<_, Option<String>>(7)?.as_deref());
                 item.set_track_number(row.get::<_, Option<u32>>(8)?);
                 item.set_lyrics(row.get::<_, Option<String>>(9)?.as_deref());
+                item.set_replay_gain(crate::app::library::ReplayGain::from_row(row, 10)?);
+                item.set_album_artist(row.get::<_, Option<String>>(14)?.as_deref());
+                item.set_artists(crate::app::library::split_joined_artists(row.get(15)?));
+                item.set_added_at(row.get(16)?);
+                item.set_modified_at(row.get(17)?);
+                item.set_format(crate::app::audio_format::AudioFormat::from_row(row, 18)?);
+                item.set_preamp_db(row.get::<_, Option<f32>>(23)?.unwrap_or(0.0));
+                item.set_play_count(row.get::<_, Option<u32>>(24)?.unwrap_or(0));
+                item.set_rating(row.get::<_, Option<u8>>(25)?.unwrap_or(0));
+                item.set_skip_count(row.get::<_, Option<u32>>(26)?.unwrap_or(0));
+                item.set_explicit(row.get::<_, Option<bool>>(27)?.unwrap_or(false));
+                item.set_audio_track(row.get(28)?);
 
                 // Set the key from the database
-                if let Ok(key_val) = key_str.parse::<usize>() {
+                if let Ok(key_val) = key_str.parse::<TrackKey>() {
                     item.set_key(key_val);
                 }
 
-                // Load album art (pictures) from the database
-                let mut pic_stmt = conn_guard.prepare(
-                    "SELECT mime_type, picture_type, description, file_path 
-                     FROM pictures WHERE library_item_id = ?",
-                )?;
+                playlist.tracks.push(item);
+            }
 
-                let picture_rows = pic_stmt.query_map(rusqlite::params![key_str], |row| {
-                    let mime_type: String = row.get(0)?;
-                    let picture_type: u8 = row.get(1)?;
-                    let description: String = row.get(2)?;
-                    let file_path: String = row.get(3)?;
-
-                    Ok(crate::app::library::Picture::new(
-                        mime_type,
-                        picture_type,
-                        description,
-                        std::path::PathBuf::from(file_path),
-                    ))
-                })?;
-
-                for picture in picture_rows {
-                    item.add_picture(picture?);
+            // Load album art (pictures) of all tracks at once
+            let pictures = Picture::load_all(&conn_guard, Some(playlist_id))?;
+            for track in &mut playlist.tracks {
+                for picture in pictures.get(&track.key().to_string()).into_iter().flatten() {
+                    track.add_picture(picture.clone());
                 }
-
-                playlist.tracks.push(item);
             }
 
             Ok(playlist)
@@ -274,32 +570,45 @@ impl Playlist {
         }
     }
 
-    pub fn load_all_from_db(conn: &Arc<Mutex<Connection>>) -> SqlResult<Vec<Self>> {
-        let mut playlists = Vec::new();
-
-        // First, get all playlist IDs
-        let playlist_ids = {
-            let conn_guard = conn.lock().unwrap();
-            let mut stmt = conn_guard.prepare("SELECT id FROM playlists")?;
-            let id_iter = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+    /// Every stored playlist by name only, their tracks are loaded later.
+    pub fn load_names_from_db(conn: &Arc<Mutex<Connection>>) -> SqlResult<Vec<Self>> {
+        let conn_guard = conn.lock().unwrap();
+        let mut stmt = conn_guard.prepare("SELECT id, name FROM playlists")?;
+        let rows = stmt.query_map([], |row| {
+            let mut playlist = Playlist::new();
+            playlist.id = Some(row.get(0)?);
+            playlist.name = row.get(1)?;
+            playlist.loading = true;
+            Ok(playlist)
+        })?;
+        rows.collect()
+    }
 
-            // Collect IDs into a Vec to release the connection lock
-            let mut ids = Vec::new();
-            for id_result in id_iter {
-                ids.push(id_result?);
-            }
-            ids
-        };
+    /// The first stored playlist holding the file.
+    pub fn find_in_db(conn: &Arc<Mutex<Connection>>, path: &Path) -> SqlResult<Option<i64>> {
+        let conn_guard = conn.lock().unwrap();
+        conn_guard
+            .query_row(
+                "SELECT pi.playlist_id FROM playlist_items pi
+                 JOIN library_items li ON li.key = pi.library_item_id
+                 WHERE li.path = ?1
+                 ORDER BY pi.playlist_id
+                 LIMIT 1",
+                rusqlite::params![path.to_string_lossy().to_string()],
+                |row| row.get(0),
+            )
+            .optional()
+    }
 
-        // Now load each playlist by ID
-        for id in playlist_ids {
-            match Self::load_from_db(conn, id) {
-                Ok(playlist) => playlists.push(playlist),
-                Err(e) => tracing::error!("Failed to load playlist {}: {}", id, e),
+    /// Fills in the tracks read from the database, after the ones added meanwhile.
+    pub fn finish_loading(&mut self, loaded: Playlist) {
+        let added = std::mem::replace(&mut self.tracks, loaded.tracks);
+        for track in added {
+            if !self.tracks.contains(&track) {
+                self.tracks.push(track);
             }
         }
-
-        Ok(playlists)
+        self.loading = false;
     }
 
     pub fn delete_from_db(conn: &Arc<Mutex<Connection>>, playlist_id: i64) -> SqlResult<()> {
@@ -379,6 +688,9 @@ mod tests {
             ],
             selected: None,
             selected_indices: HashSet::new(),
+            virtual_kind: None,
+            view: PlaylistView::default(),
+            loading: false,
         };
 
         assert_eq!(playlist.tracks.len(), 3);
@@ -390,6 +702,31 @@ mod tests {
         assert_eq!(playlist.tracks.last().unwrap().path(), path3);
     }
 
+    #[test]
+    fn tracks_added_while_loading_are_kept() {
+        let track = |name: &str| LibraryItem::new(PathBuf::from(name), LibraryPathId::new(0));
+        let mut playlist = Playlist::new();
+        playlist.loading = true;
+        playlist.add(track("new.mp3"));
+
+        let mut loaded = Playlist::new();
+        loaded.tracks = vec![track("a.mp3"), track("b.mp3")];
+        let stored = loaded.tracks[0].clone();
+        playlist.add(stored);
+        playlist.finish_loading(loaded);
+
+        assert!(!playlist.loading);
+        let names: Vec<PathBuf> = playlist.tracks.iter().map(|t| t.path()).collect();
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("a.mp3"),
+                PathBuf::from("b.mp3"),
+                PathBuf::from("new.mp3")
+            ]
+        );
+    }
+
     #[test]
     fn reorder_track_in_playlist() {
         let path1 = PathBuf::from(r"C:\music\song1.mp3");
@@ -406,6 +743,9 @@ mod tests {
             ],
             selected: None,
             selected_indices: HashSet::new(),
+            virtual_kind: None,
+            view: PlaylistView::default(),
+            loading: false,
         };
 
         assert_eq!(playlist.tracks.len(), 3);
@@ -418,6 +758,89 @@ mod tests {
         assert_eq!(playlist.tracks[2].path(), path1);
     }
 
+    #[test]
+    fn sort_playlist_by_column_keeps_selection() {
+        let mut playlist = Playlist::new();
+        for (name, added_at) in [("b", 20), ("C", 10), ("a", 30)] {
+            let mut track = LibraryItem::new(PathBuf::from(name), LibraryPathId::new(0));
+            track.set_title(Some(name));
+            track.set_added_at(Some(added_at));
+            playlist.add(track);
+        }
+        playlist.toggle_selection(1);
+
+        playlist.sort_by_column(PlaylistColumn::Title, true);
+        let titles: Vec<_> = playlist.tracks.iter().filter_map(|t| t.title()).collect();
+        assert_eq!(titles, ["a", "b", "C"]);
+        assert!(playlist.is_selected(2));
+
+        playlist.sort_by_column(PlaylistColumn::Added, false);
+        let titles: Vec<_> = playlist.tracks.iter().filter_map(|t| t.title()).collect();
+        assert_eq!(titles, ["a", "b", "C"]);
+
+        playlist.sort_by_column(PlaylistColumn::Added, true);
+        assert_eq!(playlist.tracks[0].title().as_deref(), Some("C"));
+        assert!(playlist.is_selected(0));
+    }
+
+    #[test]
+    fn append_missing_skips_tracks_already_present() {
+        let first = LibraryItem::new(PathBuf::from("first.mp3"), LibraryPathId::new(0));
+        let second = LibraryItem::new(PathBuf::from("second.mp3"), LibraryPathId::new(0));
+
+        let mut playlist = Playlist::new();
+        playlist.add(first.clone());
+
+        assert_eq!(playlist.append_missing(&[second.clone(), first.clone()]), 1);
+        assert_eq!(playlist.tracks, vec![first, second]);
+    }
+
+    #[test]
+    fn find_by_prefix_wraps_around() {
+        let mut playlist = Playlist::new();
+        for title in ["Alpha", "beta", "Bravo", "Charlie"] {
+            let mut track = LibraryItem::new(PathBuf::from(title), LibraryPathId::new(0));
+            track.set_title(Some(title));
+            playlist.add(track);
+        }
+
+        assert_eq!(playlist.find_by_prefix("b", 0, |_| true), Some(1));
+        assert_eq!(playlist.find_by_prefix("b", 2, |_| true), Some(2));
+        assert_eq!(playlist.find_by_prefix("b", 3, |_| true), Some(1));
+        assert_eq!(playlist.find_by_prefix("BR", 0, |_| true), Some(2));
+        assert_eq!(playlist.find_by_prefix("x", 0, |_| true), None);
+        assert_eq!(
+            playlist.find_by_prefix("b", 0, |track| track.title().as_deref() != Some("beta")),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn filters_combine_across_columns() {
+        let mut track = LibraryItem::new(PathBuf::from("song.mp3"), LibraryPathId::new(0));
+        track.set_title(Some("Blue Monday"));
+        track.set_artist(Some("New Order"));
+        track.set_added_at(Some(0));
+
+        let filters = |title: &str, artist: &str, added: &str| {
+            let mut filters = vec![String::new(); PlaylistColumn::ALL.len()];
+            filters[0] = title.to_string();
+            filters[1] = artist.to_string();
+            filters[PlaylistColumn::Added.index()] = added.to_string();
+            filters
+        };
+
+        assert!(matches_filters(&track, &filters("", "", "")));
+        assert!(matches_filters(
+            &track,
+            &filters("monday", "order ", "1970")
+        ));
+        assert!(!matches_filters(&track, &filters("monday", "joy", "")));
+        // Tracks without a value never match a filter on that column
+        track.set_added_at(None);
+        assert!(!matches_filters(&track, &filters("", "", "1970")));
+    }
+
     // #[test]
     // fn select_track() {
     //     let track1 = LibraryItem::new(PathBuf::from(r"C:\music\song1.mp3"));
@@ -0,0 +1,181 @@
+use super::playlist::Playlist;
+use super::{tf, App};
+
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+
+/// Ctrl+J (⌘J on macOS) opens the playlist that's playing.
+pub const JUMP_TO_PLAYING_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND, Key::J);
+
+impl App {
+    /// Copies a playlist into a new tab right after it.
+    pub fn duplicate_playlist(&mut self, idx: usize) {
+        let Some(source) = self.playlists.get(idx) else {
+            return;
+        };
+
+        let mut copy = source.clone();
+        copy.id = None;
+        copy.virtual_kind = None;
+        copy.selected_indices.clear();
+        copy.view = Default::default();
+        copy.set_name(tf(
+            "playlist_copy_name",
+            &[&source.get_name().unwrap_or_default()],
+        ));
+
+        let copy_idx = idx + 1;
+        self.playlists.insert(copy_idx, copy);
+        self.shift_playlist_indices(copy_idx, true);
+        self.current_playlist_idx = Some(copy_idx);
+        self.persist_playlist(copy_idx);
+    }
+
+    /// Adds the tracks of `source` that `target` doesn't have yet to the end of `target`.
+    pub fn append_playlist(&mut self, source: usize, target: usize) {
+        if source == target
+            || source >= self.playlists.len()
+            || self.playlists.get(target).is_none_or(|p| p.is_virtual())
+        {
+            return;
+        }
+
+        let tracks = self.playlists[source].tracks.clone();
+        let added = self.playlists[target].append_missing(&tracks);
+        tracing::info!(
+            "Appended {} of {} tracks to playlist {}",
+            added,
+            tracks.len(),
+            target
+        );
+
+        self.persist_playlist(target);
+    }
+
+    /// Appends `source` to `target`, then removes `source`.
+    pub fn merge_playlist(&mut self, source: usize, target: usize) {
+        if source == target
+            || self.playlists.get(source).is_none_or(|p| p.is_virtual())
+            || self.playlists.get(target).is_none_or(|p| p.is_virtual())
+        {
+            return;
+        }
+
+        self.append_playlist(source, target);
+        let target = if target > source { target - 1 } else { target };
+        self.remove_playlist(source);
+        self.current_playlist_idx = Some(target);
+    }
+
+    /// Removes a playlist from the tabs and the database.
+    pub fn remove_playlist(&mut self, idx: usize) {
+        if idx >= self.playlists.len() {
+            return;
+        }
+
+        let playlist = self.playlists.remove(idx);
+        if let (Some(id), Some(db)) = (playlist.id, &self.database) {
+            if let Err(e) = Playlist::delete_from_db(&db.connection(), id) {
+                tracing::error!("Failed to delete playlist from database: {}", e);
+            }
+        }
+
+        // Removing the selected tab selects the one before it
+        let current = self.current_playlist_idx;
+        self.shift_playlist_indices(idx, false);
+        if current == Some(idx) {
+            self.current_playlist_idx = (!self.playlists.is_empty()).then(|| idx.saturating_sub(1));
+        }
+    }
+
+    // Stores the playlist right away, so a new playlist gets its database id
+    pub fn persist_playlist(&mut self, idx: usize) {
+        let Some(db) = &self.database else {
+            return;
+        };
+
+        // The tracks must be saved in the library before the playlist can refer to them
+        db.wait_for_writes();
+        match self.playlists[idx].save_to_db(&db.connection()) {
+            Ok(Some(id)) => self.playlists[idx].id = Some(id),
+            Ok(None) => (),
+            Err(e) => tracing::error!("Failed to save playlist to database: {}", e),
+        }
+    }
+
+    /// Keeps the selected and playing tabs in place when a tab is inserted or
+    /// removed at `idx`.
+    pub fn shift_playlist_indices(&mut self, idx: usize, inserted: bool) {
+        let shift = |current: Option<usize>| match current {
+            Some(current) if inserted && current >= idx => Some(current + 1),
+            Some(current) if !inserted && current == idx => None,
+            Some(current) if !inserted && current > idx => Some(current - 1),
+            current => current,
+        };
+
+        self.current_playlist_idx = shift(self.current_playlist_idx);
+        self.playing_playlist_idx = shift(self.playing_playlist_idx);
+        self.playlist_being_renamed = shift(self.playlist_being_renamed);
+
+        if self.current_playlist_idx.is_none() && !self.playlists.is_empty() {
+            self.current_playlist_idx = Some(0);
+        }
+    }
+
+    /// Scrolls the playlist table to the playing track, switching to the tab it plays
+    /// from when the open one doesn't have it.
+    pub fn locate_current_track(&mut self) {
+        let Some(track) = self
+            .player
+            .as_ref()
+            .and_then(|player| player.selected_track.as_ref())
+        else {
+            return;
+        };
+
+        let in_current = self
+            .current_playlist_idx
+            .and_then(|idx| self.playlists.get(idx))
+            .is_some_and(|playlist| playlist.get_pos(track).is_some());
+        if !in_current {
+            if let Some(idx) = self
+                .playing_playlist_idx
+                .filter(|&idx| idx < self.playlists.len())
+            {
+                self.current_playlist_idx = Some(idx);
+            }
+        }
+        self.locate_requested = true;
+    }
+
+    /// The playlist playing, while another one is open.
+    pub fn playing_playlist_elsewhere(&self) -> Option<usize> {
+        let has_track = self
+            .player
+            .as_ref()
+            .is_some_and(|player| player.selected_track.is_some());
+        self.playing_playlist_idx.filter(|&idx| {
+            has_track && idx < self.playlists.len() && self.current_playlist_idx != Some(idx)
+        })
+    }
+
+    /// Opens the playlist that's playing and scrolls to its track.
+    pub fn jump_to_playing_playlist(&mut self) {
+        let Some(idx) = self.playing_playlist_elsewhere() else {
+            return;
+        };
+        self.current_playlist_idx = Some(idx);
+        self.locate_requested = true;
+    }
+
+    /// Jumps to the playing playlist on its shortcut. Ignored while a text field has
+    /// focus.
+    pub fn handle_jump_key(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&JUMP_TO_PLAYING_SHORTCUT)) {
+            self.jump_to_playing_playlist();
+        }
+    }
+}
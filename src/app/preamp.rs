@@ -0,0 +1,73 @@
+//! Per-track volume offset, e.g. to lift a quiet live recording. It's applied on top of
+//! the master volume and ReplayGain and stored with the track in the library.
+
+use super::library::TrackKey;
+use super::App;
+
+pub const MIN_PREAMP_DB: f32 = -12.0;
+pub const MAX_PREAMP_DB: f32 = 12.0;
+
+/// Linear gain factor for an offset in dB.
+pub fn preamp_factor(preamp_db: f32) -> f32 {
+    10f32.powf(preamp_db.clamp(MIN_PREAMP_DB, MAX_PREAMP_DB) / 20.0)
+}
+
+impl App {
+    /// Changes the offset of every copy of the track and of the playing one. Only a
+    /// final value is `persist`ed, not every step of a drag.
+    pub fn set_track_preamp(&mut self, key: TrackKey, preamp_db: f32, persist: bool) {
+        let preamp_db = preamp_db.clamp(MIN_PREAMP_DB, MAX_PREAMP_DB);
+
+        self.library.update_item(key, |item| {
+            item.set_preamp_db(preamp_db);
+        });
+        for playlist in &mut self.playlists {
+            for track in playlist.tracks.iter_mut() {
+                if track.key() == key {
+                    track.set_preamp_db(preamp_db);
+                }
+            }
+        }
+
+        if let Some(player) = &mut self.player {
+            let is_selected = player
+                .selected_track
+                .as_ref()
+                .is_some_and(|track| track.key() == key);
+
+            if is_selected {
+                if let Some(track) = &mut player.selected_track {
+                    track.set_preamp_db(preamp_db);
+                }
+                player.refresh_track_gain();
+            }
+        }
+
+        if !persist {
+            return;
+        }
+        if let Some(db) = &self.database {
+            let conn = db.connection();
+            let conn_guard = conn.lock().unwrap();
+            match conn_guard.execute(
+                "UPDATE library_items SET preamp_db = ?1 WHERE key = ?2",
+                rusqlite::params![preamp_db, key.to_string()],
+            ) {
+                Ok(_) => tracing::info!("Stored pre-amp of {} dB for track {}", preamp_db, key),
+                Err(e) => tracing::error!("Failed to store pre-amp in database: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preamp_is_limited_to_its_range() {
+        assert_eq!(preamp_factor(0.0), 1.0);
+        assert!((preamp_factor(6.0) - 1.995).abs() < 0.001);
+        assert_eq!(preamp_factor(40.0), preamp_factor(MAX_PREAMP_DB));
+    }
+}
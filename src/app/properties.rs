@@ -0,0 +1,257 @@
+//! The properties dialog: every tag the library knows of a track, its file and stream,
+//! with the tags that can be written open for editing. Opened on a selection it steps
+//! through the selected tracks, saving the shown one before moving on.
+
+use eframe::egui;
+
+use super::library::{LibraryItem, TrackKey};
+use super::tag_writer::TagField;
+use super::App;
+
+/// Fields the dialog edits, in the order it shows them.
+pub const EDITABLE_FIELDS: [TagField; 7] = [
+    TagField::Title,
+    TagField::Artist,
+    TagField::Album,
+    TagField::Genre,
+    TagField::TrackNumber,
+    TagField::Year,
+    TagField::Lyrics,
+];
+
+pub struct TrackProperties {
+    /// Tracks to step through, in playlist order.
+    pub keys: Vec<TrackKey>,
+    pub position: usize,
+    /// The shown track's values as edited, in the order of `EDITABLE_FIELDS`.
+    pub values: Vec<String>,
+    // What the values were when the track was shown
+    original: Vec<String>,
+    /// The audio tracks of the shown file, one can be picked when there are several.
+    pub audio_tracks: Vec<String>,
+    /// The shown track's note as edited, kept in the database and not in the file.
+    pub note: String,
+    original_note: String,
+}
+
+impl TrackProperties {
+    pub fn key(&self) -> Option<TrackKey> {
+        self.keys.get(self.position).copied()
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.values != self.original || self.note != self.original_note
+    }
+
+    pub fn is_valid(&self) -> bool {
+        EDITABLE_FIELDS
+            .iter()
+            .zip(&self.values)
+            .all(|(field, value)| field.is_valid(value))
+    }
+
+    fn show(&mut self, track: Option<&LibraryItem>, note: &str) {
+        self.values = EDITABLE_FIELDS
+            .iter()
+            .map(|field| {
+                track
+                    .map(|track| field_value(track, *field))
+                    .unwrap_or_default()
+            })
+            .collect();
+        self.original = self.values.clone();
+        self.note = note.to_string();
+        self.original_note = self.note.clone();
+        self.audio_tracks = track
+            .map(|track| {
+                crate::media_source::audio_tracks(&track.path()).unwrap_or_else(|e| {
+                    tracing::warn!("Failed to list audio tracks: {}", e);
+                    Vec::new()
+                })
+            })
+            .unwrap_or_default();
+    }
+}
+
+/// The value of a field as the dialog edits it, empty when the track has none.
+pub fn field_value(track: &LibraryItem, field: TagField) -> String {
+    match field {
+        TagField::Title => track.title(),
+        TagField::Artist => track.artist(),
+        TagField::Album => track.album(),
+        TagField::Genre => track.genre(),
+        TagField::Lyrics => track.lyrics(),
+        TagField::TrackNumber => track.track_number().map(|number| number.to_string()),
+        TagField::Year => track.year().map(|year| year.to_string()),
+    }
+    .unwrap_or_default()
+}
+
+impl App {
+    /// Opens the properties of the track at `position` of `keys`.
+    pub fn open_properties(&mut self, keys: Vec<TrackKey>, position: usize) {
+        let mut properties = TrackProperties {
+            keys,
+            position,
+            values: Vec::new(),
+            original: Vec::new(),
+            audio_tracks: Vec::new(),
+            note: String::new(),
+            original_note: String::new(),
+        };
+        let key = properties.key();
+        properties.show(
+            key.and_then(|key| self.find_track(key)),
+            key.map_or("", |key| self.track_notes.get(key)),
+        );
+        self.properties = Some(properties);
+    }
+
+    /// The track the library or any playlist has under `key`.
+    pub fn find_track(&self, key: TrackKey) -> Option<&LibraryItem> {
+        self.library
+            .items()
+            .iter()
+            .chain(self.playlists.iter().flat_map(|playlist| &playlist.tracks))
+            .find(|track| track.key() == key)
+    }
+
+    /// Writes the fields changed in the dialog, values that can't be written are skipped.
+    pub fn save_properties(&mut self, ctx: &egui::Context) {
+        let Some(track) = self
+            .properties
+            .as_ref()
+            .and_then(|properties| properties.key())
+            .and_then(|key| self.find_track(key))
+            .cloned()
+        else {
+            return;
+        };
+        let Some(properties) = &mut self.properties else {
+            return;
+        };
+
+        let edits: Vec<(TagField, String)> = EDITABLE_FIELDS
+            .iter()
+            .zip(&properties.values)
+            .zip(&properties.original)
+            .filter(|((field, value), original)| value != original && field.is_valid(value))
+            .map(|((field, value), _)| (*field, value.trim().to_string()))
+            .collect();
+        properties.original = properties.values.clone();
+        let note = (properties.note != properties.original_note).then(|| {
+            properties.original_note = properties.note.clone();
+            properties.note.clone()
+        });
+
+        for (field, value) in edits {
+            self.queue_metadata_edit(ctx, &track, field.name(), &value);
+        }
+        if let Some(note) = note {
+            self.set_track_note(track.key(), &note);
+        }
+    }
+
+    /// Saves the shown track and moves `step` tracks on, -1 going back.
+    pub fn step_properties(&mut self, ctx: &egui::Context, step: isize) {
+        self.save_properties(ctx);
+        let Some(properties) = &mut self.properties else {
+            return;
+        };
+        let Some(position) = properties
+            .position
+            .checked_add_signed(step)
+            .filter(|position| *position < properties.keys.len())
+        else {
+            return;
+        };
+        properties.position = position;
+        self.refresh_properties();
+    }
+
+    /// Plays `audio_track` of the file from now on, switching over right away if it's
+    /// the current track.
+    pub fn set_audio_track(&mut self, key: TrackKey, audio_track: Option<u32>) {
+        self.library.update_item(key, |item| {
+            item.set_audio_track(audio_track);
+        });
+        for playlist in &mut self.playlists {
+            for track in playlist.tracks.iter_mut() {
+                if track.key() == key {
+                    track.set_audio_track(audio_track);
+                }
+            }
+        }
+
+        if let Some(player) = &mut self.player {
+            if player
+                .selected_track
+                .as_ref()
+                .is_some_and(|track| track.key() == key)
+            {
+                player.switch_audio_track(audio_track);
+            }
+        }
+
+        if let Some(db) = &self.database {
+            let conn = db.connection();
+            let conn_guard = conn.lock().unwrap();
+            match conn_guard.execute(
+                "UPDATE library_items SET audio_track = ?1 WHERE key = ?2",
+                rusqlite::params![audio_track, key.to_string()],
+            ) {
+                Ok(_) => tracing::info!("Playing audio track {:?} of {}", audio_track, key),
+                Err(e) => tracing::error!("Failed to store audio track in database: {}", e),
+            }
+        }
+    }
+
+    /// Shows the track's values again, e.g. once its edits were written. Values being
+    /// edited are kept.
+    pub fn refresh_properties(&mut self) {
+        let track = self
+            .properties
+            .as_ref()
+            .filter(|properties| !properties.is_changed())
+            .and_then(|properties| properties.key())
+            .and_then(|key| self.find_track(key))
+            .cloned();
+        if let (Some(properties), Some(track)) = (&mut self.properties, track) {
+            properties.show(Some(&track), self.track_notes.get(track.key()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+    use std::path::PathBuf;
+
+    #[test]
+    fn edits_are_checked_against_the_shown_track() {
+        let mut track = LibraryItem::new(PathBuf::from("song.mp3"), LibraryPathId::new(0));
+        track.set_title(Some("Song"));
+        track.set_track_number(Some(3));
+
+        let mut properties = TrackProperties {
+            keys: vec![track.key()],
+            position: 0,
+            values: Vec::new(),
+            original: Vec::new(),
+            audio_tracks: Vec::new(),
+            note: String::new(),
+            original_note: String::new(),
+        };
+        properties.show(Some(&track), "");
+        assert_eq!(properties.values[0], "Song");
+        assert_eq!(properties.values[4], "3");
+        assert!(!properties.is_changed());
+
+        properties.values[5] = "19".to_string();
+        assert!(properties.is_changed());
+        assert!(!properties.is_valid());
+        properties.values[5] = "1999".to_string();
+        assert!(properties.is_valid());
+    }
+}
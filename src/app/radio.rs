@@ -0,0 +1,260 @@
+//! Temporary queues grown from one track: ones like it, more by its artist, its album
+//! and albums like it, or its genre. Each track is drawn with a chance by how close it
+//! is to the seed in artist, genre and year and by how much it's played and liked, so
+//! every radio comes out a little different.
+
+use rand::Rng;
+
+use super::library::LibraryItem;
+use super::play_stats::skip_weight;
+use super::player::TrackState;
+use super::playlist::Playlist;
+use super::{t, tf, App};
+
+/// Tracks a radio queue gets after its seed.
+pub const RADIO_LENGTH: usize = 50;
+
+// Years apart at which a track no longer counts as from the same time
+const YEAR_SPAN: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadioKind {
+    Similar,
+    Artist,
+    Album,
+    Genre,
+}
+
+impl RadioKind {
+    pub fn all() -> [RadioKind; 4] {
+        [
+            RadioKind::Similar,
+            RadioKind::Artist,
+            RadioKind::Album,
+            RadioKind::Genre,
+        ]
+    }
+
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            RadioKind::Similar => "play_similar",
+            RadioKind::Artist => "radio_artist",
+            RadioKind::Album => "radio_album",
+            RadioKind::Genre => "radio_genre",
+        }
+    }
+
+    /// Whether the seed has the tag this radio is built around.
+    pub fn fits(&self, seed: &LibraryItem) -> bool {
+        match self {
+            RadioKind::Similar => seed.artist().is_some() || seed.genre().is_some(),
+            RadioKind::Artist => seed.artist().is_some(),
+            RadioKind::Album => seed.album().is_some(),
+            RadioKind::Genre => seed.genre().is_some(),
+        }
+    }
+
+    fn queue_name(&self, seed: &LibraryItem) -> String {
+        match self {
+            RadioKind::Similar => tf(
+                "radio_similar_name",
+                &[&seed.title().unwrap_or_else(|| t("unknown_title"))],
+            ),
+            RadioKind::Artist => tf("radio_artist_name", &[&seed.artist().unwrap_or_default()]),
+            RadioKind::Album => tf("radio_album_name", &[&seed.album().unwrap_or_default()]),
+            RadioKind::Genre => tf("radio_genre_name", &[&seed.genre().unwrap_or_default()]),
+        }
+    }
+}
+
+fn same_text(a: Option<String>, b: Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.trim().eq_ignore_ascii_case(b.trim()),
+        _ => false,
+    }
+}
+
+fn shares_artist(seed: &LibraryItem, candidate: &LibraryItem) -> bool {
+    same_text(seed.artist(), candidate.artist())
+        || seed.artists().iter().any(|artist| {
+            candidate
+                .artists()
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(artist))
+        })
+}
+
+/// How well `candidate` goes on a radio of `seed`, 0 when it doesn't belong on it.
+pub fn affinity(kind: RadioKind, seed: &LibraryItem, candidate: &LibraryItem) -> f32 {
+    let same_artist = shares_artist(seed, candidate);
+    let same_genre = same_text(seed.genre(), candidate.genre());
+    let same_album = seed.album_key().is_some() && seed.album_key() == candidate.album_key();
+    // Unknown years count as somewhat close
+    let same_time = match (seed.year(), candidate.year()) {
+        (Some(a), Some(b)) => (1.0 - (a - b).abs() as f32 / YEAR_SPAN).max(0.0),
+        _ => 0.3,
+    };
+
+    let closeness = match kind {
+        RadioKind::Artist if same_artist => 1.0 + same_time,
+        RadioKind::Genre if same_genre => 1.0 + same_time,
+        RadioKind::Album if same_album => 4.0,
+        RadioKind::Album if same_genre => 1.0 + same_time + same_artist as u8 as f32,
+        RadioKind::Similar if same_artist || same_genre => {
+            2.0 * same_genre as u8 as f32 + 1.5 * same_artist as u8 as f32 + same_time
+        }
+        _ => return 0.0,
+    };
+
+    let popularity =
+        1.0 + (candidate.play_count() as f32 + 1.0).ln() * 0.25 + candidate.rating() as f32 * 0.1;
+    closeness * popularity
+}
+
+/// Draws up to `count` tracks for a radio of `seed`, leaving the seed out. Closer
+/// tracks are likelier to be drawn, and to come early, often skipped ones less so when
+/// `downrank_skipped` is on.
+pub fn pick(
+    kind: RadioKind,
+    seed: &LibraryItem,
+    items: &[LibraryItem],
+    count: usize,
+    downrank_skipped: bool,
+    rng: &mut impl Rng,
+) -> Vec<LibraryItem> {
+    let mut candidates: Vec<(f32, &LibraryItem)> = items
+        .iter()
+        .filter(|item| item.key() != seed.key())
+        .map(|item| {
+            let weight = affinity(kind, seed, item);
+            if downrank_skipped {
+                (weight * skip_weight(item), item)
+            } else {
+                (weight, item)
+            }
+        })
+        .filter(|(weight, _)| *weight > 0.0)
+        .collect();
+
+    let mut picked = Vec::new();
+    while picked.len() < count && !candidates.is_empty() {
+        let total: f32 = candidates.iter().map(|(weight, _)| weight).sum();
+        let mut target = rng.gen::<f32>() * total;
+        let idx = candidates
+            .iter()
+            .position(|(weight, _)| {
+                if target < *weight {
+                    return true;
+                }
+                target -= weight;
+                false
+            })
+            .unwrap_or(candidates.len() - 1);
+        picked.push(candidates.swap_remove(idx).1.clone());
+    }
+    picked
+}
+
+impl App {
+    /// Replaces the queue with a radio grown from `seed` and plays it, starting with the
+    /// seed unless that's already playing.
+    pub fn play_radio(&mut self, seed: &LibraryItem, kind: RadioKind) {
+        let items: Vec<LibraryItem> = self
+            .library
+            .items()
+            .iter()
+            .filter(|item| !(self.clean_filter && item.explicit()))
+            .cloned()
+            .collect();
+        let picked = pick(
+            kind,
+            seed,
+            &items,
+            RADIO_LENGTH,
+            self.downrank_skipped,
+            &mut rand::thread_rng(),
+        );
+        if picked.is_empty() {
+            tracing::info!("Nothing in the library fits a {:?} radio", kind);
+            return;
+        }
+
+        let mut queue = Playlist::new();
+        queue.set_name(kind.queue_name(seed));
+        queue.add(seed.clone());
+        for track in picked {
+            queue.add(track);
+        }
+
+        if let Some(player) = &mut self.player {
+            let seed_playing = matches!(player.track_state, TrackState::Playing)
+                && player
+                    .selected_track
+                    .as_ref()
+                    .is_some_and(|track| track.key() == seed.key());
+            if !seed_playing {
+                player.select_track(Some(seed.clone()));
+                player.play();
+            }
+        }
+
+        tracing::info!("Playing {:?} radio of {} tracks", kind, queue.tracks.len());
+        self.queue = Some(queue);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+    use rand::SeedableRng;
+    use std::path::PathBuf;
+
+    fn track(artist: &str, genre: &str, year: i32) -> LibraryItem {
+        let path = PathBuf::from(format!("{artist}-{genre}-{year}.mp3"));
+        let mut track = LibraryItem::new(path, LibraryPathId::new(0));
+        track.set_artist(Some(artist));
+        track.set_genre(Some(genre));
+        track.set_year(Some(year));
+        track
+    }
+
+    #[test]
+    fn closer_tracks_have_more_affinity() {
+        let seed = track("A", "Jazz", 1960);
+        let near = track("B", "jazz", 1962);
+        let far = track("C", "Jazz", 1995);
+        let other = track("D", "Metal", 1960);
+
+        let similar = |candidate| affinity(RadioKind::Similar, &seed, candidate);
+        assert!(similar(&near) > similar(&far));
+        assert_eq!(similar(&other), 0.0);
+        assert_eq!(affinity(RadioKind::Artist, &seed, &near), 0.0);
+
+        let mut played = near.clone();
+        played.set_play_count(20);
+        assert!(similar(&played) > similar(&near));
+    }
+
+    #[test]
+    fn radios_leave_out_the_seed_and_unrelated_tracks() {
+        let seed = track("A", "Jazz", 1960);
+        let items = vec![
+            seed.clone(),
+            track("A", "Blues", 1970),
+            track("B", "Jazz", 1961),
+            track("C", "Metal", 1990),
+        ];
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let picked = pick(RadioKind::Similar, &seed, &items, 10, false, &mut rng);
+        assert_eq!(picked.len(), 2);
+        assert!(picked.iter().all(|track| track.key() != seed.key()));
+        assert!(picked
+            .iter()
+            .all(|track| track.genre().as_deref() != Some("Metal")));
+
+        let picked = pick(RadioKind::Artist, &seed, &items, 10, false, &mut rng);
+        assert_eq!(picked.len(), 1);
+    }
+}
@@ -0,0 +1,107 @@
+use super::library::LibraryItem;
+use super::playlist::{Playlist, VirtualPlaylist};
+use super::{t, App};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_RECENTLY_ADDED_DAYS: u32 = 30;
+
+/// Tracks imported within the last `days`, newest first.
+pub fn recently_added(items: &[LibraryItem], now: i64, days: u32) -> Vec<LibraryItem> {
+    let since = now - days as i64 * 86400;
+
+    let mut tracks: Vec<LibraryItem> = items
+        .iter()
+        .filter(|item| item.added_at().is_some_and(|added_at| added_at >= since))
+        .cloned()
+        .collect();
+
+    // Tracks of one import share a timestamp, keep those in path order
+    tracks.sort_by(|a, b| {
+        b.added_at()
+            .cmp(&a.added_at())
+            .then_with(|| a.path().cmp(&b.path()))
+    });
+    tracks
+}
+
+impl App {
+    fn recently_added_idx(&self) -> Option<usize> {
+        self.playlists
+            .iter()
+            .position(|p| p.virtual_kind == Some(VirtualPlaylist::RecentlyAdded))
+    }
+
+    /// Rebuilds the Recently Added playlist, which stays pinned as the first tab.
+    /// Setting the period to 0 days hides it.
+    pub fn refresh_recently_added(&mut self) {
+        let existing = self.recently_added_idx();
+
+        if self.recently_added_days == 0 {
+            if let Some(idx) = existing {
+                self.playlists.remove(idx);
+                self.shift_playlist_indices(idx, false);
+            }
+            return;
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        let tracks = recently_added(self.library.items(), now, self.recently_added_days);
+
+        let idx = match existing {
+            Some(idx) => idx,
+            None => {
+                self.playlists
+                    .insert(0, Playlist::new_virtual(VirtualPlaylist::RecentlyAdded));
+                self.shift_playlist_indices(0, true);
+                0
+            }
+        };
+
+        let playlist = &mut self.playlists[idx];
+        playlist.set_name(t("recently_added"));
+        if playlist.tracks != tracks {
+            playlist.tracks = tracks;
+            playlist.selected_indices.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+    use std::path::PathBuf;
+
+    fn item(path: &str, added_at: Option<i64>) -> LibraryItem {
+        LibraryItem::new(PathBuf::from(path), LibraryPathId::new(0)).set_added_at(added_at)
+    }
+
+    #[test]
+    fn recently_added_keeps_tracks_within_period_newest_first() {
+        let now = 100 * 86400;
+        let items = vec![
+            item("old.mp3", Some(now - 31 * 86400)),
+            item("b.mp3", Some(now - 86400)),
+            item("unknown.mp3", None),
+            item("a.mp3", Some(now - 86400)),
+            item("new.mp3", Some(now)),
+        ];
+
+        let paths: Vec<PathBuf> = recently_added(&items, now, 30)
+            .iter()
+            .map(|item| item.path())
+            .collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("new.mp3"),
+                PathBuf::from("a.mp3"),
+                PathBuf::from("b.mp3"),
+            ]
+        );
+    }
+}
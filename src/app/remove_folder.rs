@@ -0,0 +1,184 @@
+//! Removing a library folder asks first and can be taken back until the app closes.
+//! The database rows go right away, the album art files only once the removal is final.
+
+use super::library::{Library, LibraryItem, LibraryPath, LibraryPathId, TrackKey};
+use super::App;
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// What removing a folder took away, kept to put it back.
+pub struct RemovedFolder {
+    path: LibraryPath,
+    items: Vec<LibraryItem>,
+    // Stored playlists by id, with the removed tracks and where they were
+    playlist_tracks: Vec<(i64, Vec<(usize, LibraryItem)>)>,
+    // Deleted along with the tracks in the database
+    bookmarks: Vec<(TrackKey, String, i64)>,
+}
+
+impl RemovedFolder {
+    pub fn name(&self) -> &str {
+        self.path.display_name()
+    }
+
+    pub fn items(&self) -> &[LibraryItem] {
+        &self.items
+    }
+}
+
+impl App {
+    /// Takes the folder and its tracks out of the library and the playlists.
+    pub fn remove_library_path(&mut self, path_id: LibraryPathId) {
+        let Some((path, items)) = self.library.take_path(path_id) else {
+            return;
+        };
+        let keys: HashSet<TrackKey> = items.iter().map(|item| item.key()).collect();
+
+        let mut playlist_tracks = Vec::new();
+        for playlist in &mut self.playlists {
+            let mut removed = Vec::new();
+            let mut idx = 0;
+            while idx < playlist.tracks.len() {
+                if keys.contains(&playlist.tracks[idx].key()) {
+                    // Where it was before the tracks ahead of it were removed
+                    removed.push((idx + removed.len(), playlist.tracks[idx].clone()));
+                    playlist.remove(idx);
+                } else {
+                    idx += 1;
+                }
+            }
+            if let (Some(id), false) = (playlist.id, removed.is_empty()) {
+                playlist_tracks.push((id, removed));
+            }
+        }
+
+        let mut bookmarks = Vec::new();
+        if let Some(db) = &self.database {
+            bookmarks = Self::bookmarks_of(&db.connection(), &keys);
+            db.write_in_background(move |conn| {
+                if let Err(e) = Library::delete_path_from_db(conn, path_id) {
+                    tracing::error!("Failed to remove library path from database: {}", e);
+                }
+            });
+        }
+
+        tracing::info!("Removed {:?} with {} tracks", path.path(), items.len());
+        self.removed_folders.push(RemovedFolder {
+            path,
+            items,
+            playlist_tracks,
+            bookmarks,
+        });
+        self.refresh_recently_added();
+        self.refresh_label_playlists();
+        self.refresh_favorites();
+        self.reload_album_stats();
+    }
+
+    /// Puts back the folder removed last, with its tracks in the playlists they were in.
+    pub fn undo_remove_library_path(&mut self) {
+        let Some(removed) = self.removed_folders.pop() else {
+            return;
+        };
+
+        for (id, tracks) in removed.playlist_tracks {
+            if let Some(playlist) = self.playlists.iter_mut().find(|p| p.id == Some(id)) {
+                for (position, track) in tracks {
+                    let position = position.min(playlist.tracks.len());
+                    playlist.tracks.insert(position, track);
+                }
+                playlist.selected_indices.clear();
+            }
+        }
+        self.library.restore_path(removed.path, removed.items);
+        self.refresh_recently_added();
+        self.refresh_label_playlists();
+        self.refresh_favorites();
+        self.reload_album_stats();
+
+        // The bookmarks refer to the tracks, they're written after them
+        self.save_state();
+        if let Some(db) = &self.database {
+            let bookmarks = removed.bookmarks;
+            db.write_in_background(move |conn| {
+                let conn = conn.lock().unwrap();
+                for (key, name, position_ms) in &bookmarks {
+                    if let Err(e) = conn.execute(
+                        "INSERT INTO bookmarks (library_item_id, name, position_ms)
+                         VALUES (?1, ?2, ?3)",
+                        rusqlite::params![key.to_string(), name, position_ms],
+                    ) {
+                        tracing::error!("Failed to restore bookmark: {}", e);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Once the removals can't be undone anymore, deletes the album art only the
+    /// removed tracks used.
+    pub fn delete_art_of_removed_folders(&mut self) {
+        let referenced: HashSet<PathBuf> = self
+            .library
+            .items()
+            .iter()
+            .chain(self.playlists.iter().flat_map(|playlist| &playlist.tracks))
+            .flat_map(|item| item.pictures())
+            .map(|picture| picture.file_path.clone())
+            .collect();
+        let unused: HashSet<PathBuf> = self
+            .removed_folders
+            .drain(..)
+            .flat_map(|removed| removed.items)
+            .flat_map(|item| item.pictures().to_vec())
+            .map(|picture| picture.file_path)
+            .filter(|path| !referenced.contains(path))
+            .collect();
+
+        for path in &unused {
+            if let Err(e) = std::fs::remove_file(path) {
+                tracing::warn!("Failed to remove album art {:?}: {}", path, e);
+            }
+        }
+        if !unused.is_empty() {
+            tracing::info!(
+                "Removed {} album art files of removed folders",
+                unused.len()
+            );
+        }
+    }
+
+    fn bookmarks_of(
+        conn: &Arc<Mutex<Connection>>,
+        keys: &HashSet<TrackKey>,
+    ) -> Vec<(TrackKey, String, i64)> {
+        let conn = conn.lock().unwrap();
+        let rows = conn
+            .prepare("SELECT library_item_id, name, position_ms FROM bookmarks")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            });
+        match rows {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|(key, name, position_ms)| {
+                    let key = key.parse::<TrackKey>().ok()?;
+                    keys.contains(&key).then_some((key, name, position_ms))
+                })
+                .collect(),
+            Err(e) => {
+                tracing::error!("Failed to read bookmarks: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
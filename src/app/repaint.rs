@@ -0,0 +1,35 @@
+//! When the window redraws on its own. Egui only repaints on input, so playback asks
+//! for frames while it runs and the UI goes fully idle when paused.
+
+use super::player::TrackState;
+use super::App;
+use eframe::egui;
+use std::time::Duration;
+
+// Frame interval for the spinning reels and scrolling titles, about 30 fps
+pub const ANIMATION_INTERVAL: Duration = Duration::from_millis(33);
+
+// With animations off only the position and time move, which needs far fewer frames
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+impl App {
+    pub fn is_playing(&self) -> bool {
+        self.player
+            .as_ref()
+            .is_some_and(|player| matches!(player.track_state, TrackState::Playing))
+    }
+
+    /// Whether the reels spin and long titles scroll right now.
+    pub fn animations_running(&self) -> bool {
+        self.is_playing() && !self.battery_saver
+    }
+
+    /// Asks for the next frame playback needs to show, nothing while paused.
+    pub fn request_playback_repaint(&self, ctx: &egui::Context) {
+        if self.animations_running() {
+            ctx.request_repaint_after(ANIMATION_INTERVAL);
+        } else if self.is_playing() {
+            ctx.request_repaint_after(PROGRESS_INTERVAL);
+        }
+    }
+}
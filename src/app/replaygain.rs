@@ -0,0 +1,243 @@
+use super::library::{LibraryItem, ReplayGain, TrackKey};
+use super::{App, LibraryCommand};
+use crate::loudness::{self, LoudnessScan};
+
+use id3::frame::ExtendedText;
+use id3::{Tag, TagLike};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+const TRACK_GAIN: &str = "REPLAYGAIN_TRACK_GAIN";
+const TRACK_PEAK: &str = "REPLAYGAIN_TRACK_PEAK";
+const ALBUM_GAIN: &str = "REPLAYGAIN_ALBUM_GAIN";
+const ALBUM_PEAK: &str = "REPLAYGAIN_ALBUM_PEAK";
+
+/// Reads ReplayGain values stored in TXXX frames, e.g. "REPLAYGAIN_TRACK_GAIN" = "-6.54 dB".
+pub fn read_tags(tag: &Tag) -> ReplayGain {
+    from_pairs(
+        tag.extended_texts()
+            .map(|text| (text.description.as_str(), text.value.as_str())),
+    )
+}
+
+/// Picks the ReplayGain values out of (key, value) tag items of any format.
+pub fn from_pairs<'a>(pairs: impl Iterator<Item = (&'a str, &'a str)>) -> ReplayGain {
+    let mut replay_gain = ReplayGain::default();
+
+    for (key, value) in pairs {
+        let value = value
+            .trim()
+            .trim_end_matches("dB")
+            .trim_end_matches("db")
+            .trim()
+            .parse::<f32>()
+            .ok();
+
+        match key.to_uppercase().as_str() {
+            TRACK_GAIN => replay_gain.track_gain = value,
+            TRACK_PEAK => replay_gain.track_peak = value,
+            ALBUM_GAIN => replay_gain.album_gain = value,
+            ALBUM_PEAK => replay_gain.album_peak = value,
+            _ => (),
+        }
+    }
+
+    replay_gain
+}
+
+fn write_tags(path: &Path, replay_gain: &ReplayGain) -> Result<(), id3::Error> {
+    let mut tag = match Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(err) if matches!(err.kind, id3::ErrorKind::NoTag) => Tag::new(),
+        Err(err) => return Err(err),
+    };
+
+    let values = [
+        (
+            TRACK_GAIN,
+            replay_gain.track_gain.map(|g| format!("{:.2} dB", g)),
+        ),
+        (
+            TRACK_PEAK,
+            replay_gain.track_peak.map(|p| format!("{:.6}", p)),
+        ),
+        (
+            ALBUM_GAIN,
+            replay_gain.album_gain.map(|g| format!("{:.2} dB", g)),
+        ),
+        (
+            ALBUM_PEAK,
+            replay_gain.album_peak.map(|p| format!("{:.6}", p)),
+        ),
+    ];
+
+    for (description, value) in values {
+        if let Some(value) = value {
+            tag.add_frame(ExtendedText {
+                description: description.to_string(),
+                value,
+            });
+        }
+    }
+
+    tag.write_to_path(path, id3::Version::Id3v24)
+}
+
+impl App {
+    /// Spawns a background thread measuring the loudness of every item without
+    /// ReplayGain values. Results are stored in the database, sent back to the UI
+    /// and, if enabled, written to the files' tags.
+    pub fn scan_replay_gain(&self, items: Vec<LibraryItem>) {
+        let items: Vec<LibraryItem> = items
+            .into_iter()
+            .filter(|item| item.replay_gain().is_missing())
+            .collect();
+
+        if items.is_empty() {
+            tracing::info!("No tracks need a ReplayGain scan");
+            return;
+        }
+
+        if self.replay_gain_scanning.swap(true, Ordering::SeqCst) {
+            tracing::info!("ReplayGain scan already running");
+            return;
+        }
+
+        let lib_cmd_tx = self.library_cmd_tx.as_ref().unwrap().clone();
+        let database = self.database.clone();
+        let write_tags_enabled = self.replay_gain_write_tags && !self.read_only_library;
+        let scanning = self.replay_gain_scanning.clone();
+
+        std::thread::spawn(move || {
+            tracing::info!("Scanning loudness of {} tracks", items.len());
+
+            let scans: Vec<(LibraryItem, LoudnessScan)> = items
+                .into_par_iter()
+                .filter_map(|item| match loudness::scan_file(&item.path()) {
+                    Ok(scan) => Some((item, scan)),
+                    Err(err) => {
+                        tracing::warn!("Couldn't measure loudness of {:?}: {}", item.path(), err);
+                        None
+                    }
+                })
+                .collect();
+
+            // Album gain is measured over all scanned tracks sharing the same folder and album
+            let mut albums: HashMap<(super::LibraryPathId, (String, String)), Vec<usize>> =
+                HashMap::new();
+            for (idx, (item, _)) in scans.iter().enumerate() {
+                if let Some(album_key) = item.album_key() {
+                    albums
+                        .entry((item.library_id(), album_key))
+                        .or_default()
+                        .push(idx);
+                }
+            }
+
+            let mut album_values: HashMap<usize, (Option<f32>, f32)> = HashMap::new();
+            for indices in albums.values() {
+                let album_scans: Vec<LoudnessScan> =
+                    indices.iter().map(|&idx| scans[idx].1.clone()).collect();
+                let (album_loudness, album_peak) = loudness::album_loudness(&album_scans);
+                let album_gain = album_loudness.map(|l| (loudness::REFERENCE_LUFS - l) as f32);
+                for &idx in indices {
+                    album_values.insert(idx, (album_gain, album_peak));
+                }
+            }
+
+            let results: Vec<(LibraryItem, ReplayGain)> = scans
+                .into_iter()
+                .enumerate()
+                .map(|(idx, (item, scan))| {
+                    let (album_gain, album_peak) = match album_values.get(&idx) {
+                        Some(&(gain, peak)) => (gain, Some(peak)),
+                        None => (None, None),
+                    };
+                    let replay_gain = ReplayGain {
+                        track_gain: scan.gain_db().map(|g| g as f32),
+                        track_peak: Some(scan.peak),
+                        album_gain,
+                        album_peak,
+                    };
+                    (item, replay_gain)
+                })
+                .collect();
+
+            if let Some(db) = &database {
+                let conn = db.connection();
+                let mut conn_guard = conn.lock().unwrap();
+                let update = conn_guard.transaction().and_then(|tx| {
+                    for (item, replay_gain) in &results {
+                        tx.execute(
+                            "UPDATE library_items SET track_gain = ?1, track_peak = ?2,
+                             album_gain = ?3, album_peak = ?4 WHERE key = ?5",
+                            rusqlite::params![
+                                replay_gain.track_gain,
+                                replay_gain.track_peak,
+                                replay_gain.album_gain,
+                                replay_gain.album_peak,
+                                item.key().to_string(),
+                            ],
+                        )?;
+                    }
+                    tx.commit()
+                });
+
+                if let Err(e) = update {
+                    tracing::error!("Failed to store ReplayGain values in database: {}", e);
+                }
+            }
+
+            for (item, replay_gain) in results {
+                if write_tags_enabled && !replay_gain.is_missing() {
+                    if let Err(e) = write_tags(&item.path(), &replay_gain) {
+                        tracing::error!(
+                            "Failed to write ReplayGain tags to {:?}: {}",
+                            item.path(),
+                            e
+                        );
+                    }
+                }
+
+                if lib_cmd_tx
+                    .send(LibraryCommand::UpdateReplayGain(item.key(), replay_gain))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+
+            scanning.store(false, Ordering::SeqCst);
+            tracing::info!("ReplayGain scan finished");
+        });
+    }
+
+    /// Applies scanned ReplayGain values to every copy of the track.
+    pub fn apply_replay_gain(&mut self, key: TrackKey, replay_gain: ReplayGain) {
+        self.library.set_replay_gain(key, replay_gain);
+
+        for playlist in &mut self.playlists {
+            for track in playlist.tracks.iter_mut() {
+                if track.key() == key {
+                    track.set_replay_gain(replay_gain);
+                }
+            }
+        }
+
+        if let Some(player) = &mut self.player {
+            let is_selected = player
+                .selected_track
+                .as_ref()
+                .is_some_and(|track| track.key() == key);
+
+            if is_selected {
+                if let Some(track) = &mut player.selected_track {
+                    track.set_replay_gain(replay_gain);
+                }
+                player.refresh_track_gain();
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! A summary of the last week's or month's listening from the play history: time spent,
+//! the most played artists, tracks and genres and the longest run of days with music.
+//! It can be copied as text or saved as a picture of its window.
+
+use super::history::{self, HistoryEntry, HistoryRange};
+use super::library::LibraryItem;
+use super::{t, tf, tp, App};
+
+use eframe::egui::ColorImage;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Entries in each top list
+const TOP_COUNT: usize = 5;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportPeriod {
+    #[default]
+    Week,
+    Month,
+}
+
+impl ReportPeriod {
+    fn range(self) -> HistoryRange {
+        match self {
+            ReportPeriod::Week => HistoryRange::Week,
+            ReportPeriod::Month => HistoryRange::Month,
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            ReportPeriod::Week => t("report_week"),
+            ReportPeriod::Month => t("report_month"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ListeningReport {
+    pub plays: usize,
+    pub listened_ms: i64,
+    // Most plays first, with their play count
+    pub top_artists: Vec<(String, usize)>,
+    pub top_tracks: Vec<(String, usize)>,
+    pub top_genres: Vec<(String, usize)>,
+    // Most days in a row with a play
+    pub longest_streak: u32,
+}
+
+impl ListeningReport {
+    pub fn hours(&self) -> f64 {
+        self.listened_ms as f64 / 3_600_000.0
+    }
+}
+
+// The most counted names, ties in name order
+fn top(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(TOP_COUNT);
+    counts
+}
+
+// Days since 1970-01-01 of a "YYYY-MM-DD..." date, the inverse of `format_date`
+fn day_number(date: &str) -> Option<i64> {
+    let mut parts = date
+        .get(..10)?
+        .split('-')
+        .map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+fn longest_streak(entries: &[HistoryEntry]) -> u32 {
+    let mut days: Vec<i64> = entries
+        .iter()
+        .filter_map(|entry| day_number(&entry.played_at_local))
+        .collect();
+    days.sort_unstable();
+    days.dedup();
+
+    let mut longest = 0;
+    let mut current = 0;
+    for (idx, day) in days.iter().enumerate() {
+        current = if idx > 0 && days[idx - 1] + 1 == *day {
+            current + 1
+        } else {
+            1
+        };
+        longest = longest.max(current);
+    }
+    longest
+}
+
+/// Sums up the plays. `genre` finds the genre of a play's track, which the history
+/// doesn't keep.
+pub fn build(
+    entries: &[HistoryEntry],
+    genre: impl Fn(&HistoryEntry) -> Option<String>,
+) -> ListeningReport {
+    let mut artists: HashMap<String, usize> = HashMap::new();
+    let mut tracks: HashMap<String, usize> = HashMap::new();
+    let mut genres: HashMap<String, usize> = HashMap::new();
+
+    for entry in entries {
+        if let Some(artist) = &entry.artist {
+            *artists.entry(artist.clone()).or_default() += 1;
+        }
+        let title = entry.title.clone().unwrap_or_else(|| t("unknown_title"));
+        let track = match &entry.artist {
+            Some(artist) => format!("{} - {}", artist, title),
+            None => title,
+        };
+        *tracks.entry(track).or_default() += 1;
+        if let Some(genre) = genre(entry) {
+            *genres.entry(genre).or_default() += 1;
+        }
+    }
+
+    ListeningReport {
+        plays: entries.len(),
+        listened_ms: entries.iter().map(|entry| entry.listened_ms).sum(),
+        top_artists: top(artists),
+        top_tracks: top(tracks),
+        top_genres: top(genres),
+        longest_streak: longest_streak(entries),
+    }
+}
+
+/// The report as plain text, to paste somewhere.
+pub fn to_text(report: &ListeningReport, period: ReportPeriod) -> String {
+    let mut text = format!("{} – {}\n", t("listening_report"), period.label());
+    text.push_str(&tp(
+        "report_listened",
+        report.plays as u64,
+        &[&format!("{:.1}", report.hours()), &report.plays.to_string()],
+    ));
+    text.push('\n');
+    text.push_str(&tp(
+        "report_streak",
+        report.longest_streak as u64,
+        &[&report.longest_streak.to_string()],
+    ));
+    text.push('\n');
+
+    for (label, list) in [
+        ("report_top_artists", &report.top_artists),
+        ("report_top_tracks", &report.top_tracks),
+        ("report_top_genres", &report.top_genres),
+    ] {
+        if list.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("\n{}\n", t(label)));
+        for (idx, (name, plays)) in list.iter().enumerate() {
+            text.push_str(&format!("{}. {} ({})\n", idx + 1, name, plays));
+        }
+    }
+    text
+}
+
+/// Writes a screenshot region as a PNG file.
+pub fn save_image(image: &ColorImage, target: &Path) -> Result<(), String> {
+    let pixels = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_srgba_unmultiplied())
+        .collect();
+    let [width, height] = image.size;
+    ::image::RgbaImage::from_raw(width as u32, height as u32, pixels)
+        .ok_or_else(|| "empty image".to_string())?
+        .save_with_format(target, ::image::ImageFormat::Png)
+        .map_err(|e| e.to_string())
+}
+
+impl App {
+    fn report_genre(&self, entry: &HistoryEntry) -> Option<String> {
+        self.library
+            .items()
+            .iter()
+            .find(|item| item.key() == entry.track_key || item.path() == entry.path)
+            .and_then(LibraryItem::genre)
+    }
+
+    /// Builds the report of the chosen period again.
+    pub fn reload_report(&mut self) {
+        let Some(db) = &self.database else {
+            return;
+        };
+
+        self.save_listened_time();
+        match history::load(&db.connection(), self.report_period.range()) {
+            Ok(entries) => {
+                self.report = Some(build(&entries, |entry| self.report_genre(entry)));
+                self.report_status = None;
+            }
+            Err(e) => {
+                tracing::error!("Failed to load the listening report: {}", e);
+                self.report_status = Some(tf("report_failed", &[&e.to_string()]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::TrackKey;
+    use std::path::PathBuf;
+
+    fn entry(day: &str, artist: &str, title: &str, listened_ms: i64) -> HistoryEntry {
+        HistoryEntry {
+            id: 0,
+            track_key: TrackKey::nil(),
+            path: PathBuf::from(format!("/music/{}.mp3", title)),
+            title: Some(title.to_string()),
+            artist: Some(artist.to_string()),
+            played_at: 0,
+            played_at_local: format!("{} 21:30:00", day),
+            listened_ms,
+        }
+    }
+
+    #[test]
+    fn dates_count_days_like_format_date() {
+        assert_eq!(day_number("1970-01-01 00:00:00"), Some(0));
+        assert_eq!(day_number("2024-03-01"), Some(19783));
+        assert_eq!(
+            crate::app::library::format_date(19783 * 86400),
+            "2024-03-01"
+        );
+        assert_eq!(day_number("yesterday"), None);
+    }
+
+    #[test]
+    fn report_ranks_plays_and_finds_the_longest_streak() {
+        let entries = [
+            entry("2024-02-28", "Low", "Sunflower", 1_800_000),
+            entry("2024-02-29", "Low", "Lazy", 1_800_000),
+            entry("2024-02-29", "Low", "Sunflower", 0),
+            entry("2024-03-01", "Can", "Vitamin C", 1_800_000),
+            entry("2024-03-05", "Can", "Halleluwah", 1_800_000),
+        ];
+        let report = build(&entries, |entry| {
+            (entry.artist.as_deref() == Some("Low")).then(|| "Slowcore".to_string())
+        });
+
+        assert_eq!(report.plays, 5);
+        assert_eq!(report.hours(), 2.0);
+        assert_eq!(report.longest_streak, 3);
+        assert_eq!(
+            report.top_artists,
+            [("Low".to_string(), 3), ("Can".to_string(), 2)]
+        );
+        assert_eq!(report.top_tracks[0], ("Low - Sunflower".to_string(), 2));
+        assert_eq!(report.top_genres, [("Slowcore".to_string(), 3)]);
+    }
+}
@@ -0,0 +1,70 @@
+use super::library::TrackKey;
+use rusqlite::{Connection, Result as SqlResult};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Last playback position of long tracks (audiobooks, mixes, podcasts), keyed by
+/// library item key. Positions are in the track's timestamp units.
+#[derive(Debug, Clone, Default)]
+pub struct ResumePositions {
+    positions: HashMap<TrackKey, u64>,
+}
+
+impl ResumePositions {
+    pub fn get(&self, key: TrackKey) -> Option<u64> {
+        self.positions.get(&key).copied()
+    }
+
+    pub fn set(&mut self, key: TrackKey, position: u64) {
+        self.positions.insert(key, position);
+    }
+
+    pub fn remove(&mut self, key: TrackKey) {
+        self.positions.remove(&key);
+    }
+
+    // Database methods
+
+    pub fn save_to_db(&self, conn: &Arc<Mutex<Connection>>) -> SqlResult<()> {
+        let mut conn_guard = conn.lock().unwrap();
+        let tx = conn_guard.transaction()?;
+
+        // Replace the whole table so finished tracks are forgotten
+        tx.execute("DELETE FROM resume_positions", [])?;
+
+        // Tracks removed from the library have nothing to resume
+        for (key, position) in &self.positions {
+            tx.execute(
+                "INSERT OR REPLACE INTO resume_positions (library_item_id, position)
+                 SELECT ?1, ?2 WHERE EXISTS (SELECT 1 FROM library_items WHERE key = ?1)",
+                rusqlite::params![key.to_string(), *position as i64],
+            )?;
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    pub fn load_from_db(conn: &Arc<Mutex<Connection>>) -> SqlResult<Self> {
+        let conn_guard = conn.lock().unwrap();
+        let mut stmt =
+            conn_guard.prepare("SELECT library_item_id, position FROM resume_positions")?;
+
+        let rows = stmt.query_map([], |row| {
+            let key: String = row.get(0)?;
+            let position: i64 = row.get(1)?;
+            Ok((key, position))
+        })?;
+
+        let mut resume_positions = Self::default();
+        for row in rows {
+            let (key, position) = row?;
+            if let Ok(key) = key.parse::<TrackKey>() {
+                resume_positions.positions.insert(key, position as u64);
+            }
+        }
+
+        Ok(resume_positions)
+    }
+}
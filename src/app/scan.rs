@@ -0,0 +1,109 @@
+//! Walking a library folder for its files. Symbolic links are only followed when the
+//! setting asks for it, loops of links are reported rather than walked forever, and a
+//! file reachable under several names (links, hard links) is listed once.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+const AUDIO_EXTENSIONS: [&str; 6] = ["mp3", "m4a", "m4b", "flac", "ogg", "oga"];
+// Only their audio is played, e.g. of concert recordings
+const VIDEO_EXTENSIONS: [&str; 2] = ["mp4", "mkv"];
+
+// What makes two directory entries the same file
+#[derive(Debug, Hash, PartialEq, Eq)]
+enum FileId {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    Path(PathBuf),
+}
+
+fn file_id(entry: &DirEntry) -> FileId {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Follows the link when links are followed, like the walk itself
+        if let Ok(metadata) = entry.metadata() {
+            return FileId::Inode(metadata.dev(), metadata.ino());
+        }
+    }
+    FileId::Path(std::fs::canonicalize(entry.path()).unwrap_or_else(|_| entry.path().into()))
+}
+
+/// Every file below `root`, each file once. Without `follow_links`, linked files and
+/// folders are left out.
+pub fn files(root: &Path, follow_links: bool) -> Vec<DirEntry> {
+    let mut seen = HashSet::new();
+    WalkDir::new(root)
+        .follow_links(follow_links)
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                // Link loops end up here too
+                tracing::warn!("Skipping while scanning {:?}: {}", root, e);
+                None
+            }
+        })
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| seen.insert(file_id(entry)))
+        .collect()
+}
+
+/// Whether the importer takes the file, going by its extension. Video files only count
+/// with `include_video`.
+pub fn is_importable(path: &Path, include_video: bool) -> bool {
+    let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    let matches =
+        |extensions: &[&str]| extensions.iter().any(|e| e.eq_ignore_ascii_case(extension));
+    matches(&AUDIO_EXTENSIONS) || (include_video && matches(&VIDEO_EXTENSIONS))
+}
+
+/// Whether the file is one of the video files the importer takes.
+pub fn is_video(path: &Path) -> bool {
+    is_importable(path, true) && !is_importable(path, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_files_are_only_imported_when_asked_for() {
+        assert!(is_importable(Path::new("live/set.MP3"), false));
+        assert!(is_importable(Path::new("books/novel.m4b"), false));
+        assert!(!is_importable(Path::new("live/concert.mkv"), false));
+        assert!(is_importable(Path::new("live/concert.mkv"), true));
+        assert!(is_importable(Path::new("live/concert.Mp4"), true));
+        assert!(!is_importable(Path::new("live/cover.jpg"), true));
+        assert!(!is_importable(Path::new("live/README"), true));
+
+        assert!(is_video(Path::new("live/concert.mkv")));
+        assert!(!is_video(Path::new("live/set.mp3")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_are_followed_once() {
+        use std::fs;
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join(format!("bird-player-scan-{}", std::process::id()));
+        fs::create_dir_all(dir.join("album")).unwrap();
+        fs::write(dir.join("album/song.mp3"), b"song").unwrap();
+        fs::hard_link(dir.join("album/song.mp3"), dir.join("album/copy.mp3")).unwrap();
+        symlink(dir.join("album"), dir.join("linked")).unwrap();
+        // A loop back to the top
+        symlink(&dir, dir.join("album/loop")).unwrap();
+
+        assert_eq!(files(&dir, false).len(), 1);
+        assert_eq!(files(&dir, true).len(), 1);
+
+        fs::write(dir.join("other.mp3"), b"other").unwrap();
+        assert_eq!(files(&dir, true).len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use super::player::TrackState;
+use super::App;
+
+use eframe::egui::{self, Key, Modifiers};
+
+pub const SEEK_STEP_SECONDS: f64 = 5.0;
+pub const SEEK_LONG_STEP_SECONDS: f64 = 30.0;
+// About a couple of codec frames, for finding an exact spot
+pub const SEEK_NUDGE_SECONDS: f64 = 0.05;
+
+/// Formats a position as "mm:ss", or "h:mm:ss" from an hour on.
+pub fn format_time(seconds: u64) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds / 60) % 60;
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds % 60)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds % 60)
+    }
+}
+
+/// Parses a time typed as "ss", "mm:ss" or "h:mm:ss", seconds may have a fraction.
+pub fn parse_time(input: &str) -> Option<f64> {
+    let parts: Vec<&str> = input.trim().split(':').collect();
+    if parts.is_empty() || parts.len() > 3 {
+        return None;
+    }
+
+    let (seconds, whole) = parts.split_last()?;
+    let seconds: f64 = seconds.trim().parse().ok()?;
+    if !seconds.is_finite() || seconds < 0.0 || (!whole.is_empty() && seconds >= 60.0) {
+        return None;
+    }
+
+    let mut total = 0.0;
+    for (i, part) in whole.iter().enumerate() {
+        let value: u64 = part.trim().parse().ok()?;
+        // Minutes below hours must stay under an hour
+        if i > 0 && value >= 60 {
+            return None;
+        }
+        total = total * 60.0 + value as f64;
+    }
+
+    Some(total * 60.0 + seconds)
+}
+
+impl App {
+    /// Moves the playback position by `seconds`, clamped to the track.
+    pub fn seek_by(&mut self, seconds: f64) {
+        let Some(player) = &self.player else {
+            return;
+        };
+        let Some(current) = player.ts_to_seconds(player.seek_to_timestamp) else {
+            return;
+        };
+        self.seek_to_seconds(current + seconds);
+    }
+
+    pub fn seek_to_seconds(&mut self, seconds: f64) {
+        let Some(player) = &mut self.player else {
+            return;
+        };
+        if player.selected_track.is_none() {
+            return;
+        }
+
+        let duration = player.ts_to_seconds(player.duration).unwrap_or(0.0);
+        let seconds = seconds.clamp(0.0, duration);
+        if let Some(ts) = player.ms_to_ts((seconds * 1000.0) as u64) {
+            player.seek_to(ts.min(player.duration));
+
+            // The audio thread plays after seeking, keep the buttons in sync
+            player.track_state = TrackState::Playing;
+        }
+    }
+
+    /// ←/→ seek 5 s, with Shift 30 s, and ,/. nudge by a few milliseconds.
+    /// Ignored while a text field has focus.
+    pub fn handle_seek_keys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+
+        // Extra Shift is ignored when matching, so the Shift bindings go first
+        let bindings = [
+            (Modifiers::SHIFT, Key::ArrowLeft, -SEEK_LONG_STEP_SECONDS),
+            (Modifiers::SHIFT, Key::ArrowRight, SEEK_LONG_STEP_SECONDS),
+            (Modifiers::NONE, Key::ArrowLeft, -SEEK_STEP_SECONDS),
+            (Modifiers::NONE, Key::ArrowRight, SEEK_STEP_SECONDS),
+            (Modifiers::NONE, Key::Comma, -SEEK_NUDGE_SECONDS),
+            (Modifiers::NONE, Key::Period, SEEK_NUDGE_SECONDS),
+        ];
+
+        let mut offset = 0.0;
+        ctx.input_mut(|i| {
+            for (modifiers, key, seconds) in bindings {
+                if i.consume_key(modifiers, key) {
+                    offset += seconds;
+                }
+            }
+
+            // The nudge keys also type text, which must not reach type-ahead search
+            if offset != 0.0 {
+                i.events.retain(
+                    |event| !matches!(event, egui::Event::Text(text) if text == "," || text == "."),
+                );
+            }
+        });
+
+        if offset != 0.0 {
+            self.seek_by(offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_time_shows_seconds_and_hours_when_needed() {
+        assert_eq!(format_time(0), "00:00");
+        assert_eq!(format_time(65), "01:05");
+        assert_eq!(format_time(3599), "59:59");
+        assert_eq!(format_time(3723), "1:02:03");
+    }
+
+    #[test]
+    fn parse_time_accepts_seconds_minutes_and_hours() {
+        assert_eq!(parse_time("42"), Some(42.0));
+        assert_eq!(parse_time("3:05"), Some(185.0));
+        assert_eq!(parse_time(" 1:02:03 "), Some(3723.0));
+        assert_eq!(parse_time("0:07.5"), Some(7.5));
+        assert_eq!(parse_time("90:00"), Some(5400.0));
+
+        assert_eq!(parse_time(""), None);
+        assert_eq!(parse_time("1:60"), None);
+        assert_eq!(parse_time("1:60:00"), None);
+        assert_eq!(parse_time("a:10"), None);
+        assert_eq!(parse_time("1:2:3:4"), None);
+        assert_eq!(parse_time("-5"), None);
+    }
+}
@@ -0,0 +1,60 @@
+//! How the player draws the playing track: the classic tape, a spinning record or just
+//! the artwork, with colors that can be changed from the theme defaults.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkinStyle {
+    #[default]
+    Cassette,
+    Vinyl,
+    Artwork,
+}
+
+impl SkinStyle {
+    pub fn all() -> [SkinStyle; 3] {
+        [SkinStyle::Cassette, SkinStyle::Vinyl, SkinStyle::Artwork]
+    }
+
+    /// Translation key of the name shown in the settings.
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            SkinStyle::Cassette => "skin_cassette",
+            SkinStyle::Vinyl => "skin_vinyl",
+            SkinStyle::Artwork => "skin_artwork",
+        }
+    }
+}
+
+// Radians per second the reels turn at while playing
+pub const DEFAULT_SPIN_SPEED: f32 = 2.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Skin {
+    pub style: SkinStyle,
+
+    /// Radians per second the reels or the record turn while playing.
+    pub spin_speed: f32,
+
+    /// Title and artist on the label when the track has no artwork.
+    pub show_label: bool,
+
+    // Colors as RGB, None follows the light or dark theme
+    pub frame_color: Option<[u8; 3]>,
+    pub tape_color: Option<[u8; 3]>,
+    pub label_color: Option<[u8; 3]>,
+}
+
+impl Default for Skin {
+    fn default() -> Self {
+        Self {
+            style: SkinStyle::default(),
+            spin_speed: DEFAULT_SPIN_SPEED,
+            show_label: true,
+            frame_color: None,
+            tape_color: None,
+            label_color: None,
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Batch cleanup of tags: whitespace, capitalization, "feat." credits, leading "The" of
+//! artists and padded track numbers. Like search and replace, the changes are listed
+//! before anything is written, and written through the same tag writers.
+
+use eframe::egui;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::library::{LibraryItem, TrackKey};
+use super::properties::field_value;
+use super::tag_replace::ReplaceScope;
+use super::tag_writer::TagField;
+use super::App;
+
+// Words kept lower case inside a title, as English title case does
+const SMALL_WORDS: [&str; 16] = [
+    "a", "an", "and", "as", "at", "but", "by", "for", "from", "in", "nor", "of", "on", "or", "the",
+    "to",
+];
+
+lazy_static! {
+    static ref SPACES: Regex = Regex::new(r"\s+").unwrap();
+    // "ft", "ft.", "feat", "Feat." and "featuring" before a name
+    static ref FEATURING: Regex = Regex::new(r"(?i)\b(?:featuring|feat\.?|ft\.?)(?:\s+|$)").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupOp {
+    TrimWhitespace,
+    TitleCase,
+    NormalizeFeaturing,
+    MoveThe,
+    PadTrackNumbers,
+}
+
+impl CleanupOp {
+    pub fn all() -> [CleanupOp; 5] {
+        [
+            CleanupOp::TrimWhitespace,
+            CleanupOp::TitleCase,
+            CleanupOp::NormalizeFeaturing,
+            CleanupOp::MoveThe,
+            CleanupOp::PadTrackNumbers,
+        ]
+    }
+
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            CleanupOp::TrimWhitespace => "cleanup_trim",
+            CleanupOp::TitleCase => "cleanup_title_case",
+            CleanupOp::NormalizeFeaturing => "cleanup_featuring",
+            CleanupOp::MoveThe => "cleanup_move_the",
+            CleanupOp::PadTrackNumbers => "cleanup_pad_track",
+        }
+    }
+
+    /// Whether the cleanup touches the field.
+    pub fn applies_to(&self, field: TagField) -> bool {
+        match self {
+            CleanupOp::TrimWhitespace | CleanupOp::TitleCase => matches!(
+                field,
+                TagField::Title | TagField::Artist | TagField::Album | TagField::Genre
+            ),
+            CleanupOp::NormalizeFeaturing => matches!(field, TagField::Title | TagField::Artist),
+            CleanupOp::MoveThe => field == TagField::Artist,
+            CleanupOp::PadTrackNumbers => field == TagField::TrackNumber,
+        }
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            CleanupOp::TrimWhitespace => SPACES.replace_all(value.trim(), " ").into_owned(),
+            CleanupOp::TitleCase => title_case(value),
+            CleanupOp::NormalizeFeaturing => FEATURING.replace_all(value, "feat. ").into_owned(),
+            CleanupOp::MoveThe => move_the(value),
+            CleanupOp::PadTrackNumbers => match value.trim().parse::<u32>() {
+                Ok(number) => format!("{:02}", number),
+                Err(_) => value.to_string(),
+            },
+        }
+    }
+}
+
+/// Capitalizes words typed all in lower or all in upper case, leaving the likes of
+/// "AC/DC" or "iPhone" in mixed case alone. Small words stay lower case inside a title.
+pub fn title_case(value: &str) -> String {
+    let shouting = value.chars().any(char::is_alphabetic) && !value.chars().any(char::is_lowercase);
+    let words: Vec<&str> = value.split(' ').collect();
+    let last = words.len().saturating_sub(1);
+    words
+        .iter()
+        .enumerate()
+        .map(|(idx, word)| {
+            let lower = word.to_lowercase();
+            let uniform = shouting || *word == lower;
+            if !uniform {
+                word.to_string()
+            } else if idx != 0 && idx != last && SMALL_WORDS.contains(&lower.as_str()) {
+                lower
+            } else {
+                let mut chars = lower.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => lower,
+                }
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// "The Beatles" -> "Beatles, The", so artists sort by their name.
+pub fn move_the(value: &str) -> String {
+    match value.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("the ") && value.len() > 4 => {
+            format!("{}, {}", value[4..].trim_start(), &value[..3])
+        }
+        _ => value.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CleanupChange {
+    pub key: TrackKey,
+    pub field: TagField,
+    pub before: String,
+    pub after: String,
+}
+
+/// Fields the cleanups go over, in the order the preview lists them.
+const CLEANUP_FIELDS: [TagField; 5] = [
+    TagField::Title,
+    TagField::Artist,
+    TagField::Album,
+    TagField::Genre,
+    TagField::TrackNumber,
+];
+
+/// What the cleanups change in each field of the tracks, run in the order of `ops`.
+pub fn changes<'a>(
+    ops: &[CleanupOp],
+    tracks: impl IntoIterator<Item = &'a LibraryItem>,
+) -> Vec<CleanupChange> {
+    let mut changes = Vec::new();
+    for track in tracks {
+        for field in CLEANUP_FIELDS {
+            let before = field_value(track, field);
+            if before.is_empty() {
+                continue;
+            }
+            let after = ops
+                .iter()
+                .filter(|op| op.applies_to(field))
+                .fold(before.clone(), |value, op| op.apply(&value));
+            if after != before {
+                changes.push(CleanupChange {
+                    key: track.key(),
+                    field,
+                    before,
+                    after,
+                });
+            }
+        }
+    }
+    changes
+}
+
+pub struct TagCleanup {
+    pub scope: ReplaceScope,
+    pub ops: Vec<CleanupOp>,
+    pub preview: Vec<CleanupChange>,
+}
+
+impl App {
+    pub fn open_tag_cleanup(&mut self, scope: ReplaceScope) {
+        self.tag_cleanup = Some(TagCleanup {
+            scope,
+            ops: vec![CleanupOp::TrimWhitespace],
+            preview: Vec::new(),
+        });
+        self.update_tag_cleanup_preview();
+    }
+
+    /// Works out the changes again after the cleanups were picked.
+    pub fn update_tag_cleanup_preview(&mut self) {
+        let Some(cleanup) = &self.tag_cleanup else {
+            return;
+        };
+        // Keep the order of the list, not the one they were ticked in
+        let ops: Vec<CleanupOp> = CleanupOp::all()
+            .into_iter()
+            .filter(|op| cleanup.ops.contains(op))
+            .collect();
+        let preview = changes(&ops, self.scope_tracks(&cleanup.scope));
+        if let Some(cleanup) = &mut self.tag_cleanup {
+            cleanup.preview = preview;
+        }
+    }
+
+    /// Writes the previewed changes.
+    pub fn apply_tag_cleanup(&mut self, ctx: &egui::Context) {
+        let Some(cleanup) = &mut self.tag_cleanup else {
+            return;
+        };
+        let changes = std::mem::take(&mut cleanup.preview);
+        tracing::info!("Cleaning up {} tags", changes.len());
+        for change in changes {
+            if let Some(track) = self.find_track(change.key).cloned() {
+                self.queue_metadata_edit(ctx, &track, change.field.name(), &change.after);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_case_leaves_mixed_case_alone() {
+        assert_eq!(title_case("the end of the world"), "The End of the World");
+        assert_eq!(title_case("LOUD AND CLEAR"), "Loud and Clear");
+        assert_eq!(title_case("iPhone on AC/DC"), "iPhone on AC/DC");
+        assert_eq!(title_case("what it's for"), "What It's For");
+    }
+
+    #[test]
+    fn cleanups_run_in_order() {
+        assert_eq!(
+            CleanupOp::NormalizeFeaturing.apply("Song (Ft. Someone)"),
+            "Song (feat. Someone)"
+        );
+        assert_eq!(
+            CleanupOp::NormalizeFeaturing.apply("A featuring B"),
+            "A feat. B"
+        );
+        assert_eq!(CleanupOp::NormalizeFeaturing.apply("Left Out"), "Left Out");
+        assert_eq!(move_the("The Beatles"), "Beatles, The");
+        assert_eq!(move_the("Theory"), "Theory");
+        assert_eq!(CleanupOp::PadTrackNumbers.apply("3"), "03");
+        assert_eq!(CleanupOp::PadTrackNumbers.apply("12"), "12");
+
+        let mut track = LibraryItem::new(
+            std::path::PathBuf::from("song.mp3"),
+            crate::app::library::LibraryPathId::new(0),
+        );
+        track.set_artist(Some("  the   beatles "));
+        track.set_track_number(Some(3));
+        let found = changes(&CleanupOp::all(), [&track]);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].field, TagField::Artist);
+        assert_eq!(found[0].after, "Beatles, The");
+        assert_eq!(found[1].after, "03");
+    }
+}
@@ -0,0 +1,213 @@
+//! Search and replace over one tag of many tracks, e.g. stripping "(Remastered 2011)"
+//! from titles. The text is found as typed or as a regular expression, and every change
+//! is listed for review before any tag gets written.
+
+use eframe::egui;
+use regex::{NoExpand, Regex, RegexBuilder};
+
+use super::library::{LibraryItem, TrackKey};
+use super::properties::field_value;
+use super::tag_writer::TagField;
+use super::App;
+
+/// Fields search and replace works on, numbers are left to the properties dialog.
+pub const REPLACE_FIELDS: [TagField; 5] = [
+    TagField::Title,
+    TagField::Artist,
+    TagField::Album,
+    TagField::Genre,
+    TagField::Lyrics,
+];
+
+// Changes listed in the preview, the rest are counted
+pub const MAX_PREVIEW: usize = 500;
+
+/// Which tracks a search and replace runs over.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplaceScope {
+    Tracks(Vec<TrackKey>),
+    Library,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplaceChange {
+    pub key: TrackKey,
+    pub before: String,
+    pub after: String,
+}
+
+pub struct TagReplace {
+    pub scope: ReplaceScope,
+    pub field: TagField,
+    pub find: String,
+    pub replace: String,
+    pub use_regex: bool,
+    pub case_sensitive: bool,
+    /// The changes the current settings make, or why the pattern is invalid.
+    pub preview: Result<Vec<ReplaceChange>, String>,
+}
+
+impl TagReplace {
+    pub fn new(scope: ReplaceScope) -> Self {
+        Self {
+            scope,
+            field: TagField::Title,
+            find: String::new(),
+            replace: String::new(),
+            use_regex: false,
+            case_sensitive: false,
+            preview: Ok(Vec::new()),
+        }
+    }
+}
+
+/// Builds the pattern to search for, plain text matches itself.
+pub fn compile(find: &str, use_regex: bool, case_sensitive: bool) -> Result<Regex, String> {
+    let pattern = if use_regex {
+        find.to_string()
+    } else {
+        regex::escape(find)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// The new value of every track the replacement changes. Regular expressions can use
+/// `$1` or `${name}` for groups, plain replacements are taken as typed.
+pub fn changes<'a>(
+    pattern: &Regex,
+    replace: &str,
+    use_regex: bool,
+    field: TagField,
+    tracks: impl IntoIterator<Item = &'a LibraryItem>,
+) -> Vec<ReplaceChange> {
+    if pattern.as_str().is_empty() {
+        return Vec::new();
+    }
+    tracks
+        .into_iter()
+        .filter_map(|track| {
+            let before = field_value(track, field);
+            let after = if use_regex {
+                pattern.replace_all(&before, replace)
+            } else {
+                pattern.replace_all(&before, NoExpand(replace))
+            };
+            let after = after.trim().to_string();
+            (after != before).then(|| ReplaceChange {
+                key: track.key(),
+                before,
+                after,
+            })
+        })
+        .collect()
+}
+
+impl App {
+    /// The tracks a search and replace or cleanup runs over.
+    pub fn scope_tracks(&self, scope: &ReplaceScope) -> Vec<&LibraryItem> {
+        match scope {
+            ReplaceScope::Library => self.library.items().iter().collect(),
+            ReplaceScope::Tracks(keys) => keys
+                .iter()
+                .filter_map(|key| self.find_track(*key))
+                .collect(),
+        }
+    }
+
+    pub fn open_tag_replace(&mut self, scope: ReplaceScope) {
+        self.tag_replace = Some(TagReplace::new(scope));
+    }
+
+    /// Works out the changes again after the search, replacement or scope changed.
+    pub fn update_tag_replace_preview(&mut self) {
+        let Some(replace) = &self.tag_replace else {
+            return;
+        };
+        let preview =
+            compile(&replace.find, replace.use_regex, replace.case_sensitive).map(|pattern| {
+                changes(
+                    &pattern,
+                    &replace.replace,
+                    replace.use_regex,
+                    replace.field,
+                    self.scope_tracks(&replace.scope),
+                )
+            });
+        if let Some(replace) = &mut self.tag_replace {
+            replace.preview = preview;
+        }
+    }
+
+    /// Writes the previewed changes and returns how many tracks were changed.
+    pub fn apply_tag_replace(&mut self, ctx: &egui::Context) -> usize {
+        let Some(replace) = self.tag_replace.take() else {
+            return 0;
+        };
+        let changes = replace.preview.clone().unwrap_or_default();
+        for change in &changes {
+            if let Some(track) = self.find_track(change.key).cloned() {
+                self.queue_metadata_edit(ctx, &track, replace.field.name(), &change.after);
+            }
+        }
+        tracing::info!(
+            "Replacing {:?} with {:?} in the {} of {} tracks",
+            replace.find,
+            replace.replace,
+            replace.field.name(),
+            changes.len()
+        );
+
+        // Start over from the written values
+        self.tag_replace = Some(TagReplace {
+            preview: Ok(Vec::new()),
+            ..replace
+        });
+        changes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+    use std::path::PathBuf;
+
+    fn track(title: &str) -> LibraryItem {
+        let mut track =
+            LibraryItem::new(PathBuf::from(format!("{title}.mp3")), LibraryPathId::new(0));
+        track.set_title(Some(title));
+        track
+    }
+
+    #[test]
+    fn plain_text_is_replaced_as_typed() {
+        let tracks = [track("Help! (Remastered 2009)"), track("Yesterday")];
+        let pattern = compile("(remastered 2009)", false, false).unwrap();
+        let found = changes(&pattern, "", false, TagField::Title, &tracks);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].before, "Help! (Remastered 2009)");
+        assert_eq!(found[0].after, "Help!");
+
+        // Plain replacements don't expand groups
+        let found = changes(&pattern, "$1", false, TagField::Title, &tracks);
+        assert_eq!(found[0].after, "Help! $1");
+        assert!(compile("(remastered", false, false).is_ok());
+    }
+
+    #[test]
+    fn regular_expressions_use_groups() {
+        let tracks = [track("Song (Remastered 2011)"), track("Other (Live)")];
+        let pattern = compile(r"\s*\(Remastered \d{4}\)", true, true).unwrap();
+        let found = changes(&pattern, "", true, TagField::Title, &tracks);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].after, "Song");
+
+        let pattern = compile(r"^(\w+) \((\w+)\)$", true, true).unwrap();
+        let found = changes(&pattern, "$2: $1", true, TagField::Title, &tracks);
+        assert_eq!(found[0].after, "Live: Other");
+        assert!(compile("(unclosed", true, true).is_err());
+    }
+}
@@ -0,0 +1,1019 @@
+use id3::TagLike;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Metadata fields that can be edited in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagField {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Lyrics,
+    TrackNumber,
+    Year,
+}
+
+impl TagField {
+    /// Maps the column names used by the playlist editor.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "title" => Some(Self::Title),
+            "artist" => Some(Self::Artist),
+            "album" => Some(Self::Album),
+            "genre" => Some(Self::Genre),
+            "lyrics" => Some(Self::Lyrics),
+            "track_number" => Some(Self::TrackNumber),
+            "year" => Some(Self::Year),
+            _ => None,
+        }
+    }
+
+    /// The column name, also the one in the library database.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Album => "album",
+            Self::Genre => "genre",
+            Self::Lyrics => "lyrics",
+            Self::TrackNumber => "track_number",
+            Self::Year => "year",
+        }
+    }
+
+    /// Translation key of the field's name, shared with the playlist columns.
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            Self::Title => "column_title",
+            Self::Artist => "column_artist",
+            Self::Album => "column_album",
+            Self::Genre => "column_genre",
+            Self::Lyrics => "lyrics",
+            Self::TrackNumber => "column_track",
+            Self::Year => "column_year",
+        }
+    }
+
+    /// Whether the field holds a number rather than text.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::TrackNumber | Self::Year)
+    }
+
+    /// Whether `value` can be written to the field. Track numbers are positive and years
+    /// have four digits, an empty value removes the field.
+    pub fn is_valid(&self, value: &str) -> bool {
+        let value = value.trim();
+        if value.is_empty() {
+            return true;
+        }
+        match self {
+            Self::TrackNumber => value.parse::<u16>().is_ok_and(|number| number > 0),
+            Self::Year => value.len() == 4 && value.parse::<u16>().is_ok(),
+            _ => true,
+        }
+    }
+
+    fn vorbis_key(&self) -> &'static str {
+        match self {
+            Self::Title => "TITLE",
+            Self::Artist => "ARTIST",
+            Self::Album => "ALBUM",
+            Self::Genre => "GENRE",
+            Self::Lyrics => "LYRICS",
+            Self::TrackNumber => "TRACKNUMBER",
+            Self::Year => "DATE",
+        }
+    }
+
+    fn mp4_atom(&self) -> [u8; 4] {
+        match self {
+            Self::Title => *b"\xA9nam",
+            Self::Artist => *b"\xA9ART",
+            Self::Album => *b"\xA9alb",
+            Self::Genre => *b"\xA9gen",
+            Self::Lyrics => *b"\xA9lyr",
+            Self::TrackNumber => *b"trkn",
+            Self::Year => *b"\xA9day",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TagWriteError {
+    Io(io::Error),
+    Id3(id3::Error),
+    Malformed(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for TagWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Id3(e) => write!(f, "{}", e),
+            Self::Malformed(msg) => write!(f, "malformed file: {}", msg),
+            Self::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for TagWriteError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<id3::Error> for TagWriteError {
+    fn from(e: id3::Error) -> Self {
+        Self::Id3(e)
+    }
+}
+
+fn malformed<T>(msg: &str) -> Result<T, TagWriteError> {
+    Err(TagWriteError::Malformed(msg.to_string()))
+}
+
+// The number of a numeric field, `None` for an empty value
+fn parse_number<N: std::str::FromStr>(value: &str) -> Result<Option<N>, TagWriteError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| TagWriteError::Unsupported(format!("not a number: {}", value)))
+}
+
+/// Writes a metadata field into the file's native tag format.
+pub trait TagWriter: Sync {
+    fn format(&self) -> &'static str;
+
+    /// An empty value removes the field.
+    fn write(&self, path: &Path, field: TagField, value: &str) -> Result<(), TagWriteError>;
+}
+
+pub struct Id3Writer;
+pub struct FlacWriter;
+pub struct OggWriter;
+pub struct Mp4Writer;
+
+/// Picks the writer for a file by its extension.
+pub fn writer_for(path: &Path) -> Option<&'static dyn TagWriter> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+
+    match extension.as_str() {
+        "mp3" | "mp2" | "aiff" | "aif" | "wav" => Some(&Id3Writer),
+        "flac" => Some(&FlacWriter),
+        "ogg" | "oga" => Some(&OggWriter),
+        "m4a" | "m4b" | "mp4" | "aac" => Some(&Mp4Writer),
+        _ => None,
+    }
+}
+
+// Writes through a temporary file so a failure never leaves a half written track
+fn replace_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("bird-tag-tmp");
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+impl TagWriter for Id3Writer {
+    fn format(&self) -> &'static str {
+        "ID3v2"
+    }
+
+    fn write(&self, path: &Path, field: TagField, value: &str) -> Result<(), TagWriteError> {
+        let mut tag = match id3::Tag::read_from_path(path) {
+            Ok(tag) => tag,
+            Err(err) if matches!(err.kind, id3::ErrorKind::NoTag) => {
+                tracing::info!("Creating new ID3 tag for file: {:?}", path);
+                id3::Tag::new()
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        match field {
+            TagField::Title => tag.set_title(value),
+            TagField::Artist => tag.set_artist(value),
+            TagField::Album => tag.set_album(value),
+            TagField::Genre => tag.set_genre(value),
+            TagField::Lyrics => {
+                tag.remove_all_lyrics();
+                if !value.is_empty() {
+                    tag.add_frame(id3::frame::Lyrics {
+                        lang: "eng".to_string(),
+                        description: String::new(),
+                        text: value.to_string(),
+                    });
+                }
+            }
+            // TRCK and TYER, the track number as typed so a padded one stays padded
+            TagField::TrackNumber => match parse_number::<u32>(value)? {
+                Some(_) => tag.set_text("TRCK", value.trim()),
+                None => tag.remove_track(),
+            },
+            TagField::Year => match parse_number(value)? {
+                Some(year) => tag.set_year(year),
+                None => tag.remove_year(),
+            },
+        }
+
+        tag.write_to_path(path, id3::Version::Id3v24)?;
+        Ok(())
+    }
+}
+
+/// The comment list shared by FLAC, Ogg Vorbis and Opus.
+#[derive(Debug, Clone, PartialEq)]
+struct VorbisComments {
+    vendor: String,
+    comments: Vec<String>,
+}
+
+impl VorbisComments {
+    fn new() -> Self {
+        Self {
+            vendor: "bird-player".to_string(),
+            comments: Vec::new(),
+        }
+    }
+
+    /// Parses the comments, returning them and the number of bytes used.
+    fn parse(data: &[u8]) -> Result<(Self, usize), TagWriteError> {
+        let mut pos = 0;
+        let read_string = |pos: &mut usize| -> Result<String, TagWriteError> {
+            let len = read_u32_le(data, *pos)? as usize;
+            let Some(bytes) = data.get(*pos + 4..*pos + 4 + len) else {
+                return malformed("truncated vorbis comment");
+            };
+            *pos += 4 + len;
+            Ok(String::from_utf8_lossy(bytes).to_string())
+        };
+
+        let vendor = read_string(&mut pos)?;
+        let count = read_u32_le(data, pos)?;
+        pos += 4;
+
+        let mut comments = Vec::new();
+        for _ in 0..count {
+            comments.push(read_string(&mut pos)?);
+        }
+
+        Ok((Self { vendor, comments }, pos))
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        self.comments.retain(|comment| {
+            !comment
+                .split_once('=')
+                .is_some_and(|(k, _)| k.eq_ignore_ascii_case(key))
+        });
+
+        if !value.is_empty() {
+            self.comments.push(format!("{}={}", key, value));
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend((self.vendor.len() as u32).to_le_bytes());
+        bytes.extend(self.vendor.as_bytes());
+        bytes.extend((self.comments.len() as u32).to_le_bytes());
+        for comment in &self.comments {
+            bytes.extend((comment.len() as u32).to_le_bytes());
+            bytes.extend(comment.as_bytes());
+        }
+        bytes
+    }
+}
+
+fn read_u32_le(data: &[u8], pos: usize) -> Result<u32, TagWriteError> {
+    match data.get(pos..pos + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => malformed("unexpected end of data"),
+    }
+}
+
+fn read_u32_be(data: &[u8], pos: usize) -> Result<u32, TagWriteError> {
+    match data.get(pos..pos + 4) {
+        Some(b) => Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+        None => malformed("unexpected end of data"),
+    }
+}
+
+// FLAC
+
+const FLAC_VORBIS_COMMENT: u8 = 4;
+
+impl TagWriter for FlacWriter {
+    fn format(&self) -> &'static str {
+        "FLAC"
+    }
+
+    fn write(&self, path: &Path, field: TagField, value: &str) -> Result<(), TagWriteError> {
+        let data = fs::read(path)?;
+        let data = set_flac_comment(&data, field.vorbis_key(), value)?;
+        replace_file(path, &data)?;
+        Ok(())
+    }
+}
+
+const FLAC_PICTURE: u8 = 6;
+
+// Metadata blocks as (type, body), STREAMINFO always comes first
+type FlacBlocks<'a> = Vec<(u8, &'a [u8])>;
+
+/// The metadata blocks and where the audio frames start.
+fn flac_blocks(data: &[u8]) -> Result<(FlacBlocks<'_>, usize), TagWriteError> {
+    if !data.starts_with(b"fLaC") {
+        return malformed("missing fLaC marker");
+    }
+
+    let mut blocks = FlacBlocks::new();
+    let mut pos = 4;
+    loop {
+        let Some(header) = data.get(pos..pos + 4) else {
+            return malformed("truncated metadata block");
+        };
+        let is_last = header[0] & 0x80 != 0;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let Some(body) = data.get(pos + 4..pos + 4 + len) else {
+            return malformed("truncated metadata block");
+        };
+        blocks.push((header[0] & 0x7F, body));
+        pos += 4 + len;
+        if is_last {
+            break;
+        }
+    }
+
+    Ok((blocks, pos))
+}
+
+fn set_flac_comment(data: &[u8], key: &str, value: &str) -> Result<Vec<u8>, TagWriteError> {
+    let (mut blocks, pos) = flac_blocks(data)?;
+    let mut comments = match blocks.iter().find(|(kind, _)| *kind == FLAC_VORBIS_COMMENT) {
+        Some((_, body)) => VorbisComments::parse(body)?.0,
+        None => VorbisComments::new(),
+    };
+    comments.set(key, value);
+    let comment_body = comments.to_bytes();
+
+    blocks.retain(|(kind, _)| *kind != FLAC_VORBIS_COMMENT);
+    blocks.insert(1.min(blocks.len()), (FLAC_VORBIS_COMMENT, &comment_body));
+
+    let mut out = b"fLaC".to_vec();
+    for (idx, (kind, body)) in blocks.iter().enumerate() {
+        if body.len() >= 1 << 24 {
+            return malformed("metadata block too large");
+        }
+        let last_flag = if idx == blocks.len() - 1 { 0x80 } else { 0 };
+        out.push(kind | last_flag);
+        out.extend(&(body.len() as u32).to_be_bytes()[1..]);
+        out.extend(*body);
+    }
+    out.extend(&data[pos..]);
+
+    Ok(out)
+}
+
+// Ogg Vorbis and Opus
+
+struct OggPage {
+    header_type: u8,
+    granule: u64,
+    serial: u32,
+    sequence: u32,
+    segments: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl OggPage {
+    fn parse(data: &[u8], pos: usize) -> Result<(Self, usize), TagWriteError> {
+        let Some(header) = data.get(pos..pos + 27) else {
+            return malformed("truncated ogg page");
+        };
+        if &header[..4] != b"OggS" {
+            return malformed("missing OggS capture pattern");
+        }
+
+        let segment_count = header[26] as usize;
+        let Some(segments) = data.get(pos + 27..pos + 27 + segment_count) else {
+            return malformed("truncated ogg page");
+        };
+        let data_len: usize = segments.iter().map(|&s| s as usize).sum();
+        let data_start = pos + 27 + segment_count;
+        let Some(page_data) = data.get(data_start..data_start + data_len) else {
+            return malformed("truncated ogg page");
+        };
+
+        let page = Self {
+            header_type: header[5],
+            granule: u64::from_le_bytes(header[6..14].try_into().unwrap()),
+            serial: u32::from_le_bytes(header[14..18].try_into().unwrap()),
+            sequence: u32::from_le_bytes(header[18..22].try_into().unwrap()),
+            segments: segments.to_vec(),
+            data: page_data.to_vec(),
+        };
+
+        Ok((page, data_start + data_len))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"OggS".to_vec();
+        bytes.push(0); // Version
+        bytes.push(self.header_type);
+        bytes.extend(self.granule.to_le_bytes());
+        bytes.extend(self.serial.to_le_bytes());
+        bytes.extend(self.sequence.to_le_bytes());
+        bytes.extend([0; 4]); // CRC, filled in below
+        bytes.push(self.segments.len() as u8);
+        bytes.extend(&self.segments);
+        bytes.extend(&self.data);
+
+        let crc = ogg_crc(&bytes);
+        bytes[22..26].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+}
+
+fn ogg_crc(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Lays packets out on pages, each packet split into 255 byte lacing segments.
+fn paginate(packets: &[Vec<u8>], serial: u32) -> Vec<OggPage> {
+    let mut pages = Vec::new();
+    let mut page = OggPage {
+        header_type: 0,
+        granule: 0,
+        serial,
+        sequence: 0,
+        segments: Vec::new(),
+        data: Vec::new(),
+    };
+
+    for packet in packets {
+        let mut lacing = vec![255u8; packet.len() / 255];
+        lacing.push((packet.len() % 255) as u8);
+
+        let mut offset = 0;
+        for (idx, &len) in lacing.iter().enumerate() {
+            if page.segments.len() == 255 {
+                let continued = if idx > 0 { 0x01 } else { 0 };
+                pages.push(std::mem::replace(
+                    &mut page,
+                    OggPage {
+                        header_type: continued,
+                        granule: 0,
+                        serial,
+                        sequence: 0,
+                        segments: Vec::new(),
+                        data: Vec::new(),
+                    },
+                ));
+            }
+            page.segments.push(len);
+            page.data.extend(&packet[offset..offset + len as usize]);
+            offset += len as usize;
+        }
+    }
+
+    if !page.segments.is_empty() {
+        pages.push(page);
+    }
+
+    // Pages on which no packet ends carry no granule position
+    for page in &mut pages {
+        if page.segments.iter().all(|&s| s == 255) {
+            page.granule = u64::MAX;
+        }
+    }
+
+    pages
+}
+
+impl TagWriter for OggWriter {
+    fn format(&self) -> &'static str {
+        "Ogg"
+    }
+
+    fn write(&self, path: &Path, field: TagField, value: &str) -> Result<(), TagWriteError> {
+        let data = fs::read(path)?;
+        let data = set_ogg_comment(&data, field.vorbis_key(), value)?;
+        replace_file(path, &data)?;
+        Ok(())
+    }
+}
+
+// The id, comment and (for Vorbis) setup packets that start the stream
+struct OggHeaders {
+    serial: u32,
+    packets: Vec<Vec<u8>>,
+    // Where the first page after the headers starts
+    end: usize,
+}
+
+impl OggHeaders {
+    fn parse(data: &[u8]) -> Result<Self, TagWriteError> {
+        let mut serial = None;
+        let mut packets: Vec<Vec<u8>> = Vec::new();
+        let mut header_count = usize::MAX;
+        let mut current = Vec::new();
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let (page, next) = OggPage::parse(data, pos)?;
+            pos = next;
+            if *serial.get_or_insert(page.serial) != page.serial {
+                return Err(TagWriteError::Unsupported(
+                    "multiplexed ogg streams".to_string(),
+                ));
+            }
+
+            let mut offset = 0;
+            for (seg_idx, &len) in page.segments.iter().enumerate() {
+                current.extend(&page.data[offset..offset + len as usize]);
+                offset += len as usize;
+                if len < 255 {
+                    packets.push(std::mem::take(&mut current));
+
+                    if packets.len() == 1 {
+                        header_count = if packets[0].starts_with(b"\x01vorbis") {
+                            3
+                        } else if packets[0].starts_with(b"OpusHead") {
+                            2
+                        } else {
+                            return Err(TagWriteError::Unsupported(
+                                "ogg codec other than Vorbis or Opus".to_string(),
+                            ));
+                        };
+                    }
+
+                    if packets.len() == header_count {
+                        if seg_idx != page.segments.len() - 1 {
+                            return malformed("audio data shares a page with the headers");
+                        }
+                        return Ok(Self {
+                            serial: page.serial,
+                            packets,
+                            end: pos,
+                        });
+                    }
+                }
+            }
+        }
+
+        match serial {
+            None => malformed("no ogg pages"),
+            Some(_) => malformed("incomplete ogg headers"),
+        }
+    }
+
+    fn comment_prefix(&self) -> &'static [u8] {
+        if self.packets.len() == 3 {
+            b"\x03vorbis"
+        } else {
+            b"OpusTags"
+        }
+    }
+
+    /// Parses the comments, returning them and the number of bytes used after the prefix.
+    fn comments(&self) -> Result<(VorbisComments, usize), TagWriteError> {
+        let prefix = self.comment_prefix();
+        let comment_packet = &self.packets[1];
+        if !comment_packet.starts_with(prefix) {
+            return malformed("missing comment header");
+        }
+        VorbisComments::parse(&comment_packet[prefix.len()..])
+    }
+}
+
+fn set_ogg_comment(data: &[u8], key: &str, value: &str) -> Result<Vec<u8>, TagWriteError> {
+    let mut headers = OggHeaders::parse(data)?;
+    let (mut comments, used) = headers.comments()?;
+    comments.set(key, value);
+
+    // Keep what follows the comments: the Vorbis framing bit or Opus padding
+    let prefix = headers.comment_prefix();
+    let mut new_comment = prefix.to_vec();
+    new_comment.extend(comments.to_bytes());
+    new_comment.extend(&headers.packets[1][prefix.len() + used..]);
+    headers.packets[1] = new_comment;
+
+    // The id header sits alone on the first page
+    let mut pages = paginate(&headers.packets[..1], headers.serial);
+    pages.extend(paginate(&headers.packets[1..], headers.serial));
+    pages[0].header_type |= 0x02; // Beginning of stream
+
+    let mut pos = headers.end;
+    while pos < data.len() {
+        let (page, next) = OggPage::parse(data, pos)?;
+        pages.push(page);
+        pos = next;
+    }
+
+    let mut out = Vec::new();
+    for (sequence, page) in pages.iter_mut().enumerate() {
+        page.sequence = sequence as u32;
+        out.extend(page.to_bytes());
+    }
+
+    Ok(out)
+}
+
+// Reading
+
+/// What a FLAC or Ogg file holds for the tag reader: the comments as "KEY=value" and the
+/// bodies of the FLAC picture blocks.
+#[derive(Debug, Default)]
+pub struct VorbisTag {
+    pub comments: Vec<String>,
+    pub pictures: Vec<Vec<u8>>,
+}
+
+/// Reads the tag from the start of a FLAC or Ogg file. The headers of a longer file may
+/// not fit into `data`, which is reported as malformed.
+pub fn read_vorbis_tag(data: &[u8]) -> Result<VorbisTag, TagWriteError> {
+    if !data.starts_with(b"fLaC") {
+        return Ok(VorbisTag {
+            comments: OggHeaders::parse(data)?.comments()?.0.comments,
+            pictures: Vec::new(),
+        });
+    }
+
+    let (blocks, _) = flac_blocks(data)?;
+    let mut tag = VorbisTag::default();
+    for (kind, body) in blocks {
+        match kind {
+            FLAC_VORBIS_COMMENT => tag.comments = VorbisComments::parse(body)?.0.comments,
+            FLAC_PICTURE => tag.pictures.push(body.to_vec()),
+            _ => (),
+        }
+    }
+    Ok(tag)
+}
+
+// MP4
+
+const MP4_CONTAINERS: [&[u8; 4]; 5] = [b"moov", b"trak", b"mdia", b"minf", b"stbl"];
+
+struct Atom {
+    kind: [u8; 4],
+    start: usize,
+    header_len: usize,
+    end: usize,
+}
+
+fn parse_atoms(data: &[u8], start: usize, end: usize) -> Result<Vec<Atom>, TagWriteError> {
+    let mut atoms = Vec::new();
+    let mut pos = start;
+
+    while pos + 8 <= end {
+        let size = read_u32_be(data, pos)? as usize;
+        let kind: [u8; 4] = data[pos + 4..pos + 8].try_into().unwrap();
+
+        let (header_len, atom_end) = match size {
+            0 => (8, end),
+            1 => {
+                let Some(ext) = data.get(pos + 8..pos + 16) else {
+                    return malformed("truncated atom");
+                };
+                (
+                    16,
+                    pos + u64::from_be_bytes(ext.try_into().unwrap()) as usize,
+                )
+            }
+            _ => (8, pos + size),
+        };
+
+        if atom_end > end || atom_end < pos + header_len {
+            return malformed("atom size out of bounds");
+        }
+
+        atoms.push(Atom {
+            kind,
+            start: pos,
+            header_len,
+            end: atom_end,
+        });
+        pos = atom_end;
+    }
+
+    Ok(atoms)
+}
+
+fn build_atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut atom = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    atom.extend(kind);
+    atom.extend(payload);
+    atom
+}
+
+/// Replaces (or appends) the child `kind` of a container payload. `update` gets the
+/// old child payload, if any, and returns the new one or `None` to remove it.
+fn update_child(
+    payload: &[u8],
+    kind: &[u8; 4],
+    update: impl FnOnce(Option<&[u8]>) -> Result<Option<Vec<u8>>, TagWriteError>,
+) -> Result<Vec<u8>, TagWriteError> {
+    let atoms = parse_atoms(payload, 0, payload.len())?;
+    let existing = atoms.iter().find(|atom| &atom.kind == kind);
+    let new_child = update(existing.map(|atom| &payload[atom.start + atom.header_len..atom.end]))?
+        .map(|child| build_atom(kind, &child));
+
+    let mut out = Vec::new();
+    let mut replaced = false;
+    for atom in &atoms {
+        if &atom.kind == kind && !replaced {
+            replaced = true;
+            if let Some(child) = &new_child {
+                out.extend(child);
+            }
+        } else {
+            out.extend(&payload[atom.start..atom.end]);
+        }
+    }
+    if !replaced {
+        if let Some(child) = new_child {
+            out.extend(child);
+        }
+    }
+
+    Ok(out)
+}
+
+// Handler box marking the meta atom as iTunes metadata
+fn mdir_handler() -> Vec<u8> {
+    let mut hdlr = vec![0u8; 8]; // Version, flags and pre_defined
+    hdlr.extend(b"mdir");
+    hdlr.extend(b"appl");
+    hdlr.extend([0u8; 8]);
+    hdlr.push(0); // Empty name
+    build_atom(b"hdlr", &hdlr)
+}
+
+fn set_mp4_item(moov: &[u8], item: &[u8; 4], value: &str) -> Result<Vec<u8>, TagWriteError> {
+    update_child(moov, b"udta", |udta| {
+        let udta = update_child(udta.unwrap_or_default(), b"meta", |meta| {
+            // meta is a full box: version and flags come before the children
+            let meta = match meta {
+                Some(meta) if meta.len() >= 4 => meta.to_vec(),
+                Some(_) => return malformed("truncated meta atom"),
+                None => [vec![0u8; 4], mdir_handler()].concat(),
+            };
+
+            let children = update_child(&meta[4..], b"ilst", |ilst| {
+                let ilst = update_child(ilst.unwrap_or_default(), item, |_| {
+                    if value.is_empty() {
+                        return Ok(None);
+                    }
+                    Ok(Some(build_atom(b"data", &mp4_data(item, value)?)))
+                })?;
+                Ok(Some(ilst))
+            })?;
+
+            Ok(Some([&meta[..4], &children].concat()))
+        })?;
+        Ok(Some(udta))
+    })
+}
+
+// Payload of an item's data atom, UTF-8 text except for the binary track number
+fn mp4_data(item: &[u8; 4], value: &str) -> Result<Vec<u8>, TagWriteError> {
+    if item == b"trkn" {
+        let number: u16 = parse_number(value)?.unwrap_or_default();
+        let mut data = 0u32.to_be_bytes().to_vec(); // Implicit type
+        data.extend(0u32.to_be_bytes()); // Locale
+        data.extend([0, 0]);
+        data.extend(number.to_be_bytes());
+        data.extend([0, 0, 0, 0]); // No total, padding
+        return Ok(data);
+    }
+    let mut data = 1u32.to_be_bytes().to_vec(); // UTF-8 text
+    data.extend(0u32.to_be_bytes()); // Locale
+    data.extend(value.as_bytes());
+    Ok(data)
+}
+
+/// Shifts chunk offsets in stco/co64 tables that point at or past `threshold`.
+fn patch_chunk_offsets(
+    data: &mut [u8],
+    start: usize,
+    end: usize,
+    threshold: u64,
+    delta: i64,
+) -> Result<(), TagWriteError> {
+    for atom in parse_atoms(data, start, end)? {
+        let body = atom.start + atom.header_len;
+
+        if MP4_CONTAINERS.contains(&&atom.kind) {
+            patch_chunk_offsets(data, body, atom.end, threshold, delta)?;
+        } else if &atom.kind == b"stco" || &atom.kind == b"co64" {
+            let entry_len = if &atom.kind == b"stco" { 4 } else { 8 };
+            let count = read_u32_be(data, body + 4)? as usize;
+            if body + 8 + count * entry_len > atom.end {
+                return malformed("chunk offset table out of bounds");
+            }
+
+            for idx in 0..count {
+                let pos = body + 8 + idx * entry_len;
+                if entry_len == 4 {
+                    let offset = read_u32_be(data, pos)? as u64;
+                    if offset >= threshold {
+                        let offset = u32::try_from(offset as i64 + delta).map_err(|_| {
+                            TagWriteError::Unsupported("chunk offset overflow".to_string())
+                        })?;
+                        data[pos..pos + 4].copy_from_slice(&offset.to_be_bytes());
+                    }
+                } else {
+                    let offset = u64::from_be_bytes(data[pos..pos + 8].try_into().unwrap());
+                    if offset >= threshold {
+                        let offset = (offset as i64 + delta) as u64;
+                        data[pos..pos + 8].copy_from_slice(&offset.to_be_bytes());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl TagWriter for Mp4Writer {
+    fn format(&self) -> &'static str {
+        "MP4"
+    }
+
+    fn write(&self, path: &Path, field: TagField, value: &str) -> Result<(), TagWriteError> {
+        let data = fs::read(path)?;
+        let data = set_mp4_tag(&data, &field.mp4_atom(), value)?;
+        replace_file(path, &data)?;
+        Ok(())
+    }
+}
+
+fn set_mp4_tag(data: &[u8], item: &[u8; 4], value: &str) -> Result<Vec<u8>, TagWriteError> {
+    let atoms = parse_atoms(data, 0, data.len())?;
+    let Some(moov) = atoms.iter().find(|atom| &atom.kind == b"moov") else {
+        return malformed("missing moov atom");
+    };
+    if moov.header_len != 8 {
+        return Err(TagWriteError::Unsupported("64-bit moov atom".to_string()));
+    }
+
+    let payload = set_mp4_item(&data[moov.start + 8..moov.end], item, value)?;
+    let mut new_moov = build_atom(b"moov", &payload);
+
+    // Media data after the moov atom moves by the size difference
+    let delta = new_moov.len() as i64 - (moov.end - moov.start) as i64;
+    let moov_len = new_moov.len();
+    patch_chunk_offsets(&mut new_moov, 8, moov_len, moov.end as u64, delta)?;
+
+    let mut out = data[..moov.start].to_vec();
+    out.extend(new_moov);
+    out.extend(&data[moov.end..]);
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comments_of(body: &[u8]) -> Vec<String> {
+        VorbisComments::parse(body).unwrap().0.comments
+    }
+
+    #[test]
+    fn numbers_are_checked_before_writing() {
+        assert!(TagField::TrackNumber.is_valid("7"));
+        assert!(TagField::TrackNumber.is_valid(""));
+        assert!(!TagField::TrackNumber.is_valid("0"));
+        assert!(!TagField::TrackNumber.is_valid("7/12"));
+        assert!(TagField::Year.is_valid(" 1969 "));
+        assert!(!TagField::Year.is_valid("69"));
+        assert!(!TagField::Year.is_valid("late 60s"));
+        assert!(TagField::Title.is_valid("69"));
+
+        let data = mp4_data(b"trkn", "7").unwrap();
+        assert_eq!(&data[8..], &[0, 0, 0, 7, 0, 0, 0, 0]);
+        assert!(mp4_data(b"trkn", "seven").is_err());
+    }
+
+    #[test]
+    fn writes_flac_comments() {
+        let mut flac = b"fLaC".to_vec();
+        flac.push(0x80); // Last block: STREAMINFO
+        flac.extend([0, 0, 34]);
+        flac.extend([0u8; 34]);
+        flac.extend(b"audio");
+
+        let flac = set_flac_comment(&flac, "TITLE", "Song").unwrap();
+        let flac = set_flac_comment(&flac, "title", "Другая").unwrap();
+
+        // STREAMINFO lost its last flag, the comment block follows it
+        assert_eq!(flac[4], 0);
+        assert_eq!(flac[42], 0x80 | FLAC_VORBIS_COMMENT);
+        let len = u32::from_be_bytes([0, flac[43], flac[44], flac[45]]) as usize;
+        assert_eq!(comments_of(&flac[46..46 + len]), vec!["title=Другая"]);
+        assert!(flac.ends_with(b"audio"));
+    }
+
+    #[test]
+    fn writes_ogg_vorbis_comments() {
+        let mut comment = b"\x03vorbis".to_vec();
+        comment.extend(VorbisComments::new().to_bytes());
+        comment.push(1); // Framing bit
+        let headers = [
+            b"\x01vorbis-id".to_vec(),
+            comment,
+            [b"\x05vorbis".to_vec(), vec![7u8; 600]].concat(),
+        ];
+
+        let mut pages = paginate(&headers[..1], 42);
+        pages.extend(paginate(&headers[1..], 42));
+        pages.extend(paginate(&[vec![9u8; 10]], 42));
+        pages[0].header_type |= 0x02;
+        let mut ogg = Vec::new();
+        for (idx, page) in pages.iter_mut().enumerate() {
+            page.sequence = idx as u32;
+            ogg.extend(page.to_bytes());
+        }
+
+        let long_lyrics = "la ".repeat(200);
+        let ogg = set_ogg_comment(&ogg, "LYRICS", &long_lyrics).unwrap();
+
+        let mut pos = 0;
+        let mut parsed = Vec::new();
+        while pos < ogg.len() {
+            let (page, next) = OggPage::parse(&ogg, pos).unwrap();
+            // The CRC of every page is rewritten
+            assert_eq!(page.to_bytes(), ogg[pos..next]);
+            parsed.push(page);
+            pos = next;
+        }
+
+        for (idx, page) in parsed.iter().enumerate() {
+            assert_eq!(page.sequence, idx as u32);
+        }
+        assert_eq!(parsed.last().unwrap().data, vec![9u8; 10]);
+
+        let comment_page = &parsed[1];
+        let (comments, _) = VorbisComments::parse(&comment_page.data[7..]).unwrap();
+        assert_eq!(comments.comments, vec![format!("LYRICS={}", long_lyrics)]);
+        // And the tag reader finds them
+        let tag = read_vorbis_tag(&ogg).unwrap();
+        assert_eq!(tag.comments, comments.comments);
+    }
+
+    #[test]
+    fn writes_mp4_items_and_moves_chunk_offsets() {
+        let mut stco = vec![0u8; 4];
+        stco.extend(1u32.to_be_bytes());
+        stco.extend(0u32.to_be_bytes()); // Patched below
+        let stbl = build_atom(b"stbl", &build_atom(b"stco", &stco));
+        let trak = build_atom(b"trak", &build_atom(b"mdia", &build_atom(b"minf", &stbl)));
+        let moov = build_atom(b"moov", &trak);
+        let ftyp = build_atom(b"ftyp", b"M4A ");
+
+        let mdat_data_offset = (ftyp.len() + moov.len() + 8) as u32;
+        let mut mp4 = [ftyp.clone(), moov].concat();
+        let stco_entry = mp4.len() - 4;
+        mp4[stco_entry..].copy_from_slice(&mdat_data_offset.to_be_bytes());
+        mp4.extend(build_atom(b"mdat", b"sound"));
+
+        let mp4 = set_mp4_tag(&mp4, &TagField::Title.mp4_atom(), "Title").unwrap();
+
+        let atoms = parse_atoms(&mp4, 0, mp4.len()).unwrap();
+        let mdat = atoms.iter().find(|a| &a.kind == b"mdat").unwrap();
+        let moov = atoms.iter().find(|a| &a.kind == b"moov").unwrap();
+
+        // The chunk offset still points at the media data
+        let stco_pos = ftyp.len() + 8 * 6 + 8;
+        let offset = read_u32_be(&mp4, stco_pos).unwrap() as usize;
+        assert_eq!(&mp4[offset..mdat.end], b"sound");
+
+        let moov_bytes = &mp4[moov.start..moov.end];
+        let needle = b"\xA9nam";
+        assert!(moov_bytes.windows(4).any(|w| w == needle));
+        assert!(moov_bytes.ends_with(b"Title"));
+    }
+}
@@ -0,0 +1,948 @@
+use super::library::ReplayGain;
+use super::mp4_chapters;
+use super::replaygain;
+use super::tag_writer::{self, TagWriteError, VorbisTag};
+
+use id3::TagLike;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Metadata of a track, independent of the tag format it was read from.
+#[derive(Debug, Clone, Default)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>, // Display form, e.g. "A, B feat. C"
+    pub artists: Vec<String>,   // Every credited artist
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    pub lyrics: Option<String>,
+    pub replay_gain: ReplayGain,
+    pub pictures: Vec<TagPicture>,
+    pub explicit: bool, // Marked as explicit content by an advisory tag or comment
+}
+
+#[derive(Debug, Clone)]
+pub struct TagPicture {
+    pub mime_type: String,
+    pub picture_type: u8,
+    pub description: String,
+    pub data: Vec<u8>,
+}
+
+impl TrackTags {
+    /// Fills fields that are still missing from a lower priority tag.
+    fn fill_from(&mut self, other: TrackTags) {
+        self.title = self.title.take().or(other.title);
+        if self.artist.is_none() {
+            self.artist = other.artist;
+            self.artists = other.artists;
+        }
+        self.album_artist = self.album_artist.take().or(other.album_artist);
+        self.album = self.album.take().or(other.album);
+        self.year = self.year.or(other.year);
+        self.genre = self.genre.take().or(other.genre);
+        self.track_number = self.track_number.or(other.track_number);
+        self.lyrics = self.lyrics.take().or(other.lyrics);
+
+        let rg = &mut self.replay_gain;
+        rg.track_gain = rg.track_gain.or(other.replay_gain.track_gain);
+        rg.track_peak = rg.track_peak.or(other.replay_gain.track_peak);
+        rg.album_gain = rg.album_gain.or(other.replay_gain.album_gain);
+        rg.album_peak = rg.album_peak.or(other.replay_gain.album_peak);
+
+        if self.pictures.is_empty() {
+            self.pictures = other.pictures;
+        }
+        self.explicit |= other.explicit;
+    }
+}
+
+/// A source of track metadata in one tag format.
+pub trait TagReader: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns `None` when the file carries no tag of this format.
+    fn read(&self, path: &Path) -> Option<TrackTags>;
+}
+
+/// ID3v2.2, v2.3 and v2.4 tags.
+pub struct Id3v2Reader;
+
+/// The fixed size ID3v1 tag at the end of the file.
+pub struct Id3v1Reader;
+
+/// APEv2 tags, found at the end of the file (optionally before an ID3v1 tag).
+pub struct ApeReader;
+
+/// The Vorbis comments of FLAC and Ogg files, and the pictures of FLAC files.
+pub struct VorbisReader;
+
+/// The iTunes item list of MP4 files (.m4a, .m4b), with the cover.
+pub struct Mp4Reader;
+
+// In order of priority, later readers only fill in what earlier ones lack
+static READERS: [&dyn TagReader; 5] = [
+    &Id3v2Reader,
+    &VorbisReader,
+    &Mp4Reader,
+    &ApeReader,
+    &Id3v1Reader,
+];
+
+/// Reads the metadata of a file through all known tag formats.
+/// Returns `None` when the file has no tags at all.
+pub fn read_tags(path: &Path) -> Option<TrackTags> {
+    let mut result: Option<TrackTags> = None;
+
+    for reader in READERS {
+        if let Some(tags) = reader.read(path) {
+            tracing::debug!("Read {} tag from {:?}", reader.name(), path);
+            match &mut result {
+                Some(result) => result.fill_from(tags),
+                None => result = Some(tags),
+            }
+        }
+    }
+
+    result
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Like `non_empty`, for ID3 text that may have been decoded with the wrong encoding.
+fn id3_text(value: &str) -> Option<String> {
+    non_empty(&repair_encoding(value))
+}
+
+/// Many taggers write UTF-8, UTF-16 or local code pages (mostly GBK) into frames
+/// that declare Latin-1, which then decode to mojibake such as "ä½ å¥½". When the
+/// text only holds Latin-1 characters, this recovers the original bytes and
+/// decodes them with the encoding they were most likely written in. Text that is
+/// valid in none of them is kept as Latin-1.
+pub fn repair_encoding(value: &str) -> String {
+    if value.chars().any(|c| c as u32 > 0xFF) {
+        // Decoded from a Unicode frame, nothing to repair
+        return value.to_string();
+    }
+
+    let bytes: Vec<u8> = value.chars().map(|c| c as u8).collect();
+    let trimmed = {
+        let end = bytes.iter().rposition(|&b| b != 0).map_or(0, |pos| pos + 1);
+        &bytes[..end]
+    };
+
+    if trimmed.contains(&0) {
+        if let Some(text) = decode_utf16(&bytes) {
+            return text;
+        }
+    }
+
+    if trimmed.is_ascii() {
+        return value.to_string();
+    }
+
+    if let Ok(text) = std::str::from_utf8(trimmed) {
+        return text.to_string();
+    }
+
+    if looks_like_double_byte(trimmed) {
+        if let Some(text) =
+            encoding_rs::GBK.decode_without_bom_handling_and_without_replacement(trimmed)
+        {
+            return text.into_owned();
+        }
+    }
+
+    value.to_string()
+}
+
+// UTF-16 text mislabelled as Latin-1 leaves a NUL byte next to every ASCII character
+fn decode_utf16(bytes: &[u8]) -> Option<String> {
+    let (bytes, little_endian) = match bytes {
+        [0xFF, 0xFE, rest @ ..] => (rest, true),
+        [0xFE, 0xFF, rest @ ..] => (rest, false),
+        [_, 0, ..] => (bytes, true),
+        [0, _, ..] => (bytes, false),
+        _ => return None,
+    };
+
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .filter(|chunk| chunk.len() == 2)
+        .map(|chunk| {
+            if little_endian {
+                u16::from_le_bytes([chunk[0], chunk[1]])
+            } else {
+                u16::from_be_bytes([chunk[0], chunk[1]])
+            }
+        })
+        .collect();
+
+    String::from_utf16(&units)
+        .ok()
+        .map(|text| text.trim_end_matches('\0').to_string())
+}
+
+// In double byte encodings every non-ASCII character takes two high bytes, while
+// accented Latin-1 text ("Mötley Crüe") mostly has single high bytes between letters.
+fn looks_like_double_byte(bytes: &[u8]) -> bool {
+    bytes.split(|b| b.is_ascii()).all(|run| run.len() % 2 == 0)
+}
+
+// "3/12" -> 3
+fn parse_track_number(value: &str) -> Option<u32> {
+    value.split('/').next()?.trim().parse::<u32>().ok()
+}
+
+// "1999", "1999-05-01" -> 1999
+fn parse_year(value: &str) -> Option<i32> {
+    value.trim().get(..4)?.parse::<i32>().ok()
+}
+
+const FEATURING: [&str; 5] = [" feat. ", " feat ", " ft. ", " featuring ", " with "];
+
+/// Splits artist values into the main and the featured artists. Values are
+/// separated by NUL (ID3v2.4), ";" or " / ", guests follow "feat."/"ft." either
+/// inline or in parentheses. "&" and "," only separate guests, as they are
+/// common inside band names.
+pub fn split_artists(values: &[&str]) -> (Vec<String>, Vec<String>) {
+    let mut main = Vec::new();
+    let mut featured = Vec::new();
+
+    for value in values {
+        for part in value.split(['\0', ';']).flat_map(|part| part.split(" / ")) {
+            let part = part.replace(['(', ')', '[', ']'], " ");
+            // ASCII lowercasing keeps byte offsets, so positions map back onto `part`
+            // (shifted by the leading space)
+            let padded = format!(" {} ", part.to_ascii_lowercase());
+
+            let split_at = FEATURING
+                .iter()
+                .filter_map(|marker| padded.find(marker).map(|pos| (pos, marker.len())))
+                .min();
+
+            let (main_part, guests) = match split_at {
+                Some((pos, len)) => {
+                    let start = pos.saturating_sub(1);
+                    let end = (pos + len - 1).min(part.len());
+                    (&part[..start], &part[end..])
+                }
+                None => (part.as_str(), ""),
+            };
+
+            if let Some(name) = non_empty(main_part) {
+                if !main.contains(&name) {
+                    main.push(name);
+                }
+            }
+
+            for guest in guests.split([',', '&']) {
+                if let Some(name) = non_empty(guest) {
+                    if !main.contains(&name) && !featured.contains(&name) {
+                        featured.push(name);
+                    }
+                }
+            }
+        }
+    }
+
+    (main, featured)
+}
+
+/// Formats artists for display: "A, B feat. C & D".
+pub fn format_artists(main: &[String], featured: &[String]) -> Option<String> {
+    let main = main.join(", ");
+    let featured = featured.join(" & ");
+
+    match (main.is_empty(), featured.is_empty()) {
+        (true, true) => None,
+        (false, true) => Some(main),
+        (true, false) => Some(featured),
+        (false, false) => Some(format!("{} feat. {}", main, featured)),
+    }
+}
+
+/// All artists credited in an artist value, main artists first.
+pub fn all_artists(value: &str) -> Vec<String> {
+    let (main, featured) = split_artists(&[value]);
+    main.into_iter().chain(featured).collect()
+}
+
+// iTunes rates explicit tracks 1 (or 4 in older files) and clean versions 2
+fn advisory_is_explicit(value: &str) -> bool {
+    matches!(value.trim(), "1" | "4")
+}
+
+// Comments like "Explicit" or "[explicit version]", not "inexplicit"
+fn comment_is_explicit(value: &str) -> bool {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word.eq_ignore_ascii_case("explicit"))
+}
+
+/// Sets the display artist and the list of all artists from raw tag values.
+fn set_artists(tags: &mut TrackTags, values: &[&str]) {
+    let (main, featured) = split_artists(values);
+    tags.artist = format_artists(&main, &featured);
+    tags.artists = main.into_iter().chain(featured).collect();
+}
+
+impl TagReader for Id3v2Reader {
+    fn name(&self) -> &'static str {
+        "ID3v2"
+    }
+
+    fn read(&self, path: &Path) -> Option<TrackTags> {
+        let tag = id3::Tag::read_from_path(path).ok()?;
+        let lyrics = tag.lyrics().next().and_then(|l| id3_text(&l.text));
+        let artists: Vec<String> = tag
+            .artists()
+            .unwrap_or_default()
+            .into_iter()
+            .map(repair_encoding)
+            .collect();
+
+        let mut tags = TrackTags {
+            title: tag.title().and_then(id3_text),
+            album_artist: tag.album_artist().and_then(id3_text),
+            album: tag.album().and_then(id3_text),
+            year: tag.year(),
+            genre: tag.genre().and_then(id3_text),
+            track_number: tag
+                .get("TRCK")
+                .and_then(|frame| frame.content().text())
+                .and_then(parse_track_number),
+            lyrics,
+            replay_gain: replaygain::read_tags(&tag),
+            pictures: tag
+                .pictures()
+                .map(|pic| TagPicture {
+                    mime_type: pic.mime_type.clone(),
+                    picture_type: u8::from(pic.picture_type),
+                    description: pic.description.clone(),
+                    data: pic.data.clone(),
+                })
+                .collect(),
+            explicit: tag.extended_texts().any(|text| {
+                text.description.eq_ignore_ascii_case("ITUNESADVISORY")
+                    && advisory_is_explicit(&text.value)
+            }) || tag
+                .comments()
+                .any(|comment| comment_is_explicit(&comment.text)),
+            ..Default::default()
+        };
+
+        set_artists(
+            &mut tags,
+            &artists.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+
+        Some(tags)
+    }
+}
+
+impl TagReader for Id3v1Reader {
+    fn name(&self) -> &'static str {
+        "ID3v1"
+    }
+
+    fn read(&self, path: &Path) -> Option<TrackTags> {
+        let tag = id3::v1::Tag::read_from_path(path).ok()?;
+
+        let mut tags = TrackTags {
+            title: id3_text(&tag.title),
+            album: id3_text(&tag.album),
+            year: parse_year(&tag.year),
+            genre: tag.genre().and_then(non_empty),
+            track_number: tag.track.filter(|&n| n > 0).map(u32::from),
+            ..Default::default()
+        };
+        set_artists(&mut tags, &[&repair_encoding(&tag.artist)]);
+
+        Some(tags)
+    }
+}
+
+impl TagReader for ApeReader {
+    fn name(&self) -> &'static str {
+        "APEv2"
+    }
+
+    fn read(&self, path: &Path) -> Option<TrackTags> {
+        let file = File::open(path).ok()?;
+        let items = match read_ape_items(file) {
+            Ok(Some(items)) => items,
+            Ok(None) => return None,
+            Err(e) => {
+                tracing::warn!("Failed to read APE tag from {:?}: {}", path, e);
+                return None;
+            }
+        };
+
+        let mut tags = TrackTags {
+            replay_gain: replaygain::from_pairs(
+                items.iter().map(|(k, v)| (k.as_str(), v.as_str())),
+            ),
+            ..Default::default()
+        };
+
+        for (key, value) in &items {
+            match key.to_lowercase().as_str() {
+                "title" => tags.title = non_empty(value),
+                "artist" => set_artists(&mut tags, &[value]),
+                "album artist" | "albumartist" => tags.album_artist = non_empty(value),
+                "album" => tags.album = non_empty(value),
+                "year" => tags.year = parse_year(value),
+                "genre" => tags.genre = non_empty(value),
+                "track" => tags.track_number = parse_track_number(value),
+                "lyrics" => tags.lyrics = non_empty(value),
+                "itunesadvisory" => tags.explicit |= advisory_is_explicit(value),
+                "comment" => tags.explicit |= comment_is_explicit(value),
+                _ => (),
+            }
+        }
+
+        Some(tags)
+    }
+}
+
+impl TagReader for VorbisReader {
+    fn name(&self) -> &'static str {
+        "Vorbis comment"
+    }
+
+    fn read(&self, path: &Path) -> Option<TrackTags> {
+        let tag = match read_vorbis_head(path) {
+            Ok(Some(tag)) => tag,
+            Ok(None) => return None,
+            Err(e) => {
+                tracing::warn!("Failed to read Vorbis comments from {:?}: {}", path, e);
+                return None;
+            }
+        };
+
+        let comments: Vec<(&str, &str)> = tag
+            .comments
+            .iter()
+            .filter_map(|comment| comment.split_once('='))
+            .collect();
+        let mut tags = TrackTags {
+            replay_gain: replaygain::from_pairs(comments.iter().copied()),
+            pictures: tag
+                .pictures
+                .iter()
+                .filter_map(|body| flac_picture(body))
+                .collect(),
+            ..Default::default()
+        };
+
+        // Each artist has a comment of its own
+        let artists: Vec<&str> = comments
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("artist"))
+            .map(|(_, value)| *value)
+            .collect();
+        set_artists(&mut tags, &artists);
+
+        for (key, value) in &comments {
+            match key.to_lowercase().as_str() {
+                "title" => tags.title = non_empty(value),
+                "albumartist" | "album artist" => tags.album_artist = non_empty(value),
+                "album" => tags.album = non_empty(value),
+                "date" | "year" => tags.year = parse_year(value),
+                "genre" => tags.genre = non_empty(value),
+                "tracknumber" => tags.track_number = parse_track_number(value),
+                "lyrics" | "unsyncedlyrics" => tags.lyrics = non_empty(value),
+                "itunesadvisory" => tags.explicit |= advisory_is_explicit(value),
+                "comment" | "description" => tags.explicit |= comment_is_explicit(value),
+                _ => (),
+            }
+        }
+
+        Some(tags)
+    }
+}
+
+impl TagReader for Mp4Reader {
+    fn name(&self) -> &'static str {
+        "MP4"
+    }
+
+    fn read(&self, path: &Path) -> Option<TrackTags> {
+        let moov = match read_mp4_moov(path) {
+            Ok(Some(moov)) => moov,
+            Ok(None) => return None,
+            Err(e) => {
+                tracing::warn!("Failed to read MP4 tags from {:?}: {}", path, e);
+                return None;
+            }
+        };
+        // meta is a full box: version and flags come before the children
+        let meta = mp4_chapters::child(&moov, &[b"udta", b"meta"])?;
+        let ilst = mp4_chapters::child(meta.get(4..)?, &[b"ilst"])?;
+
+        let mut tags = TrackTags::default();
+        for (item, payload) in mp4_chapters::boxes(ilst) {
+            // The data atom starts with the value type and the locale
+            let Some(data) =
+                mp4_chapters::child(payload, &[b"data"]).filter(|data| data.len() >= 8)
+            else {
+                continue;
+            };
+            let value = &data[8..];
+            if item == b"covr" {
+                // Audiobooks have no other place for their cover
+                let mime_type = match data[..4] {
+                    [0, 0, 0, 13] => "image/jpeg",
+                    [0, 0, 0, 14] => "image/png",
+                    _ => continue,
+                };
+                tags.pictures.push(TagPicture {
+                    mime_type: mime_type.to_string(),
+                    picture_type: 3, // Front cover
+                    description: String::new(),
+                    data: value.to_vec(),
+                });
+                continue;
+            }
+            if item == b"trkn" {
+                tags.track_number = value
+                    .get(2..4)
+                    .map(|number| u16::from_be_bytes([number[0], number[1]]) as u32)
+                    .filter(|&number| number > 0);
+                continue;
+            }
+
+            let value = String::from_utf8_lossy(value);
+            match item {
+                b"\xA9nam" => tags.title = non_empty(&value),
+                b"\xA9ART" => set_artists(&mut tags, &[&value]),
+                b"\xA9alb" => tags.album = non_empty(&value),
+                b"aART" => tags.album_artist = non_empty(&value),
+                b"\xA9day" => tags.year = parse_year(&value),
+                b"\xA9gen" => tags.genre = non_empty(&value),
+                b"\xA9lyr" => tags.lyrics = non_empty(&value),
+                _ => (),
+            }
+        }
+
+        Some(tags)
+    }
+}
+
+/// Reads the movie header of an MP4 file, `None` for other formats.
+fn read_mp4_moov(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 8];
+    if file.read_exact(&mut header).is_err() || &header[4..] != b"ftyp" {
+        return Ok(None);
+    }
+    mp4_chapters::read_moov(&mut file)
+}
+
+// Enough for the headers of most files, ones with large covers are read further
+const VORBIS_HEAD_LEN: u64 = 256 * 1024;
+
+/// Reads the start of the file until the tag is in it, `None` for other formats.
+fn read_vorbis_head(path: &Path) -> Result<Option<VorbisTag>, TagWriteError> {
+    let mut file = File::open(path)?;
+    let mut data = Vec::new();
+    let mut limit = VORBIS_HEAD_LEN;
+    loop {
+        let missing = limit - data.len() as u64;
+        file.by_ref().take(missing).read_to_end(&mut data)?;
+        if !data.starts_with(b"fLaC") && !data.starts_with(b"OggS") {
+            return Ok(None);
+        }
+        match tag_writer::read_vorbis_tag(&data) {
+            Err(TagWriteError::Malformed(_)) if data.len() as u64 == limit => limit *= 4,
+            result => return result.map(Some),
+        }
+    }
+}
+
+// A FLAC picture block: the picture type, MIME type, description, dimensions and image
+fn flac_picture(body: &[u8]) -> Option<TagPicture> {
+    fn read_u32(body: &[u8], pos: &mut usize) -> Option<u32> {
+        let bytes = body.get(*pos..*pos + 4)?;
+        *pos += 4;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    fn read_bytes<'a>(body: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+        let len = read_u32(body, pos)? as usize;
+        let bytes = body.get(*pos..*pos + len)?;
+        *pos += len;
+        Some(bytes)
+    }
+
+    let mut pos = 0;
+    let picture_type = read_u32(body, &mut pos)?;
+    let mime_type = String::from_utf8_lossy(read_bytes(body, &mut pos)?).to_string();
+    let description = String::from_utf8_lossy(read_bytes(body, &mut pos)?).to_string();
+    pos += 16; // Width, height, color depth and palette size
+    let data = read_bytes(body, &mut pos)?.to_vec();
+
+    Some(TagPicture {
+        mime_type,
+        picture_type: picture_type as u8,
+        description,
+        data,
+    })
+}
+
+const APE_PREAMBLE: &[u8; 8] = b"APETAGEX";
+const APE_FOOTER_LEN: u64 = 32;
+const ID3V1_LEN: u64 = 128;
+
+/// Reads the text items of an APEv2 tag. Multiple values of an item are
+/// separated by "; ". Binary items (cover art) are skipped.
+fn read_ape_items(mut reader: impl Read + Seek) -> io::Result<Option<Vec<(String, String)>>> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+
+    // The footer is either at the very end or right before an ID3v1 tag
+    let mut footer = [0u8; APE_FOOTER_LEN as usize];
+    let mut footer_pos = None;
+    for trailer in [0, ID3V1_LEN] {
+        let Some(pos) = file_len.checked_sub(APE_FOOTER_LEN + trailer) else {
+            continue;
+        };
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut footer)?;
+        if &footer[..8] == APE_PREAMBLE {
+            footer_pos = Some(pos);
+            break;
+        }
+    }
+
+    let Some(footer_pos) = footer_pos else {
+        return Ok(None);
+    };
+
+    let le_u32 = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let tag_size = le_u32(&footer[12..16]) as u64; // Items and footer, without header
+    let item_count = le_u32(&footer[16..20]);
+
+    let items_len = tag_size
+        .checked_sub(APE_FOOTER_LEN)
+        .filter(|len| *len <= footer_pos)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid APE tag size"))?;
+
+    let mut data = vec![0u8; items_len as usize];
+    reader.seek(SeekFrom::Start(footer_pos - items_len))?;
+    reader.read_exact(&mut data)?;
+
+    let mut items = Vec::new();
+    let mut pos = 0usize;
+    for _ in 0..item_count {
+        if pos + 8 > data.len() {
+            break;
+        }
+        let value_len = le_u32(&data[pos..pos + 4]) as usize;
+        let flags = le_u32(&data[pos + 4..pos + 8]);
+        pos += 8;
+
+        let Some(key_len) = data[pos..].iter().position(|&b| b == 0) else {
+            break;
+        };
+        let key = String::from_utf8_lossy(&data[pos..pos + key_len]).to_string();
+        pos += key_len + 1;
+
+        let Some(value) = data.get(pos..pos + value_len) else {
+            break;
+        };
+        pos += value_len;
+
+        // Bits 1-2 hold the item type, 0 is UTF-8 text
+        if (flags >> 1) & 0b11 == 0 {
+            let value = String::from_utf8_lossy(value)
+                .split('\0')
+                .filter(|v| !v.is_empty())
+                .collect::<Vec<_>>()
+                .join("; ");
+            items.push((key, value));
+        }
+    }
+
+    Ok(Some(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn ape_item(key: &str, value: &str, flags: u32) -> Vec<u8> {
+        let mut item = Vec::new();
+        item.extend((value.len() as u32).to_le_bytes());
+        item.extend(flags.to_le_bytes());
+        item.extend(key.as_bytes());
+        item.push(0);
+        item.extend(value.as_bytes());
+        item
+    }
+
+    fn ape_tag(items: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = items.concat();
+        let mut tag = body.clone();
+        tag.extend(APE_PREAMBLE);
+        tag.extend(2000u32.to_le_bytes());
+        tag.extend((body.len() as u32 + APE_FOOTER_LEN as u32).to_le_bytes());
+        tag.extend((items.len() as u32).to_le_bytes());
+        tag.extend(0u32.to_le_bytes());
+        tag.extend([0u8; 8]);
+        tag
+    }
+
+    #[test]
+    fn reads_ape_text_items() {
+        let mut file = vec![0xFFu8; 100]; // Audio data
+        file.extend(ape_tag(&[
+            ape_item("Title", "Старая песня", 0),
+            ape_item("Artist", "A\0B", 0),
+            ape_item("Cover Art (Front)", "binary", 0b10),
+        ]));
+
+        let items = read_ape_items(Cursor::new(file)).unwrap().unwrap();
+
+        assert_eq!(
+            items,
+            vec![
+                ("Title".to_string(), "Старая песня".to_string()),
+                ("Artist".to_string(), "A; B".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_ape_tag_before_id3v1() {
+        let mut file = vec![0xFFu8; 100];
+        file.extend(ape_tag(&[ape_item("Track", "7/12", 0)]));
+        file.extend(b"TAG");
+        file.extend([0u8; 125]);
+
+        let items = read_ape_items(Cursor::new(file)).unwrap().unwrap();
+
+        assert_eq!(items, vec![("Track".to_string(), "7/12".to_string())]);
+        assert_eq!(parse_track_number(&items[0].1), Some(7));
+    }
+
+    fn latin1(bytes: &[u8]) -> String {
+        bytes.iter().map(|&b| b as char).collect()
+    }
+
+    #[test]
+    fn repairs_misdecoded_text() {
+        // UTF-8 and GBK bytes in frames declared as Latin-1
+        assert_eq!(repair_encoding(&latin1("你好".as_bytes())), "你好");
+        assert_eq!(repair_encoding(&latin1(&[0xC4, 0xE3, 0xBA, 0xC3])), "你好");
+
+        // UTF-16LE with BOM and without
+        assert_eq!(
+            repair_encoding(&latin1(&[0xFF, 0xFE, b'H', 0, b'i', 0])),
+            "Hi"
+        );
+        assert_eq!(repair_encoding(&latin1(&[b'H', 0, b'i', 0])), "Hi");
+    }
+
+    #[test]
+    fn keeps_valid_text() {
+        assert_eq!(repair_encoding("Mötley Crüe"), "Mötley Crüe");
+        assert_eq!(repair_encoding("Café"), "Café");
+        assert_eq!(repair_encoding("夜に駆ける"), "夜に駆ける");
+        assert_eq!(repair_encoding("Кино"), "Кино");
+        assert_eq!(repair_encoding("Plain title"), "Plain title");
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_artists() {
+        assert_eq!(
+            split_artists(&["Simon & Garfunkel"]),
+            (names(&["Simon & Garfunkel"]), vec![])
+        );
+        assert_eq!(
+            split_artists(&["A\0B", "C"]),
+            (names(&["A", "B", "C"]), vec![])
+        );
+        assert_eq!(
+            split_artists(&["A feat. B & C"]),
+            (names(&["A"]), names(&["B", "C"]))
+        );
+        assert_eq!(
+            split_artists(&["A (Ft. B)"]),
+            (names(&["A"]), names(&["B"]))
+        );
+        assert_eq!(split_artists(&["AC/DC"]), (names(&["AC/DC"]), vec![]));
+
+        assert_eq!(
+            format_artists(&names(&["A", "B"]), &names(&["C"])),
+            Some("A, B feat. C".to_string())
+        );
+    }
+
+    #[test]
+    fn finds_explicit_markers() {
+        assert!(advisory_is_explicit("1"));
+        assert!(!advisory_is_explicit("2"));
+        assert!(comment_is_explicit("Explicit version"));
+        assert!(comment_is_explicit("[explicit]"));
+        assert!(!comment_is_explicit("inexplicit"));
+    }
+
+    #[test]
+    fn no_ape_tag() {
+        let file = vec![0u8; 300];
+        assert!(read_ape_items(Cursor::new(file)).unwrap().is_none());
+    }
+
+    #[test]
+    fn flac_tags_are_imported_after_editing() {
+        use crate::app::scan;
+        use crate::app::tag_writer::TagField;
+
+        let mut picture = Vec::new();
+        picture.extend(3u32.to_be_bytes()); // Front cover
+        for text in ["image/png", "cover"] {
+            picture.extend((text.len() as u32).to_be_bytes());
+            picture.extend(text.as_bytes());
+        }
+        picture.extend([0u8; 16]);
+        picture.extend(4u32.to_be_bytes());
+        picture.extend(b"\x89PNG");
+
+        let mut flac = b"fLaC".to_vec();
+        flac.push(0); // STREAMINFO
+        flac.extend([0, 0, 34]);
+        flac.extend([0u8; 34]);
+        flac.push(0x80 | 6); // Last block: PICTURE
+        flac.extend(&(picture.len() as u32).to_be_bytes()[1..]);
+        flac.extend(&picture);
+        flac.extend(b"audio");
+
+        let dir = std::env::temp_dir().join(format!("bird-player-tags-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("song.flac");
+        std::fs::write(&path, flac).unwrap();
+        assert!(scan::is_importable(&path, false));
+
+        let writer = tag_writer::writer_for(&path).unwrap();
+        writer.write(&path, TagField::Title, "Song").unwrap();
+        writer.write(&path, TagField::Artist, "A feat. B").unwrap();
+        writer.write(&path, TagField::TrackNumber, "3").unwrap();
+
+        let tags = read_tags(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.artists, ["A", "B"]);
+        assert_eq!(tags.track_number, Some(3));
+        assert_eq!(tags.pictures.len(), 1);
+        assert_eq!(tags.pictures[0].mime_type, "image/png");
+        assert_eq!(tags.pictures[0].data, b"\x89PNG");
+
+        writer.write(&path, TagField::Title, "").unwrap();
+        assert_eq!(read_tags(&path).unwrap().title, None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mp4_tags_are_read_after_editing() {
+        use crate::app::tag_writer::TagField;
+
+        let mp4_box = |kind: &[u8], payload: &[u8]| {
+            [&(payload.len() as u32 + 8).to_be_bytes()[..], kind, payload].concat()
+        };
+        let mp4 = [
+            mp4_box(b"ftyp", b"M4A "),
+            mp4_box(b"moov", &mp4_box(b"mvhd", &[0u8; 100])),
+            mp4_box(b"mdat", b"audio"),
+        ]
+        .concat();
+
+        let dir = std::env::temp_dir().join(format!("bird-player-mp4-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("song.m4a");
+        std::fs::write(&path, mp4).unwrap();
+        assert!(crate::app::scan::is_importable(&path, false));
+        assert!(read_tags(&path).is_none());
+
+        let writer = tag_writer::writer_for(&path).unwrap();
+        for (field, value) in [
+            (TagField::Title, "Song"),
+            (TagField::Artist, "A feat. B"),
+            (TagField::Album, "Album"),
+            (TagField::Genre, "Jazz"),
+            (TagField::Lyrics, "la la"),
+            (TagField::TrackNumber, "3"),
+            (TagField::Year, "1999"),
+        ] {
+            writer.write(&path, field, value).unwrap();
+        }
+
+        let tags = read_tags(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.artist.as_deref(), Some("A feat. B"));
+        assert_eq!(tags.artists, ["A", "B"]);
+        assert_eq!(tags.album.as_deref(), Some("Album"));
+        assert_eq!(tags.genre.as_deref(), Some("Jazz"));
+        assert_eq!(tags.lyrics.as_deref(), Some("la la"));
+        assert_eq!(tags.track_number, Some(3));
+        assert_eq!(tags.year, Some(1999));
+
+        writer.write(&path, TagField::Title, "").unwrap();
+        assert_eq!(read_tags(&path).unwrap().title, None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn audiobooks_have_their_title_author_and_cover() {
+        let mp4_box = |kind: &[u8], payload: &[u8]| {
+            [&(payload.len() as u32 + 8).to_be_bytes()[..], kind, payload].concat()
+        };
+        let item = |kind: &[u8], data_type: u32, value: &[u8]| {
+            let data = [&data_type.to_be_bytes()[..], &[0u8; 4], value].concat();
+            mp4_box(kind, &mp4_box(b"data", &data))
+        };
+        let ilst = [
+            item(b"\xA9nam", 1, "Chapter One".as_bytes()),
+            item(b"\xA9alb", 1, "The Book".as_bytes()),
+            item(b"\xA9ART", 1, "The Author".as_bytes()),
+            item(b"aART", 1, "The Narrator".as_bytes()),
+            item(b"covr", 13, b"\xFF\xD8jpeg"),
+        ]
+        .concat();
+        let meta = [&[0u8; 4][..], &mp4_box(b"ilst", &ilst)].concat();
+        let mp4 = [
+            mp4_box(b"ftyp", b"M4B "),
+            mp4_box(b"mdat", b"audio"),
+            mp4_box(b"moov", &mp4_box(b"udta", &mp4_box(b"meta", &meta))),
+        ]
+        .concat();
+
+        let dir = std::env::temp_dir().join(format!("bird-player-m4b-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("book.m4b");
+        std::fs::write(&path, mp4).unwrap();
+
+        let tags = read_tags(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Chapter One"));
+        assert_eq!(tags.album.as_deref(), Some("The Book"));
+        assert_eq!(tags.artist.as_deref(), Some("The Author"));
+        assert_eq!(tags.album_artist.as_deref(), Some("The Narrator"));
+        assert_eq!(tags.pictures.len(), 1);
+        assert_eq!(tags.pictures[0].mime_type, "image/jpeg");
+        assert_eq!(tags.pictures[0].data, b"\xFF\xD8jpeg");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
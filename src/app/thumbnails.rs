@@ -0,0 +1,157 @@
+//! Small covers for the playlist and library rows. Pictures are scaled down on a worker
+//! thread and kept on disk, so scrolling never waits for an image to decode.
+
+use super::art_cache::{ArtCache, ART_STALE_AFTER};
+use super::App;
+use ::image::imageops::FilterType;
+use ::image::ImageFormat;
+use eframe::egui::{self, vec2, Color32, ColorImage, Rect, Sense, TextureHandle};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Side of a thumbnail in a row, in points.
+pub const THUMBNAIL_POINTS: f32 = 16.0;
+
+// Side in pixels stored on disk, enough for high-DPI screens
+const THUMBNAIL_PIXELS: u32 = 48;
+
+// Thumbnails are tiny, a few hundred fit comfortably
+const MAX_THUMBNAIL_BYTES: usize = 4 * 1024 * 1024;
+
+type Loaded = (PathBuf, Option<ColorImage>);
+
+pub struct Thumbnails {
+    textures: ArtCache<PathBuf, Option<TextureHandle>>,
+    pending: HashSet<PathBuf>,
+    worker: Option<(Sender<PathBuf>, Receiver<Loaded>)>,
+}
+
+impl Default for Thumbnails {
+    fn default() -> Self {
+        Self {
+            textures: ArtCache::new(MAX_THUMBNAIL_BYTES),
+            pending: HashSet::new(),
+            worker: None,
+        }
+    }
+}
+
+impl Thumbnails {
+    /// Draws the thumbnail of `picture` in a small square, or leaves the square empty
+    /// while it loads or when the track has no cover.
+    pub fn show(&mut self, ui: &mut egui::Ui, picture: Option<&Path>) -> egui::Response {
+        let (rect, response) =
+            ui.allocate_exact_size(vec2(THUMBNAIL_POINTS, THUMBNAIL_POINTS), Sense::hover());
+        // Rows scrolled out of view don't ask for their cover
+        if let Some(picture) = picture.filter(|_| ui.is_rect_visible(rect)) {
+            if let Some(texture) = self.get(ui.ctx(), picture) {
+                ui.painter().image(
+                    texture.id(),
+                    rect,
+                    Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    Color32::WHITE,
+                );
+            }
+        }
+        response
+    }
+
+    /// The texture of a picture once it's been loaded, queueing it otherwise.
+    fn get(&mut self, ctx: &egui::Context, picture: &Path) -> Option<TextureHandle> {
+        let now = ctx.input(|input| input.time);
+        self.receive(ctx, now);
+
+        if let Some(texture) = self.textures.get(&picture.to_path_buf(), now) {
+            return texture.clone();
+        }
+        if self.pending.insert(picture.to_path_buf()) {
+            let (requests, _) = self.worker.get_or_insert_with(|| spawn_worker(ctx.clone()));
+            let _ = requests.send(picture.to_path_buf());
+        }
+        None
+    }
+
+    // Uploads what the worker finished since the last frame
+    fn receive(&mut self, ctx: &egui::Context, now: f64) {
+        let Some((_, results)) = &self.worker else {
+            return;
+        };
+        while let Ok((path, image)) = results.try_recv() {
+            self.pending.remove(&path);
+            let bytes = image.as_ref().map_or(0, |image| image.pixels.len() * 4);
+            let texture = image.map(|image| {
+                ctx.load_texture(
+                    format!("thumbnail:{}", path.display()),
+                    image,
+                    Default::default(),
+                )
+            });
+            self.textures.insert(path, texture, bytes, now);
+        }
+        self.textures.evict_stale(now, ART_STALE_AFTER);
+    }
+}
+
+fn spawn_worker(ctx: egui::Context) -> (Sender<PathBuf>, Receiver<Loaded>) {
+    let (request_tx, request_rx) = channel::<PathBuf>();
+    let (result_tx, result_rx) = channel();
+
+    std::thread::spawn(move || {
+        for path in request_rx {
+            let image = load_thumbnail(&path);
+            if result_tx.send((path, image)).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+    });
+
+    (request_tx, result_rx)
+}
+
+// Reads the thumbnail from the disk cache, making it from the picture on a miss
+fn load_thumbnail(picture: &Path) -> Option<ColorImage> {
+    let cached = cache_path(picture);
+
+    let thumbnail = match cached.as_ref().and_then(|path| ::image::open(path).ok()) {
+        Some(thumbnail) => thumbnail,
+        None => {
+            let thumbnail = ::image::open(picture)
+                .map_err(|e| tracing::warn!("Failed to make a thumbnail of {:?}: {}", picture, e))
+                .ok()?
+                .resize_to_fill(THUMBNAIL_PIXELS, THUMBNAIL_PIXELS, FilterType::Triangle);
+
+            if let Some(cached) = &cached {
+                let saved = std::fs::create_dir_all(App::get_thumbnail_dir())
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| {
+                        thumbnail
+                            .save_with_format(cached, ImageFormat::Png)
+                            .map_err(|e| e.to_string())
+                    });
+                if let Err(e) = saved {
+                    tracing::warn!("Failed to cache thumbnail {:?}: {}", cached, e);
+                }
+            }
+            thumbnail
+        }
+    };
+
+    let rgba = thumbnail.into_rgba8();
+    let size = [rgba.width() as usize, rgba.height() as usize];
+    Some(ColorImage::from_rgba_unmultiplied(size, &rgba.into_raw()))
+}
+
+// Named after the picture's path, size and modification time, so a changed cover gets
+// a new thumbnail
+fn cache_path(picture: &Path) -> Option<PathBuf> {
+    let metadata = std::fs::metadata(picture).ok()?;
+    let mut hasher = DefaultHasher::new();
+    picture.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok().hash(&mut hasher);
+    Some(App::get_thumbnail_dir().join(format!("{:016x}.png", hasher.finish())))
+}
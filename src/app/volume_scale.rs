@@ -0,0 +1,68 @@
+//! Maps the volume slider to a gain. Hearing is logarithmic, so the slider moves in
+//! decibels and its lower half stays useful instead of jumping straight to silence.
+
+// Where the far left of the slider sits, right before it mutes
+pub const MIN_VOLUME_DB: f32 = -60.0;
+
+// How far one notch of the mouse wheel moves the slider
+pub const WHEEL_STEP: f32 = 0.05;
+
+/// Gain for a slider position between 0 and 1.
+pub fn slider_to_gain(position: f32) -> f32 {
+    let position = position.clamp(0.0, 1.0);
+    if position <= 0.0 {
+        return 0.0;
+    }
+    10.0_f32.powf(MIN_VOLUME_DB * (1.0 - position) / 20.0)
+}
+
+/// Slider position for a gain, the inverse of `slider_to_gain`.
+pub fn gain_to_slider(gain: f32) -> f32 {
+    if gain <= 0.0 {
+        return 0.0;
+    }
+    let db = 20.0 * gain.min(1.0).log10();
+    (1.0 - db / MIN_VOLUME_DB).clamp(0.0, 1.0)
+}
+
+/// The slider position after scrolling by `scroll` points, one notch being about 50.
+pub fn scrolled(position: f32, scroll: f32) -> f32 {
+    let notches = scroll / 50.0;
+    (((position + notches * WHEEL_STEP) / 0.01).round() * 0.01).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slider_ends_map_to_silence_and_full_gain() {
+        assert_eq!(slider_to_gain(0.0), 0.0);
+        assert_eq!(slider_to_gain(1.0), 1.0);
+        assert_eq!(gain_to_slider(0.0), 0.0);
+        assert_eq!(gain_to_slider(1.0), 1.0);
+        // Halfway is -30 dB
+        assert!((slider_to_gain(0.5) - 0.0316).abs() < 0.001);
+    }
+
+    #[test]
+    fn gain_round_trips_through_the_slider() {
+        for step in 1..=100 {
+            let position = step as f32 / 100.0;
+            let back = gain_to_slider(slider_to_gain(position));
+            assert!(
+                (back - position).abs() < 1e-4,
+                "{} became {}",
+                position,
+                back
+            );
+        }
+    }
+
+    #[test]
+    fn scrolling_moves_by_whole_steps() {
+        assert!((scrolled(0.5, 50.0) - 0.55).abs() < 1e-6);
+        assert!((scrolled(0.5, -100.0) - 0.4).abs() < 1e-6);
+        assert_eq!(scrolled(0.98, 50.0), 1.0);
+    }
+}
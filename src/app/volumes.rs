@@ -0,0 +1,158 @@
+use super::library::{LibraryItem, LibraryPathId, LibraryPathStatus};
+use super::player::TrackState;
+use super::{App, LibraryCommand};
+
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+// How often library folders are checked for reachability
+pub const VOLUME_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Playback that stopped because the track's volume went away.
+#[derive(Debug, Clone)]
+pub struct InterruptedPlayback {
+    pub path_id: LibraryPathId,
+    pub track: LibraryItem,
+    pub position: u64,
+}
+
+// Listing the folder, rather than `exists`, fails for stale network mounts too
+fn is_reachable(path: &Path) -> bool {
+    std::fs::read_dir(path).is_ok()
+}
+
+impl App {
+    /// Periodically checks in the background whether the library folders are
+    /// reachable. Unmounted shares and unplugged drives can block for a long
+    /// time, so the UI thread never touches them itself.
+    pub fn check_library_volumes(&mut self) {
+        if self
+            .last_volume_check
+            .is_some_and(|last| last.elapsed() < VOLUME_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_volume_check = Some(Instant::now());
+
+        // A check may still hang on an unresponsive share
+        if self.volume_check_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let Some(lib_cmd_tx) = self.library_cmd_tx.clone() else {
+            self.volume_check_running.store(false, Ordering::SeqCst);
+            return;
+        };
+
+        let paths: Vec<_> = self
+            .library
+            .paths()
+            .iter()
+            .filter(|p| p.status() == LibraryPathStatus::Imported)
+            .map(|p| (p.id(), p.path().clone()))
+            .collect();
+        let running = self.volume_check_running.clone();
+
+        std::thread::spawn(move || {
+            let availability = paths
+                .into_iter()
+                .map(|(id, path)| (id, is_reachable(&path)))
+                .collect();
+
+            let _ = lib_cmd_tx.send(LibraryCommand::PathAvailability(availability));
+            running.store(false, Ordering::SeqCst);
+        });
+    }
+
+    pub fn set_path_availability(&mut self, path_id: LibraryPathId, online: bool) {
+        if !self.library.set_path_online(path_id, online) {
+            return;
+        }
+
+        if online {
+            tracing::info!("Library path {:?} is back online", path_id);
+            self.resume_interrupted_playback(path_id);
+        } else {
+            tracing::warn!("Library path {:?} went offline", path_id);
+            self.interrupt_playback(path_id);
+        }
+    }
+
+    // Pauses playback of a track on a volume that just went away
+    fn interrupt_playback(&mut self, path_id: LibraryPathId) {
+        let Some(player) = &mut self.player else {
+            return;
+        };
+
+        let Some(track) = player
+            .selected_track
+            .clone()
+            .filter(|track| track.library_id() == path_id)
+        else {
+            return;
+        };
+
+        if matches!(player.track_state, TrackState::Playing) {
+            self.interrupted_playback = Some(InterruptedPlayback {
+                path_id,
+                track,
+                position: player.seek_to_timestamp,
+            });
+            player.pause();
+        }
+    }
+
+    fn resume_interrupted_playback(&mut self, path_id: LibraryPathId) {
+        if self
+            .interrupted_playback
+            .as_ref()
+            .is_none_or(|interrupted| interrupted.path_id != path_id)
+        {
+            return;
+        }
+
+        let Some(interrupted) = self.interrupted_playback.take() else {
+            return;
+        };
+
+        if let Some(player) = &mut self.player {
+            // The decoder lost its file, so load the track again
+            tracing::info!("Resuming interrupted playback at {}", interrupted.position);
+            player.select_track(Some(interrupted.track));
+            player.seek_to(interrupted.position);
+            player.play();
+        }
+    }
+
+    /// Called when the audio thread runs out of data. Returns true when that
+    /// happened because the track's volume disappeared rather than the track ending.
+    pub fn playback_lost_volume(&mut self) -> bool {
+        let Some(track) = self
+            .player
+            .as_ref()
+            .and_then(|player| player.selected_track.clone())
+        else {
+            return false;
+        };
+
+        if track.path().exists() {
+            return false;
+        }
+
+        let root_reachable = self
+            .library
+            .paths()
+            .iter()
+            .find(|p| p.id() == track.library_id())
+            .is_some_and(|p| is_reachable(p.path()));
+
+        if root_reachable {
+            // The file itself is gone, not the volume
+            return false;
+        }
+
+        self.set_path_availability(track.library_id(), false);
+        self.interrupted_playback.is_some()
+    }
+}
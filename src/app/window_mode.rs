@@ -0,0 +1,178 @@
+//! Where the window goes. The full window and the mini-player each keep their own
+//! size and position, which are saved so the next start opens where the last ended.
+
+use super::{App, DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+use eframe::egui::{self, Pos2, Vec2, ViewportBuilder, ViewportCommand, WindowLevel};
+use serde::{Deserialize, Serialize};
+
+pub const MINI_PLAYER_SIZE: [f32; 2] = [340.0, 104.0];
+
+// What the mini-player grows by to list the recent plays
+pub const MINI_RECENTS_HEIGHT: f32 = 180.0;
+
+/// Position and inner size of the window, in points.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub position: Option<[f32; 2]>,
+    pub size: [f32; 2],
+}
+
+impl WindowGeometry {
+    fn sized(size: [f32; 2]) -> Self {
+        Self {
+            position: None,
+            size,
+        }
+    }
+
+    /// The geometry of the window right now, None until the platform reported it.
+    pub fn current(ctx: &egui::Context) -> Option<Self> {
+        ctx.input(|input| {
+            let viewport = input.viewport();
+            let size = viewport.inner_rect?.size();
+            Some(Self {
+                position: viewport.outer_rect.map(|rect| [rect.min.x, rect.min.y]),
+                size: [size.x, size.y],
+            })
+        })
+    }
+
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.send_viewport_cmd(ViewportCommand::InnerSize(Vec2::from(self.size)));
+        if let Some(position) = self.position {
+            ctx.send_viewport_cmd(ViewportCommand::OuterPosition(Pos2::from(position)));
+        }
+    }
+}
+
+impl App {
+    /// Opens the window at the size and position of the last session.
+    pub fn startup_viewport(&self, builder: ViewportBuilder) -> ViewportBuilder {
+        let geometry = self.full_geometry.unwrap_or(WindowGeometry::sized([
+            DEFAULT_WINDOW_WIDTH,
+            DEFAULT_WINDOW_HEIGHT,
+        ]));
+        let builder = builder
+            .with_inner_size(geometry.size)
+            .with_maximized(self.is_maximized);
+        match geometry.position {
+            Some(position) => builder.with_position(position),
+            None => builder,
+        }
+    }
+
+    pub fn enter_mini_player(&mut self, ctx: &egui::Context) {
+        if self.mini_player {
+            return;
+        }
+        self.remember_window_geometry(ctx);
+        self.mini_player = true;
+
+        self.mini_geometry
+            .unwrap_or(WindowGeometry::sized(MINI_PLAYER_SIZE))
+            .apply(ctx);
+        self.apply_window_level(ctx);
+        self.save_state();
+    }
+
+    pub fn leave_mini_player(&mut self, ctx: &egui::Context) {
+        if !self.mini_player {
+            return;
+        }
+        self.remember_window_geometry(ctx);
+        self.mini_player = false;
+        self.mini_recents_open = false;
+
+        self.full_geometry
+            .unwrap_or(WindowGeometry::sized([
+                DEFAULT_WINDOW_WIDTH,
+                DEFAULT_WINDOW_HEIGHT,
+            ]))
+            .apply(ctx);
+        self.apply_window_level(ctx);
+        self.save_state();
+    }
+
+    /// Fills the screen with the artwork, track info and lyrics, for parties and kiosks.
+    pub fn enter_now_playing(&mut self, ctx: &egui::Context) {
+        self.remember_window_geometry(ctx);
+        self.now_playing_view = true;
+        ctx.send_viewport_cmd(ViewportCommand::Fullscreen(true));
+    }
+
+    pub fn leave_now_playing(&mut self, ctx: &egui::Context) {
+        self.now_playing_view = false;
+        ctx.send_viewport_cmd(ViewportCommand::Fullscreen(false));
+    }
+
+    /// Lists the recent plays under the mini-player, or hides them again.
+    pub fn toggle_mini_recents(&mut self, ctx: &egui::Context) {
+        let Some(geometry) = WindowGeometry::current(ctx) else {
+            return;
+        };
+        self.mini_recents_open = !self.mini_recents_open;
+        let grow = if self.mini_recents_open {
+            self.reload_recent_plays();
+            MINI_RECENTS_HEIGHT
+        } else {
+            -MINI_RECENTS_HEIGHT
+        };
+        ctx.send_viewport_cmd(ViewportCommand::InnerSize(Vec2::new(
+            geometry.size[0],
+            geometry.size[1] + grow,
+        )));
+    }
+
+    pub fn toggle_always_on_top(&mut self, ctx: &egui::Context) {
+        self.mini_always_on_top = !self.mini_always_on_top;
+        self.apply_window_level(ctx);
+        self.save_state();
+    }
+
+    /// Stores where the window of the current mode is. Called when leaving a mode and
+    /// before closing, in between the window reports sizes of both modes while resizing.
+    pub fn remember_window_geometry(&mut self, ctx: &egui::Context) {
+        let Some(geometry) = WindowGeometry::current(ctx) else {
+            return;
+        };
+        // The full screen size isn't one to come back to
+        if self.now_playing_view {
+            return;
+        }
+        if self.mini_player {
+            // The size without the recent plays, which start hidden
+            let mut geometry = geometry;
+            if self.mini_recents_open {
+                geometry.size[1] = (geometry.size[1] - MINI_RECENTS_HEIGHT).max(0.0);
+            }
+            self.mini_geometry = Some(geometry);
+            return;
+        }
+
+        if let Some(maximized) = ctx.input(|input| input.viewport().maximized) {
+            self.is_maximized = maximized;
+        }
+        // Keep the size to restore to, not the maximized or collapsed one
+        if self.is_maximized {
+            return;
+        }
+        self.full_geometry = Some(if self.show_library_and_playlist {
+            geometry
+        } else {
+            WindowGeometry {
+                size: self.full_geometry.map_or(geometry.size, |full| full.size),
+                ..geometry
+            }
+        });
+    }
+
+    // Only the mini-player floats, the full window stays with the others
+    fn apply_window_level(&self, ctx: &egui::Context) {
+        let level = if self.mini_player && self.mini_always_on_top {
+            WindowLevel::AlwaysOnTop
+        } else {
+            WindowLevel::Normal
+        };
+        ctx.send_viewport_cmd(ViewportCommand::WindowLevel(level));
+    }
+}
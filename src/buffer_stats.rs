@@ -0,0 +1,172 @@
+//! How the output's ring buffer keeps up: how full it is, how often the device found it
+//! empty and how often the decoder found it full. Published through atomics like the
+//! levels, so the audio callback never waits on the UI. A stream that ran dry repeatedly
+//! while the decoder was feeding it makes the next one open with a larger buffer.
+
+// Only the CPAL output has a ring buffer
+#![cfg_attr(all(target_os = "linux", feature = "pulseaudio"), allow(dead_code))]
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// Ring buffer length in samples a stream starts with
+const MIN_RING_LEN: usize = 8192;
+// Over a second of stereo at 48 kHz
+const MAX_RING_LEN: usize = MIN_RING_LEN * 8;
+// Underruns during one stream that make the next one larger
+const UNDERRUNS_TO_GROW: usize = 3;
+
+/// The buffer of the open output stream.
+pub static OUTPUT_BUFFER: BufferStats = BufferStats::new();
+
+pub struct BufferStats {
+    // Length of the current stream's buffer, 0 before the first one
+    len: AtomicUsize,
+    // Queued samples after the decoder last wrote
+    fill: AtomicUsize,
+    // Counted since the app started
+    underruns: AtomicUsize,
+    overflows: AtomicUsize,
+    // Counted since the current stream opened
+    stream_underruns: AtomicUsize,
+    // The decoder writes to the stream, it's not waiting for a track to start or load
+    feeding: AtomicBool,
+}
+
+/// A snapshot for the UI.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferReport {
+    pub len: usize,
+    pub fill: usize,
+    pub underruns: usize,
+    pub overflows: usize,
+}
+
+impl BufferReport {
+    pub fn fill_percent(&self) -> usize {
+        if self.len == 0 {
+            return 0;
+        }
+        (self.fill * 100 / self.len).min(100)
+    }
+}
+
+impl BufferStats {
+    pub const fn new() -> Self {
+        Self {
+            len: AtomicUsize::new(0),
+            fill: AtomicUsize::new(0),
+            underruns: AtomicUsize::new(0),
+            overflows: AtomicUsize::new(0),
+            stream_underruns: AtomicUsize::new(0),
+            feeding: AtomicBool::new(false),
+        }
+    }
+
+    /// The buffer length for a stream about to open, longer than the last stream's if
+    /// that one ran dry repeatedly.
+    pub fn open_stream(&self) -> usize {
+        let previous = self.len.load(Ordering::Relaxed);
+        let len = next_len(previous, self.stream_underruns.swap(0, Ordering::Relaxed));
+        if previous != 0 && len > previous {
+            tracing::info!(
+                "Output buffer ran dry repeatedly, growing it to {} samples",
+                len
+            );
+        }
+        self.len.store(len, Ordering::Relaxed);
+        self.fill.store(0, Ordering::Relaxed);
+        self.feeding.store(false, Ordering::Relaxed);
+        len
+    }
+
+    /// Whether the decoder is writing to the stream. Set by each write, cleared when a
+    /// track ended or the stream was flushed.
+    pub fn set_feeding(&self, feeding: bool) {
+        self.feeding.store(feeding, Ordering::Relaxed);
+    }
+
+    /// The device asked for more than was queued. Only counted while the decoder feeds
+    /// the stream, otherwise there is just nothing to play yet.
+    pub fn underrun(&self) {
+        if !self.feeding.load(Ordering::Relaxed) {
+            return;
+        }
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+        self.stream_underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The decoder had to wait for room.
+    pub fn overflow(&self) {
+        self.overflows.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_fill(&self, fill: usize) {
+        self.fill.store(fill, Ordering::Relaxed);
+    }
+
+    pub fn report(&self) -> BufferReport {
+        BufferReport {
+            len: self.len.load(Ordering::Relaxed),
+            fill: self.fill.load(Ordering::Relaxed),
+            underruns: self.underruns.load(Ordering::Relaxed),
+            overflows: self.overflows.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// Doubles the length after a stream with too many underruns, up to the maximum
+fn next_len(previous: usize, underruns: usize) -> usize {
+    let previous = previous.max(MIN_RING_LEN);
+    if underruns >= UNDERRUNS_TO_GROW {
+        (previous * 2).min(MAX_RING_LEN)
+    } else {
+        previous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_grow_after_repeated_underruns_up_to_a_limit() {
+        assert_eq!(next_len(0, 0), MIN_RING_LEN);
+        assert_eq!(next_len(MIN_RING_LEN, UNDERRUNS_TO_GROW - 1), MIN_RING_LEN);
+        assert_eq!(next_len(MIN_RING_LEN, UNDERRUNS_TO_GROW), MIN_RING_LEN * 2);
+        assert_eq!(next_len(MAX_RING_LEN, 100), MAX_RING_LEN);
+
+        let stats = BufferStats::new();
+        assert_eq!(stats.open_stream(), MIN_RING_LEN);
+        stats.set_feeding(true);
+        for _ in 0..UNDERRUNS_TO_GROW {
+            stats.underrun();
+        }
+        assert_eq!(stats.open_stream(), MIN_RING_LEN * 2);
+        // The count starts over with each stream
+        assert_eq!(stats.open_stream(), MIN_RING_LEN * 2);
+
+        stats.set_fill(MIN_RING_LEN);
+        let report = stats.report();
+        assert_eq!((report.fill_percent(), report.underruns), (50, 3));
+    }
+
+    #[test]
+    fn an_idle_stream_does_not_grow() {
+        let stats = BufferStats::new();
+        assert_eq!(stats.open_stream(), MIN_RING_LEN);
+
+        // Before the first write, at the end of a track and while the next one loads
+        for _ in 0..UNDERRUNS_TO_GROW * 10 {
+            stats.underrun();
+        }
+        stats.set_feeding(true);
+        stats.underrun();
+        stats.set_feeding(false);
+        for _ in 0..UNDERRUNS_TO_GROW * 10 {
+            stats.underrun();
+        }
+
+        assert_eq!(stats.report().underruns, 1);
+        assert_eq!(stats.open_stream(), MIN_RING_LEN);
+    }
+}
@@ -0,0 +1,269 @@
+//! Channel layout conversion applied to decoded audio before it reaches the output.
+//!
+//! Multichannel sources are folded down to stereo with the ITU-R BS.775 coefficients,
+//! mono sources are spread over both speakers. Mono output sums the stereo mix into
+//! both channels for setups with a single speaker.
+
+use serde::{Deserialize, Serialize};
+use symphonia::core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+
+// -3 dB, used for the centre and surround channels
+const MINUS_3_DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+// -6 dB, the LFE only adds weight and must not dominate the mix
+const MINUS_6_DB: f32 = 0.5;
+
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum ChannelMix {
+    /// Play the channels of the source as they are.
+    #[default]
+    Source,
+    /// Downmix multichannel and upmix mono sources to stereo.
+    Stereo,
+    /// The same stereo mix on both speakers.
+    Mono,
+}
+
+/// Left and right weights of each source channel, in the order of the planes of a
+/// buffer with these channels. The weights are scaled so a full scale signal on
+/// every channel can't clip.
+pub fn stereo_coefficients(channels: Channels) -> Vec<(f32, f32)> {
+    if channels.count() == 1 {
+        return vec![(1.0, 1.0)];
+    }
+
+    let left_side = Channels::FRONT_LEFT_CENTRE
+        | Channels::FRONT_LEFT_WIDE
+        | Channels::FRONT_LEFT_HIGH
+        | Channels::SIDE_LEFT
+        | Channels::REAR_LEFT
+        | Channels::REAR_LEFT_CENTRE
+        | Channels::TOP_FRONT_LEFT
+        | Channels::TOP_REAR_LEFT;
+    let right_side = Channels::FRONT_RIGHT_CENTRE
+        | Channels::FRONT_RIGHT_WIDE
+        | Channels::FRONT_RIGHT_HIGH
+        | Channels::SIDE_RIGHT
+        | Channels::REAR_RIGHT
+        | Channels::REAR_RIGHT_CENTRE
+        | Channels::TOP_FRONT_RIGHT
+        | Channels::TOP_REAR_RIGHT;
+
+    let mut coefficients: Vec<(f32, f32)> = channels
+        .iter()
+        .map(|channel| {
+            if channel == Channels::FRONT_LEFT {
+                (1.0, 0.0)
+            } else if channel == Channels::FRONT_RIGHT {
+                (0.0, 1.0)
+            } else if channel == Channels::LFE1 || channel == Channels::LFE2 {
+                (MINUS_6_DB, MINUS_6_DB)
+            } else if left_side.contains(channel) {
+                (MINUS_3_DB, 0.0)
+            } else if right_side.contains(channel) {
+                (0.0, MINUS_3_DB)
+            } else {
+                // Centre channels
+                (MINUS_3_DB, MINUS_3_DB)
+            }
+        })
+        .collect();
+
+    let left: f32 = coefficients.iter().map(|(left, _)| left).sum();
+    let right: f32 = coefficients.iter().map(|(_, right)| right).sum();
+    let peak = left.max(right);
+    if peak > 1.0 {
+        for (left, right) in coefficients.iter_mut() {
+            *left /= peak;
+            *right /= peak;
+        }
+    }
+
+    coefficients
+}
+
+/// Per-channel gain applied in the effect chain, for the balance and to even out
+/// headphones that are louder on one side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Balance {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl Default for Balance {
+    fn default() -> Self {
+        Self {
+            left: 1.0,
+            right: 1.0,
+        }
+    }
+}
+
+impl Balance {
+    /// `balance` goes from -1.0 (left only) to 1.0 (right only), the trims are in dB.
+    pub fn new(balance: f32, left_trim_db: f32, right_trim_db: f32) -> Self {
+        let balance = balance.clamp(-1.0, 1.0);
+        Self {
+            left: (1.0 - balance.max(0.0)) * 10f32.powf(left_trim_db / 20.0),
+            right: (1.0 + balance.min(0.0)) * 10f32.powf(right_trim_db / 20.0),
+        }
+    }
+
+    pub fn is_unity(&self) -> bool {
+        self.left == 1.0 && self.right == 1.0
+    }
+
+    /// Gain of an interleaved channel. Only the front pair is adjusted, a mono
+    /// stream plays on both speakers and is left alone.
+    pub fn gain(&self, channel: usize, channels: usize) -> f32 {
+        match (channel, channels) {
+            (_, 1) => 1.0,
+            (0, _) => self.left,
+            (1, _) => self.right,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Converts decoded buffers to the channel layout of the selected mix.
+pub struct ChannelMixer {
+    mode: ChannelMix,
+    source: Option<AudioBuffer<f32>>,
+    mixed: Option<AudioBuffer<f32>>,
+    coefficients: Vec<(f32, f32)>,
+}
+
+impl ChannelMixer {
+    pub fn new() -> Self {
+        Self {
+            mode: ChannelMix::Source,
+            source: None,
+            mixed: None,
+            coefficients: Vec::new(),
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: ChannelMix) {
+        self.mode = mode;
+    }
+
+    fn is_passthrough(&self, spec: &SignalSpec) -> bool {
+        let stereo = spec.channels == Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+        match self.mode {
+            ChannelMix::Source => true,
+            ChannelMix::Stereo => stereo,
+            ChannelMix::Mono => false,
+        }
+    }
+
+    /// Spec of the buffers `process` returns for decoded buffers of `spec`.
+    pub fn output_spec(&self, spec: SignalSpec) -> SignalSpec {
+        if self.is_passthrough(&spec) {
+            spec
+        } else {
+            SignalSpec::new(spec.rate, Channels::FRONT_LEFT | Channels::FRONT_RIGHT)
+        }
+    }
+
+    /// Mixes a decoded buffer, or returns it untouched when no conversion is needed.
+    pub fn process<'a>(&'a mut self, decoded: AudioBufferRef<'a>) -> AudioBufferRef<'a> {
+        let spec = *decoded.spec();
+        if self.is_passthrough(&spec) {
+            return decoded;
+        }
+
+        // Buffers are reused while the source layout and size stay the same
+        let capacity = decoded.capacity() as u64;
+        let reusable = self
+            .source
+            .as_ref()
+            .is_some_and(|buf| *buf.spec() == spec && buf.capacity() as u64 == capacity);
+        if !reusable {
+            self.source = Some(AudioBuffer::new(capacity, spec));
+            self.mixed = Some(AudioBuffer::new(capacity, self.output_spec(spec)));
+            self.coefficients = stereo_coefficients(spec.channels);
+        }
+
+        let source = self.source.as_mut().unwrap();
+        let mixed = self.mixed.as_mut().unwrap();
+        decoded.convert(source);
+
+        let frames = source.frames();
+        mixed.clear();
+        mixed.render_reserved(Some(frames));
+
+        let source_planes = source.planes();
+        let mut mixed_planes = mixed.planes_mut();
+        let [left, right] = mixed_planes.planes() else {
+            unreachable!("the mix is always stereo");
+        };
+
+        for frame in 0..frames {
+            let (mut l, mut r) = (0.0, 0.0);
+            for (plane, (left_weight, right_weight)) in
+                source_planes.planes().iter().zip(&self.coefficients)
+            {
+                l += plane[frame] * left_weight;
+                r += plane[frame] * right_weight;
+            }
+            if self.mode == ChannelMix::Mono {
+                l = (l + r) * 0.5;
+                r = l;
+            }
+            left[frame] = l;
+            right[frame] = r;
+        }
+        drop(mixed_planes);
+
+        mixed.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downmix_coefficients_keep_full_scale_from_clipping() {
+        let five_one = Channels::FRONT_LEFT
+            | Channels::FRONT_RIGHT
+            | Channels::FRONT_CENTRE
+            | Channels::LFE1
+            | Channels::REAR_LEFT
+            | Channels::REAR_RIGHT;
+        let coefficients = stereo_coefficients(five_one);
+        assert_eq!(coefficients.len(), 6);
+
+        let left: f32 = coefficients.iter().map(|(left, _)| left).sum();
+        let right: f32 = coefficients.iter().map(|(_, right)| right).sum();
+        assert!((left - 1.0).abs() < 1e-6);
+        assert!((right - 1.0).abs() < 1e-6);
+
+        // Front left stays louder than the centre, which is shared by both sides
+        assert!(coefficients[0].0 > coefficients[2].0);
+        assert_eq!(coefficients[2].0, coefficients[2].1);
+
+        assert_eq!(
+            stereo_coefficients(Channels::FRONT_LEFT | Channels::FRONT_RIGHT),
+            vec![(1.0, 0.0), (0.0, 1.0)]
+        );
+        assert_eq!(stereo_coefficients(Channels::FRONT_LEFT), vec![(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn balance_attenuates_the_opposite_side_and_applies_trims() {
+        assert!(Balance::new(0.0, 0.0, 0.0).is_unity());
+
+        let right = Balance::new(0.5, 0.0, 0.0);
+        assert_eq!((right.left, right.right), (0.5, 1.0));
+
+        let left_only = Balance::new(-2.0, 0.0, 0.0);
+        assert_eq!((left_only.left, left_only.right), (1.0, 0.0));
+
+        let trimmed = Balance::new(0.0, -6.0, 0.0);
+        assert!((trimmed.left - 0.501).abs() < 1e-3);
+        assert_eq!(trimmed.gain(0, 1), 1.0);
+        assert_eq!(trimmed.gain(2, 6), 1.0);
+    }
+}
@@ -0,0 +1,163 @@
+//! Bauer-style crossfeed for headphones. Speakers reach both ears, the far one a little
+//! later and with less treble, while headphones keep the channels apart. Feeding a
+//! delayed, low-passed part of each channel into the other makes wide stereo mixes less
+//! tiring. The direct signal gets the treble back, so mono content keeps its tone.
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::{Effect, EffectConfig, EffectKind};
+
+// How much later the sound reaches the far ear
+const DELAY_SECONDS: f32 = 0.0003;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CrossfeedLevel {
+    Light,
+    #[default]
+    Medium,
+    Strong,
+}
+
+impl CrossfeedLevel {
+    pub fn all() -> [CrossfeedLevel; 3] {
+        [
+            CrossfeedLevel::Light,
+            CrossfeedLevel::Medium,
+            CrossfeedLevel::Strong,
+        ]
+    }
+
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            CrossfeedLevel::Light => "crossfeed_light",
+            CrossfeedLevel::Medium => "crossfeed_medium",
+            CrossfeedLevel::Strong => "crossfeed_strong",
+        }
+    }
+
+    // Cutoff of the crossfed part in Hz and how far below the direct signal it is in dB,
+    // after the common presets of Jan Meier, Chu Moy and bs2b
+    fn parameters(&self) -> (f32, f32) {
+        match self {
+            CrossfeedLevel::Light => (650.0, 9.5),
+            CrossfeedLevel::Medium => (700.0, 6.0),
+            CrossfeedLevel::Strong => (700.0, 4.5),
+        }
+    }
+}
+
+pub struct Crossfeed {
+    level: CrossfeedLevel,
+    rate: u32,
+    feed: f32,     // Linear gain of the crossfed part
+    lowpass: f32,  // One-pole coefficient for the cutoff at the current rate
+    low: [f32; 2], // Low-passed left and right
+    // Low-passed samples waiting to reach the other side
+    delayed: [Vec<f32>; 2],
+    delay_pos: usize,
+}
+
+impl Crossfeed {
+    pub fn new(level: CrossfeedLevel) -> Self {
+        let mut crossfeed = Self {
+            level,
+            rate: 0,
+            feed: 0.0,
+            lowpass: 0.0,
+            low: [0.0; 2],
+            delayed: [Vec::new(), Vec::new()],
+            delay_pos: 0,
+        };
+        crossfeed.set_level(level);
+        crossfeed
+    }
+
+    pub fn set_level(&mut self, level: CrossfeedLevel) {
+        self.level = level;
+        let (_, feed_db) = level.parameters();
+        self.feed = 10f32.powf(-feed_db / 20.0);
+        // Recomputed for the next buffer
+        self.rate = 0;
+    }
+
+    fn prepare(&mut self, rate: u32) {
+        if self.rate == rate {
+            return;
+        }
+        self.rate = rate;
+        let (cutoff, _) = self.level.parameters();
+        self.lowpass = (-2.0 * std::f32::consts::PI * cutoff / rate as f32).exp();
+        let delay = ((DELAY_SECONDS * rate as f32).round() as usize).max(1);
+        self.delayed = [vec![0.0; delay], vec![0.0; delay]];
+        self.delay_pos = 0;
+        self.low = [0.0; 2];
+    }
+}
+
+impl Effect for Crossfeed {
+    fn kind(&self) -> EffectKind {
+        EffectKind::Crossfeed
+    }
+
+    fn configure(&mut self, config: &EffectConfig) {
+        if let EffectConfig::Crossfeed(level) = config {
+            if *level != self.level {
+                self.set_level(*level);
+            }
+        }
+    }
+
+    fn process(&mut self, planes: &mut [&mut [f32]], rate: u32) {
+        // Only a stereo pair has sides to blend
+        let [left, right] = planes else {
+            return;
+        };
+        self.prepare(rate);
+
+        let normalize = 1.0 / (1.0 + self.feed);
+        for frame in 0..left.len() {
+            let input = [left[frame], right[frame]];
+            let mut output = [0.0; 2];
+            for side in 0..2 {
+                self.low[side] = input[side] + (self.low[side] - input[side]) * self.lowpass;
+                let high = input[side] - self.low[side];
+                let crossed = self.delayed[1 - side][self.delay_pos];
+                output[side] = (input[side] + self.feed * (high + crossed)) * normalize;
+            }
+            for side in 0..2 {
+                self.delayed[side][self.delay_pos] = self.low[side];
+            }
+            self.delay_pos = (self.delay_pos + 1) % self.delayed[0].len();
+
+            left[frame] = output[0];
+            right[frame] = output[1];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mono_keeps_its_level() {
+        let mut left = vec![0.5; 4096];
+        let mut right = vec![0.5; 4096];
+        Crossfeed::new(CrossfeedLevel::Strong).process(&mut [&mut left, &mut right], 44100);
+
+        assert!((left[4095] - 0.5).abs() < 1e-3);
+        assert!((right[4095] - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn bass_of_one_side_reaches_the_other() {
+        let mut left = vec![1.0; 4096];
+        let mut right = vec![0.0; 4096];
+        Crossfeed::new(CrossfeedLevel::Medium).process(&mut [&mut left, &mut right], 44100);
+
+        // Steady, i.e. all bass, after the filter settled
+        let feed = 10f32.powf(-6.0 / 20.0);
+        assert!((right[4095] - feed / (1.0 + feed)).abs() < 1e-3);
+        assert!(left[4095] < 1.0);
+    }
+}
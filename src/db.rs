@@ -1,14 +1,28 @@
 use rusqlite::{Connection, Error, ErrorCode, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ulid::Ulid;
+
+// How long a write waits for another connection's before failing with "database is locked"
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+type WriteJob = Box<dyn FnOnce(&Arc<Mutex<Connection>>) + Send>;
 
 pub struct Database {
     connection: Arc<Mutex<Connection>>,
+    // Long writes queue up here and run in order on their own thread
+    writer: Mutex<Option<(Sender<WriteJob>, JoinHandle<()>)>>,
 }
 
 impl Database {
     // The current schema version - increment this when making schema changes
-    const SCHEMA_VERSION: i32 = 2;
+    const SCHEMA_VERSION: i32 = 24;
+
+    // Databases older than this are rebuilt from scratch, newer ones are migrated in place
+    const MIN_MIGRATABLE_VERSION: i32 = 2;
 
     pub fn new() -> Result<Self> {
         // Get the app's configuration directory
@@ -27,17 +41,145 @@ impl Database {
             }
         }
 
+        // A restored backup takes over before the database is opened
+        Self::apply_pending_restore(&db_path);
+
         // Create or open the database connection
-        let connection = Connection::open(&db_path)?;
+        Self::from_connection(Connection::open(&db_path)?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self> {
+        Self::configure(&connection)?;
 
         // Initialize the database schema
         Self::initialize_schema(&connection)?;
 
+        let connection = Arc::new(Mutex::new(connection));
+        let writer = Self::start_writer(connection.clone());
         Ok(Self {
-            connection: Arc::new(Mutex::new(connection)),
+            connection,
+            writer: Mutex::new(Some(writer)),
         })
     }
 
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    // With write-ahead logging readers don't wait for a writer, and writers on other
+    // connections wait their turn instead of failing right away
+    fn configure(connection: &Connection) -> Result<()> {
+        let _mode: String =
+            connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        connection.busy_timeout(BUSY_TIMEOUT)
+    }
+
+    fn start_writer(connection: Arc<Mutex<Connection>>) -> (Sender<WriteJob>, JoinHandle<()>) {
+        let (job_tx, job_rx) = channel::<WriteJob>();
+        let handle = std::thread::spawn(move || {
+            for job in job_rx {
+                job(&connection);
+            }
+        });
+        (job_tx, handle)
+    }
+
+    /// Runs a write on the writer thread after the ones queued before it, so saving a
+    /// large library doesn't stall the UI. After `flush` writes run right away.
+    pub fn write_in_background(&self, job: impl FnOnce(&Arc<Mutex<Connection>>) + Send + 'static) {
+        let writer = self.writer.lock().unwrap();
+        let job: WriteJob = Box::new(job);
+        let job = match writer.as_ref() {
+            Some((job_tx, _)) => match job_tx.send(job) {
+                Ok(()) => return,
+                Err(unsent) => unsent.0,
+            },
+            None => job,
+        };
+        drop(writer);
+        job(&self.connection);
+    }
+
+    /// Waits for the queued writes, for writes that depend on them.
+    pub fn wait_for_writes(&self) {
+        let (done_tx, done_rx) = channel();
+        self.write_in_background(move |_| {
+            let _ = done_tx.send(());
+        });
+        let _ = done_rx.recv();
+    }
+
+    /// Finishes the queued writes and stops the writer thread, before the app exits.
+    pub fn flush(&self) {
+        let writer = self.writer.lock().unwrap().take();
+        if let Some((job_tx, handle)) = writer {
+            drop(job_tx);
+            if handle.join().is_err() {
+                tracing::error!("Database writer thread panicked");
+            }
+        }
+    }
+
+    /// Where a verified backup waits for the next start to replace the database.
+    pub fn pending_restore_path() -> Result<PathBuf> {
+        Ok(Self::get_database_path()?.with_extension("db.restore"))
+    }
+
+    fn apply_pending_restore(db_path: &Path) {
+        let Ok(restore_path) = Self::pending_restore_path() else {
+            return;
+        };
+        if !restore_path.exists() {
+            return;
+        }
+
+        // Journal files of the old database would be replayed into the restored one
+        for suffix in ["-wal", "-shm", "-journal"] {
+            let mut journal = db_path.as_os_str().to_owned();
+            journal.push(suffix);
+            let _ = std::fs::remove_file(journal);
+        }
+
+        match std::fs::rename(&restore_path, db_path) {
+            Ok(()) => tracing::info!("Restored database from backup"),
+            Err(e) => tracing::error!("Failed to restore database from backup: {}", e),
+        }
+    }
+
+    /// Writes a consistent copy of the database to `path`, which must not exist yet.
+    /// Queued writes are in the copy.
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        self.wait_for_writes();
+        let conn = self.connection.lock().unwrap();
+        conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Checks that the file at `path` is an intact database of this app.
+    pub fn verify_file(path: &Path) -> Result<()> {
+        let conn = Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+        if integrity != "ok" {
+            return Err(Error::SqliteFailure(
+                rusqlite::ffi::Error {
+                    code: ErrorCode::DatabaseCorrupt,
+                    extended_code: 0,
+                },
+                Some(integrity),
+            ));
+        }
+
+        // Fails on databases of other programs
+        let _version: i32 =
+            conn.query_row("SELECT version FROM schema_version LIMIT 1", [], |row| {
+                row.get(0)
+            })?;
+        Ok(())
+    }
+
     fn get_database_path() -> Result<PathBuf> {
         let config_dir = confy::get_configuration_file_path("bird-player", None)
             .map_err(|_| rusqlite::Error::ExecuteReturnedResults)?
@@ -69,6 +211,10 @@ impl Database {
             return Ok(());
         }
 
+        if current_version >= Self::MIN_MIGRATABLE_VERSION {
+            return Self::migrate(connection, current_version);
+        }
+
         // Drop existing tables if they exist to reset the schema
         Self::drop_tables_if_exist(connection)?;
 
@@ -78,12 +224,52 @@ impl Database {
                 id INTEGER PRIMARY KEY,
                 path TEXT NOT NULL,
                 status INTEGER NOT NULL,
-                display_name TEXT NOT NULL
+                display_name TEXT NOT NULL,
+                priority INTEGER NOT NULL DEFAULT 0,
+                exclude_patterns TEXT NOT NULL DEFAULT ''
             )",
             [],
         )?;
 
-        // Create the library_items table
+        Self::create_library_items_table(connection)?;
+        Self::create_pictures_table(connection)?;
+
+        // Create the playlists table
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS playlists (
+                id INTEGER PRIMARY KEY,
+                name TEXT
+            )",
+            [],
+        )?;
+
+        Self::create_playlist_items_table(connection)?;
+        Self::create_resume_positions_table(connection)?;
+        Self::create_bookmarks_table(connection)?;
+        Self::create_play_history_table(connection)?;
+        Self::create_labels_tables(connection)?;
+        Self::create_track_notes_table(connection)?;
+        Self::create_favorites_table(connection)?;
+        Self::create_indexes(connection)?;
+
+        Self::set_schema_version(connection, Self::SCHEMA_VERSION)
+    }
+
+    // Playlists are read by id in position order, pictures by their item
+    fn create_indexes(connection: &Connection) -> Result<()> {
+        connection.execute(
+            "CREATE INDEX IF NOT EXISTS idx_playlist_items_playlist
+             ON playlist_items (playlist_id, position)",
+            [],
+        )?;
+        connection.execute(
+            "CREATE INDEX IF NOT EXISTS idx_pictures_item ON pictures (library_item_id)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn create_library_items_table(connection: &Connection) -> Result<()> {
         connection.execute(
             "CREATE TABLE IF NOT EXISTS library_items (
                 key TEXT PRIMARY KEY,
@@ -96,12 +282,34 @@ impl Database {
                 genre TEXT,
                 track_number INTEGER,
                 lyrics TEXT,
-                FOREIGN KEY (library_path_id) REFERENCES library_paths (id)
+                track_gain REAL,
+                track_peak REAL,
+                album_gain REAL,
+                album_peak REAL,
+                album_artist TEXT,
+                artists TEXT,
+                added_at INTEGER,
+                modified_at INTEGER,
+                codec TEXT,
+                bitrate INTEGER,
+                sample_rate INTEGER,
+                bit_depth INTEGER,
+                channels INTEGER,
+                preamp_db REAL,
+                play_count INTEGER,
+                rating INTEGER,
+                skip_count INTEGER,
+                explicit INTEGER,
+                audio_track INTEGER,
+                FOREIGN KEY (library_path_id) REFERENCES library_paths (id) ON DELETE CASCADE
             )",
             [],
         )?;
 
-        // Create the pictures table
+        Ok(())
+    }
+
+    fn create_pictures_table(connection: &Connection) -> Result<()> {
         connection.execute(
             "CREATE TABLE IF NOT EXISTS pictures (
                 id INTEGER PRIMARY KEY,
@@ -110,38 +318,426 @@ impl Database {
                 picture_type INTEGER NOT NULL,
                 description TEXT NOT NULL,
                 file_path TEXT NOT NULL,
-                FOREIGN KEY (library_item_id) REFERENCES library_items (key)
+                FOREIGN KEY (library_item_id) REFERENCES library_items (key) ON DELETE CASCADE
             )",
             [],
         )?;
 
-        // Create the playlists table
+        Ok(())
+    }
+
+    fn create_playlist_items_table(connection: &Connection) -> Result<()> {
         connection.execute(
-            "CREATE TABLE IF NOT EXISTS playlists (
+            "CREATE TABLE IF NOT EXISTS playlist_items (
                 id INTEGER PRIMARY KEY,
-                name TEXT
+                playlist_id INTEGER NOT NULL,
+                library_item_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                FOREIGN KEY (playlist_id) REFERENCES playlists (id) ON DELETE CASCADE,
+                FOREIGN KEY (library_item_id) REFERENCES library_items (key) ON DELETE CASCADE
             )",
             [],
         )?;
 
-        // Create the playlist_items table (mapping tracks to playlists)
+        Ok(())
+    }
+
+    fn create_resume_positions_table(connection: &Connection) -> Result<()> {
         connection.execute(
-            "CREATE TABLE IF NOT EXISTS playlist_items (
+            "CREATE TABLE IF NOT EXISTS resume_positions (
+                library_item_id TEXT PRIMARY KEY,
+                position INTEGER NOT NULL,
+                FOREIGN KEY (library_item_id) REFERENCES library_items (key) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    fn create_bookmarks_table(connection: &Connection) -> Result<()> {
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS bookmarks (
                 id INTEGER PRIMARY KEY,
-                playlist_id INTEGER NOT NULL,
                 library_item_id TEXT NOT NULL,
-                position INTEGER NOT NULL,
-                FOREIGN KEY (playlist_id) REFERENCES playlists (id),
-                FOREIGN KEY (library_item_id) REFERENCES library_items (key)
+                name TEXT NOT NULL,
+                position_ms INTEGER NOT NULL,
+                FOREIGN KEY (library_item_id) REFERENCES library_items (key) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    fn create_play_history_table(connection: &Connection) -> Result<()> {
+        // Title and artist are copied so entries survive removing the track from the library
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS play_history (
+                id INTEGER PRIMARY KEY,
+                library_item_id TEXT NOT NULL,
+                path TEXT NOT NULL,
+                title TEXT,
+                artist TEXT,
+                played_at INTEGER NOT NULL,
+                listened_ms INTEGER NOT NULL DEFAULT 0
             )",
             [],
         )?;
 
-        // Update schema version
+        Ok(())
+    }
+
+    fn create_labels_tables(connection: &Connection) -> Result<()> {
+        // Colors are stored as 0xRRGGBB
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS labels (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                color INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS track_labels (
+                library_item_id TEXT NOT NULL,
+                label_id INTEGER NOT NULL,
+                PRIMARY KEY (library_item_id, label_id),
+                FOREIGN KEY (library_item_id) REFERENCES library_items (key) ON DELETE CASCADE,
+                FOREIGN KEY (label_id) REFERENCES labels (id) ON DELETE CASCADE
+            );
+            CREATE TABLE IF NOT EXISTS playlist_labels (
+                playlist_id INTEGER NOT NULL,
+                label_id INTEGER NOT NULL,
+                PRIMARY KEY (playlist_id, label_id),
+                FOREIGN KEY (playlist_id) REFERENCES playlists (id) ON DELETE CASCADE,
+                FOREIGN KEY (label_id) REFERENCES labels (id) ON DELETE CASCADE
+            );",
+        )
+    }
+
+    fn create_track_notes_table(connection: &Connection) -> Result<()> {
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS track_notes (
+                library_item_id TEXT PRIMARY KEY,
+                note TEXT NOT NULL,
+                FOREIGN KEY (library_item_id) REFERENCES library_items (key) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn create_favorites_table(connection: &Connection) -> Result<()> {
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS favorites (
+                library_item_id TEXT PRIMARY KEY,
+                added_at INTEGER NOT NULL,
+                FOREIGN KEY (library_item_id) REFERENCES library_items (key) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Applies the incremental migrations needed to bring `from_version` up to date
+    /// without losing the user's library. They run in one transaction, so a migration
+    /// that fails leaves the database at its old version and is retried whole.
+    fn migrate(connection: &Connection, from_version: i32) -> Result<()> {
+        // Renaming a table mustn't rewrite what the other tables refer to, see
+        // `add_cascading_deletes`. The foreign keys pragma does nothing inside a
+        // transaction, so both are set around it.
+        connection.execute_batch("PRAGMA foreign_keys = OFF; PRAGMA legacy_alter_table = ON;")?;
+        let result = connection.unchecked_transaction().and_then(|tx| {
+            Self::apply_migrations(&tx, from_version)?;
+            tx.commit()
+        });
+        connection.execute_batch("PRAGMA legacy_alter_table = OFF; PRAGMA foreign_keys = ON;")?;
+        result
+    }
+
+    fn apply_migrations(connection: &Connection, from_version: i32) -> Result<()> {
+        if from_version < 3 {
+            // ReplayGain values, appended at the end so `SELECT li.*` indices stay valid
+            for column in ["track_gain", "track_peak", "album_gain", "album_peak"] {
+                connection.execute(
+                    &format!("ALTER TABLE library_items ADD COLUMN {} REAL", column),
+                    [],
+                )?;
+            }
+        }
+
+        if from_version < 4 {
+            Self::create_resume_positions_table(connection)?;
+        }
+
+        if from_version < 5 {
+            Self::create_bookmarks_table(connection)?;
+        }
+
+        if from_version < 6 {
+            Self::create_play_history_table(connection)?;
+        }
+
+        if from_version < 7 {
+            // Album artist and the separate artists of multi-artist tracks
+            for column in ["album_artist", "artists"] {
+                connection.execute(
+                    &format!("ALTER TABLE library_items ADD COLUMN {} TEXT", column),
+                    [],
+                )?;
+            }
+        }
+
+        if from_version < 8 {
+            // Import time in unix seconds, unknown for tracks imported before
+            connection.execute("ALTER TABLE library_items ADD COLUMN added_at INTEGER", [])?;
+        }
+
+        if from_version < 9 {
+            // File modification time in unix seconds, filled in by the next resync
+            connection.execute(
+                "ALTER TABLE library_items ADD COLUMN modified_at INTEGER",
+                [],
+            )?;
+        }
+
+        if from_version < 10 {
+            // Audio format, filled in by the next resync
+            for (column, sql_type) in [
+                ("codec", "TEXT"),
+                ("bitrate", "INTEGER"),
+                ("sample_rate", "INTEGER"),
+                ("bit_depth", "INTEGER"),
+                ("channels", "INTEGER"),
+            ] {
+                connection.execute(
+                    &format!(
+                        "ALTER TABLE library_items ADD COLUMN {} {}",
+                        column, sql_type
+                    ),
+                    [],
+                )?;
+            }
+        }
+
+        if from_version < 11 {
+            // Per-track volume offset, unset for every track until it's changed
+            connection.execute("ALTER TABLE library_items ADD COLUMN preamp_db REAL", [])?;
+        }
+
+        // Databases from before version 6 just got the history table with the column
+        if (6..12).contains(&from_version) {
+            // How long each play was listened to, unknown for earlier plays
+            connection.execute(
+                "ALTER TABLE play_history ADD COLUMN listened_ms INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        if from_version < 13 {
+            // Play counts and star ratings, also filled in by imports from other players
+            for column in ["play_count", "rating"] {
+                connection.execute(
+                    &format!("ALTER TABLE library_items ADD COLUMN {} INTEGER", column),
+                    [],
+                )?;
+            }
+        }
+
+        if from_version < 14 {
+            // Times a track was skipped early, see `App::skip_track`
+            connection.execute(
+                "ALTER TABLE library_items ADD COLUMN skip_count INTEGER",
+                [],
+            )?;
+        }
+
+        if from_version < 15 {
+            // Explicit content marks, hidden by the clean filter. Tracks scanned before
+            // count as clean until they're rescanned.
+            connection.execute("ALTER TABLE library_items ADD COLUMN explicit INTEGER", [])?;
+        }
+
+        if from_version < 16 {
+            // Which library path copies of a file are taken from
+            connection.execute(
+                "ALTER TABLE library_paths ADD COLUMN priority INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        if from_version < 17 {
+            // Files the importer skips, one glob per line
+            connection.execute(
+                "ALTER TABLE library_paths ADD COLUMN exclude_patterns TEXT NOT NULL DEFAULT ''",
+                [],
+            )?;
+        }
+
+        if from_version < 18 {
+            Self::create_indexes(connection)?;
+        }
+
+        if from_version < 19 {
+            Self::migrate_track_keys(connection)?;
+        }
+
+        if from_version < 20 {
+            Self::add_cascading_deletes(connection)?;
+        }
+
+        // Databases from before version 20 got the column with the rebuilt table
+        if from_version == 20 {
+            // Which audio track of a file with several plays, the first one until picked
+            connection.execute(
+                "ALTER TABLE library_items ADD COLUMN audio_track INTEGER",
+                [],
+            )?;
+        }
+
+        if from_version < 22 {
+            Self::create_labels_tables(connection)?;
+        }
+
+        if from_version < 23 {
+            Self::create_track_notes_table(connection)?;
+        }
+
+        if from_version < 24 {
+            Self::create_favorites_table(connection)?;
+        }
+
+        Self::set_schema_version(connection, Self::SCHEMA_VERSION)
+    }
+
+    // Tracks used to be keyed by random numbers. Each gets a ULID from the time it was
+    // added, and every table referring to a track follows along.
+    fn migrate_track_keys(connection: &Connection) -> Result<()> {
+        // Parents and children are renamed one table at a time, they only match again at the end
+        connection.execute_batch(
+            "PRAGMA defer_foreign_keys = ON;
+             CREATE TEMP TABLE key_map (old TEXT PRIMARY KEY, new TEXT NOT NULL);",
+        )?;
+
+        {
+            let mut select = connection.prepare("SELECT key, added_at FROM library_items")?;
+            let mut insert =
+                connection.prepare("INSERT INTO key_map (old, new) VALUES (?1, ?2)")?;
+            let rows = select.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?))
+            })?;
+            for row in rows {
+                let (old, added_at) = row?;
+                let added_at = added_at
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64))
+                    .unwrap_or_else(SystemTime::now);
+                insert.execute(rusqlite::params![
+                    old,
+                    Ulid::from_datetime(added_at).to_string()
+                ])?;
+            }
+        }
+
+        for table in [
+            "library_items",
+            "pictures",
+            "playlist_items",
+            "resume_positions",
+            "bookmarks",
+            "play_history",
+        ] {
+            let column = if table == "library_items" {
+                "key"
+            } else {
+                "library_item_id"
+            };
+            connection.execute(
+                &format!(
+                    "UPDATE {table} SET {column} = (SELECT new FROM key_map WHERE old = {table}.{column})
+                     WHERE {column} IN (SELECT old FROM key_map)"
+                ),
+                [],
+            )?;
+        }
+
+        connection.execute("DROP TABLE key_map", [])?;
+        Ok(())
+    }
+
+    // Rows referring to removed tracks and playlists used to stay behind. The tables get
+    // foreign keys that delete them along, and the rows already left dangling are dropped.
+    // Runs in the migration's transaction, with foreign keys off and legacy renames on.
+    fn add_cascading_deletes(connection: &Connection) -> Result<()> {
+        type CreateTable = fn(&Connection) -> Result<()>;
+        // Each table with the rows worth keeping, parents before their children
+        let tables: [(&str, CreateTable, &str); 5] = [
+            (
+                "library_items",
+                Self::create_library_items_table,
+                "library_path_id IN (SELECT id FROM library_paths)",
+            ),
+            (
+                "pictures",
+                Self::create_pictures_table,
+                // Saving the library used to add the pictures once more every time
+                "library_item_id IN (SELECT key FROM library_items)
+                 AND id IN (SELECT MIN(id) FROM old_pictures
+                            GROUP BY library_item_id, picture_type, file_path)",
+            ),
+            (
+                "playlist_items",
+                Self::create_playlist_items_table,
+                "playlist_id IN (SELECT id FROM playlists)
+                 AND library_item_id IN (SELECT key FROM library_items)",
+            ),
+            (
+                "resume_positions",
+                Self::create_resume_positions_table,
+                "library_item_id IN (SELECT key FROM library_items)",
+            ),
+            (
+                "bookmarks",
+                Self::create_bookmarks_table,
+                "library_item_id IN (SELECT key FROM library_items)",
+            ),
+        ];
+
+        for (table, create_table, keep) in tables {
+            connection.execute(&format!("ALTER TABLE {table} RENAME TO old_{table}"), [])?;
+            create_table(connection)?;
+
+            // Columns added after this migration are left empty
+            let columns = {
+                let mut stmt = connection.prepare(
+                    "SELECT name FROM pragma_table_info(?1)
+                     WHERE name IN (SELECT name FROM pragma_table_info(?2))",
+                )?;
+                let names = stmt.query_map([table, &format!("old_{table}")], |row| {
+                    row.get::<_, String>(0)
+                })?;
+                names.collect::<Result<Vec<_>>>()?.join(", ")
+            };
+            let kept = connection.execute(
+                &format!("INSERT INTO {table} ({columns}) SELECT {columns} FROM old_{table} WHERE {keep}"),
+                [],
+            )?;
+            let total: usize =
+                connection.query_row(&format!("SELECT COUNT(*) FROM old_{table}"), [], |row| {
+                    row.get(0)
+                })?;
+            if kept < total {
+                tracing::info!("Dropped {} dangling rows from {}", total - kept, table);
+            }
+
+            connection.execute(&format!("DROP TABLE old_{table}"), [])?;
+        }
+        // The indexes went with the old tables
+        Self::create_indexes(connection)
+    }
+
+    fn set_schema_version(connection: &Connection, version: i32) -> Result<()> {
         connection.execute("DELETE FROM schema_version", [])?;
         connection.execute(
             "INSERT INTO schema_version (version) VALUES (?1)",
-            rusqlite::params![Self::SCHEMA_VERSION],
+            rusqlite::params![version],
         )?;
 
         Ok(())
@@ -150,6 +746,14 @@ impl Database {
     fn drop_tables_if_exist(connection: &Connection) -> Result<()> {
         // Drop tables in the reverse order of their dependency
         let tables = [
+            "favorites",
+            "track_notes",
+            "playlist_labels",
+            "track_labels",
+            "labels",
+            "play_history",
+            "bookmarks",
+            "resume_positions",
             "playlist_items",
             "playlists",
             "pictures",
@@ -168,3 +772,288 @@ impl Database {
         self.connection.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn background_writes_run_in_order() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        db.connection()
+            .lock()
+            .unwrap()
+            .execute("CREATE TABLE log (entry INTEGER)", [])
+            .unwrap();
+
+        let log = |entry: i32| {
+            move |conn: &Arc<Mutex<Connection>>| {
+                conn.lock()
+                    .unwrap()
+                    .execute("INSERT INTO log VALUES (?1)", [entry])
+                    .unwrap();
+            }
+        };
+        let entries = |db: &Database| -> Vec<i32> {
+            let conn = db.connection();
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare("SELECT entry FROM log").unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.map(|row| row.unwrap()).collect()
+        };
+
+        for entry in 0..3 {
+            db.write_in_background(log(entry));
+        }
+        db.wait_for_writes();
+        assert_eq!(entries(&db), vec![0, 1, 2]);
+
+        // Once flushed, writes happen before the call returns
+        db.write_in_background(log(3));
+        db.flush();
+        db.write_in_background(log(4));
+        assert_eq!(entries(&db), vec![0, 1, 2, 3, 4]);
+
+        let foreign_keys: bool = db
+            .connection()
+            .lock()
+            .unwrap()
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .unwrap();
+        assert!(foreign_keys);
+    }
+
+    #[test]
+    fn track_keys_become_ulids_everywhere() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let conn = db.connection();
+        let conn = conn.lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO library_paths (id, path, status, display_name) VALUES (1, '/music', 0, 'music');
+             INSERT INTO library_items (key, library_path_id, path, added_at)
+                 VALUES ('42', 1, '/music/a.mp3', 1000), ('7', 1, '/music/b.mp3', 2000);
+             INSERT INTO playlists (id, name) VALUES (1, 'mix');
+             INSERT INTO playlist_items (playlist_id, library_item_id, position) VALUES (1, '7', 0);
+             INSERT INTO bookmarks (library_item_id, name, position_ms) VALUES ('42', 'intro', 0);",
+        )
+        .unwrap();
+
+        Database::migrate(&conn, 18).unwrap();
+
+        let key_of = |path: &str| -> Ulid {
+            let key: String = conn
+                .query_row(
+                    "SELECT key FROM library_items WHERE path = ?1",
+                    [path],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            key.parse().unwrap()
+        };
+        let a = key_of("/music/a.mp3");
+        let b = key_of("/music/b.mp3");
+        // Ordered by when they were added
+        assert!(a < b);
+        assert_eq!(a.timestamp_ms(), 1_000_000);
+
+        let in_playlist: String = conn
+            .query_row("SELECT library_item_id FROM playlist_items", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(in_playlist, b.to_string());
+        let bookmarked: String = conn
+            .query_row("SELECT library_item_id FROM bookmarks", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(bookmarked, a.to_string());
+    }
+
+    #[test]
+    fn version_20_databases_get_the_audio_track_column() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let conn = db.connection();
+        let conn = conn.lock().unwrap();
+        conn.execute_batch(
+            "ALTER TABLE library_items DROP COLUMN audio_track;
+             INSERT INTO library_paths (id, path, status, display_name) VALUES (1, '/music', 1, 'music');
+             INSERT INTO library_items (key, library_path_id, path) VALUES ('a', 1, '/music/a.mkv');",
+        )
+        .unwrap();
+
+        Database::migrate(&conn, 20).unwrap();
+        let audio_track: Option<u32> = conn
+            .query_row("SELECT audio_track FROM library_items", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(audio_track, None);
+    }
+
+    #[test]
+    fn labels_go_along_with_their_tracks_and_playlists() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let conn = db.connection();
+        let conn = conn.lock().unwrap();
+        conn.execute_batch(
+            "DROP TABLE playlist_labels; DROP TABLE track_labels; DROP TABLE labels;",
+        )
+        .unwrap();
+        Database::migrate(&conn, 21).unwrap();
+
+        let count = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        conn.execute_batch(
+            "INSERT INTO library_paths (id, path, status, display_name) VALUES (1, '/music', 1, 'music');
+             INSERT INTO library_items (key, library_path_id, path) VALUES ('a', 1, '/music/a.mp3');
+             INSERT INTO playlists (id, name) VALUES (1, 'mix');
+             INSERT INTO labels (id, name, color) VALUES (1, 'workout', 16711680), (2, 'chill', 255);
+             INSERT INTO track_labels (library_item_id, label_id) VALUES ('a', 1), ('a', 2);
+             INSERT INTO playlist_labels (playlist_id, label_id) VALUES (1, 1);",
+        )
+        .unwrap();
+
+        conn.execute("DELETE FROM labels WHERE id = 1", []).unwrap();
+        assert_eq!((count("track_labels"), count("playlist_labels")), (1, 0));
+        conn.execute("DELETE FROM library_items", []).unwrap();
+        assert_eq!((count("track_labels"), count("labels")), (0, 1));
+    }
+
+    #[test]
+    fn version_22_databases_get_track_notes_and_favorites() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let conn = db.connection();
+        let conn = conn.lock().unwrap();
+        conn.execute_batch("DROP TABLE track_notes; DROP TABLE favorites;")
+            .unwrap();
+        Database::migrate(&conn, 22).unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO library_paths (id, path, status, display_name) VALUES (1, '/music', 1, 'music');
+             INSERT INTO library_items (key, library_path_id, path) VALUES ('a', 1, '/music/a.mp3');
+             INSERT INTO track_notes (library_item_id, note) VALUES ('a', 'heard at a wedding');
+             INSERT INTO favorites (library_item_id, added_at) VALUES ('a', 0);",
+        )
+        .unwrap();
+        conn.execute("DELETE FROM library_items", []).unwrap();
+        for table in ["track_notes", "favorites"] {
+            let count: i64 = conn
+                .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(count, 0, "{table}");
+        }
+    }
+
+    #[test]
+    fn removed_tracks_take_their_rows_along() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let conn = db.connection();
+        let conn = conn.lock().unwrap();
+        let count = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+
+        // Left dangling before there were foreign keys to prevent it
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+             INSERT INTO library_paths (id, path, status, display_name) VALUES (1, '/music', 1, 'music');
+             INSERT INTO library_items (key, library_path_id, path) VALUES ('a', 1, '/music/a.mp3');
+             INSERT INTO library_items (key, library_path_id, path) VALUES ('gone', 2, '/old/b.mp3');
+             INSERT INTO pictures (library_item_id, mime_type, picture_type, description, file_path)
+                 VALUES ('a', 'image/jpeg', 3, '', 'a.jpg'), ('a', 'image/jpeg', 3, '', 'a.jpg');
+             INSERT INTO playlists (id, name) VALUES (1, 'mix');
+             INSERT INTO playlist_items (playlist_id, library_item_id, position)
+                 VALUES (1, 'a', 0), (1, 'gone', 1), (2, 'a', 0);
+             INSERT INTO bookmarks (library_item_id, name, position_ms) VALUES ('gone', 'x', 0);
+             PRAGMA foreign_keys = ON;",
+        )
+        .unwrap();
+
+        Database::migrate(&conn, 19).unwrap();
+        assert_eq!(count("library_items"), 1);
+        assert_eq!(count("pictures"), 1);
+        assert_eq!(count("playlist_items"), 1);
+        assert_eq!(count("bookmarks"), 0);
+
+        conn.execute("DELETE FROM library_paths WHERE id = 1", [])
+            .unwrap();
+        for table in ["library_items", "pictures", "playlist_items"] {
+            assert_eq!(count(table), 0, "{table}");
+        }
+    }
+
+    #[test]
+    fn failed_migrations_are_retried_whole() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let conn = db.connection();
+        let conn = conn.lock().unwrap();
+        let column_count = || -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('library_items') WHERE name = 'audio_track'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        // A version 20 database where the labels table can't be created
+        conn.execute_batch(
+            "DROP TABLE playlist_labels; DROP TABLE track_labels; DROP TABLE labels;
+             ALTER TABLE library_items DROP COLUMN audio_track;
+             CREATE INDEX labels ON library_items (path);
+             DELETE FROM schema_version; INSERT INTO schema_version (version) VALUES (20);",
+        )
+        .unwrap();
+        assert!(Database::initialize_schema(&conn).is_err());
+        assert_eq!(column_count(), 0);
+
+        conn.execute("DROP INDEX labels", []).unwrap();
+        Database::initialize_schema(&conn).unwrap();
+        assert_eq!(column_count(), 1);
+        let version: i32 = conn
+            .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, Database::SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn failed_table_rebuild_changes_nothing() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let conn = db.connection();
+        let conn = conn.lock().unwrap();
+        let query = |sql: &str| -> i64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+             INSERT INTO resume_positions (library_item_id, position) VALUES ('gone', 1000);
+             PRAGMA foreign_keys = ON;
+             -- The last table can't be moved out of the way
+             CREATE TABLE old_bookmarks (id INTEGER);",
+        )
+        .unwrap();
+
+        assert!(Database::migrate(&conn, 19).is_err());
+        // The tables rebuilt before are back as they were
+        assert_eq!(query("SELECT COUNT(*) FROM resume_positions"), 1);
+        assert_eq!(
+            query("SELECT COUNT(*) FROM sqlite_master WHERE name LIKE 'old_%'"),
+            1
+        );
+        assert_eq!(query("PRAGMA foreign_keys"), 1);
+        assert_eq!(query("PRAGMA legacy_alter_table"), 0);
+
+        conn.execute("DROP TABLE old_bookmarks", []).unwrap();
+        Database::migrate(&conn, 19).unwrap();
+        assert_eq!(query("SELECT COUNT(*) FROM resume_positions"), 0);
+    }
+}
@@ -0,0 +1,330 @@
+//! The effects audio runs through between the channel mixer and the output. Each effect
+//! works on planar f32 audio and the chain runs them in the order the user picked, so a
+//! new effect only needs an [`Effect`] impl and an [`EffectConfig`] to switch it on.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef};
+
+use crate::channel_mix::Balance;
+use crate::crossfeed::{Crossfeed, CrossfeedLevel};
+use crate::limiter::Limiter;
+use crate::plugin::{self, PluginConfig};
+
+pub trait Effect: Send {
+    fn kind(&self) -> EffectKind;
+
+    /// Takes new settings of the same kind, keeping any state like a running envelope.
+    fn configure(&mut self, config: &EffectConfig);
+
+    /// Whether `config` can be taken by [`Effect::configure`] or needs a new effect.
+    fn accepts(&self, config: &EffectConfig) -> bool {
+        self.kind() == config.kind()
+    }
+
+    /// Processes one buffer in place, a plane per channel.
+    fn process(&mut self, planes: &mut [&mut [f32]], rate: u32);
+}
+
+/// The effects there are, in the order the settings list them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EffectKind {
+    Balance,
+    Crossfeed,
+    Plugin, // Only available with the ladspa feature
+    Limiter,
+}
+
+impl EffectKind {
+    pub fn all() -> &'static [EffectKind] {
+        &[
+            EffectKind::Balance,
+            EffectKind::Crossfeed,
+            #[cfg(feature = "ladspa")]
+            EffectKind::Plugin,
+            EffectKind::Limiter,
+        ]
+    }
+
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            EffectKind::Balance => "effect_balance",
+            EffectKind::Crossfeed => "effect_crossfeed",
+            EffectKind::Plugin => "effect_plugin",
+            EffectKind::Limiter => "effect_limiter",
+        }
+    }
+}
+
+/// Settings of an effect in the chain, as sent by the UI.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EffectConfig {
+    Balance(Balance),
+    Crossfeed(CrossfeedLevel),
+    Plugin(PluginConfig),
+    Limiter { ceiling_db: f32 },
+}
+
+impl EffectConfig {
+    pub fn kind(&self) -> EffectKind {
+        match self {
+            EffectConfig::Balance(_) => EffectKind::Balance,
+            EffectConfig::Crossfeed(_) => EffectKind::Crossfeed,
+            EffectConfig::Plugin(_) => EffectKind::Plugin,
+            EffectConfig::Limiter { .. } => EffectKind::Limiter,
+        }
+    }
+
+    // `None` when the effect can't run, e.g. a plugin that fails to load
+    fn build(&self) -> Option<Box<dyn Effect>> {
+        match self {
+            EffectConfig::Balance(balance) => Some(Box::new(*balance)),
+            EffectConfig::Crossfeed(level) => Some(Box::new(Crossfeed::new(*level))),
+            EffectConfig::Plugin(config) => plugin::load(config),
+            EffectConfig::Limiter { ceiling_db } => Some(Box::new(Limiter::new(*ceiling_db))),
+        }
+    }
+}
+
+/// Default order of the effects, the limiter comes last so it caps the final level.
+pub fn default_order() -> Vec<EffectKind> {
+    EffectKind::all().to_vec()
+}
+
+/// Puts the effects a saved order is missing, e.g. ones added since it was saved, at
+/// their default place, and drops those this build doesn't have.
+pub fn complete_order(order: &[EffectKind]) -> Vec<EffectKind> {
+    let mut complete: Vec<EffectKind> = Vec::new();
+    for kind in order {
+        if !complete.contains(kind) && EffectKind::all().contains(kind) {
+            complete.push(*kind);
+        }
+    }
+    for (idx, kind) in EffectKind::all().iter().enumerate() {
+        if !complete.contains(kind) {
+            complete.insert(idx.min(complete.len()), *kind);
+        }
+    }
+    complete
+}
+
+// How long a change of gain takes, a jump from one buffer to the next clicks
+const GAIN_RAMP: Duration = Duration::from_millis(20);
+
+#[derive(Default)]
+pub struct DspChain {
+    effects: Vec<Box<dyn Effect>>,
+    // The audio as it runs through the chain, reused while the layout stays the same
+    buffer: Option<AudioBuffer<f32>>,
+    gain: GainRamp,
+}
+
+/// Moves the gain to a new value a little per frame, over [`GAIN_RAMP`], instead of
+/// stepping at a buffer boundary.
+#[derive(Debug, Default)]
+struct GainRamp {
+    // The gain the last frame got, `None` before the first buffer
+    current: Option<f32>,
+    // The gain being ramped to and the change per frame to get there
+    target: f32,
+    step: f32,
+}
+
+impl GainRamp {
+    fn apply(&mut self, planes: &mut [&mut [f32]], target: f32, rate: u32) {
+        let start = self.current.unwrap_or(target);
+        if start == target {
+            if target != 1.0 {
+                for plane in planes.iter_mut() {
+                    plane.iter_mut().for_each(|sample| *sample *= target);
+                }
+            }
+            self.current = Some(target);
+            self.step = 0.0;
+            return;
+        }
+
+        // A ramp left unfinished by a short buffer goes on at the same pace
+        if target != self.target || self.step == 0.0 {
+            let ramp_frames = (GAIN_RAMP.as_secs_f32() * rate as f32).max(1.0);
+            self.target = target;
+            self.step = (target - start) / ramp_frames;
+        }
+        let step = self.step;
+        let frames = planes.first().map_or(0, |plane| plane.len());
+        let mut gain = start;
+        for frame in 0..frames {
+            gain = if step > 0.0 {
+                (gain + step).min(target)
+            } else {
+                (gain + step).max(target)
+            };
+            for plane in planes.iter_mut() {
+                plane[frame] *= gain;
+            }
+        }
+        self.current = Some(gain);
+        if gain == target {
+            self.step = 0.0;
+        }
+    }
+}
+
+impl DspChain {
+    /// Sets the effects and their order. Effects that stay in the chain keep their state.
+    pub fn configure(&mut self, configs: &[EffectConfig]) {
+        let mut previous = std::mem::take(&mut self.effects);
+        for config in configs {
+            let effect = match previous.iter().position(|effect| effect.accepts(config)) {
+                Some(idx) => {
+                    let mut effect = previous.remove(idx);
+                    effect.configure(config);
+                    effect
+                }
+                None => match config.build() {
+                    Some(effect) => effect,
+                    None => continue,
+                },
+            };
+            self.effects.push(effect);
+        }
+    }
+
+    /// Applies `gain`, i.e. volume and loudness normalization, then each effect in order.
+    /// A gain that differs from the last buffer's is ramped to over a few milliseconds.
+    pub fn process<'a>(&'a mut self, decoded: AudioBufferRef<'a>, gain: f32) -> AudioBufferRef<'a> {
+        let spec = *decoded.spec();
+        let capacity = decoded.capacity() as u64;
+        let reusable = self
+            .buffer
+            .as_ref()
+            .is_some_and(|buf| *buf.spec() == spec && buf.capacity() as u64 == capacity);
+        if !reusable {
+            self.buffer = Some(AudioBuffer::new(capacity, spec));
+        }
+
+        let buffer = self.buffer.as_mut().unwrap();
+        decoded.convert(buffer);
+        {
+            let mut planes = buffer.planes_mut();
+            let planes = planes.planes();
+            self.gain.apply(planes, gain, spec.rate);
+            for effect in &mut self.effects {
+                effect.process(planes, spec.rate);
+            }
+        }
+
+        buffer.as_audio_buffer_ref()
+    }
+}
+
+impl Effect for Balance {
+    fn kind(&self) -> EffectKind {
+        EffectKind::Balance
+    }
+
+    fn configure(&mut self, config: &EffectConfig) {
+        if let EffectConfig::Balance(balance) = config {
+            *self = *balance;
+        }
+    }
+
+    fn process(&mut self, planes: &mut [&mut [f32]], _rate: u32) {
+        if self.is_unity() {
+            return;
+        }
+        let channels = planes.len();
+        for (channel, plane) in planes.iter_mut().enumerate() {
+            let gain = self.gain(channel, channels);
+            plane.iter_mut().for_each(|sample| *sample *= gain);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::audio::{Channels, Signal, SignalSpec};
+
+    #[test]
+    fn saved_orders_get_new_effects_at_their_default_place() {
+        assert_eq!(
+            complete_order(&[EffectKind::Balance, EffectKind::Limiter]),
+            default_order()
+        );
+        let mut expected = vec![
+            EffectKind::Limiter,
+            EffectKind::Crossfeed,
+            EffectKind::Balance,
+        ];
+        if cfg!(feature = "ladspa") {
+            expected.insert(2, EffectKind::Plugin);
+        }
+        assert_eq!(
+            complete_order(&[EffectKind::Limiter, EffectKind::Balance]),
+            expected
+        );
+        assert_eq!(complete_order(&[]), default_order());
+    }
+
+    #[cfg(not(feature = "ladspa"))]
+    #[test]
+    fn saved_orders_lose_effects_this_build_lacks() {
+        assert_eq!(
+            complete_order(&[EffectKind::Plugin, EffectKind::Limiter]),
+            default_order()
+        );
+    }
+
+    #[test]
+    fn effects_run_in_order_after_the_gain() {
+        let spec = SignalSpec::new(44100, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut source = AudioBuffer::<f32>::new(16, spec);
+        source.render_reserved(Some(16));
+        for plane in source.planes_mut().planes() {
+            plane.iter_mut().for_each(|sample| *sample = 0.5);
+        }
+
+        let mut chain = DspChain::default();
+        chain.configure(&[
+            EffectConfig::Balance(Balance::new(1.0, 0.0, 0.0)),
+            EffectConfig::Limiter { ceiling_db: -6.0 },
+        ]);
+        let AudioBufferRef::F32(out) = chain.process(source.as_audio_buffer_ref(), 4.0) else {
+            panic!("the chain works in f32");
+        };
+
+        // Left is silenced by the balance, right is 2.0 before the limiter caps it
+        let planes = out.planes();
+        let [left, right] = planes.planes() else {
+            panic!("stereo in, stereo out");
+        };
+        assert!(left.iter().all(|sample| *sample == 0.0));
+        let ceiling = 10f32.powf(-6.0 / 20.0);
+        assert!(right.iter().all(|sample| *sample <= ceiling + 1e-6));
+    }
+
+    #[test]
+    fn gain_changes_are_ramped_across_buffers() {
+        // 20 ms are 20 frames at 1 kHz
+        let rate = 1000;
+        let mut ramp = GainRamp::default();
+        let mut first = [1.0f32; 8];
+        ramp.apply(&mut [&mut first[..]], 0.5, rate);
+        assert!(first.iter().all(|sample| *sample == 0.5));
+
+        let mut left = [1.0f32; 16];
+        let mut right = [1.0f32; 16];
+        ramp.apply(&mut [&mut left[..], &mut right[..]], 1.5, rate);
+        assert_eq!(left, right);
+        assert!(left.windows(2).all(|pair| pair[1] > pair[0]));
+        assert!((left[0] - 0.55).abs() < 1e-6);
+        assert!(left[15] < 1.5);
+
+        // The rest of the ramp runs in the next buffer
+        let mut next = [1.0f32; 8];
+        ramp.apply(&mut [&mut next[..]], 1.5, rate);
+        assert!(next[..3].iter().all(|sample| *sample < 1.5));
+        assert!(next[4..].iter().all(|sample| *sample == 1.5));
+    }
+}
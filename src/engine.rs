@@ -0,0 +1,1059 @@
+//! The playback state machine of the audio thread.
+//!
+//! The engine reads tracks from a [`MediaSource`], runs the decoded audio through the
+//! loudness leveler, the channel mixer and the effect chain and writes it to an
+//! [`AudioOutput`]. Both ends are injected, so the state handling can be tested without
+//! files or a sound device.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, Signal, SignalSpec};
+
+use crate::app::{AudioCommand, UiCommand};
+use crate::channel_mix::ChannelMixer;
+use crate::dsp::DspChain;
+use crate::loudness::VolumeLeveler;
+use crate::media_source::{Decoded, MediaSource, TrackStream};
+use crate::output::{self, AudioOutput};
+use crate::perf_stats;
+use crate::transition::{self, Transition};
+
+/// Opens an output for buffers of a spec, given the capacity of a decoded buffer.
+pub type OpenOutput = Box<dyn FnMut(SignalSpec, u64) -> output::Result<Box<dyn AudioOutput>>>;
+
+// A track opened by the loader thread, tagged with the load it belongs to
+type Loaded = (u64, PathBuf, Result<Box<dyn TrackStream>, String>);
+
+// How often the engine looks for the opened track while it's loading
+const LOADING_POLL: Duration = Duration::from_millis(5);
+
+#[derive(Debug, PartialEq)]
+pub enum PlayerState {
+    Unstarted,
+    Stopped,
+    Playing,
+    Paused,
+    LoadFile(PathBuf, Option<u32>),
+    Loading, // The file is being opened, e.g. waiting on a network share
+    SeekTo(u64),
+}
+
+/// The engine's end of the channel to the UI. Once the UI is gone there's nobody left to
+/// play for, and the engine stops instead of panicking on the next message.
+struct UiSender {
+    tx: Sender<UiCommand>,
+    gone: bool,
+}
+
+impl UiSender {
+    fn send(&mut self, command: UiCommand) {
+        if self.tx.send(command).is_err() && !self.gone {
+            tracing::error!("The UI stopped listening, stopping the audio thread");
+            self.gone = true;
+        }
+    }
+}
+
+pub struct AudioEngine {
+    state: PlayerState,
+    source: Arc<dyn MediaSource>,
+    loaded_tx: Sender<Loaded>,
+    loaded_rx: Receiver<Loaded>,
+    load_generation: u64, // Results of older loads are dropped
+    play_when_loaded: bool,
+    seek_when_loaded: Option<u64>,
+    open_output: OpenOutput,
+    ui_tx: UiSender,
+    track: Option<Box<dyn TrackStream>>,
+    track_path: Option<PathBuf>,
+    audio_track: Option<u32>, // Of the file at `track_path`, see `MediaSource::open`
+    audio_output: Option<Box<dyn AudioOutput>>,
+    output_spec: Option<SignalSpec>, // The open output was created with
+    volume: f32,
+    preamp: f32, // Of the current track, on top of the volume
+    leveler: VolumeLeveler,
+    mixer: ChannelMixer,
+    dsp: DspChain,
+    soft_fades: bool,
+    fade_in_pending: bool,
+    faded_out: bool,
+    upcoming: Option<Transition>, // How the current track hands over to the next one
+    handover: Option<Transition>, // Of the track that just ended, until the next one plays
+    fade_in: Option<f32>,         // Seconds the current track fades in over
+    timer: Instant,
+    last_ts: u64, // Track last timestamp to avoid duplicate updates
+}
+
+impl AudioEngine {
+    pub fn new(
+        source: Arc<dyn MediaSource>,
+        open_output: OpenOutput,
+        ui_tx: Sender<UiCommand>,
+    ) -> Self {
+        let (loaded_tx, loaded_rx) = channel();
+        Self {
+            state: PlayerState::Unstarted,
+            source,
+            loaded_tx,
+            loaded_rx,
+            load_generation: 0,
+            play_when_loaded: true,
+            seek_when_loaded: None,
+            open_output,
+            ui_tx: UiSender {
+                tx: ui_tx,
+                gone: false,
+            },
+            track: None,
+            track_path: None,
+            audio_track: None,
+            audio_output: None,
+            output_spec: None,
+            volume: 1.0,
+            preamp: 1.0,
+            leveler: VolumeLeveler::new(),
+            mixer: ChannelMixer::new(),
+            dsp: DspChain::default(),
+            soft_fades: true,
+            fade_in_pending: false,
+            faded_out: false,
+            upcoming: None,
+            handover: None,
+            fade_in: None,
+            timer: Instant::now(),
+            last_ts: 0,
+        }
+    }
+
+    /// Runs the engine until the UI hangs up.
+    pub fn run(
+        mut self,
+        audio_rx: Receiver<AudioCommand>,
+        is_processing_ui_change: Arc<AtomicBool>,
+    ) {
+        loop {
+            // Nothing happens while paused or idle until the UI asks for something, so wait
+            // for the next command instead of polling
+            let command = if matches!(self.state, PlayerState::Paused | PlayerState::Unstarted) {
+                match audio_rx.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => break,
+                }
+            } else if self.state == PlayerState::Loading {
+                match audio_rx.recv_timeout(LOADING_POLL) {
+                    Ok(command) => Some(command),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                    Err(RecvTimeoutError::Timeout) => None,
+                }
+            } else {
+                match audio_rx.try_recv() {
+                    Ok(command) => Some(command),
+                    Err(TryRecvError::Disconnected) => break,
+                    Err(TryRecvError::Empty) => None, // The common case
+                }
+            };
+
+            if let Some(command) = command {
+                if matches!(command, AudioCommand::Shutdown) {
+                    self.shutdown();
+                    break;
+                }
+                let is_volume = matches!(command, AudioCommand::SetVolume(_));
+                self.handle_command(command);
+                if is_volume {
+                    is_processing_ui_change.store(false, Ordering::Relaxed);
+                }
+            }
+
+            self.step();
+
+            // Nobody is left to play for
+            if self.ui_tx.gone {
+                self.shutdown();
+                break;
+            }
+
+            // Yield to other threads if we're not actively playing
+            if self.state != PlayerState::Playing {
+                std::thread::yield_now();
+            }
+        }
+    }
+
+    pub fn handle_command(&mut self, command: AudioCommand) {
+        match command {
+            AudioCommand::Seek(ts) => {
+                tracing::info!("Processing SEEK command for {} seconds", ts);
+                self.seek(ts);
+            }
+            AudioCommand::Stop => {
+                tracing::info!("Processing STOP command");
+                self.stop();
+            }
+            AudioCommand::Pause => {
+                tracing::info!("Processing PAUSE command");
+                self.pause();
+            }
+            AudioCommand::Play => {
+                tracing::info!("Processing PLAY command");
+                self.play();
+            }
+            AudioCommand::LoadFile(path, audio_track) => {
+                tracing::info!(
+                    "Processing LOAD FILE command for path: {:?}, audio track {:?}",
+                    &path,
+                    audio_track
+                );
+                self.load(path, audio_track);
+            }
+            AudioCommand::Prefetch(path) => {
+                tracing::info!("Processing PREFETCH command for path: {:?}", &path);
+                self.source.prefetch(&path);
+            }
+            AudioCommand::SetVolume(volume) => {
+                tracing::info!("Processing SET VOLUME command to: {:?}", &volume);
+                self.volume = volume;
+            }
+            AudioCommand::SetTrackGain(gain) => {
+                tracing::info!("Processing SET TRACK GAIN command to: {:?}", &gain);
+                self.leveler.set_track_gain(gain);
+            }
+            AudioCommand::SetTrackPreamp(preamp) => {
+                tracing::info!("Processing SET TRACK PREAMP command to: {:?}", &preamp);
+                self.preamp = preamp;
+            }
+            AudioCommand::SetChannelMix(mix) => {
+                tracing::info!("Processing SET CHANNEL MIX command to: {:?}", &mix);
+                self.mixer.set_mode(mix);
+            }
+            AudioCommand::SetDspChain(effects) => {
+                tracing::info!("Processing SET DSP CHAIN command to: {:?}", &effects);
+                self.dsp.configure(&effects);
+            }
+            AudioCommand::SetSoftFades(enabled) => {
+                tracing::info!("Processing SET SOFT FADES command to: {:?}", &enabled);
+                self.soft_fades = enabled;
+            }
+            AudioCommand::SetTransition(transition) => {
+                tracing::info!("Processing SET TRANSITION command to: {:?}", &transition);
+                self.upcoming = transition;
+            }
+            AudioCommand::Shutdown => {
+                tracing::info!("Processing SHUTDOWN command");
+                self.shutdown();
+            }
+            _ => tracing::warn!("Unhandled case in audio command loop"),
+        }
+    }
+
+    pub fn play(&mut self) {
+        self.transition(PlayerState::Playing);
+    }
+
+    pub fn pause(&mut self) {
+        self.transition(PlayerState::Paused);
+    }
+
+    pub fn stop(&mut self) {
+        self.transition(PlayerState::Stopped);
+    }
+
+    pub fn seek(&mut self, ts: u64) {
+        self.transition(PlayerState::SeekTo(ts));
+    }
+
+    pub fn load(&mut self, path: PathBuf, audio_track: Option<u32>) {
+        self.transition(PlayerState::LoadFile(path, audio_track));
+    }
+
+    /// Fades out what's playing and closes the track and the output stream.
+    pub fn shutdown(&mut self) {
+        if self.soft_fades && self.state == PlayerState::Playing {
+            if let Some(audio_output) = self.audio_output.as_mut() {
+                audio_output.fade_out();
+            }
+        }
+        self.load_generation += 1;
+        self.handover = None;
+        self.close_track();
+        self.track_path = None;
+        self.state = PlayerState::Unstarted;
+    }
+
+    // Fade out before pausing, stopping, seeking or switching tracks and
+    // back in when playback continues, so the audio doesn't click
+    fn transition(&mut self, state: PlayerState) {
+        // Pausing or stopping after a track ended doesn't go on to another one
+        if matches!(state, PlayerState::Paused | PlayerState::Stopped) {
+            self.handover = None;
+        }
+
+        // While a file opens, remember what to do with it once it's there
+        if self.state == PlayerState::Loading {
+            match state {
+                PlayerState::Playing => {
+                    self.play_when_loaded = true;
+                    return;
+                }
+                PlayerState::Paused => {
+                    self.play_when_loaded = false;
+                    return;
+                }
+                PlayerState::SeekTo(ts) => {
+                    self.seek_when_loaded = Some(ts);
+                    return;
+                }
+                // Stopping or picking another file cancels the load
+                _ => self.load_generation += 1,
+            }
+        }
+
+        let was_playing = self.state == PlayerState::Playing;
+        let was_paused = self.state == PlayerState::Paused;
+        self.state = state;
+        let is_playing = self.state == PlayerState::Playing;
+        let is_paused = self.state == PlayerState::Paused;
+
+        if self.soft_fades && was_playing && !is_playing {
+            if let Some(audio_output) = self.audio_output.as_mut() {
+                audio_output.fade_out();
+                self.faded_out = true;
+            }
+            self.fade_in_pending = matches!(self.state, PlayerState::SeekTo(_));
+        } else if !was_playing && is_playing {
+            // A paused output stays silent until faded in, even if fades were turned off since
+            self.fade_in_pending = self.soft_fades || self.faded_out;
+        }
+
+        // Pausing holds the stream with the decoded audio still queued, instead of
+        // letting the device run dry while the decoder waits
+        if let Some(audio_output) = self.audio_output.as_mut() {
+            if was_playing && is_paused {
+                audio_output.pause();
+            } else if was_paused && is_playing {
+                audio_output.resume();
+            }
+        }
+    }
+
+    /// Does one unit of work for the current state, e.g. plays one packet.
+    pub fn step(&mut self) {
+        match self.state {
+            PlayerState::Playing => {
+                // Give up on the track, the UI skips to the next one
+                if let Err(message) = self.play_packet() {
+                    self.fail(message);
+                }
+            }
+            PlayerState::Stopped => {
+                // This is kind of a hack to get stopping to work. Flush the buffer so there is
+                // nothing left in the resampler, but the decoder needs to be reset. This is as
+                // simple as reloading the current track so the next time it plays from the
+                // beginning.
+                self.close_track();
+
+                if let Some(path) = self.track_path.clone() {
+                    if let Err(message) = self.open_track(&path, 0) {
+                        self.fail(message);
+                    }
+                    self.send(UiCommand::CurrentTimestamp(0));
+                }
+                self.state = PlayerState::Unstarted;
+            }
+            PlayerState::SeekTo(seek_ts) => {
+                tracing::info!("AudioThread Seeking");
+                let Some(path) = self.track_path.clone() else {
+                    self.state = PlayerState::Unstarted;
+                    return;
+                };
+
+                self.close_track();
+                match self.open_track(&path, seek_ts) {
+                    Ok(()) => {
+                        self.state = PlayerState::Playing;
+
+                        // Update UI with playing state to ensure synchronization
+                        self.send(UiCommand::PlaybackStateChanged(true));
+                    }
+                    Err(message) => self.fail(message),
+                }
+            }
+            PlayerState::LoadFile(ref path, audio_track) => {
+                tracing::info!("AudioThread Loading File");
+                let path = path.clone();
+
+                self.close_track();
+                self.track_path = None;
+                self.audio_track = audio_track;
+                self.upcoming = None;
+                self.fade_in = None;
+
+                // Open on a thread of its own, so commands keep coming while the file
+                // opens and a newer pick can replace it
+                self.load_generation += 1;
+                self.play_when_loaded = true;
+                self.seek_when_loaded = None;
+                let generation = self.load_generation;
+                let source = self.source.clone();
+                let loaded_tx = self.loaded_tx.clone();
+                std::thread::spawn(move || {
+                    let result = source.open(&path, audio_track, 0);
+                    let _ = loaded_tx.send((generation, path, result));
+                });
+
+                self.state = PlayerState::Loading;
+            }
+            PlayerState::Loading => self.receive_loaded(),
+            PlayerState::Paused | PlayerState::Unstarted => {}
+        }
+    }
+
+    // Decodes the next packet and writes it to the output
+    fn play_packet(&mut self) -> Result<(), String> {
+        // Nothing is loaded, e.g. after the last track failed to open
+        let Some(track) = self.track.as_mut() else {
+            self.state = PlayerState::Unstarted;
+            return Ok(());
+        };
+
+        let info = track.info();
+        let (duration, time_base) = (info.duration, info.time_base);
+        let started = Instant::now();
+        let (ts, decoded) = match track.next()? {
+            Decoded::Audio(ts, decoded) => (ts, decoded),
+            Decoded::Skipped => return Ok(()),
+            Decoded::End => {
+                // Track is over.. update the state to stopped and send message to
+                // UI to play next track
+                tracing::info!("end of stream");
+                if let Some(audio_output) = self.audio_output.as_mut() {
+                    audio_output.end_of_stream();
+                }
+                self.state = PlayerState::Stopped;
+                self.handover = self.upcoming.take();
+                self.ui_tx.send(UiCommand::AudioFinished);
+                return Ok(());
+            }
+        };
+        let decoding = started.elapsed();
+
+        // Only send timestamp updates every second and only if the timestamp has changed
+        // by more than a second or went backwards
+        if self.timer.elapsed() > Duration::from_secs(1)
+            && (ts > self.last_ts + 1000 || ts < self.last_ts)
+        {
+            self.ui_tx.send(UiCommand::CurrentTimestamp(ts));
+            self.timer = Instant::now();
+            self.last_ts = ts;
+        }
+
+        // Get the spec of the audio after channel mixing. This is a description
+        // of the sample format and sample rate the output is opened with.
+        let spec = self.mixer.output_spec(*decoded.spec());
+
+        // Reopen the output when the mix changed its channel layout
+        if self.output_spec != Some(spec) {
+            if let Some(audio_output) = self.audio_output.as_mut() {
+                audio_output.flush();
+            }
+            self.audio_output = None;
+        }
+
+        // If the audio output is not open, try to open it.
+        let audio_output = match self.audio_output.as_mut() {
+            Some(audio_output) => audio_output,
+            None => {
+                // Get the capacity of the decoded buffer. Note that this is capacity, not
+                // length! The capacity of the decoded buffer is constant for the life of the
+                // decoder, but the length is not.
+                let duration = decoded.capacity() as u64;
+
+                let opened = (self.open_output)(spec, duration)
+                    .map_err(|err| format!("couldn't open audio output: {:?}", err))?;
+                if !opened.info().is_resampled() {
+                    perf_stats::RESAMPLER.clear();
+                }
+                self.ui_tx
+                    .send(UiCommand::OutputInfo(opened.info().clone()));
+                self.output_spec = Some(spec);
+                self.audio_output.insert(opened)
+            }
+        };
+
+        if self.fade_in_pending {
+            self.fade_in_pending = false;
+            self.faded_out = false;
+            audio_output.fade_in();
+        }
+
+        // The track that ended asked for silence or a fade before this one
+        match self.handover.take() {
+            Some(Transition::Gap(seconds)) => {
+                let frames = (seconds as f64 * spec.rate as f64) as u64;
+                write_silence(
+                    audio_output.as_mut(),
+                    spec,
+                    decoded.capacity() as u64,
+                    frames,
+                )?;
+            }
+            Some(Transition::Fade(seconds)) => self.fade_in = Some(seconds),
+            Some(Transition::Gapless) | None => {}
+        }
+
+        let fade = match time_base {
+            Some(tb) => {
+                let seconds = |ts| {
+                    let time = tb.calc_time(ts);
+                    time.seconds as f64 + time.frac
+                };
+                let fade_out = match self.upcoming {
+                    Some(Transition::Fade(seconds)) => Some(seconds),
+                    _ => None,
+                };
+                transition::fade_gain(seconds(ts), seconds(duration), self.fade_in, fade_out)
+            }
+            None => 1.0,
+        };
+
+        // Opening the output and writing silence don't count as decoding work
+        let processing = Instant::now();
+        let frames = decoded.frames();
+        let gain = self.volume * self.preamp * self.leveler.process(&decoded) * fade;
+        let mixed = self.mixer.process(decoded);
+        let processed = self.dsp.process(mixed, gain);
+        perf_stats::DECODE.record(decoding + processing.elapsed(), frames, spec.rate);
+        audio_output
+            .write(processed)
+            .map_err(|err| format!("couldn't write to audio output: {:?}", err))
+    }
+
+    // Takes over the track the loader opened, unless another one was picked since
+    fn receive_loaded(&mut self) {
+        let Ok((generation, path, result)) = self.loaded_rx.try_recv() else {
+            return;
+        };
+        if generation != self.load_generation {
+            tracing::info!("Dropping {:?}, another track was picked", path);
+            if let Ok(mut track) = result {
+                track.finish();
+            }
+            return;
+        }
+
+        let track = match result {
+            Ok(track) => track,
+            Err(message) => return self.fail(message),
+        };
+        let info = track.info().clone();
+        self.track = Some(track);
+        self.track_path = Some(path);
+
+        self.send(UiCommand::TotalTrackDuration(info.duration));
+        if let Some(tb) = info.time_base {
+            self.send(UiCommand::TrackTimeBase(tb.numer, tb.denom));
+        }
+        if let Some(format) = info.format {
+            self.send(UiCommand::TrackFormat(format));
+        }
+
+        self.state = match self.seek_when_loaded.take() {
+            Some(ts) => PlayerState::SeekTo(ts),
+            None if self.play_when_loaded => PlayerState::Playing,
+            None => PlayerState::Paused,
+        };
+    }
+
+    fn open_track(&mut self, path: &std::path::Path, seek_ts: u64) -> Result<(), String> {
+        self.track = Some(self.source.open(path, self.audio_track, seek_ts)?);
+        Ok(())
+    }
+
+    // Stops the output and finishes the current track before another one is opened. After
+    // a track ended on its own the output plays on, so the next one follows without a gap.
+    fn close_track(&mut self) {
+        if self.handover.is_none() {
+            if let Some(audio_output) = self.audio_output.as_mut() {
+                tracing::info!("AudioThread - Flushing output");
+                audio_output.flush();
+            }
+            self.audio_output = None;
+            self.output_spec = None;
+        }
+
+        if let Some(mut track) = self.track.take() {
+            track.finish();
+        }
+    }
+
+    // Drops whatever is left of a track that can't be played and tells the UI why
+    fn fail(&mut self, message: String) {
+        tracing::error!("Playback failed: {}", message);
+
+        self.handover = None;
+        self.close_track();
+        self.track_path = None;
+        self.state = PlayerState::Unstarted;
+
+        self.send(UiCommand::PlaybackError(message));
+    }
+
+    fn send(&mut self, command: UiCommand) {
+        self.ui_tx.send(command);
+    }
+}
+
+// Writes `frames` of silence in buffers of at most `capacity` frames
+fn write_silence(
+    audio_output: &mut dyn AudioOutput,
+    spec: SignalSpec,
+    capacity: u64,
+    frames: u64,
+) -> Result<(), String> {
+    let mut silence = AudioBuffer::<f32>::new(capacity, spec);
+    let mut left = frames;
+    while left > 0 {
+        let chunk = left.min(capacity);
+        silence.clear();
+        silence.render_reserved(Some(chunk as usize));
+        audio_output
+            .write(silence.as_audio_buffer_ref())
+            .map_err(|err| format!("couldn't write to audio output: {:?}", err))?;
+        left -= chunk;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media_source::TrackInfo;
+    use crate::output::OutputInfo;
+
+    use std::path::Path;
+    use std::sync::mpsc::channel;
+    use std::sync::Mutex;
+
+    use symphonia::core::audio::Channels;
+
+    const PACKET_FRAMES: u64 = 100;
+
+    // What was opened: the path, the audio track and the seek position
+    type Opened = Arc<Mutex<Vec<(PathBuf, Option<u32>, u64)>>>;
+
+    // Every path is a track of this many packets, "missing" can't be opened
+    struct FakeSource {
+        packets: u64,
+        opened: Opened,
+    }
+
+    impl MediaSource for FakeSource {
+        fn open(
+            &self,
+            path: &Path,
+            audio_track: Option<u32>,
+            seek_ts: u64,
+        ) -> Result<Box<dyn TrackStream>, String> {
+            if path == Path::new("missing") {
+                return Err("missing not found".to_string());
+            }
+            self.opened
+                .lock()
+                .unwrap()
+                .push((path.to_path_buf(), audio_track, seek_ts));
+
+            let spec = SignalSpec::new(44100, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+            let mut buffer = AudioBuffer::new(PACKET_FRAMES, spec);
+            buffer.render_reserved(None);
+            Ok(Box::new(FakeTrack {
+                buffer,
+                ts: seek_ts,
+                end: self.packets * PACKET_FRAMES,
+                info: TrackInfo {
+                    duration: self.packets * PACKET_FRAMES,
+                    ..Default::default()
+                },
+            }))
+        }
+    }
+
+    struct FakeTrack {
+        buffer: AudioBuffer<f32>,
+        ts: u64,
+        end: u64,
+        info: TrackInfo,
+    }
+
+    impl TrackStream for FakeTrack {
+        fn info(&self) -> &TrackInfo {
+            &self.info
+        }
+
+        fn next(&mut self) -> Result<Decoded<'_>, String> {
+            if self.ts >= self.end {
+                return Ok(Decoded::End);
+            }
+            let ts = self.ts;
+            self.ts += PACKET_FRAMES;
+            Ok(Decoded::Audio(ts, self.buffer.as_audio_buffer_ref()))
+        }
+    }
+
+    #[derive(Default)]
+    struct OutputLog {
+        opened: usize,
+        written: usize,
+        flushed: usize,
+        paused: bool,
+    }
+
+    struct FakeOutput {
+        log: Arc<Mutex<OutputLog>>,
+        info: OutputInfo,
+    }
+
+    impl AudioOutput for FakeOutput {
+        fn write(
+            &mut self,
+            _decoded: symphonia::core::audio::AudioBufferRef<'_>,
+        ) -> output::Result<()> {
+            self.log.lock().unwrap().written += 1;
+            Ok(())
+        }
+
+        fn flush(&mut self) {
+            self.log.lock().unwrap().flushed += 1;
+        }
+
+        fn info(&self) -> &OutputInfo {
+            &self.info
+        }
+
+        fn pause(&mut self) {
+            self.log.lock().unwrap().paused = true;
+        }
+
+        fn resume(&mut self) {
+            self.log.lock().unwrap().paused = false;
+        }
+    }
+
+    struct Harness {
+        engine: AudioEngine,
+        ui_rx: Receiver<UiCommand>,
+        opened: Opened,
+        output: Arc<Mutex<OutputLog>>,
+    }
+
+    impl Harness {
+        fn new(packets: u64) -> Self {
+            let (ui_tx, ui_rx) = channel();
+            let opened = Arc::new(Mutex::new(Vec::new()));
+            let output = Arc::new(Mutex::new(OutputLog::default()));
+
+            let log = output.clone();
+            let open_output: OpenOutput = Box::new(move |spec: SignalSpec, _| {
+                log.lock().unwrap().opened += 1;
+                Ok(Box::new(FakeOutput {
+                    log: log.clone(),
+                    info: OutputInfo {
+                        device_name: "test".to_string(),
+                        sample_format: "f32".to_string(),
+                        source_rate: spec.rate,
+                        device_rate: spec.rate,
+                    },
+                }) as Box<dyn AudioOutput>)
+            });
+            let source = FakeSource {
+                packets,
+                opened: opened.clone(),
+            };
+
+            let mut engine = AudioEngine::new(Arc::new(source), open_output, ui_tx);
+            engine.soft_fades = false;
+            Self {
+                engine,
+                ui_rx,
+                opened,
+                output,
+            }
+        }
+
+        fn steps(&mut self, count: usize) {
+            for _ in 0..count {
+                self.engine.step();
+            }
+        }
+
+        // Steps until the loader thread handed over the track
+        fn finish_loading(&mut self) {
+            let started = Instant::now();
+            while self.engine.state == PlayerState::Loading {
+                assert!(
+                    started.elapsed() < Duration::from_secs(5),
+                    "load never finished"
+                );
+                std::thread::sleep(Duration::from_millis(1));
+                self.engine.step();
+            }
+        }
+
+        fn load(&mut self, path: &str) {
+            self.engine.load(PathBuf::from(path), None);
+            self.engine.step();
+            self.finish_loading();
+        }
+
+        fn drain(&self) -> Vec<UiCommand> {
+            self.ui_rx.try_iter().collect()
+        }
+    }
+
+    #[test]
+    fn seeking_while_paused_reopens_the_track_and_resumes() {
+        let mut h = Harness::new(10);
+        h.load("a");
+        h.steps(2);
+        h.engine.pause();
+        h.steps(2);
+        let written = h.output.lock().unwrap().written;
+        assert_eq!(written, 2);
+
+        h.engine.seek(500);
+        h.engine.step();
+        assert_eq!(h.engine.state, PlayerState::Playing);
+        assert_eq!(
+            h.opened.lock().unwrap().last(),
+            Some(&(PathBuf::from("a"), None, 500))
+        );
+        assert!(h
+            .drain()
+            .iter()
+            .any(|command| matches!(command, UiCommand::PlaybackStateChanged(true))));
+
+        h.engine.step();
+        assert_eq!(h.output.lock().unwrap().written, written + 1);
+    }
+
+    #[test]
+    fn pausing_holds_the_stream_and_resumes_where_it_stopped() {
+        let mut h = Harness::new(10);
+        h.load("a");
+        h.steps(2);
+
+        h.engine.pause();
+        assert!(h.output.lock().unwrap().paused);
+        h.steps(5);
+
+        h.engine.play();
+        assert!(!h.output.lock().unwrap().paused);
+        h.engine.step();
+
+        // Same output, no flush and no reopened track
+        let log = h.output.lock().unwrap();
+        assert_eq!((log.opened, log.flushed, log.written), (1, 0, 3));
+        assert_eq!(h.opened.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn loading_during_playback_switches_tracks() {
+        let mut h = Harness::new(10);
+        h.load("a");
+        h.steps(2);
+        h.drain();
+
+        h.load("b");
+        assert_eq!(h.engine.state, PlayerState::Playing);
+        assert_eq!(h.engine.track_path, Some(PathBuf::from("b")));
+        assert_eq!(h.output.lock().unwrap().flushed, 1);
+        assert!(h
+            .drain()
+            .iter()
+            .any(|command| matches!(command, UiCommand::TotalTrackDuration(1000))));
+
+        // The new track gets a fresh output
+        h.engine.step();
+        assert_eq!(h.output.lock().unwrap().opened, 2);
+    }
+
+    #[test]
+    fn end_of_stream_finishes_and_rewinds_the_track() {
+        let mut h = Harness::new(2);
+        h.load("a");
+        h.steps(2);
+        assert_eq!(h.output.lock().unwrap().written, 2);
+
+        h.engine.step();
+        assert_eq!(h.engine.state, PlayerState::Stopped);
+        assert!(h
+            .drain()
+            .iter()
+            .any(|command| matches!(command, UiCommand::AudioFinished)));
+
+        // Stopping reloads the track so playing again starts from the beginning
+        h.engine.step();
+        assert_eq!(h.engine.state, PlayerState::Unstarted);
+        assert_eq!(
+            h.opened.lock().unwrap().last(),
+            Some(&(PathBuf::from("a"), None, 0))
+        );
+    }
+
+    #[test]
+    fn tracks_that_end_hand_the_output_to_the_next() {
+        let mut h = Harness::new(2);
+        h.load("a");
+        h.engine
+            .handle_command(AudioCommand::SetTransition(Some(Transition::Gapless)));
+        // Two packets, the end and the rewind
+        h.steps(4);
+
+        h.load("b");
+        h.engine.step();
+        let log = h.output.lock().unwrap();
+        assert_eq!((log.opened, log.flushed, log.written), (1, 0, 3));
+    }
+
+    #[test]
+    fn gaps_are_silence_and_stopping_at_the_end_flushes() {
+        let mut h = Harness::new(2);
+        h.load("a");
+        h.engine
+            .handle_command(AudioCommand::SetTransition(Some(Transition::Gap(0.01))));
+        h.steps(4);
+
+        // 441 frames of silence in buffers of 100 before the first packet
+        h.load("b");
+        h.engine.step();
+        assert_eq!(h.output.lock().unwrap().written, 2 + 5 + 1);
+
+        h.engine
+            .handle_command(AudioCommand::SetTransition(Some(Transition::Gapless)));
+        h.steps(3);
+        h.engine.stop();
+        h.engine.step();
+        assert_eq!(h.output.lock().unwrap().flushed, 1);
+    }
+
+    #[test]
+    fn unplayable_files_are_reported_and_leave_the_engine_idle() {
+        let mut h = Harness::new(2);
+        h.load("missing");
+        assert_eq!(h.engine.state, PlayerState::Unstarted);
+        assert!(h
+            .drain()
+            .iter()
+            .any(|command| matches!(command, UiCommand::PlaybackError(_))));
+
+        // Play with nothing loaded doesn't do anything
+        h.engine.play();
+        h.engine.step();
+        assert_eq!(h.engine.state, PlayerState::Unstarted);
+        assert_eq!(h.output.lock().unwrap().written, 0);
+    }
+
+    #[test]
+    fn commands_while_loading_apply_once_the_track_is_open() {
+        let mut h = Harness::new(10);
+        h.engine.load(PathBuf::from("a"), None);
+        h.engine.step();
+        assert_eq!(h.engine.state, PlayerState::Loading);
+
+        // The resume position and a pause wait for the file
+        h.engine.seek(300);
+        h.engine.pause();
+        assert_eq!(h.engine.state, PlayerState::Loading);
+
+        h.finish_loading();
+        assert_eq!(h.engine.state, PlayerState::SeekTo(300));
+        h.engine.step();
+        assert_eq!(
+            h.opened.lock().unwrap().last(),
+            Some(&(PathBuf::from("a"), None, 300))
+        );
+    }
+
+    #[test]
+    fn the_picked_audio_track_stays_when_seeking() {
+        let mut h = Harness::new(10);
+        h.engine.load(PathBuf::from("a"), Some(1));
+        h.engine.step();
+        h.finish_loading();
+        h.engine.seek(500);
+        h.engine.step();
+        assert_eq!(
+            *h.opened.lock().unwrap(),
+            vec![
+                (PathBuf::from("a"), Some(1), 0),
+                (PathBuf::from("a"), Some(1), 500)
+            ]
+        );
+
+        // Another file starts over with its first track
+        h.load("b");
+        assert_eq!(
+            h.opened.lock().unwrap().last(),
+            Some(&(PathBuf::from("b"), None, 0))
+        );
+    }
+
+    #[test]
+    fn picking_another_track_while_loading_drops_the_first() {
+        let mut h = Harness::new(10);
+        h.engine.load(PathBuf::from("a"), None);
+        h.engine.step();
+        h.load("b");
+        assert_eq!(h.engine.state, PlayerState::Playing);
+        assert_eq!(h.engine.track_path, Some(PathBuf::from("b")));
+
+        // The result of "a" may still come in, it must not replace "b"
+        std::thread::sleep(Duration::from_millis(20));
+        h.engine.load(PathBuf::from("c"), None);
+        h.engine.step();
+        h.finish_loading();
+        assert_eq!(h.engine.track_path, Some(PathBuf::from("c")));
+        let durations = h
+            .drain()
+            .iter()
+            .filter(|command| matches!(command, UiCommand::TotalTrackDuration(_)))
+            .count();
+        assert_eq!(durations, 2);
+    }
+
+    #[test]
+    fn shutting_down_closes_the_stream_and_ends_the_loop() {
+        let mut h = Harness::new(10);
+        h.load("a");
+        h.steps(2);
+
+        let (audio_tx, audio_rx) = channel();
+        audio_tx.send(AudioCommand::Shutdown).unwrap();
+        // Returns although the sender is still around
+        h.engine.run(audio_rx, Arc::new(AtomicBool::new(false)));
+
+        let log = h.output.lock().unwrap();
+        assert_eq!((log.flushed, log.written), (1, 2));
+    }
+
+    #[test]
+    fn the_engine_stops_when_the_ui_goes_away_mid_playback() {
+        let mut h = Harness::new(10_000);
+        h.load("a");
+        h.steps(2);
+
+        // The next timestamp update finds nobody listening
+        drop(std::mem::replace(&mut h.ui_rx, channel().1));
+        h.engine.timer = Instant::now() - Duration::from_secs(2);
+
+        let (_audio_tx, audio_rx) = channel();
+        h.engine.run(audio_rx, Arc::new(AtomicBool::new(false)));
+
+        let log = h.output.lock().unwrap();
+        assert_eq!(log.flushed, 1);
+        assert!(log.written < 100);
+    }
+}
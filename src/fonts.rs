@@ -0,0 +1,146 @@
+//! Text fonts: the ones picked in the settings, then a system font with CJK support when
+//! one is found, otherwise the Noto font built into the binary, so Chinese and Japanese
+//! text shows up however the app is packaged.
+
+use anyhow::{Context, Result};
+use eframe::egui;
+
+// Noto Sans SC covers Chinese and the kana. The file isn't checked in, see assets/fonts.
+#[cfg(feature = "bundled-font")]
+const BUNDLED_FONT: Option<&[u8]> = Some(include_bytes!("../assets/fonts/NotoSansSC-Regular.otf"));
+#[cfg(not(feature = "bundled-font"))]
+const BUNDLED_FONT: Option<&[u8]> = None;
+
+fn font_data(handle: font_kit::handle::Handle) -> Result<Vec<u8>> {
+    match handle {
+        font_kit::handle::Handle::Memory { bytes, .. } => Ok(bytes.to_vec()),
+        font_kit::handle::Handle::Path { path, .. } => {
+            std::fs::read(&path).with_context(|| format!("Failed to read font {:?}", path))
+        }
+    }
+}
+
+// A preferred system font for the OS, these have the CJK glyphs
+fn system_cjk_font(source: &font_kit::source::SystemSource) -> Option<(&'static str, Vec<u8>)> {
+    // Define font names to try based on OS for better CJK support
+    let font_names: Vec<&str> = match std::env::consts::OS {
+        "macos" => vec!["PingFang SC", "Hiragino Sans GB", "STSong", "Heiti SC"],
+        "windows" => vec!["Microsoft YaHei", "SimSun", "SimHei", "MS Gothic"],
+        "linux" => vec!["Noto Sans CJK TC"],
+        _ => vec![], // Empty for other OSes - we'll use the fallbacks
+    };
+
+    for font_name in font_names {
+        // Get family by name, and the first font in the family
+        let Some(font_handle) = source
+            .select_family_by_name(font_name)
+            .ok()
+            .and_then(|family| family.fonts().first().cloned())
+        else {
+            continue;
+        };
+        match font_data(font_handle) {
+            Ok(data) => return Some((font_name, data)),
+            Err(e) => tracing::warn!("{:#}", e),
+        }
+    }
+    None
+}
+
+/// Font families installed on the system, sorted by name.
+pub fn system_families() -> Vec<String> {
+    let mut families = font_kit::source::SystemSource::new()
+        .all_families()
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to list system fonts: {}", e);
+            Vec::new()
+        });
+    families.sort_by_key(|family| family.to_lowercase());
+    families.dedup();
+    families
+}
+
+// The regular style of a family picked in the settings
+fn family_font(source: &font_kit::source::SystemSource, family: &str) -> Result<Vec<u8>> {
+    let handle = source
+        .select_best_match(
+            &[font_kit::family_name::FamilyName::Title(family.to_string())],
+            &font_kit::properties::Properties::new(),
+        )
+        .with_context(|| format!("Font '{}' not found", family))?;
+    font_data(handle)
+}
+
+/// Sets up the fonts: the `chosen` families in order, then a system CJK font, else the
+/// built-in one, else any sans-serif system font. egui's own fonts stay behind them for
+/// what they don't cover. Takes effect with the next frame.
+pub fn install(ctx: &egui::Context, chosen: &[String]) {
+    // Create font definitions - start with defaults so we have fallbacks
+    let mut fonts = egui::FontDefinitions::default();
+    let source = font_kit::source::SystemSource::new();
+
+    let mut position = 0;
+    for family in chosen {
+        match family_font(&source, family) {
+            Ok(data) => {
+                add_font(&mut fonts, family, data, position, true);
+                position += 1;
+                tracing::info!("Using font '{}'", family);
+            }
+            // E.g. uninstalled since, the next one takes over
+            Err(e) => tracing::warn!("{:#}", e),
+        }
+    }
+
+    if let Some((font_name, data)) = system_cjk_font(&source) {
+        add_font(&mut fonts, "SystemCJKFont", data, position, true);
+        tracing::info!("Using system font '{}' for CJK support", font_name);
+    } else if let Some(data) = BUNDLED_FONT {
+        add_font(&mut fonts, "BundledCJKFont", data.to_vec(), position, true);
+        tracing::info!("Using the built-in font for CJK support");
+    } else {
+        // Try a generic sans-serif as backup
+        match source
+            .select_best_match(
+                &[font_kit::family_name::FamilyName::SansSerif],
+                &font_kit::properties::Properties::new(),
+            )
+            .context("Could not find suitable system font")
+            .and_then(font_data)
+        {
+            Ok(data) => {
+                add_font(&mut fonts, "SystemFont", data, position, false);
+                tracing::info!("Using generic system font for text");
+            }
+            Err(e) => tracing::warn!("{:#}, using defaults", e),
+        }
+    }
+
+    ctx.set_fonts(fonts);
+}
+
+// Puts the font at `position` for proportional text, and behind the monospace font for
+// the glyphs that one lacks
+fn add_font(
+    fonts: &mut egui::FontDefinitions,
+    name: &str,
+    data: Vec<u8>,
+    position: usize,
+    monospace: bool,
+) {
+    fonts
+        .font_data
+        .insert(name.to_owned(), egui::FontData::from_owned(data).into());
+    let proportional = fonts
+        .families
+        .entry(egui::FontFamily::Proportional)
+        .or_default();
+    proportional.insert(position.min(proportional.len()), name.to_owned());
+    if monospace {
+        fonts
+            .families
+            .entry(egui::FontFamily::Monospace)
+            .or_default()
+            .push(name.to_owned());
+    }
+}
@@ -0,0 +1,101 @@
+//! Brick-wall limiter, by default the last effect of the chain. It caps the level of
+//! what's written to the device after volume, pre-amp and balance, so nothing pushed up
+//! by them reaches the ears or speakers louder than the ceiling.
+
+use crate::dsp::{Effect, EffectConfig, EffectKind};
+
+pub const DEFAULT_CEILING_DB: f32 = -1.0;
+pub const MIN_CEILING_DB: f32 = -24.0;
+
+// How long the gain takes to recover after a peak
+const RELEASE_SECONDS: f32 = 0.1;
+
+pub struct Limiter {
+    ceiling: f32, // Linear
+    gain: f32,    // Applied to the last frame
+}
+
+impl Limiter {
+    pub fn new(ceiling_db: f32) -> Self {
+        let mut limiter = Self {
+            ceiling: 1.0,
+            gain: 1.0,
+        };
+        limiter.set_ceiling_db(ceiling_db);
+        limiter
+    }
+
+    pub fn set_ceiling_db(&mut self, ceiling_db: f32) {
+        self.ceiling = 10f32.powf(ceiling_db.clamp(MIN_CEILING_DB, 0.0) / 20.0);
+    }
+}
+
+impl Effect for Limiter {
+    fn kind(&self) -> EffectKind {
+        EffectKind::Limiter
+    }
+
+    fn configure(&mut self, config: &EffectConfig) {
+        if let EffectConfig::Limiter { ceiling_db } = config {
+            self.set_ceiling_db(*ceiling_db);
+        }
+    }
+
+    fn process(&mut self, planes: &mut [&mut [f32]], rate: u32) {
+        let release = (-1.0 / (RELEASE_SECONDS * rate as f32)).exp();
+        let frames = planes.first().map_or(0, |plane| plane.len());
+        for frame in 0..frames {
+            let peak = planes
+                .iter()
+                .map(|plane| plane[frame].abs())
+                .fold(0.0, f32::max);
+            self.gain = limited_gain(self.gain, peak, self.ceiling, release);
+            if self.gain < 1.0 {
+                for plane in planes.iter_mut() {
+                    plane[frame] *= self.gain;
+                }
+            }
+        }
+    }
+}
+
+// The gain for the next frame. It drops at once to keep the peak under the ceiling and
+// recovers smoothly, so the limiting doesn't crackle.
+fn limited_gain(gain: f32, peak: f32, ceiling: f32, release: f32) -> f32 {
+    let needed = if peak > ceiling { ceiling / peak } else { 1.0 };
+    if needed < gain {
+        needed
+    } else {
+        needed - (needed - gain) * release
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peaks_never_pass_the_ceiling() {
+        let mut left: Vec<f32> = (0..64)
+            .map(|i| if i % 2 == 0 { 1.8 } else { -1.8 })
+            .collect();
+        let mut right = vec![0.2; 64];
+
+        let mut limiter = Limiter::new(-6.0);
+        limiter.process(&mut [&mut left, &mut right], 44100);
+
+        let ceiling = 10f32.powf(-6.0 / 20.0);
+        assert!(left.iter().all(|sample| sample.abs() <= ceiling + 1e-6));
+        // Both sides are turned down together, keeping the stereo image
+        assert!(right.iter().all(|sample| *sample < 0.2));
+    }
+
+    #[test]
+    fn gain_recovers_after_a_peak() {
+        let release = 0.5;
+        let gain = limited_gain(1.0, 2.0, 1.0, release);
+        assert_eq!(gain, 0.5);
+        let gain = limited_gain(gain, 0.1, 1.0, release);
+        assert_eq!(gain, 0.75);
+    }
+}
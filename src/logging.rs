@@ -0,0 +1,148 @@
+//! Tracing output goes to the terminal and to a log file, so there's something to attach
+//! to a bug report. The file is rotated once it outgrows a limit, keeping a few old ones.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+pub const LOG_FILE_NAME: &str = "bird-player.log";
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+// Rotated files kept next to the current one, bird-player.log.1 being the newest
+const KEPT_LOGS: usize = 3;
+
+/// Appends to a file, moving it aside once it would grow past `max_bytes`.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    kept: usize,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, max_bytes: u64, kept: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            written,
+            max_bytes,
+            kept,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for n in (1..self.kept).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+        if self.kept > 0 {
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Logs to the terminal and to `LOG_FILE_NAME` in `dir`. Without a usable log file
+/// only the terminal gets the output.
+pub fn init(dir: &Path) {
+    let file = match RotatingFile::open(dir.join(LOG_FILE_NAME), MAX_LOG_BYTES, KEPT_LOGS) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            eprintln!("Failed to open log file in {:?}: {}", dir, e);
+            None
+        }
+    };
+    let file_layer = file.map(|file| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+    });
+
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(tracing_subscriber::fmt::layer())
+        .with(file_layer)
+        .init();
+}
+
+/// The last `max_bytes` of the log file, cut at a line start.
+pub fn recent_log(dir: &Path, max_bytes: u64) -> io::Result<String> {
+    let mut file = File::open(dir.join(LOG_FILE_NAME))?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(max_bytes)))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let text = match (len > max_bytes, text.find('\n')) {
+        (true, Some(first_break)) => &text[first_break + 1..],
+        _ => &text,
+    };
+    Ok(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_logs_are_moved_aside() {
+        let dir = std::env::temp_dir().join(format!("bird-player-logs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut log = RotatingFile::open(dir.join(LOG_FILE_NAME), 10, 2).unwrap();
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            log.write_all(line.as_bytes()).unwrap();
+        }
+        log.flush().unwrap();
+
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read(LOG_FILE_NAME), "fourth\n");
+        assert_eq!(read("bird-player.log.1"), "third\n");
+        assert_eq!(read("bird-player.log.2"), "second\n");
+        assert!(!dir.join("bird-player.log.3").exists());
+
+        assert_eq!(recent_log(&dir, 100).unwrap(), "fourth\n");
+        assert_eq!(recent_log(&dir, 3).unwrap(), "");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,503 @@
+//! EBU R128 loudness measurement used by the ReplayGain scanner.
+//!
+//! The meter implements the K-weighted, gated integrated loudness described in
+//! ITU-R BS.1770-4. Gating blocks are kept per track so album loudness can be
+//! computed over the concatenation of all tracks of an album.
+
+use std::path::Path;
+
+use symphonia::core::audio::{AudioBufferRef, Channels, SampleBuffer};
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// ReplayGain 2.0 reference level in LUFS.
+pub const REFERENCE_LUFS: f64 = -18.0;
+
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+// Volume leveling estimates loudness from the start of a track only.
+const LEVELING_MIN_SECONDS: usize = 3;
+const LEVELING_MAX_SECONDS: usize = 10;
+const LEVELING_MAX_GAIN_DB: f64 = 12.0;
+// Time constant of the gain smoothing between estimates and tracks.
+const LEVELING_SMOOTHING_SECONDS: f32 = 1.5;
+
+/// Second order IIR filter in direct form I.
+#[derive(Clone, Copy)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl Biquad {
+    fn new(b0: f64, b1: f64, b2: f64, a1: f64, a2: f64) -> Self {
+        Self {
+            b0,
+            b1,
+            b2,
+            a1,
+            a2,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Builds the two K-weighting stages (high shelf + high pass) for a sample rate.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    // Stage 1: high shelf modelling the acoustic effect of the head.
+    let f0 = 1_681.974_450_955_533;
+    let gain_db = 3.999_843_853_973_347;
+    let q = 0.707_175_236_955_419_6;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        (vh + vb * k / q + k * k) / a0,
+        2.0 * (k * k - vh) / a0,
+        (vh - vb * k / q + k * k) / a0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    );
+
+    // Stage 2: RLB high pass.
+    let f0 = 38.135_470_876_024_44;
+    let q = 0.500_327_037_323_877_3;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        1.0,
+        -2.0,
+        1.0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    );
+
+    [shelf, high_pass]
+}
+
+/// Channel weight as defined by BS.1770 (surround channels are boosted, LFE is ignored).
+fn channel_weight(channel: Channels) -> f64 {
+    if channel == Channels::LFE1 || channel == Channels::LFE2 {
+        0.0
+    } else if channel == Channels::SIDE_LEFT
+        || channel == Channels::SIDE_RIGHT
+        || channel == Channels::REAR_LEFT
+        || channel == Channels::REAR_RIGHT
+    {
+        1.41
+    } else {
+        1.0
+    }
+}
+
+pub struct LoudnessMeter {
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    // 100 ms sub-blocks; four of them make one 400 ms gating block with 75% overlap.
+    sub_block_len: usize,
+    sub_block_pos: usize,
+    sub_block_sum: f64,
+    recent_sub_blocks: Vec<f64>,
+    blocks: Vec<f64>,
+    peak: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, weights: Vec<f64>) -> Self {
+        Self {
+            filters: vec![k_weighting(sample_rate); weights.len()],
+            weights,
+            sub_block_len: (sample_rate as usize / 10).max(1),
+            sub_block_pos: 0,
+            sub_block_sum: 0.0,
+            recent_sub_blocks: Vec::with_capacity(4),
+            blocks: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    pub fn for_channels(sample_rate: u32, channels: Channels) -> Self {
+        Self::new(sample_rate, channels.iter().map(channel_weight).collect())
+    }
+
+    /// Feeds interleaved samples into the meter.
+    pub fn add_interleaved(&mut self, samples: &[f32]) {
+        let num_channels = self.weights.len();
+        if num_channels == 0 {
+            return;
+        }
+
+        for frame in samples.chunks_exact(num_channels) {
+            let mut weighted = 0.0;
+            for (ch, &sample) in frame.iter().enumerate() {
+                self.peak = self.peak.max(sample.abs());
+                let [shelf, high_pass] = &mut self.filters[ch];
+                let filtered = high_pass.process(shelf.process(sample as f64));
+                weighted += self.weights[ch] * filtered * filtered;
+            }
+
+            self.sub_block_sum += weighted;
+            self.sub_block_pos += 1;
+
+            if self.sub_block_pos == self.sub_block_len {
+                self.finish_sub_block();
+            }
+        }
+    }
+
+    fn finish_sub_block(&mut self) {
+        let mean = self.sub_block_sum / self.sub_block_len as f64;
+        self.sub_block_sum = 0.0;
+        self.sub_block_pos = 0;
+
+        if self.recent_sub_blocks.len() == 4 {
+            self.recent_sub_blocks.remove(0);
+        }
+        self.recent_sub_blocks.push(mean);
+
+        if self.recent_sub_blocks.len() == 4 {
+            self.blocks
+                .push(self.recent_sub_blocks.iter().sum::<f64>() / 4.0);
+        }
+    }
+
+    /// Integrated loudness of everything measured so far.
+    pub fn loudness(&self) -> Option<f64> {
+        integrated_loudness(&self.blocks)
+    }
+
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    pub fn finish(self) -> LoudnessScan {
+        LoudnessScan {
+            loudness: integrated_loudness(&self.blocks),
+            peak: self.peak,
+            blocks: self.blocks,
+        }
+    }
+}
+
+/// Result of measuring a single track.
+#[derive(Debug, Clone)]
+pub struct LoudnessScan {
+    /// Integrated loudness in LUFS, `None` when everything was below the gates.
+    pub loudness: Option<f64>,
+    /// Sample peak, linear.
+    pub peak: f32,
+    blocks: Vec<f64>,
+}
+
+impl LoudnessScan {
+    /// ReplayGain in dB relative to the reference level.
+    pub fn gain_db(&self) -> Option<f64> {
+        self.loudness.map(|l| REFERENCE_LUFS - l)
+    }
+}
+
+fn block_loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+fn integrated_loudness<'a>(blocks: impl IntoIterator<Item = &'a f64> + Clone) -> Option<f64> {
+    let above_absolute: Vec<f64> = blocks
+        .into_iter()
+        .copied()
+        .filter(|&e| e > 0.0 && block_loudness(e) > ABSOLUTE_GATE_LUFS)
+        .collect();
+
+    if above_absolute.is_empty() {
+        return None;
+    }
+
+    let relative_gate =
+        block_loudness(above_absolute.iter().sum::<f64>() / above_absolute.len() as f64)
+            + RELATIVE_GATE_LU;
+
+    let gated: Vec<f64> = above_absolute
+        .into_iter()
+        .filter(|&e| block_loudness(e) > relative_gate)
+        .collect();
+
+    if gated.is_empty() {
+        return None;
+    }
+
+    Some(block_loudness(
+        gated.iter().sum::<f64>() / gated.len() as f64,
+    ))
+}
+
+/// Integrated loudness and peak over all tracks of an album.
+pub fn album_loudness(scans: &[LoudnessScan]) -> (Option<f64>, f32) {
+    let loudness = integrated_loudness(scans.iter().flat_map(|s| s.blocks.iter()));
+    let peak = scans.iter().map(|s| s.peak).fold(0.0, f32::max);
+    (loudness, peak)
+}
+
+/// Gain applied by the audio thread on top of the user volume.
+///
+/// Tracks with known ReplayGain use it as is. For tracks without it, the loudness
+/// of the first seconds of playback is measured and the gain glides towards the
+/// estimate, which also smooths the jump from the previous track.
+pub struct VolumeLeveler {
+    target_gain: f32,
+    current_gain: f32,
+    meter: Option<LoudnessMeter>,
+    sample_buf: Option<SampleBuffer<f32>>,
+    estimating: bool,
+    measured_frames: usize,
+    next_estimate_frames: usize,
+}
+
+impl Default for VolumeLeveler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VolumeLeveler {
+    pub fn new() -> Self {
+        Self {
+            target_gain: 1.0,
+            current_gain: 1.0,
+            meter: None,
+            sample_buf: None,
+            estimating: false,
+            measured_frames: 0,
+            next_estimate_frames: 0,
+        }
+    }
+
+    /// Sets the gain of the next track, `None` estimates it during playback.
+    pub fn set_track_gain(&mut self, gain: Option<f32>) {
+        self.meter = None;
+        self.sample_buf = None;
+        self.measured_frames = 0;
+        self.next_estimate_frames = 0;
+
+        match gain {
+            Some(gain) => {
+                self.estimating = false;
+                self.target_gain = gain;
+                self.current_gain = gain;
+            }
+            None => self.estimating = true,
+        }
+    }
+
+    /// Feeds the buffer about to be played and returns the gain to apply to it.
+    pub fn process(&mut self, decoded: &AudioBufferRef) -> f32 {
+        let spec = *decoded.spec();
+        let frames = decoded.frames();
+
+        if self.estimating {
+            self.measure(decoded);
+        }
+
+        if self.current_gain != self.target_gain && spec.rate > 0 {
+            let elapsed = frames as f32 / spec.rate as f32;
+            let alpha = 1.0 - (-elapsed / LEVELING_SMOOTHING_SECONDS).exp();
+            self.current_gain += (self.target_gain - self.current_gain) * alpha;
+        }
+
+        self.current_gain
+    }
+
+    fn measure(&mut self, decoded: &AudioBufferRef) {
+        let spec = *decoded.spec();
+        let rate = spec.rate as usize;
+
+        let meter = self
+            .meter
+            .get_or_insert_with(|| LoudnessMeter::for_channels(spec.rate, spec.channels));
+        let buf = self
+            .sample_buf
+            .get_or_insert_with(|| SampleBuffer::<f32>::new(decoded.capacity() as u64, spec));
+        buf.copy_interleaved_ref(decoded.clone());
+        meter.add_interleaved(buf.samples());
+        self.measured_frames += decoded.frames();
+
+        if self.measured_frames < rate * LEVELING_MIN_SECONDS
+            || self.measured_frames < self.next_estimate_frames
+        {
+            return;
+        }
+
+        // Refine the estimate every second until enough of the track was heard
+        self.next_estimate_frames = self.measured_frames + rate;
+
+        if let Some(loudness) = meter.loudness() {
+            let gain_db =
+                (REFERENCE_LUFS - loudness).clamp(-LEVELING_MAX_GAIN_DB, LEVELING_MAX_GAIN_DB);
+            let mut gain = 10f32.powf(gain_db as f32 / 20.0);
+            if meter.peak() > 0.0 {
+                gain = gain.min(1.0 / meter.peak());
+            }
+            self.target_gain = gain;
+        }
+
+        if self.measured_frames >= rate * LEVELING_MAX_SECONDS {
+            self.estimating = false;
+            self.meter = None;
+            self.sample_buf = None;
+        }
+    }
+}
+
+/// Decodes a whole file and measures its loudness.
+pub fn scan_file(path: &Path) -> Result<LoudnessScan, String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| e.to_string())?;
+    let mut reader = probed.format;
+
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| "no supported audio track".to_string())?;
+    let track_id = track.id;
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| e.to_string())?;
+
+    let mut meter: Option<LoudnessMeter> = None;
+    let mut sample_buf: Option<SampleBuffer<f32>> = None;
+
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(ref err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(Error::ResetRequired) => break,
+            Err(err) => return Err(err.to_string()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                let meter = meter
+                    .get_or_insert_with(|| LoudnessMeter::for_channels(spec.rate, spec.channels));
+                let buf = sample_buf.get_or_insert_with(|| {
+                    SampleBuffer::<f32>::new(decoded.capacity() as u64, spec)
+                });
+                buf.copy_interleaved_ref(decoded);
+                meter.add_interleaved(buf.samples());
+            }
+            Err(Error::DecodeError(err)) => {
+                tracing::warn!("loudness scan decode error in {:?}: {}", path, err);
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+
+    meter
+        .map(LoudnessMeter::finish)
+        .ok_or_else(|| "no audio decoded".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(sample_rate: u32, seconds: f32, amplitude: f32, channels: usize) -> Vec<f32> {
+        let frames = (sample_rate as f32 * seconds) as usize;
+        let mut samples = Vec::with_capacity(frames * channels);
+        for i in 0..frames {
+            let t = i as f32 / sample_rate as f32;
+            let s = amplitude * (2.0 * std::f32::consts::PI * 997.0 * t).sin();
+            for _ in 0..channels {
+                samples.push(s);
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn stereo_sine_at_minus_23_dbfs_measures_minus_23_lufs() {
+        let amplitude = 10f32.powf(-23.0 / 20.0);
+        let mut meter = LoudnessMeter::new(48000, vec![1.0, 1.0]);
+        meter.add_interleaved(&sine(48000, 5.0, amplitude, 2));
+        let scan = meter.finish();
+
+        let loudness = scan.loudness.unwrap();
+        assert!((loudness + 23.0).abs() < 0.1, "measured {}", loudness);
+        assert!((scan.gain_db().unwrap() - 5.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn silence_is_gated() {
+        let mut meter = LoudnessMeter::new(44100, vec![1.0, 1.0]);
+        meter.add_interleaved(&vec![0.0; 44100 * 2 * 2]);
+
+        assert_eq!(meter.finish().loudness, None);
+    }
+
+    #[test]
+    fn album_loudness_combines_tracks() {
+        let loud = 10f32.powf(-13.0 / 20.0);
+        let quiet = 10f32.powf(-33.0 / 20.0);
+
+        let mut a = LoudnessMeter::new(48000, vec![1.0, 1.0]);
+        a.add_interleaved(&sine(48000, 3.0, loud, 2));
+        let mut b = LoudnessMeter::new(48000, vec![1.0, 1.0]);
+        b.add_interleaved(&sine(48000, 3.0, quiet, 2));
+
+        let (album, peak) = album_loudness(&[a.finish(), b.finish()]);
+        let album = album.unwrap();
+
+        // The quiet track falls below the relative gate of the combined blocks.
+        assert!((album + 13.0).abs() < 0.2, "measured {}", album);
+        assert!((peak - loud).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,380 @@
+//! Opening and decoding tracks for the audio engine.
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use symphonia::core::audio::AudioBufferRef;
+use symphonia::core::codecs::{Decoder, DecoderOptions, FinalizeResult};
+use symphonia::core::errors::{Error, Result};
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo, Track};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::TimeBase;
+
+use crate::app::audio_format::AudioFormat;
+
+/// Opens tracks by path. The engine only talks to this, so it can be driven by
+/// generated audio in tests. New tracks are opened on a loader thread.
+pub trait MediaSource: Send + Sync {
+    /// Opens a track and positions it at `seek_ts`, in the time base of the track.
+    /// `audio_track` picks one of the file's playable audio tracks, the first when `None`.
+    fn open(
+        &self,
+        path: &Path,
+        audio_track: Option<u32>,
+        seek_ts: u64,
+    ) -> std::result::Result<Box<dyn TrackStream>, String>;
+
+    /// Starts reading a track that's likely to be opened next, so it doesn't wait on a
+    /// network share when it does.
+    fn prefetch(&self, _path: &Path) {}
+}
+
+/// An opened track, decoded one packet at a time.
+pub trait TrackStream: Send {
+    fn info(&self) -> &TrackInfo;
+
+    /// Decodes the next packet.
+    fn next(&mut self) -> std::result::Result<Decoded<'_>, String>;
+
+    /// Called before the track is dropped, while it's still current.
+    fn finish(&mut self) {}
+}
+
+/// What the engine reports to the UI about a loaded track.
+#[derive(Debug, Clone, Default)]
+pub struct TrackInfo {
+    pub duration: u64,
+    pub time_base: Option<TimeBase>,
+    pub format: Option<AudioFormat>,
+}
+
+pub enum Decoded<'a> {
+    /// Audio to play, with the timestamp of its packet.
+    Audio(u64, AudioBufferRef<'a>),
+    /// A packet that produced nothing to play, e.g. before the seek position.
+    Skipped,
+    /// The track is over.
+    End,
+}
+
+// Larger files, e.g. long mixes, are streamed from disk as usual
+const MAX_PREFETCH_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Reads files from disk with symphonia.
+#[derive(Default)]
+pub struct SymphoniaSource {
+    // The last track read ahead into memory. It's kept while it plays, so seeking
+    // doesn't go back to the network either.
+    prefetched: Arc<Mutex<Option<Prefetched>>>,
+}
+
+type Prefetched = (PathBuf, Arc<[u8]>);
+
+impl MediaSource for SymphoniaSource {
+    fn open(
+        &self,
+        path: &Path,
+        audio_track: Option<u32>,
+        seek_ts: u64,
+    ) -> std::result::Result<Box<dyn TrackStream>, String> {
+        let hint = Hint::new();
+        let prefetched = self
+            .prefetched
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|(prefetched, _)| prefetched == path)
+            .map(|(_, data)| data.clone());
+        let (input, file_size): (Box<dyn symphonia::core::io::MediaSource>, _) = match prefetched {
+            Some(data) => {
+                tracing::info!("Playing {} from memory", path.display());
+                let size = data.len() as u64;
+                (Box::new(Cursor::new(data)), Some(size))
+            }
+            None => {
+                let file = std::fs::File::open(path)
+                    .map_err(|err| format!("couldn't open {}: {}", path.display(), err))?;
+                let file_size = file.metadata().ok().map(|metadata| metadata.len());
+                (Box::new(file), file_size)
+            }
+        };
+        let mss = MediaSourceStream::new(input, Default::default());
+        let format_opts = FormatOptions {
+            enable_gapless: true,
+            ..Default::default()
+        };
+        let metadata_opts: MetadataOptions = Default::default();
+
+        let probed = symphonia::default::get_probe()
+            .format(&hint, mss, &format_opts, &metadata_opts)
+            .map_err(|err| {
+                // The input was not supported by any format reader.
+                format!(
+                    "the audio format of {} is not supported: {}",
+                    path.display(),
+                    err
+                )
+            })?;
+        let mut reader = probed.format;
+
+        let Some((track_id, seek_ts)) = seek_reader(reader.as_mut(), audio_track, seek_ts) else {
+            return Err(format!("no playable track in {}", path.display()));
+        };
+        let track = reader
+            .tracks()
+            .iter()
+            .find(|track| track.id == track_id)
+            .ok_or_else(|| format!("no playable track in {}", path.display()))?;
+
+        // Create a decoder for the track.
+        let decode_opts = DecoderOptions { verify: true };
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &decode_opts)
+            .map_err(|err| format!("no decoder for {}: {}", path.display(), err))?;
+
+        // Get the selected track's timebase and duration.
+        let time_base = track.codec_params.time_base;
+        let duration = track
+            .codec_params
+            .n_frames
+            .map(|frames| track.codec_params.start_ts + frames);
+
+        tracing::info!(
+            "Track Duration: {}, TimeBase: {:?}",
+            duration.unwrap_or(0),
+            time_base
+        );
+
+        let info = TrackInfo {
+            duration: duration.unwrap_or(0),
+            time_base,
+            format: Some(AudioFormat::from_codec_params(
+                &track.codec_params,
+                file_size,
+            )),
+        };
+
+        Ok(Box::new(SymphoniaTrack {
+            reader,
+            decoder,
+            track_id,
+            seek_ts,
+            info,
+        }))
+    }
+
+    fn prefetch(&self, path: &Path) {
+        let already = self
+            .prefetched
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|(prefetched, _)| prefetched == path);
+        if already {
+            return;
+        }
+
+        let prefetched = self.prefetched.clone();
+        let path = path.to_path_buf();
+        std::thread::spawn(move || {
+            let too_big = std::fs::metadata(&path)
+                .map_or(true, |metadata| metadata.len() > MAX_PREFETCH_BYTES);
+            if too_big {
+                return;
+            }
+            match std::fs::read(&path) {
+                Ok(data) => {
+                    tracing::info!("Prefetched {} ({} bytes)", path.display(), data.len());
+                    *prefetched.lock().unwrap() = Some((path, data.into()));
+                }
+                Err(err) => tracing::warn!("Couldn't prefetch {}: {}", path.display(), err),
+            }
+        });
+    }
+}
+
+struct SymphoniaTrack {
+    reader: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    // Packets before this timestamp are decoded but not played
+    seek_ts: u64,
+    info: TrackInfo,
+}
+
+impl TrackStream for SymphoniaTrack {
+    fn info(&self) -> &TrackInfo {
+        &self.info
+    }
+
+    fn next(&mut self) -> std::result::Result<Decoded<'_>, String> {
+        // Get the next packet from the format reader.
+        let packet = match self.reader.next_packet() {
+            Ok(packet) => packet,
+            Err(err) => {
+                return match ignore_end_of_stream_error(Err(err)) {
+                    Ok(()) => Ok(Decoded::End),
+                    Err(err) => Err(format!("couldn't read packet: {}", err)),
+                };
+            }
+        };
+
+        // If the packet does not belong to the selected track, skip it. Files with more
+        // than one track interleave their packets, so this is routine.
+        if packet.track_id() != self.track_id {
+            tracing::trace!("skipping a packet of track {}", packet.track_id());
+            return Ok(Decoded::Skipped);
+        }
+
+        // Decode the packet into audio samples. Only packets with a presentation timestamp
+        // >= the seeked position (0 if not seeking) are played.
+        match self.decoder.decode(&packet) {
+            Ok(decoded) if packet.ts() >= self.seek_ts => Ok(Decoded::Audio(packet.ts(), decoded)),
+            Ok(_) => Ok(Decoded::Skipped),
+            Err(Error::DecodeError(err)) => {
+                // Decode errors are not fatal. Print the error message and try to decode the next
+                // packet as usual.
+                tracing::warn!("decode error: {}", err);
+                Ok(Decoded::Skipped)
+            }
+            Err(err) => Err(format!("decoder failed: {}", err)),
+        }
+    }
+
+    fn finish(&mut self) {
+        do_verification(self.decoder.finalize());
+    }
+}
+
+/// Picks the track to play and seeks the reader to `seek_ts`. Returns the track and the
+/// timestamp playback actually starts at.
+fn seek_reader(
+    reader: &mut dyn FormatReader,
+    audio_track: Option<u32>,
+    seek_ts: u64,
+) -> Option<(u32, u64)> {
+    let mut track_id = pick_track(reader.tracks(), audio_track)?.id;
+
+    // Seek the reader to the timestamp specified and get the timestamp of the seeked
+    // position. All packets with a timestamp < the seeked position will not be played.
+    //
+    // Note: This is a half-baked approach to seeking! After seeking the reader, packets should be
+    // decoded and *samples* discarded up-to the exact *sample* indicated by required_ts. The
+    // current approach will discard excess samples if seeking to a sample within a packet.
+    let seek_to = SeekTo::TimeStamp {
+        ts: seek_ts,
+        track_id,
+    };
+
+    // Attempt the seek. If the seek fails, ignore the error and return a seek timestamp of 0 so
+    // that no samples are trimmed.
+    let seek_ts = match reader.seek(SeekMode::Accurate, seek_to) {
+        Ok(seeked_to) => seeked_to.required_ts,
+        Err(Error::ResetRequired) => {
+            tracing::warn!("reset required...");
+            track_id = pick_track(reader.tracks(), audio_track)?.id;
+            0
+        }
+        Err(err) => {
+            // Don't give-up on a seek error.
+            tracing::warn!("seek error: {}", err);
+            0
+        }
+    };
+
+    tracing::info!("seek ts: {}", seek_ts);
+
+    Some((track_id, seek_ts))
+}
+
+// The tracks there's a decoder for, the ones `audio_track` counts. Leaves out a video's
+// picture and subtitle tracks as well as audio in codecs we can't play.
+fn supported_tracks(tracks: &[Track]) -> impl Iterator<Item = &Track> {
+    tracks.iter().filter(|t| {
+        symphonia::default::get_codecs()
+            .get_codec(t.codec_params.codec)
+            .is_some()
+    })
+}
+
+// The picked track, or the first one if there's no pick or the file changed since
+fn pick_track(tracks: &[Track], audio_track: Option<u32>) -> Option<&Track> {
+    audio_track
+        .and_then(|number| supported_tracks(tracks).nth(number as usize))
+        .or_else(|| supported_tracks(tracks).next())
+}
+
+/// Describes the playable audio tracks of a file, in the order `audio_track` counts
+/// them, e.g. to pick one of a Matroska file's.
+pub fn audio_tracks(path: &Path) -> std::result::Result<Vec<String>, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|err| format!("couldn't open {}: {}", path.display(), err))?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let probed = symphonia::default::get_probe()
+        .format(&Hint::new(), mss, &Default::default(), &Default::default())
+        .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+
+    Ok(supported_tracks(probed.format.tracks())
+        .map(|track| {
+            let format = AudioFormat::from_codec_params(&track.codec_params, None).summary();
+            match track
+                .language
+                .as_deref()
+                .filter(|language| !language.is_empty())
+            {
+                Some(language) => format!("{} ({})", format, language),
+                None => format,
+            }
+        })
+        .collect())
+}
+
+fn ignore_end_of_stream_error(result: Result<()>) -> Result<()> {
+    match result {
+        Err(Error::IoError(err))
+            if err.kind() == std::io::ErrorKind::UnexpectedEof
+                && err.to_string() == "end of stream" =>
+        {
+            // Do not treat "end of stream" as a fatal error. It's the currently only way a
+            // format reader can indicate the media is complete.
+            Ok(())
+        }
+        _ => result,
+    }
+}
+
+fn do_verification(finalization: FinalizeResult) {
+    if let Some(is_ok) = finalization.verify_ok {
+        // Verification not enabled by user, or unsupported by the codec otherwise.
+        tracing::info!("verification: {}", if is_ok { "passed" } else { "failed" });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::codecs::{
+        CodecParameters, CODEC_TYPE_FLAC, CODEC_TYPE_NULL, CODEC_TYPE_OPUS,
+    };
+
+    #[test]
+    fn picked_tracks_count_the_playable_ones() {
+        let track = |id, codec| Track::new(id, CodecParameters::new().for_codec(codec).clone());
+        // A subtitle track doesn't count
+        let tracks = [
+            track(1, CODEC_TYPE_FLAC),
+            track(2, CODEC_TYPE_NULL),
+            track(3, CODEC_TYPE_FLAC),
+        ];
+        assert_eq!(pick_track(&tracks, None).map(|t| t.id), Some(1));
+        assert_eq!(pick_track(&tracks, Some(1)).map(|t| t.id), Some(3));
+        // A pick the file no longer has falls back to the first
+        assert_eq!(pick_track(&tracks, Some(5)).map(|t| t.id), Some(1));
+        assert!(pick_track(&tracks[1..2], None).is_none());
+        // Nor does audio without a decoder
+        assert!(pick_track(&[track(4, CODEC_TYPE_OPUS)], None).is_none());
+    }
+}
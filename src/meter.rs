@@ -0,0 +1,147 @@
+//! Output levels for the meters in the footer. The audio callback measures each block it
+//! hands to the device and publishes it through atomics, so it never waits on the UI.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Levels of the left and right channel as last written to the device.
+pub static OUTPUT_LEVELS: OutputLevels = OutputLevels::new();
+
+pub struct OutputLevels {
+    channels: [ChannelLevel; 2],
+}
+
+struct ChannelLevel {
+    // Highest values since the UI last looked, as f32 bits. The bits of positive floats
+    // sort like the floats, so `fetch_max` works on them.
+    peak: AtomicU32,
+    rms: AtomicU32,
+    // Stays set until the user resets it
+    clipped: AtomicBool,
+}
+
+impl ChannelLevel {
+    const fn new() -> Self {
+        Self {
+            peak: AtomicU32::new(0),
+            rms: AtomicU32::new(0),
+            clipped: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Levels read by the UI, linear between 0 and 1 (or above when clipping).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Levels {
+    pub peak: [f32; 2],
+    pub rms: [f32; 2],
+    pub clipped: [bool; 2],
+}
+
+impl OutputLevels {
+    pub const fn new() -> Self {
+        Self {
+            channels: [ChannelLevel::new(), ChannelLevel::new()],
+        }
+    }
+
+    pub fn publish(&self, block: &BlockLevels) {
+        for (side, level) in self.channels.iter().enumerate() {
+            if block.count[side] == 0 {
+                continue;
+            }
+            let rms = (block.sum_squares[side] / block.count[side] as f32).sqrt();
+            level
+                .peak
+                .fetch_max(block.peak[side].to_bits(), Ordering::Relaxed);
+            level.rms.fetch_max(rms.to_bits(), Ordering::Relaxed);
+            if block.peak[side] >= 1.0 {
+                level.clipped.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The highest levels since the last call.
+    pub fn take(&self) -> Levels {
+        let mut levels = Levels::default();
+        for (side, level) in self.channels.iter().enumerate() {
+            levels.peak[side] = f32::from_bits(level.peak.swap(0, Ordering::Relaxed));
+            levels.rms[side] = f32::from_bits(level.rms.swap(0, Ordering::Relaxed));
+            levels.clipped[side] = level.clipped.load(Ordering::Relaxed);
+        }
+        levels
+    }
+
+    pub fn reset_clipped(&self) {
+        for level in &self.channels {
+            level.clipped.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Collects one block of interleaved samples in the audio callback.
+#[derive(Debug, Default)]
+pub struct BlockLevels {
+    peak: [f32; 2],
+    sum_squares: [f32; 2],
+    count: [usize; 2],
+}
+
+impl BlockLevels {
+    /// Adds a sample of `channel` out of `channels`. Mono shows on both meters, channels
+    /// past the first two aren't metered.
+    pub fn add(&mut self, channel: usize, channels: usize, sample: f32) {
+        match (channels, channel) {
+            (1, _) => {
+                self.add_to(0, sample);
+                self.add_to(1, sample);
+            }
+            (_, 0 | 1) => self.add_to(channel, sample),
+            _ => {}
+        }
+    }
+
+    fn add_to(&mut self, side: usize, sample: f32) {
+        let magnitude = sample.abs();
+        self.peak[side] = self.peak[side].max(magnitude);
+        self.sum_squares[side] += magnitude * magnitude;
+        self.count[side] += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_are_held_until_taken() {
+        let levels = OutputLevels::new();
+
+        let mut loud = BlockLevels::default();
+        for sample in [0.5, -0.5, 0.5, -0.5] {
+            loud.add(0, 2, sample);
+        }
+        let mut quiet = BlockLevels::default();
+        quiet.add(0, 2, 0.1);
+        levels.publish(&loud);
+        levels.publish(&quiet);
+
+        let taken = levels.take();
+        assert_eq!(taken.peak, [0.5, 0.0]);
+        assert_eq!(taken.rms, [0.5, 0.0]);
+        assert_eq!(taken.clipped, [false, false]);
+        assert_eq!(levels.take().peak, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn clipping_stays_until_reset() {
+        let levels = OutputLevels::new();
+        let mut block = BlockLevels::default();
+        block.add(0, 1, -1.0);
+        levels.publish(&block);
+
+        assert_eq!(levels.take().clipped, [true, true]);
+        assert_eq!(levels.take().clipped, [true, true]);
+        levels.reset_clipped();
+        assert_eq!(levels.take().clipped, [false, false]);
+    }
+}
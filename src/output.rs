@@ -13,8 +13,46 @@ use symphonia::core::audio::{AudioBufferRef, SignalSpec};
 use symphonia::core::units::Duration;
 
 pub trait AudioOutput {
-    fn write(&mut self, decoded: AudioBufferRef<'_>, volume: f32) -> Result<()>;
+    /// Queues audio for the device. Volume and effects are already applied.
+    fn write(&mut self, decoded: AudioBufferRef<'_>) -> Result<()>;
     fn flush(&mut self);
+    fn info(&self) -> &OutputInfo;
+
+    /// Ramps the output down to silence and returns once it is quiet. Audio that is
+    /// still queued stays queued until `fade_in`.
+    fn fade_out(&mut self) {}
+
+    /// Ramps the output up from silence.
+    fn fade_in(&mut self) {}
+
+    /// Stops the device from pulling audio. Everything queued stays where it is, so
+    /// `resume` continues at the exact sample playback stopped at.
+    fn pause(&mut self) {}
+
+    fn resume(&mut self) {}
+
+    /// The track ended and nothing is written until the next one plays. The queued
+    /// audio still plays out.
+    fn end_of_stream(&mut self) {}
+}
+
+/// Length of the soft fades on pause, resume, stop and seek.
+pub const FADE_DURATION: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// The device an output stream plays on and how the decoded audio is converted for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputInfo {
+    pub device_name: String,
+    /// Sample format written to the device, e.g. "f32" or "i16".
+    pub sample_format: String,
+    pub source_rate: u32,
+    pub device_rate: u32,
+}
+
+impl OutputInfo {
+    pub fn is_resampled(&self) -> bool {
+        self.source_rate != self.device_rate
+    }
 }
 
 #[allow(dead_code)]
@@ -28,9 +66,283 @@ pub enum AudioOutputError {
 
 pub type Result<T> = result::Result<T, AudioOutputError>;
 
+/// Soft fades and pausing for outputs that block on a server queue instead of being pulled
+/// from by a callback. Everything sent to the server gets played, so the last `FADE_DURATION`
+/// of audio is held back until the next write and a fade out ramps that tail down instead.
+/// A server that can't be corked is flushed on pause and gets the unplayed audio again.
+#[cfg_attr(
+    not(all(target_os = "linux", feature = "pulseaudio")),
+    allow(dead_code)
+)]
+mod blocking {
+    use super::{AudioOutput, OutputInfo, Result};
+
+    use std::collections::VecDeque;
+
+    use symphonia::core::audio::{AudioBufferRef, SampleBuffer, SignalSpec};
+    use symphonia::core::units::Duration;
+
+    /// An interleaved f32 stream on a sound server.
+    pub trait Sink {
+        fn write(&mut self, samples: &[f32]) -> Result<()>;
+
+        /// Blocks until everything written has been played.
+        fn drain(&mut self);
+
+        /// Frames written but not played yet.
+        fn queued_frames(&mut self) -> usize;
+
+        /// Drops everything written but not played yet.
+        fn discard(&mut self);
+    }
+
+    /// How much of the sent audio is kept to requeue on pause. More than a server queues.
+    const SENT_HISTORY: std::time::Duration = std::time::Duration::from_secs(1);
+
+    pub struct BlockingOutput<S> {
+        sink: S,
+        sample_buf: SampleBuffer<f32>,
+        // Interleaved samples not sent yet, at most `hold_len` of them between writes
+        held: Vec<f32>,
+        hold_len: usize,
+        // The newest sent samples, at most `sent_len` of them
+        sent: VecDeque<f32>,
+        sent_len: usize,
+        channels: usize,
+        // The gain the last sent frame got and how much it rises per frame after a fade in
+        gain: f32,
+        fade_step: f32,
+        muted: bool,
+        info: OutputInfo,
+    }
+
+    impl<S: Sink> BlockingOutput<S> {
+        pub fn new(sink: S, spec: SignalSpec, duration: Duration, info: OutputInfo) -> Self {
+            let channels = spec.channels.count();
+            let fade_frames = (super::FADE_DURATION.as_secs_f32() * spec.rate as f32).max(1.0);
+            BlockingOutput {
+                sink,
+                sample_buf: SampleBuffer::new(duration, spec),
+                held: Vec::new(),
+                hold_len: fade_frames as usize * channels,
+                sent: VecDeque::new(),
+                sent_len: (SENT_HISTORY.as_secs_f32() * spec.rate as f32) as usize * channels,
+                channels,
+                gain: 1.0,
+                fade_step: 1.0 / fade_frames,
+                muted: false,
+                info,
+            }
+        }
+
+        /// Sends the first `len` held samples, ramping them up if a fade in is running.
+        fn send(&mut self, len: usize) -> Result<()> {
+            let samples = &mut self.held[..len];
+            if self.gain < 1.0 {
+                for frame in samples.chunks_mut(self.channels) {
+                    self.gain = (self.gain + self.fade_step).min(1.0);
+                    frame.iter_mut().for_each(|sample| *sample *= self.gain);
+                }
+            }
+            let result = self.sink.write(samples);
+            self.remember_sent(len);
+            result
+        }
+
+        fn remember_sent(&mut self, len: usize) {
+            self.sent.extend(self.held.drain(..len));
+            let excess = self.sent.len().saturating_sub(self.sent_len);
+            self.sent.drain(..excess);
+        }
+    }
+
+    impl<S: Sink> AudioOutput for BlockingOutput<S> {
+        fn write(&mut self, decoded: AudioBufferRef<'_>) -> Result<()> {
+            if decoded.frames() == 0 {
+                return Ok(());
+            }
+
+            self.sample_buf.copy_interleaved_ref(decoded);
+            self.held.extend_from_slice(self.sample_buf.samples());
+            if self.muted {
+                return Ok(());
+            }
+
+            let ready = self.held.len().saturating_sub(self.hold_len);
+            self.send(ready)
+        }
+
+        fn flush(&mut self) {
+            if self.muted {
+                self.held.clear();
+            } else {
+                let _ = self.send(self.held.len());
+            }
+            self.sink.drain();
+        }
+
+        fn info(&self) -> &OutputInfo {
+            &self.info
+        }
+
+        fn fade_out(&mut self) {
+            if self.muted {
+                return;
+            }
+
+            // Ramp across whatever is held, which is less than a full fade right after opening
+            let frames = self.held.len() / self.channels;
+            for (i, frame) in self.held.chunks_mut(self.channels).enumerate() {
+                let gain = self.gain * (frames - 1 - i) as f32 / frames as f32;
+                frame.iter_mut().for_each(|sample| *sample *= gain);
+            }
+            let _ = self.sink.write(&self.held);
+            self.held.clear();
+            self.sink.drain();
+            self.sent.clear();
+            self.gain = 0.0;
+            self.muted = true;
+        }
+
+        fn fade_in(&mut self) {
+            self.muted = false;
+        }
+
+        fn pause(&mut self) {
+            // Take back what the server hasn't played, so the next write starts right there
+            let queued = (self.sink.queued_frames() * self.channels).min(self.sent.len());
+            self.sink.discard();
+            let unplayed = self.sent.drain(self.sent.len() - queued..);
+            self.held.splice(..0, unplayed);
+            self.sent.clear();
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, Channels, Signal};
+
+        #[derive(Default)]
+        struct SinkLog {
+            sent: Vec<f32>,
+            drains: usize,
+            queued: usize,
+            discards: usize,
+        }
+
+        struct FakeSink(Rc<RefCell<SinkLog>>);
+
+        impl Sink for FakeSink {
+            fn write(&mut self, samples: &[f32]) -> Result<()> {
+                self.0.borrow_mut().sent.extend_from_slice(samples);
+                Ok(())
+            }
+
+            fn drain(&mut self) {
+                self.0.borrow_mut().drains += 1;
+            }
+
+            fn queued_frames(&mut self) -> usize {
+                self.0.borrow().queued
+            }
+
+            fn discard(&mut self) {
+                self.0.borrow_mut().discards += 1;
+            }
+        }
+
+        // 1 kHz stereo, so a fade is 50 frames
+        fn open() -> (Box<dyn AudioOutput>, Rc<RefCell<SinkLog>>) {
+            let log = Rc::new(RefCell::new(SinkLog::default()));
+            let spec = SignalSpec::new(1000, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+            let info = OutputInfo {
+                device_name: "fake".to_string(),
+                sample_format: "f32".to_string(),
+                source_rate: 1000,
+                device_rate: 1000,
+            };
+            let output = BlockingOutput::new(FakeSink(log.clone()), spec, 100, info);
+            (Box::new(output), log)
+        }
+
+        fn write_frames(output: &mut dyn AudioOutput, frames: impl Iterator<Item = f32>) {
+            let frames: Vec<f32> = frames.collect();
+            let spec = SignalSpec::new(1000, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+            let mut buffer = AudioBuffer::<f32>::new(frames.len() as u64, spec);
+            buffer.render_reserved(Some(frames.len()));
+            for plane in buffer.planes_mut().planes() {
+                plane.copy_from_slice(&frames);
+            }
+            output.write(buffer.as_audio_buffer_ref()).unwrap();
+        }
+
+        fn write_ones(output: &mut dyn AudioOutput, frames: usize) {
+            write_frames(output, std::iter::repeat_n(1.0, frames));
+        }
+
+        #[test]
+        fn a_fade_out_ramps_the_held_tail_down_and_waits_for_it() {
+            let (mut output, log) = open();
+            write_ones(output.as_mut(), 100);
+            assert_eq!(log.borrow().sent, vec![1.0; 100]);
+
+            output.fade_out();
+            let log = log.borrow();
+            assert_eq!(log.sent.len(), 200);
+            assert_eq!(log.drains, 1);
+            let tail = &log.sent[100..];
+            assert!(tail.windows(3).all(|w| w[2] <= w[0]));
+            assert!(tail[0] > 0.9 && tail[0] < 1.0);
+            assert_eq!(tail[tail.len() - 1], 0.0);
+        }
+
+        #[test]
+        fn a_fade_in_ramps_up_from_silence() {
+            let (mut output, log) = open();
+            write_ones(output.as_mut(), 100);
+            output.fade_out();
+
+            write_ones(output.as_mut(), 100);
+            assert_eq!(log.borrow().sent.len(), 200);
+
+            output.fade_in();
+            write_ones(output.as_mut(), 100);
+            let log = log.borrow();
+            let ramp = &log.sent[200..];
+            assert_eq!(ramp.len(), 300);
+            assert!(ramp[0] > 0.0 && ramp[0] < 0.1);
+            assert!(ramp.windows(3).all(|w| w[2] >= w[0]));
+            assert_eq!(ramp[ramp.len() - 1], 1.0);
+        }
+
+        #[test]
+        fn a_pause_requeues_what_the_server_has_not_played() {
+            let (mut output, log) = open();
+            write_frames(output.as_mut(), (0..100).map(|frame| frame as f32));
+            log.borrow_mut().queued = 20;
+
+            output.pause();
+            assert_eq!(log.borrow().discards, 1);
+
+            output.resume();
+            write_frames(output.as_mut(), (100..150).map(|frame| frame as f32));
+            let log = log.borrow();
+            let frames: Vec<f32> = log.sent.iter().step_by(2).copied().collect();
+            let expected: Vec<f32> = (0..50).chain(30..100).map(|frame| frame as f32).collect();
+            assert_eq!(frames, expected);
+        }
+    }
+}
+
 #[cfg(all(target_os = "linux", feature = "pulseaudio"))]
 mod pulseaudio {
-    use super::{AudioOutput, AudioOutputError, Result};
+    use super::blocking::{BlockingOutput, Sink};
+    use super::{AudioOutput, AudioOutputError, OutputInfo, Result};
 
     use symphonia::core::audio::*;
     use symphonia::core::units::Duration;
@@ -40,17 +352,59 @@ mod pulseaudio {
 
     use log::{error, warn};
 
-    pub struct PulseAudioOutput {
+    /// How much audio the server keeps queued. Short, since a fade out waits for it to play.
+    const SERVER_BUFFER_MS: u32 = 100;
+
+    pub struct PulseSink {
         pa: psimple::Simple,
-        sample_buf: RawSampleBuffer<f32>,
+        rate: u32,
+        bytes: Vec<u8>,
+    }
+
+    impl Sink for PulseSink {
+        fn write(&mut self, samples: &[f32]) -> Result<()> {
+            self.bytes.clear();
+            for sample in samples {
+                self.bytes.extend_from_slice(&sample.to_ne_bytes());
+            }
+
+            match self.pa.write(&self.bytes) {
+                Err(err) => {
+                    error!("audio output stream write error: {}", err);
+
+                    Err(AudioOutputError::StreamClosedError)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        fn drain(&mut self) {
+            // Drain is best-effort, ignore the returned result.
+            let _ = self.pa.drain();
+        }
+
+        fn queued_frames(&mut self) -> usize {
+            match self.pa.get_latency() {
+                Ok(latency) => (latency.0 * self.rate as u64 / 1_000_000) as usize,
+                Err(err) => {
+                    warn!("audio output stream latency error: {}", err);
+                    0
+                }
+            }
+        }
+
+        fn discard(&mut self) {
+            // The simple API can't cork the stream, so a pause empties it instead.
+            if let Err(err) = self.pa.flush() {
+                error!("audio output stream flush error: {}", err);
+            }
+        }
     }
 
+    pub struct PulseAudioOutput;
+
     impl PulseAudioOutput {
         pub fn try_open(spec: SignalSpec, duration: Duration) -> Result<Box<dyn AudioOutput>> {
-            // An interleaved buffer is required to send data to PulseAudio. Use a SampleBuffer to
-            // move data between Symphonia AudioBuffers and the byte buffers required by PulseAudio.
-            let sample_buf = RawSampleBuffer::<f32>::new(duration, spec);
-
             // Create a PulseAudio stream specification.
             let pa_spec = pulse::sample::Spec {
                 format: pulse::sample::Format::FLOAT32NE,
@@ -62,16 +416,15 @@ mod pulseaudio {
 
             let pa_ch_map = map_channels_to_pa_channelmap(spec.channels);
 
-            // PulseAudio seems to not play very short audio buffers, use these custom buffer
-            // attributes for very short audio streams.
-            //
-            // let pa_buf_attr = pulse::def::BufferAttr {
-            //     maxlength: std::u32::MAX,
-            //     tlength: 1024,
-            //     prebuf: std::u32::MAX,
-            //     minreq: std::u32::MAX,
-            //     fragsize: std::u32::MAX,
-            // };
+            // Keep the server queue short, the default holds about two seconds
+            let bytes_per_ms = spec.rate * spec.channels.count() as u32 * 4 / 1000;
+            let pa_buf_attr = pulse::def::BufferAttr {
+                maxlength: u32::MAX,
+                tlength: bytes_per_ms * SERVER_BUFFER_MS,
+                prebuf: u32::MAX,
+                minreq: u32::MAX,
+                fragsize: u32::MAX,
+            };
 
             // Create a PulseAudio connection.
             let pa_result = psimple::Simple::new(
@@ -82,11 +435,26 @@ mod pulseaudio {
                 "Music",                            // Description of the stream
                 &pa_spec,                           // Signal specification
                 pa_ch_map.as_ref(),                 // Channel map
-                None,                               // Custom buffering attributes
+                Some(&pa_buf_attr),                 // Custom buffering attributes
             );
 
             match pa_result {
-                Ok(pa) => Ok(Box::new(PulseAudioOutput { pa, sample_buf })),
+                // The stream is opened at the source rate, any conversion happens in the server
+                Ok(pa) => Ok(Box::new(BlockingOutput::new(
+                    PulseSink {
+                        pa,
+                        rate: spec.rate,
+                        bytes: Vec::new(),
+                    },
+                    spec,
+                    duration,
+                    OutputInfo {
+                        device_name: "PulseAudio".to_string(),
+                        sample_format: "f32".to_string(),
+                        source_rate: spec.rate,
+                        device_rate: spec.rate,
+                    },
+                ))),
                 Err(err) => {
                     error!("audio output stream open error: {}", err);
 
@@ -96,66 +464,6 @@ mod pulseaudio {
         }
     }
 
-    impl AudioOutput for PulseAudioOutput {
-        fn write(&mut self, decoded: AudioBufferRef<'_>, volume: f32) -> Result<()> {
-            // Do nothing if there are no audio frames.
-            if decoded.frames() == 0 {
-                return Ok(());
-            }
-
-            // Interleave samples from the audio buffer into the sample buffer.
-            self.sample_buf.copy_interleaved_ref(decoded);
-
-            // Apply volume adjustment
-            if volume != 1.0 {
-                // Use a temporary buffer to apply volume
-                let buf_bytes = self.sample_buf.as_bytes();
-                let sample_count = buf_bytes.len() / std::mem::size_of::<f32>();
-
-                // Create a buffer with the samples
-                let mut volume_adjusted = Vec::with_capacity(buf_bytes.len());
-                volume_adjusted.extend_from_slice(buf_bytes);
-
-                // Convert the buffer to f32 samples and apply volume
-                let samples_f32 = unsafe {
-                    std::slice::from_raw_parts_mut(
-                        volume_adjusted.as_mut_ptr() as *mut f32,
-                        sample_count,
-                    )
-                };
-
-                // Apply volume
-                for sample in samples_f32 {
-                    *sample *= volume;
-                }
-
-                // Write the volume-adjusted buffer to PulseAudio
-                match self.pa.write(&volume_adjusted) {
-                    Err(err) => {
-                        error!("audio output stream write error: {}", err);
-                        return Err(AudioOutputError::StreamClosedError);
-                    }
-                    _ => return Ok(()),
-                }
-            }
-
-            // Write interleaved samples to PulseAudio.
-            match self.pa.write(self.sample_buf.as_bytes()) {
-                Err(err) => {
-                    error!("audio output stream write error: {}", err);
-
-                    Err(AudioOutputError::StreamClosedError)
-                }
-                _ => Ok(()),
-            }
-        }
-
-        fn flush(&mut self) {
-            // Flush is best-effort, ignore the returned result.
-            let _ = self.pa.drain();
-        }
-    }
-
     /// Maps a set of Symphonia `Channels` to a PulseAudio channel map.
     fn map_channels_to_pa_channelmap(channels: Channels) -> Option<pulse::channelmap::Map> {
         let mut map: pulse::channelmap::Map = Default::default();
@@ -207,7 +515,10 @@ pub fn try_open(spec: SignalSpec, duration: Duration) -> Result<Box<dyn AudioOut
 mod cpal {
     use crate::resampler::Resampler;
 
-    use super::{AudioOutput, AudioOutputError, Result};
+    use super::{AudioOutput, AudioOutputError, OutputInfo, Result};
+    use crate::buffer_stats::OUTPUT_BUFFER;
+    use crate::meter::{BlockLevels, OUTPUT_LEVELS};
+    use crate::perf_stats::RESAMPLER;
 
     use symphonia::core::audio::{AudioBufferRef, RawSample, SampleBuffer, SignalSpec};
     use symphonia::core::conv::{ConvertibleSample, IntoSample};
@@ -216,6 +527,9 @@ mod cpal {
     use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
     use rb::*;
 
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, OnceLock};
+
     use log::{error, info};
 
     pub struct CpalAudioOutput;
@@ -223,27 +537,46 @@ mod cpal {
     trait AudioOutputSample:
         cpal::Sample + ConvertibleSample + IntoSample<f32> + RawSample + std::marker::Send + 'static
     {
-        fn mul(&self, n: f32) -> Self;
-    }
-
-    impl AudioOutputSample for f32 {
-        fn mul(&self, n: f32) -> Self {
-            self * n
-        }
-    }
-
-    // TODO - I don't think this will actually work as intended due to truncation?
-    impl AudioOutputSample for i16 {
+        // Scales towards the middle, which for unsigned samples isn't zero
         fn mul(&self, n: f32) -> Self {
-            (*self as f32 * n) as i16
+            self.mul_amp(<Self::Float as cpal::FromSample<f32>>::from_sample_(n))
         }
     }
 
-    // TODO - I don't think this will actually work as intended due to truncation?
-    impl AudioOutputSample for u16 {
-        fn mul(&self, n: f32) -> Self {
-            (*self as f32 * n) as u16
+    impl AudioOutputSample for f32 {}
+    impl AudioOutputSample for f64 {}
+    impl AudioOutputSample for i8 {}
+    impl AudioOutputSample for i16 {}
+    impl AudioOutputSample for i32 {}
+    impl AudioOutputSample for u8 {}
+    impl AudioOutputSample for u16 {}
+    impl AudioOutputSample for u32 {}
+
+    // The device formats decoded audio converts to, the preferred first. There's no
+    // conversion to 64 bit integers.
+    const OUTPUT_FORMATS: [cpal::SampleFormat; 8] = [
+        cpal::SampleFormat::F32,
+        cpal::SampleFormat::I32,
+        cpal::SampleFormat::I16,
+        cpal::SampleFormat::F64,
+        cpal::SampleFormat::U32,
+        cpal::SampleFormat::U16,
+        cpal::SampleFormat::I8,
+        cpal::SampleFormat::U8,
+    ];
+
+    // The device's default format when it's one to convert to, otherwise the preferred
+    // one among those the device offers
+    fn pick_format(
+        default: cpal::SampleFormat,
+        offered: &[cpal::SampleFormat],
+    ) -> Option<cpal::SampleFormat> {
+        if OUTPUT_FORMATS.contains(&default) {
+            return Some(default);
         }
+        OUTPUT_FORMATS
+            .into_iter()
+            .find(|format| offered.contains(format))
     }
 
     impl CpalAudioOutput {
@@ -268,30 +601,84 @@ mod cpal {
                 }
             };
 
+            let offered: Vec<cpal::SampleFormat> = device
+                .supported_output_configs()
+                .map(|configs| configs.map(|config| config.sample_format()).collect())
+                .unwrap_or_default();
+            let Some(format) = pick_format(config.sample_format(), &offered) else {
+                error!(
+                    "no supported sample format, the device offers {:?}",
+                    offered
+                );
+                return Err(AudioOutputError::OpenStreamError);
+            };
+            if format != config.sample_format() {
+                info!(
+                    "device default format {} unsupported, using {}",
+                    config.sample_format(),
+                    format
+                );
+            }
+
             // Select proper playback routine based on sample format.
-            match config.sample_format() {
+            match format {
                 cpal::SampleFormat::F32 => {
                     CpalAudioOutputImpl::<f32>::try_open(spec, duration, &device)
                 }
+                cpal::SampleFormat::F64 => {
+                    CpalAudioOutputImpl::<f64>::try_open(spec, duration, &device)
+                }
+                cpal::SampleFormat::I8 => {
+                    CpalAudioOutputImpl::<i8>::try_open(spec, duration, &device)
+                }
                 cpal::SampleFormat::I16 => {
                     CpalAudioOutputImpl::<i16>::try_open(spec, duration, &device)
                 }
+                cpal::SampleFormat::I32 => {
+                    CpalAudioOutputImpl::<i32>::try_open(spec, duration, &device)
+                }
+                cpal::SampleFormat::U8 => {
+                    CpalAudioOutputImpl::<u8>::try_open(spec, duration, &device)
+                }
                 cpal::SampleFormat::U16 => {
                     CpalAudioOutputImpl::<u16>::try_open(spec, duration, &device)
                 }
-                _ => panic!("Unsupported sample format"),
+                cpal::SampleFormat::U32 => {
+                    CpalAudioOutputImpl::<u32>::try_open(spec, duration, &device)
+                }
+                // Not picked, there's no conversion to it
+                _ => Err(AudioOutputError::OpenStreamError),
             }
         }
     }
 
+    // Fade requests from the decoding thread to the stream callback
+    #[derive(Default)]
+    struct FadeControl {
+        // Ramp towards silence and stop reading the ring buffer
+        muted: AtomicBool,
+        // Set by the callback once the ramp reached silence
+        silent: AtomicBool,
+        // Start the next ramp up from silence
+        restart: AtomicBool,
+        // The thread waiting in `fade_out`, woken once it's silent
+        waiter: OnceLock<std::thread::Thread>,
+    }
+
+    // A device that read nothing for this long is taken as gone
+    const STALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
     struct CpalAudioOutputImpl<T: AudioOutputSample>
     where
         T: AudioOutputSample,
     {
+        ring_buf: SpscRb<T>,
         ring_buf_producer: rb::Producer<T>,
         sample_buf: SampleBuffer<T>,
         stream: cpal::Stream,
         resampler: Option<Resampler<T>>,
+        info: OutputInfo,
+        fade: Arc<FadeControl>,
     }
 
     impl<T: cpal::SizedSample + AudioOutputSample> CpalAudioOutputImpl<T>
@@ -314,26 +701,74 @@ mod cpal {
                 }
             } else {
                 // Use the default config for Windows.
-                device
-                    .default_output_config()
-                    .expect("Failed to get the default output config.")
-                    .config()
+                match device.default_output_config() {
+                    Ok(config) => config.config(),
+                    Err(err) => {
+                        error!("failed to get default audio output device config: {}", err);
+                        return Err(AudioOutputError::OpenStreamError);
+                    }
+                }
             };
 
-            // Create a ring buffer with a capacity for up-to 200ms of audio.
-            // let ring_len = ((2 * config.sample_rate.0 as usize) / 1000) * num_channels;
-            let ring_len: usize = 8192; // Increased to reduce buffer underruns
+            // Longer than the last stream's buffer if that one kept running dry
+            let ring_len = OUTPUT_BUFFER.open_stream();
 
             let ring_buf = SpscRb::new(ring_len);
             let (ring_buf_producer, ring_buf_consumer) = (ring_buf.producer(), ring_buf.consumer());
 
+            let fade = Arc::new(FadeControl::default());
+            let callback_fade = fade.clone();
+            let frame_len = config.channels as usize;
+            let fade_step =
+                1.0 / (super::FADE_DURATION.as_secs_f32() * config.sample_rate.0 as f32);
+            let mut gain = 1.0f32;
+
             let stream_result = device.build_output_stream(
                 &config,
                 move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                    if callback_fade.restart.swap(false, Ordering::Relaxed) {
+                        gain = 0.0;
+                    }
+                    let muted = callback_fade.muted.load(Ordering::Relaxed);
+
+                    // Once faded out, keep the queued audio for when playback resumes
+                    if muted && gain <= 0.0 {
+                        if !callback_fade.silent.swap(true, Ordering::Relaxed) {
+                            if let Some(waiter) = callback_fade.waiter.get() {
+                                waiter.unpark();
+                            }
+                        }
+                        data.iter_mut().for_each(|s| *s = T::MID);
+                        return;
+                    }
+
                     // let volume = 1.0f32;
                     // Write out as many samples as possible from the ring buffer to the audio
                     // output.
                     let written = ring_buf_consumer.read(data).unwrap_or(0);
+                    if written < data.len() {
+                        OUTPUT_BUFFER.underrun();
+                    }
+
+                    // Ramp the gain a step per frame while fading
+                    let target = if muted { 0.0 } else { 1.0 };
+                    if gain != target {
+                        for frame in data[..written].chunks_mut(frame_len) {
+                            gain = if muted {
+                                (gain - fade_step).max(0.0)
+                            } else {
+                                (gain + fade_step).min(1.0)
+                            };
+                            frame.iter_mut().for_each(|s| *s = s.mul(gain));
+                        }
+                    }
+
+                    // Measure what the device actually plays for the level meters
+                    let mut levels = BlockLevels::default();
+                    for (i, sample) in data[..written].iter().enumerate() {
+                        levels.add(i % frame_len, frame_len, cpal::Sample::to_sample(*sample));
+                    }
+                    OUTPUT_LEVELS.publish(&levels);
 
                     // Mute any remaining samples.
                     data[written..].iter_mut().for_each(|s| *s = T::MID);
@@ -370,11 +805,21 @@ mod cpal {
                 None
             };
 
+            let info = OutputInfo {
+                device_name: device.name().unwrap_or_else(|_| "Unknown".to_string()),
+                sample_format: <T as cpal::SizedSample>::FORMAT.to_string(),
+                source_rate: spec.rate,
+                device_rate: config.sample_rate.0,
+            };
+
             Ok(Box::new(CpalAudioOutputImpl {
+                ring_buf,
                 ring_buf_producer,
                 sample_buf,
                 stream,
                 resampler,
+                info,
+                fade,
             }))
         }
     }
@@ -383,7 +828,7 @@ mod cpal {
     where
         f32: cpal::FromSample<T>,
     {
-        fn write(&mut self, decoded: AudioBufferRef<'_>, volume: f32) -> Result<()> {
+        fn write(&mut self, decoded: AudioBufferRef<'_>) -> Result<()> {
             // Do nothing if there are no audio frames.
             if decoded.frames() == 0 {
                 return Ok(());
@@ -392,7 +837,11 @@ mod cpal {
             let mut samples = if let Some(resampler) = &mut self.resampler {
                 // Resampling is required. The resampler will return interleaved samples in the
                 // correct sample format.
-                match resampler.resample(decoded) {
+                let (frames, rate) = (decoded.frames(), decoded.spec().rate);
+                let started = std::time::Instant::now();
+                let resampled = resampler.resample(decoded);
+                RESAMPLER.record(started.elapsed(), frames, rate);
+                match resampled {
                     Some(resampled) => resampled,
                     None => return Ok(()),
                 }
@@ -402,78 +851,43 @@ mod cpal {
                 self.sample_buf.samples()
             };
 
-            // Now handle audio output with volume adjustment
-            // Using a fixed-size buffer to avoid allocations in the hot path
-            // This buffer is used to batch samples with volume applied
-            const BATCH_SIZE: usize = 1024;
-            let mut volume_adjusted_samples = [T::MID; BATCH_SIZE];
-
+            // Queue everything, waiting while the ring buffer is full
+            if self.ring_buf.slots_free() < samples.len() {
+                OUTPUT_BUFFER.overflow();
+            }
             while !samples.is_empty() {
-                // Calculate how many samples to process in this batch
-                let batch_count = std::cmp::min(BATCH_SIZE, samples.len());
-
-                // Apply volume to batch
-                for i in 0..batch_count {
-                    volume_adjusted_samples[i] = samples[i].mul(volume);
-                }
-
-                // Write the volume-adjusted batch to the ring buffer
                 match self
                     .ring_buf_producer
-                    .write_blocking(&volume_adjusted_samples[..batch_count])
+                    .write_blocking_timeout(samples, STALL_TIMEOUT)
                 {
-                    Some(written) => {
-                        // If not all samples were written, try again with the remaining ones
-                        if written < batch_count {
-                            // Move remaining unwritten samples to the beginning of the batch
-                            for i in 0..(batch_count - written) {
-                                volume_adjusted_samples[i] = volume_adjusted_samples[written + i];
-                            }
-
-                            // Continuously try to write the remaining samples
-                            let mut remaining = batch_count - written;
-                            while remaining > 0 {
-                                if let Some(written) = self
-                                    .ring_buf_producer
-                                    .write_blocking(&volume_adjusted_samples[..remaining])
-                                {
-                                    if written == 0 {
-                                        // If we can't write any more, break to avoid infinite loop
-                                        break;
-                                    }
-
-                                    // Move remaining samples again
-                                    for i in 0..(remaining - written) {
-                                        volume_adjusted_samples[i] =
-                                            volume_adjusted_samples[written + i];
-                                    }
-                                    remaining -= written;
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-
-                        // Advance to the next batch of samples
-                        samples = &samples[batch_count..];
-                    }
-                    None => {
-                        // If we can't write at all, break out
-                        break;
+                    Ok(Some(written)) => samples = &samples[written..],
+                    Ok(None) => break,
+                    Err(err) => {
+                        error!("audio device stopped reading: {:?}", err);
+                        return Err(AudioOutputError::StreamClosedError);
                     }
                 }
             }
+            OUTPUT_BUFFER.set_fill(self.ring_buf.count());
+            OUTPUT_BUFFER.set_feeding(true);
 
             Ok(())
         }
 
         fn flush(&mut self) {
+            OUTPUT_BUFFER.set_feeding(false);
+
             // If there is a resampler, then it may need to be flushed
-            // depending on the number of samples it has.
-            if let Some(resampler) = &mut self.resampler {
+            // depending on the number of samples it has. A faded out stream
+            // doesn't read anymore and only gets discarded.
+            let muted = self.fade.muted.load(Ordering::Relaxed);
+            if let Some(resampler) = self.resampler.as_mut().filter(|_| !muted) {
                 let mut remaining_samples = resampler.flush().unwrap_or_default();
 
-                while let Some(written) = self.ring_buf_producer.write_blocking(remaining_samples) {
+                while let Ok(Some(written)) = self
+                    .ring_buf_producer
+                    .write_blocking_timeout(remaining_samples, STALL_TIMEOUT)
+                {
                     remaining_samples = &remaining_samples[written..];
                 }
             }
@@ -481,6 +895,72 @@ mod cpal {
             // Flush is best-effort, ignore the returned result.
             let _ = self.stream.pause();
         }
+
+        fn info(&self) -> &OutputInfo {
+            &self.info
+        }
+
+        fn fade_out(&mut self) {
+            self.fade.waiter.get_or_init(std::thread::current);
+            self.fade.silent.store(false, Ordering::Relaxed);
+            self.fade.muted.store(true, Ordering::Relaxed);
+
+            // The callback wakes us once it's silent. It may not run at all, e.g. when the
+            // device went away.
+            let deadline = std::time::Instant::now() + super::FADE_DURATION * 4;
+            while !self.fade.silent.load(Ordering::Relaxed) {
+                let now = std::time::Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                std::thread::park_timeout(deadline - now);
+            }
+        }
+
+        fn fade_in(&mut self) {
+            self.fade.restart.store(true, Ordering::Relaxed);
+            self.fade.muted.store(false, Ordering::Relaxed);
+        }
+
+        fn pause(&mut self) {
+            if let Err(err) = self.stream.pause() {
+                error!("audio output stream pause error: {}", err);
+            }
+        }
+
+        fn resume(&mut self) {
+            if let Err(err) = self.stream.play() {
+                error!("audio output stream play error: {}", err);
+            }
+        }
+
+        fn end_of_stream(&mut self) {
+            OUTPUT_BUFFER.set_feeding(false);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use cpal::SampleFormat;
+
+        #[test]
+        fn unusual_device_formats_fall_back_to_one_offered() {
+            assert_eq!(pick_format(SampleFormat::I32, &[]), Some(SampleFormat::I32));
+            assert_eq!(
+                pick_format(SampleFormat::I64, &[SampleFormat::I64, SampleFormat::U16]),
+                Some(SampleFormat::U16)
+            );
+            assert_eq!(pick_format(SampleFormat::U64, &[SampleFormat::U64]), None);
+        }
+
+        #[test]
+        fn unsigned_samples_are_scaled_around_the_middle() {
+            assert_eq!(0u16.mul(0.0), 32768);
+            assert_eq!(65535u16.mul(0.5), 49151);
+            assert_eq!(i32::MIN.mul(0.5), i32::MIN / 2);
+            assert_eq!(1.0f64.mul(0.25), 0.25);
+        }
     }
 }
 
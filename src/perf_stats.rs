@@ -0,0 +1,90 @@
+//! What the audio thread's work costs, as a share of the time the audio it handled takes
+//! to play. At 100% a stage only just keeps up, so on a slow machine this tells which
+//! one makes playback stutter. Published through atomics like the buffer stats.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Audio to average over, in microseconds
+const WINDOW: u64 = 1_000_000;
+// No window finished yet
+const NONE: u64 = u64::MAX;
+
+/// Reading and decoding packets, channel mixing and the effects.
+pub static DECODE: Load = Load::new();
+/// Converting to the device's sample rate.
+pub static RESAMPLER: Load = Load::new();
+
+pub struct Load {
+    // Summed up in the current window, in microseconds
+    busy: AtomicU64,
+    audio: AtomicU64,
+    // Of the last full window, in tenths of a percent
+    last: AtomicU64,
+}
+
+impl Load {
+    pub const fn new() -> Self {
+        Self {
+            busy: AtomicU64::new(0),
+            audio: AtomicU64::new(0),
+            last: AtomicU64::new(NONE),
+        }
+    }
+
+    /// Working for `busy` on `frames` frames at `rate`. Called from a single thread.
+    pub fn record(&self, busy: Duration, frames: usize, rate: u32) {
+        if rate == 0 {
+            return;
+        }
+        let busy = self.busy.load(Ordering::Relaxed) + busy.as_micros() as u64;
+        let audio = self.audio.load(Ordering::Relaxed) + frames as u64 * 1_000_000 / rate as u64;
+        if audio >= WINDOW {
+            self.last.store(busy * 1000 / audio, Ordering::Relaxed);
+            self.busy.store(0, Ordering::Relaxed);
+            self.audio.store(0, Ordering::Relaxed);
+        } else {
+            self.busy.store(busy, Ordering::Relaxed);
+            self.audio.store(audio, Ordering::Relaxed);
+        }
+    }
+
+    /// Forgets the last figure, e.g. when the stage isn't used anymore.
+    pub fn clear(&self) {
+        self.busy.store(0, Ordering::Relaxed);
+        self.audio.store(0, Ordering::Relaxed);
+        self.last.store(NONE, Ordering::Relaxed);
+    }
+
+    /// Percent of real time over the last second of audio, `None` before there was one.
+    pub fn percent(&self) -> Option<f32> {
+        match self.last.load(Ordering::Relaxed) {
+            NONE => None,
+            tenths => Some(tenths as f32 / 10.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_is_reported_per_second_of_audio() {
+        let load = Load::new();
+        // 10 ms of work per 100 ms of audio
+        for _ in 0..9 {
+            load.record(Duration::from_millis(10), 4410, 44100);
+        }
+        assert_eq!(load.percent(), None);
+        load.record(Duration::from_millis(10), 4410, 44100);
+        assert_eq!(load.percent(), Some(10.0));
+
+        // The figure stays until the next window is full
+        load.record(Duration::from_millis(50), 4410, 44100);
+        assert_eq!(load.percent(), Some(10.0));
+
+        load.clear();
+        assert_eq!(load.percent(), None);
+    }
+}
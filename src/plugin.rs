@@ -0,0 +1,476 @@
+//! External LADSPA effects in the chain, for power users who want an effect the player
+//! doesn't have. Hosting needs the `ladspa` feature. Without it the settings are still
+//! read and saved, the plugin just never runs.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::Effect;
+
+/// Which plugin to run and the values of its controls.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginConfig {
+    pub path: PathBuf, // The shared library
+    pub label: String, // The plugin within it, a library can hold several
+    // By port name, so the values survive a plugin update that adds controls
+    pub controls: BTreeMap<String, f32>,
+}
+
+/// Starts the configured plugin, `None` when it can't run.
+#[cfg(feature = "ladspa")]
+pub fn load(config: &PluginConfig) -> Option<Box<dyn Effect>> {
+    match ladspa::LadspaEffect::load(config) {
+        Ok(effect) => Some(Box::new(effect)),
+        Err(e) => {
+            tracing::warn!("Failed to load plugin {}: {}", config.path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "ladspa"))]
+pub fn load(config: &PluginConfig) -> Option<Box<dyn Effect>> {
+    tracing::warn!(
+        "Can't run plugin {}, built without the ladspa feature",
+        config.path.display()
+    );
+    None
+}
+
+#[cfg(feature = "ladspa")]
+pub use ladspa::{describe, PluginInfo};
+
+#[cfg(feature = "ladspa")]
+mod ladspa {
+    use std::ffi::{c_char, c_int, c_ulong, c_void, CStr};
+    use std::path::Path;
+
+    use libloading::Library;
+
+    use super::PluginConfig;
+    use crate::dsp::{Effect, EffectConfig, EffectKind};
+
+    // The plugin API as in ladspa.h, version 1.1
+    type Handle = *mut c_void;
+
+    const PORT_INPUT: c_int = 0x1;
+    const PORT_OUTPUT: c_int = 0x2;
+    const PORT_CONTROL: c_int = 0x4;
+    const PORT_AUDIO: c_int = 0x8;
+
+    const HINT_BOUNDED_BELOW: c_int = 0x1;
+    const HINT_BOUNDED_ABOVE: c_int = 0x2;
+    const HINT_TOGGLED: c_int = 0x4;
+    const HINT_SAMPLE_RATE: c_int = 0x8;
+    const HINT_LOGARITHMIC: c_int = 0x10;
+    const HINT_INTEGER: c_int = 0x20;
+    const HINT_DEFAULT_MASK: c_int = 0x3C0;
+    const HINT_DEFAULT_MINIMUM: c_int = 0x40;
+    const HINT_DEFAULT_LOW: c_int = 0x80;
+    const HINT_DEFAULT_MIDDLE: c_int = 0xC0;
+    const HINT_DEFAULT_HIGH: c_int = 0x100;
+    const HINT_DEFAULT_MAXIMUM: c_int = 0x140;
+    const HINT_DEFAULT_1: c_int = 0x240;
+    const HINT_DEFAULT_100: c_int = 0x280;
+    const HINT_DEFAULT_440: c_int = 0x2C0;
+
+    // Bounds relative to the sample rate are shown for this one in the settings
+    const NOMINAL_RATE: f32 = 44100.0;
+
+    #[repr(C)]
+    struct PortRangeHint {
+        hint: c_int,
+        lower: f32,
+        upper: f32,
+    }
+
+    // Not every field is used, but the layout has to match
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Descriptor {
+        unique_id: c_ulong,
+        label: *const c_char,
+        properties: c_int,
+        name: *const c_char,
+        maker: *const c_char,
+        copyright: *const c_char,
+        port_count: c_ulong,
+        port_descriptors: *const c_int,
+        port_names: *const *const c_char,
+        port_range_hints: *const PortRangeHint,
+        implementation_data: *mut c_void,
+        instantiate: Option<unsafe extern "C" fn(*const Descriptor, c_ulong) -> Handle>,
+        connect_port: Option<unsafe extern "C" fn(Handle, c_ulong, *mut f32)>,
+        activate: Option<unsafe extern "C" fn(Handle)>,
+        run: Option<unsafe extern "C" fn(Handle, c_ulong)>,
+        run_adding: Option<unsafe extern "C" fn(Handle, c_ulong)>,
+        set_run_adding_gain: Option<unsafe extern "C" fn(Handle, f32)>,
+        deactivate: Option<unsafe extern "C" fn(Handle)>,
+        cleanup: Option<unsafe extern "C" fn(Handle)>,
+    }
+
+    type DescriptorFn = unsafe extern "C" fn(c_ulong) -> *const Descriptor;
+
+    /// A plugin found in a library, for the settings.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct PluginInfo {
+        pub label: String,
+        pub name: String,
+        pub inputs: usize,  // Audio ports
+        pub outputs: usize, // Audio ports
+        pub controls: Vec<ControlInfo>,
+    }
+
+    /// An input control of a plugin.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ControlInfo {
+        pub name: String,
+        pub min: Option<f32>,
+        pub max: Option<f32>,
+        pub default: f32,
+        pub toggled: bool,
+        pub integer: bool,
+    }
+
+    impl ControlInfo {
+        fn new(name: String, range: &PortRangeHint) -> Self {
+            let scale = if range.hint & HINT_SAMPLE_RATE != 0 {
+                NOMINAL_RATE
+            } else {
+                1.0
+            };
+            let min = (range.hint & HINT_BOUNDED_BELOW != 0).then_some(range.lower * scale);
+            let max = (range.hint & HINT_BOUNDED_ABOVE != 0).then_some(range.upper * scale);
+            let logarithmic = range.hint & HINT_LOGARITHMIC != 0;
+            // The defaults between the bounds as the spec defines them
+            let between = |low_weight: f32| {
+                let (lower, upper) = (min.unwrap_or(0.0), max.unwrap_or(0.0));
+                if logarithmic && lower > 0.0 && upper > 0.0 {
+                    (lower.ln() * low_weight + upper.ln() * (1.0 - low_weight)).exp()
+                } else {
+                    lower * low_weight + upper * (1.0 - low_weight)
+                }
+            };
+            let default = match range.hint & HINT_DEFAULT_MASK {
+                HINT_DEFAULT_MINIMUM => min.unwrap_or(0.0),
+                HINT_DEFAULT_LOW => between(0.75),
+                HINT_DEFAULT_MIDDLE => between(0.5),
+                HINT_DEFAULT_HIGH => between(0.25),
+                HINT_DEFAULT_MAXIMUM => max.unwrap_or(0.0),
+                HINT_DEFAULT_1 => 1.0,
+                HINT_DEFAULT_100 => 100.0,
+                HINT_DEFAULT_440 => 440.0,
+                _ => 0.0, // None or DEFAULT_0
+            };
+            Self {
+                name,
+                min,
+                max,
+                default,
+                toggled: range.hint & HINT_TOGGLED != 0,
+                integer: range.hint & HINT_INTEGER != 0,
+            }
+        }
+    }
+
+    // Ports of a plugin by what they're for
+    struct Ports {
+        audio_in: Vec<usize>,
+        audio_out: Vec<usize>,
+        controls: Vec<(usize, ControlInfo)>, // Inputs only
+        count: usize,
+    }
+
+    impl Ports {
+        // Safety: `descriptor` must point to a valid descriptor
+        unsafe fn of(descriptor: &Descriptor) -> Self {
+            let count = descriptor.port_count as usize;
+            let mut ports = Self {
+                audio_in: Vec::new(),
+                audio_out: Vec::new(),
+                controls: Vec::new(),
+                count,
+            };
+            for port in 0..count {
+                let kind = *descriptor.port_descriptors.add(port);
+                if kind & PORT_AUDIO != 0 {
+                    if kind & PORT_INPUT != 0 {
+                        ports.audio_in.push(port);
+                    } else if kind & PORT_OUTPUT != 0 {
+                        ports.audio_out.push(port);
+                    }
+                } else if kind & PORT_CONTROL != 0 && kind & PORT_INPUT != 0 {
+                    let name = text(*descriptor.port_names.add(port));
+                    let range = &*descriptor.port_range_hints.add(port);
+                    ports.controls.push((port, ControlInfo::new(name, range)));
+                }
+            }
+            ports
+        }
+    }
+
+    fn text(ptr: *const c_char) -> String {
+        if ptr.is_null() {
+            return String::new();
+        }
+        unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn open(path: &Path) -> Result<(Library, Vec<*const Descriptor>), String> {
+        // Safety: loading runs the library's initializers, which is what picking a
+        // plugin asks for
+        let library = unsafe { Library::new(path) }.map_err(|e| e.to_string())?;
+        let mut descriptors = Vec::new();
+        {
+            let descriptor_fn = unsafe { library.get::<DescriptorFn>(b"ladspa_descriptor\0") }
+                .map_err(|e| e.to_string())?;
+            // The library lists its plugins until it returns null
+            for index in 0.. {
+                let descriptor = unsafe { descriptor_fn(index) };
+                if descriptor.is_null() {
+                    break;
+                }
+                descriptors.push(descriptor);
+            }
+        }
+        Ok((library, descriptors))
+    }
+
+    /// The plugins in the library at `path`.
+    pub fn describe(path: &Path) -> Result<Vec<PluginInfo>, String> {
+        let (_library, descriptors) = open(path)?;
+        Ok(descriptors
+            .into_iter()
+            .map(|descriptor| unsafe {
+                let descriptor = &*descriptor;
+                let ports = Ports::of(descriptor);
+                PluginInfo {
+                    label: text(descriptor.label),
+                    name: text(descriptor.name),
+                    inputs: ports.audio_in.len(),
+                    outputs: ports.audio_out.len(),
+                    controls: ports.controls.into_iter().map(|(_, info)| info).collect(),
+                }
+            })
+            .collect())
+    }
+
+    pub struct LadspaEffect {
+        config: PluginConfig,
+        descriptor: *const Descriptor,
+        ports: Ports,
+        // A value per port, the plugin reads its controls from here. Boxed so the
+        // addresses handed to it don't move.
+        values: Box<[f32]>,
+        // One instance for all channels, or one per channel for mono plugins
+        instances: Vec<Handle>,
+        rate: u32,
+        channels: usize,
+        output: Vec<Vec<f32>>,
+        // Dropped last, the code of the instances lives in it
+        _library: Library,
+    }
+
+    // The chain and the instances in it are only ever used by the audio thread
+    unsafe impl Send for LadspaEffect {}
+
+    impl LadspaEffect {
+        pub fn load(config: &PluginConfig) -> Result<Self, String> {
+            let (library, descriptors) = open(&config.path)?;
+            let descriptor = descriptors
+                .into_iter()
+                .find(|descriptor| text(unsafe { (**descriptor).label }) == config.label)
+                .ok_or_else(|| format!("no plugin labelled {}", config.label))?;
+            let (connect, run) = unsafe { ((*descriptor).connect_port, (*descriptor).run) };
+            if connect.is_none() || run.is_none() {
+                return Err(format!("{} can't be run", config.label));
+            }
+
+            let ports = unsafe { Ports::of(&*descriptor) };
+            let mut effect = Self {
+                config: config.clone(),
+                descriptor,
+                values: vec![0.0; ports.count].into_boxed_slice(),
+                ports,
+                instances: Vec::new(),
+                rate: 0,
+                channels: 0,
+                output: Vec::new(),
+                _library: library,
+            };
+            effect.set_controls(config);
+            Ok(effect)
+        }
+
+        fn set_controls(&mut self, config: &PluginConfig) {
+            for (port, info) in &self.ports.controls {
+                self.values[*port] = config
+                    .controls
+                    .get(&info.name)
+                    .copied()
+                    .unwrap_or(info.default);
+            }
+        }
+
+        // Sets up instances for the rate and channels, false if the plugin can't take them
+        fn prepare(&mut self, rate: u32, channels: usize) -> bool {
+            if self.rate == rate && self.channels == channels {
+                return !self.instances.is_empty();
+            }
+            self.release();
+            self.rate = rate;
+            self.channels = channels;
+
+            let (inputs, outputs) = (self.ports.audio_in.len(), self.ports.audio_out.len());
+            let count = if inputs == channels && outputs == channels {
+                1
+            } else if inputs == 1 && outputs == 1 {
+                channels
+            } else {
+                tracing::warn!(
+                    "Plugin {} has {} inputs and {} outputs, can't run it on {} channels",
+                    self.config.label,
+                    inputs,
+                    outputs,
+                    channels
+                );
+                return false;
+            };
+
+            let descriptor = unsafe { &*self.descriptor };
+            let Some(instantiate) = descriptor.instantiate else {
+                return false;
+            };
+            for _ in 0..count {
+                let instance = unsafe { instantiate(self.descriptor, rate as c_ulong) };
+                if instance.is_null() {
+                    tracing::warn!("Plugin {} failed to start", self.config.label);
+                    self.release();
+                    return false;
+                }
+                let connect = descriptor.connect_port.unwrap();
+                // Control outputs get a place to write to as well
+                for port in 0..self.ports.count {
+                    if !self.ports.audio_in.contains(&port) && !self.ports.audio_out.contains(&port)
+                    {
+                        unsafe { connect(instance, port as c_ulong, &mut self.values[port]) };
+                    }
+                }
+                if let Some(activate) = descriptor.activate {
+                    unsafe { activate(instance) };
+                }
+                self.instances.push(instance);
+            }
+            self.output = vec![Vec::new(); channels];
+            true
+        }
+
+        fn release(&mut self) {
+            let descriptor = unsafe { &*self.descriptor };
+            for instance in self.instances.drain(..) {
+                unsafe {
+                    if let Some(deactivate) = descriptor.deactivate {
+                        deactivate(instance);
+                    }
+                    if let Some(cleanup) = descriptor.cleanup {
+                        cleanup(instance);
+                    }
+                }
+            }
+        }
+    }
+
+    impl Drop for LadspaEffect {
+        fn drop(&mut self) {
+            self.release();
+        }
+    }
+
+    impl Effect for LadspaEffect {
+        fn kind(&self) -> EffectKind {
+            EffectKind::Plugin
+        }
+
+        fn accepts(&self, config: &EffectConfig) -> bool {
+            matches!(config, EffectConfig::Plugin(plugin)
+                if plugin.path == self.config.path && plugin.label == self.config.label)
+        }
+
+        fn configure(&mut self, config: &EffectConfig) {
+            if let EffectConfig::Plugin(plugin) = config {
+                self.set_controls(plugin);
+                self.config = plugin.clone();
+            }
+        }
+
+        fn process(&mut self, planes: &mut [&mut [f32]], rate: u32) {
+            if !self.prepare(rate, planes.len()) {
+                return;
+            }
+            let descriptor = unsafe { &*self.descriptor };
+            let (connect, run) = (descriptor.connect_port.unwrap(), descriptor.run.unwrap());
+            let frames = planes.first().map_or(0, |plane| plane.len());
+            for output in &mut self.output {
+                output.resize(frames, 0.0);
+            }
+
+            // The plugin writes to its own buffers, some can't work in place
+            let per_instance = self.ports.audio_in.len();
+            for (idx, instance) in self.instances.iter().enumerate() {
+                for port in 0..per_instance {
+                    let channel = idx * per_instance + port;
+                    unsafe {
+                        connect(
+                            *instance,
+                            self.ports.audio_in[port] as c_ulong,
+                            planes[channel].as_mut_ptr(),
+                        );
+                        connect(
+                            *instance,
+                            self.ports.audio_out[port] as c_ulong,
+                            self.output[channel].as_mut_ptr(),
+                        );
+                    }
+                }
+                unsafe { run(*instance, frames as c_ulong) };
+            }
+            for (plane, output) in planes.iter_mut().zip(&self.output) {
+                plane.copy_from_slice(output);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn control_defaults_follow_the_range_hints() {
+            let range = |hint, lower, upper| PortRangeHint { hint, lower, upper };
+            let bounded = HINT_BOUNDED_BELOW | HINT_BOUNDED_ABOVE;
+
+            let middle = ControlInfo::new(
+                String::new(),
+                &range(bounded | HINT_DEFAULT_MIDDLE, 0.0, 10.0),
+            );
+            assert_eq!(middle.default, 5.0);
+            let low = ControlInfo::new(
+                String::new(),
+                &range(bounded | HINT_LOGARITHMIC | HINT_DEFAULT_LOW, 1.0, 10000.0),
+            );
+            assert!((low.default - 10.0).abs() < 1e-3);
+            let rate = ControlInfo::new(
+                String::new(),
+                &range(bounded | HINT_SAMPLE_RATE | HINT_DEFAULT_MAXIMUM, 0.0, 0.5),
+            );
+            assert_eq!(rate.default, NOMINAL_RATE / 2.0);
+            let unbounded = ControlInfo::new(String::new(), &range(HINT_DEFAULT_440, 0.0, 0.0));
+            assert_eq!((unbounded.min, unbounded.default), (None, 440.0));
+        }
+    }
+}
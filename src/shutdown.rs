@@ -0,0 +1,56 @@
+//! Termination signals close the window the same way the close button does, so the
+//! state is saved and the audio stream stopped instead of the process just dying.
+
+use eframe::egui;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::time::Duration;
+
+#[cfg(unix)]
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// How often the watcher looks for a signal; the handler itself can't touch the UI
+#[cfg(unix)]
+const POLL: Duration = Duration::from_millis(100);
+
+/// Closes the window on SIGTERM, SIGINT or SIGHUP. A second signal while the app is
+/// still saving exits right away.
+#[cfg(unix)]
+pub fn install(ctx: egui::Context) {
+    unsafe {
+        for signal in [libc::SIGTERM, libc::SIGINT, libc::SIGHUP] {
+            libc::signal(
+                signal,
+                on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+    }
+
+    let spawned = std::thread::Builder::new()
+        .name("shutdown-watcher".to_string())
+        .spawn(move || loop {
+            if REQUESTED.load(Ordering::Relaxed) {
+                tracing::info!("Termination signal received, closing");
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                ctx.request_repaint();
+                return;
+            }
+            std::thread::sleep(POLL);
+        });
+    if let Err(e) = spawned {
+        tracing::error!("Failed to watch for termination signals: {}", e);
+    }
+}
+
+// Closing the window is all there is on other systems
+#[cfg(not(unix))]
+pub fn install(_ctx: egui::Context) {}
+
+#[cfg(unix)]
+extern "C" fn on_signal(_signal: libc::c_int) {
+    // Only async-signal-safe calls in here
+    if REQUESTED.swap(true, Ordering::Relaxed) {
+        unsafe { libc::_exit(1) };
+    }
+}
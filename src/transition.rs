@@ -0,0 +1,119 @@
+//! How one track hands over to the next. Albums usually want to run on without a gap,
+//! shuffled tracks fade into each other and unrelated ones get a moment of silence. The
+//! engine decodes one track at a time, so a fade takes the end of a track down and the
+//! start of the next one up back to back rather than overlapping them.
+
+use serde::{Deserialize, Serialize};
+
+// Longest fade or gap the settings allow, in seconds
+pub const MAX_TRANSITION_SECONDS: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Transition {
+    /// The next track follows right after the last sample.
+    Gapless,
+    /// The track fades out over its last seconds and the next fades in over as many.
+    Fade(f32),
+    /// Seconds of silence between the tracks.
+    Gap(f32),
+}
+
+impl Transition {
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            Transition::Gapless => "transition_gapless",
+            Transition::Fade(_) => "transition_fade",
+            Transition::Gap(_) => "transition_gap",
+        }
+    }
+
+    /// The kinds with their default lengths, for the settings to pick from.
+    pub fn all() -> [Transition; 3] {
+        [
+            Transition::Gapless,
+            Transition::Fade(2.0),
+            Transition::Gap(1.0),
+        ]
+    }
+
+    pub fn seconds_mut(&mut self) -> Option<&mut f32> {
+        match self {
+            Transition::Gapless => None,
+            Transition::Fade(seconds) | Transition::Gap(seconds) => Some(seconds),
+        }
+    }
+}
+
+/// Which transition to use, by how the tracks relate.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransitionPrefs {
+    /// Consecutive tracks of the same album.
+    pub same_album: Transition,
+    /// Tracks of different albums while shuffling.
+    pub shuffle: Transition,
+    /// Tracks of different albums played in order, e.g. a radio or mixed playlist.
+    pub other: Transition,
+}
+
+impl Default for TransitionPrefs {
+    fn default() -> Self {
+        Self {
+            same_album: Transition::Gapless,
+            shuffle: Transition::Fade(2.0),
+            other: Transition::Gap(1.0),
+        }
+    }
+}
+
+impl TransitionPrefs {
+    pub fn pick(&self, same_album: bool, shuffle: bool) -> Transition {
+        if same_album {
+            self.same_album
+        } else if shuffle {
+            self.shuffle
+        } else {
+            self.other
+        }
+    }
+}
+
+/// Gain at `position` seconds into a track of `length` seconds that fades in over its
+/// first `fade_in` and out over its last `fade_out` seconds.
+pub fn fade_gain(position: f64, length: f64, fade_in: Option<f32>, fade_out: Option<f32>) -> f32 {
+    let ramp = |elapsed: f64, seconds: f32| {
+        if seconds > 0.0 {
+            (elapsed / seconds as f64).clamp(0.0, 1.0) as f32
+        } else {
+            1.0
+        }
+    };
+    let fade_in = fade_in.map_or(1.0, |seconds| ramp(position, seconds));
+    let fade_out = fade_out
+        .filter(|_| length > 0.0)
+        .map_or(1.0, |seconds| ramp(length - position, seconds));
+    fade_in * fade_out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn albums_win_over_shuffle() {
+        let prefs = TransitionPrefs::default();
+        assert_eq!(prefs.pick(true, true), Transition::Gapless);
+        assert_eq!(prefs.pick(false, true), Transition::Fade(2.0));
+        assert_eq!(prefs.pick(false, false), Transition::Gap(1.0));
+    }
+
+    #[test]
+    fn fades_ramp_at_both_ends() {
+        assert_eq!(fade_gain(1.0, 100.0, Some(2.0), Some(2.0)), 0.5);
+        assert_eq!(fade_gain(50.0, 100.0, Some(2.0), Some(2.0)), 1.0);
+        assert_eq!(fade_gain(99.5, 100.0, None, Some(2.0)), 0.25);
+        assert_eq!(fade_gain(99.5, 100.0, None, None), 1.0);
+        // An unknown length never fades out
+        assert_eq!(fade_gain(5.0, 0.0, None, Some(2.0)), 1.0);
+    }
+}
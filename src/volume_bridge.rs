@@ -0,0 +1,334 @@
+//! Keeps the app's volume and its volume in the system mixer the same. Where the system
+//! has a volume for the app's stream, that one carries the volume and the samples go out
+//! unscaled, so there's a single volume instead of two multiplied together.
+//!
+//! Only PulseAudio has such a bridge. Elsewhere, the WASAPI session volume on Windows
+//! included, the app's slider scales the samples and the system volume stays separate.
+
+use eframe::egui;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+
+// Differences below this are the mixer rounding what the app set
+#[cfg(any(test, all(target_os = "linux", feature = "pulseaudio")))]
+const TOLERANCE: f32 = 0.005;
+
+/// What the system's end tells the app.
+#[cfg_attr(
+    not(any(test, all(target_os = "linux", feature = "pulseaudio"))),
+    allow(dead_code)
+)]
+enum Report {
+    /// The app's stream was found and has the app's volume now.
+    Carrying,
+    /// The app was set to this volume in the mixer.
+    Changed(f32),
+}
+
+/// The app's end of the bridge, the system's end runs on its own thread.
+pub struct VolumeBridge {
+    to_system: Sender<f32>,
+    from_system: Receiver<Report>,
+    carrying: bool,
+}
+
+impl VolumeBridge {
+    /// Sets the app's volume in the system mixer.
+    pub fn set(&self, volume: f32) {
+        let _ = self.to_system.send(volume);
+    }
+
+    /// The volume the app was set to in the system mixer since the last call, if any.
+    pub fn changed(&mut self) -> Option<f32> {
+        let mut volume = None;
+        loop {
+            match self.from_system.try_recv() {
+                Ok(Report::Carrying) => self.carrying = true,
+                Ok(Report::Changed(v)) => volume = Some(v),
+                Err(TryRecvError::Empty) => break,
+                // The connection failed or was lost
+                Err(TryRecvError::Disconnected) => {
+                    self.carrying = false;
+                    break;
+                }
+            }
+        }
+        volume
+    }
+
+    /// Whether the mixer holds the app's volume. Until the app's stream turns up in it, and
+    /// for good once the connection is gone, the app has to scale the samples itself.
+    pub fn carries_volume(&self) -> bool {
+        self.carrying
+    }
+}
+
+/// Connects to the system mixer, `None` where there's no volume per app to follow.
+/// The UI is repainted for changes made in the mixer.
+#[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+pub fn connect(ctx: egui::Context) -> Option<VolumeBridge> {
+    pulse::spawn(ctx)
+}
+
+#[cfg(not(all(target_os = "linux", feature = "pulseaudio")))]
+pub fn connect(_ctx: egui::Context) -> Option<VolumeBridge> {
+    None
+}
+
+/// Tells the mixer reporting a change made in it from it reporting the app's own.
+#[cfg(any(test, all(target_os = "linux", feature = "pulseaudio")))]
+#[derive(Debug, Default)]
+struct Echo {
+    known: Option<f32>,
+}
+
+#[cfg(any(test, all(target_os = "linux", feature = "pulseaudio")))]
+impl Echo {
+    fn sent(&mut self, volume: f32) {
+        self.known = Some(volume);
+    }
+
+    // The reported volume, unless it's the one the mixer already had
+    fn external(&mut self, reported: f32) -> Option<f32> {
+        if self
+            .known
+            .is_some_and(|known| (known - reported).abs() < TOLERANCE)
+        {
+            return None;
+        }
+        self.known = Some(reported);
+        Some(reported)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "pulseaudio"))]
+mod pulse {
+    use super::{Echo, Report, VolumeBridge};
+    use eframe::egui;
+    use libpulse_binding as pulse;
+    use pulse::callbacks::ListResult;
+    use pulse::context::subscribe::{Facility, InterestMaskSet};
+    use pulse::context::{Context, FlagSet, State};
+    use pulse::mainloop::standard::{IterateResult, Mainloop};
+    use pulse::proplist::properties;
+    use pulse::volume::{ChannelVolumes, Volume, VolumeLinear};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+    use std::time::Duration;
+
+    // How often the thread looks at the app's changes and the mixer's events
+    const POLL: Duration = Duration::from_millis(50);
+
+    // What the mainloop callbacks and the thread share
+    #[derive(Default)]
+    struct Shared {
+        echo: Echo,
+        // The app's volume, given to each new stream of the app
+        volume: Option<f32>,
+        // The app's sink input and its channel count
+        stream: Option<(u32, u8)>,
+        // Sink inputs came, went or changed
+        refresh: bool,
+        // A new stream is there to be set to the app's volume
+        push: bool,
+        // Changed in the mixer, for the app
+        changed: Option<f32>,
+    }
+
+    pub fn spawn(ctx: egui::Context) -> Option<VolumeBridge> {
+        let (to_system, from_app) = channel();
+        let (to_app, from_system) = channel();
+        let spawned = std::thread::Builder::new()
+            .name("volume-bridge".to_string())
+            .spawn(move || {
+                if let Err(e) = run(from_app, to_app, ctx) {
+                    tracing::warn!("Not following the system volume: {}", e);
+                }
+            });
+        match spawned {
+            Ok(_) => Some(VolumeBridge {
+                to_system,
+                from_system,
+                carrying: false,
+            }),
+            Err(e) => {
+                tracing::error!("Failed to start the volume bridge: {}", e);
+                None
+            }
+        }
+    }
+
+    fn run(
+        from_app: Receiver<f32>,
+        to_app: Sender<Report>,
+        ctx: egui::Context,
+    ) -> Result<(), String> {
+        let mut mainloop = Mainloop::new().ok_or("No PulseAudio mainloop")?;
+        let mut context =
+            Context::new(&mainloop, "Bird Player volume").ok_or("No PulseAudio context")?;
+        context
+            .connect(None, FlagSet::NOFLAGS, None)
+            .map_err(|e| e.to_string())?;
+        loop {
+            iterate(&mut mainloop, true)?;
+            match context.get_state() {
+                State::Ready => break,
+                State::Failed | State::Terminated => return Err("Connection failed".to_string()),
+                _ => {}
+            }
+        }
+
+        let shared = Rc::new(RefCell::new(Shared {
+            refresh: true,
+            ..Default::default()
+        }));
+        {
+            let shared = shared.clone();
+            context.set_subscribe_callback(Some(Box::new(move |facility, _, _| {
+                if facility == Some(Facility::SinkInput) {
+                    shared.borrow_mut().refresh = true;
+                }
+            })));
+        }
+        context.subscribe(InterestMaskSet::SINK_INPUT, |_| {});
+        tracing::info!("Following the app's volume in PulseAudio");
+
+        let pid = std::process::id().to_string();
+        let mut carrying = false;
+        loop {
+            // Only the latest of the app's changes matters
+            let mut volume = None;
+            loop {
+                match from_app.try_recv() {
+                    Ok(v) => volume = Some(v),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            }
+            if let Some(volume) = volume {
+                let mut shared = shared.borrow_mut();
+                shared.volume = Some(volume);
+                shared.push = true;
+            }
+
+            let (push, refresh) = {
+                let mut shared = shared.borrow_mut();
+                let flags = (shared.push, shared.refresh);
+                shared.push = false;
+                shared.refresh = false;
+                flags
+            };
+            if push {
+                let mut shared = shared.borrow_mut();
+                if let (Some(volume), Some((index, channels))) = (shared.volume, shared.stream) {
+                    let mut volumes = ChannelVolumes::default();
+                    volumes.set(channels, Volume::from(VolumeLinear(volume as f64)));
+                    context
+                        .introspect()
+                        .set_sink_input_volume(index, &volumes, None);
+                    shared.echo.sent(volume);
+                    if !carrying {
+                        carrying = true;
+                        if to_app.send(Report::Carrying).is_err() {
+                            return Ok(());
+                        }
+                        ctx.request_repaint();
+                    }
+                }
+            }
+            if refresh {
+                find_stream(&context, &shared, pid.clone());
+            }
+
+            if let Some(volume) = shared.borrow_mut().changed.take() {
+                if to_app.send(Report::Changed(volume)).is_err() {
+                    return Ok(());
+                }
+                ctx.request_repaint();
+            }
+
+            iterate(&mut mainloop, false)?;
+            std::thread::sleep(POLL);
+        }
+    }
+
+    fn iterate(mainloop: &mut Mainloop, block: bool) -> Result<(), String> {
+        match mainloop.iterate(block) {
+            IterateResult::Success(_) => Ok(()),
+            IterateResult::Quit(_) => Err("Mainloop quit".to_string()),
+            IterateResult::Err(e) => Err(e.to_string()),
+        }
+    }
+
+    // Looks up the app's playback stream, the simple API used for output doesn't tell it
+    fn find_stream(context: &Context, shared: &Rc<RefCell<Shared>>, pid: String) {
+        let shared = shared.clone();
+        context
+            .introspect()
+            .get_sink_input_info_list(move |result| {
+                let ListResult::Item(info) = result else {
+                    return;
+                };
+                let process = info.proplist.get_str(properties::APPLICATION_PROCESS_ID);
+                if process.as_deref() != Some(pid.as_str()) {
+                    return;
+                }
+
+                let mut shared = shared.borrow_mut();
+                let is_new = shared.stream.map(|(index, _)| index) != Some(info.index);
+                shared.stream = Some((info.index, info.volume.len()));
+                if is_new {
+                    // A new stream takes the app's volume
+                    shared.push = shared.volume.is_some();
+                } else {
+                    let reported = VolumeLinear::from(info.volume.avg()).0 as f32;
+                    if let Some(volume) = shared.echo.external(reported) {
+                        shared.changed = Some(volume);
+                    }
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changes_made_in_the_mixer_are_reported() {
+        let mut echo = Echo::default();
+        echo.sent(0.5);
+        // The app's own change coming back, rounded by the mixer
+        assert_eq!(echo.external(0.502), None);
+
+        assert_eq!(echo.external(0.3), Some(0.3));
+        assert_eq!(echo.external(0.3), None);
+
+        echo.sent(0.8);
+        assert_eq!(echo.external(0.8), None);
+    }
+
+    #[test]
+    fn the_app_keeps_the_volume_until_the_mixer_has_its_stream() {
+        let (to_system, _from_app) = std::sync::mpsc::channel();
+        let (to_app, from_system) = std::sync::mpsc::channel();
+        let mut bridge = VolumeBridge {
+            to_system,
+            from_system,
+            carrying: false,
+        };
+
+        to_app.send(Report::Changed(0.4)).unwrap();
+        assert_eq!(bridge.changed(), Some(0.4));
+        assert!(!bridge.carries_volume());
+
+        to_app.send(Report::Carrying).unwrap();
+        assert_eq!(bridge.changed(), None);
+        assert!(bridge.carries_volume());
+
+        // The connection went away
+        drop(to_app);
+        bridge.changed();
+        assert!(!bridge.carries_volume());
+    }
+}